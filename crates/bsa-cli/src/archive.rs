@@ -0,0 +1,249 @@
+//! fallout 4/starfield `.ba2`, oblivion/skyrim `.bsa` and morrowind `.bsa` behind a single interface, the format is guessed from the header
+
+use {
+    anyhow::{Context, Result},
+    ba2::{fo4, tes3, tes4, BStr, ByteSlice, Reader},
+    hoola_paths::MaybeWindowsPath,
+    serde::Serialize,
+    std::{io::Write, path::Path},
+    tap::prelude::*,
+};
+
+pub enum BethesdaArchive {
+    Fallout4(fo4::Archive<'static>, fo4::ArchiveOptions),
+    Tes4(tes4::Archive<'static>, tes4::ArchiveOptions),
+    Tes3(tes3::Archive<'static>),
+}
+
+#[derive(Debug, Clone)]
+pub enum EntryKey<'a> {
+    Fallout4(fo4::ArchiveKey<'a>),
+    Tes4(tes4::ArchiveKey<'a>, tes4::DirectoryKey<'a>),
+    Tes3(tes3::ArchiveKey<'a>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry<'a> {
+    pub path: MaybeWindowsPath,
+    pub key: EntryKey<'a>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TextureInfo {
+    pub width: u16,
+    pub height: u16,
+    pub mip_count: u8,
+    /// DXGI_FORMAT
+    pub format: u8,
+    /// 1 for cube maps
+    pub flags: u8,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryInfo {
+    pub path: MaybeWindowsPath,
+    /// decompressed
+    pub size: u64,
+    /// only for compressed entries
+    pub compressed_size: Option<u64>,
+    /// only fallout 4 archives split files into chunks
+    pub chunks: Option<usize>,
+    /// only for fallout 4 texture archives
+    pub texture: Option<TextureInfo>,
+}
+
+pub(crate) fn lossy_path(name: &BStr) -> MaybeWindowsPath {
+    String::from_utf8_lossy(name.as_bytes())
+        .to_string()
+        .pipe(MaybeWindowsPath)
+}
+
+pub(crate) fn tes4_path(archive_key: &tes4::ArchiveKey<'_>, directory_key: &tes4::DirectoryKey<'_>) -> MaybeWindowsPath {
+    MaybeWindowsPath(format!("{}\\{}", lossy_path(archive_key.name()), lossy_path(directory_key.name())))
+}
+
+/// header synthesized from the archived texture info, followed by the decompressed chunks
+fn write_dds(file: &fo4::File<'_>, header: &fo4::DX10Header, options: &fo4::ArchiveOptions, output: &mut impl Write) -> Result<()> {
+    crate::dds::header(&TextureInfo {
+        width: header.width,
+        height: header.height,
+        mip_count: header.mip_count,
+        format: header.format,
+        flags: header.flags,
+    })
+    .pipe(|header| output.write_all(&header).context("writing dds header"))
+    .and_then(|_| {
+        file.iter().enumerate().try_for_each(|(idx, chunk)| {
+            match chunk.is_compressed() {
+                true => chunk
+                    .decompress(
+                        &fo4::ChunkCompressionOptions::builder()
+                            .compression_format(options.compression_format())
+                            .build(),
+                    )
+                    .context("decompressing")
+                    .and_then(|chunk| output.write_all(chunk.as_bytes()).context("writing")),
+                false => output.write_all(chunk.as_bytes()).context("writing"),
+            }
+            .with_context(|| format!("chunk {idx}"))
+        })
+    })
+    .context("writing texture as dds")
+}
+
+impl BethesdaArchive {
+    pub fn open(path: &Path) -> Result<Self> {
+        std::fs::File::open(path)
+            .context("opening file")
+            .and_then(|mut file| ba2::guess_format(&mut file).context("unrecognized archive format"))
+            .and_then(|format| {
+                match format {
+                    ba2::FileFormat::FO4 => fo4::Archive::read(path).map(|(archive, options)| Self::Fallout4(archive, options)),
+                    ba2::FileFormat::TES4 => tes4::Archive::read(path).map(|(archive, options)| Self::Tes4(archive, options)),
+                    ba2::FileFormat::TES3 => tes3::Archive::read(path).map(Self::Tes3),
+                }
+                .with_context(|| format!("reading {format:?} archive"))
+            })
+            .with_context(|| format!("opening archive at [{}]", path.display()))
+    }
+
+    pub fn entries(&self) -> Vec<Entry<'static>> {
+        match self {
+            Self::Fallout4(archive, _) => archive
+                .iter()
+                .map(|(key, _)| Entry {
+                    path: lossy_path(key.name()),
+                    key: EntryKey::Fallout4(key.clone()),
+                })
+                .collect(),
+            Self::Tes4(archive, _) => archive
+                .iter()
+                .flat_map(|(archive_key, directory)| {
+                    directory.iter().map(move |(directory_key, _)| Entry {
+                        path: tes4_path(archive_key, directory_key),
+                        key: EntryKey::Tes4(archive_key.clone(), directory_key.clone()),
+                    })
+                })
+                .collect(),
+            Self::Tes3(archive) => archive
+                .iter()
+                .map(|(key, _)| Entry {
+                    path: lossy_path(key.name()),
+                    key: EntryKey::Tes3(key.clone()),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn find(&self, path: &MaybeWindowsPath) -> Result<Entry<'static>> {
+        self.entries().pipe(|entries| {
+            entries
+                .iter()
+                .find(|entry| entry.path.eq(path))
+                .cloned()
+                .with_context(|| format!("no [{path}] in {entries:#?}"))
+        })
+    }
+
+    pub fn info(&self, entry: &Entry<'_>) -> Result<EntryInfo> {
+        match (self, &entry.key) {
+            (Self::Fallout4(archive, _), EntryKey::Fallout4(key)) => archive.get(key).context("opening using key").map(|file| {
+                let (size, compressed_size) = file
+                    .iter()
+                    .fold((0, None), |(size, compressed_size), chunk| match chunk.decompressed_len() {
+                        Some(decompressed) => (size + decompressed as u64, Some(compressed_size.unwrap_or(0) + chunk.len() as u64)),
+                        None => (size + chunk.len() as u64, compressed_size),
+                    });
+                EntryInfo {
+                    path: entry.path.clone(),
+                    size,
+                    compressed_size,
+                    chunks: Some(file.len()),
+                    texture: match &file.header {
+                        fo4::FileHeader::DX10(header) => Some(TextureInfo {
+                            width: header.width,
+                            height: header.height,
+                            mip_count: header.mip_count,
+                            format: header.format,
+                            flags: header.flags,
+                        }),
+                        _ => None,
+                    },
+                }
+            }),
+            (Self::Tes4(archive, _), EntryKey::Tes4(archive_key, directory_key)) => archive
+                .get(archive_key)
+                .context("no such directory")
+                .and_then(|directory| {
+                    directory
+                        .get(directory_key)
+                        .context("no such file in directory")
+                })
+                .map(|file| EntryInfo {
+                    path: entry.path.clone(),
+                    size: file.decompressed_len().unwrap_or(file.len()) as u64,
+                    compressed_size: file.is_compressed().then_some(file.len() as u64),
+                    chunks: None,
+                    texture: None,
+                }),
+            (Self::Tes3(archive), EntryKey::Tes3(key)) => archive
+                .get(key)
+                .context("opening using key")
+                .map(|file| EntryInfo {
+                    path: entry.path.clone(),
+                    size: file.len() as u64,
+                    compressed_size: None,
+                    chunks: None,
+                    texture: None,
+                }),
+            (_, key) => anyhow::bail!("key {key:?} belongs to another archive format"),
+        }
+        .with_context(|| format!("reading info of [{}]", entry.path))
+    }
+
+    /// writes the decompressed contents of the entry
+    pub fn extract(&self, key: &EntryKey<'_>, output: &mut impl Write) -> Result<()> {
+        match (self, key) {
+            (Self::Fallout4(archive, options), EntryKey::Fallout4(key)) => archive
+                .get(key)
+                .context("opening using key")
+                .and_then(|file| match &file.header {
+                    fo4::FileHeader::DX10(header) => write_dds(file, header, options, output),
+                    _ => file
+                        .write(
+                            output,
+                            &fo4::FileWriteOptions::builder()
+                                .compression_format(options.compression_format())
+                                .build(),
+                        )
+                        .context("writing fallout 4 file"),
+                }),
+            (Self::Tes4(archive, options), EntryKey::Tes4(archive_key, directory_key)) => archive
+                .get(archive_key)
+                .context("no such directory")
+                .and_then(|directory| {
+                    directory
+                        .get(directory_key)
+                        .context("no such file in directory")
+                })
+                .and_then(|file| {
+                    file.write(
+                        output,
+                        &tes4::FileCompressionOptions::builder()
+                            .version(options.version())
+                            .build(),
+                    )
+                    .context("writing tes4 file")
+                }),
+            (Self::Tes3(archive), EntryKey::Tes3(key)) => archive
+                .get(key)
+                .context("opening using key")
+                .and_then(|file| {
+                    output
+                        .write_all(file.as_bytes())
+                        .context("writing tes3 file")
+                }),
+            _ => anyhow::bail!("key {key:?} belongs to another archive format"),
+        }
+    }
+}
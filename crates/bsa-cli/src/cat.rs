@@ -0,0 +1,70 @@
+use {
+    crate::archive::BethesdaArchive,
+    anyhow::{Context, Result},
+    hoola_paths::MaybeWindowsPath,
+    std::{io::Write, path::Path},
+};
+
+/// passes through only the bytes in `[skip, skip + limit)`, the rest is dropped on the floor
+struct RangeWriter<W> {
+    inner: W,
+    skip: u64,
+    limit: Option<u64>,
+}
+
+impl<W: Write> Write for RangeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let skipped = (self.skip.min(buf.len() as u64)) as usize;
+        self.skip -= skipped as u64;
+        let rest = &buf[skipped..];
+        let taken = self
+            .limit
+            .map(|limit| limit.min(rest.len() as u64) as usize)
+            .unwrap_or(rest.len());
+        self.inner.write_all(&rest[..taken])?;
+        if let Some(limit) = self.limit.as_mut() {
+            *limit -= taken as u64;
+        }
+        // everything counts as written, the extraction must not fail on the dropped bytes
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub fn cat(archive_path: &Path, file_path: &MaybeWindowsPath, offset: u64, length: Option<u64>) -> Result<()> {
+    BethesdaArchive::open(archive_path).and_then(|archive| {
+        archive.find(file_path).and_then(|entry| {
+            let mut output = RangeWriter {
+                inner: std::io::BufWriter::new(std::io::stdout().lock()),
+                skip: offset,
+                limit: length,
+            };
+            archive
+                .extract(&entry.key, &mut output)
+                .and_then(|_| output.flush().context("flushing stdout"))
+                .with_context(|| format!("writing [{}] to stdout", entry.path))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_writer() -> Result<()> {
+        let mut output = RangeWriter {
+            inner: Vec::new(),
+            skip: 3,
+            limit: Some(4),
+        };
+        output.write_all(b"ab")?;
+        output.write_all(b"cdef")?;
+        output.write_all(b"ghij")?;
+        assert_eq!(output.inner, b"defg");
+        Ok(())
+    }
+}
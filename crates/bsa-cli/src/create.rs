@@ -0,0 +1,265 @@
+use {
+    super::create_file_all,
+    anyhow::{Context, Result},
+    ba2::{
+        fo4::{Archive, ArchiveKey, ArchiveOptions, CompressionFormat, CompressionLevel, File, FileReadOptions, Format, Version},
+        BString,
+        CompressionResult,
+        Copied,
+        ReaderWithOptions,
+    },
+    clap::ValueEnum,
+    std::path::{Path, PathBuf},
+    tap::prelude::*,
+};
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum ArchiveFormat {
+    /// anything but textures
+    #[default]
+    General,
+    /// textures only, every file has to be a `.dds`
+    Dx10,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum Compression {
+    None,
+    /// what fallout 4 uses
+    #[default]
+    Zip,
+    /// zip with the settings of the xbox version of fallout 4
+    ZipXbox,
+    /// starfield only
+    Lz4,
+}
+
+impl Compression {
+    pub(crate) fn format(self) -> CompressionFormat {
+        match self {
+            Compression::None | Compression::Zip | Compression::ZipXbox => CompressionFormat::Zip,
+            Compression::Lz4 => CompressionFormat::LZ4,
+        }
+    }
+
+    pub(crate) fn level(self) -> CompressionLevel {
+        match self {
+            Compression::None | Compression::Zip => CompressionLevel::FO4,
+            Compression::ZipXbox => CompressionLevel::FO4Xbox,
+            Compression::Lz4 => CompressionLevel::SF,
+        }
+    }
+
+    fn result(self) -> CompressionResult {
+        match self {
+            Compression::None => CompressionResult::Decompressed,
+            Compression::Zip | Compression::ZipXbox | Compression::Lz4 => CompressionResult::Compressed,
+        }
+    }
+}
+
+pub(crate) fn parse_version(version: u32) -> Result<Version> {
+    match version {
+        1 => Ok(Version::v1),
+        2 => Ok(Version::v2),
+        3 => Ok(Version::v3),
+        7 => Ok(Version::v7),
+        8 => Ok(Version::v8),
+        other => anyhow::bail!("unsupported archive version: {other}"),
+    }
+}
+
+/// archives use windows separators
+pub(crate) fn archive_key(relative_path: &Path) -> ArchiveKey<'static> {
+    relative_path
+        .to_string_lossy()
+        .replace('/', "\\")
+        .into_bytes()
+        .conv::<BString>()
+        .conv::<ArchiveKey>()
+}
+
+pub(crate) fn read_loose_file(path: &Path, format: ArchiveFormat, compression: Compression) -> Result<File<'static>> {
+    let is_dds = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("dds"));
+    match (format, is_dds) {
+        (ArchiveFormat::Dx10, false) => anyhow::bail!("texture archives can only hold .dds files"),
+        (ArchiveFormat::Dx10, true) => Format::DX10,
+        (ArchiveFormat::General, _) => Format::GNRL,
+    }
+    .pipe(|format| {
+        std::fs::read(path)
+            .context("reading file")
+            .and_then(|bytes| {
+                File::read(
+                    Copied(&bytes),
+                    &FileReadOptions::builder()
+                        .format(format)
+                        .compression_format(compression.format())
+                        .compression_level(compression.level())
+                        .compression_result(compression.result())
+                        .build(),
+                )
+                .context("building archive file")
+            })
+    })
+    .with_context(|| format!("reading [{}]", path.display()))
+}
+
+pub(crate) fn archive_options(format: ArchiveFormat, compression: Compression, version: Version) -> ArchiveOptions {
+    ArchiveOptions::builder()
+        .format(match format {
+            ArchiveFormat::General => Format::GNRL,
+            ArchiveFormat::Dx10 => Format::DX10,
+        })
+        .compression_format(compression.format())
+        .version(version)
+        .strings(true)
+        .build()
+}
+
+pub(crate) fn write_archive(archive: &Archive<'_>, options: &ArchiveOptions, archive_path: &Path) -> Result<()> {
+    create_file_all(archive_path)
+        .map(std::io::BufWriter::new)
+        .and_then(|mut output| {
+            archive
+                .write(&mut output, options)
+                .context("writing archive")
+        })
+        .with_context(|| format!("writing archive to [{}]", archive_path.display()))
+}
+
+/// (path relative to `source`, full path) of every file under `source`, sorted by name
+pub(crate) fn loose_files(source: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    walkdir::WalkDir::new(source)
+        .sort_by_file_name()
+        .into_iter()
+        .filter(|entry| !entry.as_ref().is_ok_and(|entry| entry.file_type().is_dir()))
+        .map(|entry| {
+            entry.context("walking source directory").and_then(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(source)
+                    .context("file outside of source directory")
+                    .map(|relative| (relative.to_owned(), entry.path().to_owned()))
+            })
+        })
+        .collect()
+}
+
+/// packs every file under `source`, paths inside of the archive are relative to it
+pub fn create_archive(source: &Path, archive_path: &Path, format: ArchiveFormat, compression: Compression, version: u32) -> Result<()> {
+    parse_version(version).and_then(|version| {
+        loose_files(source)
+            .and_then(|files| {
+                files
+                    .into_iter()
+                    .map(|(relative, path)| read_loose_file(&path, format, compression).map(|file| (archive_key(&relative), file)))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .and_then(|files| {
+                let count = files.len();
+                files
+                    .into_iter()
+                    .fold(Archive::new(), |archive, (key, file)| {
+                        archive.tap_mut(|archive| {
+                            archive.insert(key, file);
+                        })
+                    })
+                    .pipe(|archive| write_archive(&archive, &archive_options(format, compression, version), archive_path))
+                    .tap_ok(|_| println!("packed {count} files into [{}]", archive_path.display()))
+            })
+            .with_context(|| format!("creating archive [{}] from [{}]", archive_path.display(), source.display()))
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::archive::{BethesdaArchive, TextureInfo},
+    };
+
+    /// (path within the archive, contents)
+    pub(crate) fn write_loose(source: &Path, files: &[(&str, Vec<u8>)]) -> Result<()> {
+        files.iter().try_for_each(|(path, contents)| {
+            source
+                .join(path)
+                .pipe(|path| create_file_all(&path).and_then(|_| std::fs::write(&path, contents).context("writing loose file")))
+        })
+    }
+
+    /// every entry comes back with the contents it was packed with, `compressed` tells whether the entries were compressed
+    pub(crate) fn assert_round_trip(archive_path: &Path, files: &[(&str, Vec<u8>)], compressed: bool) -> Result<()> {
+        let archive = BethesdaArchive::open(archive_path)?;
+        assert_eq!(archive.entries().len(), files.len());
+        files.iter().try_for_each(|(path, contents)| {
+            let entry = archive.find(&hoola_paths::MaybeWindowsPath::new(path.replace('/', "\\")))?;
+            assert_eq!(archive.info(&entry)?.compressed_size.is_some(), compressed, "{path}");
+            let mut extracted = Vec::new();
+            archive.extract(&entry.key, &mut extracted)?;
+            assert_eq!(&extracted, contents, "{path}");
+            Ok(())
+        })
+    }
+
+    pub(crate) fn general_files() -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("meshes/armor/a.nif", b"nif ".repeat(64)),
+            ("scripts/b.pex", (0..=255).collect()),
+            ("c.txt", b"plain text".to_vec()),
+        ]
+    }
+
+    #[test]
+    fn test_general_archives_round_trip_with_every_compression() -> Result<()> {
+        [
+            (Compression::None, 1, false),
+            (Compression::Zip, 1, true),
+            (Compression::ZipXbox, 1, true),
+            (Compression::Zip, 8, true),
+            (Compression::Lz4, 3, true),
+        ]
+        .into_iter()
+        .try_for_each(|(compression, version, compressed)| {
+            let directory = tempfile::tempdir()?;
+            let source = directory.path().join("source");
+            let archive_path = directory.path().join("out.ba2");
+            let files = general_files();
+            write_loose(&source, &files)?;
+            create_archive(&source, &archive_path, ArchiveFormat::General, compression, version)?;
+            assert_round_trip(&archive_path, &files, compressed).with_context(|| format!("{compression:?}, version {version}"))
+        })
+    }
+
+    #[test]
+    fn test_texture_archives_round_trip() -> Result<()> {
+        // BC1, a single 4x4 mip is one 8 byte block
+        let texture = crate::dds::header(&TextureInfo {
+            width: 4,
+            height: 4,
+            mip_count: 1,
+            format: 71,
+            flags: 0,
+        })
+        .tap_mut(|texture| texture.extend(0..8));
+        let directory = tempfile::tempdir()?;
+        let source = directory.path().join("source");
+        let archive_path = directory.path().join("textures.ba2");
+        let files = vec![("textures/a.dds", texture)];
+        write_loose(&source, &files)?;
+        create_archive(&source, &archive_path, ArchiveFormat::Dx10, Compression::Zip, 1)?;
+        assert_round_trip(&archive_path, &files, true)
+    }
+
+    #[test]
+    fn test_bad_inputs_are_rejected() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let source = directory.path().join("source");
+        write_loose(&source, &general_files())?;
+        assert!(create_archive(&source, &directory.path().join("textures.ba2"), ArchiveFormat::Dx10, Compression::Zip, 1).is_err());
+        assert!(create_archive(&source, &directory.path().join("general.ba2"), ArchiveFormat::General, Compression::Zip, 4).is_err());
+        Ok(())
+    }
+}
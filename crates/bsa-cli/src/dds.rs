@@ -0,0 +1,163 @@
+//! texture archives store the pixel data of `.dds` files split into chunks (usually one per mip), the header is reduced
+//! to a handful of fields - a loadable file needs the full DDS header with the DX10 extension put back in front of it
+
+use crate::archive::TextureInfo;
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: u32 = 124;
+const PIXEL_FORMAT_SIZE: u32 = 32;
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+
+const DDPF_FOURCC: u32 = 0x4;
+
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+/// cube map with all six faces
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xFE00;
+
+const DDS_DIMENSION_TEXTURE2D: u32 = 3;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+/// `flags` of the archived header
+const ARCHIVE_FLAG_CUBEMAP: u8 = 0x1;
+
+enum Pitch {
+    /// bytes per 4x4 block
+    Blocks(u32),
+    BitsPerPixel(u32),
+}
+
+/// only the formats the games actually use, others get no pitch which readers are fine with thanks to the DX10 header
+fn pitch(dxgi_format: u8) -> Option<Pitch> {
+    match dxgi_format {
+        // BC1, BC4
+        70..=72 | 79..=81 => Some(Pitch::Blocks(8)),
+        // BC2, BC3, BC5, BC6H, BC7
+        73..=78 | 82..=84 | 94..=99 => Some(Pitch::Blocks(16)),
+        // R8G8B8A8, B8G8R8A8, B8G8R8X8
+        27..=32 | 87..=93 => Some(Pitch::BitsPerPixel(32)),
+        // R8G8
+        48..=52 => Some(Pitch::BitsPerPixel(16)),
+        // R8, A8
+        60..=65 => Some(Pitch::BitsPerPixel(8)),
+        _ => None,
+    }
+}
+
+/// `DDS ` + DDS_HEADER + DDS_HEADER_DXT10
+pub fn header(
+    &TextureInfo {
+        width,
+        height,
+        mip_count,
+        format,
+        flags,
+    }: &TextureInfo,
+) -> Vec<u8> {
+    let (width, height) = (width as u32, height as u32);
+    let mip_count = (mip_count as u32).max(1);
+    let cubemap = flags & ARCHIVE_FLAG_CUBEMAP != 0;
+    let (pitch_flag, pitch_or_linear_size) = match pitch(format) {
+        Some(Pitch::Blocks(block_size)) => (DDSD_LINEARSIZE, width.div_ceil(4).max(1) * height.div_ceil(4).max(1) * block_size),
+        Some(Pitch::BitsPerPixel(bits)) => (DDSD_PITCH, (width * bits).div_ceil(8)),
+        None => (0, 0),
+    };
+    let fields = [
+        HEADER_SIZE,
+        DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT | pitch_flag,
+        height,
+        width,
+        pitch_or_linear_size,
+        // depth
+        0,
+        mip_count,
+    ]
+    .into_iter()
+    // reserved
+    .chain([0; 11])
+    .chain([
+        PIXEL_FORMAT_SIZE,
+        DDPF_FOURCC,
+        u32::from_le_bytes(*b"DX10"),
+        // bit count and masks
+        0,
+        0,
+        0,
+        0,
+        0,
+    ])
+    .chain([
+        DDSCAPS_TEXTURE
+            | match mip_count > 1 || cubemap {
+                true => DDSCAPS_COMPLEX,
+                false => 0,
+            }
+            | match mip_count > 1 {
+                true => DDSCAPS_MIPMAP,
+                false => 0,
+            },
+        match cubemap {
+            true => DDSCAPS2_CUBEMAP_ALLFACES,
+            false => 0,
+        },
+        // caps3, caps4, reserved
+        0,
+        0,
+        0,
+    ])
+    .chain([
+        format as u32,
+        DDS_DIMENSION_TEXTURE2D,
+        match cubemap {
+            true => DDS_RESOURCE_MISC_TEXTURECUBE,
+            false => 0,
+        },
+        // array size
+        1,
+        // misc flags 2 (alpha mode unknown)
+        0,
+    ]);
+    MAGIC
+        .iter()
+        .copied()
+        .chain(fields.flat_map(u32::to_le_bytes))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(header: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(header[offset..offset + 4].try_into().expect("4 bytes"))
+    }
+
+    #[test]
+    fn test_bc7_header() {
+        let header = header(&TextureInfo {
+            width: 1024,
+            height: 512,
+            mip_count: 11,
+            format: 98,
+            flags: 0,
+        });
+        assert_eq!(header.len(), 4 + 124 + 20);
+        assert_eq!(&header[..4], b"DDS ");
+        assert_eq!(field(&header, 4), 124);
+        assert_eq!(field(&header, 12), 512);
+        assert_eq!(field(&header, 16), 1024);
+        assert_eq!(field(&header, 20), 256 * 128 * 16);
+        assert_eq!(field(&header, 28), 11);
+        assert_eq!(&header[84..88], b"DX10");
+        assert_eq!(field(&header, 128), 98);
+        assert_eq!(field(&header, 140), 1);
+    }
+}
@@ -0,0 +1,144 @@
+//! compares two archives, or an archive with a directory of loose files - entries are matched case-insensitively
+//! and compared by their decompressed size and xxhash64 (texture entries are compared as the `.dds` files they extract to)
+
+use {
+    crate::archive::BethesdaArchive,
+    anyhow::{Context, Result},
+    hoola_paths::MaybeWindowsPath,
+    std::{collections::BTreeMap, path::Path},
+    tap::prelude::*,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Content {
+    size: u64,
+    hash: u64,
+}
+
+impl Content {
+    fn of(bytes: &[u8]) -> Self {
+        Self {
+            size: bytes.len() as u64,
+            hash: xxhash_rust::xxh64::xxh64(bytes, 0),
+        }
+    }
+}
+
+/// lowercase with windows separators -> (original path, content)
+type Contents = BTreeMap<String, (MaybeWindowsPath, Content)>;
+
+fn normalize(path: &str) -> String {
+    path.replace('/', "\\").to_lowercase()
+}
+
+fn archive_contents(path: &Path) -> Result<Contents> {
+    BethesdaArchive::open(path).and_then(|archive| {
+        archive
+            .entries()
+            .into_iter()
+            .map(|entry| {
+                Vec::new()
+                    .pipe(|mut bytes| archive.extract(&entry.key, &mut bytes).map(|_| bytes))
+                    .with_context(|| format!("reading [{}]", entry.path))
+                    .map(|bytes| (normalize(&entry.path.0), (entry.path, Content::of(&bytes))))
+            })
+            .collect()
+    })
+}
+
+fn directory_contents(path: &Path) -> Result<Contents> {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter(|entry| !entry.as_ref().is_ok_and(|entry| entry.file_type().is_dir()))
+        .map(|entry| {
+            entry.context("walking directory").and_then(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(path)
+                    .context("file outside of directory")
+                    .map(|relative| {
+                        relative
+                            .to_string_lossy()
+                            .replace('/', "\\")
+                            .pipe(MaybeWindowsPath)
+                    })
+                    .and_then(|relative| {
+                        std::fs::read(entry.path())
+                            .with_context(|| format!("reading [{}]", entry.path().display()))
+                            .map(|bytes| (normalize(&relative.0), (relative, Content::of(&bytes))))
+                    })
+            })
+        })
+        .collect()
+}
+
+fn contents(path: &Path) -> Result<Contents> {
+    match path.is_dir() {
+        true => directory_contents(path),
+        false => archive_contents(path),
+    }
+    .with_context(|| format!("reading contents of [{}]", path.display()))
+}
+
+#[derive(Debug, Default)]
+pub struct DiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+}
+
+impl DiffSummary {
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed == 0 && self.changed == 0
+    }
+}
+
+/// prints `+` for entries only in `right`, `-` for entries only in `left` and `~` for changed ones
+pub fn diff(left: &Path, right: &Path) -> Result<DiffSummary> {
+    contents(left).and_then(|left| {
+        contents(right).map(|mut right| {
+            left.into_iter()
+                .fold(DiffSummary::default(), |summary, (key, (path, left))| match right.remove(&key) {
+                    None => {
+                        println!("- {path}");
+                        DiffSummary {
+                            removed: summary.removed + 1,
+                            ..summary
+                        }
+                    }
+                    Some((_, right)) if right == left => DiffSummary {
+                        unchanged: summary.unchanged + 1,
+                        ..summary
+                    },
+                    Some((_, right)) => {
+                        println!(
+                            "~ {path} ({} bytes, {:016x} -> {} bytes, {:016x})",
+                            left.size, left.hash, right.size, right.hash
+                        );
+                        DiffSummary {
+                            changed: summary.changed + 1,
+                            ..summary
+                        }
+                    }
+                })
+                .pipe(|summary| {
+                    right.into_values().fold(summary, |summary, (path, _)| {
+                        println!("+ {path}");
+                        DiffSummary {
+                            added: summary.added + 1,
+                            ..summary
+                        }
+                    })
+                })
+                .tap(
+                    |DiffSummary {
+                         added,
+                         removed,
+                         changed,
+                         unchanged,
+                     }| { println!("{added} added, {removed} removed, {changed} changed, {unchanged} unchanged") },
+                )
+        })
+    })
+}
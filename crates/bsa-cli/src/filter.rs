@@ -0,0 +1,69 @@
+use {
+    anyhow::{Context, Result},
+    globset::{GlobBuilder, GlobSet, GlobSetBuilder},
+    hoola_paths::MaybeWindowsPath,
+};
+
+/// `--include "meshes/**" --exclude "*.psc"`, matched case-insensitively against `/` separated paths
+#[derive(Debug)]
+pub struct EntryFilter {
+    /// everything is included when empty
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    patterns
+        .iter()
+        .try_fold(GlobSetBuilder::new(), |mut builder, pattern| {
+            GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .literal_separator(false)
+                .build()
+                .with_context(|| format!("invalid glob [{pattern}]"))
+                .map(|glob| {
+                    builder.add(glob);
+                    builder
+                })
+        })
+        .and_then(|builder| builder.build().context("building glob set"))
+}
+
+impl EntryFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: match include.is_empty() {
+                true => None,
+                false => Some(glob_set(include)?),
+            },
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    pub fn matches(&self, path: &MaybeWindowsPath) -> bool {
+        let path = path.0.replace('\\', "/");
+        self.include
+            .as_ref()
+            .map(|include| include.is_match(&path))
+            .unwrap_or(true)
+            && !self.exclude.is_match(&path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_include_and_exclude() -> Result<()> {
+        let filter = EntryFilter::new(&["meshes/**".into(), "scripts/**".into()], &["*.psc".into()])?;
+        assert!(filter.matches(&MaybeWindowsPath(r"Meshes\Armor\helmet.nif".into())));
+        assert!(filter.matches(&MaybeWindowsPath(r"scripts\quest.pex".into())));
+        assert!(!filter.matches(&MaybeWindowsPath(r"scripts\source\quest.psc".into())));
+        assert!(!filter.matches(&MaybeWindowsPath(r"textures\armor\helmet.dds".into())));
+
+        let everything = EntryFilter::new(&[], &[])?;
+        assert!(everything.matches(&MaybeWindowsPath(r"textures\armor\helmet.dds".into())));
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use {
+    crate::archive::{BethesdaArchive, EntryInfo},
+    anyhow::{Context, Result},
+    clap::ValueEnum,
+    tap::prelude::*,
+};
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum ListFormat {
+    /// human readable
+    #[default]
+    Text,
+    /// a single json array
+    Json,
+    /// tab separated, with a header line
+    Tsv,
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn tsv_line(
+    EntryInfo {
+        path,
+        size,
+        compressed_size,
+        chunks,
+        texture,
+    }: &EntryInfo,
+) -> String {
+    [
+        path.to_string(),
+        size.to_string(),
+        optional(*compressed_size),
+        optional(*chunks),
+        optional(texture.as_ref().map(|texture| texture.width)),
+        optional(texture.as_ref().map(|texture| texture.height)),
+        optional(texture.as_ref().map(|texture| texture.mip_count)),
+        optional(texture.as_ref().map(|texture| texture.format)),
+    ]
+    .join("\t")
+}
+
+const TSV_HEADER: &str = "path\tsize\tcompressed_size\tchunks\twidth\theight\tmip_count\tformat";
+
+pub fn list(archive: &BethesdaArchive, format: ListFormat) -> Result<()> {
+    match format {
+        ListFormat::Text => archive
+            .entries()
+            .into_iter()
+            .enumerate()
+            .for_each(|(idx, entry)| println!("{}. {}  ({:?})", idx + 1, entry.path, entry.key))
+            .pipe(Ok),
+        ListFormat::Json => archive
+            .entries()
+            .iter()
+            .map(|entry| archive.info(entry))
+            .collect::<Result<Vec<_>>>()
+            .and_then(|infos| serde_json::to_string_pretty(&infos).context("serializing listing"))
+            .map(|json| println!("{json}")),
+        ListFormat::Tsv => archive
+            .entries()
+            .iter()
+            .map(|entry| archive.info(entry).map(|info| tsv_line(&info)))
+            .collect::<Result<Vec<_>>>()
+            .map(|lines| {
+                println!("{TSV_HEADER}");
+                lines.iter().for_each(|line| println!("{line}"));
+            }),
+    }
+}
@@ -1,11 +1,27 @@
 use {
     anyhow::{Context, Result},
-    ba2::{fo4::FileWriteOptions, ByteSlice, Reader},
+    archive::BethesdaArchive,
     clap::{Parser, Subcommand},
-    std::path::{Path, PathBuf},
+    create::{ArchiveFormat, Compression},
+    filter::EntryFilter,
+    hoola_paths::MaybeWindowsPath,
+    listing::ListFormat,
+    std::path::{Component, Path, PathBuf},
     tap::prelude::*,
 };
 
+mod archive;
+mod cat;
+mod create;
+mod dds;
+mod diff;
+mod filter;
+mod listing;
+mod modify;
+mod repack;
+mod stats;
+mod verify;
+
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
@@ -16,57 +32,112 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum ArchiveCommand {
-    /// list the archive under path
+    /// list the archive under path, fallout 4 and starfield `.ba2` as well as oblivion, skyrim and morrowind `.bsa` are supported
     List {
         /// path to archive
         archive_path: PathBuf,
+        /// json and tsv also include sizes, chunk counts and texture metadata
+        #[arg(long, value_enum, default_value_t)]
+        output: ListFormat,
     },
     /// extract file to current directory
     Extract {
+        /// path to archive
+        archive_path: PathBuf,
+        /// path to file within archive
+        #[arg(required_unless_present = "from_file")]
+        file_path: Option<MaybeWindowsPath>,
+        /// file with one path within the archive per line, `-` reads them from stdin - the extracted files are printed as they are written
+        #[arg(long, conflicts_with = "file_path")]
+        from_file: Option<PathBuf>,
+    },
+    /// write a single file to stdout
+    Cat {
         /// path to archive
         archive_path: PathBuf,
         /// path to file within archive
         file_path: MaybeWindowsPath,
+        /// skip this many bytes
+        #[arg(long, default_value_t = 0)]
+        offset: u64,
+        /// stop after this many bytes
+        #[arg(long)]
+        length: Option<u64>,
+    },
+    /// extract every file (or the ones matching the filters) into a directory, keeping the directory structure
+    ExtractAll {
+        /// path to archive
+        archive_path: PathBuf,
+        /// directory to extract to
+        output_dir: PathBuf,
+        /// glob of the files to extract (eg. `meshes/**`), can be repeated
+        #[arg(long)]
+        include: Vec<String>,
+        /// glob of the files to skip (eg. `*.psc`), can be repeated
+        #[arg(long)]
+        exclude: Vec<String>,
+    },
+    /// compare two archives, or an archive with a directory of loose files
+    Diff {
+        /// archive (or directory)
+        left: PathBuf,
+        /// archive (or directory)
+        right: PathBuf,
+        /// exit with an error when there are any differences
+        #[arg(long)]
+        check: bool,
+    },
+    /// rewrite an archive with another compression, keeping the order of the entries
+    Repack {
+        /// path to archive
+        archive_path: PathBuf,
+        /// path of the repacked archive
+        output_path: PathBuf,
+        /// for oblivion and skyrim archives anything but `none` means compressed, the codec depends on the archive version
+        #[arg(long, value_enum, default_value_t)]
+        compression: Compression,
+    },
+    /// add (or replace) every file under a directory, paths inside of the archive are relative to it
+    Add {
+        /// path to archive
+        archive_path: PathBuf,
+        /// directory with the files to add
+        source: PathBuf,
+        /// only used for fallout 4 archives, oblivion and skyrim ones compress according to their flags
+        #[arg(long, value_enum, default_value_t)]
+        compression: Compression,
+    },
+    /// remove the entries matching any of the globs (eg. `scripts/source/**`)
+    Remove {
+        /// path to archive
+        archive_path: PathBuf,
+        #[arg(required = true)]
+        patterns: Vec<String>,
+    },
+    /// sizes per extension and a histogram of compression ratios
+    Stats {
+        /// path to archive
+        archive_path: PathBuf,
+    },
+    /// decompress every entry and report the corrupt ones
+    Verify {
+        /// path to archive
+        archive_path: PathBuf,
+    },
+    /// pack a directory into a new fallout 4 archive
+    Create {
+        /// directory to pack
+        source: PathBuf,
+        /// path of the new archive
+        archive_path: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: ArchiveFormat,
+        #[arg(long, value_enum, default_value_t)]
+        compression: Compression,
+        /// archive version, 1 for fallout 4, 2 or 3 for starfield
+        #[arg(long, default_value_t = 1)]
+        version: u32,
     },
-}
-fn list_paths_with_originals<'a>(archive: &ba2::fo4::Archive<'a>) -> Vec<(MaybeWindowsPath, ba2::fo4::ArchiveKey<'a>)> {
-    archive
-        .iter()
-        .map(|(key, _file)| {
-            key.name()
-                .pipe(|s| s.as_bytes())
-                .pipe(|b| {
-                    String::from_utf8_lossy(b)
-                        .to_string()
-                        .pipe(MaybeWindowsPath)
-                })
-                .pipe(|path| (path, key.clone()))
-        })
-        .collect()
-}
-
-fn open_archive<'a>(path: &Path) -> Result<(ba2::fo4::Archive<'a>, ba2::fo4::ArchiveOptions)> {
-    ba2::fo4::Archive::read(path)
-        .context("opening archive")
-        .with_context(|| format!("openinig archive at {path:#?}"))
-}
-
-#[derive(Debug, derive_more::From, derive_more::FromStr, derive_more::Display, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub struct MaybeWindowsPath(pub String);
-
-impl MaybeWindowsPath {
-    pub fn into_path(self) -> PathBuf {
-        let s = self.0;
-        let s = match s.contains("\\\\") {
-            true => s.split("\\\\").collect::<Vec<_>>().join("/"),
-            false => s,
-        };
-        let s = match s.contains("\\") {
-            true => s.split("\\").collect::<Vec<_>>().join("/"),
-            false => s,
-        };
-        PathBuf::from(s)
-    }
 }
 
 pub(crate) fn create_file_all(path: &Path) -> Result<std::fs::File> {
@@ -84,40 +155,143 @@ pub(crate) fn create_file_all(path: &Path) -> Result<std::fs::File> {
         .with_context(|| format!("creating full path [{path:?}]"))
 }
 
+/// entry names come from the archive, `..` or a root in one of them would write outside of the output directory
+fn output_path_of(output_dir: &Path, entry_path: &MaybeWindowsPath) -> Result<PathBuf> {
+    entry_path.clone().into_path().pipe(|path| {
+        match path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            true => Ok(output_dir.join(path)),
+            false => Err(anyhow::anyhow!("[{entry_path}] points outside of the output directory, refusing to extract it")),
+        }
+    })
+}
+
+fn extract_entry(archive: &BethesdaArchive, entry: &archive::Entry<'_>) -> Result<PathBuf> {
+    output_path_of(Path::new(""), &entry.path).and_then(|output_path| {
+        create_file_all(&output_path)
+            .context("creating output file")
+            .map(std::io::BufWriter::new)
+            .and_then(|mut output_file| archive.extract(&entry.key, &mut output_file))
+            .with_context(|| format!("extracting [{}]", entry.path))
+            .map(|_| output_path)
+    })
+}
+
+/// the list is read line by line, so a pipeline can keep feeding paths while earlier ones are extracted
+fn extract_batch(archive: &BethesdaArchive, from_file: &Path) -> Result<()> {
+    let entries = archive
+        .entries()
+        .into_iter()
+        .map(|entry| (entry.path.clone(), entry))
+        .collect::<std::collections::HashMap<_, _>>();
+    match from_file == Path::new("-") {
+        true => Box::new(std::io::stdin().lock()) as Box<dyn std::io::BufRead>,
+        false => std::fs::File::open(from_file)
+            .with_context(|| format!("opening [{}]", from_file.display()))
+            .map(|file| Box::new(std::io::BufReader::new(file)) as Box<dyn std::io::BufRead>)?,
+    }
+    .lines()
+    .map(|line| line.context("reading path list"))
+    .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+    .try_for_each(|line| {
+        line.and_then(|line| {
+            MaybeWindowsPath(line.trim_end_matches('\r').to_string()).pipe(|path| {
+                entries
+                    .get(&path)
+                    .with_context(|| format!("no [{path}] in the archive"))
+                    .and_then(|entry| extract_entry(archive, entry))
+                    .map(|output_path| println!("{}", output_path.display()))
+            })
+        })
+    })
+}
+
+fn extract_all(archive_path: &Path, output_dir: &Path, filter: &EntryFilter) -> Result<()> {
+    BethesdaArchive::open(archive_path).and_then(|archive| {
+        archive
+            .entries()
+            .into_iter()
+            .filter(|entry| filter.matches(&entry.path))
+            .try_fold(0, |extracted, entry| {
+                output_path_of(output_dir, &entry.path)
+                    .and_then(|output_path| create_file_all(&output_path))
+                    .map(std::io::BufWriter::new)
+                    .and_then(|mut output| archive.extract(&entry.key, &mut output))
+                    .with_context(|| format!("extracting [{}]", entry.path))
+                    .map(|_| extracted + 1)
+            })
+            .map(|extracted| println!("extracted {extracted} files to [{}]", output_dir.display()))
+    })
+}
+
 fn main() -> anyhow::Result<()> {
     Cli::parse().pipe(|Cli { command }| match command {
-        ArchiveCommand::List { archive_path } => open_archive(&archive_path).map(|(archive, _)| {
-            list_paths_with_originals(&archive)
-                .into_iter()
-                .enumerate()
-                .for_each(|(idx, (file, key))| println!("{}. {}  ({:?})", idx + 1, file, key))
+        ArchiveCommand::List { archive_path, output } => BethesdaArchive::open(&archive_path).and_then(|archive| listing::list(&archive, output)),
+        ArchiveCommand::Extract {
+            archive_path,
+            file_path,
+            from_file,
+        } => BethesdaArchive::open(&archive_path).and_then(|archive| match (file_path, from_file) {
+            (Some(file_path), _) => archive
+                .find(&file_path)
+                .and_then(|entry| extract_entry(&archive, &entry)),
+            (None, Some(from_file)) => extract_batch(&archive, &from_file),
+            (None, None) => anyhow::bail!("either a file path or --from-file is required"),
         }),
-        ArchiveCommand::Extract { archive_path, file_path } => open_archive(&archive_path).and_then(|(archive, options)| {
-            list_paths_with_originals(&archive).pipe(|entries| {
-                entries
-                    .iter()
-                    .find(|(name, _key)| file_path.eq(name))
-                    .with_context(|| format!("no [{file_path}] in {entries:#?}"))
-                    .and_then(|(path, key)| {
-                        archive
-                            .get(key)
-                            .context("opening using key")
-                            .and_then(|archive_file| {
-                                create_file_all(&path.clone().into_path())
-                                    .context("creating output file")
-                                    .and_then(|mut output_file| {
-                                        archive_file
-                                            .write(
-                                                &mut output_file,
-                                                &FileWriteOptions::builder()
-                                                    .compression_format(options.compression_format())
-                                                    .build(),
-                                            )
-                                            .context("writing to file")
-                                    })
-                            })
-                    })
-            })
+        ArchiveCommand::Cat {
+            archive_path,
+            file_path,
+            offset,
+            length,
+        } => cat::cat(&archive_path, &file_path, offset, length),
+        ArchiveCommand::ExtractAll {
+            archive_path,
+            output_dir,
+            include,
+            exclude,
+        } => EntryFilter::new(&include, &exclude).and_then(|filter| extract_all(&archive_path, &output_dir, &filter)),
+        ArchiveCommand::Diff { left, right, check } => diff::diff(&left, &right).and_then(|summary| match check && !summary.is_empty() {
+            true => Err(anyhow::anyhow!("[{}] and [{}] differ", left.display(), right.display())),
+            false => Ok(()),
         }),
+        ArchiveCommand::Repack {
+            archive_path,
+            output_path,
+            compression,
+        } => repack::repack(&archive_path, &output_path, compression),
+        ArchiveCommand::Add {
+            archive_path,
+            source,
+            compression,
+        } => modify::add(&archive_path, &source, compression),
+        ArchiveCommand::Remove { archive_path, patterns } => modify::remove(&archive_path, &patterns),
+        ArchiveCommand::Stats { archive_path } => stats::stats(&archive_path),
+        ArchiveCommand::Verify { archive_path } => verify::verify(&archive_path),
+        ArchiveCommand::Create {
+            source,
+            archive_path,
+            format,
+            compression,
+            version,
+        } => create::create_archive(&source, &archive_path, format, compression, version),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_cannot_escape_the_output_directory() {
+        let output_dir = Path::new("output");
+        assert_eq!(
+            output_path_of(output_dir, &MaybeWindowsPath::new("textures\\armor\\a.dds")).unwrap(),
+            output_dir.join("textures/armor/a.dds")
+        );
+        ["..\\..\\x.dll", "textures\\..\\..\\x.dll", "/etc/passwd", "\\x.dll"]
+            .into_iter()
+            .for_each(|malicious| assert!(output_path_of(output_dir, &MaybeWindowsPath::new(malicious)).is_err(), "{malicious}"));
+    }
+}
@@ -0,0 +1,274 @@
+//! small patches without a full repack - the archive is rebuilt with entries added from loose files or dropped by glob.
+//! the archive being modified stays memory mapped while the new one is written, so it's written next to it and moved over it once done
+
+use {
+    crate::{
+        archive::{lossy_path, tes4_path, BethesdaArchive},
+        create::{archive_key, loose_files, read_loose_file, ArchiveFormat, Compression},
+        filter::EntryFilter,
+    },
+    anyhow::{Context, Result},
+    ba2::{fo4, tes4, BString, CompressionResult, Copied, ReaderWithOptions},
+    std::{
+        io::Write,
+        path::{Path, PathBuf},
+    },
+    tap::prelude::*,
+};
+
+fn write_next_to(archive_path: &Path, write: impl FnOnce(&mut std::io::BufWriter<&mut std::fs::File>) -> Result<()>) -> Result<tempfile::NamedTempFile> {
+    archive_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .pipe(tempfile::NamedTempFile::new_in)
+        .context("creating temp file")
+        .and_then(|mut temp| {
+            let mut writer = std::io::BufWriter::new(temp.as_file_mut());
+            write(&mut writer)
+                .and_then(|_| writer.flush().context("flushing"))
+                .map(|_| drop(writer))
+                .map(|_| temp)
+        })
+}
+
+/// `rebuild` gets the opened archive and writes the new one, the old one is closed before it's replaced
+fn rewrite(archive_path: &Path, rebuild: impl FnOnce(BethesdaArchive, &mut std::io::BufWriter<&mut std::fs::File>) -> Result<()>) -> Result<()> {
+    BethesdaArchive::open(archive_path)
+        .and_then(|archive| write_next_to(archive_path, |writer| rebuild(archive, writer)))
+        .and_then(|temp| temp.persist(archive_path).context("replacing archive"))
+        .map(|_| ())
+        .with_context(|| format!("rewriting [{}]", archive_path.display()))
+}
+
+fn fallout4_with(archive: fo4::Archive<'static>, added: Vec<(fo4::ArchiveKey<'static>, fo4::File<'static>)>) -> fo4::Archive<'static> {
+    archive
+        .iter()
+        .map(|(key, file)| (key.clone(), file.clone()))
+        .chain(added)
+        .fold(fo4::Archive::new(), |archive, (key, file)| {
+            archive.tap_mut(|archive| {
+                // files already in the archive get replaced
+                archive.insert(key, file);
+            })
+        })
+}
+
+fn tes4_keys(relative: &Path) -> Result<(tes4::ArchiveKey<'static>, tes4::DirectoryKey<'static>)> {
+    relative
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .context("files at the root of the archive are not supported")
+        .and_then(|directory| {
+            relative
+                .file_name()
+                .context("no file name")
+                .map(|file_name| {
+                    (
+                        directory
+                            .to_string_lossy()
+                            .replace('/', "\\")
+                            .into_bytes()
+                            .conv::<BString>()
+                            .conv::<tes4::ArchiveKey>(),
+                        file_name
+                            .to_string_lossy()
+                            .into_owned()
+                            .into_bytes()
+                            .conv::<BString>()
+                            .conv::<tes4::DirectoryKey>(),
+                    )
+                })
+        })
+        .with_context(|| format!("deriving keys for [{}]", relative.display()))
+}
+
+fn read_tes4_file(path: &Path, options: &tes4::ArchiveOptions) -> Result<tes4::File<'static>> {
+    std::fs::read(path)
+        .context("reading file")
+        .and_then(|bytes| {
+            tes4::File::read(
+                Copied(&bytes),
+                &tes4::FileReadOptions::builder()
+                    .version(options.version())
+                    .compression_result(match options.flags().contains(tes4::ArchiveFlags::COMPRESSED) {
+                        true => CompressionResult::Compressed,
+                        false => CompressionResult::Decompressed,
+                    })
+                    .build(),
+            )
+            .context("building archive file")
+        })
+        .with_context(|| format!("reading [{}]", path.display()))
+}
+
+type Tes4Entry = (tes4::ArchiveKey<'static>, tes4::DirectoryKey<'static>, tes4::File<'static>);
+
+fn tes4_with(archive: tes4::Archive<'static>, keep: impl Fn(&Tes4Entry) -> bool, added: Vec<Tes4Entry>) -> tes4::Archive<'static> {
+    archive
+        .iter()
+        .flat_map(|(archive_key, directory)| {
+            directory
+                .iter()
+                .map(|(directory_key, file)| (archive_key.clone(), directory_key.clone(), file.clone()))
+                .collect::<Vec<_>>()
+        })
+        .filter(|entry| keep(entry))
+        .chain(added)
+        .fold(tes4::Archive::new(), |mut archive, (archive_key, directory_key, file)| {
+            let directory = archive
+                .remove(&archive_key)
+                .unwrap_or_default()
+                .tap_mut(|directory| {
+                    directory.insert(directory_key, file);
+                });
+            archive.insert(archive_key, directory);
+            archive
+        })
+}
+
+/// adds (or replaces) every file under `source`, paths inside of the archive are relative to it.
+/// fallout 4 entries are compressed with `compression`, oblivion/skyrim ones follow the archive flags
+pub fn add(archive_path: &Path, source: &Path, compression: Compression) -> Result<()> {
+    loose_files(source).and_then(|files: Vec<(PathBuf, PathBuf)>| {
+        let count = files.len();
+        rewrite(archive_path, |archive, writer| match archive {
+            BethesdaArchive::Fallout4(archive, options) => {
+                let format = match options.format() {
+                    fo4::Format::DX10 => ArchiveFormat::Dx10,
+                    _ => ArchiveFormat::General,
+                };
+                files
+                    .iter()
+                    .map(|(relative, path)| read_loose_file(path, format, compression).map(|file| (archive_key(relative), file)))
+                    .collect::<Result<Vec<_>>>()
+                    .map(|added| fallout4_with(archive, added))
+                    .and_then(|archive| archive.write(writer, &options).context("writing archive"))
+            }
+            BethesdaArchive::Tes4(archive, options) => files
+                .iter()
+                .map(|(relative, path)| {
+                    tes4_keys(relative).and_then(|(archive_key, directory_key)| read_tes4_file(path, &options).map(|file| (archive_key, directory_key, file)))
+                })
+                .collect::<Result<Vec<_>>>()
+                .map(|added| tes4_with(archive, |_| true, added))
+                .and_then(|archive| archive.write(writer, &options).context("writing archive")),
+            BethesdaArchive::Tes3(_) => anyhow::bail!("morrowind archives can't be modified"),
+        })
+        .tap_ok(|_| println!("added {count} files to [{}]", archive_path.display()))
+    })
+}
+
+/// drops every entry matching any of the globs
+pub fn remove(archive_path: &Path, patterns: &[String]) -> Result<()> {
+    EntryFilter::new(patterns, &[]).and_then(|filter| {
+        rewrite(archive_path, |archive, writer| {
+            let before = archive.entries().len();
+            match archive {
+                BethesdaArchive::Fallout4(archive, options) => archive
+                    .iter()
+                    .filter(|(key, _)| !filter.matches(&lossy_path(key.name())))
+                    .map(|(key, file)| (key.clone(), file.clone()))
+                    .fold(fo4::Archive::new(), |archive, (key, file)| {
+                        archive.tap_mut(|archive| {
+                            archive.insert(key, file);
+                        })
+                    })
+                    .pipe(|archive| {
+                        archive
+                            .write(writer, &options)
+                            .context("writing archive")
+                            .map(|_| archive.len())
+                    }),
+                BethesdaArchive::Tes4(archive, options) => tes4_with(
+                    archive,
+                    |(archive_key, directory_key, _)| !filter.matches(&tes4_path(archive_key, directory_key)),
+                    vec![],
+                )
+                .pipe(|archive| {
+                    archive
+                        .write(writer, &options)
+                        .context("writing archive")
+                        .map(|_| archive.iter().map(|(_, directory)| directory.len()).sum())
+                }),
+                BethesdaArchive::Tes3(_) => anyhow::bail!("morrowind archives can't be modified"),
+            }
+            .map(|after: usize| println!("removed {} entries from [{}]", before - after, archive_path.display()))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            create::{
+                create_archive,
+                tests::{assert_round_trip, general_files, write_loose},
+            },
+            repack::tests::write_tes4,
+        },
+    };
+
+    /// (files to add, `general_files` after adding them) - one replaces an existing file, one is new
+    fn added_files() -> (Vec<(&'static str, Vec<u8>)>, Vec<(&'static str, Vec<u8>)>) {
+        let added = vec![("scripts/b.pex", b"replaced".to_vec()), ("sound/d.wav", b"new file".to_vec())];
+        general_files()
+            .into_iter()
+            .filter(|(path, _)| *path != "scripts/b.pex")
+            .chain(added.clone())
+            .collect::<Vec<_>>()
+            .pipe(|expected| (added, expected))
+    }
+
+    #[test]
+    fn test_fallout4_added_files_read_back() -> Result<()> {
+        [(Compression::None, false), (Compression::Zip, true), (Compression::ZipXbox, true)]
+            .into_iter()
+            .try_for_each(|(compression, compressed)| {
+                let directory = tempfile::tempdir()?;
+                let (source, added_source) = (directory.path().join("source"), directory.path().join("added"));
+                let archive_path = directory.path().join("archive.ba2");
+                let (added, expected) = added_files();
+                write_loose(&source, &general_files())?;
+                write_loose(&added_source, &added)?;
+                create_archive(&source, &archive_path, ArchiveFormat::General, compression, 1)?;
+                add(&archive_path, &added_source, compression)?;
+                assert_round_trip(&archive_path, &expected, compressed).with_context(|| format!("{compression:?}"))
+            })
+    }
+
+    #[test]
+    fn test_tes4_added_files_read_back() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let added_source = directory.path().join("added");
+        let archive_path = directory.path().join("archive.bsa");
+        let (added, expected) = added_files();
+        // tes4 archives have no files at their root
+        let expected = expected
+            .into_iter()
+            .filter(|(path, _)| path.contains('/'))
+            .collect::<Vec<_>>();
+        write_tes4(&archive_path, &general_files()[..2])?;
+        write_loose(&added_source, &added)?;
+        add(&archive_path, &added_source, Compression::Zip)?;
+        assert_round_trip(&archive_path, &expected, false)
+    }
+
+    #[test]
+    fn test_entries_matching_the_globs_are_removed() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let source = directory.path().join("source");
+        let (fallout4, skyrim) = (directory.path().join("archive.ba2"), directory.path().join("archive.bsa"));
+        let files = general_files();
+        write_loose(&source, &files)?;
+        create_archive(&source, &fallout4, ArchiveFormat::General, Compression::Zip, 1)?;
+        write_tes4(&skyrim, &files[..2])?;
+
+        remove(&fallout4, &["scripts/**".to_string(), "*.txt".to_string()])?;
+        assert_round_trip(&fallout4, &files[..1], true)?;
+        remove(&skyrim, &["meshes/**".to_string()])?;
+        assert_round_trip(&skyrim, &files[1..2], false)
+    }
+}
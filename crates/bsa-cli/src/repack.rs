@@ -0,0 +1,277 @@
+//! rewrites an archive with another compression, entries keep their order - older engine builds crash on compression they don't know
+
+use {
+    crate::{archive::BethesdaArchive, create::Compression},
+    anyhow::{Context, Result},
+    ba2::{fo4, tes4},
+    std::path::Path,
+    tap::prelude::*,
+};
+
+fn repack_fallout4(
+    archive: &fo4::Archive<'static>,
+    options: &fo4::ArchiveOptions,
+    compression: Compression,
+) -> Result<(fo4::Archive<'static>, fo4::ArchiveOptions)> {
+    let decompress = fo4::ChunkCompressionOptions::builder()
+        .compression_format(options.compression_format())
+        .build();
+    let compress = fo4::ChunkCompressionOptions::builder()
+        .compression_format(compression.format())
+        .compression_level(compression.level())
+        .build();
+    archive
+        .iter()
+        .map(|(key, file)| {
+            file.iter()
+                .map(|chunk| {
+                    match chunk.is_compressed() {
+                        true => chunk.decompress(&decompress).context("decompressing"),
+                        false => Ok(chunk.clone()),
+                    }
+                    .and_then(|chunk| match compression {
+                        Compression::None => Ok(chunk),
+                        _ => chunk.compress(&compress).context("compressing"),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+                .map(|chunks| {
+                    file.clone().tap_mut(|file| {
+                        file.iter_mut()
+                            .zip(chunks)
+                            .for_each(|(chunk, repacked)| *chunk = repacked)
+                    })
+                })
+                .with_context(|| format!("repacking [{}]", String::from_utf8_lossy(key.name())))
+                .map(|file| (key.clone(), file))
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(|files| {
+            (
+                files
+                    .into_iter()
+                    .fold(fo4::Archive::new(), |archive, (key, file)| {
+                        archive.tap_mut(|archive| {
+                            archive.insert(key, file);
+                        })
+                    }),
+                fo4::ArchiveOptions::builder()
+                    .format(options.format())
+                    .version(options.version())
+                    .strings(options.strings())
+                    .compression_format(compression.format())
+                    .build(),
+            )
+        })
+}
+
+/// tes4 archives only know compressed or not, the codec follows from the archive version
+fn repack_tes4(
+    archive: &tes4::Archive<'static>,
+    options: &tes4::ArchiveOptions,
+    compression: Compression,
+) -> Result<(tes4::Archive<'static>, tes4::ArchiveOptions)> {
+    let file_options = tes4::FileCompressionOptions::builder()
+        .version(options.version())
+        .build();
+    archive
+        .iter()
+        .map(|(archive_key, directory)| {
+            directory
+                .iter()
+                .map(|(directory_key, file)| {
+                    match file.is_compressed() {
+                        true => file.decompress(&file_options).context("decompressing"),
+                        false => Ok(file.clone()),
+                    }
+                    .and_then(|file| match compression {
+                        Compression::None => Ok(file),
+                        _ => file.compress(&file_options).context("compressing"),
+                    })
+                    .with_context(|| format!("repacking [{}]", String::from_utf8_lossy(directory_key.name())))
+                    .map(|file| (directory_key.clone(), file))
+                })
+                .collect::<Result<Vec<_>>>()
+                .map(|files| {
+                    files
+                        .into_iter()
+                        .fold(tes4::Directory::default(), |directory, (key, file)| {
+                            directory.tap_mut(|directory| {
+                                directory.insert(key, file);
+                            })
+                        })
+                })
+                .with_context(|| format!("repacking directory [{}]", String::from_utf8_lossy(archive_key.name())))
+                .map(|directory| (archive_key.clone(), directory))
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(|directories| {
+            (
+                directories
+                    .into_iter()
+                    .fold(tes4::Archive::new(), |archive, (key, directory)| {
+                        archive.tap_mut(|archive| {
+                            archive.insert(key, directory);
+                        })
+                    }),
+                tes4::ArchiveOptions::builder()
+                    .version(options.version())
+                    .types(options.types())
+                    .flags(match compression {
+                        Compression::None => options.flags() - tes4::ArchiveFlags::COMPRESSED,
+                        _ => options.flags() | tes4::ArchiveFlags::COMPRESSED,
+                    })
+                    .build(),
+            )
+        })
+}
+
+pub fn repack(input: &Path, output: &Path, compression: Compression) -> Result<()> {
+    // the input stays memory mapped while the output is written
+    (std::fs::canonicalize(input).ok() != std::fs::canonicalize(output).ok())
+        .then_some(())
+        .context("output has to be a different file than the input")
+        .and_then(|_| BethesdaArchive::open(input))
+        .and_then(|archive| match &archive {
+            BethesdaArchive::Fallout4(archive, options) => {
+                repack_fallout4(archive, options, compression).and_then(|(archive, options)| crate::create::write_archive(&archive, &options, output))
+            }
+            BethesdaArchive::Tes4(archive, options) => repack_tes4(archive, options, compression).and_then(|(archive, options)| {
+                crate::create_file_all(output)
+                    .map(std::io::BufWriter::new)
+                    .and_then(|mut writer| {
+                        archive
+                            .write(&mut writer, &options)
+                            .context("writing archive")
+                    })
+            }),
+            BethesdaArchive::Tes3(_) => anyhow::bail!("morrowind archives are never compressed"),
+        })
+        .with_context(|| format!("repacking [{}] into [{}]", input.display(), output.display()))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::create::{
+            create_archive,
+            tests::{assert_round_trip, general_files, write_loose},
+            ArchiveFormat,
+        },
+        ba2::{BString, CompressionResult, Copied, ReaderWithOptions},
+    };
+
+    fn entry_paths(archive_path: &Path) -> Result<Vec<String>> {
+        BethesdaArchive::open(archive_path).map(|archive| {
+            archive
+                .entries()
+                .into_iter()
+                .map(|entry| entry.path.to_string())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_fallout4_archives_round_trip_with_every_compression() -> Result<()> {
+        [
+            (Compression::Zip, 1, Compression::None, false),
+            (Compression::None, 1, Compression::Zip, true),
+            (Compression::Zip, 1, Compression::ZipXbox, true),
+            (Compression::Zip, 3, Compression::Lz4, true),
+            (Compression::Lz4, 3, Compression::Zip, true),
+        ]
+        .into_iter()
+        .try_for_each(|(from, version, to, compressed)| {
+            let directory = tempfile::tempdir()?;
+            let source = directory.path().join("source");
+            let (input, output) = (directory.path().join("in.ba2"), directory.path().join("out.ba2"));
+            let files = general_files();
+            write_loose(&source, &files)?;
+            create_archive(&source, &input, ArchiveFormat::General, from, version)?;
+            repack(&input, &output, to)?;
+            assert_eq!(entry_paths(&input)?, entry_paths(&output)?);
+            assert_round_trip(&output, &files, compressed).with_context(|| format!("{from:?} -> {to:?}, version {version}"))
+        })
+    }
+
+    pub(crate) fn write_tes4(archive_path: &Path, files: &[(&str, Vec<u8>)]) -> Result<()> {
+        let options = tes4::ArchiveOptions::builder()
+            .version(tes4::Version::v105)
+            .flags(tes4::ArchiveFlags::DIRECTORY_STRINGS | tes4::ArchiveFlags::FILE_STRINGS)
+            .build();
+        files
+            .iter()
+            .try_fold(tes4::Archive::new(), |mut archive, (path, contents)| {
+                let (directory, file_name) = path.rsplit_once('/').context("no directory")?;
+                let file = tes4::File::read(
+                    Copied(contents),
+                    &tes4::FileReadOptions::builder()
+                        .version(options.version())
+                        .compression_result(CompressionResult::Decompressed)
+                        .build(),
+                )?;
+                let archive_key = directory
+                    .replace('/', "\\")
+                    .into_bytes()
+                    .conv::<BString>()
+                    .conv::<tes4::ArchiveKey>();
+                let directory = archive
+                    .remove(&archive_key)
+                    .unwrap_or_default()
+                    .tap_mut(|directory| {
+                        directory.insert(
+                            file_name
+                                .as_bytes()
+                                .to_vec()
+                                .conv::<BString>()
+                                .conv::<tes4::DirectoryKey>(),
+                            file,
+                        );
+                    });
+                archive.insert(archive_key, directory);
+                anyhow::Ok(archive)
+            })
+            .and_then(|archive| {
+                crate::create_file_all(archive_path)
+                    .map(std::io::BufWriter::new)
+                    .and_then(|mut writer| {
+                        archive
+                            .write(&mut writer, &options)
+                            .context("writing archive")
+                    })
+            })
+    }
+
+    #[test]
+    fn test_tes4_archives_round_trip() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let files = general_files()
+            .into_iter()
+            .filter(|(path, _)| path.contains('/'))
+            .collect::<Vec<_>>();
+        let input = directory.path().join("in.bsa");
+        write_tes4(&input, &files)?;
+        assert_round_trip(&input, &files, false)?;
+
+        let compressed = directory.path().join("compressed.bsa");
+        repack(&input, &compressed, Compression::Zip)?;
+        assert_eq!(entry_paths(&input)?, entry_paths(&compressed)?);
+        assert_round_trip(&compressed, &files, true)?;
+
+        let decompressed = directory.path().join("decompressed.bsa");
+        repack(&compressed, &decompressed, Compression::None)?;
+        assert_round_trip(&decompressed, &files, false)
+    }
+
+    #[test]
+    fn test_repacking_into_the_input_is_refused() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let source = directory.path().join("source");
+        let archive_path = directory.path().join("in.ba2");
+        write_loose(&source, &general_files())?;
+        create_archive(&source, &archive_path, ArchiveFormat::General, Compression::Zip, 1)?;
+        assert!(repack(&archive_path, &archive_path, Compression::None).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+//! where the bytes of an archive go - handy when deciding whether repacking texture archives is worth it
+
+use {
+    crate::archive::{BethesdaArchive, EntryInfo},
+    anyhow::{Context, Result},
+    std::{collections::BTreeMap, path::Path},
+};
+
+/// compressed size as a percentage of the decompressed size, in steps of 10
+const HISTOGRAM_BUCKETS: usize = 10;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Totals {
+    entries: usize,
+    size: u64,
+    /// uncompressed entries count with their full size
+    stored: u64,
+    chunks: usize,
+}
+
+impl Totals {
+    fn add(self, info: &EntryInfo) -> Self {
+        Self {
+            entries: self.entries + 1,
+            size: self.size + info.size,
+            stored: self.stored + info.compressed_size.unwrap_or(info.size),
+            chunks: self.chunks + info.chunks.unwrap_or(0),
+        }
+    }
+
+    fn ratio(&self) -> f64 {
+        match self.size {
+            0 => 1.,
+            size => self.stored as f64 / size as f64,
+        }
+    }
+}
+
+fn extension(info: &EntryInfo) -> String {
+    info.path
+        .0
+        .rsplit(['\\', '/'])
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+fn bucket(info: &EntryInfo) -> Option<usize> {
+    info.compressed_size
+        .filter(|_| info.size > 0)
+        .map(|compressed| ((compressed as f64 / info.size as f64 * HISTOGRAM_BUCKETS as f64) as usize).min(HISTOGRAM_BUCKETS - 1))
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024. * 1024.)
+}
+
+pub fn stats(archive_path: &Path) -> Result<()> {
+    BethesdaArchive::open(archive_path)
+        .and_then(|archive| {
+            archive
+                .entries()
+                .iter()
+                .map(|entry| archive.info(entry))
+                .collect::<Result<Vec<_>>>()
+        })
+        .map(|infos| {
+            let total = infos.iter().fold(Totals::default(), Totals::add);
+            let by_extension = infos
+                .iter()
+                .fold(BTreeMap::<String, Totals>::new(), |mut by_extension, info| {
+                    let totals = by_extension.entry(extension(info)).or_default();
+                    *totals = totals.add(info);
+                    by_extension
+                });
+            let (histogram, uncompressed) = infos
+                .iter()
+                .fold(([0usize; HISTOGRAM_BUCKETS], 0usize), |(mut histogram, uncompressed), info| {
+                    match bucket(info) {
+                        Some(bucket) => {
+                            histogram[bucket] += 1;
+                            (histogram, uncompressed)
+                        }
+                        None => (histogram, uncompressed + 1),
+                    }
+                });
+
+            println!("{}", archive_path.display());
+            println!(
+                "{} entries, {:.2} MiB decompressed, {:.2} MiB stored ({:.1}%)",
+                total.entries,
+                mib(total.size),
+                mib(total.stored),
+                total.ratio() * 100.
+            );
+            if total.chunks > 0 {
+                println!(
+                    "{} chunks, {:.2} per entry on average",
+                    total.chunks,
+                    total.chunks as f64 / total.entries.max(1) as f64
+                );
+            }
+            println!();
+            println!(
+                "{:<12} {:>8} {:>14} {:>14} {:>8}",
+                "extension", "entries", "size (MiB)", "stored (MiB)", "ratio"
+            );
+            by_extension.iter().for_each(|(extension, totals)| {
+                println!(
+                    "{:<12} {:>8} {:>14.2} {:>14.2} {:>7.1}%",
+                    extension,
+                    totals.entries,
+                    mib(totals.size),
+                    mib(totals.stored),
+                    totals.ratio() * 100.
+                )
+            });
+            println!();
+            println!("compressed size / size");
+            let widest = histogram.iter().copied().max().unwrap_or(0).max(1);
+            histogram.iter().enumerate().for_each(|(bucket, count)| {
+                println!(
+                    "{:>3}-{:<3}% {:>8} {}",
+                    bucket * 100 / HISTOGRAM_BUCKETS,
+                    (bucket + 1) * 100 / HISTOGRAM_BUCKETS,
+                    count,
+                    "#".repeat(count * 40 / widest)
+                )
+            });
+            println!("uncompressed {uncompressed:>6}");
+        })
+        .with_context(|| format!("collecting stats of [{}]", archive_path.display()))
+}
@@ -0,0 +1,89 @@
+//! decompresses every entry and checks it comes out as big as the archive says it should - archives cut short
+//! by an interrupted install are a frequent cause of crashes which are very hard to track down otherwise
+
+use {
+    crate::archive::{BethesdaArchive, Entry},
+    anyhow::{Context, Result},
+    std::{
+        io::Write,
+        panic::{catch_unwind, AssertUnwindSafe},
+        path::Path,
+    },
+    tap::prelude::*,
+};
+
+/// texture entries are written out with a synthesized dds header in front of them
+const DDS_HEADER_LENGTH: u64 = 4 + 124 + 20;
+
+#[derive(Default)]
+struct CountingSink(u64);
+
+impl Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn verify_entry(archive: &BethesdaArchive, entry: &Entry<'_>) -> Result<()> {
+    archive.info(entry).and_then(|info| {
+        // ba2 panics on some malformed data instead of returning an error
+        catch_unwind(AssertUnwindSafe(|| {
+            CountingSink::default().pipe(|mut sink| archive.extract(&entry.key, &mut sink).map(|_| sink.0))
+        }))
+        .map_err(|panic| {
+            anyhow::anyhow!(
+                "panicked: {}",
+                panic
+                    .downcast_ref::<String>()
+                    .map(String::as_str)
+                    .or_else(|| panic.downcast_ref::<&str>().copied())
+                    .unwrap_or("unknown reason")
+            )
+        })
+        .and_then(std::convert::identity)
+        .and_then(|written| {
+            let expected = info.size
+                + match info.texture.is_some() {
+                    true => DDS_HEADER_LENGTH,
+                    false => 0,
+                };
+            (written == expected)
+                .then_some(())
+                .with_context(|| format!("decompressed to {written} bytes, expected {expected}"))
+        })
+    })
+}
+
+/// prints every corrupt entry, fails when there are any
+pub fn verify(archive_path: &Path) -> Result<()> {
+    BethesdaArchive::open(archive_path).and_then(|archive| {
+        archive
+            .entries()
+            .pipe(|entries| {
+                let count = entries.len();
+                entries
+                    .iter()
+                    .filter_map(|entry| {
+                        verify_entry(&archive, entry)
+                            .err()
+                            .map(|error| (entry.path.clone(), error))
+                    })
+                    .inspect(|(path, error)| println!("CORRUPT {path}: {error:#}"))
+                    .count()
+                    .pipe(|corrupt| (count, corrupt))
+            })
+            .pipe(|(count, corrupt)| match corrupt {
+                0 => {
+                    println!("all {count} entries are fine");
+                    Ok(())
+                }
+                corrupt => Err(anyhow::anyhow!("{corrupt} out of {count} entries are corrupt")),
+            })
+            .with_context(|| format!("verifying [{}]", archive_path.display()))
+    })
+}
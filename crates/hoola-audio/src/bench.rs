@@ -0,0 +1,271 @@
+use {
+    crate::{convert_to_mp3, detect_container, DecodeOptions, FormatReaderIterator, LoadedTrack, TrackFilters},
+    anyhow::{Context, Result},
+    clap::{Args, ValueEnum},
+    itertools::Itertools,
+    std::{
+        ops::Add,
+        path::{Path, PathBuf},
+        time::{Duration, Instant},
+    },
+    tap::prelude::*,
+    tracing::{info, info_span, instrument},
+};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BenchTarget {
+    Wav,
+    Ogg,
+    Mp3,
+}
+
+impl BenchTarget {
+    fn extension(self) -> &'static str {
+        match self {
+            BenchTarget::Wav => "wav",
+            BenchTarget::Ogg => "ogg",
+            BenchTarget::Mp3 => "mp3",
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct BenchArgs {
+    /// directory containing the audio files used for benchmarking (not recursive)
+    pub corpus: PathBuf,
+    /// format each file is converted to
+    #[arg(long, value_enum, default_value_t = BenchTarget::Ogg)]
+    pub target: BenchTarget,
+    /// how many times the whole corpus is converted
+    #[arg(long, default_value_t = 3)]
+    pub iterations: usize,
+    /// target sample frequency (defaults to source frequency)
+    #[arg(long)]
+    pub target_frequency: Option<u32>,
+    /// target bitrate in kbps (mp3 only, defaults to 192)
+    #[arg(long)]
+    pub target_bitrate: Option<u32>,
+    #[command(flatten)]
+    pub filters: TrackFilters,
+    #[command(flatten)]
+    pub decode: DecodeOptions,
+}
+
+/// time spent in each stage of a conversion
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StageTimings {
+    pub decode: Duration,
+    pub resample: Duration,
+    pub filter: Duration,
+    pub encode: Duration,
+    /// mp3 goes through the same streaming pipeline as the installer, its stages run interleaved and are timed as a whole
+    pub streamed: Duration,
+}
+
+impl StageTimings {
+    pub fn total(&self) -> Duration {
+        self.decode + self.resample + self.filter + self.encode + self.streamed
+    }
+}
+
+impl Add for StageTimings {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            decode: self.decode + rhs.decode,
+            resample: self.resample + rhs.resample,
+            filter: self.filter + rhs.filter,
+            encode: self.encode + rhs.encode,
+            streamed: self.streamed + rhs.streamed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConversionStats {
+    pub timings: StageTimings,
+    /// duration of the decoded source audio
+    pub audio: Duration,
+    pub input_bytes: u64,
+}
+
+impl Add for ConversionStats {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            timings: self.timings + rhs.timings,
+            audio: self.audio + rhs.audio,
+            input_bytes: self.input_bytes + rhs.input_bytes,
+        }
+    }
+}
+
+fn duration_of(track: &LoadedTrack) -> Duration {
+    Duration::from_secs_f64(track.frame_count() as f64 / track.sample_rate as f64)
+}
+
+fn timed<T>(f: impl FnOnce() -> Result<T>) -> Result<(T, Duration)> {
+    let started = Instant::now();
+    f().map(|output| (output, started.elapsed()))
+}
+
+impl BenchArgs {
+    fn load(&self, from: &Path) -> Result<LoadedTrack> {
+        FormatReaderIterator::from_file(from, self.decode).and_then(LoadedTrack::from_reader)
+    }
+
+    /// wav and ogg are written from a fully loaded track, so each stage is timed on its own
+    fn convert_staged(&self, from: &Path, to: &Path, encode: impl FnOnce(&LoadedTrack, &Path) -> Result<()>) -> Result<(StageTimings, Duration)> {
+        let (track, decode) = timed(|| self.load(from)).context("decoding")?;
+        let audio = duration_of(&track);
+        let (track, resample) = timed(|| match self.target_frequency {
+            Some(target_frequency) => track.resample_if_needed(target_frequency),
+            None => Ok(track),
+        })
+        .context("resampling")?;
+        let (track, filter) = timed(|| Ok(self.filters.apply(track)))?;
+        let ((), encode) = timed(|| encode(&track, to)).context("encoding")?;
+        Ok((
+            StageTimings {
+                decode,
+                resample,
+                filter,
+                encode,
+                ..Default::default()
+            },
+            audio,
+        ))
+    }
+
+    fn convert_streamed(&self, from: &Path, to: &Path) -> Result<(StageTimings, Duration)> {
+        // the source is decoded once more outside of the timing, only to know how long it is
+        let audio = self
+            .load(from)
+            .context("measuring source duration")
+            .map(|track| duration_of(&track))?;
+        let ((), streamed) =
+            timed(|| convert_to_mp3(from, to, self.target_bitrate, self.target_frequency, None, &self.filters, self.decode)).context("converting")?;
+        Ok((
+            StageTimings {
+                streamed,
+                ..Default::default()
+            },
+            audio,
+        ))
+    }
+
+    #[instrument(skip(self), fields(from=?from))]
+    fn convert_once(&self, from: &Path, to: &Path) -> Result<ConversionStats> {
+        let (timings, audio) = match self.target {
+            BenchTarget::Mp3 => self.convert_streamed(from, to),
+            BenchTarget::Wav => self.convert_staged(from, to, LoadedTrack::write_wav),
+            BenchTarget::Ogg => self.convert_staged(from, to, LoadedTrack::write_ogg),
+        }?;
+        std::fs::metadata(from)
+            .context("reading source metadata")
+            .map(|metadata| ConversionStats {
+                timings,
+                audio,
+                input_bytes: metadata.len(),
+            })
+    }
+
+    fn corpus(&self) -> Result<Vec<PathBuf>> {
+        std::fs::read_dir(&self.corpus)
+            .with_context(|| format!("reading corpus directory [{:?}]", self.corpus))?
+            .map(|entry| {
+                entry
+                    .context("reading directory entry")
+                    .map(|entry| entry.path())
+            })
+            .filter_ok(|path| path.is_file())
+            .map(|path| path.and_then(|path| detect_container(&path).map(|container| container.map(|_| path))))
+            .filter_map(Result::transpose)
+            .collect::<Result<Vec<_>>>()
+            .map(|paths| paths.tap_mut(|paths| paths.sort()))
+            .and_then(|paths| match paths.is_empty() {
+                true => Err(anyhow::anyhow!("no audio files found in [{:?}]", self.corpus)),
+                false => Ok(paths),
+            })
+    }
+
+    #[instrument(skip(self))]
+    pub fn run(&self) -> Result<()> {
+        let corpus = self.corpus().context("collecting corpus")?;
+        let output_dir = tempfile::tempdir().context("creating output directory")?;
+        let started = Instant::now();
+        let stats = (0..self.iterations)
+            .map(|iteration| {
+                let _span = info_span!("iteration", %iteration).entered();
+                corpus
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, from)| {
+                        output_dir
+                            .path()
+                            .join(format!("{idx}.{}", self.target.extension()))
+                            .pipe(|to| self.convert_once(from, &to))
+                            .with_context(|| format!("converting [{from:?}]"))
+                    })
+                    .fold_ok(ConversionStats::default(), Add::add)
+                    .tap_ok(|stats| info!(%iteration, elapsed=?stats.timings.total(), "iteration finished"))
+            })
+            .fold_ok(ConversionStats::default(), Add::add)?;
+        let wall = started.elapsed();
+        BenchReport {
+            files: corpus.len(),
+            iterations: self.iterations,
+            wall,
+            stats,
+        }
+        .pipe(|report| println!("{report}"))
+        .pipe(Ok)
+    }
+}
+
+pub struct BenchReport {
+    pub files: usize,
+    pub iterations: usize,
+    pub wall: Duration,
+    pub stats: ConversionStats,
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            files,
+            iterations,
+            wall,
+            stats,
+        } = self;
+        let conversions = (files * iterations).max(1) as u32;
+        let wall_secs = wall.as_secs_f64().max(f64::EPSILON);
+        writeln!(f, "converted [{files}] files [{iterations}] times in {wall:.2?}")?;
+        [
+            ("decode", stats.timings.decode),
+            ("resample", stats.timings.resample),
+            ("filter", stats.timings.filter),
+            ("encode", stats.timings.encode),
+            ("streamed", stats.timings.streamed),
+        ]
+        .into_iter()
+        .filter(|(_, total)| !total.is_zero())
+        .try_for_each(|(stage, total)| {
+            writeln!(
+                f,
+                "  {stage:<10} total {total:>10.2?}  per file {:>10.2?}  ({:>5.1}%)",
+                total / conversions,
+                100. * total.as_secs_f64() / stats.timings.total().as_secs_f64().max(f64::EPSILON),
+            )
+        })?;
+        write!(
+            f,
+            "throughput: {:.1}x realtime, {:.2} MiB/s, {:.2} files/s",
+            stats.audio.as_secs_f64() / wall_secs,
+            stats.input_bytes as f64 / (1024. * 1024.) / wall_secs,
+            (files * iterations) as f64 / wall_secs,
+        )
+    }
+}
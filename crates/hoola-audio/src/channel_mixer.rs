@@ -0,0 +1,135 @@
+use {
+    crate::Mp3TargetChannelMode,
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    std::f32::consts::FRAC_1_SQRT_2,
+    symphonia::core::audio::Channels,
+    tap::prelude::*,
+};
+
+/// mixes interleaved samples between channel layouts, taking speaker positions into account
+/// (ITU-R BS.775: center and surround channels are folded into the front pair at -3dB, LFE is dropped)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelMixer {
+    /// `weights[output][input]`
+    weights: Vec<Vec<f32>>,
+    input_count: usize,
+    /// the weights of an output add up to more than 1, so loud passages can go past full scale
+    limited: bool,
+}
+
+/// the limiter only bends peaks above this, quieter samples pass through untouched
+const LIMITER_THRESHOLD: f32 = 0.9;
+
+/// soft knee peak limiter, approaches full scale without ever going past it
+fn limit(sample: f32) -> f32 {
+    const HEADROOM: f32 = 1. - LIMITER_THRESHOLD;
+    match sample.abs() {
+        magnitude if magnitude > LIMITER_THRESHOLD => (LIMITER_THRESHOLD + HEADROOM * ((magnitude - LIMITER_THRESHOLD) / HEADROOM).tanh()).copysign(sample),
+        _ => sample,
+    }
+}
+
+/// single-bit channel flags present in the layout, in interleaving order
+fn positions(channels: Channels) -> impl Iterator<Item = Channels> {
+    (0..u32::BITS)
+        .map(|bit| 1u32 << bit)
+        .filter(move |bit| channels.bits() & bit != 0)
+        .map(Channels::from_bits_truncate)
+}
+
+/// contribution of a speaker position to the `[left, right]` output
+fn stereo_weights(position: Channels) -> [f32; 2] {
+    const HALF_POWER: f32 = FRAC_1_SQRT_2;
+    if position == Channels::FRONT_LEFT {
+        [1., 0.]
+    } else if position == Channels::FRONT_RIGHT {
+        [0., 1.]
+    } else if position == Channels::LFE1 {
+        [0., 0.]
+    } else if [Channels::REAR_LEFT, Channels::SIDE_LEFT, Channels::FRONT_LEFT_CENTRE].contains(&position) {
+        [HALF_POWER, 0.]
+    } else if [Channels::REAR_RIGHT, Channels::SIDE_RIGHT, Channels::FRONT_RIGHT_CENTRE].contains(&position) {
+        [0., HALF_POWER]
+    } else {
+        // center channels and anything more exotic end up in the middle
+        [HALF_POWER, HALF_POWER]
+    }
+}
+
+impl ChannelMixer {
+    pub fn new(source: Channels, target: Mp3TargetChannelMode) -> Result<Self> {
+        let input_count = source.count();
+        let stereo = match input_count {
+            0 => anyhow::bail!("source has 0 channels"),
+            // a single channel is spread evenly, regardless of the position it is tagged with
+            1 => vec![vec![1.], vec![1.]],
+            _ => positions(source)
+                .map(stereo_weights)
+                .collect_vec()
+                .pipe(|weights| (0..2).map(|output| weights.iter().map(|weights| weights[output]).collect_vec()))
+                .collect_vec(),
+        };
+        let weights = match target {
+            Mp3TargetChannelMode::Stereo => stereo,
+            Mp3TargetChannelMode::Mono => (0..input_count)
+                .map(|input| stereo.iter().map(|output| output[input]).sum::<f32>() / 2.)
+                .collect_vec()
+                .pipe(|mono| vec![mono]),
+        };
+        let limited = weights.iter().any(|output| output.iter().sum::<f32>() > 1.);
+        Ok(Self { weights, input_count, limited })
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.weights.len()
+    }
+
+    fn is_identity(&self) -> bool {
+        self.input_count == self.output_count()
+            && self.weights.iter().enumerate().all(|(output, weights)| {
+                weights
+                    .iter()
+                    .enumerate()
+                    .all(|(input, weight)| *weight == if input == output { 1. } else { 0. })
+            })
+    }
+
+    pub fn mix_interleaved(&self, interleaved: &[f32]) -> Result<Vec<f32>> {
+        if interleaved.len() % self.input_count != 0 {
+            anyhow::bail!("interleaved data does not contain all channels");
+        }
+        if self.is_identity() {
+            return Ok(interleaved.to_vec());
+        }
+        interleaved
+            .chunks_exact(self.input_count)
+            .flat_map(|frame| {
+                self.weights.iter().map(move |weights| {
+                    frame
+                        .iter()
+                        .zip(weights)
+                        .map(|(sample, weight)| sample * weight)
+                        .sum::<f32>()
+                        .pipe(|mixed| match self.limited {
+                            true => limit(mixed),
+                            false => mixed,
+                        })
+                })
+            })
+            .collect_vec()
+            .pipe(Ok)
+    }
+}
+
+impl Mp3TargetChannelMode {
+    /// the closest supported mode, surround sources are mixed down to stereo
+    pub fn for_source(count: usize) -> Result<Self> {
+        match count {
+            0 => Err(anyhow::anyhow!("source has 0 channels")),
+            1 => Ok(Self::Mono),
+            _ => Ok(Self::Stereo),
+        }
+        .context("deducing channel mode")
+    }
+}
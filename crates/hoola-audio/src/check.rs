@@ -0,0 +1,152 @@
+use {
+    crate::{detect_container, DecodeOptions, FormatReaderIterator, PatchedFrames},
+    anyhow::{Context, Result},
+    clap::Args,
+    itertools::Itertools,
+    std::path::{Path, PathBuf},
+    tap::prelude::*,
+    tracing::{info, instrument, warn},
+};
+
+#[derive(Args, Clone, Debug)]
+pub struct CheckArgs {
+    /// file or directory (searched recursively) to check
+    pub path: PathBuf,
+    /// ratio of full-scale samples above which a file is reported as clipped
+    #[arg(long, default_value_t = 0.01)]
+    pub clipping_threshold: f64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CheckReport {
+    pub frames: u64,
+    pub samples: u64,
+    /// length declared by the container
+    pub declared_frames: Option<u64>,
+    /// packets which could not be decoded (or went missing while demuxing)
+    pub corrupt: PatchedFrames,
+    /// the error which stopped the decoding before the end of the stream
+    pub abrupt_end: Option<String>,
+    pub non_finite: u64,
+    pub clipped: u64,
+}
+
+impl CheckReport {
+    pub fn problems(&self, clipping_threshold: f64) -> Vec<String> {
+        let Self {
+            frames,
+            samples,
+            declared_frames,
+            corrupt,
+            abrupt_end,
+            non_finite,
+            clipped,
+        } = self;
+        [
+            (corrupt.packets > 0).then(|| format!("[{}] corrupt packets ([{}] frames)", corrupt.packets, corrupt.frames)),
+            abrupt_end
+                .as_ref()
+                .map(|reason| format!("stream ended abruptly: {reason}")),
+            declared_frames
+                .filter(|declared| declared > frames)
+                .map(|declared| format!("truncated: decoded [{frames}] out of [{declared}] frames")),
+            (*frames == 0 && abrupt_end.is_none()).then(|| "stream contains no audio".to_string()),
+            (*non_finite > 0).then(|| format!("[{non_finite}] NaN/infinite samples")),
+            (*samples > 0)
+                .then(|| *clipped as f64 / *samples as f64)
+                .filter(|ratio| *ratio > clipping_threshold)
+                .map(|ratio| format!("[{clipped}] clipped samples ({:.2}%)", ratio * 100.)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+#[instrument(level = "DEBUG", ret)]
+pub fn check_file(path: &Path) -> Result<CheckReport> {
+    FormatReaderIterator::from_file(path, DecodeOptions { lenient: true }).map(|mut reader| {
+        let mut report = CheckReport {
+            declared_frames: reader.declared_frames(),
+            ..Default::default()
+        };
+        // samples are compared to the positive full scale, the negative one is always further away
+        let full_scale = reader.full_scale();
+        for chunk in reader.by_ref() {
+            match chunk {
+                Ok(chunk) => {
+                    let samples = chunk.sample_buffer.samples();
+                    report.frames += chunk.single_channel_length() as u64;
+                    report.samples += samples.len() as u64;
+                    report.non_finite += samples.iter().filter(|sample| !sample.is_finite()).count() as u64;
+                    report.clipped += samples
+                        .iter()
+                        .filter(|sample| sample.abs() >= full_scale)
+                        .count() as u64;
+                }
+                Err(e) => {
+                    report.abrupt_end = Some(format!("{e:#}"));
+                    break;
+                }
+            }
+        }
+        report.tap_mut(|report| report.corrupt = reader.patched())
+    })
+}
+
+fn audio_files(path: &Path) -> Result<Vec<PathBuf>> {
+    match path.is_dir() {
+        false => Ok(vec![path.to_owned()]),
+        true => walkdir::WalkDir::new(path)
+            .follow_links(false)
+            .into_iter()
+            .map(|entry| {
+                entry
+                    .context("reading directory entry")
+                    .map(|entry| entry.into_path())
+            })
+            .filter_ok(|path| path.is_file())
+            .map(|path| path.and_then(|path| detect_container(&path).map(|container| container.map(|_| path))))
+            .filter_map(Result::transpose)
+            .collect::<Result<Vec<_>>>()
+            .map(|paths| paths.tap_mut(|paths| paths.sort())),
+    }
+}
+
+impl CheckArgs {
+    #[instrument(skip(self), fields(path=?self.path))]
+    pub fn run(&self) -> Result<()> {
+        let files = audio_files(&self.path).context("collecting audio files")?;
+        let broken = files
+            .iter()
+            .filter_map(|file| {
+                check_file(file)
+                    .map(|report| report.problems(self.clipping_threshold))
+                    .unwrap_or_else(|e| vec![format!("could not be opened: {e:#}")])
+                    .pipe(|problems| match problems.is_empty() {
+                        true => {
+                            info!(?file, "ok");
+                            None
+                        }
+                        false => {
+                            warn!(?file, "found [{}] problems", problems.len());
+                            println!(
+                                "{}\n{}",
+                                file.display(),
+                                problems
+                                    .iter()
+                                    .map(|problem| format!("  - {problem}"))
+                                    .join("\n")
+                            );
+                            Some(file)
+                        }
+                    })
+            })
+            .count();
+        println!("checked [{}] files, [{broken}] have problems", files.len());
+        match broken {
+            0 => Ok(()),
+            broken => Err(anyhow::anyhow!("[{broken}] files are corrupt")),
+        }
+    }
+}
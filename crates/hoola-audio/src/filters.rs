@@ -0,0 +1,158 @@
+use {
+    super::LoadedTrack,
+    clap::Args,
+    std::time::Duration,
+    tap::prelude::*,
+    tracing::{debug, instrument},
+};
+
+/// optional processing stages applied to a fully loaded track before it's encoded
+#[derive(Args, Clone, Debug, Default, PartialEq)]
+pub struct TrackFilters {
+    /// trims leading and trailing samples quieter than the threshold (in dBFS, defaults to -60)
+    #[arg(long, num_args = 0..=1, default_missing_value = "-60", allow_negative_numbers = true)]
+    pub trim_silence: Option<f32>,
+    /// linear fade in duration (in milliseconds)
+    #[arg(long)]
+    pub fade_in: Option<u64>,
+    /// linear fade out duration (in milliseconds)
+    #[arg(long)]
+    pub fade_out: Option<u64>,
+}
+
+impl TrackFilters {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    #[instrument(level = "DEBUG", skip(track))]
+    pub fn apply(&self, track: LoadedTrack) -> LoadedTrack {
+        let Self {
+            trim_silence,
+            fade_in,
+            fade_out,
+        } = self;
+        track
+            .pipe(|track| match trim_silence {
+                Some(threshold) => track.trim_silence(decibels_to_amplitude(*threshold)),
+                None => track,
+            })
+            .pipe(|track| match fade_in {
+                Some(fade_in) => track.fade_in(Duration::from_millis(*fade_in)),
+                None => track,
+            })
+            .pipe(|track| match fade_out {
+                Some(fade_out) => track.fade_out(Duration::from_millis(*fade_out)),
+                None => track,
+            })
+    }
+}
+
+pub fn decibels_to_amplitude(decibels: f32) -> f32 {
+    10f32.powf(decibels / 20.)
+}
+
+impl LoadedTrack {
+    pub fn frame_count(&self) -> usize {
+        self.channels
+            .first()
+            .map(|channel| channel.len())
+            .unwrap_or(0)
+    }
+
+    fn frames_for(&self, duration: Duration) -> usize {
+        ((duration.as_secs_f64() * self.sample_rate as f64).round() as usize).min(self.frame_count())
+    }
+
+    fn is_silent_frame(&self, frame: usize, threshold: f32) -> bool {
+        self.channels.iter().all(|channel| {
+            channel
+                .get(frame)
+                .map(|sample| sample.abs() < threshold)
+                .unwrap_or(true)
+        })
+    }
+
+    /// removes leading and trailing frames where every channel stays below the (linear) threshold
+    #[instrument(level = "DEBUG", ret)]
+    pub fn trim_silence(mut self, threshold: f32) -> Self {
+        let frames = self.frame_count();
+        let start = (0..frames)
+            .find(|frame| !self.is_silent_frame(*frame, threshold))
+            .unwrap_or(frames);
+        let end = (start..frames)
+            .rev()
+            .find(|frame| !self.is_silent_frame(*frame, threshold))
+            .map(|last| last + 1)
+            .unwrap_or(start);
+        debug!(%start, %end, %frames, "trimming silence");
+        self.channels.iter_mut().for_each(|channel| {
+            channel.truncate(end);
+            channel.drain(..start.min(channel.len()));
+        });
+        self
+    }
+
+    #[instrument(level = "DEBUG", ret)]
+    pub fn fade_in(mut self, duration: Duration) -> Self {
+        let length = self.frames_for(duration);
+        self.channels.iter_mut().for_each(|channel| {
+            channel
+                .iter_mut()
+                .take(length)
+                .enumerate()
+                .for_each(|(idx, sample)| *sample *= idx as f32 / length as f32)
+        });
+        self
+    }
+
+    #[instrument(level = "DEBUG", ret)]
+    pub fn fade_out(mut self, duration: Duration) -> Self {
+        let length = self.frames_for(duration);
+        self.channels.iter_mut().for_each(|channel| {
+            channel
+                .iter_mut()
+                .rev()
+                .take(length)
+                .enumerate()
+                .for_each(|(idx, sample)| *sample *= idx as f32 / length as f32)
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::HeaplessVecTryCollectExt};
+
+    fn track(samples: &[f32]) -> LoadedTrack {
+        LoadedTrack {
+            channels: [samples.to_vec(), samples.to_vec()]
+                .into_iter()
+                .pipe(crate::ChanVec::try_from_iter)
+                .unwrap(),
+            sample_rate: 10,
+        }
+    }
+
+    #[test]
+    fn test_trim_silence() {
+        let trimmed = track(&[0., 0.0001, 0.5, 0., -0.5, 0.0001, 0.]).trim_silence(decibels_to_amplitude(-60.));
+        assert_eq!(trimmed.channels[0], vec![0.5, 0., -0.5]);
+        assert_eq!(trimmed.channels[1], vec![0.5, 0., -0.5]);
+    }
+
+    #[test]
+    fn test_trim_silence_all_silent() {
+        assert_eq!(track(&[0.; 5]).trim_silence(0.1).frame_count(), 0);
+    }
+
+    #[test]
+    fn test_fades() {
+        // 10Hz sample rate, so 400ms is 4 frames
+        let faded = track(&[1.; 10])
+            .fade_in(Duration::from_millis(400))
+            .fade_out(Duration::from_millis(400));
+        assert_eq!(faded.channels[0], vec![0., 0.25, 0.5, 0.75, 1., 1., 0.75, 0.5, 0.25, 0.]);
+    }
+}
@@ -10,20 +10,21 @@ use {
     rubato::{FastFixedIn, FftFixedOut, PolynomialDegree, Resampler},
     std::{
         convert::identity,
-        io::{BufWriter, Write},
+        io::{BufWriter, Read, Write},
         num::{NonZeroU32, NonZeroU8, NonZeroUsize},
         ops::Not,
         path::{Path, PathBuf},
     },
     symphonia::core::{
-        audio::{SampleBuffer, SignalSpec},
+        audio::{AudioBuffer, SampleBuffer, SignalSpec},
         codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL},
         formats::{FormatReader, Packet},
         io::MediaSourceStream,
         probe::{Hint, ProbeResult},
+        sample::SampleFormat,
     },
     tap::prelude::*,
-    tracing::{debug, info_span, instrument, trace, warn},
+    tracing::{debug, info, info_span, instrument, trace, warn},
     vorbis_rs::VorbisEncoderBuilder,
 };
 
@@ -31,6 +32,16 @@ pub mod resampler;
 
 pub mod chunk_while;
 
+pub mod filters;
+
+pub mod bench;
+
+pub mod check;
+
+pub mod channel_mixer;
+
+pub use {channel_mixer::ChannelMixer, filters::TrackFilters};
+
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
@@ -51,15 +62,62 @@ pub enum Commands {
     ConvertStereoMP3ToMono {
         #[command(flatten)]
         context: FromTo,
+        #[command(flatten)]
+        filters: TrackFilters,
+        #[command(flatten)]
+        decode: DecodeOptions,
+    },
+    ConvertOGGToWAV {
+        #[command(flatten)]
+        context: FromTo,
+        #[command(flatten)]
+        filters: TrackFilters,
+        #[command(flatten)]
+        decode: DecodeOptions,
+    },
+    ConvertWAVToOGG {
+        #[command(flatten)]
+        context: FromTo,
+        #[command(flatten)]
+        filters: TrackFilters,
+        #[command(flatten)]
+        decode: DecodeOptions,
+        /// target sample frequency (defaults to source frequency)
+        #[arg(long)]
+        target_frequency: Option<u32>,
+    },
+    ConvertWAVToMP3 {
+        #[command(flatten)]
+        context: FromTo,
+        #[command(flatten)]
+        filters: TrackFilters,
+        #[command(flatten)]
+        decode: DecodeOptions,
+        /// target sample frequency (defaults to source frequency)
+        #[arg(long)]
+        target_frequency: Option<u32>,
+        /// target bitrate in kbps (defaults to 192)
+        #[arg(long)]
+        target_bitrate: Option<u32>,
+        /// downmix the output to mono
+        #[arg(long)]
+        mono: bool,
     },
-    ConvertOGGToWAV(FromTo),
     ResampleOGG {
         #[command(flatten)]
         context: FromTo,
+        #[command(flatten)]
+        filters: TrackFilters,
+        #[command(flatten)]
+        decode: DecodeOptions,
         /// target sample frequency
         #[arg(long)]
         target_frequency: u32,
     },
+    /// converts every file in a corpus directory repeatedly and reports per-stage timings and throughput
+    Bench(bench::BenchArgs),
+    /// fully decodes a file (or every audio file in a directory) and reports corruption without writing any output
+    Check(check::CheckArgs),
 }
 
 impl Commands {
@@ -68,18 +126,80 @@ impl Commands {
         debug!("debug logging on");
         let _span = info_span!("running", ?command).entered();
         match command {
-            Commands::ConvertStereoMP3ToMono { context: FromTo { from, to } } => {
-                convert_to_mp3(&from, &to, None, Some(44100), Some(Mp3TargetChannelMode::Mono))
-            }
-            Commands::ConvertOGGToWAV(FromTo { from, to }) => convert_to_wav(&from, &to, None),
+            Commands::ConvertStereoMP3ToMono {
+                context: FromTo { from, to },
+                filters,
+                decode,
+            } => convert_to_mp3(&from, &to, None, Some(44100), Some(Mp3TargetChannelMode::Mono), &filters, decode),
+            Commands::ConvertOGGToWAV {
+                context: FromTo { from, to },
+                filters,
+                decode,
+            } => convert_to_wav(&from, &to, None, &filters, decode),
+            Commands::ConvertWAVToOGG {
+                context: FromTo { from, to },
+                filters,
+                decode,
+                target_frequency,
+            } => convert_to_ogg(&from, &to, target_frequency, &filters, decode),
+            Commands::ConvertWAVToMP3 {
+                context: FromTo { from, to },
+                filters,
+                decode,
+                target_frequency,
+                target_bitrate,
+                mono,
+            } => convert_to_mp3(
+                &from,
+                &to,
+                target_bitrate,
+                target_frequency,
+                mono.then_some(Mp3TargetChannelMode::Mono),
+                &filters,
+                decode,
+            ),
             Commands::ResampleOGG {
                 context: FromTo { from, to },
+                filters,
+                decode,
                 target_frequency,
-            } => resample_ogg(&from, &to, target_frequency),
+            } => convert_to_ogg(&from, &to, Some(target_frequency), &filters, decode),
+            Commands::Bench(bench) => bench.run(),
+            Commands::Check(check) => check.run(),
         }
     }
 }
 
+#[derive(Args, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    /// replace undecodable frames with silence of equivalent duration instead of skipping them,
+    /// so that the duration of slightly corrupt sources is preserved
+    #[arg(long)]
+    pub lenient: bool,
+}
+
+/// a longer gap is not lost audio but a corrupt timestamp (or duration), patching it would allocate the whole gap at once
+const MAX_PATCHED_SECONDS: u64 = 5;
+
+/// frames of silence for a gap, failing when the gap is too long to be real
+fn patched_gap(frames: u64, spec: &SignalSpec) -> Result<u64> {
+    match frames > MAX_PATCHED_SECONDS * spec.rate as u64 {
+        true => bail!(
+            "refusing to patch a gap of [{frames}] frames ([{:.1}s], at most [{MAX_PATCHED_SECONDS}s] is patched), the stream timestamps are most likely \
+             corrupt",
+            frames as f64 / spec.rate as f64
+        ),
+        false => Ok(frames),
+    }
+}
+
+/// frames replaced with silence when decoding in lenient mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PatchedFrames {
+    pub packets: usize,
+    pub frames: u64,
+}
+
 #[derive(derivative::Derivative)]
 #[derivative(Debug)]
 pub struct FormatReaderIterator {
@@ -88,23 +208,30 @@ pub struct FormatReaderIterator {
     #[derivative(Debug = "ignore")]
     probe_result: ProbeResult,
     selected_track: u32,
+    options: DecodeOptions,
+    /// spec of the last successfully decoded packet, used for generating silence
+    last_spec: Option<SignalSpec>,
+    /// timestamp at which the next packet is expected to start
+    next_timestamp: Option<u64>,
+    /// packet held back while the gap before it is patched over
+    #[derivative(Debug = "ignore")]
+    pending_packet: Option<Packet>,
+    patched: PatchedFrames,
 }
 
 impl FormatReaderIterator {
     #[instrument]
-    fn from_file(path: &Path) -> Result<Self> {
+    fn from_file(path: &Path, options: DecodeOptions) -> Result<Self> {
         let file = std::fs::File::open(path).with_context(|| format!("opening file at [{path:?}]"))?;
+        let hint = probe_hint(path).context("building probe hint")?;
         let from = MediaSourceStream::new(Box::new(file), Default::default());
-        let mut hint = Hint::new();
-        path.extension()
-            .map(|extension| hint.with_extension(&extension.to_string_lossy()));
         let probe_result = symphonia::default::get_probe()
             .format(&hint, from, &Default::default(), &Default::default())
             .context("probing format")?;
-        Self::new(probe_result).context("instantiating the decoder iterator")
+        Self::new(probe_result, options).context("instantiating the decoder iterator")
     }
     #[instrument(skip(probe_result), ret, level = "DEBUG")]
-    fn new(probe_result: ProbeResult) -> Result<Self> {
+    fn new(probe_result: ProbeResult, options: DecodeOptions) -> Result<Self> {
         let track = probe_result
             .format
             .tracks()
@@ -126,8 +253,109 @@ impl FormatReaderIterator {
             selected_track: track.id,
             probe_result,
             decoder,
+            options,
+            last_spec: None,
+            next_timestamp: None,
+            pending_packet: None,
+            patched: Default::default(),
         })
     }
+
+    pub fn patched(&self) -> PatchedFrames {
+        self.patched
+    }
+
+    /// length of the track as declared by the container, if known
+    pub fn declared_frames(&self) -> Option<u64> {
+        self.decoder.codec_params().n_frames
+    }
+
+    /// largest positive sample the source format can hold, integer pcm stops one step short of 1.0
+    pub fn full_scale(&self) -> f32 {
+        let params = self.decoder.codec_params();
+        match params.sample_format {
+            Some(SampleFormat::F32 | SampleFormat::F64) => 1.,
+            _ => params
+                .bits_per_sample
+                .filter(|bits| (2..=32).contains(bits))
+                .map(|bits| 1. - 1. / (1u64 << (bits - 1)) as f32)
+                .unwrap_or(1.),
+        }
+    }
+}
+
+/// container kinds that can be recognized by their magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceContainer {
+    /// RIFF/WAVE, including 24-bit and WAVE_FORMAT_EXTENSIBLE PCM
+    Wav,
+    Ogg,
+    Mp3,
+}
+
+impl SourceContainer {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SourceContainer::Wav => "wav",
+            SourceContainer::Ogg => "ogg",
+            SourceContainer::Mp3 => "mp3",
+        }
+    }
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            SourceContainer::Wav => "audio/wav",
+            SourceContainer::Ogg => "audio/ogg",
+            SourceContainer::Mp3 => "audio/mpeg",
+        }
+    }
+    pub fn from_magic(header: &[u8]) -> Option<Self> {
+        match header {
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some(Self::Wav),
+            [b'O', b'g', b'g', b'S', ..] => Some(Self::Ogg),
+            [b'I', b'D', b'3', ..] => Some(Self::Mp3),
+            // mpeg frame sync
+            [0xFF, second, ..] if second & 0xE0 == 0xE0 => Some(Self::Mp3),
+            _ => None,
+        }
+    }
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "wav" | "wave" => Some(Self::Wav),
+            "ogg" | "oga" => Some(Self::Ogg),
+            "mp3" => Some(Self::Mp3),
+            _ => None,
+        }
+    }
+}
+
+/// sources are often extracted to extension-less temp files, so the magic bytes take precedence over the extension
+#[instrument(level = "DEBUG", ret)]
+pub fn detect_container(path: &Path) -> Result<Option<SourceContainer>> {
+    let mut header = [0u8; 12];
+    std::fs::File::open(path)
+        .with_context(|| format!("opening file at [{path:?}]"))
+        .and_then(|mut file| file.read(&mut header).context("reading file header"))
+        .map(|read| {
+            SourceContainer::from_magic(&header[..read]).or_else(|| {
+                path.extension()
+                    .and_then(|extension| SourceContainer::from_extension(&extension.to_string_lossy()))
+            })
+        })
+}
+
+fn probe_hint(path: &Path) -> Result<Hint> {
+    detect_container(path).map(|container| {
+        Hint::new().tap_mut(|hint| match container {
+            Some(container) => {
+                hint.with_extension(container.extension())
+                    .mime_type(container.mime_type());
+            }
+            None => {
+                path.extension()
+                    .map(|extension| hint.with_extension(&extension.to_string_lossy().to_lowercase()));
+            }
+        })
+    })
 }
 
 #[instrument(skip_all, ret, level = "TRACE")]
@@ -185,6 +413,7 @@ impl FormatReaderIterator {
                         message => bail!("{message:#?}"),
                     },
                     symphonia::core::errors::Error::DecodeError(_) => {
+                        // in lenient mode the gap this leaves is patched over using the timestamp of the next packet
                         tracing::warn!("{e:#?}");
                         continue;
                     }
@@ -233,61 +462,108 @@ impl DecodedChunk {
         })
     }
 
+    /// interleaved samples mixed into the target layout, based on the speaker positions of the source
+    pub fn mix_to(&self, target: Mp3TargetChannelMode) -> Result<Vec<f32>> {
+        ChannelMixer::new(self.spec.channels, target)
+            .context("building channel mixer")
+            .and_then(|mixer| mixer.mix_interleaved(self.sample_buffer.samples()))
+    }
+
     #[instrument(level = "DEBUG")]
     pub fn upmix_to_stereo(&self) -> Result<Vec<f32>> {
-        let current_mode = Mp3TargetChannelMode::from_count(self.spec.channels.count()).context("checking current rate")?;
-        match current_mode {
-            Mp3TargetChannelMode::Mono => {
-                debug!("converting from mono to stereo makes no sense, but sure here you go");
-                self.sample_buffer
-                    .samples()
-                    .iter()
-                    .flat_map(|s| [*s, *s])
-                    .collect_vec()
-                    .pipe(Ok)
-            }
-            Mp3TargetChannelMode::Stereo => self.sample_buffer.samples().to_vec().pipe(Ok),
-        }
+        self.mix_to(Mp3TargetChannelMode::Stereo)
+            .tap_ok(|upmixed| debug!(upmixed_samples = upmixed.len()))
     }
     #[instrument(level = "DEBUG")]
     pub fn downmix_to_mono(&self) -> Result<Vec<f32>> {
-        let channel_count = self.spec.channels.count();
-        let mut buf: Vec<f32> = Vec::with_capacity(channel_count);
-        match channel_count {
-            0 => anyhow::bail!("track has 0 channels"),
-            1 => self
-                .sample_buffer
-                .samples()
-                .iter()
-                .copied()
-                .collect_vec()
-                .pipe(Ok),
-            more => self
-                .sample_buffer
-                .samples()
-                .iter()
-                .chunks(more)
-                .into_iter()
-                .map(|chunk| {
-                    buf.clear().pipe(|_| {
-                        chunk.collect_into(&mut buf).pipe(|chunk| {
-                            chunk
-                                .len()
-                                .eq(&channel_count)
-                                .then_some(chunk)
-                                .context("interleaved data does not contain all channels")
-                                .map(|chunk| {
-                                    chunk
-                                        .drain(..)
-                                        .map(|sample| sample / (channel_count as f32))
-                                        .sum::<f32>()
-                                })
+        self.mix_to(Mp3TargetChannelMode::Mono)
+            .tap_ok(|downmixed| debug!(downmixed_samples = downmixed.len()))
+    }
+}
+
+impl FormatReaderIterator {
+    fn silence_spec(&self) -> Result<SignalSpec> {
+        self.last_spec
+            .map(Ok)
+            .unwrap_or_else(|| {
+                let params = self.decoder.codec_params();
+                params
+                    .sample_rate
+                    .zip(params.channels)
+                    .map(|(rate, channels)| SignalSpec::new(rate, channels))
+                    .context("codec parameters do not specify sample rate and channels")
+            })
+            .context("deducing spec of the silence")
+    }
+
+    /// converts a packet duration (expressed in the track time base) into frames
+    fn duration_to_frames(&self, duration: u64, spec: &SignalSpec) -> u64 {
+        match self.decoder.codec_params().time_base {
+            Some(time_base) => time_base
+                .calc_time(duration)
+                .pipe(|time| ((time.seconds as f64 + time.frac) * spec.rate as f64).round() as u64),
+            None => duration,
+        }
+    }
+
+    #[instrument(level = "DEBUG", skip(self))]
+    fn silence(&mut self, duration: u64) -> Result<DecodedChunk> {
+        self.silence_spec().and_then(|spec| {
+            let frames = patched_gap(self.duration_to_frames(duration, &spec), &spec)?;
+            self.patched.packets += 1;
+            self.patched.frames += frames;
+            warn!(%frames, "patching over undecodable audio with silence");
+            AudioBuffer::<f32>::new(frames, spec)
+                .tap_mut(|silence| silence.render_silence(Some(frames as usize)))
+                .pipe(|silence| SampleBuffer::<f32>::new(frames, spec).tap_mut(|sample_buffer| sample_buffer.copy_interleaved_typed(&silence)))
+                .pipe(|sample_buffer| DecodedChunk { spec, sample_buffer })
+                .pipe(Ok)
+        })
+    }
+
+    /// in lenient mode a packet starting later than expected means packets were lost while demuxing
+    fn missing_before(&self, packet: &Packet) -> Option<u64> {
+        self.options
+            .lenient
+            .then_some(self.next_timestamp)
+            .flatten()
+            .and_then(|expected| packet.ts().checked_sub(expected))
+            .filter(|missing| *missing > 0)
+    }
+
+    fn decode_packet(&mut self, packet: Packet) -> Result<DecodedChunk> {
+        trace!("decoding packet");
+        self.next_timestamp = Some(packet.ts() + packet.dur());
+        match self.decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                trace!(?spec, "packet decode success");
+                self.last_spec = Some(spec);
+
+                SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec()).pipe(|mut sample_buf| {
+                    trace!("copying decoded data into a buffer");
+                    sample_buf
+                        .copy_interleaved_ref(decoded)
+                        .pipe(|_| DecodedChunk {
+                            spec,
+                            sample_buffer: sample_buf,
                         })
-                    })
+                        .pipe(Ok)
                 })
-                .collect::<Result<Vec<f32>>>(),
+            }
+            Err(symphonia::core::errors::Error::DecodeError(message)) if self.options.lenient => {
+                debug!(%message, "could not decode packet");
+                self.silence(packet.dur())
+            }
+            Err(e) => Err(e).context("decoding packet for track"),
+        }
+    }
+
+    fn finish(&self) {
+        match self.patched {
+            PatchedFrames { packets: 0, .. } => {}
+            PatchedFrames { packets, frames } => info!(%packets, %frames, "replaced undecodable packets with silence"),
         }
-        .tap_ok(|downmixed| debug!(downmixed_samples = downmixed.len()))
     }
 }
 
@@ -295,29 +571,24 @@ impl Iterator for FormatReaderIterator {
     type Item = Result<self::DecodedChunk>;
     #[instrument(level = "trace", ret)]
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(packet) = self.pending_packet.take() {
+            return Some(self.decode_packet(packet));
+        }
         self.next_packet()
             .context("reading next packet")
+            .tap_ok(|packet| {
+                if packet.is_none() {
+                    self.finish()
+                }
+            })
             .transpose()
             .map(|packet| {
-                packet.and_then(|packet| {
-                    trace!("decoding packet");
-                    self.decoder
-                        .decode(&packet)
-                        .context("decoding packet for track")
-                        .map(|decoded| {
-                            let spec = *decoded.spec();
-                            trace!(?spec, "packet decode success");
-
-                            SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec()).pipe(|mut sample_buf| {
-                                trace!("copying decoded data into a buffer");
-                                sample_buf
-                                    .copy_interleaved_ref(decoded)
-                                    .pipe(|_| DecodedChunk {
-                                        spec,
-                                        sample_buffer: sample_buf,
-                                    })
-                            })
-                        })
+                packet.and_then(|packet| match self.missing_before(&packet) {
+                    Some(missing) => {
+                        self.pending_packet = Some(packet);
+                        self.silence(missing)
+                    }
+                    None => self.decode_packet(packet),
                 })
             })
     }
@@ -365,9 +636,99 @@ pub fn convert_to_mp3(
     target_bitrate: Option<u32>,
     target_frequency: Option<u32>,
     target_channel_mode: Option<Mp3TargetChannelMode>,
+    filters: &TrackFilters,
+    decode: DecodeOptions,
+) -> Result<()> {
+    match filters.is_empty() {
+        true => convert_to_mp3_streaming(from, to, target_bitrate, target_frequency, target_channel_mode, decode),
+        false => {
+            // the mp3 encoder works on a stream of decoded chunks, so the filtered track is staged as a wav file
+            let filtered = tempfile::Builder::new()
+                .suffix(".wav")
+                .tempfile()
+                .context("creating temp file for filtered track")?
+                .into_temp_path();
+            FormatReaderIterator::from_file(from, decode)
+                .and_then(LoadedTrack::from_reader)
+                .context("loading track")
+                .map(|track| filters.apply(track))
+                .and_then(|track| track.write_wav(&filtered))
+                .context("applying filters")
+                .and_then(|_| convert_to_mp3_streaming(&filtered, to, target_bitrate, target_frequency, target_channel_mode, Default::default()))
+        }
+    }
+}
+
+fn mp3_bitrate(target_bitrate: Option<u32>) -> Result<Bitrate> {
+    target_bitrate
+        .map(|f| match f {
+            8 => Ok(Bitrate::Kbps8),
+            16 => Ok(Bitrate::Kbps16),
+            24 => Ok(Bitrate::Kbps24),
+            32 => Ok(Bitrate::Kbps32),
+            40 => Ok(Bitrate::Kbps40),
+            48 => Ok(Bitrate::Kbps48),
+            64 => Ok(Bitrate::Kbps64),
+            80 => Ok(Bitrate::Kbps80),
+            96 => Ok(Bitrate::Kbps96),
+            112 => Ok(Bitrate::Kbps112),
+            128 => Ok(Bitrate::Kbps128),
+            160 => Ok(Bitrate::Kbps160),
+            192 => Ok(Bitrate::Kbps192),
+            224 => Ok(Bitrate::Kbps224),
+            256 => Ok(Bitrate::Kbps256),
+            320 => Ok(Bitrate::Kbps320),
+            bad_bitrate => Err(anyhow::anyhow!("invalid bitrate: [{bad_bitrate}]")),
+        })
+        .transpose()
+        .context("Reading frequency")
+        .map(|bitrate| bitrate.unwrap_or(Bitrate::Kbps192))
+}
+
+fn build_mp3_encoder(channel_mode: Mp3TargetChannelMode, sample_rate: u32, target_bitrate: Option<u32>) -> Result<mp3lame_encoder::Encoder> {
+    mp3lame_encoder::Builder::new()
+        .context("creating mp3 lame encoder builder")
+        .and_then(|mut encoder| {
+            encoder
+                .set_num_channels(channel_mode.as_count() as u8)
+                .for_anyhow()
+                .context("set_num_channels")?;
+            encoder
+                .set_sample_rate(sample_rate)
+                .for_anyhow()
+                .context("set_sample_rate")?;
+            encoder
+                .set_brate(mp3_bitrate(target_bitrate)?)
+                .for_anyhow()
+                .context("setting bitrate")?;
+            encoder
+                .set_quality(mp3lame_encoder::Quality::Good)
+                .for_anyhow()
+                .context("set quality")?;
+            encoder
+                .build()
+                .for_anyhow()
+                .context("building lame encoder")
+        })
+        .tap_ok(|encoder| {
+            tracing::debug!(
+                encoder_sample_rate = encoder.sample_rate(),
+                encoder_num_channels = encoder.num_channels(),
+                "created mp3 lame encoder"
+            );
+        })
+}
+
+fn convert_to_mp3_streaming(
+    from: &Path,
+    to: &Path,
+    target_bitrate: Option<u32>,
+    target_frequency: Option<u32>,
+    target_channel_mode: Option<Mp3TargetChannelMode>,
+    decode: DecodeOptions,
 ) -> Result<()> {
-    FormatReaderIterator::from_file(from).and_then(|reader| -> Result<_> {
-        use mp3lame_encoder::{Builder, FlushNoGap};
+    FormatReaderIterator::from_file(from, decode).and_then(|reader| -> Result<_> {
+        use mp3lame_encoder::FlushNoGap;
         let mut reader = reader
             .filter(|c| {
                 c.as_ref()
@@ -383,7 +744,7 @@ pub fn convert_to_mp3(
                     .map_err(|e| anyhow::anyhow!("{e:#?}"))
                     .map(|c| (c.spec.rate, c.spec.channels.count(), c.sample_buffer.len()))
             })
-            .and_then(|(rate, channels, chunk_size)| Mp3TargetChannelMode::from_count(channels).map(|channels| (rate, channels, chunk_size)))?;
+            .and_then(|(rate, channels, chunk_size)| Mp3TargetChannelMode::for_source(channels).map(|channels| (rate, channels, chunk_size)))?;
 
         let target_frequency = target_frequency.unwrap_or(source_sample_rate);
 
@@ -407,7 +768,7 @@ pub fn convert_to_mp3(
                     source_sample_rate,
                     target_frequency,
                     buffer_size,
-                    source_channel_mode
+                    target_channel_mode
                         .as_count()
                         .pipe(NonZeroUsize::new)
                         .expect("enum to handle empty channels"),
@@ -417,62 +778,8 @@ pub fn convert_to_mp3(
             .transpose()
             .context("deducing resampler")?;
         let mut buffer = Vec::new();
-        Builder::new()
-            .context("creating mp3 lame encoder builder")
-            .and_then(|mut encoder| {
-                encoder
-                    .set_num_channels(target_channel_mode.as_count() as u8)
-                    .for_anyhow()
-                    .context("set_num_channels")?;
-                encoder
-                    .set_sample_rate(target_frequency)
-                    .for_anyhow()
-                    .context("set_sample_rate")?;
-
-                let target_bitrate = target_bitrate
-                    .map(|f| match f {
-                        8 => Ok(Bitrate::Kbps8),
-                        16 => Ok(Bitrate::Kbps16),
-                        24 => Ok(Bitrate::Kbps24),
-                        32 => Ok(Bitrate::Kbps32),
-                        40 => Ok(Bitrate::Kbps40),
-                        48 => Ok(Bitrate::Kbps48),
-                        64 => Ok(Bitrate::Kbps64),
-                        80 => Ok(Bitrate::Kbps80),
-                        96 => Ok(Bitrate::Kbps96),
-                        112 => Ok(Bitrate::Kbps112),
-                        128 => Ok(Bitrate::Kbps128),
-                        160 => Ok(Bitrate::Kbps160),
-                        192 => Ok(Bitrate::Kbps192),
-                        224 => Ok(Bitrate::Kbps224),
-                        256 => Ok(Bitrate::Kbps256),
-                        320 => Ok(Bitrate::Kbps320),
-                        bad_bitrate => Err(anyhow::anyhow!("invalid bitrate: [{bad_bitrate}]")),
-                    })
-                    .transpose()
-                    .context("Reading frequency")?
-                    .unwrap_or(Bitrate::Kbps192);
-
-                encoder
-                    .set_brate(target_bitrate)
-                    .for_anyhow()
-                    .context("setting bitrate")?;
-                encoder
-                    .set_quality(mp3lame_encoder::Quality::Good)
-                    .for_anyhow()
-                    .context("set quality")?;
-                encoder
-                    .build()
-                    .for_anyhow()
-                    .context("building lame encoder")
-            })
-            .tap_ok(|encoder| {
-                tracing::debug!(
-                    encoder_sample_rate = encoder.sample_rate(),
-                    encoder_num_channels = encoder.num_channels(),
-                    "created mp3 lame encoder"
-                );
-            })
+        build_mp3_encoder(target_channel_mode, target_frequency, target_bitrate)
+            .context("building mp3 encoder")
             .and_then(|mut encoder| {
                 reader
                     .try_for_each(|chunk| {
@@ -514,13 +821,10 @@ pub fn convert_to_mp3(
                                     }
                                 },
                                 (Mp3TargetChannelMode::Stereo, Mp3TargetChannelMode::Stereo) => {
-                                    let [left, right] = chunk
-                                        .split_channels()
-                                        .map(|ch| ch.collect_vec())
-                                        .collect_vec()
-                                        .try_conv::<[_; 2]>()
-                                        .map_err(|bad_size| anyhow::anyhow!("bad size: {bad_size:?}"))
-                                        .context("channel size mismatch")?;
+                                    let stereo = chunk
+                                        .mix_to(Mp3TargetChannelMode::Stereo)
+                                        .context("mixing to stereo")?;
+                                    let [left, right] = split_channels_raw::<2>(stereo.as_slice()).map(|i| i.collect_vec());
                                     match resampler.as_mut() {
                                         Some(resampler) => {
                                             let resampled = resampler.process(&[&left, &right]).context("resampling")?;
@@ -672,31 +976,17 @@ pub fn convert_to_mp3(
     })
 }
 
-pub fn convert_to_wav(from: &Path, to: &Path, target_frequency: Option<u32>) -> Result<()> {
-    let track = FormatReaderIterator::from_file(from)
+pub fn convert_to_wav(from: &Path, to: &Path, target_frequency: Option<u32>, filters: &TrackFilters, decode: DecodeOptions) -> Result<()> {
+    FormatReaderIterator::from_file(from, decode)
         .and_then(LoadedTrack::from_reader)
         .context("loading track")
         .and_then(|track| match target_frequency {
             Some(target) => track.resample_if_needed(target).context("resampling"),
             None => Ok(track),
         })
-        .context("maybe resampling")?;
-    let mut writer = hound::WavWriter::create(
-        to,
-        hound::WavSpec {
-            channels: track.channels.len() as _,
-            sample_rate: track.sample_rate,
-            bits_per_sample: 32,
-            sample_format: hound::SampleFormat::Float,
-        }
-        .tap(|spec| tracing::trace!(?spec, "creating wav writer with spec")),
-    )
-    .context("creating WAV writer")?;
-    let wrote = track
-        .interleaved_samples_iter()
-        .try_for_each(|sample| writer.write_sample(sample))
-        .context("writing to writer failed");
-    wrote.and_then(|_| writer.finalize().context("finalizing the writer"))
+        .context("maybe resampling")
+        .map(|track| filters.apply(track))
+        .and_then(|track| track.write_wav(to))
 }
 
 struct BufferedResampler {
@@ -807,20 +1097,36 @@ impl LoadedTrack {
                     .unwrap_or(true)
             })
             .peekable();
-        let (original_rate, original_channel_count, _sample_buffer_size) = reader
+        let (original_rate, original_channels, _sample_buffer_size) = reader
             .peek()
             .map(|r| {
                 r.as_ref()
                     .map_err(|e| anyhow::anyhow!("{e:#?}"))
-                    .map(|r| (r.spec.rate, r.spec.channels.count(), r.sample_buffer.len()))
+                    .map(|r| (r.spec.rate, r.spec.channels, r.sample_buffer.len()))
             })
             .context("input is empty?")
             .and_then(identity)
             .context("deducing original metadata")?;
+        // surround sources are mixed down to stereo while loading, mono and stereo ones are loaded as they are
+        let mixer = Mp3TargetChannelMode::for_source(original_channels.count())
+            .and_then(|target| match target.as_count() == original_channels.count() {
+                true => Ok(None),
+                false => ChannelMixer::new(original_channels, target).map(Some),
+            })
+            .context("building channel mixer")?;
+        let channel_count = mixer
+            .as_ref()
+            .map(ChannelMixer::output_count)
+            .unwrap_or(original_channels.count());
         reader
-            .try_fold(Self::empty(original_rate, original_channel_count), |mut acc, next| {
-                next.map(|next| acc.load_interleaved(next.sample_buffer.samples()))
-                    .map(|_| acc)
+            .try_fold(Self::empty(original_rate, channel_count), |mut acc, next| {
+                next.and_then(|next| match mixer.as_ref() {
+                    Some(mixer) => mixer
+                        .mix_interleaved(next.sample_buffer.samples())
+                        .map(|mixed| acc.load_interleaved(&mixed)),
+                    None => Ok(acc.load_interleaved(next.sample_buffer.samples())),
+                })
+                .map(|_| acc)
             })
             .context("loading raw track")
     }
@@ -929,6 +1235,25 @@ impl LoadedTrack {
         }
     }
 
+    pub fn write_wav(&self, to: &Path) -> Result<()> {
+        let mut writer = hound::WavWriter::create(
+            to,
+            hound::WavSpec {
+                channels: self.channels.len() as _,
+                sample_rate: self.sample_rate,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            }
+            .tap(|spec| tracing::trace!(?spec, "creating wav writer with spec")),
+        )
+        .context("creating WAV writer")?;
+        let wrote = self
+            .interleaved_samples_iter()
+            .try_for_each(|sample| writer.write_sample(sample))
+            .context("writing to writer failed");
+        wrote.and_then(|_| writer.finalize().context("finalizing the writer"))
+    }
+
     pub fn load_channel(&mut self, channel: usize, data: &[f32]) {
         self.channels[channel].extend_from_slice(data);
     }
@@ -948,48 +1273,63 @@ impl LoadedTrack {
 }
 
 pub fn resample_ogg(from: &Path, to: &Path, target_frequency: u32) -> Result<()> {
-    let track = FormatReaderIterator::from_file(from)
+    convert_to_ogg(from, to, Some(target_frequency), &TrackFilters::default(), DecodeOptions::default())
+}
+
+pub fn convert_to_ogg(from: &Path, to: &Path, target_frequency: Option<u32>, filters: &TrackFilters, decode: DecodeOptions) -> Result<()> {
+    FormatReaderIterator::from_file(from, decode)
         .context("opening source file")
         .and_then(LoadedTrack::from_reader)?
-        .resample_if_needed(target_frequency)?;
-
-    const REASONABLE_OGG_BLOCK_SIZE: usize = 2048;
-
-    let mut output = std::fs::File::create(to)
-        .context("opening output file for writing")?
-        .pipe(BufWriter::new);
-    let mut encoder = info_span!("building_vobis_encoder").in_scope(|| -> Result<_> {
-        VorbisEncoderBuilder::new(
-            target_frequency
-                .pipe(NonZeroU32::new)
-                .context("zero sampling frequency?")
-                .tap_ok(|target_frequency| debug!(%target_frequency))?,
-            track
-                .channels
-                .len()
-                .to_u8()
-                .context("too many channels (max is 255)")
-                .and_then(|channels| NonZeroU8::new(channels).context("no channels"))
-                .context("validating input channels")
-                .tap_ok(|target_channel_count| debug!(%target_channel_count))?,
-            &mut output,
-        )
-        .context("crating vorbis encoder builder")
-        .and_then(|mut e| e.build().context("finalizing vorbis encoder"))
-        .context("creating vorbis encoder")
-    })?;
-
-    let reencoded = track
-        .iter_chunks(REASONABLE_OGG_BLOCK_SIZE)
-        .try_for_each(|chunk| {
-            encoder
-                .encode_audio_block(&chunk)
-                .context("encoding sample")?;
+        .pipe(|track| match target_frequency {
+            Some(target_frequency) => track.resample_if_needed(target_frequency),
+            None => Ok(track),
+        })?
+        .pipe(|track| filters.apply(track))
+        .write_ogg(to)
+        .with_context(|| format!("converting [{from:?}] -> [{to:?}]"))
+}
 
-            Ok(())
-        });
-    reencoded
-        .and_then(|_| encoder.finish().context("finalizing encoder"))
-        .and_then(|w| w.flush().context("flushing the output"))
-        .with_context(|| format!("resampling [{from:?}] -> [{to:?}]"))
+impl LoadedTrack {
+    pub fn write_ogg(&self, to: &Path) -> Result<()> {
+        const REASONABLE_OGG_BLOCK_SIZE: usize = 2048;
+
+        let mut output = std::fs::File::create(to)
+            .context("opening output file for writing")?
+            .pipe(BufWriter::new);
+        let mut encoder = info_span!("building_vobis_encoder").in_scope(|| -> Result<_> {
+            VorbisEncoderBuilder::new(
+                self.sample_rate
+                    .pipe(NonZeroU32::new)
+                    .context("zero sampling frequency?")
+                    .tap_ok(|target_frequency| debug!(%target_frequency))?,
+                self.channels
+                    .len()
+                    .to_u8()
+                    .context("too many channels (max is 255)")
+                    .and_then(|channels| NonZeroU8::new(channels).context("no channels"))
+                    .context("validating input channels")
+                    .tap_ok(|target_channel_count| debug!(%target_channel_count))?,
+                &mut output,
+            )
+            .context("crating vorbis encoder builder")
+            .and_then(|mut e| e.build().context("finalizing vorbis encoder"))
+            .context("creating vorbis encoder")
+        })?;
+
+        let reencoded = self
+            .iter_chunks(REASONABLE_OGG_BLOCK_SIZE)
+            .try_for_each(|chunk| {
+                encoder
+                    .encode_audio_block(&chunk)
+                    .context("encoding sample")?;
+
+                Ok(())
+            });
+        reencoded
+            .and_then(|_| encoder.finish().context("finalizing encoder"))
+            .and_then(|w| w.flush().context("flushing the output"))
+    }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,227 @@
+use {super::*, symphonia::core::audio::Channels};
+
+fn write_sine_wav(path: &Path, bits_per_sample: u16, channels: u16, sample_rate: u32, samples: usize) -> Result<()> {
+    let amplitude = (1i32 << (bits_per_sample - 1)) - 1;
+    hound::WavWriter::create(
+        path,
+        hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format: hound::SampleFormat::Int,
+        },
+    )
+    .context("creating wav writer")
+    .and_then(|mut writer| {
+        (0..samples)
+            .map(|idx| (idx as f32 * 440. * std::f32::consts::TAU / sample_rate as f32).sin())
+            .map(|sample| (sample * amplitude as f32) as i32)
+            .flat_map(|sample| repeat_n(sample, channels as usize))
+            .try_for_each(|sample| writer.write_sample(sample))
+            .context("writing samples")
+            .and_then(|_| writer.finalize().context("finalizing"))
+    })
+}
+
+#[test]
+fn test_detect_container_by_magic() {
+    assert_eq!(SourceContainer::from_magic(b"RIFF\0\0\0\0WAVEfmt "), Some(SourceContainer::Wav));
+    assert_eq!(SourceContainer::from_magic(b"OggS\0\x02"), Some(SourceContainer::Ogg));
+    assert_eq!(SourceContainer::from_magic(b"ID3\x04"), Some(SourceContainer::Mp3));
+    assert_eq!(SourceContainer::from_magic(&[0xFF, 0xFB, 0x90]), Some(SourceContainer::Mp3));
+    assert_eq!(SourceContainer::from_magic(b"RIFF\0\0\0\0AVI "), None);
+    assert_eq!(SourceContainer::from_extension("WAV"), Some(SourceContainer::Wav));
+}
+
+#[test_log::test]
+fn test_24_bit_wav_without_extension_loads() -> Result<()> {
+    let source = tempfile::NamedTempFile::new().context("creating temp file")?;
+    write_sine_wav(source.path(), 24, 2, 44100, 4410)?;
+    assert_eq!(detect_container(source.path())?, Some(SourceContainer::Wav));
+    FormatReaderIterator::from_file(source.path(), DecodeOptions::default())
+        .and_then(LoadedTrack::from_reader)
+        .map(|track| {
+            assert_eq!(track.sample_rate, 44100);
+            assert_eq!(track.channels.len(), 2);
+            assert_eq!(track.channels[0].len(), 4410);
+            assert!(track.channels[0].iter().all(|sample| sample.abs() <= 1.));
+        })
+}
+
+#[test_log::test]
+fn test_wav_to_ogg() -> Result<()> {
+    let source = tempfile::NamedTempFile::new().context("creating temp file")?;
+    let target = tempfile::NamedTempFile::new().context("creating temp file")?;
+    write_sine_wav(source.path(), 16, 1, 22050, 22050)?;
+    convert_to_ogg(source.path(), target.path(), Some(44100), &TrackFilters::default(), DecodeOptions::default())?;
+    assert_eq!(detect_container(target.path())?, Some(SourceContainer::Ogg));
+    FormatReaderIterator::from_file(target.path(), DecodeOptions::default())
+        .and_then(LoadedTrack::from_reader)
+        .map(|track| assert_eq!(track.sample_rate, 44100))
+}
+
+#[test_log::test]
+fn test_lenient_decode_preserves_duration() -> Result<()> {
+    let source = tempfile::NamedTempFile::new().context("creating temp file")?;
+    let encoded = tempfile::NamedTempFile::new().context("creating temp file")?;
+    write_sine_wav(source.path(), 16, 1, 44100, 44100 * 4)?;
+    convert_to_ogg(source.path(), encoded.path(), None, &TrackFilters::default(), DecodeOptions::default())?;
+    let frames_of = |options: DecodeOptions| -> Result<(usize, PatchedFrames)> {
+        FormatReaderIterator::from_file(encoded.path(), options).and_then(|mut reader| {
+            reader
+                .by_ref()
+                .map(|chunk| chunk.map(|chunk| chunk.single_channel_length()))
+                .sum::<Result<usize>>()
+                .map(|frames| (frames, reader.patched()))
+        })
+    };
+    let (clean, clean_patched) = frames_of(DecodeOptions::default())?;
+    assert_eq!(clean_patched, PatchedFrames::default());
+
+    // damage a chunk of page data in the middle of the stream
+    let mut bytes = std::fs::read(encoded.path()).context("reading encoded file")?;
+    let middle = bytes.len() / 2;
+    bytes[middle..middle + 512]
+        .iter_mut()
+        .for_each(|byte| *byte = !*byte);
+    std::fs::write(encoded.path(), bytes).context("writing corrupted file")?;
+
+    let (lenient, patched) = frames_of(DecodeOptions { lenient: true })?;
+    assert!(patched.packets > 0);
+    assert!(clean.abs_diff(lenient) <= 2048, "clean: {clean}, lenient: {lenient}");
+    Ok(())
+}
+
+#[test]
+fn test_patched_gap_is_capped() {
+    let spec = SignalSpec::new(44100, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+    assert_eq!(patched_gap(44100, &spec).ok(), Some(44100));
+    assert!(patched_gap(44100 * 60 * 60, &spec).is_err());
+}
+
+#[test_log::test]
+fn test_bench_runs_every_target() -> Result<()> {
+    let corpus = tempfile::tempdir().context("creating corpus dir")?;
+    write_sine_wav(&corpus.path().join("sine.wav"), 16, 2, 22050, 22050)?;
+    [bench::BenchTarget::Wav, bench::BenchTarget::Ogg, bench::BenchTarget::Mp3]
+        .into_iter()
+        .try_for_each(|target| {
+            bench::BenchArgs {
+                corpus: corpus.path().to_owned(),
+                target,
+                iterations: 2,
+                target_frequency: Some(44100),
+                target_bitrate: None,
+                filters: Default::default(),
+                decode: Default::default(),
+            }
+            .run()
+            .with_context(|| format!("benchmarking {target:?}"))
+        })
+}
+
+#[test_log::test]
+fn test_check_reports_broken_samples() -> Result<()> {
+    let clean = tempfile::NamedTempFile::new().context("creating temp file")?;
+    write_sine_wav(clean.path(), 16, 2, 44100, 4410)?;
+    check::check_file(clean.path()).map(|report| {
+        assert_eq!(report.frames, 4410);
+        assert!(report.problems(0.01).is_empty(), "{:?}", report.problems(0.01));
+    })?;
+
+    let broken = tempfile::NamedTempFile::new().context("creating temp file")?;
+    LoadedTrack::empty(44100, 1)
+        .tap_mut(|track| track.load_channel(0, &[0.5, f32::NAN, 1.0, -1.0, 0.25]))
+        .write_wav(broken.path())?;
+    check::check_file(broken.path()).map(|report| {
+        assert_eq!(report.non_finite, 1);
+        assert_eq!(report.clipped, 2);
+        assert_eq!(report.problems(0.01).len(), 2, "{:?}", report.problems(0.01));
+    })?;
+
+    // integer pcm clips at both ends, even though the positive end is one step short of 1.0
+    let clipped = tempfile::NamedTempFile::new().context("creating temp file")?;
+    hound::WavWriter::create(
+        clipped.path(),
+        hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        },
+    )
+    .context("creating wav writer")
+    .and_then(|mut writer| {
+        [0i16, i16::MAX, i16::MIN, 100]
+            .into_iter()
+            .try_for_each(|sample| writer.write_sample(sample))
+            .and_then(|_| writer.finalize())
+            .context("writing wav")
+    })?;
+    check::check_file(clipped.path()).map(|report| assert_eq!(report.clipped, 2))
+}
+
+const FIVE_POINT_ONE: Channels = Channels::FRONT_LEFT
+    .union(Channels::FRONT_RIGHT)
+    .union(Channels::FRONT_CENTRE)
+    .union(Channels::LFE1)
+    .union(Channels::SIDE_LEFT)
+    .union(Channels::SIDE_RIGHT);
+
+#[test]
+fn test_channel_mixer_stereo_passthrough() -> Result<()> {
+    let mixer = ChannelMixer::new(Channels::FRONT_LEFT | Channels::FRONT_RIGHT, Mp3TargetChannelMode::Stereo)?;
+    assert_eq!(mixer.mix_interleaved(&[0.1, 0.2, 0.3, 0.4])?, vec![0.1, 0.2, 0.3, 0.4]);
+    Ok(())
+}
+
+#[test]
+fn test_channel_mixer_mono_upmix_duplicates() -> Result<()> {
+    let mixer = ChannelMixer::new(Channels::FRONT_LEFT, Mp3TargetChannelMode::Stereo)?;
+    assert_eq!(mixer.mix_interleaved(&[0.5, -0.5])?, vec![0.5, 0.5, -0.5, -0.5]);
+    Ok(())
+}
+
+#[test]
+fn test_channel_mixer_surround_downmix() -> Result<()> {
+    let mixer = ChannelMixer::new(FIVE_POINT_ONE, Mp3TargetChannelMode::Stereo)?;
+    // only the LFE channel carries signal
+    assert_eq!(mixer.mix_interleaved(&[0., 0., 0., 1., 0., 0.])?, vec![0., 0.]);
+    // only the left side channel carries signal
+    let mixed = mixer.mix_interleaved(&[0., 0., 0., 0., 1., 0.])?;
+    assert!(mixed[0] > 0. && mixed[1] == 0., "{mixed:?}");
+    // the front pair keeps its level, surround channels come in at -3dB
+    let mixed = mixer.mix_interleaved(&[0.5, 0., 0., 0., 0.5, 0.])?;
+    assert!((mixed[0] - (0.5 + 0.5 * std::f32::consts::FRAC_1_SQRT_2)).abs() < 1e-6, "{mixed:?}");
+    // full scale everywhere does not clip
+    let mixed = mixer.mix_interleaved(&[1.; 6])?;
+    assert!(mixed.iter().all(|sample| *sample <= 1.), "{mixed:?}");
+    let mixed = mixer.mix_interleaved(&[-1.; 6])?;
+    assert!(mixed.iter().all(|sample| *sample >= -1.), "{mixed:?}");
+
+    let mono = ChannelMixer::new(FIVE_POINT_ONE, Mp3TargetChannelMode::Mono)?;
+    assert_eq!(mono.output_count(), 1);
+    let mixed = mono.mix_interleaved(&[0., 0., 1., 0., 0., 0.])?;
+    assert!(mixed[0] > 0., "{mixed:?}");
+    Ok(())
+}
+
+#[test_log::test]
+fn test_surround_wav_is_mixed_to_stereo() -> Result<()> {
+    let source = tempfile::NamedTempFile::new().context("creating temp file")?;
+    let target = tempfile::NamedTempFile::new().context("creating temp file")?;
+    write_sine_wav(source.path(), 16, 6, 22050, 22050)?;
+    convert_to_wav(source.path(), target.path(), None, &TrackFilters::default(), DecodeOptions::default())?;
+    FormatReaderIterator::from_file(target.path(), DecodeOptions::default())
+        .and_then(LoadedTrack::from_reader)
+        .map(|track| {
+            assert_eq!(track.channels.len(), 2);
+            track.channels.iter().for_each(|channel| {
+                assert_eq!(channel.len(), 22050);
+                let peak = channel
+                    .iter()
+                    .fold(0f32, |peak, sample| peak.max(sample.abs()));
+                assert!(peak > 0.5 && peak <= 1., "peak: {peak}");
+            });
+        })
+}
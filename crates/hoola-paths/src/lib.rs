@@ -0,0 +1,223 @@
+//! paths coming from archives and modlists are windows paths, this is the single place where they get translated
+
+use {
+    serde::{Deserialize, Serialize},
+    std::path::PathBuf,
+};
+
+/// `\\?\C:\...` and `\\.\C:\...`, windows uses them to skip its own normalization
+const VERBATIM_PREFIXES: &[&str] = &["\\\\?\\", "\\\\.\\", "//?/", "//./"];
+/// `\\?\UNC\server\share\...`
+const VERBATIM_UNC_PREFIX: &str = "UNC\\";
+
+/// device names which cannot be used as file names on windows, regardless of the extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7",
+    "LPT8", "LPT9",
+];
+
+#[derive(
+    Serialize, Deserialize, schemars::JsonSchema, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, derive_more::Display, derive_more::From, derive_more::FromStr,
+)]
+pub struct MaybeWindowsPath(pub String);
+
+impl std::fmt::Debug for MaybeWindowsPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '\\' | '/')
+}
+
+/// strips the verbatim prefix, verbatim UNC paths are turned into regular UNC paths
+fn strip_verbatim_prefix(path: &str) -> std::borrow::Cow<'_, str> {
+    VERBATIM_PREFIXES
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix))
+        .map(|rest| match rest.strip_prefix(VERBATIM_UNC_PREFIX) {
+            Some(unc) => format!("\\\\{unc}").into(),
+            None => rest.into(),
+        })
+        .unwrap_or(path.into())
+}
+
+/// windows silently drops trailing dots and spaces from file names (`foo.esp. ` is `foo.esp`)
+pub fn trim_windows_file_name(name: &str) -> &str {
+    match name {
+        "." | ".." => name,
+        name => name.trim_end_matches(['.', ' ']),
+    }
+}
+
+/// `CON`, `nul.txt`, `Com1.tar.gz` and friends
+pub fn is_reserved_name(name: &str) -> bool {
+    let stem = trim_windows_file_name(name)
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(' ');
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+/// case folding used for comparing paths, windows filesystems are case insensitive
+fn fold_case(path: &str) -> String {
+    path.to_lowercase()
+}
+
+impl MaybeWindowsPath {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self(path.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// path components with separators of both kinds, empty and `.` components are skipped
+    pub fn components(&self) -> impl Iterator<Item = &str> + '_ {
+        self.0
+            .split(is_separator)
+            .filter(|component| !component.is_empty() && *component != ".")
+    }
+
+    pub fn file_name(&self) -> Option<&str> {
+        self.components().last()
+    }
+
+    pub fn is_unc(&self) -> bool {
+        let path = strip_verbatim_prefix(&self.0);
+        let mut chars = path.chars();
+        chars.next().is_some_and(is_separator) && chars.next().is_some_and(is_separator)
+    }
+
+    /// components which would be rejected (or silently renamed) by windows
+    pub fn reserved_components(&self) -> impl Iterator<Item = &str> + '_ {
+        self.components()
+            .filter(|component| is_reserved_name(component) || trim_windows_file_name(component) != *component)
+    }
+
+    /// forward slashes only, no verbatim prefix, no trailing dots/spaces in components - this is what windows itself would open
+    pub fn normalized(&self) -> String {
+        let path = strip_verbatim_prefix(&self.0);
+        let root = match (self.is_unc(), path.starts_with(is_separator)) {
+            (true, _) => "//",
+            (false, true) => "/",
+            (false, false) => "",
+        };
+        let components = path
+            .split(is_separator)
+            .filter(|component| !component.is_empty() && *component != ".")
+            .map(trim_windows_file_name)
+            .collect::<Vec<_>>()
+            .join("/");
+        format!("{root}{components}")
+    }
+
+    /// normalized and case folded, two paths pointing at the same file on windows have the same key
+    pub fn comparison_key(&self) -> String {
+        fold_case(&self.normalized())
+    }
+
+    /// converts the separators, every (double) backslash becomes a single forward slash
+    pub fn into_path(self) -> PathBuf {
+        let s = strip_verbatim_prefix(&self.0).into_owned();
+        let s = match s.contains("\\\\") {
+            true => s.split("\\\\").collect::<Vec<_>>().join("/"),
+            false => s,
+        };
+        let s = match s.contains("\\") {
+            true => s.split("\\").collect::<Vec<_>>().join("/"),
+            false => s,
+        };
+        PathBuf::from(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, pretty_assertions::assert_eq, std::path::Path};
+
+    fn path(s: &str) -> MaybeWindowsPath {
+        MaybeWindowsPath::new(s)
+    }
+
+    #[test]
+    fn test_into_path_separators() {
+        assert_eq!(path("textures\\armor\\a.dds").into_path(), Path::new("textures/armor/a.dds"));
+        assert_eq!(path("textures\\\\armor\\\\a.dds").into_path(), Path::new("textures/armor/a.dds"));
+        assert_eq!(path("textures/armor/a.dds").into_path(), Path::new("textures/armor/a.dds"));
+        assert_eq!(path("textures\\armor/a.dds").into_path(), Path::new("textures/armor/a.dds"));
+        assert_eq!(path("").into_path(), Path::new(""));
+    }
+
+    #[test]
+    fn test_unc_prefixes() {
+        assert_eq!(path("\\\\?\\C:\\games\\fallout").into_path(), Path::new("C:/games/fallout"));
+        assert_eq!(path("\\\\.\\C:\\games").normalized(), "C:/games");
+        assert_eq!(path("\\\\?\\UNC\\server\\share\\file.esp").normalized(), "//server/share/file.esp");
+        assert_eq!(path("\\\\server\\share\\file.esp").normalized(), "//server/share/file.esp");
+        assert!(path("\\\\server\\share").is_unc());
+        assert!(path("\\\\?\\UNC\\server\\share").is_unc());
+        assert!(!path("\\\\?\\C:\\games").is_unc());
+        assert!(!path("\\data\\file").is_unc());
+        assert_eq!(path("\\data\\file").normalized(), "/data/file");
+    }
+
+    #[test]
+    fn test_trailing_dots_and_spaces() {
+        assert_eq!(trim_windows_file_name("foo.esp. "), "foo.esp");
+        assert_eq!(trim_windows_file_name("foo..."), "foo");
+        assert_eq!(trim_windows_file_name(".."), "..");
+        assert_eq!(trim_windows_file_name("."), ".");
+        assert_eq!(path("meshes. \\armor .\\a.nif ").normalized(), "meshes/armor/a.nif");
+        assert_eq!(
+            path("meshes. \\a.nif")
+                .reserved_components()
+                .collect::<Vec<_>>(),
+            vec!["meshes. "]
+        );
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        ["CON", "con", "Nul.txt", "com1.tar.gz", "LPT9", "aux ", "prn."]
+            .into_iter()
+            .for_each(|name| assert!(is_reserved_name(name), "{name}"));
+        ["CONSOLE", "com10", "nul_", "LPT", "icon.dds", ""]
+            .into_iter()
+            .for_each(|name| assert!(!is_reserved_name(name), "{name}"));
+        assert_eq!(
+            path("data\\aux\\file.esp")
+                .reserved_components()
+                .collect::<Vec<_>>(),
+            vec!["aux"]
+        );
+    }
+
+    #[test]
+    fn test_case_folding() {
+        assert_eq!(path("Data\\Textures\\A.DDS").comparison_key(), path("data/textures/a.dds").comparison_key());
+        assert_eq!(
+            path("\\\\?\\C:\\Games\\Skyrim.esm").comparison_key(),
+            path("c:/games/skyrim.esm").comparison_key()
+        );
+        assert_ne!(path("data\\a.dds").comparison_key(), path("data\\b.dds").comparison_key());
+        assert_eq!(fold_case("ÄBC"), "äbc");
+    }
+
+    #[test]
+    fn test_components() {
+        assert_eq!(
+            path("\\data\\.\\textures//a.dds")
+                .components()
+                .collect::<Vec<_>>(),
+            vec!["data", "textures", "a.dds"]
+        );
+        assert_eq!(path("data\\textures\\a.dds").file_name(), Some("a.dds"));
+        assert_eq!(path("").file_name(), None);
+    }
+}
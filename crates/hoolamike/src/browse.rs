@@ -0,0 +1,251 @@
+//! the modlist gallery in the terminal (build.wabbajack.org/gallery lists the same modlist repositories `fetch-modlist` uses),
+//! the picked modlist is downloaded and verified straight to where the config expects it
+use {
+    crate::{
+        config_file::HoolamikeConfig,
+        downloaders::http_client::{configure_http_client, http_client},
+        fetch_modlist::{download_modlist, fetch_gallery, find_modlist, output_path, ModlistMetadata, ModlistRow},
+        helpers::parse_size,
+        progress_bars_v2::events::print_report,
+    },
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    std::{
+        io::{IsTerminal, Write},
+        path::PathBuf,
+    },
+    tabled::{settings::Style, Tabled},
+    tap::prelude::*,
+};
+
+#[derive(clap::Args)]
+pub struct BrowseCli {
+    #[command(flatten)]
+    pub filter: GalleryFilter,
+    /// downloads this modlist (its number in the listing or its machine url) instead of asking for one
+    #[arg(long)]
+    pub download: Option<String>,
+    /// where to save the .wabbajack file (defaults to `installation.wabbajack_file_path` from the config)
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct GalleryFilter {
+    /// only modlists for this game, e.g. `skyrimspecialedition` or `Fallout 4` (case and spaces do not matter)
+    #[arg(long)]
+    pub game: Option<String>,
+    /// only modlists with this in the title, author or machine url
+    #[arg(long)]
+    pub search: Option<String>,
+    /// only modlists whose archives take at most this much, e.g. `200GB`
+    #[arg(long, value_parser = parse_size)]
+    pub max_download_size: Option<u64>,
+    /// only modlists which take at most this much once installed, e.g. `500GB`
+    #[arg(long, value_parser = parse_size)]
+    pub max_install_size: Option<u64>,
+    /// include nsfw modlists
+    #[arg(long)]
+    pub nsfw: bool,
+}
+
+fn normalized(text: &str) -> String {
+    text.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl GalleryFilter {
+    /// modlists marked as down by their maintainers are never listed, the ones without published sizes only without a size limit
+    pub fn matches(&self, modlist: &ModlistMetadata) -> bool {
+        let sizes = modlist.download_metadata.as_ref();
+        let search = self.search.as_deref().map(str::to_lowercase);
+        (self.nsfw || !modlist.nsfw)
+            && !modlist.force_down
+            && self
+                .game
+                .as_deref()
+                .is_none_or(|game| normalized(game) == normalized(&modlist.game))
+            && search.is_none_or(|search| {
+                [&modlist.title, &modlist.author, &modlist.machine_url()]
+                    .iter()
+                    .any(|field| field.to_lowercase().contains(&search))
+            })
+            && self
+                .max_download_size
+                .is_none_or(|limit| sizes.is_some_and(|sizes| sizes.size_of_archives <= limit))
+            && self
+                .max_install_size
+                .is_none_or(|limit| sizes.is_some_and(|sizes| sizes.size_of_installed_files <= limit))
+    }
+}
+
+#[derive(Tabled)]
+struct BrowseRow {
+    #[tabled(rename = "#")]
+    number: usize,
+    #[tabled(inline)]
+    modlist: ModlistRow,
+}
+
+/// `None` when nothing was picked, or when there is no one to ask (stdin is not a terminal)
+async fn ask_for_selection() -> Result<Option<String>> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    tokio::task::spawn_blocking(|| {
+        print!("modlist to download (number or machine url, nothing to quit): ");
+        std::io::stdout().flush().context("flushing stdout")?;
+        String::new()
+            .pipe(|mut line| std::io::stdin().read_line(&mut line).map(|_| line))
+            .context("reading selection")
+            .map(|line| Some(line.trim().to_string()).filter(|line| !line.is_empty()))
+    })
+    .await
+    .context("thread crashed")
+    .and_then(|selection| selection)
+}
+
+fn select<'a>(listed: &'a [ModlistMetadata], selection: &str) -> Result<&'a ModlistMetadata> {
+    match selection.parse::<usize>() {
+        Ok(number) => number
+            .checked_sub(1)
+            .and_then(|index| listed.get(index))
+            .with_context(|| format!("there is no modlist number [{number}], pick one between 1 and {}", listed.len())),
+        Err(_) => find_modlist(listed, selection),
+    }
+}
+
+impl BrowseCli {
+    pub async fn run(self, config: Option<HoolamikeConfig>) -> Result<()> {
+        let Self { filter, download, output } = self;
+        if let Some(config) = &config {
+            configure_http_client(&config.downloaders)?;
+        }
+        let client = http_client()?;
+        let gallery = fetch_gallery(&client)
+            .await
+            .context("fetching modlist gallery")?;
+        let listed = gallery
+            .iter()
+            .filter(|modlist| filter.matches(modlist))
+            .cloned()
+            .collect_vec();
+        if listed.is_empty() {
+            anyhow::bail!(
+                "no modlist matches the filters, games in the gallery: {}",
+                gallery
+                    .iter()
+                    .map(|modlist| modlist.game.as_str())
+                    .filter(|game| !game.is_empty())
+                    .unique()
+                    .sorted()
+                    .join(", ")
+            );
+        }
+        listed
+            .iter()
+            .enumerate()
+            .map(|(index, modlist)| BrowseRow {
+                number: index + 1,
+                modlist: modlist.into(),
+            })
+            .pipe(tabled::Table::new)
+            .with(Style::modern())
+            .pipe(print_report);
+        let selection = match download {
+            Some(download) => download,
+            None => match ask_for_selection().await? {
+                Some(selection) => selection,
+                None => return Ok(()),
+            },
+        };
+        let modlist = select(&listed, &selection)?;
+        let configured = config
+            .as_ref()
+            .map(|config| config.installation.wabbajack_file_path.clone());
+        let output = output_path(output, config, modlist);
+        download_modlist(&client, modlist, &output)
+            .await
+            .map(|output| {
+                print_report(format_args!("saved [{}] to [{}]", modlist.title, output.display()));
+                match configured.is_some_and(|configured| configured == output) {
+                    true => print_report("run `hoolamike install` to install it"),
+                    false => print_report(format_args!(
+                        "set `installation.wabbajack_file_path` to [{}] in the config and run `hoolamike install` to install it",
+                        output.display()
+                    )),
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::fetch_modlist::{DownloadMetadata, ModlistLinks},
+    };
+
+    fn modlist(title: &str, game: &str, size_of_archives: u64, nsfw: bool) -> Result<ModlistMetadata> {
+        Ok(ModlistMetadata {
+            title: title.into(),
+            author: "someone".into(),
+            game: game.into(),
+            version: None,
+            nsfw,
+            force_down: false,
+            links: ModlistLinks {
+                image: String::new(),
+                readme: String::new(),
+                download: "https://example.com/modlist.wabbajack".parse()?,
+                machine_url: normalized(title),
+            },
+            download_metadata: Some(DownloadMetadata {
+                hash: "AAAAAAAAAAA=".into(),
+                size: 1024,
+                size_of_archives,
+                size_of_installed_files: size_of_archives * 2,
+            }),
+            repository: "wj-featured".into(),
+        })
+    }
+
+    #[test]
+    fn test_gallery_filter() -> Result<()> {
+        let gallery = [
+            modlist("Small Skyrim", "skyrimspecialedition", parse_size("20GB")?, false)?,
+            modlist("Big Skyrim", "skyrimspecialedition", parse_size("1.5 TiB")?, false)?,
+            modlist("Wasteland", "fallout4", parse_size("80GB")?, false)?,
+            modlist("Lewd Skyrim", "skyrimspecialedition", parse_size("10GB")?, true)?,
+        ];
+        let titles = |filter: GalleryFilter| {
+            gallery
+                .iter()
+                .filter(|modlist| filter.matches(modlist))
+                .map(|modlist| modlist.title.as_str())
+                .collect_vec()
+        };
+        assert_eq!(titles(GalleryFilter::default()), ["Small Skyrim", "Big Skyrim", "Wasteland"]);
+        assert_eq!(
+            titles(GalleryFilter {
+                game: Some("Skyrim Special Edition".into()),
+                max_download_size: Some(parse_size("100GB")?),
+                nsfw: true,
+                ..Default::default()
+            }),
+            ["Small Skyrim", "Lewd Skyrim"]
+        );
+        assert_eq!(
+            titles(GalleryFilter {
+                search: Some("WASTE".into()),
+                ..Default::default()
+            }),
+            ["Wasteland"]
+        );
+        assert_eq!(select(&gallery, "3")?.title, "Wasteland");
+        assert!(select(&gallery, "0").is_err());
+        Ok(())
+    }
+}
@@ -16,11 +16,75 @@ use {
     tracing::{info_span, instrument, warn, Instrument},
 };
 
+/// shared by every 7z process hoolamike starts
+static WRAPPED_7ZIP_CANCELLATION: once_cell::sync::Lazy<::wrapped_7zip::cancellation::CancellationToken> = once_cell::sync::Lazy::new(Default::default);
+
+/// kills the running 7z processes and waits for their extraction directories to be cleaned up
+pub fn cancel_running_extractions(timeout: std::time::Duration) {
+    WRAPPED_7ZIP_CANCELLATION.cancel();
+    if !WRAPPED_7ZIP_CANCELLATION.wait_idle(timeout) {
+        warn!(running = WRAPPED_7ZIP_CANCELLATION.running(), "some 7z processes did not stop in time");
+    }
+}
+
+/// `archives.seven_zip` section of the config, archives are opened far away from it
+static WRAPPED_7ZIP_CONFIG: once_cell::sync::Lazy<parking_lot::RwLock<crate::config_file::SevenZipConfig>> = once_cell::sync::Lazy::new(Default::default);
+
+static WRAPPED_7ZIP_TEMP_QUOTA: once_cell::sync::Lazy<parking_lot::RwLock<Option<::wrapped_7zip::TempQuota>>> = once_cell::sync::Lazy::new(Default::default);
+
+pub fn configure_wrapped_7zip(config: crate::config_file::SevenZipConfig) {
+    ::wrapped_7zip::set_max_processes(config.max_processes);
+    *WRAPPED_7ZIP_TEMP_QUOTA.write() = config
+        .max_temp_gigabytes
+        .map(|gigabytes| ::wrapped_7zip::TempQuota::new(*crate::consts::TEMP_FILE_DIR, gigabytes.saturating_mul(1024 * 1024 * 1024)));
+    *WRAPPED_7ZIP_CONFIG.write() = config;
+}
+
+pub fn log_wrapped_7zip_temp_stats() {
+    if let Some(quota) = WRAPPED_7ZIP_TEMP_QUOTA.read().as_ref() {
+        tracing::info!(stats=?quota.snapshot(), "7z temp files quota");
+    }
+}
+
+static WRAPPED_7ZIP_LIST_CACHE: once_cell::sync::Lazy<Option<::wrapped_7zip::ListCache>> = once_cell::sync::Lazy::new(|| {
+    ::wrapped_7zip::ListCache::new(&crate::consts::DATA_DIR.join("LIST_CACHE"))
+        .tap_err(|error| warn!(?error, "archive listings will not be cached"))
+        .ok()
+});
+
+/// files extracted to the temp dir are listed once and then deleted, caching them would only fill up the cache
+fn with_list_cache(wrapped: ::wrapped_7zip::Wrapped7Zip, archive: &Path) -> ::wrapped_7zip::Wrapped7Zip {
+    match (WRAPPED_7ZIP_CONFIG.read().list_cache, archive.starts_with(*crate::consts::TEMP_FILE_DIR)) {
+        (true, false) => match WRAPPED_7ZIP_LIST_CACHE.as_ref() {
+            Some(cache) => wrapped.with_list_cache(cache.clone()),
+            None => wrapped,
+        },
+        _ => wrapped,
+    }
+}
+
 fn get_wrapped_7zip_for_extension(extension: Option<&OsStr>) -> Result<::wrapped_7zip::Wrapped7Zip> {
-    match extension.and_then(|ext| ext.to_str()).map(|s| s.to_lowercase()).as_deref() {
-        Some("7z") => ::wrapped_7zip::Wrapped7Zip::find_bin(*crate::consts::TEMP_FILE_DIR, Some(1)),
-        _ => ::wrapped_7zip::Wrapped7Zip::find_bin(*crate::consts::TEMP_FILE_DIR, None),
+    let config = WRAPPED_7ZIP_CONFIG.read().clone();
+    match extension
+        .and_then(|ext| ext.to_str())
+        .map(|s| s.to_lowercase())
+        .as_deref()
+    {
+        Some("7z") => ::wrapped_7zip::Wrapped7Zip::find_bin_for(*crate::consts::TEMP_FILE_DIR, config.threads.or(Some(1)), Some("7z")),
+        other => ::wrapped_7zip::Wrapped7Zip::find_bin_for(*crate::consts::TEMP_FILE_DIR, config.threads, other),
     }
+    .map(|wrapped| {
+        wrapped
+            .with_cancellation(WRAPPED_7ZIP_CANCELLATION.clone())
+            .with_compression(::wrapped_7zip::CompressionOptions {
+                dictionary_size: config.dictionary_size,
+                level: config.compression_level,
+            })
+            .pipe(|wrapped| match WRAPPED_7ZIP_TEMP_QUOTA.read().clone() {
+                Some(quota) => wrapped.with_temp_quota(quota),
+                None => wrapped,
+            })
+    })
 }
 
 pub mod preheated_archive;
@@ -36,6 +100,8 @@ pub mod detect_lzma_method_14;
 
 pub mod forward_only_seek;
 
+pub mod passwords;
+
 pub trait ProcessArchive: Sized {
     fn list_paths(&mut self) -> Result<Vec<PathBuf>>;
     fn get_handle(&mut self, path: &Path) -> Result<self::ArchiveFileHandle>;
@@ -132,14 +198,31 @@ impl<T: ProcessArchive> FileHandleIterator<T> {
 #[enum_dispatch::enum_dispatch]
 pub enum ArchiveFileHandle {
     // CompressTools(compress_tools::CompressToolsFile),
-    Wrapped7Zip((::wrapped_7zip::list_output::ListOutputEntry, ::wrapped_7zip::ArchiveFileHandle)),
+    Wrapped7Zip((::wrapped_7zip::list_output::ListOutputEntry, self::wrapped_7zip::Wrapped7ZipFile)),
     Bethesda(self::bethesda_archive::BethesdaArchiveFile),
     CompressTools(self::compress_tools::CompressToolsFile),
     Unrar(self::unrar_rs::UnrarFile),
     Zip(self::zip::ZipFile),
 }
 
+/// temp copies of archive files keep the original modification time, it ends up on the installed files
+pub fn preserve_modified_time(path: &Path, modified: Option<std::time::SystemTime>) {
+    if let Some(modified) = modified {
+        if let Err(error) = filetime::set_file_mtime(path, filetime::FileTime::from_system_time(modified)) {
+            warn!(?error, ?path, "could not preserve modification time");
+        }
+    }
+}
+
 impl ArchiveFileHandle {
+    /// only known for the backends which report it
+    pub fn modified(&self) -> Option<std::time::SystemTime> {
+        match self {
+            ArchiveFileHandle::Wrapped7Zip((entry, _)) => Some(entry.modified_time()),
+            ArchiveFileHandle::Bethesda(_) | ArchiveFileHandle::CompressTools(_) | ArchiveFileHandle::Unrar(_) | ArchiveFileHandle::Zip(_) => None,
+        }
+    }
+
     #[tracing::instrument(skip(self))]
     pub fn size(&mut self) -> Result<u64> {
         match self {
@@ -163,12 +246,46 @@ impl ArchiveFileHandle {
 // static_assertions::assert_impl_all!(zip::ZipFile<'static>: Send, Sync);
 // static_assertions::assert_impl_all!(compress_tools::CompressToolsFile: Send, Sync);
 static_assertions::assert_impl_all!(::wrapped_7zip::ArchiveFileHandle: Send, Sync);
+static_assertions::assert_impl_all!(::wrapped_7zip::ArchiveFileStream: Send, Sync);
 static_assertions::assert_impl_all!(self::bethesda_archive::BethesdaArchiveFile: Send, Sync);
 static_assertions::assert_impl_all!(ArchiveFileHandle: Send, Sync);
 
+/// another backend only helps when 7z could not run or its output could not be read, a corrupted archive,
+/// a wrong password or a missing path stay that way whichever backend opens the archive
+fn worth_trying_next(reason: &anyhow::Error) -> bool {
+    use self::wrapped_7zip::Wrapped7ZipError;
+    match Wrapped7ZipError::of(reason) {
+        Some(Wrapped7ZipError::BadPassword(_) | Wrapped7ZipError::Corrupted { .. } | Wrapped7ZipError::NotFound { .. }) => false,
+        Some(Wrapped7ZipError::ParseFailure { .. } | Wrapped7ZipError::SpawnFailure { .. }) | None => true,
+    }
+}
+
+trait OrTryNext<T> {
+    /// falls through to the next backend of the chain, unless the failure is final
+    fn or_try_next(self, next: impl FnOnce(anyhow::Error) -> Result<T>) -> Result<T>;
+}
+
+impl<T> OrTryNext<T> for Result<T> {
+    fn or_try_next(self, next: impl FnOnce(anyhow::Error) -> Result<T>) -> Result<T> {
+        self.or_else(|reason| match worth_trying_next(&reason) {
+            true => next(reason),
+            false => {
+                tracing::debug!(?reason, "not trying other backends");
+                Err(reason)
+            }
+        })
+    }
+}
+
 impl ArchiveHandle<'_> {
-    /// this is literally bruteforce approach
+    /// this is literally bruteforce approach, it stops early when the archive itself is the problem (see [worth_trying_next])
     pub fn with_guessed<T, F: FnMut(Self) -> Result<T> + Send + Sync>(path: &Path, extension: Option<&OsStr>, mut with_guessed: F) -> anyhow::Result<T> {
+        let password = passwords::password_for(path);
+        let open_with_7z = || {
+            get_wrapped_7zip_for_extension(extension)
+                .map(|wrapped| with_list_cache(wrapped, path))
+                .and_then(|wrapped| wrapped.open_file_with_password(path, password.clone()))
+        };
         match extension
             .map(|ext| ext.to_string_lossy())
             .map(|b| b.to_lowercase())
@@ -187,23 +304,23 @@ impl ArchiveHandle<'_> {
                         .and_then(&mut with_guessed)
                         .tap_err(|message| tracing::warn!("could not open archive with UnRar: {message:?}"))
                 })
-                .or_else(|reason| {
+                .or_try_next(|reason| {
                     self::zip::ZipArchive::new(path)
                         .map(Self::Zip)
                         .and_then(&mut with_guessed)
                         .with_context(|| format!("trying because: {reason:?}"))
                         .tap_err(|message| tracing::warn!("could not open archive with Zip: {message:?}"))
                 })
-                .or_else(|reason| {
+                .or_try_next(|reason| {
                     path.open_file_read()
                         .and_then(|(_, file)| self::compress_tools::ArchiveHandle::new(file).map(Self::CompressTools))
                         .and_then(&mut with_guessed)
                         .with_context(|| format!("trying because: {reason:?}"))
                         .tap_err(|message| tracing::warn!("could not open archive with CompressTools: {message:?}"))
                 })
-                .or_else(|reason| {
-                    get_wrapped_7zip_for_extension(extension)
-                        .and_then(|wrapped| wrapped.open_file(path).map(Self::Wrapped7Zip))
+                .or_try_next(|reason| {
+                    open_with_7z()
+                        .map(Self::Wrapped7Zip)
                         .and_then(&mut with_guessed)
                         .with_context(|| format!("trying because: {reason:?}"))
                         .tap_err(|message| tracing::warn!("could not open archive with 7z: {message:?}"))
@@ -212,7 +329,7 @@ impl ArchiveHandle<'_> {
                 .or_else(|reason| {
                     path.open_file_read()
                         .and_then(|(_, file)| {
-                            self::sevenz::SevenZipArchive::new(file, "".into())
+                            self::sevenz::SevenZipArchive::new(file, password.as_deref().unwrap_or_default().into())
                                 .context("opening archive with SevenzRust2 library")
                                 .map(Box::new)
                                 .map(Self::SevenzRust2)
@@ -221,16 +338,16 @@ impl ArchiveHandle<'_> {
                         .with_context(|| format!("trying because: {reason:?}"))
                         .tap_err(|message| tracing::warn!("could not open archive with SevenzRust2: {message:?}"))
                 })
-                .or_else(|reason| {
+                .or_try_next(|reason| {
                     path.open_file_read()
                         .and_then(|(_, file)| self::compress_tools::ArchiveHandle::new(file).map(Self::CompressTools))
                         .and_then(&mut with_guessed)
                         .with_context(|| format!("trying because: {reason:?}"))
                         .tap_err(|message| tracing::warn!("could not open archive with CompressTools: {message:?}"))
                 })
-                .or_else(|reason| {
-                    get_wrapped_7zip_for_extension(extension)
-                        .and_then(|wrapped| wrapped.open_file(path).map(Self::Wrapped7Zip))
+                .or_try_next(|reason| {
+                    open_with_7z()
+                        .map(Self::Wrapped7Zip)
                         .and_then(&mut with_guessed)
                         .with_context(|| format!("trying because: {reason:?}"))
                         .tap_err(|message| tracing::warn!("could not open archive with 7z: {message:?}"))
@@ -242,17 +359,17 @@ impl ArchiveHandle<'_> {
                         .and_then(&mut with_guessed)
                         .tap_err(|message| tracing::warn!("could not open archive with Zip: {message:?}"))
                 })
-                .or_else(|reason| {
+                .or_try_next(|reason| {
                     path.open_file_read()
                         .and_then(|(_, file)| self::compress_tools::ArchiveHandle::new(file).map(Self::CompressTools))
                         .and_then(&mut with_guessed)
                         .with_context(|| format!("trying because: {reason:?}"))
                         .tap_err(|message| tracing::warn!("could not open archive with CompressTools: {message:?}"))
                 })
-                .or_else(|reason| {
+                .or_try_next(|reason| {
                     path.open_file_read()
                         .and_then(|(_, file)| {
-                            self::sevenz::SevenZipArchive::new(file, "".into())
+                            self::sevenz::SevenZipArchive::new(file, password.as_deref().unwrap_or_default().into())
                                 .context("opening archive with SevenzRust2 library")
                                 .map(Box::new)
                                 .map(Self::SevenzRust2)
@@ -261,9 +378,9 @@ impl ArchiveHandle<'_> {
                         .with_context(|| format!("trying because: {reason:?}"))
                         .tap_err(|message| tracing::warn!("could not open archive with SevenzRust2: {message:?}"))
                 })
-                .or_else(|reason| {
-                    get_wrapped_7zip_for_extension(extension)
-                        .and_then(|wrapped| wrapped.open_file(path).map(Self::Wrapped7Zip))
+                .or_try_next(|reason| {
+                    open_with_7z()
+                        .map(Self::Wrapped7Zip)
                         .and_then(&mut with_guessed)
                         .with_context(|| format!("trying because: {reason:?}"))
                         .tap_err(|message| tracing::warn!("could not open archive with 7z: {message:?}"))
@@ -278,7 +395,7 @@ impl ArchiveHandle<'_> {
                             .and_then(&mut with_guessed)
                             .tap_err(|message| tracing::warn!("could not open archive with Bethesda Archive Extractor: {message:?}"))
                     })
-                    .or_else(|err| {
+                    .or_try_next(|err| {
                         unrar_rs::ArchiveHandle::new(path)
                             .context("reading rar")
                             .map(Self::Unrar)
@@ -286,17 +403,17 @@ impl ArchiveHandle<'_> {
                             .with_context(|| format!("because: {err:#?}"))
                             .tap_err(|message| tracing::warn!("could not open archive with Unrar: {message:?}"))
                     })
-                    .or_else(|err| {
+                    .or_try_next(|err| {
                         path.open_file_read()
                             .and_then(|(_, file)| self::compress_tools::ArchiveHandle::new(file).map(Self::CompressTools))
                             .and_then(&mut with_guessed)
                             .with_context(|| format!("because: {err:#?}"))
                             .tap_err(|message| tracing::warn!("could not open archive with CompressTools: {message:?}"))
                     })
-                    .or_else(|reason| {
+                    .or_try_next(|reason| {
                         path.open_file_read()
                             .and_then(|(_, file)| {
-                                self::sevenz::SevenZipArchive::new(file, "".into())
+                                self::sevenz::SevenZipArchive::new(file, password.as_deref().unwrap_or_default().into())
                                     .context("opening archive with SevenzRust2 library")
                                     .map(Box::new)
                                     .map(Self::SevenzRust2)
@@ -305,18 +422,19 @@ impl ArchiveHandle<'_> {
                             .with_context(|| format!("trying because: {reason:?}"))
                             .tap_err(|message| tracing::warn!("could not open archive with SevenzRust2: {message:?}"))
                     })
-                    .or_else(|err| {
-                        get_wrapped_7zip_for_extension(extension)
-                            .and_then(|wrapped| wrapped.open_file(path).map(Self::Wrapped7Zip))
+                    .or_try_next(|err| {
+                        open_with_7z()
+                            .map(Self::Wrapped7Zip)
                             .and_then(&mut with_guessed)
                             .with_context(|| format!("because: {err:#?}"))
                             .tap_err(|message| tracing::warn!("could not open archive with 7z: {message:?}"))
                     })
-                    .map_err(|error| anyhow::anyhow!("no defined archive handler could handle this file\n\n[{error:?}]"))
+                    .context("no defined archive handler could handle this file")
                     .with_context(|| format!("because no defined extension matched [{other:?}]"))
             }
         }
         .with_context(|| format!("no defined archive handler could handle this file: [{path:?}]"))
+        .map_err(|error| self::wrapped_7zip::explain_error(error, path))
     }
 }
 
@@ -324,7 +442,7 @@ impl std::io::Read for ArchiveFileHandle {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
             // ArchiveFileHandle::CompressTools(compress_tools_seek) => compress_tools_seek.read(buf),
-            ArchiveFileHandle::Wrapped7Zip(wrapped_7zip) => wrapped_7zip.1.file.read(buf),
+            ArchiveFileHandle::Wrapped7Zip(wrapped_7zip) => wrapped_7zip.1.read(buf),
             ArchiveFileHandle::Bethesda(bethesda_archive_file) => bethesda_archive_file.read(buf),
             ArchiveFileHandle::CompressTools(compress_tools_file) => compress_tools_file.read(buf),
             ArchiveFileHandle::Unrar(temp_path) => temp_path.read(buf),
@@ -464,6 +582,30 @@ mod tests {
         futures::{StreamExt, TryFutureExt, TryStreamExt},
         std::io::BufReader,
     };
+
+    #[test]
+    fn test_chain_stops_on_final_failures() {
+        let archive = PathBuf::from("/downloads/broken.7z");
+        let tried = std::cell::Cell::new(false);
+        let try_next = |error: anyhow::Error| {
+            tried.set(false);
+            Err::<(), _>(error)
+                .or_try_next(|reason| {
+                    tried.set(true);
+                    Err(reason)
+                })
+                .ok();
+            tried.get()
+        };
+        assert!(!try_next(
+            anyhow::anyhow!("7z failed").context(self::wrapped_7zip::Wrapped7ZipError::Corrupted { archive: archive.clone() })
+        ));
+        assert!(try_next(
+            anyhow::anyhow!("7z failed").context(self::wrapped_7zip::Wrapped7ZipError::ParseFailure { archive: archive.clone() })
+        ));
+        assert!(try_next(anyhow::anyhow!("not a zip archive")));
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_seek_with_tempfile() -> Result<()> {
         [
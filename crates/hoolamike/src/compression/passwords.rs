@@ -0,0 +1,69 @@
+//! some older mod downloads are password protected archives, their passwords come from the `archives.passwords` section of the config
+use {
+    crate::config_file::CONFIG_FILE_NAME,
+    once_cell::sync::Lazy,
+    parking_lot::RwLock,
+    std::{collections::BTreeMap, path::Path},
+    wrapped_7zip::{PasswordError, Wrapped7ZipError},
+};
+
+/// archives are opened deep inside the directive handlers, far away from the config
+static ARCHIVE_PASSWORDS: Lazy<RwLock<BTreeMap<String, String>>> = Lazy::new(Default::default);
+
+fn lookup_key(archive_name: &str) -> String {
+    archive_name.to_lowercase()
+}
+
+pub fn set_archive_passwords(passwords: impl IntoIterator<Item = (String, String)>) {
+    *ARCHIVE_PASSWORDS.write() = passwords
+        .into_iter()
+        .map(|(archive_name, password)| (lookup_key(&archive_name), password))
+        .collect();
+}
+
+pub fn password_for(archive: &Path) -> Option<String> {
+    archive
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .and_then(|name| ARCHIVE_PASSWORDS.read().get(&lookup_key(&name)).cloned())
+}
+
+/// tells the user what to put in the config instead of a wall of 7z output
+pub fn explain_password_error(error: anyhow::Error, archive: &Path) -> anyhow::Error {
+    let name = archive
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| archive.display().to_string());
+    match Wrapped7ZipError::of(&error) {
+        Some(Wrapped7ZipError::BadPassword(PasswordError::Missing { .. })) => error.context(format!(
+            "[{name}] is password protected, add its password to [{CONFIG_FILE_NAME}] under `archives.passwords` (eg. `\"{name}\": <password>`)"
+        )),
+        Some(Wrapped7ZipError::BadPassword(PasswordError::Wrong { .. })) => {
+            error.context(format!("the password for [{name}] in [{CONFIG_FILE_NAME}] (`archives.passwords`) is wrong"))
+        }
+        _ => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::path::PathBuf};
+
+    #[test]
+    fn test_password_lookup_ignores_case_and_directories() {
+        set_archive_passwords([("Old Mod-123.RAR".to_string(), "hunter2".to_string())]);
+        assert_eq!(password_for(Path::new("/downloads/old mod-123.rar")).as_deref(), Some("hunter2"));
+        assert_eq!(password_for(Path::new("/downloads/other.rar")), None);
+    }
+
+    #[test]
+    fn test_missing_password_is_explained() {
+        let archive = PathBuf::from("/downloads/protected.7z");
+        let error = anyhow::anyhow!("7z failed")
+            .context(Wrapped7ZipError::BadPassword(PasswordError::Missing { archive: archive.clone() }))
+            .context("trying because: ...");
+        let explained = explain_password_error(error, &archive).to_string();
+        assert!(explained.contains("protected.7z"), "{explained}");
+        assert!(explained.contains("archives.passwords"), "{explained}");
+    }
+}
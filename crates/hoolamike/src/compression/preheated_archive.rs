@@ -1,8 +1,8 @@
 use {
     super::{ProcessArchive, SeekWithTempFileExt},
-    crate::compression::ArchiveHandle,
+    crate::{compression::ArchiveHandle, utils::MaybeWindowsPath},
     anyhow::{Context, Result},
-    itertools::Itertools,
+    itertools::{Either, Itertools},
     rayon::iter::{IntoParallelRefIterator, ParallelIterator},
     std::{
         collections::BTreeMap,
@@ -15,45 +15,96 @@ use {
 #[derive(Debug)]
 pub struct PreheatedArchive {
     pub paths: BTreeMap<PathBuf, TempPath>,
+    /// keeps the directory alive when the whole archive was extracted at once, the paths live inside of it
+    _extraction_dir: Option<tempfile::TempDir>,
 }
 
 impl PreheatedArchive {
+    /// single 7z call for the whole archive, solid blocks get decompressed only once instead of once per chunk
     #[instrument]
-    pub fn from_archive_concurrent(archive: &Path, chunk_size: usize) -> Result<Self> {
-        ArchiveHandle::with_guessed(archive, archive.extension(), |mut a| a.list_paths()).and_then(|paths| {
-            paths
-                .chunks(chunk_size)
-                .collect_vec()
-                .par_iter()
-                .copied()
-                .map(move |chunk| {
-                    ArchiveHandle::with_guessed(archive, archive.extension(), |mut archive| {
-                        archive
-                            .get_many_handles(chunk.iter().map(|p| p.as_path()).collect_vec().as_slice())
-                            .context("getting many handles")
-                    })
-                    .and_then(|handles| {
-                        handles
+    fn from_wrapped_7zip(archive: &::wrapped_7zip::ArchiveHandle) -> Result<Self> {
+        tempfile::Builder::new()
+            .prefix("preheated-")
+            .tempdir_in(*crate::consts::TEMP_FILE_DIR)
+            .context("creating extraction directory")
+            .and_then(|extraction_dir| {
+                archive
+                    .extract_all(extraction_dir.path())
+                    .and_then(|extracted| {
+                        extracted
                             .into_iter()
-                            .map(|(path, handle)| {
-                                handle
-                                    .seek_with_temp_file_blocking_raw(0)
-                                    .context("preheating file")
-                                    .map(|(_, handle)| (path, handle))
+                            .map(|path| {
+                                path.strip_prefix(extraction_dir.path())
+                                    .with_context(|| format!("[{}] was extracted outside of the extraction directory", path.display()))
+                                    .map(|relative| MaybeWindowsPath(relative.display().to_string()).into_path())
+                                    .map(|relative| (relative, TempPath::from_path(&path)))
                             })
                             .collect::<Result<BTreeMap<_, _>>>()
-                            .context("some files could not be preheated")
                     })
+                    .map(|paths| Self {
+                        paths,
+                        _extraction_dir: Some(extraction_dir),
+                    })
+            })
+    }
+
+    #[instrument]
+    pub fn from_archive_concurrent(archive: &Path, chunk_size: usize) -> Result<Self> {
+        ArchiveHandle::with_guessed(archive, archive.extension(), |a| match a {
+            ArchiveHandle::Wrapped7Zip(wrapped_7zip) => Self::from_wrapped_7zip(&wrapped_7zip).map(Either::Left),
+            mut other => other.list_paths().map(Either::Right),
+        })
+        .and_then(|preheated_or_paths| match preheated_or_paths {
+            Either::Left(preheated) => Ok(preheated),
+            Either::Right(paths) => Self::from_paths_concurrent(archive, paths, chunk_size),
+        })
+    }
+
+    /// only the `needed` files get extracted, 7z archives still get a single call for all of them
+    /// so that their solid blocks are decompressed once
+    #[instrument(skip(needed), fields(needed=%needed.len()))]
+    pub fn from_archive_paths_concurrent(archive: &Path, needed: Vec<PathBuf>, chunk_size: usize) -> Result<Self> {
+        ArchiveHandle::with_guessed(archive, archive.extension(), |a| Ok(matches!(a, ArchiveHandle::Wrapped7Zip(_))))
+            .map(|single_call| match single_call {
+                true => needed.len().max(1),
+                false => chunk_size,
+            })
+            .and_then(|chunk_size| Self::from_paths_concurrent(archive, needed, chunk_size))
+    }
+
+    fn from_paths_concurrent(archive: &Path, paths: Vec<PathBuf>, chunk_size: usize) -> Result<Self> {
+        paths
+            .chunks(chunk_size)
+            .collect_vec()
+            .par_iter()
+            .copied()
+            .map(move |chunk| {
+                ArchiveHandle::with_guessed(archive, archive.extension(), |mut archive| {
+                    archive
+                        .get_many_handles(chunk.iter().map(|p| p.as_path()).collect_vec().as_slice())
+                        .context("getting many handles")
                 })
-                .collect::<Result<Vec<_>>>()
-                .context("some chunks failed")
-                .map(|chunks| {
-                    chunks
+                .and_then(|handles| {
+                    handles
                         .into_iter()
-                        .fold(BTreeMap::new(), |acc, next| acc.tap_mut(|acc| acc.extend(next)))
+                        .map(|(path, handle)| {
+                            handle
+                                .seek_with_temp_file_blocking_raw(0)
+                                .context("preheating file")
+                                .map(|(_, handle)| (path, handle))
+                        })
+                        .collect::<Result<BTreeMap<_, _>>>()
+                        .context("some files could not be preheated")
                 })
-                .map(|paths| Self { paths })
-        })
+            })
+            .collect::<Result<Vec<_>>>()
+            .context("some chunks failed")
+            .map(|chunks| {
+                chunks
+                    .into_iter()
+                    .fold(BTreeMap::new(), |acc, next| acc.tap_mut(|acc| acc.extend(next)))
+            })
+            .map(|paths| Self { paths, _extraction_dir: None })
     }
 
     pub fn from_archive(archive: &mut impl ProcessArchive) -> Result<Self> {
@@ -72,7 +123,7 @@ impl PreheatedArchive {
                         })
                         .collect::<Result<BTreeMap<_, _>>>()
                         .context("some files could not be preheated")
-                        .map(|paths| Self { paths })
+                        .map(|paths| Self { paths, _extraction_dir: None })
                 })
         })
     }
@@ -1,6 +1,55 @@
-pub use ::wrapped_7zip::{ArchiveFileHandle, ArchiveHandle};
+pub use ::wrapped_7zip::{ArchiveFileHandle, ArchiveFileStream, ArchiveHandle, Wrapped7ZipError};
+use {super::*, std::io::Read, tracing_indicatif::span_ext::IndicatifSpanExt};
+
+/// 7z is the last resort of every fallback chain, so its error tells the most about what is wrong with the archive
+pub fn explain_error(error: anyhow::Error, archive: &Path) -> anyhow::Error {
+    match Wrapped7ZipError::of(&error).cloned() {
+        Some(Wrapped7ZipError::BadPassword(_)) => super::passwords::explain_password_error(error, archive),
+        Some(Wrapped7ZipError::SpawnFailure { binary }) => error.context(format!(
+            "7z ([{}]) is used for archives the built-in extractors cannot handle, make sure it is installed",
+            binary.display()
+        )),
+        Some(Wrapped7ZipError::Corrupted { .. }) => error.context(format!(
+            "[{}] seems to be corrupted, remove it from the downloads directory so that it gets downloaded again",
+            archive.display()
+        )),
+        Some(Wrapped7ZipError::NotFound { .. } | Wrapped7ZipError::ParseFailure { .. }) | None => error,
+    }
+}
+
+/// archive types worth a deep check, the rest of the downloads are plain files (or bethesda archives which 7z cannot read)
+const TESTABLE_EXTENSIONS: &[&str] = &["7z", "zip", "rar"];
+
+pub fn is_testable(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| TESTABLE_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// decompresses the whole archive with `7z t`, any file failing its CRC check is an error
+pub fn test_archive(path: &Path) -> Result<::wrapped_7zip::TestReport> {
+    get_wrapped_7zip_for_extension(path.extension())
+        .and_then(|wrapped| wrapped.open_file_with_password(path, super::passwords::password_for(path)))
+        .and_then(|archive| archive.test())
+        .and_then(|report| report.ok())
+        .map_err(|error| explain_error(error, path))
+}
+
+/// single files are streamed out of 7z, batches are extracted at once so that solid archives are decompressed only once
+pub enum Wrapped7ZipFile {
+    Extracted(ArchiveFileHandle),
+    Streamed(ArchiveFileStream),
+}
+
+impl std::io::Read for Wrapped7ZipFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Wrapped7ZipFile::Extracted(extracted) => extracted.file.read(buf),
+            Wrapped7ZipFile::Streamed(streamed) => streamed.read(buf),
+        }
+    }
+}
 
-use super::*;
 impl ProcessArchive for ::wrapped_7zip::ArchiveHandle {
     fn list_paths(&mut self) -> Result<Vec<PathBuf>> {
         self.list_files()
@@ -8,7 +57,33 @@ impl ProcessArchive for ::wrapped_7zip::ArchiveHandle {
     }
 
     fn get_handle(&mut self, path: &Path) -> Result<super::ArchiveFileHandle> {
-        self.get_file(path)
-            .map(super::ArchiveFileHandle::Wrapped7Zip)
+        self.stream_file(path)
+            .map(|(entry, streamed)| super::ArchiveFileHandle::Wrapped7Zip((entry, Wrapped7ZipFile::Streamed(streamed))))
+    }
+
+    fn get_many_handles(&mut self, paths: &[&Path]) -> Result<Vec<(PathBuf, super::ArchiveFileHandle)>> {
+        // 7z matches the paths case insensitively, callers expect the paths they asked for
+        let requested = paths
+            .iter()
+            .map(|path| (path.display().to_string().to_lowercase(), path.to_path_buf()))
+            .collect::<std::collections::HashMap<_, _>>();
+        let extracting = tracing::info_span!("extracting_with_7z", file_count=%paths.len()).tap(|pb| {
+            pb.pb_set_style(&crate::progress_bars_v2::count_progress_style());
+            pb.pb_set_length(100);
+        });
+        let _entered = extracting.enter();
+        self.get_many_handles_with_progress(paths, |percentage| extracting.pb_set_position(percentage as _))
+            .and_then(|handles| {
+                handles
+                    .into_iter()
+                    .map(|(entry, extracted)| {
+                        requested
+                            .get(&entry.path.display().to_string().to_lowercase())
+                            .cloned()
+                            .with_context(|| format!("7z extracted [{}] which was not requested", entry.path.display()))
+                            .map(|path| (path, super::ArchiveFileHandle::Wrapped7Zip((entry, Wrapped7ZipFile::Extracted(extracted)))))
+                    })
+                    .collect()
+            })
     }
 }
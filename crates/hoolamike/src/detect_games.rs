@@ -0,0 +1,484 @@
+//! `hoolamike detect-games` looks for the supported games in the steam libraries and the stores managed by heroic (gog, epic),
+//! so that `games.<name>.root_directory` does not have to be typed by hand. games running through proton or wine get their prefix reported as well
+use {
+    crate::{
+        config_file::{GameConfig, GamesConfig, HoolamikeConfig},
+        modlist_json::GameName,
+        progress_bars_v2::events::print_report,
+    },
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+    tabled::{settings::Style, Tabled},
+    tap::prelude::*,
+    tracing::{debug, info},
+    vdf::Vdf,
+};
+
+pub mod heroic;
+pub mod vdf;
+
+#[derive(Debug, Clone, Copy)]
+pub struct KnownGame {
+    pub name: &'static str,
+    /// relative to the game root, its version resource is the version wabbajack records for game files
+    pub executable: &'static str,
+    /// fallout 3 is sold both as the base game and the GOTY edition
+    pub steam_app_ids: &'static [u32],
+    pub gog_product_ids: &'static [&'static str],
+    /// epic app names mean nothing, the store title is matched instead (lowercase, letters and digits only)
+    pub epic_title_prefix: Option<&'static str>,
+}
+
+/// the games modlists are installed for
+pub const KNOWN_GAMES: &[KnownGame] = &[
+    KnownGame {
+        name: "SkyrimSpecialEdition",
+        executable: "SkyrimSE.exe",
+        steam_app_ids: &[489830],
+        gog_product_ids: &["1711230643"],
+        epic_title_prefix: None,
+    },
+    KnownGame {
+        name: "Fallout4",
+        executable: "Fallout4.exe",
+        steam_app_ids: &[377160],
+        gog_product_ids: &["1998527297"],
+        epic_title_prefix: Some("fallout4"),
+    },
+    KnownGame {
+        name: "FalloutNewVegas",
+        executable: "FalloutNV.exe",
+        steam_app_ids: &[22380],
+        gog_product_ids: &["1454587428"],
+        epic_title_prefix: Some("falloutnewvegas"),
+    },
+    KnownGame {
+        name: "Fallout3",
+        executable: "Fallout3.exe",
+        steam_app_ids: &[22300, 22370],
+        gog_product_ids: &["1454315831"],
+        epic_title_prefix: Some("fallout3"),
+    },
+];
+
+pub fn known_game(game: &GameName) -> Option<&'static KnownGame> {
+    KNOWN_GAMES
+        .iter()
+        .find(|known| known.name == game.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+pub enum GameStore {
+    #[display("steam")]
+    Steam,
+    #[display("gog")]
+    Gog,
+    #[display("epic")]
+    Epic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedGame {
+    pub game: GameName,
+    pub store: GameStore,
+    pub app_id: String,
+    pub root_directory: PathBuf,
+    /// proton prefix for steam games (only there once the game was started), wine prefix configured in heroic for the others
+    pub prefix: Option<PathBuf>,
+}
+
+/// where steam installs itself by default, the flatpak and snap packages included
+pub fn default_steam_roots() -> Vec<PathBuf> {
+    #[cfg(target_os = "linux")]
+    {
+        directories::UserDirs::new()
+            .map(|dirs| dirs.home_dir().to_owned())
+            .into_iter()
+            .flat_map(|home| {
+                [
+                    ".steam/steam",
+                    ".local/share/Steam",
+                    ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+                    "snap/steam/common/.local/share/Steam",
+                ]
+                .map(|steam| home.join(steam))
+            })
+            .collect()
+    }
+    #[cfg(target_os = "macos")]
+    {
+        directories::UserDirs::new()
+            .map(|dirs| dirs.home_dir().join("Library/Application Support/Steam"))
+            .into_iter()
+            .collect()
+    }
+    #[cfg(target_os = "windows")]
+    {
+        ["ProgramFiles(x86)", "ProgramFiles"]
+            .into_iter()
+            .filter_map(std::env::var_os)
+            .map(|directory| PathBuf::from(directory).join("Steam"))
+            .collect()
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        Vec::new()
+    }
+}
+
+fn read_vdf(path: &Path) -> Result<Vdf> {
+    std::fs::read_to_string(path)
+        .context("reading")
+        .and_then(|contents| vdf::parse(&contents))
+        .with_context(|| format!("loading [{}]", path.display()))
+}
+
+/// every library of the steam installation at `steam_root`, the installation itself is always one of them
+pub fn library_folders(steam_root: &Path) -> Result<Vec<PathBuf>> {
+    read_vdf(&steam_root.join("steamapps").join("libraryfolders.vdf")).map(|vdf| {
+        std::iter::once(steam_root.to_owned())
+            .chain(
+                vdf.get("libraryfolders")
+                    .map(Vdf::entries)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|(_, library)| library.get("path").and_then(Vdf::as_str).map(PathBuf::from)),
+            )
+            .collect()
+    })
+}
+
+/// the game has to be fully installed, an appmanifest is written as soon as the download starts
+fn detect_in_library(library: &Path, game: &KnownGame, app_id: u32) -> Option<DetectedGame> {
+    let steamapps = library.join("steamapps");
+    read_vdf(&steamapps.join(format!("appmanifest_{app_id}.acf")))
+        .tap_err(|reason| debug!(?reason, game=%game.name, "not installed in [{}]", library.display()))
+        .ok()?
+        .get("AppState")?
+        .get("installdir")?
+        .as_str()
+        .map(|install_dir| steamapps.join("common").join(install_dir))
+        .filter(|root_directory| root_directory.is_dir())
+        .map(|root_directory| DetectedGame {
+            game: GameName::new(game.name.to_string()),
+            store: GameStore::Steam,
+            app_id: app_id.to_string(),
+            root_directory,
+            prefix: steamapps
+                .join("compatdata")
+                .join(app_id.to_string())
+                .join("pfx")
+                .pipe(Some)
+                .filter(|prefix| prefix.is_dir()),
+        })
+}
+
+/// the first library a game is found in wins, the same library is often listed by more than one steam root (`~/.steam/steam` is a symlink)
+pub fn detect_steam_games(steam_roots: &[PathBuf]) -> Vec<DetectedGame> {
+    let libraries = steam_roots
+        .iter()
+        .filter(|root| root.is_dir())
+        .flat_map(|root| {
+            library_folders(root)
+                .tap_err(|reason| debug!(?reason, "no libraries found in [{}]", root.display()))
+                .unwrap_or_else(|_| vec![root.clone()])
+        })
+        .unique_by(|library| library.canonicalize().unwrap_or_else(|_| library.clone()))
+        .collect_vec();
+    KNOWN_GAMES
+        .iter()
+        .filter_map(|game| {
+            libraries
+                .iter()
+                .cartesian_product(game.steam_app_ids.iter())
+                .find_map(|(library, app_id)| detect_in_library(library, game, *app_id))
+        })
+        .collect()
+}
+
+/// steam is looked at first, a game owned in more than one store is taken from the first one it is found in
+pub fn detect_games(steam_roots: &[PathBuf], heroic_roots: &[PathBuf]) -> Vec<DetectedGame> {
+    detect_steam_games(steam_roots)
+        .into_iter()
+        .chain(heroic::detect_heroic_games(heroic_roots))
+        .unique_by(|detected| detected.game.clone())
+        .collect()
+}
+
+/// detected games fill in whatever is missing or points nowhere, a root directory which exists was chosen on purpose
+pub fn merge_detected(games: &mut GamesConfig, detected: &[DetectedGame]) -> Vec<GameName> {
+    detected
+        .iter()
+        .filter(|detected| {
+            games
+                .get(&detected.game)
+                .is_none_or(|configured| !configured.root_directory.is_dir())
+        })
+        .map(|detected| {
+            games.insert(
+                detected.game.clone(),
+                GameConfig {
+                    root_directory: detected.root_directory.clone(),
+                },
+            );
+            detected.game.clone()
+        })
+        .collect()
+}
+
+#[derive(Tabled)]
+struct DetectedGameRow {
+    game: String,
+    store: GameStore,
+    app_id: String,
+    root_directory: String,
+    prefix: String,
+}
+
+#[derive(clap::Args)]
+pub struct DetectGamesCli {
+    /// steam installation to look in instead of the default locations, can be passed more than once
+    #[arg(long)]
+    pub steam_root: Vec<PathBuf>,
+    /// heroic config directory to look in instead of the default locations, can be passed more than once
+    #[arg(long)]
+    pub heroic_root: Vec<PathBuf>,
+    /// writes the detected games into the config, entries pointing at a directory which exists are left alone,
+    /// only the `games` section is rewritten (comments inside of it included)
+    #[arg(long)]
+    pub write: bool,
+}
+
+fn games_section(games: &GamesConfig) -> Result<String> {
+    BTreeMap::from([("games", games)])
+        .pipe_ref(serde_yaml::to_string)
+        .context("serializing games")
+}
+
+/// the comments and the layout of everything around the `games` section stay as they are
+fn with_games_section(config: &str, games: &GamesConfig) -> Result<String> {
+    let section = games_section(games)?;
+    let lines = config.lines().collect_vec();
+    let is_top_level = |line: &&str| line.starts_with(|c: char| !c.is_whitespace());
+    let patched = match lines.iter().position(|line| line.starts_with("games:")) {
+        Some(start) => {
+            let end = lines[start + 1..]
+                .iter()
+                .position(is_top_level)
+                .map(|offset| start + 1 + offset)
+                .unwrap_or(lines.len());
+            // blank lines in front of the next section belong to it
+            let end = (start + 1..end)
+                .rev()
+                .find(|line| !lines[*line].trim().is_empty())
+                .map(|line| line + 1)
+                .unwrap_or(start + 1);
+            lines[..start]
+                .iter()
+                .map(|line| format!("{line}\n"))
+                .chain(std::iter::once(section))
+                .chain(lines[end..].iter().map(|line| format!("{line}\n")))
+                .collect::<String>()
+        }
+        None => match config.is_empty() || config.ends_with('\n') {
+            true => format!("{config}{section}"),
+            false => format!("{config}\n{section}"),
+        },
+    };
+    // whatever the section looked like, the file has to read back as the same games
+    serde_yaml::from_str::<HoolamikeConfig>(&patched)
+        .context("parsing the patched config")
+        .and_then(|config| games_section(&config.games))
+        .and_then(|written| match written == games_section(games)? {
+            true => Ok(patched),
+            false => Err(anyhow::anyhow!("the patched config reads back as different games:\n{written}")),
+        })
+}
+
+impl DetectGamesCli {
+    pub fn run(self, config_path: &Path) -> Result<()> {
+        let Self {
+            steam_root,
+            heroic_root,
+            write,
+        } = self;
+        let steam_roots = match steam_root.is_empty() {
+            true => default_steam_roots(),
+            false => steam_root,
+        };
+        let heroic_roots = match heroic_root.is_empty() {
+            true => heroic::default_heroic_roots(),
+            false => heroic_root,
+        };
+        let detected = detect_games(&steam_roots, &heroic_roots);
+        if detected.is_empty() {
+            anyhow::bail!(
+                "none of the supported games were found in steam libraries of {steam_roots:?} nor in heroic at {heroic_roots:?}, pass `--steam-root` or \
+                 `--heroic-root` if they are installed somewhere else",
+            );
+        }
+        detected
+            .iter()
+            .map(|detected| DetectedGameRow {
+                game: detected.game.to_string(),
+                store: detected.store,
+                app_id: detected.app_id.clone(),
+                root_directory: detected.root_directory.display().to_string(),
+                prefix: detected
+                    .prefix
+                    .as_ref()
+                    .map(|prefix| prefix.display().to_string())
+                    .unwrap_or_default(),
+            })
+            .pipe(tabled::Table::new)
+            .with(Style::modern())
+            .pipe(print_report);
+
+        match write {
+            true => {
+                let (config_path, mut config) = HoolamikeConfig::find(config_path).context("reading hoolamike config file")?;
+                match merge_detected(&mut config.games, &detected) {
+                    updated if updated.is_empty() => info!("every detected game is already configured in [{}]", config_path.display()),
+                    updated => std::fs::read_to_string(&config_path)
+                        .context("reading")
+                        .and_then(|contents| with_games_section(&contents, &config.games))
+                        .and_then(|patched| std::fs::write(&config_path, patched).context("writing"))
+                        .with_context(|| format!("updating the games in [{}], add them by hand instead", config_path.display()))
+                        .tap_ok(|_| info!(?updated, "updated [{}]", config_path.display()))?,
+                }
+                Ok(())
+            }
+            false => detected
+                .iter()
+                .map(|detected| {
+                    (
+                        detected.game.clone(),
+                        GameConfig {
+                            root_directory: detected.root_directory.clone(),
+                        },
+                    )
+                })
+                .collect::<GamesConfig>()
+                .pipe_ref(games_section)
+                .map(|games| print_report(format_args!("\n# add this to your config or run again with `--write`:\n{games}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) -> Result<()> {
+        std::fs::create_dir_all(path.parent().context("no parent")?)?;
+        std::fs::write(path, contents).context("writing")
+    }
+
+    #[test]
+    fn test_games_are_found_in_every_library() -> Result<()> {
+        let steam = tempfile::tempdir()?;
+        let library = tempfile::tempdir()?;
+        write(
+            &steam.path().join("steamapps/libraryfolders.vdf"),
+            &format!(
+                r#""libraryfolders" {{ "0" {{ "path" "{}" }} "1" {{ "path" "{}" }} }}"#,
+                steam.path().display(),
+                library.path().display()
+            ),
+        )?;
+        write(
+            &library.path().join("steamapps/appmanifest_22370.acf"),
+            r#""AppState" { "appid" "22370" "installdir" "Fallout 3 goty" }"#,
+        )?;
+        std::fs::create_dir_all(library.path().join("steamapps/common/Fallout 3 goty"))?;
+        std::fs::create_dir_all(library.path().join("steamapps/compatdata/22370/pfx"))?;
+        // the download was started, but the game is not there yet
+        write(
+            &steam.path().join("steamapps/appmanifest_489830.acf"),
+            r#""AppState" { "appid" "489830" "installdir" "Skyrim Special Edition" }"#,
+        )?;
+
+        assert_eq!(
+            detect_steam_games(&[steam.path().to_owned()]),
+            vec![DetectedGame {
+                game: GameName::new("Fallout3".into()),
+                store: GameStore::Steam,
+                app_id: "22370".into(),
+                root_directory: library.path().join("steamapps/common/Fallout 3 goty"),
+                prefix: Some(library.path().join("steamapps/compatdata/22370/pfx")),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_the_games_section_is_rewritten() -> Result<()> {
+        let config = HoolamikeConfig::default()
+            .pipe_ref(serde_yaml::to_string)?
+            .replace("downloaders:", "# where the archives go\ndownloaders:")
+            .replace("games:", "games: # picked by hand");
+        let games = GamesConfig::new().tap_mut(|games| {
+            games.insert(
+                GameName::new("Fallout3".into()),
+                GameConfig {
+                    root_directory: PathBuf::from("/steam/Fallout 3 goty"),
+                },
+            );
+        });
+        let patched = with_games_section(&config, &games)?;
+        assert!(patched.contains("# where the archives go\ndownloaders:"), "{patched}");
+        assert!(!patched.contains("ExampleGame"), "{patched}");
+        assert_eq!(
+            serde_yaml::from_str::<HoolamikeConfig>(&patched)?.games[&GameName::new("Fallout3".into())].root_directory,
+            Path::new("/steam/Fallout 3 goty")
+        );
+        // everything after the games stays where it was
+        assert_eq!(
+            patched.split_once("\nfixup:").map(|(_, rest)| rest),
+            config.split_once("\nfixup:").map(|(_, rest)| rest)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_keeps_existing_directories() -> Result<()> {
+        let existing = tempfile::tempdir()?;
+        let detected = ["Fallout4", "FalloutNewVegas", "Fallout3"]
+            .map(|game| DetectedGame {
+                game: GameName::new(game.into()),
+                store: GameStore::Steam,
+                app_id: "0".into(),
+                root_directory: PathBuf::from("/steam").join(game),
+                prefix: None,
+            })
+            .to_vec();
+        let mut games = GamesConfig::new().tap_mut(|games| {
+            games.insert(
+                GameName::new("Fallout4".into()),
+                GameConfig {
+                    root_directory: existing.path().to_owned(),
+                },
+            );
+            games.insert(
+                GameName::new("FalloutNewVegas".into()),
+                GameConfig {
+                    root_directory: PathBuf::from("/typo/in/the/path"),
+                },
+            );
+        });
+        assert_eq!(
+            merge_detected(&mut games, &detected),
+            vec![GameName::new("FalloutNewVegas".into()), GameName::new("Fallout3".into())]
+        );
+        assert_eq!(games[&GameName::new("Fallout4".into())].root_directory, existing.path());
+        assert_eq!(
+            games[&GameName::new("FalloutNewVegas".into())].root_directory,
+            Path::new("/steam/FalloutNewVegas")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+//! heroic keeps gog installations in `gog_store/installed.json` and epic ones in legendary's `installed.json`,
+//! the wine prefix of every game is in `GamesConfig/<app name>.json`
+use {
+    super::{DetectedGame, GameStore, KnownGame, KNOWN_GAMES},
+    crate::modlist_json::GameName,
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    serde::{de::DeserializeOwned, Deserialize},
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+    tap::prelude::*,
+    tracing::debug,
+};
+
+/// `~/.config/heroic` (or where the platform keeps configs), the flatpak one included
+pub fn default_heroic_roots() -> Vec<PathBuf> {
+    directories::BaseDirs::new()
+        .into_iter()
+        .flat_map(|dirs| {
+            [
+                dirs.config_dir().join("heroic"),
+                dirs.home_dir()
+                    .join(".var/app/com.heroicgameslauncher.hgl/config/heroic"),
+            ]
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct GogInstalled {
+    installed: Vec<GogInstallation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GogInstallation {
+    #[serde(rename = "appName")]
+    app_name: String,
+    install_path: PathBuf,
+    #[serde(default)]
+    is_dlc: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegendaryInstallation {
+    app_name: String,
+    title: String,
+    install_path: PathBuf,
+    #[serde(default)]
+    is_dlc: bool,
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    std::fs::read_to_string(path)
+        .context("reading")
+        .and_then(|contents| serde_json::from_str(&contents).context("parsing"))
+        .with_context(|| format!("loading [{}]", path.display()))
+}
+
+/// `Data` is what the game root is recognised by, some store builds keep the game one directory deeper
+/// than the installation (gog fallout new vegas ends up as `<install path>/Fallout New Vegas/Data`)
+pub fn game_root(install_path: &Path) -> Option<PathBuf> {
+    match install_path.join("Data").is_dir() {
+        true => Some(install_path.to_owned()),
+        false => std::fs::read_dir(install_path)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .find(|directory| directory.join("Data").is_dir()),
+    }
+}
+
+fn normalized_title(title: &str) -> String {
+    title
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn wine_prefix(heroic_root: &Path, app_name: &str) -> Option<PathBuf> {
+    read_json::<BTreeMap<String, serde_json::Value>>(
+        &heroic_root
+            .join("GamesConfig")
+            .join(format!("{app_name}.json")),
+    )
+    .ok()?
+    .get(app_name)?
+    .get("winePrefix")?
+    .as_str()
+    .map(PathBuf::from)
+    .filter(|prefix| prefix.is_dir())
+}
+
+fn detected(heroic_root: &Path, game: &KnownGame, store: GameStore, app_name: &str, install_path: &Path) -> Option<DetectedGame> {
+    game_root(install_path)
+        .tap_none(|| debug!(game=%game.name, %store, "no game found in [{}]", install_path.display()))
+        .map(|root_directory| DetectedGame {
+            game: GameName::new(game.name.to_string()),
+            store,
+            app_id: app_name.to_string(),
+            root_directory,
+            prefix: wine_prefix(heroic_root, app_name),
+        })
+}
+
+fn gog_games(heroic_root: &Path) -> Result<Vec<DetectedGame>> {
+    read_json::<GogInstalled>(&heroic_root.join("gog_store").join("installed.json")).map(|GogInstalled { installed }| {
+        installed
+            .iter()
+            .filter(|installation| !installation.is_dlc)
+            .filter_map(|installation| {
+                KNOWN_GAMES
+                    .iter()
+                    .find(|game| {
+                        game.gog_product_ids
+                            .contains(&installation.app_name.as_str())
+                    })
+                    .and_then(|game| detected(heroic_root, game, GameStore::Gog, &installation.app_name, &installation.install_path))
+            })
+            .collect()
+    })
+}
+
+fn epic_games(heroic_root: &Path) -> Result<Vec<DetectedGame>> {
+    read_json::<BTreeMap<String, LegendaryInstallation>>(
+        &heroic_root
+            .join("legendaryConfig")
+            .join("legendary")
+            .join("installed.json"),
+    )
+    .map(|installed| {
+        installed
+            .values()
+            .filter(|installation| !installation.is_dlc)
+            .filter_map(|installation| {
+                let title = normalized_title(&installation.title);
+                KNOWN_GAMES
+                    .iter()
+                    .find(|game| {
+                        game.epic_title_prefix
+                            .is_some_and(|prefix| title.starts_with(prefix))
+                    })
+                    .and_then(|game| detected(heroic_root, game, GameStore::Epic, &installation.app_name, &installation.install_path))
+            })
+            .collect()
+    })
+}
+
+/// a heroic root without one of the stores is fine, most people only log in to one of them
+pub fn detect_heroic_games(heroic_roots: &[PathBuf]) -> Vec<DetectedGame> {
+    heroic_roots
+        .iter()
+        .filter(|root| root.is_dir())
+        .flat_map(|root| {
+            [gog_games(root), epic_games(root)]
+                .into_iter()
+                .filter_map(|games| {
+                    games
+                        .tap_err(|reason| debug!(?reason, "no games found in [{}]", root.display()))
+                        .ok()
+                })
+                .flatten()
+        })
+        .unique_by(|detected| detected.game.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) -> Result<()> {
+        std::fs::create_dir_all(path.parent().context("no parent")?)?;
+        std::fs::write(path, contents).context("writing")
+    }
+
+    #[test]
+    fn test_gog_and_epic_games_are_found() -> Result<()> {
+        let heroic = tempfile::tempdir()?;
+        let games = tempfile::tempdir()?;
+        let prefix = games.path().join("Prefixes/Fallout New Vegas");
+        std::fs::create_dir_all(
+            games
+                .path()
+                .join("Fallout New Vegas/Fallout New Vegas/Data"),
+        )?;
+        std::fs::create_dir_all(games.path().join("Fallout 3/Data"))?;
+        std::fs::create_dir_all(&prefix)?;
+        write(
+            &heroic.path().join("gog_store/installed.json"),
+            &serde_json::json!({
+                "installed": [
+                    {"platform": "windows", "appName": "1454587428", "install_path": games.path().join("Fallout New Vegas"), "is_dlc": false},
+                    {"platform": "windows", "appName": "1454587428-dlc", "install_path": games.path().join("Fallout New Vegas"), "is_dlc": true},
+                ]
+            })
+            .to_string(),
+        )?;
+        write(
+            &heroic.path().join("GamesConfig/1454587428.json"),
+            &serde_json::json!({"1454587428": {"winePrefix": prefix}}).to_string(),
+        )?;
+        write(
+            &heroic
+                .path()
+                .join("legendaryConfig/legendary/installed.json"),
+            &serde_json::json!({
+                "adeae8bbfc94427db57c7dfecce3f1d4": {
+                    "app_name": "adeae8bbfc94427db57c7dfecce3f1d4",
+                    "title": "Fallout 3: Game of the Year Edition",
+                    "install_path": games.path().join("Fallout 3"),
+                    "is_dlc": false
+                }
+            })
+            .to_string(),
+        )?;
+
+        assert_eq!(
+            detect_heroic_games(&[heroic.path().to_owned()]),
+            vec![
+                DetectedGame {
+                    game: GameName::new("FalloutNewVegas".into()),
+                    store: GameStore::Gog,
+                    app_id: "1454587428".into(),
+                    root_directory: games.path().join("Fallout New Vegas/Fallout New Vegas"),
+                    prefix: Some(prefix),
+                },
+                DetectedGame {
+                    game: GameName::new("Fallout3".into()),
+                    store: GameStore::Epic,
+                    app_id: "adeae8bbfc94427db57c7dfecce3f1d4".into(),
+                    root_directory: games.path().join("Fallout 3"),
+                    prefix: None,
+                },
+            ]
+        );
+        Ok(())
+    }
+}
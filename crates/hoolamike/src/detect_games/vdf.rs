@@ -0,0 +1,169 @@
+//! the key-value format steam keeps its libraries (`libraryfolders.vdf`) and installed apps (`appmanifest_<appid>.acf`) in,
+//! only the text flavour is supported, the conditionals (`[$WIN32]`) are not used by either of those files
+use anyhow::{Context, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Vdf {
+    String(String),
+    Object(Vec<(String, Vdf)>),
+}
+
+impl Vdf {
+    /// keys are compared without case, older steam versions wrote `LibraryFolders` instead of `libraryfolders`
+    pub fn get(&self, key: &str) -> Option<&Vdf> {
+        self.entries()
+            .iter()
+            .find_map(|(k, value)| k.eq_ignore_ascii_case(key).then_some(value))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Vdf::String(value) => Some(value),
+            Vdf::Object(_) => None,
+        }
+    }
+
+    pub fn entries(&self) -> &[(String, Vdf)] {
+        match self {
+            Vdf::String(_) => &[],
+            Vdf::Object(entries) => entries,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    String(String),
+    Open,
+    Close,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|c| *c == '\n');
+            }
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next().context("unterminated string")? {
+                        '"' => break,
+                        '\\' => match chars.next().context("unterminated escape sequence")? {
+                            'n' => value.push('\n'),
+                            't' => value.push('\t'),
+                            other => value.push(other),
+                        },
+                        other => value.push(other),
+                    }
+                }
+                tokens.push(Token::String(value));
+            }
+            c => {
+                let mut value = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, '{' | '}' | '"')) {
+                    value.push(c);
+                }
+                tokens.push(Token::String(value));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_object(tokens: &mut impl Iterator<Item = Token>, nested: bool) -> Result<Vec<(String, Vdf)>> {
+    let mut entries = vec![];
+    loop {
+        match tokens.next() {
+            None if nested => anyhow::bail!("unexpected end of input, an object is not closed"),
+            None => return Ok(entries),
+            Some(Token::Close) if nested => return Ok(entries),
+            Some(Token::Close) => anyhow::bail!("unexpected `}}`"),
+            Some(Token::Open) => anyhow::bail!("expected a key, found `{{`"),
+            Some(Token::String(key)) => {
+                let value = match tokens.next() {
+                    Some(Token::String(value)) => Vdf::String(value),
+                    Some(Token::Open) => parse_object(tokens, true)
+                        .with_context(|| format!("parsing [{key}]"))
+                        .map(Vdf::Object)?,
+                    other => anyhow::bail!("expected a value for [{key}], found {other:?}"),
+                };
+                entries.push((key, value));
+            }
+        }
+    }
+}
+
+/// the whole file is an object, usually with a single key (`libraryfolders`, `AppState`)
+pub fn parse(input: &str) -> Result<Vdf> {
+    tokenize(input)
+        .and_then(|tokens| parse_object(&mut tokens.into_iter(), false))
+        .map(Vdf::Object)
+        .context("parsing vdf")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_library_folders() -> Result<()> {
+        let vdf = parse(
+            r#"
+"libraryfolders"
+{
+    // comments are allowed
+    "0"
+    {
+        "path"        "/home/user/.local/share/Steam"
+        "apps"
+        {
+            "489830"        "12345"
+        }
+    }
+    "1"
+    {
+        "path"        "D:\\Steam Library"
+        "label"        "escaped \"quotes\""
+    }
+}
+"#,
+        )?;
+        let folders = vdf.get("LibraryFolders").context("no libraryfolders")?;
+        assert_eq!(folders.entries().len(), 2);
+        assert_eq!(
+            folders
+                .get("0")
+                .and_then(|folder| folder.get("apps"))
+                .and_then(|apps| apps.get("489830"))
+                .and_then(Vdf::as_str),
+            Some("12345")
+        );
+        assert_eq!(
+            folders
+                .get("1")
+                .and_then(|f| f.get("path"))
+                .and_then(Vdf::as_str),
+            Some(r"D:\Steam Library")
+        );
+        assert_eq!(
+            folders
+                .get("1")
+                .and_then(|f| f.get("label"))
+                .and_then(Vdf::as_str),
+            Some(r#"escaped "quotes""#)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unclosed_object_is_an_error() {
+        assert!(parse(r#""AppState" { "appid" "22380""#).is_err());
+        assert!(parse(r#""AppState" }"#).is_err());
+    }
+}
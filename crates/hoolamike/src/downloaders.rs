@@ -3,8 +3,10 @@ use {
     std::path::PathBuf,
 };
 
+pub mod challenge;
 pub mod gamefile_source_downloader;
 pub mod google_drive;
+pub mod http_client;
 pub mod mega;
 pub mod http {
     pub struct HttpDownloader {}
@@ -13,7 +15,10 @@ pub mod manual {
     pub struct ManualDownloader {}
 }
 pub mod mediafire;
+pub mod mirrors;
+pub mod moddb;
 pub mod nexus;
+pub mod vector_plexus;
 pub mod wabbajack_cdn;
 
 pub mod helpers;
@@ -24,13 +29,16 @@ pub struct WithArchiveDescriptor<T> {
     pub descriptor: ArchiveDescriptor,
 }
 
-pub type MergeDownloadTask = WithArchiveDescriptor<(Vec<HumanUrl>, PathBuf)>;
+pub type MergeDownloadTask = WithArchiveDescriptor<(Vec<wabbajack_cdn::PartDownload>, PathBuf)>;
 pub type DownloadTask = WithArchiveDescriptor<(HumanUrl, PathBuf)>;
 pub type CopyFileTask = WithArchiveDescriptor<(PathBuf, PathBuf)>;
+/// the website of the file and where it is expected to show up, somebody else (the user, `hoolamike handle-nxm`) downloads it
+pub type ManualDownloadTask = WithArchiveDescriptor<(String, PathBuf)>;
 
 #[derive(Debug, Clone, derive_more::From)]
 pub enum SyncTask {
     MergeDownload(MergeDownloadTask),
     Download(DownloadTask),
     Copy(CopyFileTask),
+    Manual(ManualDownloadTask),
 }
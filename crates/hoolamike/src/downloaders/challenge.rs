@@ -0,0 +1,182 @@
+//! some mod hosts sit behind javascript challenges (cloudflare, ddos-guard), a plain http client only ever gets to see the challenge page
+use {
+    anyhow::{Context, Result},
+    futures::{Stream, StreamExt},
+    reqwest::{
+        header::{HeaderMap, SERVER},
+        Response,
+        StatusCode,
+    },
+    std::ops::Deref,
+    tap::prelude::*,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+pub enum Challenge {
+    #[display("Cloudflare")]
+    Cloudflare,
+    #[display("DDoS-Guard")]
+    DdosGuard,
+}
+
+fn server(headers: &HeaderMap) -> String {
+    headers
+        .get(SERVER)
+        .and_then(|server| server.to_str().ok())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// enough to get past a byte order mark and some whitespace
+const SNIFF_LENGTH: usize = 512;
+
+/// plenty of hosts serve archives as `text/html`, only the body tells
+fn looks_like_html(start: &[u8]) -> bool {
+    String::from_utf8_lossy(&start[..start.len().min(SNIFF_LENGTH)])
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .to_ascii_lowercase()
+        .pipe_ref(|start| start.starts_with("<!doctype html") || start.starts_with("<html"))
+}
+
+const CLOUDFLARE_MARKERS: &[&str] = &["challenge-platform", "cf-browser-verification", "cf_chl_opt"];
+const DDOS_GUARD_MARKERS: &[&str] = &["ddos-guard.net/", "__ddg"];
+
+/// the headers are enough for cloudflare, the body is only needed for hosts which answer with a plain 200
+pub fn detect(status: StatusCode, headers: &HeaderMap, body: Option<&str>) -> Option<Challenge> {
+    let server = server(headers);
+    let blocked = matches!(status, StatusCode::FORBIDDEN | StatusCode::SERVICE_UNAVAILABLE | StatusCode::TOO_MANY_REQUESTS);
+    if headers.contains_key("cf-mitigated") || (blocked && server.contains("cloudflare")) {
+        return Some(Challenge::Cloudflare);
+    }
+    if blocked && server.contains("ddos-guard") {
+        return Some(Challenge::DdosGuard);
+    }
+    body.and_then(|body| {
+        let contains_any = |markers: &[&str]| markers.iter().any(|marker| body.contains(marker));
+        match (contains_any(CLOUDFLARE_MARKERS), contains_any(DDOS_GUARD_MARKERS)) {
+            (true, _) => Some(Challenge::Cloudflare),
+            (false, true) => Some(Challenge::DdosGuard),
+            (false, false) => None,
+        }
+    })
+}
+
+fn challenge_error(url: &reqwest::Url, challenge: Challenge) -> anyhow::Error {
+    anyhow::anyhow!(
+        "[{url}] is protected by a {challenge} challenge, hoolamike only gets to see the challenge page instead of the file.\nEither:\n  - open the url in a \
+         browser, export its cookies to a cookies.txt file (eg. with a `cookies.txt` browser extension), point `downloaders.cookies` at it and set \
+         `downloaders.user_agent` to the user agent of that browser\n  - or download the file manually into the downloads directory"
+    )
+}
+
+/// a response whose first bytes were already read to tell what it is, they are handed out again by [Self::bytes_stream]
+#[derive(Debug)]
+pub struct CheckedResponse {
+    pub response: Response,
+    sniffed: Vec<u8>,
+}
+
+impl CheckedResponse {
+    pub fn status(&self) -> StatusCode {
+        self.response.status()
+    }
+
+    pub fn bytes_stream(self) -> impl Stream<Item = reqwest::Result<impl Deref<Target = [u8]>>> {
+        futures::stream::iter((!self.sniffed.is_empty()).then(|| Ok(self.sniffed.into()))).chain(self.response.bytes_stream())
+    }
+}
+
+/// a web page is never what an archive download is supposed to return, unless the archive is a web page itself
+pub async fn check_response(mut response: Response, expects_html: bool) -> Result<CheckedResponse> {
+    let url = response.url().clone();
+    if let Some(challenge) = detect(response.status(), response.headers(), None) {
+        return Err(challenge_error(&url, challenge));
+    }
+    let mut sniffed = Vec::new();
+    while !expects_html && sniffed.len() < SNIFF_LENGTH {
+        match response.chunk().await.context("reading response")? {
+            Some(chunk) => sniffed.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    match !expects_html && looks_like_html(&sniffed) {
+        false => Ok(CheckedResponse { response, sniffed }),
+        true => {
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response
+                .text()
+                .await
+                .context("reading response")
+                .map(|rest| String::from_utf8_lossy(&sniffed).into_owned() + &rest)?;
+            match detect(status, &headers, Some(&body)) {
+                Some(challenge) => Err(challenge_error(&url, challenge)),
+                None => Err(anyhow::anyhow!(
+                    "[{url}] answered with a web page ([{status}]) instead of the file, the link is probably dead or the host wants you to click through its \
+                     website, download the file manually into the downloads directory"
+                )),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, reqwest::header::HeaderValue};
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(key, value)| (reqwest::header::HeaderName::from_static(key), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_cloudflare() {
+        assert_eq!(
+            detect(StatusCode::FORBIDDEN, &headers(&[("cf-mitigated", "challenge")]), None),
+            Some(Challenge::Cloudflare)
+        );
+        assert_eq!(
+            detect(StatusCode::SERVICE_UNAVAILABLE, &headers(&[("server", "cloudflare")]), None),
+            Some(Challenge::Cloudflare)
+        );
+        // plenty of files are served by cloudflare without any challenge
+        assert_eq!(detect(StatusCode::OK, &headers(&[("server", "cloudflare")]), None), None);
+        assert_eq!(
+            detect(
+                StatusCode::OK,
+                &headers(&[]),
+                Some(r#"<html><title>Just a moment...</title><script src="/cdn-cgi/challenge-platform/h/b/orchestrate/jsch/v1"></script></html>"#)
+            ),
+            Some(Challenge::Cloudflare)
+        );
+    }
+
+    #[test]
+    fn test_detect_ddos_guard() {
+        assert_eq!(
+            detect(StatusCode::FORBIDDEN, &headers(&[("server", "ddos-guard")]), None),
+            Some(Challenge::DdosGuard)
+        );
+        assert_eq!(
+            detect(
+                StatusCode::OK,
+                &headers(&[]),
+                Some(r#"<script src="https://check.ddos-guard.net/check.js"></script>"#)
+            ),
+            Some(Challenge::DdosGuard)
+        );
+        assert_eq!(detect(StatusCode::OK, &headers(&[]), Some("<html>file not found</html>")), None);
+    }
+
+    #[test]
+    fn test_web_pages_are_told_apart_by_their_body() {
+        assert!(looks_like_html(b"<!DOCTYPE html>\n<html lang=\"en\">"));
+        assert!(looks_like_html("\u{feff}\r\n  <HTML><head>".as_bytes()));
+        assert!(!looks_like_html(b"7z\xbc\xaf\x27\x1c\x00\x04"));
+        assert!(!looks_like_html(b"PK\x03\x04"));
+        assert!(!looks_like_html(b""));
+    }
+}
@@ -7,11 +7,14 @@ use {
     },
     anyhow::{Context, Result},
     futures::TryFutureExt,
+    game_version::{local_game_version, GameFileMismatch},
     indexmap::IndexMap,
     std::{future::ready, path::PathBuf},
     tap::prelude::*,
 };
 
+pub mod game_version;
+
 pub struct GameFileSourceDownloader {
     game_name: GameName,
     source_directory: PathBuf,
@@ -28,12 +31,18 @@ impl GameFileSourceDownloader {
     pub async fn prepare_copy(
         &self,
         GameFileSourceState {
-            game_version: _,
+            game_version,
             hash,
             game_file,
             game,
         }: GameFileSourceState,
     ) -> Result<PathBuf> {
+        let mismatch = GameFileMismatch {
+            game: game.clone(),
+            game_file: game_file.clone().into_path(),
+            expected_version: game_version,
+            local_version: None,
+        };
         self.game_name
             .eq(&game)
             .then_some(())
@@ -41,21 +50,35 @@ impl GameFileSourceDownloader {
             .map(|_| game_file.into_path())
             .pipe(ready)
             .and_then(|game_file| {
-                self.source_directory.join(game_file).pipe(|game_file| {
-                    game_file
-                        .clone()
-                        .pipe(tokio::fs::try_exists)
-                        .map_context("checking for file existence")
-                        .and_then(|exists| async move {
-                            exists
-                                .then_some(game_file.clone())
-                                .with_context(|| format!("[{}] does not exist", game_file.display()))
-                        })
-                })
+                self.source_directory
+                    .join(game_file)
+                    .pipe(|game_file| {
+                        game_file
+                            .clone()
+                            .pipe(tokio::fs::try_exists)
+                            .map_context("checking for file existence")
+                            .and_then(|exists| async move {
+                                exists
+                                    .then_some(game_file.clone())
+                                    .with_context(|| format!("[{}] does not exist", game_file.display()))
+                            })
+                    })
+                    .and_then(|source| validate_hash(source, hash))
+                    .or_else(|error| self.diagnose(mismatch, error))
             })
-            .and_then(|source| validate_hash(source, hash))
             .await
     }
+
+    /// a bare hash mismatch does not tell whether the game was updated or the file was edited, the version of the game executable does
+    async fn diagnose(&self, mismatch: GameFileMismatch, error: anyhow::Error) -> Result<PathBuf> {
+        let source_directory = self.source_directory.clone();
+        let game = mismatch.game.clone();
+        let local_version = tokio::task::spawn_blocking(move || local_game_version(&source_directory, &game))
+            .await
+            .ok()
+            .flatten();
+        Err(error.context(GameFileMismatch { local_version, ..mismatch }.to_string()))
+    }
 }
 
 pub type GameFileSourceSynchronizers = IndexMap<GameName, GameFileSourceDownloader>;
@@ -0,0 +1,148 @@
+//! wabbajack records the version of the game a modlist was made with (`GameFileSourceState::game_version`), which is the file version
+//! from the version resource of the game executable. comparing it with the local one tells an updated game apart from an edited file
+use {
+    crate::{detect_games::known_game, modlist_json::GameName},
+    std::{
+        cmp::Ordering,
+        path::{Path, PathBuf},
+    },
+    tap::prelude::*,
+};
+
+/// `VS_FIXEDFILEINFO::dwSignature`
+const FIXED_FILE_INFO_SIGNATURE: [u8; 4] = 0xFEEF04BDu32.to_le_bytes();
+
+/// the fixed part of the version resource is found by its signature, walking the resource directory is not needed for it
+pub fn exe_file_version(exe: &[u8]) -> Option<String> {
+    exe.windows(FIXED_FILE_INFO_SIGNATURE.len())
+        .position(|window| window == FIXED_FILE_INFO_SIGNATURE)
+        .and_then(|start| exe.get(start + 8..start + 16))
+        .map(|version| {
+            let most_significant = u32::from_le_bytes([version[0], version[1], version[2], version[3]]);
+            let least_significant = u32::from_le_bytes([version[4], version[5], version[6], version[7]]);
+            format!(
+                "{}.{}.{}.{}",
+                most_significant >> 16,
+                most_significant & 0xffff,
+                least_significant >> 16,
+                least_significant & 0xffff
+            )
+        })
+}
+
+/// `None` for games hoolamike does not know the executable of, or when it cannot be read
+pub fn local_game_version(root_directory: &Path, game: &GameName) -> Option<String> {
+    known_game(game)
+        .map(|known| root_directory.join(known.executable))
+        .and_then(|executable| {
+            std::fs::read(&executable)
+                .tap_err(|reason| tracing::debug!(?reason, "could not read [{}]", executable.display()))
+                .ok()
+        })
+        .and_then(|exe| exe_file_version(&exe))
+}
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version
+        .split('.')
+        .map(|part| part.trim().parse().ok())
+        .collect()
+}
+
+/// a game file which is missing or does not have the hash the modlist expects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameFileMismatch {
+    pub game: GameName,
+    pub game_file: PathBuf,
+    pub expected_version: String,
+    pub local_version: Option<String>,
+}
+
+impl GameFileMismatch {
+    pub fn local_version_ordering(&self) -> Option<Ordering> {
+        self.local_version
+            .as_deref()
+            .and_then(parse_version)
+            .zip(parse_version(&self.expected_version))
+            .map(|(local, expected)| local.cmp(&expected))
+    }
+
+    pub fn remedies(&self) -> Vec<String> {
+        let expected = &self.expected_version;
+        match self.local_version_ordering() {
+            Some(Ordering::Greater) => vec![
+                format!(
+                    "the game was updated after the modlist was made, downgrade it to [{expected}]: open `steam://open/console` and run `download_depot <app \
+                     id> <depot id> <manifest id>` with the manifest of that version (steamdb.info lists them), or use DepotDownloader, then copy the \
+                     downloaded files over the game folder"
+                ),
+                "set the game to only update when it is launched (steam: properties > updates) and start it through the modlist from now on, or wait for a \
+                 version of the modlist made for the new game version"
+                    .to_string(),
+            ],
+            Some(Ordering::Less) => vec![format!("the game is older than [{expected}], update it through the store")],
+            Some(Ordering::Equal) => vec![
+                "the game version matches, so the file was changed after the game was installed (a previous modlist, a patcher, a mod manager deploying into \
+                 the game folder)"
+                    .to_string(),
+                "verify the integrity of the game files through the store (steam: properties > installed files > verify integrity of game files)".to_string(),
+            ],
+            None => vec![
+                "the version of the local game could not be determined, make sure `games.<name>.root_directory` is the folder with the game executable \
+                 (`hoolamike detect-games` finds it)"
+                    .to_string(),
+                "modlists are made for the steam version of the game, files of other stores (gog, epic) are usually different".to_string(),
+                "verify the integrity of the game files through the store".to_string(),
+            ],
+        }
+    }
+}
+
+impl std::fmt::Display for GameFileMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "game file [{}] of [{}] does not match the modlist", self.game_file.display(), self.game)?;
+        writeln!(f, "  modlist was made for game version: {}", self.expected_version)?;
+        writeln!(f, "  local game version: {}", self.local_version.as_deref().unwrap_or("unknown"))?;
+        writeln!(f, "  what can be done:")?;
+        self.remedies()
+            .iter()
+            .try_for_each(|remedy| writeln!(f, "    - {remedy}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_version_is_read_from_fixed_file_info() {
+        let exe = b"MZ padding"
+            .iter()
+            .copied()
+            .chain(FIXED_FILE_INFO_SIGNATURE)
+            .chain(0x0001_0000u32.to_le_bytes())
+            .chain(((1u32 << 16) | 6).to_le_bytes())
+            .chain(((640u32 << 16) | 8).to_le_bytes())
+            .chain([0; 32])
+            .collect::<Vec<_>>();
+        assert_eq!(exe_file_version(&exe).as_deref(), Some("1.6.640.8"));
+        assert_eq!(exe_file_version(b"MZ no version resource"), None);
+    }
+
+    #[test]
+    fn test_remedies_follow_the_local_version() {
+        let mismatch = |local_version: Option<&str>| GameFileMismatch {
+            game: GameName::new("SkyrimSpecialEdition".into()),
+            game_file: PathBuf::from("Data/Skyrim.esm"),
+            expected_version: "1.6.640.0".into(),
+            local_version: local_version.map(str::to_string),
+        };
+        assert_eq!(mismatch(Some("1.6.1170.0")).local_version_ordering(), Some(Ordering::Greater));
+        assert_eq!(mismatch(Some("1.5.97.0")).local_version_ordering(), Some(Ordering::Less));
+        assert_eq!(mismatch(Some("1.6.640.0")).local_version_ordering(), Some(Ordering::Equal));
+        assert_eq!(mismatch(None).local_version_ordering(), None);
+        assert!(mismatch(Some("1.6.1170.0"))
+            .to_string()
+            .contains("downgrade it to [1.6.640.0]"));
+    }
+}
@@ -1,5 +1,5 @@
 use {
-    super::helpers::FutureAnyhowExt,
+    super::{helpers::FutureAnyhowExt, http_client::http_client},
     crate::modlist_json::HumanUrl,
     anyhow::{Context, Result},
     futures::TryFutureExt,
@@ -80,7 +80,7 @@ impl GoogleDriveDownloader {
             .context("invalid url")?;
 
         let response = {
-            reqwest::Client::new()
+            http_client()?
                 .get(original_url.to_string())
                 .send()
                 .await
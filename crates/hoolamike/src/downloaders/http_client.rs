@@ -0,0 +1,130 @@
+//! every http client used for downloading goes through here, so that the `downloaders.proxy` section of the config applies to all of them
+use {
+    crate::config_file::DownloadersConfig,
+    anyhow::{Context, Result},
+    once_cell::sync::Lazy,
+    parking_lot::RwLock,
+    reqwest::{cookie::Jar, ClientBuilder, NoProxy, Proxy},
+    std::{path::Path, sync::Arc},
+    tap::prelude::*,
+};
+
+/// clients are built deep inside the downloaders, far away from the config
+static PROXY: Lazy<RwLock<Option<Proxy>>> = Lazy::new(Default::default);
+
+/// cookies exported from a browser, these get hoolamike past javascript challenges the browser already solved
+static COOKIES: Lazy<RwLock<Option<Arc<Jar>>>> = Lazy::new(Default::default);
+
+/// challenge cookies (eg. `cf_clearance`) are only accepted together with the user agent of the browser they were issued to
+static USER_AGENT: Lazy<RwLock<Option<String>>> = Lazy::new(Default::default);
+
+/// without a configured proxy reqwest falls back to the `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY` environment variables on its own,
+/// `NO_PROXY` is honored in both cases
+pub fn configure_proxy(proxy: Option<String>) -> Result<()> {
+    *PROXY.write() = proxy
+        .map(|proxy| {
+            Proxy::all(&proxy)
+                .with_context(|| format!("invalid proxy url: [{proxy}]"))
+                .map(|configured| configured.no_proxy(NoProxy::from_env()))
+                .tap_ok(|_| tracing::info!(%proxy, "downloads will go through a proxy"))
+        })
+        .transpose()?;
+    Ok(())
+}
+
+/// netscape `cookies.txt` format: domain, include subdomains, path, secure, expiry, name, value (tab separated)
+fn parse_cookies_txt(contents: &str) -> Result<Vec<(reqwest::Url, String)>> {
+    contents
+        .lines()
+        .map(|line| line.strip_prefix("#HttpOnly_").unwrap_or(line))
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| match line.split('\t').collect::<Vec<_>>().as_slice() {
+            [domain, include_subdomains, path, _secure, _expires, name, value] => format!("https://{}{path}", domain.trim_start_matches('.'))
+                .pipe_deref(reqwest::Url::parse)
+                .with_context(|| format!("invalid domain [{domain}]"))
+                .map(|url| {
+                    let cookie = match *include_subdomains {
+                        "TRUE" => format!("{name}={value}; Domain={domain}; Path={path}"),
+                        _ => format!("{name}={value}; Path={path}"),
+                    };
+                    (url, cookie)
+                }),
+            _ => Err(anyhow::anyhow!("expected 7 tab separated fields, found [{line}]")),
+        })
+        .collect()
+}
+
+pub fn configure_cookies(cookies: Option<&Path>, user_agent: Option<String>) -> Result<()> {
+    *COOKIES.write() = cookies
+        .map(|cookies| {
+            std::fs::read_to_string(cookies)
+                .context("reading")
+                .and_then(|contents| parse_cookies_txt(&contents))
+                .map(|parsed| {
+                    Jar::default().tap(|jar| {
+                        parsed
+                            .iter()
+                            .for_each(|(url, cookie)| jar.add_cookie_str(cookie, url))
+                    })
+                })
+                .map(Arc::new)
+                .tap_ok(|_| tracing::info!("downloads will use cookies from [{}]", cookies.display()))
+                .with_context(|| format!("loading cookies from [{}]", cookies.display()))
+        })
+        .transpose()?;
+    *USER_AGENT.write() = user_agent;
+    Ok(())
+}
+
+/// the proxy and the cookies of the config, for every command which talks to the internet
+pub fn configure_http_client(downloaders: &DownloadersConfig) -> Result<()> {
+    configure_proxy(downloaders.proxy.clone()).context("configuring proxy")?;
+    configure_cookies(downloaders.cookies.as_deref(), downloaders.user_agent.clone()).context("configuring cookies")
+}
+
+pub fn http_client_builder() -> ClientBuilder {
+    reqwest::Client::builder()
+        .pipe(|builder| match PROXY.read().clone() {
+            Some(proxy) => builder.proxy(proxy),
+            None => builder,
+        })
+        .pipe(|builder| match COOKIES.read().clone() {
+            Some(cookies) => builder.cookie_provider(cookies),
+            None => builder,
+        })
+        .pipe(|builder| match USER_AGENT.read().clone() {
+            Some(user_agent) => builder.user_agent(user_agent),
+            None => builder,
+        })
+}
+
+pub fn http_client() -> Result<reqwest::Client> {
+    http_client_builder()
+        .build()
+        .context("building http client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cookies_txt() -> Result<()> {
+        let cookies = parse_cookies_txt(
+            "# Netscape HTTP Cookie \
+             File\n\n.example.com\tTRUE\t/\tTRUE\t1767225600\tcf_clearance\tabc\n#HttpOnly_files.example.org\tFALSE\t/mods\tFALSE\t0\tsession\txyz\n",
+        )?;
+        assert_eq!(
+            cookies
+                .iter()
+                .map(|(url, cookie)| (url.as_str(), cookie.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("https://example.com/", "cf_clearance=abc; Domain=.example.com; Path=/"),
+                ("https://files.example.org/mods", "session=xyz; Path=/mods"),
+            ]
+        );
+        assert!(parse_cookies_txt("example.com\tTRUE\t/").is_err());
+        Ok(())
+    }
+}
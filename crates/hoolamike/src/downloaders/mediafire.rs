@@ -1,5 +1,5 @@
 use {
-    super::helpers::FutureAnyhowExt,
+    super::{helpers::FutureAnyhowExt, http_client::http_client_builder},
     crate::modlist_json::HumanUrl,
     anyhow::{Context, Result},
     futures::TryFutureExt,
@@ -14,86 +14,155 @@ pub mod response_parsing {
     use {
         crate::modlist_json::HumanUrl,
         anyhow::{Context, Result},
+        base64::prelude::*,
         scraper::{Html, Selector},
         std::str::FromStr,
     };
 
-    /// BASED ON https://github.com/wkentaro/gdown/blob/main/gdown/download.py
-    pub fn get_url_from_mediafire_confirmation(contents: &str) -> Result<HumanUrl> {
-        Selector::parse("input.popsok[aria-label='Download file']")
+    fn select_attr(document: &Html, selector: &str, attr: &str) -> Result<String> {
+        Selector::parse(selector)
             .map_err(|e| anyhow::anyhow!("{e:?}"))
             .context("parsing selector")
             .and_then(|selector| {
-                Err(anyhow::anyhow!("finding any url"))
-                    .or_else(|cause| {
-                        Html::parse_document(contents)
-                            .select(&selector)
-                            .next()
-                            .context("selector matched nothing")
-                            .and_then(|input| input.attr("href").context("no href"))
-                            .and_then(|href| HumanUrl::from_str(href).with_context(|| format!("bad url: {href}")))
-                            .context("trying the selector method")
-                            .with_context(|| format!("trying because: {cause:?}"))
-                    })
-                    .or_else(|cause| {
-                        let start_text = "window.location.href = '";
+                document
+                    .select(&selector)
+                    .next()
+                    .context("selector matched nothing")
+                    .and_then(|element| element.attr(attr).with_context(|| format!("no {attr}")))
+                    .map(ToOwned::to_owned)
+            })
+            .with_context(|| format!("selecting [{selector}]"))
+    }
+
+    fn parse_url(url: &str) -> Result<HumanUrl> {
+        HumanUrl::from_str(url)
+            .with_context(|| format!("bad url: {url}"))
+            .and_then(|parsed| match AsRef::<url::Url>::as_ref(&parsed).scheme() {
+                "http" | "https" => Ok(parsed),
+                other => Err(anyhow::anyhow!("unexpected scheme [{other}] in [{url}]")),
+            })
+    }
+
+    /// BASED ON https://github.com/wkentaro/gdown/blob/main/gdown/download.py
+    pub fn get_url_from_mediafire_confirmation(contents: &str) -> Result<HumanUrl> {
+        let document = Html::parse_document(contents);
+        select_attr(&document, "a#downloadButton", "data-scrambled-url")
+            .and_then(|scrambled| {
+                BASE64_STANDARD
+                    .decode(scrambled.trim())
+                    .context("decoding base64")
+                    .and_then(|decoded| String::from_utf8(decoded).context("decoded url is not utf8"))
+            })
+            .and_then(|url| parse_url(&url))
+            .context("trying the scrambled url method")
+            .or_else(|cause| {
+                select_attr(&document, "a#downloadButton", "href")
+                    .and_then(|href| parse_url(&href))
+                    .context("trying the download button method")
+                    .with_context(|| format!("trying because: {cause:?}"))
+            })
+            .or_else(|cause| {
+                select_attr(&document, "input.popsok[aria-label='Download file']", "href")
+                    .and_then(|href| parse_url(&href))
+                    .context("trying the selector method")
+                    .with_context(|| format!("trying because: {cause:?}"))
+            })
+            .or_else(|cause| {
+                let start_text = "window.location.href = '";
+                contents
+                    .find(start_text)
+                    .with_context(|| format!("'{start_text}' not found"))
+                    .and_then(|start| {
                         contents
-                            .find(start_text)
-                            .with_context(|| format!("'{start_text}' not found"))
-                            .and_then(|start| {
-                                contents
-                                    .get(start..)
-                                    .with_context(|| format!("invalid subslice: {start}.."))
-                            })
-                            .map(|slice| slice.chars().take_while(|c| c != &'\'').collect::<String>())
-                            .and_then(|url| HumanUrl::from_str(&url).with_context(|| format!("bad url: {url}")))
-                            .context("trying the substring method")
-                            .with_context(|| format!("trying becasue: {cause:?}"))
+                            .get(start + start_text.len()..)
+                            .with_context(|| format!("invalid subslice: {start}.."))
                     })
+                    .map(|slice| slice.chars().take_while(|c| c != &'\'').collect::<String>())
+                    .and_then(|url| parse_url(&url))
+                    .context("trying the substring method")
+                    .with_context(|| format!("trying because: {cause:?}"))
             })
-        // Selector::parse("a.downloadButton")
-        //     .map_err(|e| anyhow::anyhow!("{e:?}"))
-        //     .context("bad selector")
-        //     .and_then(|a| {
-        //         a.pipe_ref(|a| {
-        //             contents
-        //                 .pipe(Html::parse_document)
-        //                 .select(a)
-        //                 .next()
-        //                 .context("no 'a.downloadButton' on page")
-        //                 .and_then(|button| {
-        //                     button
-        //                         .attr("href")
-        //                         .context("button has no href")
-        //                         .and_then(|url| Url::parse(url).with_context(|| format!("parsing [{url}]")))
-        //                 })
-        //         })
-        //     })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const DIRECT: &str = "https://download1234.mediafire.com/abcdef/somekey/Some+Mod.7z";
+
+        #[test]
+        fn test_download_button_href() -> Result<()> {
+            let page = format!(r#"<html><body><a class="input popsok" id="downloadButton" href="{DIRECT}">Download (1MB)</a></body></html>"#);
+            assert_eq!(get_url_from_mediafire_confirmation(&page)?.to_string(), DIRECT);
+            Ok(())
+        }
+
+        #[test]
+        fn test_scrambled_url_is_preferred() -> Result<()> {
+            let page = format!(
+                r#"<html><body><a id="downloadButton" href="javascript:void(0)" data-scrambled-url="{}">Download</a></body></html>"#,
+                BASE64_STANDARD.encode(DIRECT)
+            );
+            assert_eq!(get_url_from_mediafire_confirmation(&page)?.to_string(), DIRECT);
+            Ok(())
+        }
+
+        #[test]
+        fn test_window_location_fallback() -> Result<()> {
+            let page = format!(r#"<html><script>window.location.href = '{DIRECT}';</script></html>"#);
+            assert_eq!(get_url_from_mediafire_confirmation(&page)?.to_string(), DIRECT);
+            Ok(())
+        }
+
+        #[test]
+        fn test_no_link_is_an_error() {
+            assert!(get_url_from_mediafire_confirmation("<html><body>file was removed</body></html>").is_err());
+        }
     }
 }
 
 impl MediaFireDownloader {
     #[instrument]
     pub async fn download(url: HumanUrl) -> Result<HumanUrl> {
-        reqwest::Client::builder()
+        http_client_builder()
             .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/109.0.0.0 Safari/537.36")
             .build()
             .context("bad http client")?
             .get(url.to_string())
             .send()
             .map_context("fetching the media fire response")
-            // .and_then(|res| {
-            //     res.error_for_status()
-            //         .context("bad status code")
-            //         .pipe(ready)
-            // })
-            .and_then(|res| res.text().map_context("extracting text"))
-            .and_then(|text| {
-                tokio::task::spawn_blocking(move || {
-                    response_parsing::get_url_from_mediafire_confirmation(&text).tap_ok(|url| tracing::debug!(%url, "parsed mediafire url"))
-                })
-                .map_context("thread crashed")
-                .and_then(ready)
+            .and_then(|res| {
+                res.error_for_status()
+                    .context("bad status code")
+                    .pipe(ready)
+            })
+            .and_then(|res| async move {
+                let is_html = res
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|content_type| content_type.to_str().ok())
+                    .is_some_and(|content_type| content_type.starts_with("text/html"));
+                match is_html {
+                    // link already points at the file itself, the interstitial page was skipped
+                    false => res
+                        .url()
+                        .clone()
+                        .pipe(HumanUrl::from)
+                        .tap(|url| tracing::debug!(%url, "mediafire url is already direct"))
+                        .pipe(Ok),
+                    true => {
+                        res.text()
+                            .map_context("extracting text")
+                            .and_then(|text| {
+                                tokio::task::spawn_blocking(move || {
+                                    response_parsing::get_url_from_mediafire_confirmation(&text).tap_ok(|url| tracing::debug!(%url, "parsed mediafire url"))
+                                })
+                                .map_context("thread crashed")
+                                .and_then(ready)
+                            })
+                            .await
+                    }
+                }
             })
             .await
             .with_context(|| format!("preparing MediaFire download for [{url}]"))
@@ -0,0 +1,168 @@
+use {
+    super::{challenge, helpers::FutureAnyhowExt, http_client::http_client},
+    crate::modlist_json::HumanUrl,
+    anyhow::{Context, Result},
+    futures::TryFutureExt,
+    indexmap::IndexMap,
+    itertools::Itertools,
+    regex::Regex,
+    std::{future::ready, str::FromStr},
+    tap::prelude::*,
+    tracing::instrument,
+};
+
+#[derive(Debug)]
+struct MirrorRule {
+    pattern: Regex,
+    replacements: Vec<String>,
+}
+
+/// user provided url rewrites (`downloaders.mirrors`), tried in order when the original url of an `Http` download does not work
+#[derive(Debug, Default)]
+pub struct Mirrors(Vec<MirrorRule>);
+
+#[derive(Debug, Clone)]
+pub struct ResolvedUrl {
+    pub url: HumanUrl,
+    /// set when the original url did not work and one of the mirrors did
+    pub mirror: Option<HumanUrl>,
+}
+
+impl Mirrors {
+    pub fn new(rules: &IndexMap<String, Vec<String>>) -> Result<Self> {
+        rules
+            .iter()
+            .map(|(pattern, replacements)| {
+                Regex::new(pattern)
+                    .with_context(|| format!("invalid mirror pattern: [{pattern}]"))
+                    .map(|pattern| MirrorRule {
+                        pattern,
+                        replacements: replacements.clone(),
+                    })
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Self)
+    }
+
+    /// every replacement of every matching rule, in the order they appear in the config
+    pub fn candidates(&self, url: &HumanUrl) -> Vec<HumanUrl> {
+        let original = url.to_string();
+        self.0
+            .iter()
+            .filter(|rule| rule.pattern.is_match(&original))
+            .flat_map(|rule| {
+                rule.replacements.iter().map(|replacement| {
+                    rule.pattern
+                        .replace(&original, replacement.as_str())
+                        .to_string()
+                })
+            })
+            .filter_map(|candidate| {
+                HumanUrl::from_str(&candidate)
+                    .tap_err(|error| tracing::warn!(%candidate, ?error, "mirror rule produced an invalid url"))
+                    .ok()
+            })
+            .filter(|candidate| candidate != url)
+            .unique()
+            .collect()
+    }
+
+    /// a single byte is enough to tell whether the file is there, of a host which ignores the range only the first chunk is read
+    /// before the response is dropped
+    async fn probe(url: &HumanUrl) -> Result<()> {
+        http_client()?
+            .get(url.to_string())
+            .header(reqwest::header::RANGE, "bytes=0-0")
+            .send()
+            .map_context("sending request")
+            .and_then(|response| challenge::check_response(response, false))
+            .and_then(|checked| {
+                checked
+                    .response
+                    .error_for_status()
+                    .context("bad status code")
+                    .pipe(ready)
+            })
+            .await
+            .map(|_| ())
+            .with_context(|| format!("probing [{url}]"))
+    }
+
+    /// the original url is only checked when there are mirrors to fall back to
+    #[instrument(skip(self))]
+    pub async fn resolve(&self, url: HumanUrl) -> Result<ResolvedUrl> {
+        let candidates = self.candidates(&url);
+        if candidates.is_empty() {
+            return Ok(ResolvedUrl { url, mirror: None });
+        }
+        let mut errors = match Self::probe(&url).await {
+            Ok(()) => return Ok(ResolvedUrl { url, mirror: None }),
+            Err(message) => vec![message],
+        };
+        for candidate in candidates {
+            match Self::probe(&candidate).await {
+                Ok(()) => {
+                    tracing::info!(%url, mirror=%candidate, "original url does not work, using a mirror");
+                    return Ok(ResolvedUrl {
+                        url: candidate.clone(),
+                        mirror: Some(candidate),
+                    });
+                }
+                Err(message) => errors.push(message),
+            }
+        }
+        Err(anyhow::anyhow!("neither the original url nor any of the mirrors worked:\n{errors:#?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirrors(rules: &[(&str, &[&str])]) -> Result<Mirrors> {
+        rules
+            .iter()
+            .map(|(pattern, replacements)| (pattern.to_string(), replacements.iter().map(ToString::to_string).collect()))
+            .collect::<IndexMap<_, _>>()
+            .pipe_ref(Mirrors::new)
+    }
+
+    #[test]
+    fn test_candidates_in_config_order() -> Result<()> {
+        let mirrors = mirrors(&[
+            (r"^https?://(.*)$", &["https://web.archive.org/web/2id_/https://$1"]),
+            (
+                r"^https://files\.example\.com/(.*)$",
+                &["https://mirror-a.example.org/$1", "https://mirror-b.example.org/$1"],
+            ),
+        ])?;
+        let url = HumanUrl::from_str("https://files.example.com/mods/some-mod.7z")?;
+        assert_eq!(
+            mirrors
+                .candidates(&url)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "https://web.archive.org/web/2id_/https://files.example.com/mods/some-mod.7z",
+                "https://mirror-a.example.org/mods/some-mod.7z",
+                "https://mirror-b.example.org/mods/some-mod.7z",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_matching_rule() -> Result<()> {
+        let mirrors = mirrors(&[(r"^https://files\.example\.com/(.*)$", &["https://mirror.example.org/$1"])])?;
+        assert!(mirrors
+            .candidates(&HumanUrl::from_str("https://other.example.com/some-mod.7z")?)
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(mirrors(&[("(", &[])]).is_err());
+    }
+}
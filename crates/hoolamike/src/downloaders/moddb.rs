@@ -0,0 +1,158 @@
+use {
+    super::{helpers::FutureAnyhowExt, http_client::http_client_builder},
+    crate::modlist_json::HumanUrl,
+    anyhow::{Context, Result},
+    futures::TryFutureExt,
+    std::future::ready,
+    tap::prelude::*,
+    tracing::instrument,
+};
+
+pub struct ModDBDownloader {}
+
+pub mod response_parsing {
+    use {
+        crate::modlist_json::HumanUrl,
+        anyhow::{Context, Result},
+        itertools::Itertools,
+        scraper::{Html, Selector},
+        tap::prelude::*,
+        url::Url,
+    };
+
+    /// moddb lists every mirror of a file on `/downloads/start/<id>/all`
+    pub fn mirror_listing_url(url: &HumanUrl) -> Result<HumanUrl> {
+        let url: &Url = url.as_ref();
+        match url.path().trim_end_matches('/').ends_with("/all") {
+            true => url.clone(),
+            false => url
+                .join(&format!("{}/all", url.path().trim_end_matches('/')))
+                .with_context(|| format!("building mirror listing url for [{url}]"))?,
+        }
+        .pipe(HumanUrl::from)
+        .pipe(Ok)
+    }
+
+    /// links to the mirrors, in the order in which they appear on the page
+    pub fn mirror_urls(page_url: &HumanUrl, contents: &str) -> Result<Vec<HumanUrl>> {
+        let page_url: &Url = page_url.as_ref();
+        Selector::parse("a[href*='/downloads/mirror/']")
+            .map_err(|e| anyhow::anyhow!("{e:?}"))
+            .context("parsing selector")
+            .and_then(|selector| {
+                Html::parse_document(contents)
+                    .select(&selector)
+                    .filter_map(|link| link.attr("href"))
+                    .map(|href| {
+                        page_url
+                            .join(href)
+                            .with_context(|| format!("bad mirror url: {href}"))
+                            .map(HumanUrl::from)
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .map(|mirrors| mirrors.into_iter().unique().collect())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use {super::*, std::str::FromStr};
+
+        #[test]
+        fn test_mirror_listing_url() -> Result<()> {
+            let start = HumanUrl::from_str("https://www.moddb.com/downloads/start/123456")?;
+            assert_eq!(mirror_listing_url(&start)?.to_string(), "https://www.moddb.com/downloads/start/123456/all");
+            let all = HumanUrl::from_str("https://www.moddb.com/downloads/start/123456/all")?;
+            assert_eq!(mirror_listing_url(&all)?.to_string(), all.to_string());
+            Ok(())
+        }
+
+        #[test]
+        fn test_mirror_urls() -> Result<()> {
+            let page_url = HumanUrl::from_str("https://www.moddb.com/downloads/start/123456/all")?;
+            let page = r#"
+                <div class="row clear"><p><a href="/downloads/mirror/123456/115/deadbeef/?referer=x">Mirror A</a></p></div>
+                <div class="row clear"><p><a href="/downloads/mirror/123456/120/cafebabe/?referer=x">Mirror B</a></p></div>
+                <div class="row clear"><p><a href="/downloads/mirror/123456/115/deadbeef/?referer=x">Mirror A again</a></p></div>
+                <a href="/mods/some-mod">not a mirror</a>
+            "#;
+            assert_eq!(
+                mirror_urls(&page_url, page)?
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                vec![
+                    "https://www.moddb.com/downloads/mirror/123456/115/deadbeef/?referer=x",
+                    "https://www.moddb.com/downloads/mirror/123456/120/cafebabe/?referer=x",
+                ]
+            );
+            Ok(())
+        }
+    }
+}
+
+impl ModDBDownloader {
+    /// follows redirects of a single mirror, succeeding only if it ends up serving a file rather than a html page
+    #[instrument(skip(client))]
+    async fn resolve_mirror(client: &reqwest::Client, mirror: HumanUrl) -> Result<HumanUrl> {
+        client
+            .get(mirror.to_string())
+            .send()
+            .map_context("fetching mirror")
+            .and_then(|res| {
+                res.error_for_status()
+                    .context("bad status code")
+                    .pipe(ready)
+            })
+            .await
+            .and_then(|res| {
+                res.headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|content_type| content_type.to_str().ok())
+                    .is_some_and(|content_type| content_type.starts_with("text/html"))
+                    .then(|| anyhow::anyhow!("mirror [{}] responded with a html page instead of a file", res.url()))
+                    .map(Err)
+                    .unwrap_or_else(|| res.url().clone().pipe(HumanUrl::from).pipe(Ok))
+            })
+            .with_context(|| format!("resolving mirror [{mirror}]"))
+    }
+
+    #[instrument]
+    pub async fn download(url: HumanUrl) -> Result<HumanUrl> {
+        let client = http_client_builder()
+            .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/109.0.0.0 Safari/537.36")
+            .build()
+            .context("bad http client")?;
+        let listing_url = response_parsing::mirror_listing_url(&url)?;
+        let mirrors = client
+            .get(listing_url.to_string())
+            .send()
+            .map_context("fetching the mirror listing")
+            .and_then(|res| {
+                res.error_for_status()
+                    .context("bad status code")
+                    .pipe(ready)
+            })
+            .and_then(|res| res.text().map_context("extracting text"))
+            .and_then(|text| {
+                tokio::task::spawn_blocking({
+                    cloned![listing_url];
+                    move || response_parsing::mirror_urls(&listing_url, &text)
+                })
+                .map_context("thread crashed")
+                .and_then(ready)
+            })
+            .await
+            .with_context(|| format!("listing mirrors on [{listing_url}]"))?
+            .tap(|mirrors| tracing::debug!(?mirrors, "found moddb mirrors"));
+
+        let mut errors = vec![];
+        for mirror in mirrors {
+            match Self::resolve_mirror(&client, mirror).await {
+                Ok(resolved) => return Ok(resolved.tap(|url| tracing::debug!(%url, "resolved moddb mirror"))),
+                Err(message) => errors.push(message.tap(|message| tracing::debug!(?message, "mirror failed, trying the next one"))),
+            }
+        }
+        Err(anyhow::anyhow!("none of the mirrors worked:\n{errors:#?}")).with_context(|| format!("preparing ModDB download for [{url}]"))
+    }
+}
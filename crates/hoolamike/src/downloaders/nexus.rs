@@ -1,5 +1,8 @@
 use {
-    super::helpers::{FutureAnyhowExt, ReqwestPrettyJsonResponse},
+    super::{
+        helpers::{FutureAnyhowExt, ReqwestPrettyJsonResponse},
+        http_client::http_client_builder,
+    },
     crate::{
         modlist_json::{HumanUrl, NexusGameName, NexusState},
         nxm_handler::NxmDownloadLink,
@@ -10,7 +13,6 @@ use {
     reqwest::{
         header::{HeaderMap, HeaderValue},
         Client,
-        ClientBuilder,
         Response,
     },
     serde::{Deserialize, Serialize},
@@ -19,12 +21,18 @@ use {
         iter::{empty, once},
         str::FromStr,
         sync::Arc,
+        time::Duration,
     },
     tap::prelude::*,
+    tracing_indicatif::span_ext::IndicatifSpanExt,
 };
 
 pub struct NexusDownloader {
     client: Client,
+    /// quota reported by the most recent api response
+    rate_limit: parking_lot::Mutex<Option<ThrottlingHeaders>>,
+    /// api requests are sent one by one, so that pacing applies to all of them
+    pacing: tokio::sync::Mutex<()>,
 }
 
 const AUTH_HEADER: &str = "apikey";
@@ -69,7 +77,10 @@ impl DownloadFileRequest {
 #[serde(transparent)]
 pub struct DownloadLinkResponse(Vec<NexusDownloadLink>);
 
-#[derive(Debug)]
+/// below this many requests left, the remaining ones are spread evenly until the quota resets
+const LOW_QUOTA: usize = 20;
+
+#[derive(Debug, Clone)]
 pub struct ThrottlingHeaders {
     /// X-RL-Hourly-Limit →100
     pub hourly_limit: usize,
@@ -115,6 +126,29 @@ impl ThrottlingHeaders {
             daily_reset: header(headers, "X-RL-Daily-Reset")?,
         })
     }
+
+    /// how long to wait before the next request, the hourly quota only starts being used up once the daily one is gone
+    pub fn pace(&self, now: DateTime<Utc>) -> Duration {
+        if self.daily_remaining > 0 {
+            return Duration::ZERO;
+        }
+        let until_reset = (self.hourly_reset - now).to_std().unwrap_or_default();
+        match self.hourly_remaining {
+            0 => until_reset,
+            remaining if remaining < LOW_QUOTA => until_reset / remaining as u32,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+impl std::fmt::Display for ThrottlingHeaders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "nexus api: {}/{} daily, {}/{} hourly requests left",
+            self.daily_remaining, self.daily_limit, self.hourly_remaining, self.hourly_limit
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -146,6 +180,20 @@ impl DownloadFileRequest {
     }
 }
 
+/// the api only hands out download links to premium users, everyone else has to click through the website
+pub fn is_premium_required(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|error| error.downcast_ref::<reqwest::Error>())
+        .any(|error| error.status() == Some(reqwest::StatusCode::FORBIDDEN))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidateResponse {
+    pub name: String,
+    pub is_premium: bool,
+}
+
 #[derive(derive_more::From, Debug)]
 pub enum DownloadLinkKind {
     Premium(DownloadFileRequest),
@@ -165,15 +213,44 @@ impl NexusDownloader {
                 header.map(|(key, value)| map.tap_mut(|map| map.insert(key, value).pipe(|_| ())))
             })
             .and_then(|headers| {
-                ClientBuilder::new()
+                http_client_builder()
                     .default_headers(headers)
                     .build()
                     .context("building http client")
             })
-            .map(|client| Self { client })
+            .map(|client| Self {
+                client,
+                rate_limit: Default::default(),
+                pacing: Default::default(),
+            })
             .context("building NexusDownloader")
     }
 
+    fn record_rate_limit(&self, response: &Response) {
+        match ThrottlingHeaders::from_response(response) {
+            Ok(rate_limit) => {
+                tracing::debug!(?rate_limit);
+                tracing::Span::current().pb_set_message(&rate_limit.to_string());
+                *self.rate_limit.lock() = Some(rate_limit);
+            }
+            Err(reason) => tracing::debug!(?reason, "no rate limit headers in the response"),
+        }
+    }
+
+    /// waits when the quota is about to run out instead of letting nexus answer with 429
+    async fn pace(&self) {
+        let rate_limit = self.rate_limit.lock().clone();
+        if let Some(rate_limit) = rate_limit {
+            match rate_limit.pace(Utc::now()) {
+                Duration::ZERO => {}
+                wait => {
+                    tracing::warn!("{rate_limit}, waiting {} before the next request", indicatif::HumanDuration(wait));
+                    tokio::time::sleep(wait).await
+                }
+            }
+        }
+    }
+
     async fn generate_download_link(self: Arc<Self>, download_link: &DownloadLinkKind) -> Result<DownloadLinkResponse> {
         let (download_file_request, query_params) = match download_link {
             DownloadLinkKind::Premium(download_file_request) => (download_file_request, String::new()),
@@ -185,19 +262,33 @@ impl NexusDownloader {
             ),
         };
         let url = format!("{}{query_params}", download_file_request.nexus_api_url());
+        let pacing = self.pacing.lock().await;
+        self.pace().await;
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .map_context("sending request")
+            .inspect_ok(|response| self.record_rate_limit(response))
+            .await;
+        drop(pacing);
+        response
+            .pipe(ready)
+            .and_then(|response| response.json_response_ok(|_| Ok(())))
+            .await
+            .with_context(|| format!("when fetching from {url}"))
+    }
+    pub async fn validate(self: Arc<Self>) -> Result<ValidateResponse> {
+        let url = format!("{API_BASE_URL}/v1/users/validate.json");
         self.client
             .get(&url)
             .send()
             .map_context("sending request")
-            .inspect_ok(|response| {
-                ThrottlingHeaders::from_response(response)
-                    .tap_ok(|response| tracing::debug!("{response:?}"))
-                    .pipe(|_| ())
-            })
             .and_then(|response| response.json_response_ok(|_| Ok(())))
             .await
             .with_context(|| format!("when fetching from {url}"))
     }
+
     pub async fn download(self: Arc<Self>, request: impl Into<DownloadLinkKind>) -> Result<HumanUrl> {
         let request = request.into();
         self.clone()
@@ -214,3 +305,43 @@ impl NexusDownloader {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, chrono::TimeZone};
+
+    fn rate_limit(daily_remaining: usize, hourly_remaining: usize) -> ThrottlingHeaders {
+        ThrottlingHeaders {
+            hourly_limit: 100,
+            hourly_remaining,
+            hourly_reset: Utc.with_ymd_and_hms(2019, 2, 1, 13, 0, 0).unwrap(),
+            daily_limit: 2500,
+            daily_remaining,
+            daily_reset: Utc.with_ymd_and_hms(2019, 2, 2, 0, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_pacing() {
+        let now = Utc.with_ymd_and_hms(2019, 2, 1, 12, 0, 0).unwrap();
+        assert_eq!(rate_limit(10, 0).pace(now), Duration::ZERO);
+        assert_eq!(rate_limit(0, 50).pace(now), Duration::ZERO);
+        assert_eq!(rate_limit(0, 10).pace(now), Duration::from_secs(360));
+        assert_eq!(rate_limit(0, 0).pace(now), Duration::from_secs(3600));
+        // reset already happened, the headers are just stale
+        assert_eq!(rate_limit(0, 0).pace(now + chrono::Duration::hours(2)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_parse_reset_dates() -> Result<()> {
+        assert_eq!(
+            DateTime::<Utc>::from_str("2019-02-02 00:00:00 +0000")?,
+            Utc.with_ymd_and_hms(2019, 2, 2, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            DateTime::<Utc>::from_str("2019-02-01T12:00:00+00:00")?,
+            Utc.with_ymd_and_hms(2019, 2, 1, 12, 0, 0).unwrap()
+        );
+        Ok(())
+    }
+}
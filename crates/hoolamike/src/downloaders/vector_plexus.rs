@@ -0,0 +1,22 @@
+use {
+    crate::modlist_json::{HumanUrl, VectorPlexusState},
+    anyhow::{Context, Result},
+    std::str::FromStr,
+    tap::prelude::*,
+};
+
+pub struct VectorPlexusDownloader {}
+
+const WEBSITE_BASE_URL: &str = "https://vectorplexus.com";
+
+impl VectorPlexusDownloader {
+    /// vectorplexus only serves files through an IPS4 OAuth login, so the best that can be done is pointing the user at the right page
+    pub fn file_page_url(VectorPlexusState { ips4_mod, ips4_url, .. }: &VectorPlexusState) -> Result<HumanUrl> {
+        match ips4_url.is_empty() {
+            false => HumanUrl::from_str(ips4_url).with_context(|| format!("bad url: {ips4_url}")),
+            true => format!("{WEBSITE_BASE_URL}/files/file/{ips4_mod}-/")
+                .pipe_deref(HumanUrl::from_str)
+                .context("invalid url"),
+        }
+    }
+}
@@ -1,13 +1,16 @@
 use {
-    super::helpers::FutureAnyhowExt,
-    crate::modlist_json::{HumanUrl, WabbajackCDNDownloaderState},
+    super::{helpers::FutureAnyhowExt, http_client::http_client},
+    crate::{
+        install_modlist::download_cache::compare_hash,
+        modlist_json::{HumanUrl, WabbajackCDNDownloaderState},
+    },
     anyhow::{Context, Result},
     flate2::read::GzDecoder,
     futures::TryFutureExt,
     itertools::Itertools,
     reqwest::Client,
     serde::{Deserialize, Serialize},
-    std::{future::ready, io::Read},
+    std::{future::ready, io::Read, time::Duration},
     tap::prelude::*,
     url::Url,
 };
@@ -16,6 +19,9 @@ pub struct WabbajackCDNDownloader {}
 
 const MAGIC_FILENAME: &str = "definition.json.gz";
 
+/// a single broken part should not fail a multi-gigabyte download
+const PART_ATTEMPTS: u32 = 3;
+
 #[cfg(test)]
 mod test_responses;
 
@@ -40,6 +46,15 @@ pub struct WabbajackCdnFile {
     pub parts: Vec<Part>,
 }
 
+/// where a part of a cdn file is served from and what it has to look like
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartDownload {
+    pub url: HumanUrl,
+    pub offset: u64,
+    pub size: u64,
+    pub hash: String,
+}
+
 #[test]
 fn test_example_cdn_files() -> Result<()> {
     #[rustfmt::skip]
@@ -98,7 +113,8 @@ fn parse_wabbajack_cdn_file_response(contents: &str) -> Result<WabbajackCdnFile>
 }
 
 impl WabbajackCDNDownloader {
-    pub async fn prepare_download(WabbajackCDNDownloaderState { url }: WabbajackCDNDownloaderState) -> Result<Vec<HumanUrl>> {
+    /// fetches the file definition, the returned url is the (remapped) base url the parts are served from
+    pub async fn fetch_definition(url: HumanUrl) -> Result<(HumanUrl, WabbajackCdnFile)> {
         let url = url
             .clone()
             .conv::<url::Url>()
@@ -106,7 +122,7 @@ impl WabbajackCDNDownloader {
             .conv::<HumanUrl>();
 
         let deduced_url = format!("{url}/{MAGIC_FILENAME}");
-        Client::new()
+        http_client()?
             .get(deduced_url.to_string())
             .send()
             .map_with_context(|| format!("fetching from [{deduced_url}]"))
@@ -132,27 +148,92 @@ impl WabbajackCDNDownloader {
             })
             .map_ok({
                 let url = url.clone();
-                move |WabbajackCdnFile {
-                          munged_name,
-                          parts,
-                          author: _,
-                          server_assigned_unique_id: _,
-                          hash: _,
-                          original_file_name: _,
-                          size: _,
-                      }| {
-                    parts
-                        .into_iter()
-                        .map(move |Part { index, .. }| {
-                            url.clone().tap_mut(|url| {
-                                url.as_mut()
-                                    .set_path(&format!("{munged_name}/parts/{index}"))
-                            })
-                        })
-                        .collect_vec()
-                }
+                move |file| (url, file)
             })
             .await
             .with_context(|| format!("fetching stuff from deduced url: [{deduced_url}] based on [{url}]"))
     }
+
+    pub fn part_url(url: &HumanUrl, munged_name: &str, index: usize) -> HumanUrl {
+        url.clone().tap_mut(|url| {
+            url.as_mut()
+                .set_path(&format!("{munged_name}/parts/{index}"))
+        })
+    }
+
+    /// sorted by index, so that writing them one after another reassembles the file
+    pub fn part_downloads(url: &HumanUrl, WabbajackCdnFile { munged_name, parts, .. }: WabbajackCdnFile) -> Vec<PartDownload> {
+        parts
+            .into_iter()
+            .sorted_by_key(|part| part.index)
+            .map(|Part { hash, index, offset, size }| PartDownload {
+                url: Self::part_url(url, &munged_name, index),
+                offset: offset as u64,
+                size: size as u64,
+                hash,
+            })
+            .collect_vec()
+    }
+
+    pub async fn prepare_download(WabbajackCDNDownloaderState { url }: WabbajackCDNDownloaderState) -> Result<Vec<PartDownload>> {
+        Self::fetch_definition(url)
+            .await
+            .map(|(url, definition)| Self::part_downloads(&url, definition))
+    }
+
+    async fn try_download_part(client: &Client, PartDownload { url, size, hash, .. }: &PartDownload) -> Result<Vec<u8>> {
+        client
+            .get(url.to_string())
+            .send()
+            .map_context("sending request")
+            .and_then(|response| {
+                response
+                    .error_for_status()
+                    .context("bad status code")
+                    .pipe(ready)
+            })
+            .and_then(|response| response.bytes().map_context("reading part"))
+            .await
+            .map(Vec::from)
+            .and_then(|bytes| match bytes.len() as u64 == *size {
+                true => Ok(bytes),
+                false => Err(anyhow::anyhow!("unexpected size (expected [{size}], found [{}])", bytes.len())),
+            })
+            .and_then(|bytes| compare_hash(xxhash_rust::xxh64::xxh64(&bytes, 0), hash).map(|_| bytes))
+    }
+
+    /// fetches a whole part into memory, its size and hash are checked before it is handed out
+    pub async fn download_part(client: &Client, part: &PartDownload) -> Result<Vec<u8>> {
+        let mut errors = vec![];
+        for attempt in 0..PART_ATTEMPTS {
+            if attempt > 0 {
+                tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+            }
+            match Self::try_download_part(client, part).await {
+                Ok(bytes) => return Ok(bytes),
+                Err(message) => {
+                    tracing::debug!(url=%part.url, %attempt, ?message, "could not download part");
+                    errors.push(message)
+                }
+            }
+        }
+        Err(anyhow::anyhow!("{errors:#?}")).with_context(|| {
+            format!(
+                "downloading part at offset [{}] from [{}] failed [{PART_ATTEMPTS}] times",
+                part.offset, part.url
+            )
+        })
+    }
+}
+
+#[test]
+fn test_part_downloads_are_ordered() -> Result<()> {
+    use std::str::FromStr;
+    #[rustfmt::skip]
+    const DEFINITION: &str = r#"{"Author":"lively","OriginalFileName":"big.7z","Size":30,"Hash":"AAAAAAAAAAA=","Parts":[{"Size":10,"Offset":10,"Hash":"b","Index":1},{"Size":10,"Offset":0,"Hash":"a","Index":0},{"Size":10,"Offset":20,"Hash":"c","Index":2}],"ServerAssignedUniqueId":null,"MungedName":"big.7z_munged"}"#;
+    let definition = parse_wabbajack_cdn_file_response(DEFINITION)?;
+    let parts = WabbajackCDNDownloader::part_downloads(&HumanUrl::from_str("https://authored-files.wabbajack.org/big.7z_munged")?, definition);
+    assert_eq!(parts.iter().map(|part| part.offset).collect_vec(), vec![0, 10, 20]);
+    assert_eq!(parts[1].url.to_string(), "https://authored-files.wabbajack.org/big.7z_munged/parts/1");
+    Ok(())
 }
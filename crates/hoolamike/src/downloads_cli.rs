@@ -0,0 +1,255 @@
+//! `hoolamike downloads import <dir>` picks up archives already downloaded by wabbajack or mod organizer 2, so that they don't have to be downloaded again
+use {
+    crate::{
+        config_file::{HoolamikeConfig, InstallationConfig},
+        install_modlist::download_cache::{calculate_hash, hash_cache, to_base_64_from_u64, DownloadCache},
+        modlist_json::ArchiveDescriptor,
+        wabbajack_file::WabbajackFile,
+    },
+    anyhow::{Context, Result},
+    futures::{FutureExt, StreamExt, TryFutureExt},
+    itertools::Itertools,
+    std::{
+        collections::{BTreeMap, HashMap},
+        path::{Path, PathBuf},
+    },
+    tap::prelude::*,
+    tracing::{info, warn},
+};
+
+#[derive(clap::Args)]
+pub struct DownloadsCliCommand {
+    #[command(subcommand)]
+    pub command: DownloadsCliCommandInner,
+}
+
+#[derive(clap::Subcommand)]
+pub enum DownloadsCliCommandInner {
+    /// scans a downloads folder of another mod manager (wabbajack, mod organizer 2) for archives of the modlist
+    /// and brings them into `downloaders.downloads_directory`
+    Import {
+        /// folder to scan, subdirectories included
+        directory: PathBuf,
+        #[arg(long, value_enum, default_value_t = Default::default())]
+        mode: ImportMode,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum ImportMode {
+    /// the files stay where they are, so the other mod manager keeps working
+    #[default]
+    Symlink,
+    /// copies and removes the original when the folders are on different filesystems
+    Move,
+}
+
+#[cfg(unix)]
+fn symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(from, to)
+}
+
+/// creating symlinks needs developer mode (or an elevated prompt) on windows
+#[cfg(windows)]
+fn symlink(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(from, to)
+}
+
+fn import_file(from: &Path, to: &Path, mode: ImportMode) -> Result<()> {
+    match mode {
+        ImportMode::Symlink => from.canonicalize().and_then(|from| symlink(&from, to)),
+        ImportMode::Move => std::fs::rename(from, to).or_else(|_| std::fs::copy(from, to).and_then(|_| std::fs::remove_file(from))),
+    }
+    .with_context(|| format!("importing [{}] as [{}] ({mode:?})", from.display(), to.display()))
+}
+
+/// every name gets the file - with `ImportMode::Move` the first one takes it and the others get copies of it
+fn import_as(from: &Path, to: &[PathBuf], mode: ImportMode) -> Vec<Result<()>> {
+    to.iter()
+        .enumerate()
+        .map(|(idx, path)| match (idx, mode) {
+            (0, _) | (_, ImportMode::Symlink) => import_file(from, path, mode),
+            (_, ImportMode::Move) => std::fs::copy(&to[0], path)
+                .map(|_| ())
+                .with_context(|| format!("copying [{}] as [{}]", to[0].display(), path.display())),
+        })
+        .collect()
+}
+
+/// the same archive can be listed more than once under different names, each of them is missing
+fn matching_archives<'a>(missing: &'a HashMap<u64, Vec<ArchiveDescriptor>>, size: u64, hash: &'a str) -> impl Iterator<Item = &'a ArchiveDescriptor> {
+    missing
+        .get(&size)
+        .into_iter()
+        .flatten()
+        .filter(move |descriptor| descriptor.hash == hash)
+}
+
+impl DownloadsCliCommand {
+    pub async fn run(self, config: HoolamikeConfig) -> Result<()> {
+        match self.command {
+            DownloadsCliCommandInner::Import { directory, mode } => import(config, directory, mode).await,
+        }
+    }
+}
+
+async fn import(
+    HoolamikeConfig {
+        downloaders,
+        installation: InstallationConfig { wabbajack_file_path, .. },
+        ..
+    }: HoolamikeConfig,
+    directory: PathBuf,
+    mode: ImportMode,
+) -> Result<()> {
+    hash_cache::configure_hash_cache(&downloaders.downloads_directory, false).context("loading hash cache")?;
+    let download_cache = DownloadCache::new(downloaders.downloads_directory).context("initializing download cache")?;
+    let (_handle, WabbajackFile { modlist, .. }) = WabbajackFile::load_wabbajack_file(wabbajack_file_path).context("loading modlist file")?;
+
+    // only files with the size of a missing archive are worth hashing
+    let missing = modlist
+        .archives
+        .into_iter()
+        .map(|archive| archive.descriptor)
+        .filter(|descriptor| {
+            !download_cache
+                .download_output_path(descriptor.name.clone())
+                .exists()
+        })
+        .into_group_map_by(|descriptor| descriptor.size);
+    let candidates = walkdir::WalkDir::new(&directory)
+        .into_iter()
+        .filter_map(|entry| entry.tap_err(|error| warn!(?error, "skipping")).ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| {
+            entry
+                .metadata()
+                .is_ok_and(|metadata| missing.contains_key(&metadata.len()))
+        })
+        .map(|entry| entry.into_path())
+        .collect_vec();
+    info!(
+        missing = missing.values().map(Vec::len).sum::<usize>(),
+        candidates = candidates.len(),
+        "scanning [{}]",
+        directory.display()
+    );
+
+    let matches = futures::stream::iter(candidates)
+        .map(|path| {
+            calculate_hash(path.clone())
+                .map_ok(to_base_64_from_u64)
+                .map(move |hash| (path, hash))
+        })
+        .buffer_unordered(num_cpus::get())
+        .filter_map(|(path, hash)| {
+            hash.tap_err(|error| warn!(path=%path.display(), ?error, "could not hash"))
+                .ok()
+                .map(|hash| (path, hash))
+                .pipe(futures::future::ready)
+        })
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .flat_map(|(path, hash)| {
+            std::fs::metadata(&path)
+                .map(|metadata| {
+                    matching_archives(&missing, metadata.len(), &hash)
+                        .map(|descriptor| (descriptor.name.clone(), (path.clone(), hash.clone())))
+                        .collect_vec()
+                })
+                .unwrap_or_default()
+        })
+        // the same archive can be found in more than one place, one copy is enough
+        .collect::<BTreeMap<_, _>>();
+
+    let imported = matches
+        .into_iter()
+        .into_group_map_by(|(_, source)| source.clone())
+        .into_iter()
+        .map(|((from, hash), names)| {
+            let to = names
+                .iter()
+                .map(|(name, _)| download_cache.download_output_path(name.clone()))
+                .collect_vec();
+            import_as(&from, &to, mode)
+                .into_iter()
+                .zip(names.iter().zip(&to))
+                .filter_map(|(imported, ((name, _), to))| {
+                    imported
+                        .tap_ok(|_| hash_cache::remember_hash(to, &hash))
+                        .tap_ok(|_| info!(%name, "imported [{}]", from.display()))
+                        .tap_err(|error| warn!(%name, ?error, "could not import"))
+                        .ok()
+                })
+                .count()
+        })
+        .sum::<usize>();
+    let still_missing = missing.values().map(Vec::len).sum::<usize>() - imported;
+    info!(%imported, %still_missing, "import finished, run `hoolamike install` to download the rest");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptor(name: &str, hash: &str, size: u64) -> ArchiveDescriptor {
+        ArchiveDescriptor {
+            hash: hash.into(),
+            meta: String::new(),
+            name: name.into(),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_every_name_of_an_archive_matches() {
+        let missing = [
+            descriptor("SkyUI.7z", "skyui=", 10),
+            descriptor("SkyUI_5_2_SE.7z", "skyui=", 10),
+            descriptor("Other.7z", "other=", 10),
+        ]
+        .into_iter()
+        .into_group_map_by(|descriptor| descriptor.size);
+        assert_eq!(
+            matching_archives(&missing, 10, "skyui=")
+                .map(|descriptor| descriptor.name.as_str())
+                .collect_vec(),
+            ["SkyUI.7z", "SkyUI_5_2_SE.7z"]
+        );
+        assert_eq!(matching_archives(&missing, 11, "skyui=").count(), 0);
+    }
+
+    #[test]
+    fn test_moved_files_are_copied_for_every_other_name() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let from = directory.path().join("SkyUI.7z");
+        std::fs::write(&from, b"archive")?;
+        let to = [directory.path().join("a.7z"), directory.path().join("b.7z")];
+        import_as(&from, &to, ImportMode::Move)
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        assert!(!from.exists());
+        to.iter().try_for_each(|to| {
+            assert_eq!(std::fs::read(to)?, b"archive");
+            Ok(())
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_point_at_the_original_for_every_name() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let from = directory.path().join("SkyUI.7z");
+        std::fs::write(&from, b"archive")?;
+        let to = [directory.path().join("a.7z"), directory.path().join("b.7z")];
+        import_as(&from, &to, ImportMode::Symlink)
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        to.iter().try_for_each(|to| {
+            assert_eq!(std::fs::read_link(to)?, from.canonicalize()?);
+            Ok(())
+        })
+    }
+}
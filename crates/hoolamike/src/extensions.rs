@@ -1,2 +1,3 @@
 pub mod fallout_new_vegas_4gb_patch;
+pub mod mpi_installer;
 pub mod tale_of_two_wastelands_installer;
@@ -0,0 +1,136 @@
+//! remembers which assets of an mpi installation are done, so that a failed installation picks up where it stopped instead of
+//! transcoding all the audio and building all the archives again
+use {
+    super::manifest_file::asset::LocationIndex,
+    crate::json_store::JsonStore,
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeSet, path::Path},
+};
+
+/// one checkpoint per installer profile, so that resuming one installer does not skip the assets of another
+pub fn checkpoint_file_name(profile: &str) -> String {
+    format!("{profile}-checkpoint.json")
+}
+
+/// position of the asset in the manifest, stable for a given mpi version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, derive_more::Display)]
+pub struct AssetId(pub usize);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Completed {
+    /// asset ids of another version of the mpi file point at different assets
+    mpi_version: String,
+    /// resolved location paths, a checkpoint of an installation into another directory says nothing about this one
+    #[serde(default)]
+    locations: Vec<String>,
+    /// assets written straight into a folder
+    assets: BTreeSet<AssetId>,
+    /// write archive locations, their assets only end up on disk once the whole archive is built
+    archives: BTreeSet<LocationIndex>,
+}
+
+#[derive(Debug)]
+pub struct Checkpoint {
+    completed: JsonStore<Completed>,
+}
+
+impl Checkpoint {
+    pub fn load(path: &Path, mpi_version: &str, locations: Vec<String>) -> Self {
+        let completed = JsonStore::<Completed>::load(path.to_owned(), "mpi checkpoint");
+        let previous = completed.read(|completed| {
+            (completed.mpi_version == mpi_version && completed.locations == locations)
+                .then_some((completed.assets.len(), completed.archives.len()))
+                .ok_or_else(|| completed.mpi_version.clone())
+        });
+        match previous {
+            Ok((assets, archives)) => tracing::info!(%assets, %archives, "resuming mpi installation, skipping what the previous run finished"),
+            Err(previous) => {
+                if !previous.is_empty() {
+                    tracing::info!(%previous, current=%mpi_version, "checkpoint is for another mpi version or installation directory, starting over");
+                }
+                completed
+                    .update(|completed| {
+                        *completed = Completed {
+                            mpi_version: mpi_version.to_string(),
+                            locations,
+                            ..Default::default()
+                        }
+                    })
+                    .unwrap_or_else(|reason| tracing::warn!(?reason, "could not reset the mpi checkpoint"));
+            }
+        }
+        Self { completed }
+    }
+
+    pub fn is_asset_completed(&self, asset: AssetId) -> bool {
+        self.completed
+            .read(|completed| completed.assets.contains(&asset))
+    }
+
+    pub fn is_archive_built(&self, location: LocationIndex) -> bool {
+        self.completed
+            .read(|completed| completed.archives.contains(&location))
+    }
+
+    fn update(&self, force_flush: bool, update: impl FnOnce(&mut Completed)) {
+        self.completed
+            .update(update)
+            .and_then(|_| match force_flush {
+                true => self.completed.flush(),
+                false => Ok(()),
+            })
+            // the checkpoint is an optimization, failing to update it is not worth failing the installation over
+            .unwrap_or_else(|reason| tracing::warn!(?reason, "could not update the mpi checkpoint"))
+    }
+
+    pub fn complete_asset(&self, asset: AssetId) {
+        self.update(false, |completed| {
+            completed.assets.insert(asset);
+        })
+    }
+
+    /// archives take minutes to build, they are written down right away
+    pub fn complete_archive(&self, location: LocationIndex) {
+        self.update(true, |completed| {
+            completed.archives.insert(location);
+        })
+    }
+
+    /// the installation went through, the next one starts from scratch
+    pub fn finish(&self) {
+        self.completed
+            .remove()
+            .unwrap_or_else(|reason| tracing::warn!(?reason, "could not remove the mpi checkpoint"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tap::prelude::*};
+
+    #[test]
+    fn test_checkpoint_survives_reload_of_the_same_mpi_version() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join(checkpoint_file_name("ttw"));
+        let locations = || vec!["/games/ttw".to_string(), "/games/ttw/Data/TaleOfTwoWastelands - Main.bsa".to_string()];
+        Checkpoint::load(&path, "3.3.3", locations()).pipe(|checkpoint| {
+            checkpoint.complete_asset(AssetId(7));
+            checkpoint.complete_archive(LocationIndex(2));
+        });
+
+        let checkpoint = Checkpoint::load(&path, "3.3.3", locations());
+        assert!(checkpoint.is_asset_completed(AssetId(7)));
+        assert!(!checkpoint.is_asset_completed(AssetId(8)));
+        assert!(checkpoint.is_archive_built(LocationIndex(2)));
+        drop(checkpoint);
+
+        let checkpoint = Checkpoint::load(&path, "3.3.3", vec!["/games/ttw-2".to_string()]);
+        assert!(!checkpoint.is_asset_completed(AssetId(7)));
+        drop(checkpoint);
+
+        let checkpoint = Checkpoint::load(&path, "3.4.0", locations());
+        assert!(!checkpoint.is_asset_completed(AssetId(7)));
+        checkpoint.finish();
+        assert!(!path.exists());
+    }
+}
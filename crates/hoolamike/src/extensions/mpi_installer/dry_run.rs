@@ -0,0 +1,278 @@
+//! `--dry-run` - everything the installation needs is checked up front (resolved variables, locations which have to be
+//! read or written, post commands), nothing is written
+use {
+    super::{
+        manifest_file::{
+            asset::{Asset, LocationIndex},
+            location::{FolderLocation, Location},
+            PostCommand,
+        },
+        post_commands::{PostCommandsConfig, SkipPostCommands},
+        LocationsLookup,
+    },
+    crate::{helpers::human_readable_size, progress_bars_v2::events::print_report, utils::MaybeWindowsPath},
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    normalize_path::NormalizePath,
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+    tabled::{settings::Style, Tabled},
+    tap::prelude::*,
+};
+
+pub(super) fn location_path(location: &Location) -> PathBuf {
+    MaybeWindowsPath(location.value().to_owned())
+        .into_path()
+        .normalize()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Access {
+    read: bool,
+    write: bool,
+}
+
+impl std::fmt::Display for Access {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.read, self.write) {
+            (true, true) => write!(f, "read, write"),
+            (true, false) => write!(f, "read"),
+            (false, true) => write!(f, "write"),
+            (false, false) => write!(f, "unused"),
+        }
+    }
+}
+
+/// new assets come out of the mpi file, not out of their source location
+fn access(assets: &[Asset]) -> BTreeMap<LocationIndex, Access> {
+    assets
+        .iter()
+        .filter(|asset| !matches!(asset, Asset::XwmaFuz(_)))
+        .flat_map(|asset| {
+            [
+                (!matches!(asset, Asset::New(_))).then(|| (asset.source(), Access { read: true, write: false })),
+                Some((asset.target(), Access { read: false, write: true })),
+            ]
+        })
+        .flatten()
+        .fold(BTreeMap::new(), |mut access, (location, used)| {
+            access.entry(location).or_default().pipe(|access| {
+                access.read |= used.read;
+                access.write |= used.write;
+            });
+            access
+        })
+}
+
+/// directories which do not exist yet are created by the installation, whatever part of them exists has to be writable
+fn writable(path: &Path) -> Result<()> {
+    path.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .with_context(|| format!("no part of [{}] exists", path.display()))
+        .and_then(|existing| tempfile::tempfile_in(existing).with_context(|| format!("[{}] is not writable", existing.display())))
+        .map(|_| ())
+}
+
+fn check_location(location: &Location, access: Access) -> Result<()> {
+    let path = location_path(location);
+    match location {
+        Location::Folder(folder) => {
+            let FolderLocation { create_folder, .. } = &folder.inner;
+            if access.read {
+                std::fs::read_dir(&path).with_context(|| format!("cannot read directory [{}]", path.display()))?;
+            }
+            if access.write {
+                if !path.exists() && !create_folder {
+                    anyhow::bail!("[{}] does not exist and the installer does not create it", path.display());
+                }
+                writable(&path)?;
+            }
+            Ok(())
+        }
+        Location::ReadArchive(_) => std::fs::File::open(&path)
+            .with_context(|| format!("cannot read archive [{}]", path.display()))
+            .map(|_| ()),
+        Location::WriteArchive(_) => path
+            .parent()
+            .with_context(|| format!("[{}] has no parent directory", path.display()))
+            .and_then(writable),
+    }
+}
+
+#[derive(Tabled)]
+struct LocationRow {
+    #[tabled(rename = "#")]
+    index: u8,
+    name: String,
+    path: String,
+    access: String,
+    status: String,
+}
+
+enum SizeSource {
+    File(PathBuf),
+    Archive(PathBuf),
+}
+
+fn size_source(locations: &LocationsLookup, mpi_file: &Path, asset: &Asset) -> Option<SizeSource> {
+    match asset {
+        Asset::New(_) => Some(SizeSource::Archive(mpi_file.to_owned())),
+        Asset::XwmaFuz(_) => None,
+        asset => locations
+            .get(&asset.source())
+            .and_then(|location| match location {
+                Location::Folder(_) => location_path(location)
+                    .join(MaybeWindowsPath(asset.name().to_owned()).into_path())
+                    .pipe(SizeSource::File)
+                    .pipe(Some),
+                Location::ReadArchive(_) => Some(SizeSource::Archive(location_path(location))),
+                Location::WriteArchive(_) => None,
+            }),
+    }
+}
+
+/// the average size of a file in the archive, listing an archive is cheap compared to extracting it
+fn average_entry_size(archive: &Path) -> Option<u64> {
+    let size = std::fs::metadata(archive).ok()?.len();
+    crate::compression::ArchiveHandle::with_guessed(archive, archive.extension(), |mut archive| {
+        crate::compression::ProcessArchive::list_paths(&mut archive)
+    })
+    .ok()
+    .and_then(|entries| size.checked_div(entries.len() as u64))
+}
+
+/// (estimated bytes, assets whose size could not be estimated)
+fn estimate_output_size(locations: &LocationsLookup, assets: &[Asset], mpi_file: &Path) -> (u64, usize) {
+    let sources = assets
+        .iter()
+        .map(|asset| size_source(locations, mpi_file, asset))
+        .collect_vec();
+    let averages = sources
+        .iter()
+        .filter_map(|source| match source {
+            Some(SizeSource::Archive(archive)) => Some(archive),
+            _ => None,
+        })
+        .unique()
+        .map(|archive| (archive, average_entry_size(archive)))
+        .collect::<BTreeMap<_, _>>();
+    sources
+        .iter()
+        .map(|source| match source {
+            Some(SizeSource::File(file)) => std::fs::metadata(file).ok().map(|metadata| metadata.len()),
+            Some(SizeSource::Archive(archive)) => averages.get(archive).copied().flatten(),
+            None => None,
+        })
+        .fold((0, 0), |(estimated, unknown), size| match size {
+            Some(size) => (estimated + size, unknown),
+            None => (estimated, unknown + 1),
+        })
+}
+
+#[derive(Tabled)]
+struct PostCommandRow {
+    command: String,
+    hoolamike_will: String,
+}
+
+pub fn dry_run(
+    locations: &LocationsLookup,
+    assets: &[Asset],
+    post_commands: &[PostCommand],
+    post_commands_config: &PostCommandsConfig,
+    skip_post_commands: &SkipPostCommands,
+    mpi_file: &Path,
+) -> Result<()> {
+    let access = access(assets);
+    let checked = locations
+        .iter()
+        .map(|(index, location)| {
+            let access = access.get(index).copied().unwrap_or_default();
+            (index, location, access, check_location(location, access))
+        })
+        .collect_vec();
+    checked
+        .iter()
+        .map(|(index, location, access, status)| LocationRow {
+            index: index.0,
+            name: location.name().to_string(),
+            path: location_path(location).display().to_string(),
+            access: access.to_string(),
+            status: match status {
+                Ok(()) => "ok".to_string(),
+                Err(reason) => format!("{reason:#}"),
+            },
+        })
+        .pipe(tabled::Table::new)
+        .with(Style::modern())
+        .pipe(print_report);
+
+    let (estimated, unknown) = estimate_output_size(locations, assets, mpi_file);
+    print_report(format_args!(
+        "{} assets, estimated output size: ~{} (audio is transcoded, so this is rough){}",
+        assets.len(),
+        human_readable_size(estimated),
+        match unknown {
+            0 => String::new(),
+            unknown => format!(", {unknown} assets have no known size"),
+        }
+    ));
+
+    match post_commands.is_empty() {
+        true => print_report("no post commands"),
+        false => post_commands
+            .iter()
+            .map(|PostCommand { value, .. }| PostCommandRow {
+                command: value.clone(),
+                hoolamike_will: post_commands_config
+                    .plan(skip_post_commands, value)
+                    .describe(),
+            })
+            .pipe(tabled::Table::new)
+            .with(Style::modern())
+            .pipe(print_report),
+    }
+
+    match checked
+        .iter()
+        .filter(|(_, _, _, status)| status.is_err())
+        .count()
+    {
+        0 => {
+            tracing::info!("dry run found no problems, nothing was written");
+            Ok(())
+        }
+        problems => anyhow::bail!("{problems} locations cannot be used, fix the paths (or the variables they come from) in hoolamike.yaml"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(json: serde_json::Value) -> Result<Location> {
+        serde_json::from_value(json).context("bad location")
+    }
+
+    #[test]
+    fn test_locations_are_checked_by_access() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let existing = directory.path().display().to_string();
+        let missing = directory.path().join("missing").display().to_string();
+        let write = Access { read: false, write: true };
+        let read = Access { read: true, write: false };
+
+        let folder =
+            |value: &str, create_folder: bool| location(serde_json::json!({"Type": 0, "Name": "Destination", "Value": value, "CreateFolder": create_folder}));
+        assert!(check_location(&folder(&existing, false)?, write).is_ok());
+        assert!(check_location(&folder(&missing, true)?, write).is_ok());
+        assert!(check_location(&folder(&missing, false)?, write).is_err());
+        assert!(check_location(&folder(&missing, true)?, read).is_err());
+
+        let archive = location(serde_json::json!({"Type": 1, "Name": "Fallout - Meshes", "Value": format!("{missing}/Fallout - Meshes.bsa")}))?;
+        assert!(check_location(&archive, read).is_err());
+        Ok(())
+    }
+}
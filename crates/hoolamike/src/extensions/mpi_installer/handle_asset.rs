@@ -11,7 +11,7 @@ use {
         utils::{with_scoped_temp_path, ReadableCatchUnwindExt},
     },
     anyhow::{Context, Result},
-    hoola_audio::Mp3TargetChannelMode,
+    hoola_audio::{DecodeOptions, Mp3TargetChannelMode, TrackFilters},
     normalize_path::NormalizePath,
     std::{collections::BTreeMap, io::BufReader, sync::Arc},
     tap::prelude::*,
@@ -221,12 +221,35 @@ impl AssetContext {
                                     .and_then(|(_, source)| {
                                         with_scoped_temp_path(|buffer| {
                                             (match target_extension.as_str() {
-                                                "wav" => hoola_audio::convert_to_wav(&source, buffer, target_frequency)
-                                                    .context("converting to wav")
-                                                    .map(|_| buffer),
-                                                "mp3" => hoola_audio::convert_to_mp3(&source, buffer, target_bitrate, target_frequency, target_channel_mode)
-                                                    .context("converting to mp3")
-                                                    .map(|_| buffer),
+                                                "wav" => hoola_audio::convert_to_wav(
+                                                    &source,
+                                                    buffer,
+                                                    target_frequency,
+                                                    &TrackFilters::default(),
+                                                    DecodeOptions::default(),
+                                                )
+                                                .context("converting to wav")
+                                                .map(|_| buffer),
+                                                "mp3" => hoola_audio::convert_to_mp3(
+                                                    &source,
+                                                    buffer,
+                                                    target_bitrate,
+                                                    target_frequency,
+                                                    target_channel_mode,
+                                                    &TrackFilters::default(),
+                                                    DecodeOptions::default(),
+                                                )
+                                                .context("converting to mp3")
+                                                .map(|_| buffer),
+                                                "ogg" => hoola_audio::convert_to_ogg(
+                                                    &source,
+                                                    buffer,
+                                                    target_frequency,
+                                                    &TrackFilters::default(),
+                                                    DecodeOptions::default(),
+                                                )
+                                                .context("converting to ogg")
+                                                .map(|_| buffer),
                                                 other => Err(anyhow::anyhow!("extension [.{other}] is not supported by hoolamike, file an issue")),
                                             })
                                             .and_then(|buffer| {
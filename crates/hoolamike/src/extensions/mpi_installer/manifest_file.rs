@@ -74,7 +74,7 @@ pub struct Package {
     pub gui: Gui,
 }
 
-/// Tale of two Wastelands installer manifest file
+/// MPI installer manifest file (Tale of Two Wastelands is the best known one)
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
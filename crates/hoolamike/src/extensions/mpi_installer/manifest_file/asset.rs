@@ -155,6 +155,18 @@ impl Asset {
             Asset::XwmaFuz(_) => unimplemented!("Asset::XwmaFuz(_)"),
         }
     }
+    /// the source path is reused when the target does not name one
+    pub fn target_path(&self) -> &FileName {
+        let (source, target) = match self {
+            Asset::Copy(copy_asset) => (&copy_asset.source, &copy_asset.target),
+            Asset::New(new_asset) => (&new_asset.source, &new_asset.target),
+            Asset::Patch(patch_asset) => (&patch_asset.source, &patch_asset.target),
+            Asset::OggEnc2(ogg_enc2_asset) => (&ogg_enc2_asset.source, &ogg_enc2_asset.target),
+            Asset::AudioEnc(audio_enc_asset) => (&audio_enc_asset.source, &audio_enc_asset.target),
+            Asset::XwmaFuz(_) => unimplemented!("Asset::XwmaFuz(_)"),
+        };
+        target.path.as_ref().unwrap_or(&source.path)
+    }
     pub fn name(&self) -> &str {
         match self {
             Asset::Copy(copy_asset) => copy_asset.source.path.0 .0.as_str(),
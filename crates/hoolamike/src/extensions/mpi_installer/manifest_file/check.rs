@@ -10,6 +10,15 @@ use {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct Checksums(String);
 
+impl Checksums {
+    /// a file can have more than one accepted checksum, one for every supported version of it
+    pub fn accepted(&self) -> impl Iterator<Item = &str> {
+        self.0
+            .split(|c: char| !c.is_ascii_hexdigit())
+            .filter(|checksum| !checksum.is_empty())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
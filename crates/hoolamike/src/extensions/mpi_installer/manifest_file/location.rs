@@ -48,6 +48,13 @@ impl Location {
             Location::WriteArchive(l) => l.inner.name.as_str(),
         }
     }
+    pub fn value(&self) -> &str {
+        match self {
+            Location::Folder(l) => l.inner.value.as_str(),
+            Location::ReadArchive(l) => l.inner.value.as_str(),
+            Location::WriteArchive(l) => l.inner.value.as_str(),
+        }
+    }
     pub fn value_mut(&mut self) -> &mut String {
         match self {
             Location::Folder(WithKindGuard {
@@ -0,0 +1,440 @@
+use {
+    super::manifest_file::PostCommand,
+    crate::progress_bars_v2::events::print_report,
+    anyhow::{Context, Result},
+    futures::TryFutureExt,
+    itertools::Itertools,
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeMap, path::PathBuf},
+    tabled::{settings::Style, Tabled},
+    tap::prelude::*,
+    tracing::{debug, info, info_span, instrument, warn},
+    typed_path::Utf8TypedPath,
+};
+
+/// what happens to the post commands of the manifest. hoolamike runs `ren` (and skips `del`) on its own, the others
+/// (usually windows programs) are skipped unless a rule says otherwise
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PostCommandsConfig {
+    /// program the `wrap` commands are run through, eg. `[wine]` or `[/path/to/proton, run]`
+    #[serde(default)]
+    pub wrapper: Vec<String>,
+    /// extra environment of the commands hoolamike starts, eg. `WINEPREFIX` or `STEAM_COMPAT_DATA_PATH`
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
+    /// the first rule whose `contains` is a part of the command decides what happens to it
+    #[serde(default)]
+    pub rules: Vec<PostCommandRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostCommandRule {
+    pub contains: String,
+    pub action: PostCommandAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PostCommandAction {
+    /// hoolamike runs it on its own, only `cmd.exe /C ren` and `cmd.exe /C del` are understood
+    Builtin,
+    Skip,
+    /// runs it through the wrapper
+    Wrap,
+    /// runs this program with these arguments instead, there is no shell involved. variables (`%DESTINATION%`) are filled in
+    Replace(Vec<String>),
+}
+
+/// `--skip-post-command` and `--skip-post-commands`, these beat the rules of hoolamike.yaml
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct SkipPostCommands {
+    /// skips the post commands containing this chunk of text, they are listed at the end so that they can be run manually
+    #[arg(long)]
+    skip_post_command: Vec<String>,
+    /// skips all the post commands
+    #[arg(long)]
+    skip_post_commands: bool,
+}
+
+impl SkipPostCommands {
+    fn skips(&self, command: &str) -> bool {
+        self.skip_post_commands
+            || self
+                .skip_post_command
+                .iter()
+                .any(|phrase| command.contains(phrase))
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum ParsedPostCommand {
+    Rename(PathBuf, String),
+    Delete(PathBuf),
+}
+
+fn normalize_windows_shell_path(path: String) -> Result<PathBuf> {
+    #[cfg(unix)]
+    let path = path.replace(r#"\"#, "/");
+    let path = snailquote::unescape(&path).context("unescaping")?;
+    match Utf8TypedPath::derive(&path) {
+        Utf8TypedPath::Unix(utf8_path) => utf8_path
+            .with_platform_encoding_checked()
+            .context("converting to platform encoding"),
+        Utf8TypedPath::Windows(utf8_path) => utf8_path
+            .with_platform_encoding_checked()
+            .context("converting to platform encoding"),
+    }
+    .context("normalizing platform encoding")
+    .map(|encoded| encoded.normalize())
+    .map(PathBuf::from)
+}
+
+impl ParsedPostCommand {
+    fn parse(command: &str) -> Result<Self> {
+        futures_executor::block_on(async {
+            use yash_syntax::{input::Memory, source::Source};
+            let input = Box::new(Memory::new(command));
+            use {std::num::NonZeroU64, yash_syntax::parser::lex::Lexer};
+            let line = NonZeroU64::new(1).expect("not 0");
+            let mut lexer = Lexer::new(input, line, Source::Unknown.into());
+            use yash_syntax::{alias::EmptyGlossary, parser::Parser};
+            let mut parser = Parser::new(&mut lexer, &EmptyGlossary);
+
+            let next = async move |parser: &mut Parser, context: &'static str| {
+                parser
+                    .take_token_auto(&[])
+                    .await
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))
+                    .context(context)
+                    .map(|e| e.to_string())
+                    .inspect(|token| debug!(%token))
+            };
+            let assert_eq = async |a: String, b: &str| {
+                if a == b {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!("[{a} != {b}]"))
+                }
+            };
+            next(&mut parser, "cmd.exe")
+                .and_then(|a| assert_eq(a, "cmd.exe"))
+                .await?;
+
+            next(&mut parser, "slash_c")
+                .and_then(|a| assert_eq(a, "/C"))
+                .await?;
+            let command = next(&mut parser, "command").await?;
+            let command = match command.as_str() {
+                "del" => {
+                    let from = next(&mut parser, "from")
+                        .await
+                        .and_then(normalize_windows_shell_path)?;
+                    Ok(ParsedPostCommand::Delete(from))
+                }
+                "ren" => {
+                    let from = next(&mut parser, "from")
+                        .await
+                        .and_then(normalize_windows_shell_path)?;
+                    let to = next(&mut parser, "to")
+                        .await
+                        .and_then(|s| snailquote::unescape(&s).context("unescaping"))?;
+
+                    Ok(ParsedPostCommand::Rename(from, to))
+                }
+                other => Err(anyhow::anyhow!("bad command: [{other}]")),
+            }?;
+            debug!(?command);
+
+            Ok(command)
+        })
+    }
+}
+
+/// splits a command line the way windows programs do: whitespace separates the arguments, double quotes group them and
+/// backslashes stay as they are (they are path separators)
+fn split_windows_command_line(command: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => arguments.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    arguments.extend(current);
+    arguments
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum PlannedPostCommand {
+    Builtin(ParsedPostCommand),
+    /// the wrapper followed by the command
+    Wrapped(Vec<String>),
+    Replaced(Vec<String>),
+    Skipped(String),
+}
+
+impl PlannedPostCommand {
+    pub fn describe(&self) -> String {
+        match self {
+            PlannedPostCommand::Builtin(ParsedPostCommand::Rename(from, to)) => format!("rename [{}] to [{to}]", from.display()),
+            PlannedPostCommand::Builtin(ParsedPostCommand::Delete(path)) => format!("skip it (it would delete [{}])", path.display()),
+            PlannedPostCommand::Wrapped(command) => format!("run [{}]", command.join(" ")),
+            PlannedPostCommand::Replaced(command) => format!("run [{}] instead", command.join(" ")),
+            PlannedPostCommand::Skipped(reason) => format!("skip it, {reason}"),
+        }
+    }
+}
+
+impl PostCommandsConfig {
+    pub fn plan(&self, skip: &SkipPostCommands, command: &str) -> PlannedPostCommand {
+        if skip.skips(command) {
+            return PlannedPostCommand::Skipped("--skip-post-command(s) matches it".to_string());
+        }
+        match self
+            .rules
+            .iter()
+            .find(|rule| command.contains(&rule.contains))
+            .map(|rule| &rule.action)
+        {
+            None | Some(PostCommandAction::Builtin) => match ParsedPostCommand::parse(command) {
+                Ok(parsed) => PlannedPostCommand::Builtin(parsed),
+                Err(reason) => PlannedPostCommand::Skipped(format!("hoolamike cannot run it ({reason}), add a rule for it to hoolamike.yaml")),
+            },
+            Some(PostCommandAction::Skip) => PlannedPostCommand::Skipped("a rule in hoolamike.yaml skips it".to_string()),
+            Some(PostCommandAction::Wrap) => self
+                .wrapper
+                .iter()
+                .cloned()
+                .chain(split_windows_command_line(command))
+                .collect_vec()
+                .pipe(PlannedPostCommand::Wrapped),
+            Some(PostCommandAction::Replace(replacement)) => match replacement.is_empty() {
+                true => PlannedPostCommand::Skipped("its replacement in hoolamike.yaml is empty".to_string()),
+                false => PlannedPostCommand::Replaced(replacement.clone()),
+            },
+        }
+    }
+
+    fn run(&self, command: &[String]) -> Result<()> {
+        let (program, arguments) = command.split_first().context("empty command")?;
+        std::process::Command::new(program)
+            .args(arguments)
+            .envs(&self.environment)
+            .output()
+            .with_context(|| format!("starting [{program}]"))
+            .and_then(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .chain(String::from_utf8_lossy(&output.stderr).lines())
+                    .for_each(|line| info!("{program}: {line}"));
+                output
+                    .status
+                    .success()
+                    .then_some(())
+                    .with_context(|| format!("[{program}] exited with [{}]", output.status))
+            })
+    }
+}
+
+enum Outcome {
+    Ran,
+    Skipped(String),
+    Failed(anyhow::Error),
+}
+
+fn execute(config: &PostCommandsConfig, planned: &PlannedPostCommand) -> Outcome {
+    match planned {
+        PlannedPostCommand::Builtin(ParsedPostCommand::Rename(from, new_file_name)) => {
+            let to = from.with_file_name(new_file_name);
+            std::fs::rename(from, &to).with_context(|| format!("renaming [{from:?}] -> [{to:?}]"))
+        }
+        // std::fs::remove_file(path_buf).with_context(|| format!("removing [{path_buf:?}]"))
+        PlannedPostCommand::Builtin(ParsedPostCommand::Delete(path)) => {
+            return Outcome::Skipped(format!("hoolamike does not delete files, it would delete [{}]", path.display()))
+        }
+        PlannedPostCommand::Wrapped(command) | PlannedPostCommand::Replaced(command) => config.run(command),
+        PlannedPostCommand::Skipped(reason) => return Outcome::Skipped(reason.clone()),
+    }
+    .pipe(|result| match result {
+        Ok(()) => Outcome::Ran,
+        Err(reason) => Outcome::Failed(reason),
+    })
+}
+
+#[derive(Tabled)]
+struct PostCommandRow {
+    command: String,
+    outcome: String,
+}
+
+/// a post command which fails or is skipped does not fail the installation, it is listed at the end to be run manually
+#[instrument(skip_all)]
+pub fn handle_post_commands(config: &PostCommandsConfig, skip: &SkipPostCommands, post_commands: Vec<PostCommand>) -> Result<()> {
+    let outcomes = post_commands
+        .into_iter()
+        .map(|PostCommand { value, .. }| {
+            info_span!("post_command", command=%value).in_scope(|| {
+                let planned = config.plan(skip, &value);
+                info!("about to {}", planned.describe());
+                let outcome = execute(config, &planned).tap(|outcome| match outcome {
+                    Outcome::Ran => info!("executed succesfully"),
+                    Outcome::Skipped(reason) => info!("skipped: {reason}"),
+                    Outcome::Failed(reason) => {
+                        warn!("{reason:?}\n\ncould not execute command, please report this incident and fix it up by trying to run it manually")
+                    }
+                });
+                (value, outcome)
+            })
+        })
+        .collect_vec();
+    if outcomes.is_empty() {
+        return Ok(());
+    }
+    outcomes
+        .iter()
+        .map(|(command, outcome)| PostCommandRow {
+            command: command.clone(),
+            outcome: match outcome {
+                Outcome::Ran => "ran".to_string(),
+                Outcome::Skipped(reason) => format!("SKIPPED: {reason}"),
+                Outcome::Failed(reason) => format!("FAILED: {reason:#}"),
+            },
+        })
+        .pipe(tabled::Table::new)
+        .with(Style::modern())
+        .pipe(print_report);
+    outcomes
+        .iter()
+        .filter(|(_, outcome)| !matches!(outcome, Outcome::Ran))
+        .map(|(command, _)| command)
+        .collect_vec()
+        .pipe(|manual| {
+            if !manual.is_empty() {
+                warn!(
+                    "[{}] post commands did not run, finish the installation by running them manually (skipped deletes only clean up old archives):\n{}",
+                    manual.len(),
+                    manual.iter().join("\n")
+                )
+            }
+        });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_example_1() -> Result<()> {
+        assert_eq!(
+            ParsedPostCommand::Delete(PathBuf::from("%DESTINATION%/Fallout - Meshes.bsa")),
+            ParsedPostCommand::parse("cmd.exe /C del \"%DESTINATION%\\Fallout - Meshes.bsa\"").unwrap()
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_example_2() -> Result<()> {
+        assert_eq!(
+            ParsedPostCommand::Rename(PathBuf::from("%DESTINATION%/New Fallout - Meshes.bsa"), String::from("Fallout - Meshes.bsa")),
+            ParsedPostCommand::parse("cmd.exe /C ren \"%DESTINATION%\\New Fallout - Meshes.bsa\" \"Fallout - Meshes.bsa\"").unwrap()
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_example_3() -> Result<()> {
+        assert_eq!(
+            ParsedPostCommand::Delete(PathBuf::from("%DESTINATION%/Fallout - Textures.bsa")),
+            ParsedPostCommand::parse("cmd.exe /C del \"%DESTINATION%\\Fallout - Textures.bsa\"").unwrap()
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_example_4() -> Result<()> {
+        assert_eq!(
+            ParsedPostCommand::Rename(
+                PathBuf::from("%DESTINATION%/New Fallout - Textures.bsa"),
+                String::from("Fallout - Textures.bsa")
+            ),
+            ParsedPostCommand::parse("cmd.exe /C ren \"%DESTINATION%\\New Fallout - Textures.bsa\" \"Fallout - Textures.bsa\"").unwrap()
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_example_5() -> Result<()> {
+        assert_eq!(
+            ParsedPostCommand::Delete(PathBuf::from("%DESTINATION%/Fallout - Textures2.bsa")),
+            ParsedPostCommand::parse("cmd.exe /C del \"%DESTINATION%\\Fallout - Textures2.bsa\"").unwrap()
+        );
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_example_6() -> Result<()> {
+        assert_eq!(
+            ParsedPostCommand::Delete(PathBuf::from("%DESTINATION%/Fallout - Sound.bsa")),
+            ParsedPostCommand::parse("cmd.exe /C del \"%DESTINATION%\\Fallout - Sound.bsa\"").unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_windows_command_line() {
+        assert_eq!(
+            split_windows_command_line(r#"cmd.exe /C ren "D:\Games\New Fallout - Meshes.bsa"  "Fallout - Meshes.bsa""#),
+            ["cmd.exe", "/C", "ren", r"D:\Games\New Fallout - Meshes.bsa", "Fallout - Meshes.bsa"]
+        );
+        assert_eq!(split_windows_command_line(r#"tool.exe "" -q"#), ["tool.exe", "", "-q"]);
+    }
+
+    #[test_log::test]
+    fn test_plan_follows_skip_flags_then_rules() -> Result<()> {
+        let config = serde_yaml::from_str::<PostCommandsConfig>(
+            r#"
+wrapper: [wine]
+rules:
+  - contains: FNVPatch.exe
+    action: wrap
+  - contains: xdelta
+    action:
+      replace: [xdelta3, -d, "%DESTINATION%/patch.xdelta"]
+  - contains: Meshes
+    action: skip
+"#,
+        )?;
+        let patcher = r#""D:\FNVPatch.exe" -silent"#;
+        let skip = SkipPostCommands::default();
+        assert_eq!(
+            config.plan(&skip, patcher),
+            PlannedPostCommand::Wrapped(vec!["wine".to_string(), r"D:\FNVPatch.exe".to_string(), "-silent".to_string()])
+        );
+        assert!(matches!(config.plan(&skip, "xdelta.exe"), PlannedPostCommand::Replaced(_)));
+        assert!(matches!(
+            config.plan(&skip, "cmd.exe /C del \"%DESTINATION%\\Fallout - Meshes.bsa\""),
+            PlannedPostCommand::Skipped(_)
+        ));
+        assert!(matches!(
+            config.plan(&skip, "cmd.exe /C del \"%DESTINATION%\\Fallout - Sound.bsa\""),
+            PlannedPostCommand::Builtin(ParsedPostCommand::Delete(_))
+        ));
+        assert!(matches!(config.plan(&skip, "setup.exe"), PlannedPostCommand::Skipped(_)));
+
+        let skip = SkipPostCommands {
+            skip_post_command: vec!["FNVPatch".to_string()],
+            skip_post_commands: false,
+        };
+        assert!(matches!(config.plan(&skip, patcher), PlannedPostCommand::Skipped(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+//! `--verify` - the checks of the manifest (files which have to be there with the expected checksums, free space, not being
+//! under Program Files) run against an existing installation
+use {
+    super::{
+        dry_run::location_path,
+        manifest_file::{
+            asset::LocationIndex,
+            check::{Check, FileExistsCheck, FreeSizeCheck, NoProgramFilesCheck},
+        },
+        LocationsLookup,
+    },
+    crate::{helpers::human_readable_size, progress_bars_v2::events::print_report, utils::PathReadWrite},
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    std::{
+        io::{BufReader, Read},
+        path::{Path, PathBuf},
+    },
+    tabled::{settings::Style, Tabled},
+    tap::prelude::*,
+};
+
+/// the manifest gives free space in megabytes
+const FREE_SIZE_UNIT: u64 = 1024 * 1024;
+
+/// (crc32, md5), lowercase hex
+fn checksums(path: &Path) -> Result<(String, String)> {
+    use md5::Digest;
+    path.open_file_read().and_then(|(_, file)| {
+        let mut file = BufReader::new(file);
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut crc32 = crc32fast::Hasher::new();
+        let mut md5 = md5::Md5::new();
+        loop {
+            match file
+                .read(&mut buf)
+                .context("reading chunk into the hashers")?
+            {
+                0 => break,
+                size => {
+                    crc32.update(&buf[..size]);
+                    md5.update(&buf[..size]);
+                }
+            }
+        }
+        Ok((format!("{:08x}", crc32.finalize()), hex::encode(md5.finalize())))
+    })
+}
+
+struct Outcome {
+    passed: bool,
+    detail: String,
+}
+
+fn location(locations: &LocationsLookup, index: LocationIndex, file: &str) -> Result<PathBuf> {
+    locations
+        .get(&index)
+        .with_context(|| format!("no location [{}] in the manifest", index.0))
+        .map(|location| match file.is_empty() {
+            true => location_path(location),
+            false => location_path(location).join(crate::utils::MaybeWindowsPath(file.to_owned()).into_path()),
+        })
+}
+
+fn file_exists(
+    locations: &LocationsLookup,
+    FileExistsCheck {
+        inverted,
+        loc,
+        file,
+        custom_message: _,
+        checksums: expected,
+    }: &FileExistsCheck,
+) -> Result<Outcome> {
+    let path = location(locations, *loc, &file.0 .0)?;
+    let exists = path.exists();
+    match (exists, inverted, expected) {
+        (true, false, Some(expected)) => checksums(&path)
+            .with_context(|| format!("hashing [{}]", path.display()))
+            .map(|(crc32, md5)| {
+                let found = expected
+                    .accepted()
+                    .any(|expected| expected.eq_ignore_ascii_case(&crc32) || expected.eq_ignore_ascii_case(&md5));
+                Outcome {
+                    passed: found,
+                    detail: match found {
+                        true => format!("[{}] has an expected checksum", path.display()),
+                        false => format!(
+                            "[{}] has crc32 {crc32} and md5 {md5}, expected one of: {}",
+                            path.display(),
+                            expected.accepted().join(", ")
+                        ),
+                    },
+                }
+            }),
+        (exists, inverted, _) => Ok(Outcome {
+            passed: exists != *inverted,
+            detail: match exists {
+                true => format!("[{}] exists", path.display()),
+                false => format!("[{}] does not exist", path.display()),
+            },
+        }),
+    }
+}
+
+fn free_size(
+    locations: &LocationsLookup,
+    FreeSizeCheck {
+        inverted,
+        loc,
+        file,
+        custom_message: _,
+        free_size,
+    }: &FreeSizeCheck,
+) -> Result<Outcome> {
+    let path = location(locations, LocationIndex(*loc), file)?;
+    let needed = free_size.saturating_mul(FREE_SIZE_UNIT);
+    path.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .with_context(|| format!("no part of [{}] exists", path.display()))
+        .and_then(|existing| fs2::available_space(existing).with_context(|| format!("checking free space in [{}]", existing.display())))
+        .map(|available| Outcome {
+            passed: (available >= needed) != *inverted,
+            detail: format!(
+                "{} free in [{}], {} needed",
+                human_readable_size(available),
+                path.display(),
+                human_readable_size(needed)
+            ),
+        })
+}
+
+fn no_program_files(
+    locations: &LocationsLookup,
+    NoProgramFilesCheck {
+        inverted,
+        loc,
+        file,
+        custom_message: _,
+    }: &NoProgramFilesCheck,
+) -> Result<Outcome> {
+    location(locations, LocationIndex(*loc), file).map(|path| {
+        let in_program_files = path
+            .to_string_lossy()
+            .to_lowercase()
+            .contains("program files");
+        Outcome {
+            passed: in_program_files == *inverted,
+            detail: match in_program_files {
+                true => format!("[{}] is under Program Files", path.display()),
+                false => format!("[{}] is not under Program Files", path.display()),
+            },
+        }
+    })
+}
+
+#[derive(Tabled)]
+struct CheckRow {
+    check: String,
+    result: String,
+    detail: String,
+    message: String,
+}
+
+fn check_row(locations: &LocationsLookup, check: &Check) -> CheckRow {
+    let (name, custom_message, outcome) = match check {
+        Check::FileExists(check) => (
+            match check.inner.inverted {
+                true => "file is absent",
+                false => "file exists",
+            },
+            &check.inner.custom_message,
+            file_exists(locations, &check.inner),
+        ),
+        Check::FreeSize(check) => ("free space", &check.inner.custom_message, free_size(locations, &check.inner)),
+        Check::NoProgramFiles(check) => ("not in program files", &check.inner.custom_message, no_program_files(locations, &check.inner)),
+    };
+    match outcome {
+        Ok(Outcome { passed, detail }) => CheckRow {
+            check: name.to_string(),
+            result: match passed {
+                true => "ok",
+                false => "FAILED",
+            }
+            .to_string(),
+            detail,
+            message: match passed {
+                true => String::new(),
+                false => custom_message.clone(),
+            },
+        },
+        Err(reason) => CheckRow {
+            check: name.to_string(),
+            result: "ERROR".to_string(),
+            detail: format!("{reason:#}"),
+            message: custom_message.clone(),
+        },
+    }
+}
+
+pub fn verify(locations: &LocationsLookup, checks: &[Check]) -> Result<()> {
+    let rows = checks
+        .iter()
+        .map(|check| check_row(locations, check))
+        .collect_vec();
+    let failed = rows.iter().filter(|row| row.result != "ok").count();
+    rows.pipe(tabled::Table::new)
+        .with(Style::modern())
+        .pipe(print_report);
+    match failed {
+        0 => {
+            tracing::info!("all [{}] checks of the manifest passed", checks.len());
+            Ok(())
+        }
+        failed => anyhow::bail!("{failed} of {} checks of the manifest failed", checks.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::extensions::mpi_installer::manifest_file::location::Location, std::collections::BTreeMap};
+
+    #[test]
+    fn test_file_exists_checks() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        std::fs::write(directory.path().join("Fallout3.esm"), b"hello")?;
+        let locations = BTreeMap::from([(
+            LocationIndex(0),
+            serde_json::from_value::<Location>(serde_json::json!({
+                "Type": 0,
+                "Name": "Fallout 3",
+                "Value": directory.path().display().to_string(),
+                "CreateFolder": false,
+            }))?,
+        )]);
+        let check = |file: &str, inverted: bool, checksums: Option<&str>| -> Result<bool> {
+            serde_json::from_value::<Check>(serde_json::json!({
+                "Type": 0,
+                "Inverted": inverted,
+                "Loc": 0,
+                "File": file,
+                "CustomMessage": "Fallout 3 is not installed",
+                "Checksums": checksums,
+            }))
+            .context("bad check")
+            .map(|check| check_row(&locations, &check).result == "ok")
+        };
+        assert!(check("Fallout3.esm", false, None)?);
+        assert!(check("Fallout3.esm", false, Some("3610A686"))?);
+        assert!(check("Fallout3.esm", false, Some("00000000|5d41402abc4b2a76b9719d911017c592"))?);
+        assert!(!check("Fallout3.esm", false, Some("00000000"))?);
+        assert!(!check("Fallout3.esm", true, None)?);
+        assert!(check("FalloutNV.esm", true, None)?);
+        Ok(())
+    }
+}
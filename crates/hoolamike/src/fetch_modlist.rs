@@ -0,0 +1,515 @@
+//! fetches `.wabbajack` files published in the official modlist repositories (the gallery shown by the wabbajack gui),
+//! so that obtaining the modlist file does not require the gui or a manual download
+use {
+    crate::{
+        config_file::HoolamikeConfig,
+        downloaders::{
+            helpers::FutureAnyhowExt,
+            http_client::{configure_http_client, http_client},
+            wabbajack_cdn::{Part, WabbajackCDNDownloader, WabbajackCdnFile},
+        },
+        helpers::human_readable_size,
+        install_modlist::download_cache::{to_base_64_from_u64, validate_hash},
+        modlist_json::HumanUrl,
+        progress_bars_v2::{events::print_report, io_progress_style},
+    },
+    anyhow::{Context, Result},
+    futures::{StreamExt, TryFutureExt},
+    itertools::Itertools,
+    reqwest::{header::RANGE, Client, StatusCode},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::BTreeMap,
+        hash::Hasher,
+        path::{Path, PathBuf},
+    },
+    tabled::{settings::Style, Tabled},
+    tap::prelude::*,
+    tokio::io::AsyncWriteExt,
+    tracing::{info, instrument, warn},
+    tracing_indicatif::span_ext::IndicatifSpanExt,
+};
+
+pub const REPOSITORIES_URL: &str = "https://raw.githubusercontent.com/wabbajack-tools/mod-lists/master/repositories.json";
+pub const BROWSE: &str = "browse";
+
+#[derive(clap::Args)]
+pub struct FetchModlistCli {
+    /// machine url of the modlist (`repository/name` or just `name`), pass `browse` to list the available modlists
+    pub modlist: String,
+    /// where to save the .wabbajack file (defaults to `installation.wabbajack_file_path` from the config)
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// include nsfw modlists when browsing
+    #[arg(long)]
+    pub nsfw: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModlistLinks {
+    #[serde(default)]
+    pub image: String,
+    #[serde(default)]
+    pub readme: String,
+    pub download: HumanUrl,
+    #[serde(rename = "machineURL")]
+    pub machine_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DownloadMetadata {
+    pub hash: String,
+    pub size: u64,
+    #[serde(default)]
+    pub size_of_archives: u64,
+    #[serde(default)]
+    pub size_of_installed_files: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModlistMetadata {
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub game: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub nsfw: bool,
+    #[serde(default)]
+    pub force_down: bool,
+    pub links: ModlistLinks,
+    pub download_metadata: Option<DownloadMetadata>,
+    /// filled in after fetching, the repositories don't repeat their own name
+    #[serde(skip)]
+    pub repository: String,
+}
+
+impl ModlistMetadata {
+    pub fn machine_url(&self) -> String {
+        format!("{}/{}", self.repository, self.links.machine_url)
+    }
+    fn matches(&self, machine_url: &str) -> bool {
+        match machine_url.split_once('/') {
+            Some((repository, name)) => self.repository.eq_ignore_ascii_case(repository) && self.links.machine_url.eq_ignore_ascii_case(name),
+            None => self.links.machine_url.eq_ignore_ascii_case(machine_url),
+        }
+    }
+}
+
+#[derive(Tabled)]
+pub(crate) struct ModlistRow {
+    machine_url: String,
+    title: String,
+    game: String,
+    version: String,
+    download_size: String,
+    install_size: String,
+}
+
+impl From<&ModlistMetadata> for ModlistRow {
+    fn from(modlist: &ModlistMetadata) -> Self {
+        let sizes = modlist.download_metadata.as_ref();
+        Self {
+            machine_url: modlist.machine_url(),
+            title: modlist.title.clone(),
+            game: modlist.game.clone(),
+            version: modlist.version.clone().unwrap_or_default(),
+            download_size: sizes
+                .map(|sizes| human_readable_size(sizes.size_of_archives))
+                .unwrap_or_default(),
+            install_size: sizes
+                .map(|sizes| human_readable_size(sizes.size_of_installed_files))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[instrument(skip(client))]
+async fn fetch_json<T: serde::de::DeserializeOwned>(client: &Client, url: &str) -> Result<T> {
+    client
+        .get(url)
+        .send()
+        .map_with_context(|| format!("fetching [{url}]"))
+        .and_then(|response| async move { response.error_for_status().context("bad status") })
+        .and_then(|response| response.text().map_context("reading response"))
+        .await
+        .and_then(|text| crate::utils::deserialize_json_with_error_location(&text))
+        .with_context(|| format!("fetching json from [{url}]"))
+}
+
+#[instrument(skip(client))]
+pub async fn fetch_gallery(client: &Client) -> Result<Vec<ModlistMetadata>> {
+    let repositories = fetch_json::<BTreeMap<String, String>>(client, REPOSITORIES_URL)
+        .await
+        .context("fetching repositories")?;
+    futures::stream::iter(repositories)
+        .map(|(repository, url)| async move {
+            fetch_json::<Vec<ModlistMetadata>>(client, &url)
+                .await
+                .map(|modlists| {
+                    modlists
+                        .into_iter()
+                        .map(|modlist| ModlistMetadata {
+                            repository: repository.clone(),
+                            ..modlist
+                        })
+                        .collect_vec()
+                })
+                .tap_err(|message| warn!(%repository, ?message, "skipping repository"))
+                .unwrap_or_default()
+        })
+        .buffer_unordered(8)
+        .concat()
+        .await
+        .tap_mut(|modlists| modlists.sort_by_key(|modlist| modlist.machine_url()))
+        .pipe(Ok)
+}
+
+fn xxhash_base64(bytes: &[u8]) -> String {
+    xxhash_rust::xxh64::Xxh64::new(0)
+        .tap_mut(|hasher| hasher.update(bytes))
+        .finish()
+        .pipe(to_base_64_from_u64)
+}
+
+fn verify_part(Part { hash, index, offset, size }: &Part, bytes: &[u8]) -> Result<()> {
+    if bytes.len() != *size {
+        anyhow::bail!(
+            "part [{index}] (at offset [{offset}]) has unexpected size (expected [{size}], found [{}])",
+            bytes.len()
+        );
+    }
+    let found = xxhash_base64(bytes);
+    if &found != hash {
+        anyhow::bail!("part [{index}] has invalid hash (expected [{hash}], found [{found}])");
+    }
+    Ok(())
+}
+
+async fn open_partial(path: &Path) -> Result<(tokio::fs::File, u64)> {
+    tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_with_context(|| format!("opening [{path:?}]"))
+        .and_then(|file| async move {
+            file.metadata()
+                .await
+                .context("reading metadata")
+                .map(|metadata| (file, metadata.len()))
+        })
+        .await
+}
+
+/// downloads the parts which are not yet fully present in the partial file, verifying each of them
+#[instrument(skip(client, definition))]
+async fn download_cdn_parts(client: &Client, base: &HumanUrl, definition: WabbajackCdnFile, partial: &Path) -> Result<()> {
+    let (mut file, existing) = open_partial(partial).await?;
+    let parts = definition
+        .parts
+        .iter()
+        .sorted_by_key(|part| part.index)
+        .collect_vec();
+    // only complete parts are kept, whatever comes after the last one is redownloaded
+    let resume_from = parts
+        .iter()
+        .map(|Part { offset, size, .. }| (offset + size) as u64)
+        .take_while(|end| *end <= existing)
+        .last()
+        .unwrap_or(0);
+    file.set_len(resume_from)
+        .await
+        .context("truncating partial file")?;
+    tracing::Span::current().pipe(|pb| {
+        pb.pb_set_style(&io_progress_style());
+        pb.pb_set_length(definition.size);
+        pb.pb_inc(resume_from);
+    });
+    if resume_from > 0 {
+        info!(%resume_from, "resuming download");
+    }
+    for part in parts
+        .into_iter()
+        .filter(|part| (part.offset as u64) >= resume_from)
+    {
+        let index = part.index;
+        let url = WabbajackCDNDownloader::part_url(base, &definition.munged_name, index);
+        let bytes = client
+            .get(url.to_string())
+            .send()
+            .map_with_context(|| format!("fetching part [{index}] from [{url}]"))
+            .and_then(|response| async move { response.error_for_status().context("bad status") })
+            .and_then(|response| response.bytes().map_context("reading part"))
+            .await?;
+        verify_part(part, &bytes)?;
+        file.write_all(&bytes)
+            .await
+            .with_context(|| format!("writing part [{index}]"))?;
+        tracing::Span::current().pb_inc(bytes.len() as u64);
+    }
+    file.flush().await.context("flushing partial file")
+}
+
+/// plain http download, resumed with a range request when the server supports it
+#[instrument(skip(client))]
+async fn download_http(client: &Client, url: &HumanUrl, expected_size: u64, partial: &Path) -> Result<()> {
+    let (mut file, existing) = open_partial(partial).await?;
+    let response = client
+        .get(url.to_string())
+        .pipe(|request| match existing {
+            0 => request,
+            existing => request.header(RANGE, format!("bytes={existing}-")),
+        })
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("fetching [{url}]"))?;
+    let resumed = match response.status() {
+        StatusCode::PARTIAL_CONTENT => existing,
+        _ => {
+            file.set_len(0).await.context("truncating partial file")?;
+            0
+        }
+    };
+    tracing::Span::current().pipe(|pb| {
+        pb.pb_set_style(&io_progress_style());
+        pb.pb_set_length(expected_size);
+        pb.pb_inc(resumed);
+    });
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.context("reading chunk")?;
+        file.write_all(&chunk).await.context("writing chunk")?;
+        tracing::Span::current().pb_inc(chunk.len() as u64);
+    }
+    file.flush().await.context("flushing partial file")
+}
+
+#[instrument(skip(client, modlist), fields(machine_url=%modlist.machine_url()))]
+pub async fn download_modlist(client: &Client, modlist: &ModlistMetadata, output: &Path) -> Result<PathBuf> {
+    let DownloadMetadata { hash, size, .. } = modlist
+        .download_metadata
+        .clone()
+        .context("modlist does not publish download metadata, it cannot be verified")?;
+    if tokio::fs::try_exists(output).await.unwrap_or(false) {
+        match validate_hash(output.to_owned(), hash.clone()).await {
+            Ok(output) => {
+                info!(?output, "modlist file is already up to date");
+                return Ok(output);
+            }
+            Err(message) => info!(?message, "existing modlist file is outdated, downloading"),
+        }
+    }
+    if let Some(parent) = output
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("creating [{parent:?}]"))?;
+    }
+    let partial = output.to_owned().tap_mut(|partial| {
+        partial.add_extension("part");
+    });
+    let download = &modlist.links.download;
+    match WabbajackCDNDownloader::fetch_definition(download.clone()).await {
+        Ok((base, definition)) => download_cdn_parts(client, &base, definition, &partial).await,
+        Err(message) => {
+            info!(?message, "[{download}] is not served by the wabbajack cdn, downloading directly");
+            download_http(client, download, size, &partial).await
+        }
+    }
+    .context("downloading")?;
+    match validate_hash(partial.clone(), hash).await {
+        Ok(_) => tokio::fs::rename(&partial, output)
+            .await
+            .context("moving downloaded file into place")
+            .map(|_| output.to_owned()),
+        Err(message) => {
+            // a corrupt partial file would otherwise be resumed forever
+            tokio::fs::remove_file(&partial).await.ok();
+            Err(message).context("downloaded modlist file is corrupt, try again")
+        }
+    }
+}
+
+pub(crate) fn find_modlist<'a>(gallery: &'a [ModlistMetadata], machine_url: &str) -> Result<&'a ModlistMetadata> {
+    let found = gallery
+        .iter()
+        .filter(|modlist| modlist.matches(machine_url))
+        .collect_vec();
+    match found.as_slice() {
+        [] => anyhow::bail!("no modlist matches [{machine_url}], run with [{BROWSE}] to list the available modlists"),
+        [modlist] => Ok(modlist),
+        many => anyhow::bail!(
+            "[{machine_url}] is ambiguous, use one of:\n{}",
+            many.iter().map(|modlist| modlist.machine_url()).join("\n")
+        ),
+    }
+}
+
+/// `--output`, then `installation.wabbajack_file_path` from the config, then `<machine url>.wabbajack`
+pub(crate) fn output_path(output: Option<PathBuf>, config: Option<HoolamikeConfig>, modlist: &ModlistMetadata) -> PathBuf {
+    output
+        .or_else(|| config.map(|config| config.installation.wabbajack_file_path))
+        .unwrap_or_else(|| PathBuf::from(format!("{}.wabbajack", modlist.links.machine_url)))
+}
+
+impl FetchModlistCli {
+    pub async fn run(self, config: Option<HoolamikeConfig>) -> Result<()> {
+        let Self { modlist, output, nsfw } = self;
+        if let Some(config) = &config {
+            configure_http_client(&config.downloaders)?;
+        }
+        let client = http_client()?;
+        let gallery = fetch_gallery(&client)
+            .await
+            .context("fetching modlist gallery")?;
+        match modlist.as_str() {
+            BROWSE => gallery
+                .iter()
+                .filter(|modlist| nsfw || !modlist.nsfw)
+                .filter(|modlist| !modlist.force_down)
+                .map(ModlistRow::from)
+                .pipe(tabled::Table::new)
+                .with(Style::modern())
+                .pipe(print_report)
+                .pipe(Ok),
+            machine_url => {
+                let modlist = find_modlist(&gallery, machine_url)?;
+                if modlist.force_down {
+                    warn!("[{}] is marked as down by its maintainers", modlist.machine_url());
+                }
+                let output = output_path(output, config, modlist);
+                download_modlist(&client, modlist, &output)
+                    .await
+                    .map(|output| print_report(format_args!("saved [{}] to [{}]", modlist.title, output.display())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GALLERY: &str = r#"[
+        {
+            "title": "Example Modlist",
+            "description": "fields hoolamike does not use are ignored",
+            "author": "someone",
+            "game": "skyrimspecialedition",
+            "official": false,
+            "tags": ["Graphics"],
+            "nsfw": false,
+            "utility_list": false,
+            "image_contains_title": false,
+            "force_down": false,
+            "links": {
+                "image": "https://example.com/image.webp",
+                "readme": "https://example.com/readme",
+                "download": "https://authored-files.wabbajack.org/Example.wabbajack_00000000-0000-0000-0000-000000000000",
+                "machineURL": "example"
+            },
+            "download_metadata": {
+                "Hash": "AAAAAAAAAAA=",
+                "Size": 1000,
+                "NumberOfArchives": 2,
+                "SizeOfArchives": 2000,
+                "NumberOfInstalledFiles": 3,
+                "SizeOfInstalledFiles": 3000
+            },
+            "version": "1.0.0"
+        },
+        {
+            "title": "Minimal",
+            "links": {
+                "download": "https://example.com/Minimal.wabbajack",
+                "machineURL": "minimal"
+            }
+        }
+    ]"#;
+
+    fn gallery() -> Result<Vec<ModlistMetadata>> {
+        crate::utils::deserialize_json_with_error_location::<Vec<ModlistMetadata>>(GALLERY).map(|modlists| {
+            modlists
+                .into_iter()
+                .map(|modlist| ModlistMetadata {
+                    repository: "wj-featured".into(),
+                    ..modlist
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_gallery_parses() -> Result<()> {
+        let gallery = gallery()?;
+        assert_eq!(gallery.len(), 2);
+        assert_eq!(gallery[0].machine_url(), "wj-featured/example");
+        let sizes = gallery[0]
+            .download_metadata
+            .as_ref()
+            .context("download metadata")?;
+        assert_eq!((sizes.size, sizes.size_of_archives, sizes.size_of_installed_files), (1000, 2000, 3000));
+        assert!(gallery[1].download_metadata.is_none());
+        assert_eq!(gallery[1].version, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_modlist() -> Result<()> {
+        let gallery = gallery()?;
+        assert_eq!(find_modlist(&gallery, "example")?.title, "Example Modlist");
+        assert_eq!(find_modlist(&gallery, "WJ-Featured/Minimal")?.title, "Minimal");
+        assert!(find_modlist(&gallery, "other-repository/minimal").is_err());
+        assert!(find_modlist(&gallery, "missing").is_err());
+
+        let duplicated = gallery
+            .iter()
+            .cloned()
+            .chain(gallery.iter().cloned().map(|modlist| ModlistMetadata {
+                repository: "another-repository".into(),
+                ..modlist
+            }))
+            .collect_vec();
+        assert!(find_modlist(&duplicated, "example").is_err());
+        assert_eq!(find_modlist(&duplicated, "another-repository/example")?.repository, "another-repository");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parts_are_verified() {
+        let bytes = b"part contents";
+        let part = Part {
+            hash: xxhash_base64(bytes),
+            index: 0,
+            offset: 0,
+            size: bytes.len(),
+        };
+        assert!(verify_part(&part, bytes).is_ok());
+        assert!(verify_part(&part, b"part content").is_err());
+        assert!(verify_part(&part, b"PART CONTENTS").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_up_to_date_file_is_kept() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let output = directory.path().join("example.wabbajack");
+        let contents = b"modlist file";
+        std::fs::write(&output, contents)?;
+        let mut modlist = gallery()?.remove(0);
+        modlist.download_metadata = Some(DownloadMetadata {
+            hash: xxhash_base64(contents),
+            size: contents.len() as u64,
+            size_of_archives: 0,
+            size_of_installed_files: 0,
+        });
+        // the file is valid, so nothing is fetched
+        assert_eq!(download_modlist(&Client::new(), &modlist, &output).await?, output);
+        Ok(())
+    }
+}
@@ -0,0 +1,560 @@
+//! minimal native front-end for people who'd rather not edit yaml and read terminal output,
+//! everything it does goes through the same functions the cli uses
+use {
+    crate::{
+        config_file::{GameConfig, HoolamikeConfig},
+        downloaders::http_client::{configure_http_client, http_client},
+        fetch_modlist::{download_modlist, fetch_gallery, ModlistMetadata},
+        helpers::human_readable_size,
+        install_modlist::install_modlist,
+        modlist_json::GameName,
+        post_install_fixup::load_order::LoadOrderContext,
+        DebugHelpers,
+    },
+    anyhow::{Context as _, Result},
+    eframe::egui,
+    itertools::Itertools,
+    parking_lot::Mutex,
+    std::{
+        collections::{BTreeMap, VecDeque},
+        future::Future,
+        path::PathBuf,
+        str::FromStr,
+        sync::Arc,
+        time::{Duration, Instant},
+    },
+    tap::prelude::*,
+    tokio::sync::oneshot,
+    tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id},
+        Event,
+        Level,
+        Subscriber,
+    },
+    tracing_subscriber::{layer::Context, prelude::*, EnvFilter, Layer},
+};
+
+const MAX_LOG_LINES: usize = 500;
+/// a broken install can warn about every file, only the latest ones are kept
+const MAX_PROBLEMS: usize = 500;
+const MAX_VISIBLE_OPERATIONS: usize = 50;
+
+#[derive(Default)]
+struct FieldsVisitor {
+    message: Option<String>,
+    fields: Vec<String>,
+}
+
+impl Visit for FieldsVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.message = Some(format!("{value:?}")),
+            name => self.fields.push(format!("{name}={value:?}")),
+        }
+    }
+}
+
+struct ActiveOperation {
+    name: &'static str,
+    fields: String,
+    started: Instant,
+}
+
+struct LogLine {
+    level: Level,
+    message: String,
+}
+
+/// what the progress view shows, mirrors the span tree the cli renders with indicatif
+#[derive(Default)]
+struct Activity {
+    operations: BTreeMap<u64, ActiveOperation>,
+    log: VecDeque<LogLine>,
+    problems: VecDeque<String>,
+    /// including the ones no longer kept
+    problem_count: usize,
+}
+
+/// ring buffer, the oldest entries make room for new ones
+fn push_capped<T>(entries: &mut VecDeque<T>, entry: T, max: usize) {
+    entries.push_back(entry);
+    while entries.len() > max {
+        entries.pop_front();
+    }
+}
+
+struct ActivityLayer(Arc<Mutex<Activity>>);
+
+impl<S: Subscriber> Layer<S> for ActivityLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+        let mut visitor = FieldsVisitor::default();
+        attrs.record(&mut visitor);
+        self.0.lock().operations.insert(
+            id.into_u64(),
+            ActiveOperation {
+                name: attrs.metadata().name(),
+                fields: visitor.fields.join(", "),
+                started: Instant::now(),
+            },
+        );
+    }
+
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        self.0.lock().operations.remove(&id.into_u64());
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = FieldsVisitor::default();
+        event.record(&mut visitor);
+        let level = *event.metadata().level();
+        let message = visitor.message.into_iter().chain(visitor.fields).join(" ");
+        let mut activity = self.0.lock();
+        if level <= Level::WARN {
+            activity.problem_count += 1;
+            push_capped(&mut activity.problems, message.clone(), MAX_PROBLEMS);
+        }
+        push_capped(&mut activity.log, LogLine { level, message }, MAX_LOG_LINES);
+    }
+}
+
+fn setup_logging() -> Result<Arc<Mutex<Activity>>> {
+    let activity = Arc::new(Mutex::new(Activity::default()));
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::from_str("info").unwrap()))
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(ActivityLayer(activity.clone()))
+        .pipe(tracing::subscriber::set_global_default)
+        .context("Unable to set a global subscriber")
+        .map(|_| activity)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Config,
+    Modlists,
+    Install,
+}
+
+enum InstallStatus {
+    Idle,
+    Running(Instant),
+    Finished(String),
+    Failed(Vec<String>),
+}
+
+struct HoolamikeApp {
+    ctx: egui::Context,
+    runtime: tokio::runtime::Handle,
+    activity: Arc<Mutex<Activity>>,
+    tab: Tab,
+    config_path: PathBuf,
+    config: HoolamikeConfig,
+    /// last outcome of loading/saving the config or fetching a modlist
+    status: Option<Result<String, String>>,
+    new_game_name: String,
+    gallery: Vec<ModlistMetadata>,
+    gallery_filter: String,
+    show_nsfw: bool,
+    pending_gallery: Option<oneshot::Receiver<Result<Vec<ModlistMetadata>>>>,
+    pending_download: Option<oneshot::Receiver<Result<PathBuf>>>,
+    pending_install: Option<oneshot::Receiver<Result<usize, Vec<String>>>>,
+    install_status: InstallStatus,
+}
+
+/// takes the value out once the task is done
+fn poll<T>(pending: &mut Option<oneshot::Receiver<T>>) -> Option<T> {
+    match pending.as_mut().map(|receiver| receiver.try_recv()) {
+        Some(Ok(value)) => pending.take().pipe(|_| Some(value)),
+        Some(Err(oneshot::error::TryRecvError::Closed)) => pending.take().pipe(|_| None),
+        Some(Err(oneshot::error::TryRecvError::Empty)) | None => None,
+    }
+}
+
+fn path_edit(ui: &mut egui::Ui, path: &mut PathBuf) -> egui::Response {
+    let mut text = path.display().to_string();
+    ui.add(egui::TextEdit::singleline(&mut text).desired_width(f32::INFINITY))
+        .tap(|response| {
+            if response.changed() {
+                *path = PathBuf::from(&text);
+            }
+        })
+}
+
+impl HoolamikeApp {
+    fn new(ctx: egui::Context, runtime: tokio::runtime::Handle, activity: Arc<Mutex<Activity>>, config_path: PathBuf) -> Self {
+        let (config, status) = match config_path.exists() {
+            true => match HoolamikeConfig::find(&config_path) {
+                Ok((_, config)) => (config, Some(Ok(format!("loaded [{}]", config_path.display())))),
+                Err(e) => (HoolamikeConfig::default(), Some(Err(format!("{e:?}")))),
+            },
+            false => (
+                HoolamikeConfig::default(),
+                Some(Ok("no config found, starting with the default one".to_string())),
+            ),
+        };
+        Self {
+            ctx,
+            runtime,
+            activity,
+            tab: Tab::Config,
+            config_path,
+            config,
+            status,
+            new_game_name: String::new(),
+            gallery: vec![],
+            gallery_filter: String::new(),
+            show_nsfw: false,
+            pending_gallery: None,
+            pending_download: None,
+            pending_install: None,
+            install_status: InstallStatus::Idle,
+        }
+    }
+
+    /// the library futures are not guaranteed to be `Send`, so every task gets its own thread driving it on the shared runtime
+    /// the proxy and the cookies of the config being edited apply right away
+    fn http_client(&self) -> Result<reqwest::Client> {
+        configure_http_client(&self.config.downloaders).and_then(|_| http_client())
+    }
+
+    fn spawn<T, F>(&self, task: impl FnOnce() -> F + Send + 'static) -> oneshot::Receiver<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = T>,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let runtime = self.runtime.clone();
+        let ctx = self.ctx.clone();
+        std::thread::spawn(move || {
+            sender.send(runtime.block_on(task())).ok();
+            ctx.request_repaint();
+        });
+        receiver
+    }
+
+    fn poll_tasks(&mut self) {
+        if let Some(gallery) = poll(&mut self.pending_gallery) {
+            match gallery {
+                Ok(gallery) => {
+                    self.status = Some(Ok(format!("found [{}] modlists", gallery.len())));
+                    self.gallery = gallery;
+                }
+                Err(e) => self.status = Some(Err(format!("{e:?}"))),
+            }
+        }
+        if let Some(download) = poll(&mut self.pending_download) {
+            self.status = Some(match download {
+                Ok(path) => {
+                    self.config.installation.wabbajack_file_path = path.clone();
+                    Ok(format!("saved modlist to [{}], remember to save the config", path.display()))
+                }
+                Err(e) => Err(format!("{e:?}")),
+            });
+        }
+        if let Some(install) = poll(&mut self.pending_install) {
+            self.install_status = match install {
+                Ok(count) => InstallStatus::Finished(format!("successfully installed [{count}] mods")),
+                Err(errors) => InstallStatus::Failed(errors),
+            };
+        }
+    }
+
+    fn status_line(&self, ui: &mut egui::Ui) {
+        match &self.status {
+            Some(Ok(message)) => ui.label(message),
+            Some(Err(message)) => ui.colored_label(ui.visuals().error_fg_color, message),
+            None => ui.label(""),
+        };
+    }
+
+    fn config_tab(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("config file");
+            path_edit(ui, &mut self.config_path);
+        });
+        ui.horizontal(|ui| {
+            if ui.button("load").clicked() {
+                self.status = Some(
+                    HoolamikeConfig::find(&self.config_path)
+                        .map(|(path, config)| {
+                            self.config = config;
+                            format!("loaded [{}]", path.display())
+                        })
+                        .map_err(|e| format!("{e:?}")),
+                );
+            }
+            if ui.button("save").clicked() {
+                self.status = Some(
+                    self.config
+                        .save(&self.config_path)
+                        .map(|_| format!("saved [{}]", self.config_path.display()))
+                        .map_err(|e| format!("{e:?}")),
+                );
+            }
+            if ui.button("reset to defaults").clicked() {
+                self.config = HoolamikeConfig::default();
+            }
+        });
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("config")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    let config = &mut self.config;
+                    ui.label("modlist file (.wabbajack)");
+                    path_edit(ui, &mut config.installation.wabbajack_file_path);
+                    ui.end_row();
+                    ui.label("installation directory");
+                    path_edit(ui, &mut config.installation.installation_path);
+                    ui.end_row();
+                    ui.label("downloads directory");
+                    path_edit(ui, &mut config.downloaders.downloads_directory);
+                    ui.end_row();
+                    ui.label("nexus api key");
+                    let mut api_key = config.downloaders.nexus.api_key.clone().unwrap_or_default();
+                    if ui
+                        .add(egui::TextEdit::singleline(&mut api_key).password(true))
+                        .changed()
+                    {
+                        config.downloaders.nexus.api_key = Some(api_key).filter(|key| !key.is_empty());
+                    }
+                    ui.end_row();
+                    ui.label("game resolution");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut config.fixup.game_resolution.x));
+                        ui.label("x");
+                        ui.add(egui::DragValue::new(&mut config.fixup.game_resolution.y));
+                    });
+                    ui.end_row();
+                });
+            ui.separator();
+            ui.heading("games");
+            let mut removed = None;
+            egui::Grid::new("games")
+                .num_columns(3)
+                .striped(true)
+                .show(ui, |ui| {
+                    self.config
+                        .games
+                        .iter_mut()
+                        .for_each(|(name, GameConfig { root_directory })| {
+                            ui.label(name.to_string());
+                            path_edit(ui, root_directory);
+                            if ui.button("remove").clicked() {
+                                removed = Some(name.clone());
+                            }
+                            ui.end_row();
+                        });
+                });
+            if let Some(removed) = removed {
+                self.config.games.shift_remove(&removed);
+            }
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.new_game_name).hint_text("game name, eg. FalloutNewVegas"));
+                if ui.button("add game").clicked() && !self.new_game_name.is_empty() {
+                    self.config.games.insert(
+                        GameName::new(std::mem::take(&mut self.new_game_name)),
+                        GameConfig {
+                            root_directory: PathBuf::new(),
+                        },
+                    );
+                }
+            });
+        });
+    }
+
+    fn modlists_tab(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let fetching = self.pending_gallery.is_some();
+            if ui
+                .add_enabled(!fetching, egui::Button::new("fetch modlist gallery"))
+                .clicked()
+            {
+                let client = self.http_client();
+                self.pending_gallery = Some(self.spawn(|| async move { fetch_gallery(&client?).await }));
+            }
+            if fetching || self.pending_download.is_some() {
+                ui.spinner();
+            }
+            ui.add(egui::TextEdit::singleline(&mut self.gallery_filter).hint_text("search"));
+            ui.checkbox(&mut self.show_nsfw, "nsfw");
+        });
+        ui.separator();
+        let filter = self.gallery_filter.to_lowercase();
+        let mut picked = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("gallery")
+                .num_columns(5)
+                .striped(true)
+                .show(ui, |ui| {
+                    self.gallery
+                        .iter()
+                        .filter(|modlist| self.show_nsfw || !modlist.nsfw)
+                        .filter(|modlist| !modlist.force_down)
+                        .filter(|modlist| {
+                            [&modlist.title, &modlist.author, &modlist.game]
+                                .iter()
+                                .any(|field| field.to_lowercase().contains(&filter))
+                        })
+                        .for_each(|modlist| {
+                            ui.label(&modlist.title);
+                            ui.label(&modlist.author);
+                            ui.label(&modlist.game);
+                            ui.label(
+                                modlist
+                                    .download_metadata
+                                    .as_ref()
+                                    .map(|metadata| human_readable_size(metadata.size_of_installed_files))
+                                    .unwrap_or_default(),
+                            );
+                            if ui
+                                .add_enabled(self.pending_download.is_none(), egui::Button::new("download"))
+                                .clicked()
+                            {
+                                picked = Some(modlist.clone());
+                            }
+                            ui.end_row();
+                        });
+                });
+        });
+        if let Some(modlist) = picked {
+            let output = self
+                .config
+                .downloaders
+                .downloads_directory
+                .join(format!("{}.wabbajack", modlist.links.machine_url));
+            let client = self.http_client();
+            self.pending_download = Some(self.spawn(move || async move { download_modlist(&client?, &modlist, &output).await }));
+        }
+    }
+
+    fn install_tab(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let running = self.pending_install.is_some();
+            if ui
+                .add_enabled(!running, egui::Button::new("install"))
+                .clicked()
+            {
+                let config = self.config.clone();
+                {
+                    let mut activity = self.activity.lock();
+                    activity.problems.clear();
+                    activity.problem_count = 0;
+                }
+                self.install_status = InstallStatus::Running(Instant::now());
+                self.pending_install = Some(self.spawn(move || async move {
+                    install_modlist(config.clone(), DebugHelpers::default())
+                        .await
+                        .map_err(|errors| errors.iter().map(|e| format!("{e:?}")).collect_vec())
+                        .and_then(|installed| {
+                            LoadOrderContext::from_config(&config)
+                                .fix_load_order()
+                                .context("verifying load order")
+                                .map(|_| installed.len())
+                                .map_err(|e| vec![format!("{e:?}")])
+                        })
+                }));
+            }
+            match &self.install_status {
+                InstallStatus::Idle => ui.label("installation uses the config from the config tab"),
+                InstallStatus::Running(started) => ui
+                    .spinner()
+                    .union(ui.label(format!("installing for {}s", started.elapsed().as_secs()))),
+                InstallStatus::Finished(message) => ui.label(message),
+                InstallStatus::Failed(errors) => ui.colored_label(
+                    ui.visuals().error_fg_color,
+                    format!("could not finish installation due to [{}] errors", errors.len()),
+                ),
+            };
+        });
+        ui.separator();
+        let activity = self.activity.lock();
+        ui.heading(format!("in progress ({})", activity.operations.len()));
+        egui::ScrollArea::vertical()
+            .id_salt("operations")
+            .max_height(ui.available_height() / 3.)
+            .show(ui, |ui| {
+                activity
+                    .operations
+                    .values()
+                    .sorted_by_key(|operation| operation.started)
+                    .take(MAX_VISIBLE_OPERATIONS)
+                    .for_each(|operation| {
+                        ui.label(format!("({}s) {}({})", operation.started.elapsed().as_secs(), operation.name, operation.fields));
+                    });
+            });
+        ui.separator();
+        let errors = match &self.install_status {
+            InstallStatus::Failed(errors) => errors.as_slice(),
+            _ => &[],
+        };
+        ui.heading(match activity.problem_count > activity.problems.len() {
+            true => format!(
+                "problems ({}, only the last {} are shown)",
+                errors.len() + activity.problem_count,
+                activity.problems.len()
+            ),
+            false => format!("problems ({})", errors.len() + activity.problem_count),
+        });
+        egui::ScrollArea::vertical()
+            .id_salt("problems")
+            .max_height(ui.available_height() / 2.)
+            .show(ui, |ui| {
+                errors
+                    .iter()
+                    .chain(activity.problems.iter())
+                    .for_each(|problem| {
+                        ui.colored_label(ui.visuals().warn_fg_color, problem);
+                    });
+            });
+        ui.separator();
+        ui.heading("log");
+        egui::ScrollArea::vertical()
+            .id_salt("log")
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                activity.log.iter().for_each(|LogLine { level, message }| {
+                    ui.monospace(format!("{level:>5} {message}"));
+                });
+            });
+    }
+}
+
+impl eframe::App for HoolamikeApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_tasks();
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.tab, Tab::Config, "config");
+                ui.selectable_value(&mut self.tab, Tab::Modlists, "modlists");
+                ui.selectable_value(&mut self.tab, Tab::Install, "install");
+            });
+        });
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| self.status_line(ui));
+        egui::CentralPanel::default().show(ctx, |ui| match self.tab {
+            Tab::Config => self.config_tab(ui),
+            Tab::Modlists => self.modlists_tab(ui),
+            Tab::Install => self.install_tab(ui),
+        });
+        if self.pending_install.is_some() {
+            // elapsed times and the log keep changing while installing
+            ctx.request_repaint_after(Duration::from_millis(250));
+        }
+    }
+}
+
+pub async fn run(config_path: PathBuf) -> Result<()> {
+    let activity = setup_logging().context("setting up logging")?;
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::block_in_place(move || {
+        eframe::run_native(
+            "hoolamike",
+            eframe::NativeOptions::default(),
+            Box::new(move |cc| Ok(Box::new(HoolamikeApp::new(cc.egui_ctx.clone(), runtime, activity, config_path)))),
+        )
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .context("running the gui")
+    })
+}
@@ -11,3 +11,26 @@ pub fn human_readable_size(bytes: u64) -> String {
 
     format!("{:.2} {}", value, UNITS[exponent])
 }
+
+/// the inverse of [`human_readable_size`], `200GB`, `1.5 TiB` and `4096` (bytes) are all fine
+pub fn parse_size(size: &str) -> anyhow::Result<u64> {
+    let size = size.trim();
+    let (value, unit) = size.split_at(
+        size.find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(size.len()),
+    );
+    let unit = unit.trim().to_ascii_uppercase();
+    let exponent = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        _ => anyhow::bail!("unknown unit [{unit}] in [{size}]"),
+    };
+    value
+        .parse::<f64>()
+        .map_err(|reason| anyhow::anyhow!("bad size [{size}]: {reason}"))
+        .map(|value| (value * 1024f64.powi(exponent)) as u64)
+}
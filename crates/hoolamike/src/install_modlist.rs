@@ -1,49 +1,122 @@
 use {
     crate::{
-        config_file::{HoolamikeConfig, InstallationConfig},
+        config_file::{ArchivesConfig, BsaCompressionConfig, DownloadersConfig, HoolamikeConfig, InstallationConfig},
         downloaders::WithArchiveDescriptor,
         error::TotalResult,
-        modlist_json::{Archive, Modlist},
-        progress_bars_v2::io_progress_style,
+        modlist_json::{Archive, Directive, DirectiveKind, Modlist},
+        progress_bars_v2::{events::ProgressTracker, io_progress_style},
         utils::spawn_rayon,
         wabbajack_file::WabbajackFile,
         DebugHelpers,
     },
     anyhow::Context,
-    directives::{DirectivesHandler, DirectivesHandlerConfig},
+    directives::{scheduling::Scheduling, DirectivesHandler, DirectivesHandlerConfig},
     downloads::Synchronizers,
     futures::{FutureExt, TryFutureExt, TryStreamExt},
+    install_report::InstallReport,
     itertools::Itertools,
-    std::{future::ready, sync::Arc},
+    output_filter::OutputFilter,
+    std::{future::ready, path::PathBuf, sync::Arc},
     tap::prelude::*,
     tracing::instrument,
     tracing_indicatif::span_ext::IndicatifSpanExt,
 };
 
+pub mod deduplicate;
+pub mod directive_graph;
 pub mod directives;
 pub mod download_cache;
 pub mod downloads;
+pub mod dry_run;
+pub mod install_report;
+pub mod output_filter;
+pub mod output_space;
+pub mod upgrade;
+
+/// `--start-from-directive`, `--skip-kind`, `--contains`, `--only` and `--exclude` narrow down the directives which get handled
+fn select_directives(
+    directives: Vec<Directive>,
+    start_from_directive: Option<&String>,
+    skip_kind: &[DirectiveKind],
+    contains: &[String],
+    output_filter: &OutputFilter,
+) -> Vec<Directive> {
+    directives
+        .into_iter()
+        .skip_while(|d| {
+            start_from_directive
+                .map(|start_from_directive| &d.directive_hash() != start_from_directive)
+                .unwrap_or(false)
+        })
+        .filter(|directive| !skip_kind.contains(&directive.directive_kind()))
+        .filter(|directive| {
+            serde_json::to_string(&directive)
+                .tap_err(|e| tracing::error!("{e:#?}"))
+                .map(|directive| contains.iter().all(|contains| directive.contains(contains)))
+                .unwrap_or(false)
+        })
+        .collect_vec()
+        .pipe(|directives| output_filter.filter_directives(directives))
+}
+
+/// archive handling and downloads are configured through globals, they are used far away from the config
+fn configure(downloaders: &DownloadersConfig, archives: ArchivesConfig, scheduling: Scheduling, rehash: bool) -> anyhow::Result<()> {
+    directives::scheduling::configure_scheduling(scheduling);
+    crate::compression::passwords::set_archive_passwords(archives.passwords);
+    crate::compression::configure_wrapped_7zip(archives.seven_zip);
+    crate::downloaders::http_client::configure_http_client(downloaders)?;
+    downloads::backend::configure_download_backend(downloaders.backend.clone());
+    download_cache::hash_cache::configure_hash_cache(&downloaders.downloads_directory, rehash).context("loading hash cache")
+}
+
+/// a failed deduplication leaves the installation as it was, so it does not fail the installation either
+async fn try_deduplicate_outputs(installation_path: PathBuf, outputs: Vec<(DirectiveKind, String, u64, PathBuf)>) {
+    if let Err(error) = spawn_rayon(move || deduplicate::deduplicate_outputs(&installation_path, &outputs)).await {
+        tracing::warn!(?error, "could not deduplicate identical outputs");
+    }
+}
 
-#[allow(clippy::needless_as_bytes)]
 #[instrument(skip_all)]
 pub async fn install_modlist(
     HoolamikeConfig {
         downloaders,
-        installation: InstallationConfig {
-            wabbajack_file_path,
-            installation_path,
-        },
+        installation:
+            InstallationConfig {
+                wabbajack_file_path,
+                installation_path,
+                cpu_tasks,
+                io_tasks,
+                max_open_files,
+                copy_strategy,
+                deduplicate_outputs,
+                bsa_compression,
+                stream_bsa_files_gigabytes,
+                remapping,
+                nested_archive_temp_gigabytes,
+            },
         games,
         fixup: _,
+        archives,
         extras: _,
     }: HoolamikeConfig,
     DebugHelpers {
         skip_verify_and_downloads,
+        rehash,
+        dry_run,
         start_from_directive,
         skip_kind,
         contains,
+        only,
+        exclude,
+        force,
+        report_html,
+        uncompressed_bsa,
     }: DebugHelpers,
 ) -> TotalResult<()> {
+    configure(&downloaders, archives, Scheduling::new(cpu_tasks, io_tasks, max_open_files), rehash).map_err(|e| vec![e])?;
+    let output_filter = OutputFilter::new(&only, &exclude)
+        .context("parsing --only/--exclude")
+        .map_err(|e| vec![e])?;
     let synchronizers = Synchronizers::new(downloaders.clone(), games.clone())
         .context("setting up downloaders")
         .map_err(|e| vec![e])?;
@@ -81,8 +154,43 @@ pub async fn install_modlist(
                 })
         })
         .map_err(|e| vec![e])?;
+    let modlist = Modlist {
+        directives: select_directives(modlist.directives, start_from_directive.as_ref(), &skip_kind, &contains, &output_filter),
+        ..modlist
+    };
+
+    if dry_run {
+        return dry_run::dry_run(synchronizers.cache.clone(), modlist.archives, modlist.directives)
+            .await
+            .map(|_| vec![])
+            .map_err(|e| vec![e]);
+    }
+    if !force {
+        output_space::check_output_space(&installation_path, *crate::consts::TEMP_FILE_DIR, &modlist.directives, &modlist.archives).map_err(|e| vec![e])?;
+    }
+
+    let started = std::time::Instant::now();
+    let report = InstallReport {
+        modlist: modlist.name.clone(),
+        version: modlist.version.clone(),
+        hoolamike_version: env!("CARGO_PKG_VERSION"),
+        started_at: chrono::Local::now(),
+        elapsed_seconds: 0.0,
+        downloads: vec![],
+        directive_kinds: modlist
+            .directives
+            .iter()
+            .map(|directive| (directive.directive_kind(), directive.size()))
+            .pipe(dry_run::summarize_directives),
+        failures: vec![],
+        audit: None,
+    };
+    let expected_outputs = crate::verify_cli::expected_outputs(&modlist.directives)
+        .pipe(|outputs| crate::verify_cli::skip_streamed_bsa_files(outputs, stream_bsa_files_gigabytes));
+    let report_directory = installation_path.clone();
+    let report_synchronizers = synchronizers.clone();
 
-    modlist
+    let result = modlist
         .pipe(Ok)
         .pipe(ready)
         .and_then(
@@ -124,6 +232,13 @@ pub async fn install_modlist(
                         .boxed_local(),
                     false => synchronizers.clone().sync_downloads(archives).boxed_local(),
                 }
+                // interrupted downloads leave nothing for the directives to work with
+                .and_then(|summary| {
+                    crate::shutdown::check_stopped()
+                        .map(|_| summary)
+                        .map_err(|e| vec![e])
+                        .pipe(ready)
+                })
                 .and_then({
                     move |summary| {
                         tracing::Span::current().pb_inc(summary.iter().map(|d| d.descriptor.size).sum());
@@ -137,6 +252,14 @@ pub async fn install_modlist(
                                         output_directory: installation_path,
                                         game_directory: game_config.root_directory.clone(),
                                         downloads_directory: downloaders.downloads_directory.clone(),
+                                        copy_strategy,
+                                        bsa_compression: BsaCompressionConfig {
+                                            uncompressed: bsa_compression.uncompressed || uncompressed_bsa,
+                                            ..bsa_compression
+                                        },
+                                        stream_bsa_files_gigabytes,
+                                        remapping,
+                                        nested_archive_temp_gigabytes,
                                     },
                                     summary,
                                 )
@@ -147,27 +270,13 @@ pub async fn install_modlist(
                 })
                 .map_ok(Arc::new)
                 .and_then(move |directives_handler| {
+                    let tracker = ProgressTracker::new("handle_directives", directives.iter().map(|directive| directive.size()).sum());
                     directives_handler
-                        .handle_directives(directives.tap_mut(|directives| {
-                            *directives = directives
-                                .pipe(std::mem::take)
-                                .drain(..)
-                                .skip_while(|d| {
-                                    start_from_directive
-                                        .as_ref()
-                                        .map(|start_from_directive| &d.directive_hash() != start_from_directive)
-                                        .unwrap_or(false)
-                                })
-                                .filter(|directive| !skip_kind.contains(&directive.directive_kind()))
-                                .filter(|directive| {
-                                    serde_json::to_string(&directive)
-                                        .tap_err(|e| tracing::error!("{e:#?}"))
-                                        .map(|directive| contains.iter().all(|contains| directive.contains(contains)))
-                                        .unwrap_or(false)
-                                })
-                                .collect_vec();
-                        }))
-                        .map_ok(|size| tracing::Span::current().pb_inc(size))
+                        .handle_directives(directives)
+                        .map_ok(move |size| {
+                            tracker.advance(size);
+                            tracing::Span::current().pb_inc(size)
+                        })
                         .try_collect::<Vec<_>>()
                         .map(|res| match res {
                             Ok(out) => Ok(out),
@@ -176,5 +285,43 @@ pub async fn install_modlist(
                 })
             },
         )
+        .inspect(|_| crate::compression::log_wrapped_7zip_temp_stats())
         .await
+        // the directives which did not run are not an error of their own, the journal remembers the ones which did
+        .and_then(|out| {
+            crate::shutdown::check_stopped()
+                .map(|_| out)
+                .map_err(|e| vec![e])
+        });
+
+    if deduplicate_outputs && result.is_ok() {
+        try_deduplicate_outputs(report_directory.clone(), expected_outputs.clone()).await;
+    }
+    // sizes only, the directives themselves already checked the hashes of what they wrote
+    let audit = match &result {
+        Ok(_) => Some(crate::verify_cli::verify_outputs(&report_directory, expected_outputs, true).await),
+        Err(_) => None,
+    };
+    if let Some(audit) = audit.as_ref().filter(|audit| !audit.problems.is_empty()) {
+        tracing::warn!(
+            "[{}/{}] files do not match the modlist after the installation",
+            audit.problems.len(),
+            audit.checked
+        );
+    }
+    InstallReport {
+        elapsed_seconds: started.elapsed().as_secs_f64(),
+        downloads: report_synchronizers.download_records(),
+        failures: result
+            .as_ref()
+            .err()
+            .map(|errors| errors.iter().map(|error| format!("{error:?}")).collect())
+            .unwrap_or_default(),
+        audit,
+        ..report
+    }
+    .write(&report_directory, report_html)
+    .map(|path| tracing::info!("install report written to [{}]", path.display()))
+    .unwrap_or_else(|error| tracing::warn!(?error, "could not write the install report"));
+    result
 }
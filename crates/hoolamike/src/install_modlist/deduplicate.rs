@@ -0,0 +1,208 @@
+//! the same asset is often shipped by many mods, every set of outputs with the same directive hash ends up
+//! hard linked to a single blob in `.hoolamike-store`, so it only takes space once
+#[cfg(unix)]
+use {
+    super::{directives::is_whitelisted_by_path, download_cache::to_u64_from_base_64},
+    crate::helpers::human_readable_size,
+    anyhow::Context,
+    itertools::Itertools,
+    rayon::prelude::*,
+    std::{collections::BTreeMap, io::Read, os::unix::fs::MetadataExt},
+    tap::prelude::*,
+    tracing::{debug, info},
+};
+use {
+    crate::modlist_json::DirectiveKind,
+    anyhow::Result,
+    std::path::{Path, PathBuf},
+    tracing::instrument,
+};
+
+pub const STORE_DIRECTORY_NAME: &str = ".hoolamike-store";
+
+/// paths of outputs which share their contents, keyed by `(hash, size)`
+#[cfg(unix)]
+fn duplicate_groups(outputs: &[(DirectiveKind, String, u64, PathBuf)]) -> BTreeMap<(String, u64), Vec<PathBuf>> {
+    outputs
+        .iter()
+        // hashes of textures describe what wabbajack wrote, not necessarily what ends up on disk
+        .filter(|(_, _, _, path)| !is_whitelisted_by_path(path))
+        // staged bsa files are removed once the bsa is built
+        .filter(|(kind, _, _, _)| *kind != DirectiveKind::CreateBSA)
+        .filter(|(_, _, _, path)| super::output_filter::staged_for_bsa(path).is_none())
+        .filter(|(_, _, size, _)| *size > 0)
+        .map(|(_, hash, size, path)| ((hash.clone(), *size), path.clone()))
+        .into_group_map()
+        .into_iter()
+        .map(|(key, paths)| (key, paths.into_iter().unique().collect_vec()))
+        .filter(|(_, paths)| paths.len() > 1)
+        .collect()
+}
+
+#[cfg(unix)]
+fn blob_path(store: &Path, hash: u64) -> PathBuf {
+    store.join(format!("{hash:016x}"))
+}
+
+#[cfg(unix)]
+fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(unix)]
+fn content_hash(path: &Path) -> Result<u64> {
+    let mut file = std::fs::File::open(path).with_context(|| format!("opening [{}]", path.display()))?;
+    let mut buffer = vec![0; crate::BUFFER_SIZE];
+    let mut hasher = xxhash_rust::xxh64::Xxh64::new(0);
+    loop {
+        match file
+            .read(&mut buffer)
+            .with_context(|| format!("reading [{}]", path.display()))?
+        {
+            0 => break Ok(hasher.digest()),
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+}
+
+/// returns the number of bytes saved
+#[cfg(unix)]
+fn link_group(store: &Path, hash: &str, size: u64, outputs: &[PathBuf]) -> Result<u64> {
+    let expected = to_u64_from_base_64(hash.to_string()).with_context(|| format!("bad hash [{hash}]"))?;
+    let blob = blob_path(store, expected);
+    // a blob changed through one of its links would spread to every output linked to it
+    if blob.exists() && !content_hash(&blob).is_ok_and(|found| found == expected) {
+        debug!(blob=%blob.display(), "blob does not match its hash anymore, replacing it");
+        std::fs::remove_file(&blob).with_context(|| format!("removing [{}]", blob.display()))?;
+    }
+    let existing_blob = std::fs::metadata(&blob).ok();
+    // only outputs which are still what the directive wrote are linked, a size can match by chance
+    let outputs = outputs
+        .iter()
+        .filter_map(|output| {
+            std::fs::metadata(output)
+                .ok()
+                .filter(|metadata| metadata.len() == size)
+                .map(|metadata| (output, metadata))
+        })
+        .filter(|(output, metadata)| {
+            existing_blob
+                .as_ref()
+                .is_some_and(|blob| same_file(metadata, blob))
+                || content_hash(output).is_ok_and(|found| found == expected)
+        })
+        .collect_vec();
+    let Some((first, _)) = outputs.first() else {
+        return Ok(0);
+    };
+    if existing_blob.is_none() {
+        std::fs::hard_link(first, &blob).with_context(|| format!("storing [{}] as [{}]", first.display(), blob.display()))?;
+    }
+    let blob_metadata = std::fs::metadata(&blob).with_context(|| format!("reading [{}]", blob.display()))?;
+    outputs
+        .into_iter()
+        .filter(|(_, metadata)| !same_file(metadata, &blob_metadata))
+        .try_fold(0, |saved, (output, _)| {
+            // linked next to the output first, so the output is never missing
+            let linking = output.with_added_extension("hoolamike-dedup");
+            std::fs::hard_link(&blob, &linking)
+                .and_then(|_| std::fs::rename(&linking, output))
+                .with_context(|| format!("linking [{}] to [{}]", output.display(), blob.display()))
+                .inspect_err(|_| {
+                    std::fs::remove_file(&linking).ok();
+                })
+                .map(|_| saved + size)
+        })
+}
+
+/// blobs nothing links to anymore (eg. after an upgrade removed the outputs)
+#[cfg(unix)]
+fn remove_unused_blobs(store: &Path) -> Result<usize> {
+    std::fs::read_dir(store)
+        .with_context(|| format!("reading [{}]", store.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.metadata().is_ok_and(|metadata| metadata.nlink() == 1))
+        .try_fold(0, |removed, entry| {
+            std::fs::remove_file(entry.path())
+                .with_context(|| format!("removing unused blob [{}]", entry.path().display()))
+                .map(|_| removed + 1)
+        })
+}
+
+/// `outputs` are `(kind, hash, size, path relative to the installation directory)`, as in [crate::verify_cli::expected_outputs]
+#[cfg(unix)]
+#[instrument(skip_all, fields(installation_path=%installation_path.display()))]
+pub fn deduplicate_outputs(installation_path: &Path, outputs: &[(DirectiveKind, String, u64, PathBuf)]) -> Result<()> {
+    let store = installation_path.join(STORE_DIRECTORY_NAME);
+    std::fs::create_dir_all(&store).with_context(|| format!("creating [{}]", store.display()))?;
+    let groups = duplicate_groups(outputs);
+    let saved = groups
+        .par_iter()
+        .map(|((hash, size), paths)| {
+            paths
+                .iter()
+                .map(|path| installation_path.join(path))
+                .collect_vec()
+                .pipe(|paths| link_group(&store, hash, *size, &paths))
+        })
+        .collect::<Result<Vec<_>>>()
+        .context("hard linking duplicate outputs, set `installation.deduplicate_outputs: false` if the filesystem does not support hard links")?
+        .into_iter()
+        .sum::<u64>();
+    let removed = remove_unused_blobs(&store)?;
+    debug!(%removed, "removed unused blobs");
+    info!(
+        "[{}] sets of identical files are stored once, [{}] saved",
+        groups.len(),
+        human_readable_size(saved)
+    );
+    Ok(())
+}
+
+/// blobs are told apart from outputs by their inode, which only unix exposes
+#[cfg(not(unix))]
+#[instrument(skip_all, fields(installation_path=%installation_path.display()))]
+pub fn deduplicate_outputs(installation_path: &Path, _outputs: &[(DirectiveKind, String, u64, PathBuf)]) -> Result<()> {
+    tracing::warn!("deduplicating outputs is only supported on unix, skipping");
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use {super::*, crate::install_modlist::download_cache::to_base_64_from_u64};
+
+    #[test]
+    fn test_duplicates_share_a_blob() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let hash = xxhash_rust::xxh64::xxh64(b"plugin", 0).pipe(to_base_64_from_u64);
+        let output = |path: &str| (DirectiveKind::FromArchive, hash.clone(), 6, PathBuf::from(path));
+        let outputs = [output("a/plugin.esp"), output("b/plugin.esp"), output("c/texture.dds"), output("d/plugin.esp")];
+        [
+            ("a/plugin.esp", b"plugin"),
+            ("b/plugin.esp", b"plugin"),
+            ("c/texture.dds", b"plugin"),
+            ("d/plugin.esp", b"edited"),
+        ]
+        .iter()
+        .try_for_each(|(path, contents)| {
+            let path = directory.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).and_then(|_| std::fs::write(path, contents))
+        })?;
+
+        deduplicate_outputs(directory.path(), &outputs)?;
+        let metadata = |path: &str| std::fs::metadata(directory.path().join(path));
+        assert!(same_file(&metadata("a/plugin.esp")?, &metadata("b/plugin.esp")?));
+        assert_eq!(metadata("a/plugin.esp")?.nlink(), 3);
+        assert_eq!(metadata("c/texture.dds")?.nlink(), 1);
+        // same size, but the user changed it since
+        assert_eq!(metadata("d/plugin.esp")?.nlink(), 1);
+        assert_eq!(std::fs::read(directory.path().join("d/plugin.esp"))?, b"edited");
+
+        // the store forgets blobs once nothing uses them anymore
+        std::fs::remove_file(directory.path().join("a/plugin.esp"))?;
+        std::fs::remove_file(directory.path().join("b/plugin.esp"))?;
+        deduplicate_outputs(directory.path(), &[])?;
+        assert_eq!(std::fs::read_dir(directory.path().join(STORE_DIRECTORY_NAME))?.count(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+//! which archives feed which output directories (`hoolamike hoolamike-debug directive-graph`), sorted by size so the
+//! archives an installation spends the most time extracting come first
+use {
+    super::directives::{archive_hash_path, expected_output},
+    crate::{
+        helpers::human_readable_size,
+        modlist_json::{Archive, Directive},
+    },
+    itertools::Itertools,
+    serde::Serialize,
+    std::{collections::BTreeMap, path::Component},
+    tap::prelude::*,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum GraphFormat {
+    /// graphviz, render it with e.g. `dot -Tsvg`
+    #[default]
+    Dot,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    Archive,
+    /// files stored in the .wabbajack file itself
+    Inline,
+    /// bsa/ba2 archives built from files staged by other directives
+    CreatedBsa,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Source {
+    pub kind: SourceKind,
+    /// archive name, or the hash when the modlist does not list the archive
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceNode {
+    #[serde(flatten)]
+    pub source: Source,
+    /// size of the archive itself, what has to be downloaded and extracted
+    pub archive_size: Option<u64>,
+    /// bytes of all the outputs it feeds
+    pub output_size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TargetNode {
+    pub directory: String,
+    pub output_size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Edge {
+    pub source: Source,
+    pub target: String,
+    pub directives: usize,
+    pub output_size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DirectiveGraph {
+    pub sources: Vec<SourceNode>,
+    pub targets: Vec<TargetNode>,
+    pub edges: Vec<Edge>,
+}
+
+fn source(archive_names: &BTreeMap<&str, &str>, directive: &Directive) -> Source {
+    match (archive_hash_path(directive), directive) {
+        (Some(archive_hash_path), _) => Source {
+            kind: SourceKind::Archive,
+            name: archive_names
+                .get(archive_hash_path.source_hash.as_str())
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| archive_hash_path.source_hash.clone()),
+        },
+        (None, Directive::CreateBSA(_)) => Source {
+            kind: SourceKind::CreatedBsa,
+            name: "(created bsa/ba2)".into(),
+        },
+        (None, Directive::Unknown(unknown)) => Source {
+            kind: SourceKind::Unknown,
+            name: unknown.type_name.clone(),
+        },
+        (None, _) => Source {
+            kind: SourceKind::Inline,
+            name: "(inline files)".into(),
+        },
+    }
+}
+
+/// the first `depth` directories of the output, `.` for files in the root of the installation
+fn target_directory(directive: &Directive, depth: usize) -> String {
+    expected_output(directive)
+        .2
+        .into_path()
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                    _ => None,
+                })
+                .take(depth)
+                .join("/")
+        })
+        .filter(|directory| !directory.is_empty())
+        .unwrap_or_else(|| ".".into())
+}
+
+pub fn directive_graph(archives: &[Archive], directives: &[Directive], depth: usize) -> DirectiveGraph {
+    let archive_names = archives
+        .iter()
+        .map(|archive| (archive.descriptor.hash.as_str(), archive.descriptor.name.as_str()))
+        .collect::<BTreeMap<_, _>>();
+    let archive_sizes = archives
+        .iter()
+        .map(|archive| (archive.descriptor.name.as_str(), archive.descriptor.size))
+        .collect::<BTreeMap<_, _>>();
+    let edges = directives
+        .iter()
+        .map(|directive| ((source(&archive_names, directive), target_directory(directive, depth)), directive.size()))
+        .into_group_map()
+        .into_iter()
+        .map(|((source, target), sizes)| Edge {
+            source,
+            target,
+            directives: sizes.len(),
+            output_size: sizes.into_iter().sum(),
+        })
+        .sorted_by(|a, b| {
+            b.output_size
+                .cmp(&a.output_size)
+                .then_with(|| (&a.source, &a.target).cmp(&(&b.source, &b.target)))
+        })
+        .collect_vec();
+    let sources = edges
+        .iter()
+        .map(|edge| (edge.source.clone(), edge.output_size))
+        .into_grouping_map()
+        .sum()
+        .into_iter()
+        .map(|(source, output_size)| SourceNode {
+            archive_size: match source.kind {
+                SourceKind::Archive => archive_sizes.get(source.name.as_str()).copied(),
+                _ => None,
+            },
+            source,
+            output_size,
+        })
+        .sorted_by(|a, b| {
+            (b.archive_size, b.output_size)
+                .cmp(&(a.archive_size, a.output_size))
+                .then_with(|| a.source.cmp(&b.source))
+        })
+        .collect_vec();
+    let targets = edges
+        .iter()
+        .map(|edge| (edge.target.clone(), edge.output_size))
+        .into_grouping_map()
+        .sum()
+        .into_iter()
+        .map(|(directory, output_size)| TargetNode { directory, output_size })
+        .sorted_by(|a, b| {
+            b.output_size
+                .cmp(&a.output_size)
+                .then_with(|| a.directory.cmp(&b.directory))
+        })
+        .collect_vec();
+    DirectiveGraph { sources, targets, edges }
+}
+
+fn dot_string(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .pipe(|text| format!("\"{text}\""))
+}
+
+impl DirectiveGraph {
+    pub fn to_dot(&self) -> String {
+        let source_ids = self
+            .sources
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (&node.source, format!("s{index}")))
+            .collect::<BTreeMap<_, _>>();
+        let target_ids = self
+            .targets
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.directory.as_str(), format!("t{index}")))
+            .collect::<BTreeMap<_, _>>();
+        std::iter::empty()
+            .chain([
+                "digraph directives {".to_string(),
+                "  rankdir=LR;".to_string(),
+                "  node [shape=box];".to_string(),
+            ])
+            .chain(self.sources.iter().map(|node| {
+                let label = match node.archive_size {
+                    Some(archive_size) => format!("{}\n{} archive", node.source.name, human_readable_size(archive_size)),
+                    None => node.source.name.clone(),
+                };
+                format!("  {} [label={}];", source_ids[&node.source], dot_string(&label))
+            }))
+            .chain(self.targets.iter().map(|node| {
+                format!(
+                    "  {} [label={}, shape=folder];",
+                    target_ids[node.directory.as_str()],
+                    dot_string(&format!("{}\n{}", node.directory, human_readable_size(node.output_size)))
+                )
+            }))
+            .chain(self.edges.iter().map(|edge| {
+                format!(
+                    "  {} -> {} [label={}];",
+                    source_ids[&edge.source],
+                    target_ids[edge.target.as_str()],
+                    dot_string(&format!("{} files, {}", edge.directives, human_readable_size(edge.output_size)))
+                )
+            }))
+            .chain(["}".to_string()])
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            modlist_json::{
+                directive::{ArchiveHashPath, FromArchiveDirective, InlineFileDirective},
+                ArchiveDescriptor,
+                HttpState,
+                State,
+            },
+            utils::MaybeWindowsPath,
+        },
+    };
+
+    fn from_archive(source_hash: &str, to: &str, size: u64) -> Directive {
+        Directive::FromArchive(FromArchiveDirective {
+            hash: "AAAAAAAAAAA=".into(),
+            size,
+            to: MaybeWindowsPath::new(to),
+            archive_hash_path: ArchiveHashPath {
+                source_hash: source_hash.into(),
+                path: vec![MaybeWindowsPath::new("file")],
+            },
+        })
+    }
+
+    #[test]
+    fn test_outputs_are_grouped_by_archive_and_directory() -> anyhow::Result<()> {
+        let archives = [Archive {
+            descriptor: ArchiveDescriptor {
+                hash: "archive=".into(),
+                meta: String::new(),
+                name: "SkyUI.7z".into(),
+                size: 1000,
+            },
+            state: State::Http(HttpState {
+                headers: vec![],
+                url: "https://example.com/SkyUI.7z".parse()?,
+            }),
+        }];
+        let directives = [
+            from_archive("archive=", "mods\\SkyUI\\interface\\skyui.swf", 10),
+            from_archive("archive=", "mods\\SkyUI\\SkyUI_SE.esp", 5),
+            from_archive("missing=", "mods\\Other\\other.esp", 1),
+            Directive::InlineFile(InlineFileDirective {
+                hash: "AAAAAAAAAAA=".into(),
+                size: 2,
+                source_data_id: uuid::Uuid::nil(),
+                to: MaybeWindowsPath::new("ModOrganizer.ini"),
+            }),
+        ];
+        let graph = directive_graph(&archives, &directives, 2);
+        assert_eq!(
+            graph
+                .edges
+                .iter()
+                .map(|edge| (edge.source.name.as_str(), edge.target.as_str(), edge.directives, edge.output_size))
+                .collect_vec(),
+            [
+                ("SkyUI.7z", "mods/SkyUI", 2, 15),
+                ("(inline files)", ".", 1, 2),
+                ("missing=", "mods/Other", 1, 1)
+            ]
+        );
+        assert_eq!(graph.sources[0].archive_size, Some(1000));
+        assert!(graph.to_dot().contains("[label=\"2 files, 15 B\"]"));
+        Ok(())
+    }
+}
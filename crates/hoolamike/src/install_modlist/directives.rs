@@ -1,5 +1,6 @@
 use {
     crate::{
+        config_file::{BsaCompressionConfig, CopyStrategy, RemappingConfig},
         downloaders::{helpers::FutureAnyhowExt, WithArchiveDescriptor},
         install_modlist::{download_cache::validate_hash, io_progress_style},
         modlist_json::{
@@ -15,15 +16,17 @@ use {
             DirectiveKind,
         },
         progress_bars_v2::count_progress_style,
+        shutdown::until_stopped,
         utils::{MaybeWindowsPath, PathReadWrite},
     },
     anyhow::{Context, Result},
-    futures::{FutureExt, Stream, StreamExt, TryStreamExt},
+    futures::{FutureExt, Stream, StreamExt, TryFutureExt, TryStreamExt},
+    install_journal::InstallJournal,
     itertools::Itertools,
     nonempty::NonEmpty,
     remapped_inline_file::RemappingContext,
     std::{
-        collections::BTreeMap,
+        collections::{BTreeMap, BTreeSet},
         future::ready,
         iter::once,
         path::{Path, PathBuf},
@@ -45,9 +48,11 @@ pub(crate) fn create_file_all(path: &Path) -> Result<std::fs::File> {
 
 pub type DownloadSummary = Arc<BTreeMap<String, WithArchiveDescriptor<PathBuf>>>;
 
+pub mod copy_strategy;
 pub mod create_bsa;
 pub mod from_archive;
 pub mod inline_file;
+pub mod install_journal;
 pub mod patched_from_archive;
 pub mod remapped_inline_file;
 pub mod transformed_texture;
@@ -67,6 +72,9 @@ pub struct DirectivesHandler {
     pub remapped_inline_file: remapped_inline_file::RemappedInlineFileHandler,
     pub transformed_texture: transformed_texture::TransformedTextureHandler,
     pub download_summary: DownloadSummary,
+    pub install_journal: Arc<InstallJournal>,
+    pub streamed_bsa_files: Arc<create_bsa::streamed_files::StreamedBsaFiles>,
+    pub extraction_budget: Arc<nested_archive_manager::ExtractionBudget>,
 }
 
 #[derive(Debug, Clone)]
@@ -75,22 +83,16 @@ pub struct DirectivesHandlerConfig {
     pub output_directory: PathBuf,
     pub game_directory: PathBuf,
     pub downloads_directory: PathBuf,
+    pub copy_strategy: CopyStrategy,
+    pub bsa_compression: BsaCompressionConfig,
+    pub stream_bsa_files_gigabytes: Option<u64>,
+    pub remapping: RemappingConfig,
+    pub nested_archive_temp_gigabytes: u64,
 }
 
 pub mod nested_archive_manager;
 
-fn concurrency() -> usize {
-    #[cfg(not(debug_assertions))]
-    {
-        use std::ops::Div;
-
-        num_cpus::get().div(2).saturating_sub(1).max(1)
-    }
-    #[cfg(debug_assertions)]
-    {
-        1
-    }
-}
+pub mod scheduling;
 
 #[extension_traits::extension(pub trait StreamTryFlatMapLocalExt)]
 impl<'iter, T, E, I> I
@@ -143,7 +145,7 @@ const EXTENSION_HASH_WHITELIST: &[&str] = &[
           // "bsa",
 ];
 
-fn is_whitelisted_by_path(path: &Path) -> bool {
+pub(crate) fn is_whitelisted_by_path(path: &Path) -> bool {
     matches!(
         path
             .extension()
@@ -153,6 +155,8 @@ fn is_whitelisted_by_path(path: &Path) -> bool {
     )
 }
 
+/// textures are resized with wabbajack's filter but are still not byte identical after block compression,
+/// hashing them would read every texture only to fall back to comparing sizes
 pub async fn validate_hash_with_overrides(path: PathBuf, hash: String, size: u64) -> Result<PathBuf> {
     match is_whitelisted_by_path(&path) {
         true => super::download_cache::validate_file_size(path, size).await,
@@ -160,6 +164,45 @@ pub async fn validate_hash_with_overrides(path: PathBuf, hash: String, size: u64
     }
 }
 
+/// hash, size and path (relative to the output directory) of the file a directive produces
+pub(crate) fn expected_output(directive: &Directive) -> (String, u64, MaybeWindowsPath) {
+    match directive {
+        Directive::CreateBSA(create_bsa) => match create_bsa {
+            CreateBSADirective::Bsa(CreateBSADirectiveKind { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
+            CreateBSADirective::Ba2(CreateBSADirectiveKind { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
+        },
+        Directive::FromArchive(FromArchiveDirective { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
+        Directive::InlineFile(InlineFileDirective { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
+        Directive::PatchedFromArchive(PatchedFromArchiveDirective { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
+        Directive::RemappedInlineFile(RemappedInlineFileDirective { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
+        Directive::TransformedTexture(TransformedTextureDirective { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
+        Directive::Unknown(unknown) => (
+            unknown.hash().unwrap_or_default().to_string(),
+            unknown.size(),
+            unknown
+                .to()
+                .unwrap_or_else(|| MaybeWindowsPath(String::new())),
+        ),
+    }
+}
+
+/// the archive a directive takes its file from
+pub(crate) fn archive_hash_path(directive: &Directive) -> Option<&ArchiveHashPath> {
+    match directive {
+        Directive::FromArchive(FromArchiveDirective { archive_hash_path, .. })
+        | Directive::PatchedFromArchive(PatchedFromArchiveDirective { archive_hash_path, .. })
+        | Directive::TransformedTexture(TransformedTextureDirective { archive_hash_path, .. }) => Some(archive_hash_path),
+        Directive::CreateBSA(_) | Directive::InlineFile(_) | Directive::RemappedInlineFile(_) | Directive::Unknown(_) => None,
+    }
+}
+
+/// what the install journal needs to know about a directive, computed before the directive is handed over to its handler
+#[derive(Debug, Clone)]
+struct InstalledOutput {
+    directive_hash: String,
+    output: PathBuf,
+}
+
 #[derive(derive_more::From, Clone, Debug)]
 enum ArchivePathDirective {
     FromArchive(FromArchiveDirective),
@@ -185,6 +228,16 @@ impl ArchivePathDirective {
     }
 }
 
+impl From<ArchivePathDirective> for Directive {
+    fn from(directive: ArchivePathDirective) -> Self {
+        match directive {
+            ArchivePathDirective::FromArchive(directive) => Directive::FromArchive(directive),
+            ArchivePathDirective::PatchedFromArchive(directive) => Directive::PatchedFromArchive(directive),
+            ArchivePathDirective::TransformedTexture(directive) => Directive::TransformedTexture(directive),
+        }
+    }
+}
+
 pub mod queued_archive_task;
 
 pub mod nested_archive_directives;
@@ -232,7 +285,13 @@ impl DirectivesHandler {
             output_directory,
             game_directory,
             downloads_directory,
+            copy_strategy,
+            bsa_compression,
+            stream_bsa_files_gigabytes,
+            remapping,
+            nested_archive_temp_gigabytes,
         } = config.clone();
+        let streamed_bsa_files = create_bsa::streamed_files::StreamedBsaFiles::new(stream_bsa_files_gigabytes).pipe(Arc::new);
         let download_summary: DownloadSummary = sync_summary
             .into_iter()
             .map(|s| (s.descriptor.hash.clone(), s))
@@ -240,13 +299,18 @@ impl DirectivesHandler {
             .pipe(Arc::new);
 
         Self {
+            install_journal: InstallJournal::load(&output_directory).pipe(Arc::new),
             config,
             create_bsa: create_bsa::CreateBSAHandler {
                 output_directory: output_directory.clone(),
+                compression: bsa_compression,
+                streamed_bsa_files: streamed_bsa_files.clone(),
             },
             from_archive: from_archive::FromArchiveHandler {
                 output_directory: output_directory.clone(),
                 download_summary: download_summary.clone(),
+                copy_strategy,
+                streamed_bsa_files: streamed_bsa_files.clone(),
             },
             inline_file: inline_file::InlineFileHandler {
                 wabbajack_file: wabbajack_file.clone(),
@@ -262,6 +326,7 @@ impl DirectivesHandler {
                     game_folder: game_directory.clone(),
                     output_directory: output_directory.clone(),
                     downloads_directory,
+                    config: remapping,
                 }),
                 wabbajack_file: wabbajack_file.clone(),
             },
@@ -270,6 +335,27 @@ impl DirectivesHandler {
                 download_summary: download_summary.clone(),
             },
             download_summary,
+            streamed_bsa_files,
+            extraction_budget: nested_archive_manager::ExtractionBudget::new(nested_archive_temp_gigabytes).pipe(Arc::new),
+        }
+    }
+
+    fn installed_output(&self, directive: &Directive) -> InstalledOutput {
+        InstalledOutput {
+            directive_hash: directive.directive_hash(),
+            output: self
+                .config
+                .output_directory
+                .join(expected_output(directive).2.into_path()),
+        }
+    }
+
+    /// called once the output of a directive is in place, so that the next run does not have to hash it
+    fn record_installed(&self, InstalledOutput { directive_hash, output }: &InstalledOutput) {
+        crate::progress_bars_v2::events::directive_installed(directive_hash, output);
+        // streamed files never make it to the disk, their archive does
+        if !self.streamed_bsa_files.contains(output) {
+            self.install_journal.record(directive_hash.clone(), output)
         }
     }
 
@@ -292,39 +378,42 @@ impl DirectivesHandler {
                 Directive::PatchedFromArchive(directive) => directive.size,
                 Directive::RemappedInlineFile(directive) => directive.size,
                 Directive::TransformedTexture(directive) => directive.size,
+                Directive::Unknown(directive) => directive.size(),
             }
         }
         let manager = self.clone();
 
         enum DirectiveStatus {
             Completed(u64),
-            NeedsRebuild { reason: anyhow::Error, directive: Directive },
+            NeedsRebuild {
+                reason: anyhow::Error,
+                directive: Directive,
+                installed: InstalledOutput,
+            },
         }
 
         let check_completed = {
-            let output_directory = self.from_archive.output_directory.clone();
+            cloned![manager];
             move |directive: Directive| {
                 let _kind = DirectiveKind::from(&directive);
-                match &directive {
-                    Directive::CreateBSA(create_bsa) => match create_bsa {
-                        CreateBSADirective::Bsa(CreateBSADirectiveKind { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
-                        CreateBSADirective::Ba2(CreateBSADirectiveKind { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
-                    },
-                    Directive::FromArchive(FromArchiveDirective { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
-                    Directive::InlineFile(InlineFileDirective { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
-                    Directive::PatchedFromArchive(PatchedFromArchiveDirective { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
-                    Directive::RemappedInlineFile(RemappedInlineFileDirective { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
-                    Directive::TransformedTexture(TransformedTextureDirective { hash, size, to, .. }) => (hash.clone(), *size, to.clone()),
-                }
-                .pipe(|(hash, size, to)| (hash, size, output_directory.join(to.into_path())))
-                .pipe(move |(hash, size, to)| {
-                    validate_hash_with_overrides(to.clone(), hash, size)
-                        .map(move |res| match res {
-                            Ok(_) => DirectiveStatus::Completed(size),
-                            Err(reason) => DirectiveStatus::NeedsRebuild { reason, directive },
+                let installed = manager.installed_output(&directive);
+                let (hash, size, _) = expected_output(&directive);
+                match manager
+                    .install_journal
+                    .is_installed(&installed.directive_hash, &installed.output)
+                {
+                    true => DirectiveStatus::Completed(size).pipe(ready).boxed_local(),
+                    false => validate_hash_with_overrides(installed.output.clone(), hash, size)
+                        .map({
+                            cloned![manager];
+                            move |res| match res {
+                                Ok(_) => DirectiveStatus::Completed(size).tap(|_| manager.record_installed(&installed)),
+                                Err(reason) => DirectiveStatus::NeedsRebuild { reason, directive, installed },
+                            }
                         })
                         .instrument(handle_directives.clone())
-                })
+                        .boxed_local(),
+                }
             }
         };
         {
@@ -334,6 +423,7 @@ impl DirectivesHandler {
             });
             directives
                 .pipe(futures::stream::iter)
+                .pipe(until_stopped)
                 .map(check_completed)
                 .buffer_unordered(num_cpus::get())
                 .inspect({
@@ -343,70 +433,104 @@ impl DirectivesHandler {
                 .collect::<Vec<_>>()
                 .instrument(validating_hashes)
         }
-        .then(|directives| {
-            (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new())
-                .pipe(
-                    |(
-                        mut create_bsa,
-                        mut from_archive,
-                        mut inline_file,
-                        mut patched_from_archive,
-                        mut remapped_inline_file,
-                        mut transformed_texture,
-                        mut completed,
-                    )| {
-                        directives
-                            .into_iter()
-                            .for_each(|directive| match directive {
-                                DirectiveStatus::Completed(size) => completed.push(size),
-                                DirectiveStatus::NeedsRebuild { reason, directive } => {
-                                    tracing::debug!(
-                                        "recomputing directive\ndirective:{directive}:\nreason:{reason:?}",
-                                        directive = format!("{directive:#?}")
-                                            .chars()
-                                            .take(256)
-                                            .collect::<String>(),
-                                    );
-                                    match directive {
-                                        Directive::CreateBSA(create_bsadirective) => create_bsa.push(create_bsadirective),
-                                        Directive::FromArchive(from_archive_directive) => from_archive.push(from_archive_directive),
-                                        Directive::InlineFile(inline_file_directive) => inline_file.push(inline_file_directive),
-                                        Directive::PatchedFromArchive(patched_from_archive_directive) => {
-                                            patched_from_archive.push(patched_from_archive_directive)
-                                        }
-                                        Directive::RemappedInlineFile(remapped_inline_file_directive) => {
-                                            remapped_inline_file.push(remapped_inline_file_directive)
+        .then({
+            let streamed_bsa_files = self.streamed_bsa_files.clone();
+            move |directives| {
+                (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new())
+                    .pipe(
+                        |(
+                            mut create_bsa,
+                            mut from_archive,
+                            mut inline_file,
+                            mut patched_from_archive,
+                            mut remapped_inline_file,
+                            mut transformed_texture,
+                            mut unknown,
+                            mut completed,
+                        )| {
+                            directives
+                                .into_iter()
+                                .for_each(|directive| match directive {
+                                    DirectiveStatus::Completed(size) => completed.push(size),
+                                    DirectiveStatus::NeedsRebuild { reason, directive, installed } => {
+                                        tracing::debug!(
+                                            "recomputing directive\ndirective:{directive}:\nreason:{reason:?}",
+                                            directive = format!("{directive:#?}")
+                                                .chars()
+                                                .take(256)
+                                                .collect::<String>(),
+                                        );
+                                        match directive {
+                                            Directive::CreateBSA(create_bsadirective) => create_bsa.push((installed, create_bsadirective)),
+                                            Directive::FromArchive(from_archive_directive) => from_archive.push(from_archive_directive),
+                                            Directive::InlineFile(inline_file_directive) => inline_file.push((installed, inline_file_directive)),
+                                            Directive::PatchedFromArchive(patched_from_archive_directive) => {
+                                                patched_from_archive.push(patched_from_archive_directive)
+                                            }
+                                            Directive::RemappedInlineFile(remapped_inline_file_directive) => {
+                                                remapped_inline_file.push((installed, remapped_inline_file_directive))
+                                            }
+                                            Directive::TransformedTexture(transformed_texture_directive) => {
+                                                transformed_texture.push(transformed_texture_directive)
+                                            }
+                                            Directive::Unknown(unknown_directive) => unknown.push(unknown_directive),
                                         }
-                                        Directive::TransformedTexture(transformed_texture_directive) => transformed_texture.push(transformed_texture_directive),
                                     }
-                                }
-                            })
-                            .pipe(|_| {
-                                (
-                                    create_bsa,
-                                    from_archive,
-                                    inline_file,
-                                    patched_from_archive,
-                                    remapped_inline_file,
-                                    transformed_texture,
-                                    completed,
-                                )
-                            })
-                    },
-                )
-                .pipe(ready)
+                                })
+                                .pipe(|_| {
+                                    if streamed_bsa_files.enabled() {
+                                        // streamed files are gone after every run, only archives which are built again need them
+                                        let rebuilt_bsas = create_bsa
+                                            .iter()
+                                            .map(|(_, directive)| match directive {
+                                                CreateBSADirective::Bsa(CreateBSADirectiveKind { temp_id, .. }) => temp_id.clone(),
+                                                CreateBSADirective::Ba2(CreateBSADirectiveKind { temp_id, .. }) => temp_id.clone(),
+                                            })
+                                            .collect::<BTreeSet<_>>();
+                                        from_archive.retain(|directive: &FromArchiveDirective| {
+                                            match super::output_filter::staged_for_bsa(&directive.to.clone().into_path()) {
+                                                Some(temp_id) if !rebuilt_bsas.contains(&temp_id) => {
+                                                    completed.push(directive.size);
+                                                    false
+                                                }
+                                                _ => true,
+                                            }
+                                        });
+                                    }
+                                    (
+                                        create_bsa,
+                                        from_archive,
+                                        inline_file,
+                                        patched_from_archive,
+                                        remapped_inline_file,
+                                        transformed_texture,
+                                        unknown,
+                                        completed,
+                                    )
+                                })
+                        },
+                    )
+                    .pipe(ready)
+            }
         })
         .into_stream()
         .flat_map(
-            move |(create_bsa, from_archive, inline_file, patched_from_archive, remapped_inline_file, transformed_texture, completed)| {
+            move |(create_bsa, from_archive, inline_file, patched_from_archive, remapped_inline_file, transformed_texture, unknown, completed)| {
                 futures::stream::empty()
                     .chain(completed.pipe(futures::stream::iter).map(Ok))
+                    // only the unknown directives which are not installed already are a problem, reported before any work is done
+                    .chain(
+                        unknown
+                            .pipe(futures::stream::iter)
+                            .map(|unknown| Err(anyhow::anyhow!("directive {unknown}, update hoolamike to install this modlist"))),
+                    )
                     .chain(
                         inline_file
                             .pipe(futures::stream::iter)
+                            .pipe(until_stopped)
                             .map({
                                 cloned![manager];
-                                move |directive| {
+                                move |(installed, directive)| {
                                     manager
                                         .clone()
                                         .inline_file
@@ -414,9 +538,13 @@ impl DirectivesHandler {
                                         .handle(directive.clone())
                                         .instrument(handle_directives.clone())
                                         .map(move |res| res.with_context(|| format!("handling directive [{directive:#?}]")))
+                                        .inspect_ok({
+                                            cloned![manager];
+                                            move |_| manager.record_installed(&installed)
+                                        })
                                 }
                             })
-                            .buffer_unordered(concurrency()),
+                            .buffer_unordered(scheduling::scheduling().io_tasks),
                     )
                     .chain(
                         std::iter::empty()
@@ -435,59 +563,78 @@ impl DirectivesHandler {
                             .pipe(|directives| {
                                 const DIRECTIVE_CHUNK_SIZE: u64 = 6 * 1024 * 1024 * 1024;
                                 let download_summary = self.download_summary.clone();
-                                info_span!("handling nested archive directives", total_size=%directives.len(), estimated_chunk_size_bytes=%DIRECTIVE_CHUNK_SIZE)
-                                    .in_scope(|| {
+                                let chunk_size = manager.extraction_budget.chunk_size(DIRECTIVE_CHUNK_SIZE);
+                                info_span!("handling nested archive directives", total_size=%directives.len(), estimated_chunk_size_bytes=%chunk_size).in_scope(
+                                    || {
                                         handle_directives.in_scope(|| {
-                                            crate::utils::chunk_while(directives, |d| d.iter().map(|d| d.directive_size()).sum::<u64>() > DIRECTIVE_CHUNK_SIZE)
+                                            crate::utils::chunk_while(directives, |d| d.iter().map(|d| d.directive_size()).sum::<u64>() > chunk_size)
                                                 .pipe(futures::stream::iter)
+                                                .pipe(until_stopped)
                                                 .flat_map({
                                                     cloned![manager, download_summary];
                                                     move |directives| {
-                                                        info_span!("handling nested archive directives chunk", chunk_size=%directives.len()).in_scope(|| {
-                                                            nested_archive_directives::handle_nested_archive_directives(
-                                                                manager.clone(),
-                                                                download_summary.clone(),
-                                                                directives,
-                                                                concurrency(),
-                                                            )
-                                                        })
+                                                        let nested_archives = manager.extraction_budget.usage();
+                                                        info_span!("handling nested archive directives chunk", chunk_size=%directives.len(), %nested_archives)
+                                                            .in_scope(|| {
+                                                                nested_archive_directives::handle_nested_archive_directives(
+                                                                    manager.clone(),
+                                                                    download_summary.clone(),
+                                                                    directives,
+                                                                    scheduling::scheduling().total_tasks(),
+                                                                )
+                                                            })
                                                     }
                                                 })
                                         })
-                                    })
+                                    },
+                                )
                             }),
                     )
                     .chain(
                         remapped_inline_file
                             .pipe(futures::stream::iter)
+                            .pipe(until_stopped)
                             .map({
                                 cloned![manager];
-                                move |remapped_inline_file| {
+                                move |(installed, remapped_inline_file)| {
                                     manager
                                         .remapped_inline_file
                                         .clone()
                                         .handle(remapped_inline_file.clone())
                                         .instrument(handle_directives.clone())
                                         .map(move |res| res.with_context(|| format!("handling {remapped_inline_file:#?}")))
+                                        .inspect_ok({
+                                            cloned![manager];
+                                            move |_| manager.record_installed(&installed)
+                                        })
                                 }
                             })
-                            .buffer_unordered(concurrency()),
+                            .buffer_unordered(scheduling::scheduling().io_tasks),
+                    )
+                    .chain(
+                        create_bsa
+                            .pipe(futures::stream::iter)
+                            .pipe(until_stopped)
+                            .then({
+                                cloned![manager];
+                                move |(installed, create_bsa)| {
+                                    let debug = format!("{create_bsa:#?}")
+                                        .chars()
+                                        .take(256)
+                                        .collect::<String>();
+                                    manager
+                                        .create_bsa
+                                        .clone()
+                                        .handle(create_bsa)
+                                        .instrument(handle_directives.clone())
+                                        .map(move |res| res.with_context(|| format!("handling directive: [{debug}]")))
+                                        .inspect_ok({
+                                            cloned![manager];
+                                            move |_| manager.record_installed(&installed)
+                                        })
+                                }
+                            }),
                     )
-                    .chain(create_bsa.pipe(futures::stream::iter).then({
-                        cloned![manager];
-                        move |create_bsa| {
-                            let debug = format!("{create_bsa:#?}")
-                                .chars()
-                                .take(256)
-                                .collect::<String>();
-                            manager
-                                .create_bsa
-                                .clone()
-                                .handle(create_bsa)
-                                .instrument(handle_directives.clone())
-                                .map(move |res| res.with_context(|| format!("handling directive: [{debug}]")))
-                        }
-                    }))
                     .inspect_ok({
                         move |size| {
                             handle_directives.pb_inc(*size);
@@ -497,3 +644,34 @@ impl DirectivesHandler {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_only_texture_sizes_are_validated() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let texture = directory.path().join("textures/a.DDS");
+        let plugin = directory.path().join("a.esp");
+        std::fs::create_dir_all(texture.parent().context("no parent")?)?;
+        std::fs::write(&texture, b"texture")?;
+        std::fs::write(&plugin, b"plugin")?;
+        let hash = crate::install_modlist::download_cache::calculate_hash(plugin.clone())
+            .await
+            .map(crate::install_modlist::download_cache::to_base_64_from_u64)?;
+
+        assert!(validate_hash_with_overrides(texture.clone(), hash.clone(), 7)
+            .await
+            .is_ok());
+        assert!(validate_hash_with_overrides(texture, hash.clone(), 8)
+            .await
+            .is_err());
+        assert!(validate_hash_with_overrides(plugin.clone(), hash.clone(), 6)
+            .await
+            .is_ok());
+        std::fs::write(&plugin, b"edited")?;
+        assert!(validate_hash_with_overrides(plugin, hash, 6).await.is_err());
+        Ok(())
+    }
+}
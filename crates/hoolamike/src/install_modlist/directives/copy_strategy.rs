@@ -0,0 +1,83 @@
+//! lots of `FromArchive` directives write the exact bytes of a file which is already on disk (an extracted temporary file, or the download itself),
+//! linking or cloning it instead of copying saves both the space and the time spent writing
+use {
+    crate::config_file::CopyStrategy,
+    anyhow::{Context, Result},
+    std::{
+        path::Path,
+        sync::atomic::{AtomicBool, Ordering},
+    },
+};
+
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> Result<()> {
+    use std::os::fd::AsRawFd;
+    /// `_IOW(0x94, 9, int)` from `linux/fs.h`
+    const FICLONE: libc::c_ulong = 0x40049409;
+
+    let source = std::fs::File::open(from).with_context(|| format!("opening [{}]", from.display()))?;
+    let target = std::fs::File::create(to).with_context(|| format!("creating [{}]", to.display()))?;
+    // SAFETY: both descriptors are open for as long as the call lasts
+    match unsafe { libc::ioctl(target.as_raw_fd(), FICLONE as _, source.as_raw_fd()) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error())
+            .with_context(|| format!("cloning [{}] to [{}]", from.display(), to.display()))
+            .inspect_err(|_| {
+                std::fs::remove_file(to).ok();
+            }),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_from: &Path, _to: &Path) -> Result<()> {
+    anyhow::bail!("reflinks are only supported on linux")
+}
+
+/// fails when the file has to be copied instead, a previous `to` is gone by then
+pub fn link_file(strategy: CopyStrategy, from: &Path, to: &Path) -> Result<()> {
+    if strategy == CopyStrategy::Copy {
+        anyhow::bail!("copy strategy is [copy]");
+    }
+    match std::fs::remove_file(to) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error).with_context(|| format!("removing previous [{}]", to.display())),
+        _ => Ok(()),
+    }?;
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("creating directory for [{}]", to.display()))?;
+    }
+    match strategy {
+        CopyStrategy::Copy => unreachable!(),
+        CopyStrategy::Reflink => reflink(from, to),
+        CopyStrategy::Hardlink => std::fs::hard_link(from, to).with_context(|| format!("linking [{}] to [{}]", from.display(), to.display())),
+    }
+}
+
+static FELL_BACK: AtomicBool = AtomicBool::new(false);
+
+/// warns once per run, the reason is the same for most files (another filesystem, no reflink support)
+pub fn warn_fallback(strategy: CopyStrategy, reason: &anyhow::Error) {
+    match FELL_BACK.swap(true, Ordering::Relaxed) {
+        false => tracing::warn!(?strategy, ?reason, "could not link files, falling back to copying"),
+        true => tracing::debug!(?strategy, ?reason, "falling back to copying"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hardlink_replaces_previous_output() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let from = directory.path().join("extracted.esp");
+        let to = directory.path().join("mods/a/plugin.esp");
+        std::fs::write(&from, b"plugin")?;
+        std::fs::create_dir_all(to.parent().unwrap())?;
+        std::fs::write(&to, b"stale")?;
+
+        link_file(CopyStrategy::Hardlink, &from, &to)?;
+        assert_eq!(std::fs::read(&to)?, b"plugin");
+        assert!(link_file(CopyStrategy::Copy, &from, &to).is_err());
+        Ok(())
+    }
+}
@@ -1,19 +1,24 @@
 use {
     super::*,
     crate::{
+        config_file::BsaCompressionConfig,
         modlist_json::directive::create_bsa_directive::CreateBSADirective,
         progress_bars_v2::{count_progress_style, IndicatifWrapIoExt},
         utils::{spawn_rayon, PathReadWrite},
     },
     remapped_inline_file::wabbajack_consts::BSA_CREATION_DIR,
+    streamed_files::StreamedBsaFiles,
 };
 
 #[derive(Clone, Debug)]
 pub struct CreateBSAHandler {
     pub output_directory: PathBuf,
+    pub compression: BsaCompressionConfig,
+    pub streamed_bsa_files: Arc<StreamedBsaFiles>,
 }
 
 pub mod fallout_4;
+pub mod streamed_files;
 pub mod tes_4;
 
 #[allow(unused_variables)]
@@ -26,39 +31,86 @@ fn try_optimize_memory_mapping(memmap: &memmap2::Mmap) {
     }
 }
 
+/// contents of a file staged for an archive
+#[derive(Debug)]
+pub enum StagedBytes {
+    Mapped(memmap2::Mmap),
+    Streamed(Arc<[u8]>),
+}
+
+impl StagedBytes {
+    pub fn map(from_file: &std::fs::File) -> Result<Self> {
+        // SAFETY: do not touch that file while it's opened please
+        unsafe { memmap2::Mmap::map(from_file) }
+            .context("creating file")
+            .tap_ok(try_optimize_memory_mapping)
+            .map(Self::Mapped)
+    }
+
+    /// files streamed into memory are used before the ones staged on disk
+    pub fn open(path: &Path, streamed_bsa_files: &StreamedBsaFiles) -> Result<Self> {
+        match streamed_bsa_files.get(path) {
+            Some(bytes) => Ok(Self::Streamed(bytes)),
+            None => path
+                .open_file_read()
+                .and_then(|(path, file)| Self::map(&file).with_context(|| format!("loading file at [{path:?}]"))),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            StagedBytes::Mapped(mmap) => &mmap[..],
+            StagedBytes::Streamed(bytes) => bytes,
+        }
+    }
+}
+
 impl CreateBSAHandler {
     #[tracing::instrument(skip(create_bsa_directive), level = "INFO")]
     pub async fn handle(self, create_bsa_directive: CreateBSADirective) -> Result<u64> {
-        let Self { output_directory } = self;
+        let Self {
+            output_directory,
+            compression,
+            streamed_bsa_files,
+        } = self;
         let size = create_bsa_directive.size();
         let span = tracing::Span::current();
         spawn_rayon(move || {
             span.in_scope(|| {
                 let bsa_creation_dir = output_directory.join(BSA_CREATION_DIR.with(|p| p.to_owned()));
+                let temp_id_dir = bsa_creation_dir.join(match &create_bsa_directive {
+                    CreateBSADirective::Ba2(ba2) => &ba2.temp_id,
+                    CreateBSADirective::Bsa(bsa) => &bsa.temp_id,
+                });
                 match create_bsa_directive {
-                    CreateBSADirective::Ba2(ba2) => self::fallout_4::create_archive(bsa_creation_dir, ba2, |archive, options, output_path| {
-                        output_directory
-                            .join(output_path.into_path())
-                            .open_file_write()
-                            .context("opening file for writing")
-                            .and_then(|(output_path, output)| {
-                                archive
-                                    .write(&mut tracing::Span::current().wrap_write(size, output), &options)
-                                    .with_context(|| format!("writing ba2 (fallout 4 / starfield) file to {output_path:?}"))
-                            })
-                    }),
-                    CreateBSADirective::Bsa(bsa) => self::tes_4::create_archive(bsa_creation_dir, bsa, |archive, options, output_path| {
-                        output_directory
-                            .join(output_path.into_path())
-                            .open_file_write()
-                            .context("opening file for writing")
-                            .and_then(|(output_path, output)| {
-                                archive
-                                    .write(&mut tracing::Span::current().wrap_write(size, output), &options)
-                                    .with_context(|| format!("writing bsa file (skyrim and before) to {output_path:?}"))
-                            })
-                    }),
+                    CreateBSADirective::Ba2(ba2) => {
+                        self::fallout_4::create_archive(bsa_creation_dir, ba2, compression, &streamed_bsa_files, |archive, options, output_path| {
+                            output_directory
+                                .join(output_path.into_path())
+                                .open_file_write()
+                                .context("opening file for writing")
+                                .and_then(|(output_path, output)| {
+                                    archive
+                                        .write(&mut tracing::Span::current().wrap_write(size, output), &options)
+                                        .with_context(|| format!("writing ba2 (fallout 4 / starfield) file to {output_path:?}"))
+                                })
+                        })
+                    }
+                    CreateBSADirective::Bsa(bsa) => {
+                        self::tes_4::create_archive(bsa_creation_dir, bsa, compression, &streamed_bsa_files, |archive, options, output_path| {
+                            output_directory
+                                .join(output_path.into_path())
+                                .open_file_write()
+                                .context("opening file for writing")
+                                .and_then(|(output_path, output)| {
+                                    archive
+                                        .write(&mut tracing::Span::current().wrap_write(size, output), &options)
+                                        .with_context(|| format!("writing bsa file (skyrim and before) to {output_path:?}"))
+                                })
+                        })
+                    }
                 }
+                .tap(|_| streamed_bsa_files.remove_staged(&temp_id_dir))
             })
         })
         .instrument(tracing::Span::current())
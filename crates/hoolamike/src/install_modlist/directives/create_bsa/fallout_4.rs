@@ -1,6 +1,7 @@
 use {
-    super::{count_progress_style, try_optimize_memory_mapping, PathReadWrite},
+    super::{count_progress_style, streamed_files::StreamedBsaFiles, StagedBytes},
     crate::{
+        config_file::{Ba2CompressionFormat, Ba2CompressionLevel, BsaCompressionConfig},
         modlist_json::{
             directive::create_bsa_directive::ba2::{BA2DX10Entry, BA2FileEntry, Ba2, DirectiveStateData, FileState},
             type_guard::WithTypeGuard,
@@ -14,6 +15,7 @@ use {
             Archive,
             ArchiveKey,
             ArchiveOptions,
+            Chunk,
             ChunkCompressionOptions,
             CompressionFormat,
             CompressionLevel,
@@ -25,11 +27,16 @@ use {
         },
         BString,
         Borrowed,
+        CompressableFrom,
         CompressionResult,
         ReaderWithOptions,
     },
+    itertools::Itertools,
     rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator},
-    std::path::{Path, PathBuf},
+    std::{
+        io::Write,
+        path::{Path, PathBuf},
+    },
     tap::prelude::*,
     tracing::{info_span, instrument},
     tracing_indicatif::span_ext::IndicatifSpanExt,
@@ -43,28 +50,176 @@ enum LazyArchiveKind {
 }
 
 impl LazyArchiveKind {
+    /// the file is read uncompressed, see [LazyArchiveKind::compresses_chunk]
     fn as_archive_file(&self) -> Result<File<'_>> {
         match self {
             LazyArchiveKind::File(i) => i.as_archive_file(),
             LazyArchiveKind::DX10(i) => i.as_archive_file(),
         }
     }
+
+    /// whether the modlist has this chunk compressed
+    fn compresses_chunk(&self, index: usize) -> bool {
+        match self {
+            LazyArchiveKind::File(i) => i.directive.compressed,
+            LazyArchiveKind::DX10(i) => i
+                .directive
+                .chunks
+                .get(index)
+                .is_some_and(|BA2DX10EntryChunk { compressed, .. }| *compressed),
+        }
+    }
+}
+
+/// large texture archives have a few huge files, so chunks (and not files) are spread over the rayon pool,
+/// this bounds the uncompressed bytes compressed at once, their compressed versions are spilled to disk after every batch
+const MAX_IN_FLIGHT_CHUNK_BYTES: usize = 512 * 1024 * 1024;
+
+/// where a compressed chunk ended up in the spill file
+#[derive(Debug, Clone, Copy)]
+struct SpilledChunk {
+    offset: usize,
+    len: usize,
+    decompressed_len: usize,
+}
+
+/// compressed chunks are written to a temporary file as soon as their batch is done and mapped back in once the archive is written,
+/// so the compressed bytes of a whole archive are never held in memory at once
+struct SpilledChunks {
+    mapped: memmap2::Mmap,
+    /// removed along with the mapping
+    _spill: tempfile::NamedTempFile,
+    /// `chunks[entry][chunk index]`, chunks which stay uncompressed are `None`
+    chunks: Vec<Vec<Option<SpilledChunk>>>,
+}
+
+impl SpilledChunks {
+    /// swaps the chunks of the file for their compressed versions
+    fn restore<'a>(&'a self, entry: usize, mut file: File<'a>) -> Result<File<'a>> {
+        self.chunks
+            .get(entry)
+            .context("no spilled chunks for entry")?
+            .iter()
+            .zip(file.iter_mut())
+            .try_for_each(|(spilled, chunk)| {
+                let Some(SpilledChunk { offset, len, decompressed_len }) = *spilled else {
+                    return Ok(());
+                };
+                self.mapped
+                    .get(offset..offset + len)
+                    .context("spilled chunk is out of bounds")
+                    .map(|bytes| {
+                        let mut compressed = Chunk::from_compressed(bytes, decompressed_len);
+                        compressed.mips = chunk.mips.clone();
+                        *chunk = compressed;
+                    })
+            })
+            .map(|_| file)
+    }
+}
+
+fn compress_chunks(entries: &[(ArchiveKey<'_>, LazyArchiveKind)], options: &ChunkCompressionOptions) -> Result<SpilledChunks> {
+    let files = entries
+        .par_iter()
+        .map(|(_, lazy)| lazy.as_archive_file())
+        .collect::<Result<Vec<_>>>()?;
+    let chunks = files
+        .iter()
+        .zip(entries)
+        .enumerate()
+        .flat_map(|(entry, (file, (_, lazy)))| {
+            file.iter()
+                .enumerate()
+                .filter(move |(index, _)| lazy.compresses_chunk(*index))
+                .map(move |(index, chunk)| (entry, index, chunk))
+        })
+        .collect_vec();
+    let compressing_chunks = info_span!("compressing_chunks").entered().tap(|pb| {
+        pb.pb_set_style(&count_progress_style());
+        pb.pb_set_length(chunks.len() as _);
+    });
+    let mut spilled = files
+        .iter()
+        .map(|file| file.iter().map(|_| None).collect_vec())
+        .collect_vec();
+    let mut spill = crate::utils::scoped_temp_file()
+        .context("creating spill file for compressed chunks")?
+        .pipe(std::io::BufWriter::new);
+    let mut offset = 0;
+    crate::utils::chunk_while(chunks, |batch| {
+        batch.iter().map(|(_, _, chunk)| chunk.len()).sum::<usize>() >= MAX_IN_FLIGHT_CHUNK_BYTES
+    })
+    .into_iter()
+    .try_for_each(|batch| {
+        batch
+            .into_par_iter()
+            .map(|(entry, index, chunk)| {
+                chunk
+                    .compress(options)
+                    .context("compressing chunk")
+                    .map(|compressed| (entry, index, compressed))
+                    .tap_ok(|_| compressing_chunks.pb_inc(1))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .try_for_each(|(entry, index, compressed)| {
+                let bytes = compressed.as_bytes();
+                let decompressed_len = compressed
+                    .decompressed_len()
+                    .context("chunk did not get compressed")?;
+                spill
+                    .write_all(bytes)
+                    .context("spilling compressed chunk")?;
+                spilled[entry][index] = Some(SpilledChunk {
+                    offset,
+                    len: bytes.len(),
+                    decompressed_len,
+                });
+                offset += bytes.len();
+                Ok(())
+            })
+    })?;
+    spill
+        .into_inner()
+        .context("flushing spilled chunks")
+        .and_then(|spill| {
+            // SAFETY: the spill file is private to this function and never written to again
+            unsafe { memmap2::Mmap::map(spill.as_file()) }
+                .context("mapping spilled chunks")
+                .map(|mapped| SpilledChunks {
+                    mapped,
+                    _spill: spill,
+                    chunks: spilled,
+                })
+        })
+}
+
+fn compression_format(format: Ba2CompressionFormat) -> CompressionFormat {
+    match format {
+        Ba2CompressionFormat::Zip => CompressionFormat::Zip,
+        Ba2CompressionFormat::Lz4 => CompressionFormat::LZ4,
+    }
+}
+
+fn compression_level(level: Ba2CompressionLevel) -> CompressionLevel {
+    match level {
+        Ba2CompressionLevel::Fallout4 => CompressionLevel::FO4,
+        Ba2CompressionLevel::Fallout4Xbox => CompressionLevel::FO4Xbox,
+        Ba2CompressionLevel::Starfield => CompressionLevel::SF,
+    }
 }
 
 pub(super) struct LazyArchiveFile<Directive> {
-    file: memmap2::Mmap,
+    file: StagedBytes,
     directive: Directive,
 }
 
 impl<Directive> LazyArchiveFile<Directive> {
-    pub fn new(from_file: &std::fs::File, directive: Directive) -> Result<Self> {
-        unsafe { memmap2::Mmap::map(from_file) }
-            .context("creating file")
-            .tap_ok(try_optimize_memory_mapping)
-            .map(|file| Self { file, directive })
+    pub fn new(file: StagedBytes, directive: Directive) -> Self {
+        Self { file, directive }
     }
     fn as_bytes(&self) -> &[u8] {
-        &self.file[..]
+        self.file.as_bytes()
     }
 }
 
@@ -74,13 +229,7 @@ impl LazyArchiveFile<BA2FileEntry> {
             Borrowed(self.as_bytes()),
             &FileReadOptions::builder()
                 .format(Format::GNRL)
-                .compression_format(CompressionFormat::Zip)
-                .compression_level(CompressionLevel::FO4)
-                .compression_result(if self.directive.compressed {
-                    CompressionResult::Compressed
-                } else {
-                    CompressionResult::Decompressed
-                })
+                .compression_result(CompressionResult::Decompressed)
                 .build(),
         )
         .context("reading file using memory mapping")
@@ -99,25 +248,6 @@ impl LazyArchiveFile<BA2DX10Entry> {
         )
         .context("reading file using memory mapping")
         .context("building bsa archive file")
-        .and_then(|mut file| {
-            let res = file
-                .iter_mut()
-                .zip(&self.directive.chunks)
-                .try_for_each(|(chunk, BA2DX10EntryChunk { compressed, .. })| {
-                    if *compressed {
-                        *chunk = chunk
-                            .compress(
-                                &ChunkCompressionOptions::builder()
-                                    .compression_format(CompressionFormat::Zip)
-                                    .compression_level(CompressionLevel::FO4)
-                                    .build(),
-                            )
-                            .context("compressing chunk")?
-                    }
-                    Ok(())
-                });
-            res.map(move |_| file)
-        })
     }
 }
 
@@ -162,6 +292,8 @@ pub fn create_archive<F: FnOnce(&Archive<'_>, ArchiveOptions, MaybeWindowsPath)
                 ..
             },
     }: Ba2,
+    compression: BsaCompressionConfig,
+    streamed_bsa_files: &StreamedBsaFiles,
     handle_archive: F,
 ) -> Result<()> {
     let version: ArchiveVersion = match version {
@@ -184,22 +316,29 @@ pub fn create_archive<F: FnOnce(&Archive<'_>, ArchiveOptions, MaybeWindowsPath)
         .map(move |file_state| match file_state {
             FileState::BA2File(ba2_file_entry) => temp_id_dir
                 .join(ba2_file_entry.path.clone().into_path())
-                .pipe(|path| path.open_file_read())
-                .and_then(|(_path, file)| LazyArchiveFile::new(&file, ba2_file_entry.clone()).map(LazyArchiveKind::from))
+                .pipe(|path| StagedBytes::open(&path, streamed_bsa_files))
+                .map(|file| LazyArchiveFile::new(file, ba2_file_entry.clone()).pipe(LazyArchiveKind::from))
                 .and_then(|file| ba2_file_entry.pipe(|BA2FileEntry { path, .. }| create_key(path).map(|key| (key, file)))),
             FileState::BA2DX10Entry(ba2_dx10_entry) => temp_id_dir
                 .join(ba2_dx10_entry.path.clone().into_path())
-                .open_file_read()
-                .and_then(|(path, file)| {
-                    LazyArchiveFile::new(&file, ba2_dx10_entry.clone())
-                        .with_context(|| format!("opening file at [{path:?}]"))
-                        .map(LazyArchiveKind::from)
-                })
+                .pipe(|path| StagedBytes::open(&path, streamed_bsa_files))
+                .map(|file| LazyArchiveFile::new(file, ba2_dx10_entry.clone()).pipe(LazyArchiveKind::from))
                 .and_then(|file| ba2_dx10_entry.pipe(|BA2DX10Entry { path, .. }| create_key(path).map(|key| (key, file)))),
         })
         .inspect(|_| reading_bsa_entries.pb_inc(1))
         .collect::<Result<Vec<_>>>()
         .and_then(|entries| {
+            let spilled = match compression.uncompressed {
+                true => None,
+                false => compress_chunks(
+                    &entries,
+                    &ChunkCompressionOptions::builder()
+                        .compression_format(compression_format(compression.format))
+                        .compression_level(compression_level(compression.level))
+                        .build(),
+                )
+                .map(Some)?,
+            };
             let building_archive = info_span!("building_archive").entered().tap(|pb| {
                 pb.pb_set_style(&count_progress_style());
                 pb.pb_set_length(entries.len() as _);
@@ -207,11 +346,17 @@ pub fn create_archive<F: FnOnce(&Archive<'_>, ArchiveOptions, MaybeWindowsPath)
             entries.pipe_ref(|entries| {
                 entries
                     .par_iter()
-                    .map(|(key, file)| {
-                        file.as_archive_file().map(|file| {
-                            building_archive.pb_inc(1);
-                            (key, file)
-                        })
+                    .enumerate()
+                    .map(|(entry, (key, lazy))| {
+                        lazy.as_archive_file()
+                            .and_then(|file| match spilled.as_ref() {
+                                Some(spilled) => spilled.restore(entry, file),
+                                None => Ok(file),
+                            })
+                            .map(|file| {
+                                building_archive.pb_inc(1);
+                                (key, file)
+                            })
                     })
                     .collect::<Result<Vec<_>>>()
                     .and_then(|entries| {
@@ -223,7 +368,11 @@ pub fn create_archive<F: FnOnce(&Archive<'_>, ArchiveOptions, MaybeWindowsPath)
                                 FileHeader::GNMF(_) => Format::GNMF,
                             })
                             .unwrap_or_default()
-                            .pipe(|format| ArchiveOptions::builder().format(format))
+                            .pipe(|format| {
+                                ArchiveOptions::builder()
+                                    .format(format)
+                                    .compression_format(compression_format(compression.format))
+                            })
                             .pipe(|options| {
                                 entries
                                     .into_iter()
@@ -0,0 +1,104 @@
+//! texture archives take 10+ GB once their files are staged in `TEMP_BSA_FILES`, with `installation.stream_bsa_files_gigabytes`
+//! files taken from archives as they are go from the source archive straight into memory and from there into the archive builder
+use {
+    parking_lot::Mutex,
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+    },
+};
+
+#[derive(Debug, Default)]
+pub struct StreamedBsaFiles {
+    /// bytes kept in memory at most, whatever does not fit is staged on disk as usual
+    budget: u64,
+    used: AtomicU64,
+    /// keyed by the path the file would have been staged at
+    files: Mutex<BTreeMap<PathBuf, Arc<[u8]>>>,
+}
+
+impl StreamedBsaFiles {
+    pub fn new(gigabytes: Option<u64>) -> Self {
+        Self {
+            budget: gigabytes.unwrap_or(0) * 1024 * 1024 * 1024,
+            ..Default::default()
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.budget > 0
+    }
+
+    /// `false` when the file has to be staged on disk instead
+    pub fn try_reserve(&self, size: u64) -> bool {
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(size).filter(|used| *used <= self.budget)
+            })
+            .is_ok()
+    }
+
+    /// gives back what [StreamedBsaFiles::try_reserve] took when the file could not be read
+    pub fn release(&self, size: u64) {
+        self.used.fetch_sub(size, Ordering::SeqCst);
+    }
+
+    pub fn insert(&self, path: PathBuf, bytes: Vec<u8>) {
+        self.files.lock().insert(path, bytes.into());
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Arc<[u8]>> {
+        self.files.lock().get(path).cloned()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.lock().contains_key(path)
+    }
+
+    /// once an archive is written nothing needs the files staged for it anymore
+    pub fn remove_staged(&self, temp_id_dir: &Path) {
+        let mut freed = 0;
+        self.files
+            .lock()
+            .retain(|path, bytes| match path.starts_with(temp_id_dir) {
+                true => {
+                    freed += bytes.len() as u64;
+                    false
+                }
+                false => true,
+            });
+        self.release(freed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_is_given_back_with_the_archive() {
+        let streamed = StreamedBsaFiles::new(Some(1));
+        let gigabyte = 1024 * 1024 * 1024;
+        assert!(streamed.try_reserve(gigabyte - 4));
+        assert!(!streamed.try_reserve(5));
+        assert!(streamed.try_reserve(4));
+        streamed.release(gigabyte - 8);
+
+        streamed.insert(PathBuf::from("/out/TEMP_BSA_FILES/abc/textures/a.dds"), vec![0; 4]);
+        streamed.insert(PathBuf::from("/out/TEMP_BSA_FILES/abcd/textures/b.dds"), vec![0; 4]);
+        assert!(streamed.contains(Path::new("/out/TEMP_BSA_FILES/abc/textures/a.dds")));
+        streamed.remove_staged(Path::new("/out/TEMP_BSA_FILES/abc"));
+        assert!(streamed
+            .get(Path::new("/out/TEMP_BSA_FILES/abc/textures/a.dds"))
+            .is_none());
+        assert!(streamed
+            .get(Path::new("/out/TEMP_BSA_FILES/abcd/textures/b.dds"))
+            .is_some());
+        assert!(streamed.try_reserve(gigabyte - 4));
+        assert!(!streamed.try_reserve(1));
+    }
+}
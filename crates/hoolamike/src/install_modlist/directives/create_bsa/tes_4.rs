@@ -1,6 +1,7 @@
 use {
-    super::{count_progress_style, PathReadWrite},
+    super::{count_progress_style, streamed_files::StreamedBsaFiles, StagedBytes},
     crate::{
+        config_file::BsaCompressionConfig,
         modlist_json::{
             directive::create_bsa_directive::bsa::{self, Bsa, DirectiveStateData, FileStateData},
             type_guard::WithTypeGuard,
@@ -23,22 +24,21 @@ use {
 
 #[derive(Debug)]
 pub struct LazyArchiveFile<Directive> {
-    file: memmap2::Mmap,
+    file: StagedBytes,
     directive: Directive,
 }
 
 impl<Directive: std::fmt::Debug> LazyArchiveFile<Directive> {
     #[instrument]
     pub fn new(from_file: &std::fs::File, directive: Directive) -> Result<Self> {
-        // SAFETY: do not touch that file while it's opened please
         debug!("creating file handle");
-        unsafe { memmap2::Mmap::map(from_file) }
-            .context("creating file")
-            .tap_ok(super::try_optimize_memory_mapping)
-            .map(|file| Self { file, directive })
+        StagedBytes::map(from_file).map(|file| Self::staged(file, directive))
+    }
+    pub fn staged(file: StagedBytes, directive: Directive) -> Self {
+        Self { file, directive }
     }
     fn as_bytes(&self) -> &[u8] {
-        &self.file[..]
+        self.file.as_bytes()
     }
 }
 
@@ -138,6 +138,8 @@ pub fn create_archive<F: FnOnce(&Archive<'_>, ArchiveOptions, MaybeWindowsPath)
                 ..
             },
     }: Bsa,
+    compression: BsaCompressionConfig,
+    streamed_bsa_files: &StreamedBsaFiles,
     handle_archive: F,
 ) -> Result<()> {
     let version = match version {
@@ -146,7 +148,15 @@ pub fn create_archive<F: FnOnce(&Archive<'_>, ArchiveOptions, MaybeWindowsPath)
         105 => Version::v105,
         other => anyhow::bail!("unsuppored version: {other}"),
     };
-    let archive_flags = ArchiveFlags::from_bits(archive_flags).with_context(|| format!("invalid flags: {archive_flags:b}"))?;
+    let archive_flags = ArchiveFlags::from_bits(archive_flags)
+        .with_context(|| format!("invalid flags: {archive_flags:b}"))?
+        .pipe(|flags| match compression.uncompressed {
+            true => flags - ArchiveFlags::COMPRESSED,
+            false => flags,
+        });
+    let compression_result = compression
+        .uncompressed
+        .then_some(CompressionResult::Decompressed);
     let archive_types = {
         let file_flags = match file_flags {
             bsa::Either::Left(normal) => normal,
@@ -171,8 +181,8 @@ pub fn create_archive<F: FnOnce(&Archive<'_>, ArchiveOptions, MaybeWindowsPath)
             info_span!("handle_file_state", ?file_state_data).in_scope(|| {
                 temp_id_dir
                     .join(file_state_data.path.clone().into_path())
-                    .pipe(|path| path.open_file_read())
-                    .and_then(|(path, file)| LazyArchiveFile::new(&file, file_state_data.clone()).with_context(|| format!("loading file at [{path:?}]")))
+                    .pipe(|path| StagedBytes::open(&path, streamed_bsa_files))
+                    .map(|file| LazyArchiveFile::staged(file, file_state_data.clone()))
                     .and_then(|file| create_key(file_state_data.path).map(|key| (key, file)))
             })
         })
@@ -187,10 +197,11 @@ pub fn create_archive<F: FnOnce(&Archive<'_>, ArchiveOptions, MaybeWindowsPath)
                 entries
                     .par_iter()
                     .map(|(key, file)| {
-                        file.as_archive_file(version, None).map(|file| {
-                            building_archive.pb_inc(1);
-                            (key, file)
-                        })
+                        file.as_archive_file(version, compression_result)
+                            .map(|file| {
+                                building_archive.pb_inc(1);
+                                (key, file)
+                            })
                     })
                     .collect::<Result<Vec<_>>>()
                     .and_then(|entries| {
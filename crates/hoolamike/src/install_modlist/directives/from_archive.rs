@@ -1,17 +1,16 @@
 use {
     super::*,
     crate::{
-        install_modlist::download_cache::{to_u64_from_base_64, validate_file_size, validate_hash},
+        install_modlist::download_cache::to_u64_from_base_64,
         modlist_json::directive::FromArchiveDirective,
         progress_bars_v2::IndicatifWrapIoExt,
         read_wrappers::ReadExt,
         utils::spawn_rayon,
     },
+    copy_strategy::{link_file, warn_fallback},
+    create_bsa::streamed_files::StreamedBsaFiles,
     preheat_archive_hash_paths::PreheatedArchiveHashPaths,
-    std::{
-        io::{Read, Write},
-        path::Path,
-    },
+    std::io::{Read, Write},
     tracing::{info_span, Instrument},
 };
 
@@ -21,28 +20,9 @@ pub struct FromArchiveHandler {
     pub output_directory: PathBuf,
     #[derivative(Debug = "ignore")]
     pub download_summary: DownloadSummary,
-}
-
-const EXTENSION_HASH_WHITELIST: &[&str] = &[
-    // hashes won't match because headers are also hashed in wabbajack
-    "dds",
-];
-
-fn is_whitelisted_by_path(path: &Path) -> bool {
-    matches!(
-        path
-            .extension()
-            .map(|e| e.to_string_lossy().to_lowercase())
-            .as_deref(),
-        Some(ext) if EXTENSION_HASH_WHITELIST.contains(&ext)
-    )
-}
-
-pub async fn validate_hash_with_overrides(path: PathBuf, hash: String, size: u64) -> Result<PathBuf> {
-    match is_whitelisted_by_path(&path) {
-        true => validate_file_size(path, size).await,
-        false => validate_hash(path, hash).await,
-    }
+    pub copy_strategy: CopyStrategy,
+    #[derivative(Debug = "ignore")]
+    pub streamed_bsa_files: Arc<StreamedBsaFiles>,
 }
 
 impl FromArchiveHandler {
@@ -62,7 +42,16 @@ impl FromArchiveHandler {
             .resolve_archive_path(&archive_hash_path)
             .and_then(|path| preheated.get_archive(path))
             .with_context(|| format!("reading archive for [{archive_hash_path:?}]"))?;
+        let streamed_bsa_files = self.streamed_bsa_files.clone();
+        let streamed = crate::install_modlist::output_filter::staged_for_bsa(&to.clone().into_path()).is_some() && streamed_bsa_files.try_reserve(size);
         let output_path = self.output_directory.join(to.into_path());
+        // files extracted from archives carry the modification time from the archive, downloads themselves do not
+        let preserve_modified_time = matches!(&*source_file, queued_archive_task::SourceKind::CachedPath(_));
+        let copy_strategy = match (&*source_file, self.copy_strategy) {
+            // a download hard linked into the installation would change along with the installed file (and lose its cached hash)
+            (queued_archive_task::SourceKind::JustPath(_), CopyStrategy::Hardlink) => CopyStrategy::Copy,
+            (_, strategy) => strategy,
+        };
 
         spawn_rayon(move || -> Result<_> {
             let perform_copy = move |from: &mut dyn Read, to: &mut dyn Write, target_path: PathBuf| {
@@ -90,15 +79,44 @@ impl FromArchiveHandler {
             source_file
                 .open_file_read()
                 .and_then(|(source_path, mut final_source)| {
-                    create_file_all(&output_path).and_then(|mut output_file| {
-                        perform_copy(&mut final_source, &mut output_file, output_path.clone()).with_context(|| {
-                            format!(
-                                "when extracting from [{source_path:?}] ({:?}) to [{}]",
-                                archive_hash_path,
-                                output_path.display()
-                            )
-                        })
+                    if streamed {
+                        return Vec::with_capacity(size as usize)
+                            .pipe(|mut bytes| perform_copy(&mut final_source, &mut bytes, output_path.clone()).map(|_| bytes))
+                            .map(|bytes| streamed_bsa_files.insert(output_path.clone(), bytes))
+                            .with_context(|| format!("when streaming [{source_path:?}] ({:?}) into memory", archive_hash_path));
+                    }
+                    let linked = match copy_strategy {
+                        CopyStrategy::Copy => false,
+                        strategy => link_file(strategy, &source_path, &output_path)
+                            .map_err(|reason| warn_fallback(strategy, &reason))
+                            .is_ok(),
+                    };
+                    match linked {
+                        // the source is still read to validate it, nothing gets written though
+                        true => perform_copy(&mut final_source, &mut std::io::sink(), output_path.clone()).inspect_err(|_| {
+                            std::fs::remove_file(&output_path).ok();
+                        }),
+                        false => {
+                            create_file_all(&output_path).and_then(|mut output_file| perform_copy(&mut final_source, &mut output_file, output_path.clone()))
+                        }
+                    }
+                    .with_context(|| {
+                        format!(
+                            "when extracting from [{source_path:?}] ({:?}) to [{}]",
+                            archive_hash_path,
+                            output_path.display()
+                        )
                     })
+                    .map(|_| {
+                        if preserve_modified_time {
+                            crate::compression::preserve_modified_time(&output_path, final_source.metadata().and_then(|m| m.modified()).ok())
+                        }
+                    })
+                })
+                .inspect_err(|_| {
+                    if streamed {
+                        streamed_bsa_files.release(size)
+                    }
                 })?;
             Ok(())
         })
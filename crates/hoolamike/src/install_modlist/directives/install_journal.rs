@@ -0,0 +1,77 @@
+//! remembers which directives are already installed, so that a restarted installation does not hash the whole output directory again
+use {
+    crate::{install_modlist::download_cache::hash_cache::fingerprint, json_store::JsonStore},
+    anyhow::Result,
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeMap, path::Path},
+};
+
+pub const INSTALL_JOURNAL_FILE_NAME: &str = ".hoolamike-install-journal.json";
+
+/// an entry is only trusted as long as the output file has the same size and modification time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledFile {
+    pub size: u64,
+    /// nanoseconds since unix epoch
+    pub modified: u128,
+}
+
+/// keyed by [`crate::modlist_json::Directive::directive_hash`], a changed directive is a different entry
+#[derive(Debug)]
+pub struct InstallJournal {
+    entries: JsonStore<BTreeMap<String, InstalledFile>>,
+}
+
+impl InstallJournal {
+    pub fn load(output_directory: &Path) -> Self {
+        Self {
+            entries: JsonStore::load(output_directory.join(INSTALL_JOURNAL_FILE_NAME), "install journal"),
+        }
+    }
+
+    /// `output` is the file the directive produced, it has to be the one which was recorded
+    pub fn is_installed(&self, directive_hash: &str, output: &Path) -> bool {
+        let Some(recorded) = self
+            .entries
+            .read(|entries| entries.get(directive_hash).copied())
+        else {
+            return false;
+        };
+        fingerprint(output).is_ok_and(|(size, modified)| recorded == InstalledFile { size, modified })
+    }
+
+    pub fn insert(&self, directive_hash: String, output: &Path) -> Result<()> {
+        let (size, modified) = fingerprint(output)?;
+        self.entries.update(|entries| {
+            entries.insert(directive_hash, InstalledFile { size, modified });
+        })
+    }
+
+    /// the journal is an optimization, failing to update it is not worth failing the installation over
+    pub fn record(&self, directive_hash: String, output: &Path) {
+        self.insert(directive_hash, output)
+            .unwrap_or_else(|reason| tracing::warn!(output=%output.display(), ?reason, "could not update the install journal"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_survives_reload_and_forgets_changed_files() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let output = directory.path().join("meshes/armor.nif");
+        std::fs::create_dir_all(output.parent().unwrap())?;
+        std::fs::write(&output, b"original")?;
+
+        InstallJournal::load(directory.path()).insert("directive".into(), &output)?;
+        let journal = InstallJournal::load(directory.path());
+        assert!(journal.is_installed("directive", &output));
+        assert!(!journal.is_installed("other-directive", &output));
+
+        std::fs::write(&output, b"modified, and longer")?;
+        assert!(!journal.is_installed("directive", &output));
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use {
     super::{
         preheat_archive_hash_paths::PreheatedArchiveHashPaths,
+        scheduling::{scheduled, TaskKind},
         ArchivePathDirective,
         DirectivesHandler,
         DownloadSummary,
@@ -8,6 +9,7 @@ use {
         ResolvePathExt,
         StreamTryFlatMapExt,
     },
+    crate::shutdown::until_stopped,
     anyhow::{Context, Result},
     futures::{FutureExt, Stream, StreamExt, TryFutureExt},
     std::{future::ready, sync::Arc},
@@ -24,6 +26,7 @@ pub(crate) fn handle_nested_archive_directives(
 ) -> impl Stream<Item = Result<u64>> {
     let preheat_task = {
         let preheat_directives = info_span!("preheat_directives");
+        let extraction_budget = manager.extraction_budget.clone();
         directives
             .iter()
             .map(|d| d.archive_path())
@@ -31,9 +34,11 @@ pub(crate) fn handle_nested_archive_directives(
             .collect::<Result<Vec<_>>>()
             .pipe(ready)
             .and_then(|paths| {
-                tokio::task::spawn_blocking(move || preheat_directives.in_scope(|| PreheatedArchiveHashPaths::preheat_archive_hash_paths(paths)))
-                    .map_context("thread crashed")
-                    .and_then(ready)
+                tokio::task::spawn_blocking(move || {
+                    preheat_directives.in_scope(|| PreheatedArchiveHashPaths::preheat_archive_hash_paths(paths, &extraction_budget))
+                })
+                .map_context("thread crashed")
+                .and_then(ready)
             })
     };
     let handle_directives = info_span!("handle_directives");
@@ -43,28 +48,40 @@ pub(crate) fn handle_nested_archive_directives(
         .try_flat_map(move |preheated| {
             directives
                 .pipe(futures::stream::iter)
-                .map(move |directive| match directive {
-                    ArchivePathDirective::TransformedTexture(transformed_texture) => manager
-                        .transformed_texture
-                        .clone()
-                        .handle(transformed_texture.clone(), preheated.clone())
-                        .instrument(handle_directives.clone())
-                        .map(move |res| res.with_context(|| format!("handling directive: {transformed_texture:#?}")))
-                        .boxed(),
-                    ArchivePathDirective::FromArchive(from_archive) => manager
-                        .from_archive
-                        .clone()
-                        .handle(from_archive.clone(), preheated.clone())
-                        .instrument(handle_directives.clone())
-                        .map(move |res| res.with_context(|| format!("handling directive: {from_archive:#?}")))
-                        .boxed(),
-                    ArchivePathDirective::PatchedFromArchive(patched_from_archive_directive) => manager
-                        .patched_from_archive
-                        .clone()
-                        .handle(patched_from_archive_directive.clone(), preheated.clone())
-                        .instrument(handle_directives.clone())
-                        .map(move |res| res.with_context(|| format!("handling directive: {patched_from_archive_directive:#?}")))
-                        .boxed(),
+                .pipe(until_stopped)
+                .map(move |directive| {
+                    let installed = manager.installed_output(&directive.clone().into());
+                    match directive {
+                        ArchivePathDirective::TransformedTexture(transformed_texture) => manager
+                            .transformed_texture
+                            .clone()
+                            .handle(transformed_texture.clone(), preheated.clone())
+                            .instrument(handle_directives.clone())
+                            .map(move |res| res.with_context(|| format!("handling directive: {transformed_texture:#?}")))
+                            .pipe(|task| scheduled(TaskKind::Cpu, task))
+                            .boxed(),
+                        ArchivePathDirective::FromArchive(from_archive) => manager
+                            .from_archive
+                            .clone()
+                            .handle(from_archive.clone(), preheated.clone())
+                            .instrument(handle_directives.clone())
+                            .map(move |res| res.with_context(|| format!("handling directive: {from_archive:#?}")))
+                            .pipe(|task| scheduled(TaskKind::Io, task))
+                            .boxed(),
+                        ArchivePathDirective::PatchedFromArchive(patched_from_archive_directive) => manager
+                            .patched_from_archive
+                            .clone()
+                            .handle(patched_from_archive_directive.clone(), preheated.clone())
+                            .instrument(handle_directives.clone())
+                            .map(move |res| res.with_context(|| format!("handling directive: {patched_from_archive_directive:#?}")))
+                            // patches are streamed through bounded buffers, they wait on the disks far more than on the cpu
+                            .pipe(|task| scheduled(TaskKind::Io, task))
+                            .boxed(),
+                    }
+                    .inspect_ok({
+                        let manager = manager.clone();
+                        move |_| manager.record_installed(&installed)
+                    })
                 })
                 .buffer_unordered(concurrency)
         })
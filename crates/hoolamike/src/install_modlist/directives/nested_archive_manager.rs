@@ -1,13 +1,21 @@
 use {
-    super::concurrency,
-    crate::{downloaders::helpers::FutureAnyhowExt, modlist_json::directive::ArchiveHashPath},
+    super::{queued_archive_task::SourceKind, scheduling::scheduling},
+    crate::{downloaders::helpers::FutureAnyhowExt, modlist_json::directive::ArchiveHashPath, progress_bars_v2::io_progress_style},
     anyhow::Result,
     futures::TryFutureExt,
+    indexmap::IndexMap,
+    nonempty::NonEmpty,
     once_cell::sync::Lazy,
-    std::{future::ready, sync::Arc},
+    parking_lot::Mutex,
+    std::{
+        future::ready,
+        path::PathBuf,
+        sync::{Arc, Weak},
+    },
     tap::prelude::*,
     tokio::sync::{OwnedSemaphorePermit, Semaphore},
     tracing::{info_span, instrument, Instrument},
+    tracing_indicatif::span_ext::IndicatifSpanExt,
 };
 
 impl ArchiveHashPath {
@@ -20,7 +28,7 @@ impl ArchiveHashPath {
 }
 
 pub fn max_open_files() -> usize {
-    concurrency() * 40
+    scheduling().max_open_files
 }
 
 #[allow(dead_code)]
@@ -66,3 +74,215 @@ where
         .await
     }
 }
+
+#[derive(Debug, Clone)]
+struct CachedExtraction {
+    size: u64,
+    source: Arc<SourceKind>,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    /// every archive extracted out of another one, it takes up temp space for as long as a directive or the cache holds on to it
+    live: Vec<(Weak<SourceKind>, u64)>,
+    /// least recently used first
+    cached: IndexMap<NonEmpty<PathBuf>, CachedExtraction>,
+}
+
+impl BudgetState {
+    fn used(&mut self) -> u64 {
+        self.live.retain(|(source, _)| source.strong_count() > 0);
+        self.live.iter().map(|(_, size)| size).sum()
+    }
+
+    /// dropping a cached archive only frees its space once the directives still reading it are done
+    fn evict_until(&mut self, fits: impl Fn(u64) -> bool) {
+        while !fits(self.used()) {
+            match self.cached.shift_remove_index(0) {
+                Some((path, cached)) => tracing::debug!(?path, size = cached.size, "evicting extracted archive"),
+                None => break,
+            }
+        }
+    }
+}
+
+/// temp bytes held by archives extracted out of other archives. directives are handled in chunks no bigger than the budget,
+/// every chunk extracts the nested archives it needs and releases them once its directives are done - the room it leaves
+/// keeps the most recently used archives around for the next chunks. a chunk never waits for its own archives, so the
+/// budget can only be exceeded by a chunk whose archives are bigger than the budget alone
+#[derive(Debug)]
+pub struct ExtractionBudget {
+    max_bytes: u64,
+    state: Mutex<BudgetState>,
+    progress: tracing::Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractionBudgetUsage {
+    pub used: u64,
+    pub budget: u64,
+    pub archives: usize,
+    pub cached: usize,
+}
+
+impl std::fmt::Display for ExtractionBudgetUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            used,
+            budget,
+            archives,
+            cached,
+        } = self;
+        write!(
+            f,
+            "{}/{} in {archives} archives ({cached} cached)",
+            indicatif::HumanBytes(*used),
+            indicatif::HumanBytes(*budget)
+        )
+    }
+}
+
+impl ExtractionBudget {
+    pub fn new(gigabytes: u64) -> Self {
+        let max_bytes = gigabytes.saturating_mul(1024 * 1024 * 1024);
+        Self {
+            max_bytes,
+            state: Default::default(),
+            progress: info_span!("nested_archive_temp_files").tap(|pb| {
+                pb.pb_set_style(&io_progress_style());
+                pb.pb_set_length(max_bytes);
+                pb.in_scope(|| tracing::debug!(%max_bytes, "tracking temp files of nested archives"));
+            }),
+        }
+    }
+
+    /// directives are chunked by the size of their outputs, which roughly bounds the size of the archives they are extracted from
+    pub fn chunk_size(&self, default: u64) -> u64 {
+        default.min(self.max_bytes)
+    }
+
+    fn report(&self, state: &mut BudgetState) {
+        self.progress.pb_set_position(state.used());
+    }
+
+    /// cached archives are evicted until an extraction of `size` bytes fits next to the ones directives are reading
+    pub fn make_room(&self, size: u64) {
+        let mut state = self.state.lock();
+        state.evict_until(|used| used.saturating_add(size) <= self.max_bytes);
+        self.report(&mut state);
+    }
+
+    pub fn track(&self, size: u64, source: &Arc<SourceKind>) {
+        let mut state = self.state.lock();
+        state.live.push((Arc::downgrade(source), size));
+        state.evict_until(|used| used <= self.max_bytes);
+        self.report(&mut state);
+    }
+
+    /// marks the archive as the most recently used one
+    pub fn get(&self, path: &NonEmpty<PathBuf>) -> Option<Arc<SourceKind>> {
+        let mut state = self.state.lock();
+        state.cached.shift_remove(path).map(|cached| {
+            let source = cached.source.clone();
+            state.cached.insert(path.clone(), cached);
+            source
+        })
+    }
+
+    /// keeps a tracked archive the current chunk is done with, least recently used archives go first once the budget is exceeded
+    pub fn keep(&self, path: NonEmpty<PathBuf>, source: Arc<SourceKind>) {
+        let mut state = self.state.lock();
+        let Some(size) = state
+            .live
+            .iter()
+            .find(|(live, _)| std::ptr::eq(live.as_ptr(), Arc::as_ptr(&source)))
+            .map(|(_, size)| *size)
+        else {
+            return;
+        };
+        if size > self.max_bytes {
+            return;
+        }
+        state.cached.shift_remove(&path);
+        state.cached.insert(path, CachedExtraction { size, source });
+        state.evict_until(|used| used <= self.max_bytes);
+        self.report(&mut state);
+    }
+
+    pub fn usage(&self) -> ExtractionBudgetUsage {
+        let mut state = self.state.lock();
+        ExtractionBudgetUsage {
+            used: state.used(),
+            budget: self.max_bytes,
+            archives: state.live.len(),
+            cached: state.cached.len(),
+        }
+        .tap(|_| self.report(&mut state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(archive: &str) -> NonEmpty<PathBuf> {
+        NonEmpty::from((PathBuf::from("download.7z"), vec![PathBuf::from(archive)]))
+    }
+
+    fn source(archive: &str) -> Arc<SourceKind> {
+        Arc::new(SourceKind::JustPath(PathBuf::from(archive)))
+    }
+
+    const GIGABYTE: u64 = 1024 * 1024 * 1024;
+
+    #[test]
+    fn test_evicts_least_recently_used_archives() {
+        let budget = ExtractionBudget::new(1);
+        let (a, b, c) = (source("a.zip"), source("b.zip"), source("c.zip"));
+        budget.track(GIGABYTE / 2, &a);
+        budget.keep(path("a.zip"), a);
+        budget.track(GIGABYTE / 4, &b);
+        budget.keep(path("b.zip"), b);
+        assert!(budget.get(&path("a.zip")).is_some());
+        budget.track(GIGABYTE / 2, &c);
+        budget.keep(path("c.zip"), c);
+        assert!(budget.get(&path("b.zip")).is_none());
+        assert!(budget.get(&path("a.zip")).is_some());
+        assert_eq!(
+            budget.usage(),
+            ExtractionBudgetUsage {
+                used: GIGABYTE,
+                budget: GIGABYTE,
+                archives: 2,
+                cached: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_archives_in_use_count_against_the_budget() {
+        let budget = ExtractionBudget::new(1);
+        let cached = source("cached.zip");
+        budget.track(GIGABYTE / 2, &cached);
+        budget.keep(path("cached.zip"), cached);
+
+        // the directives of the current chunk are still reading it
+        let in_use = source("in_use.zip");
+        budget.make_room(GIGABYTE * 3 / 4);
+        assert!(budget.get(&path("cached.zip")).is_none());
+        budget.track(GIGABYTE * 3 / 4, &in_use);
+        assert_eq!(budget.usage().used, GIGABYTE * 3 / 4);
+
+        drop(in_use);
+        assert_eq!(
+            budget.usage(),
+            ExtractionBudgetUsage {
+                used: 0,
+                budget: GIGABYTE,
+                archives: 0,
+                cached: 0,
+            }
+        );
+        assert_eq!(budget.chunk_size(6 * GIGABYTE), GIGABYTE);
+    }
+}
@@ -1,7 +1,6 @@
 use {
     super::*,
     crate::{
-        compression::forward_only_seek::ForwardOnlySeek,
         install_modlist::download_cache::to_u64_from_base_64,
         modlist_json::directive::PatchedFromArchiveDirective,
         progress_bars_v2::IndicatifWrapIoExt,
@@ -9,7 +8,7 @@ use {
         utils::spawn_rayon,
     },
     preheat_archive_hash_paths::PreheatedArchiveHashPaths,
-    std::io::{Read, Seek, Write},
+    std::{fs::File, io::Write, path::Path},
     tracing::Instrument,
     wabbajack_file_handle::WabbajackFileHandle,
 };
@@ -48,17 +47,13 @@ impl PatchedFromArchiveHandler {
         spawn_rayon(move || -> Result<_> {
             let wabbajack_file = self.wabbajack_file.clone();
             #[tracing::instrument(skip(source, delta, target), level = "INFO")]
-            fn perform_copy<S, D, T>(source: S, delta: D, target: T, expected_size: u64, expected_hash: String) -> Result<()>
+            fn perform_copy<T>(source: (&Path, File), delta: (&Path, File), target: T, expected_size: u64, expected_hash: String) -> Result<()>
             where
-                S: Read + Seek,
-                D: Read,
                 T: Write,
             {
-                // this applies delta on the fly
-                let from = crate::octadiff_reader::ApplyDetla::new_from_readers(source, ForwardOnlySeek::new(delta))
-                    .context("invalid delta")?
-                    .context("delta is empty")?;
-                let mut writer = &mut std::io::BufWriter::new(target);
+                // octodiff and bsdiff deltas are applied on the fly
+                let from = crate::patching::apply_patch(source, delta)?;
+                let mut writer = &mut std::io::BufWriter::with_capacity(crate::patching::PATCH_BUFFER_SIZE, target);
                 std::io::copy(
                     &mut tracing::Span::current()
                         .wrap_read(expected_size, from)
@@ -70,7 +65,7 @@ impl PatchedFromArchiveHandler {
                 .and_then(|_| writer.flush().context("flushing"))
                 .map(|_| ())
             }
-            let (_guard, delta_file) = wabbajack_file
+            let (delta_path, delta_file) = wabbajack_file
                 .get_source_data(patch_id)
                 .and_then(|source_data| {
                     source_data
@@ -81,9 +76,9 @@ impl PatchedFromArchiveHandler {
 
             source_file
                 .open_file_read()
-                .and_then(|(final_source_path, mut final_source)| {
+                .and_then(|(final_source_path, final_source)| {
                     create_file_all(&output_path).and_then(|mut output_file| {
-                        perform_copy(&mut final_source, delta_file, &mut output_file, size, hash)
+                        perform_copy((&final_source_path, final_source), (&delta_path, delta_file), &mut output_file, size, hash)
                             .with_context(|| format!("when extracting from [{final_source_path:?}] to [{output_path:?}]"))
                             .with_context(|| format!("when handling [{archive_hash_path:?}] copy"))
                     })
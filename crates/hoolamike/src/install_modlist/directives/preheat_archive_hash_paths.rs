@@ -1,5 +1,5 @@
 use {
-    super::queued_archive_task::SourceKind,
+    super::{nested_archive_manager::ExtractionBudget, queued_archive_task::SourceKind},
     crate::{
         compression::{ArchiveHandleKind, ProcessArchive, SeekWithTempFileExt},
         install_modlist::directives::IteratorTryFlatMapExt,
@@ -38,8 +38,10 @@ impl PreheatedArchiveHashPaths {
                 .with_context(|| format!("{path:?} not found in [{:#?}]", self.0.keys().collect_vec())),
         }
     }
-    #[tracing::instrument(skip(bottom_level_paths), fields(count=%bottom_level_paths.len()), level = "trace")]
-    pub fn preheat_archive_hash_paths(bottom_level_paths: Vec<NonEmpty<PathBuf>>) -> Result<Self> {
+    /// archives cached by `budget` are not extracted again, every extracted one is tracked by it and the ones extracted to get to
+    /// the bottom level paths are cached once they are not needed anymore
+    #[tracing::instrument(skip(bottom_level_paths, budget), fields(count=%bottom_level_paths.len()), level = "trace")]
+    pub fn preheat_archive_hash_paths(bottom_level_paths: Vec<NonEmpty<PathBuf>>, budget: &ExtractionBudget) -> Result<Self> {
         fn ancestors(path: NonEmpty<PathBuf>) -> impl Iterator<Item = (NonEmpty<PathBuf>, PathBuf)> {
             fn popped<T>(mut l: NonEmpty<T>) -> Option<(NonEmpty<T>, T)> {
                 l.pop().map(|i| (l, i))
@@ -48,9 +50,26 @@ impl PreheatedArchiveHashPaths {
         }
         let bottom_level_paths_lookup = bottom_level_paths.iter().cloned().collect::<BTreeSet<_>>();
 
+        // cached archives are held on to until the end, so that they don't get evicted before they're extracted from
+        let mut cached = BTreeMap::new();
         let all_necessary_extracts = bottom_level_paths
             .into_iter()
-            .flat_map(ancestors)
+            .flat_map(|path| {
+                ancestors(path)
+                    .take_while(|(parent, archive_path)| {
+                        let path = parent
+                            .clone()
+                            .tap_mut(|parent| parent.push(archive_path.clone()));
+                        match budget.get(&path) {
+                            Some(source) => {
+                                cached.insert(path, source);
+                                false
+                            }
+                            None => true,
+                        }
+                    })
+                    .collect_vec()
+            })
             .unique()
             .sorted_by_cached_key(|(parent, _)| parent.clone())
             .collect_vec();
@@ -88,6 +107,7 @@ impl PreheatedArchiveHashPaths {
                                         .pipe(Ok),
                                     _more => previous_nesting_level
                                         .get(&parent)
+                                        .or_else(|| cached.get(&parent))
                                         .with_context(|| format!("parent not preheated: {parent:#?}"))
                                         .cloned(),
                                 })
@@ -140,11 +160,18 @@ impl PreheatedArchiveHashPaths {
                                                                                         handles
                                                                                             .into_iter()
                                                                                             .map(|(path, mut file)| {
+                                                                                                let modified = file.modified();
                                                                                                 file.size()
                                                                                                     .context("checking size")
                                                                                                     .and_then(|size| {
+                                                                                                        budget.make_room(size);
                                                                                                         file.seek_with_temp_file_blocking_raw(size)
                                                                                                     })
+                                                                                                    .tap_ok(|(_, extracted)| {
+                                                                                                        crate::compression::preserve_modified_time(
+                                                                                                            extracted, modified,
+                                                                                                        )
+                                                                                                    })
                                                                                                     .map(|e| (path, e))
                                                                                             })
                                                                                             .collect::<Result<Vec<_>>>()
@@ -197,14 +224,18 @@ impl PreheatedArchiveHashPaths {
                                         })
                                         .collect_into_vec(&mut buffer)
                                         .pipe(|_| {
-                                            previous_nesting_level.retain(|source_path, _| {
+                                            previous_nesting_level.retain(|source_path, source| {
                                                 // WARN: this is the important bit, sorry
                                                 // that's not split up further
                                                 // ALL paths with ancestors will end up here,
                                                 // but this would blow the disk out of proportion.
-                                                // by filtering here we drop the temp files,
+                                                // by filtering here we drop the temp files (unless they fit in the budget),
                                                 // and they will be cleaned up from filesystem
-                                                bottom_level_paths_lookup.contains(source_path)
+                                                bottom_level_paths_lookup.contains(source_path).tap(|keep| {
+                                                    if !keep {
+                                                        budget.keep(source_path.clone(), source.clone())
+                                                    }
+                                                })
                                             });
 
                                             buffer
@@ -212,10 +243,14 @@ impl PreheatedArchiveHashPaths {
                                                 .try_fold(previous_nesting_level, |acc, next| {
                                                     next.map(|next| {
                                                         acc.tap_mut(|acc| {
-                                                            acc.extend(
-                                                                next.into_iter()
-                                                                    .map(|(k, (_, v))| (k, v.pipe(SourceKind::CachedPath).pipe(Arc::new))),
-                                                            );
+                                                            acc.extend(next.into_iter().map(|(k, (size, v))| {
+                                                                (
+                                                                    k,
+                                                                    v.pipe(SourceKind::CachedPath)
+                                                                        .pipe(Arc::new)
+                                                                        .tap(|source| budget.track(size, source)),
+                                                                )
+                                                            }));
                                                         })
                                                     })
                                                 })
@@ -226,7 +261,15 @@ impl PreheatedArchiveHashPaths {
                     })
                 },
             )
-            .map(|(preheated, _)| preheated)
+            .map(|(preheated, _)| {
+                preheated.tap_mut(|preheated| {
+                    preheated.extend(
+                        cached
+                            .into_iter()
+                            .filter(|(path, _)| bottom_level_paths_lookup.contains(path)),
+                    )
+                })
+            })
             .map(Self)
     }
 }
@@ -1,11 +1,15 @@
 use {
     super::*,
     crate::{
+        config_file::RemappingConfig,
         modlist_json::directive::RemappedInlineFileDirective,
         progress_bars_v2::IndicatifWrapIoExt,
         utils::{spawn_rayon, PathReadWrite},
     },
-    std::io::{Read, Seek},
+    std::{
+        io::{Read, Seek},
+        path::Component,
+    },
     tracing::instrument,
     wabbajack_file_handle::WabbajackFileHandle,
 };
@@ -45,60 +49,58 @@ pub struct RemappingContext {
     pub game_folder: PathBuf,
     pub output_directory: PathBuf,
     pub downloads_directory: PathBuf,
+    pub config: RemappingConfig,
 }
 
-#[extension_traits::extension(trait PathCrossPlatformJoineryExt)]
-impl Path {
-    fn join_with_delimiter(&self, delimiter: &str) -> String {
-        self.iter().map(|e| e.to_string_lossy()).join(delimiter)
-    }
+/// the path as windows programs see it, absolute unix paths get the drive letter wine maps `/` to
+fn windows_path(path: &Path, drive_letter: Option<&str>, delimiter: &str) -> String {
+    let has_prefix = matches!(path.components().next(), Some(Component::Prefix(_)));
+    path.components()
+        .filter_map(|component| match component {
+            Component::Prefix(prefix) => Some(prefix.as_os_str().to_string_lossy()),
+            Component::RootDir => Some(match has_prefix {
+                true => "".into(),
+                false => drive_letter.unwrap_or_default().into(),
+            }),
+            Component::CurDir => None,
+            Component::ParentDir => Some("..".into()),
+            Component::Normal(normal) => Some(normal.to_string_lossy()),
+        })
+        .join(delimiter)
 }
 
 impl RemappingContext {
+    /// every wabbajack magic string, along with the path it stands for
+    fn magic_strings(&self) -> Vec<(&'static str, String)> {
+        const BACK: &str = r#"\"#;
+        const DOUBLE_BACK: &str = r#"\\"#;
+        const FORWARD: &str = r#"/"#;
+        let path = |path: &Path, delimiter| windows_path(path, self.config.drive_letter.as_deref(), delimiter);
+        vec![
+            (wabbajack_consts::GAME_PATH_MAGIC_BACK, path(&self.game_folder, BACK)),
+            (wabbajack_consts::GAME_PATH_MAGIC_DOUBLE_BACK, path(&self.game_folder, DOUBLE_BACK)),
+            (wabbajack_consts::GAME_PATH_MAGIC_FORWARD, path(&self.game_folder, FORWARD)),
+            (wabbajack_consts::MO2_PATH_MAGIC_BACK, path(&self.output_directory, BACK)),
+            (wabbajack_consts::MO2_PATH_MAGIC_DOUBLE_BACK, path(&self.output_directory, DOUBLE_BACK)),
+            (wabbajack_consts::MO2_PATH_MAGIC_FORWARD, path(&self.output_directory, FORWARD)),
+            (wabbajack_consts::DOWNLOAD_PATH_MAGIC_BACK, path(&self.downloads_directory, BACK)),
+            (wabbajack_consts::DOWNLOAD_PATH_MAGIC_DOUBLE_BACK, path(&self.downloads_directory, DOUBLE_BACK)),
+            (wabbajack_consts::DOWNLOAD_PATH_MAGIC_FORWARD, path(&self.downloads_directory, FORWARD)),
+        ]
+    }
+
+    /// user defined variables come last, so they can't be used to override the magic strings
     pub fn remap_file_contents(&self, data: &str) -> String {
-        self.pipe(
-            |Self {
-                 game_folder,
-                 output_directory: install_directory,
-                 downloads_directory,
-             }| {
-                fn trim_relative_path_start(path: &str) -> String {
-                    path.trim_start_matches(r#".\\"#)
-                        .trim_start_matches(r#".\"#)
-                        .trim_start_matches(r#"./"#)
-                        .to_string()
-                }
-                let game_folder = |delimiter| {
-                    game_folder
-                        .join_with_delimiter(delimiter)
-                        .pipe_as_ref(trim_relative_path_start)
-                };
-                let install_directory = |delimiter| {
-                    install_directory
-                        .join_with_delimiter(delimiter)
-                        .pipe_as_ref(trim_relative_path_start)
-                };
-                let downloads_directory = |delimiter| {
-                    downloads_directory
-                        .join_with_delimiter(delimiter)
-                        .pipe_as_ref(trim_relative_path_start)
-                };
-
-                const BACK: &str = r#"\"#;
-                const DOUBLE_BACK: &str = r#"\\"#;
-                const FORWARD: &str = r#"/"#;
-                data.replace(wabbajack_consts::GAME_PATH_MAGIC_BACK, game_folder(BACK).as_str())
-                    .replace(wabbajack_consts::GAME_PATH_MAGIC_DOUBLE_BACK, game_folder(DOUBLE_BACK).as_str())
-                    .replace(wabbajack_consts::GAME_PATH_MAGIC_FORWARD, game_folder(FORWARD).as_str())
-                    .replace(wabbajack_consts::MO2_PATH_MAGIC_BACK, install_directory(BACK).as_str())
-                    .replace(wabbajack_consts::MO2_PATH_MAGIC_DOUBLE_BACK, install_directory(DOUBLE_BACK).as_str())
-                    .replace(wabbajack_consts::MO2_PATH_MAGIC_FORWARD, install_directory(FORWARD).as_str())
-                    .replace(wabbajack_consts::DOWNLOAD_PATH_MAGIC_BACK, downloads_directory(BACK).as_str())
-                    .replace(wabbajack_consts::DOWNLOAD_PATH_MAGIC_DOUBLE_BACK, downloads_directory(DOUBLE_BACK).as_str())
-                    .replace(wabbajack_consts::DOWNLOAD_PATH_MAGIC_FORWARD, downloads_directory(FORWARD).as_str())
-                    .tap(|new| tracing::trace!("remapped:\n{data}-->\n{new}"))
-            },
-        )
+        self.magic_strings()
+            .into_iter()
+            .fold(data.to_string(), |data, (magic, path)| data.replace(magic, &path))
+            .pipe(|remapped| {
+                self.config
+                    .variables
+                    .iter()
+                    .fold(remapped, |data, (variable, value)| data.replace(variable.as_str(), value))
+            })
+            .tap(|new| tracing::trace!("remapped:\n{data}-->\n{new}"))
     }
 }
 
@@ -157,3 +159,56 @@ impl RemappedInlineFileHandler {
         .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(drive_letter: Option<&str>) -> RemappingContext {
+        RemappingContext {
+            game_folder: PathBuf::from("/games/Skyrim Special Edition"),
+            output_directory: PathBuf::from("/home/user/Modlists/Tuxborn"),
+            downloads_directory: PathBuf::from("./downloads"),
+            config: RemappingConfig {
+                drive_letter: drive_letter.map(ToOwned::to_owned),
+                variables: [("{--||PROTON_PREFIX||--}".to_string(), "Z:\\\\prefix".to_string())]
+                    .into_iter()
+                    .collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_windows_paths() {
+        let path = Path::new("/home/user/Modlists");
+        assert_eq!(windows_path(path, Some("Z:"), r#"\"#), r#"Z:\home\user\Modlists"#);
+        assert_eq!(windows_path(path, Some("Z:"), "/"), "Z:/home/user/Modlists");
+        assert_eq!(windows_path(path, None, r#"\\"#), r#"\\home\\user\\Modlists"#);
+        assert_eq!(windows_path(Path::new("./downloads/mods"), Some("Z:"), r#"\"#), r#"downloads\mods"#);
+    }
+
+    #[test]
+    fn test_remaps_mod_organizer_ini() {
+        let remapped = context(Some("Z:")).remap_file_contents(include_str!("./remapped_inline_file/ModOrganizer.ini"));
+        assert!(!remapped.contains("{--||"), "{remapped}");
+        [
+            r#"gamePath=@ByteArray(Z:\\games\\Skyrim Special Edition)"#,
+            r#"download_directory=downloads"#,
+            r#"base_directory=Z:/home/user/Modlists/Tuxborn"#,
+            r#"overwrite_directory=Z:\home\user\Modlists\Tuxborn\overwrite"#,
+            r#"1\binary=Z:/games/Skyrim Special Edition/skse64_loader.exe"#,
+            r#"2\arguments="-d:\"Z:\\home\\user\\Modlists\\Tuxborn\\mods\\DynDOLOD Output\"""#,
+            r#"3\arguments=--prefix Z:\\prefix"#,
+        ]
+        .into_iter()
+        .for_each(|line| assert!(remapped.lines().any(|remapped| remapped == line), "[{line}] missing from:\n{remapped}"));
+    }
+
+    #[test]
+    fn test_remaps_without_drive_letter() {
+        let remapped = context(None).remap_file_contents(include_str!("./remapped_inline_file/ModOrganizer.ini"));
+        assert!(remapped
+            .lines()
+            .any(|line| line == r#"gamePath=@ByteArray(\\games\\Skyrim Special Edition)"#));
+    }
+}
@@ -0,0 +1,101 @@
+//! directives are either cpu bound (recompressing textures) or io bound (copying files, applying patches),
+//! each kind gets its own limit so that fast drives are not held back by the cpu bound ones
+use {
+    anyhow::{Context, Result},
+    once_cell::sync::Lazy,
+    parking_lot::RwLock,
+    std::{future::Future, sync::Arc},
+    tokio::sync::Semaphore,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scheduling {
+    pub cpu_tasks: usize,
+    pub io_tasks: usize,
+    pub max_open_files: usize,
+}
+
+fn default_cpu_tasks() -> usize {
+    #[cfg(not(debug_assertions))]
+    {
+        use std::ops::Div;
+
+        num_cpus::get().div(2).saturating_sub(1).max(1)
+    }
+    #[cfg(debug_assertions)]
+    {
+        1
+    }
+}
+
+impl Scheduling {
+    /// whatever is not configured is derived from the number of cpus
+    pub fn new(cpu_tasks: Option<usize>, io_tasks: Option<usize>, max_open_files: Option<usize>) -> Self {
+        let cpu_tasks = cpu_tasks.unwrap_or_else(default_cpu_tasks).max(1);
+        let io_tasks = io_tasks.unwrap_or(cpu_tasks).max(1);
+        Self {
+            cpu_tasks,
+            io_tasks,
+            max_open_files: max_open_files.unwrap_or((cpu_tasks + io_tasks) * 20).max(1),
+        }
+    }
+
+    /// how many directives are in flight at once
+    pub fn total_tasks(&self) -> usize {
+        self.cpu_tasks + self.io_tasks
+    }
+}
+
+static SCHEDULING: Lazy<RwLock<Scheduling>> = Lazy::new(|| RwLock::new(Scheduling::new(None, None, None)));
+
+/// has to be called before the first directive is handled, the permits are created on first use
+pub fn configure_scheduling(scheduling: Scheduling) {
+    tracing::info!(?scheduling, "configured directive scheduling");
+    *SCHEDULING.write() = scheduling;
+}
+
+pub fn scheduling() -> Scheduling {
+    *SCHEDULING.read()
+}
+
+static CPU_PERMITS: Lazy<Arc<Semaphore>> = Lazy::new(|| Arc::new(Semaphore::new(scheduling().cpu_tasks)));
+static IO_PERMITS: Lazy<Arc<Semaphore>> = Lazy::new(|| Arc::new(Semaphore::new(scheduling().io_tasks)));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskKind {
+    Cpu,
+    Io,
+}
+
+/// waits for a free slot in the pool of `kind` before running `task`
+pub async fn scheduled<T>(kind: TaskKind, task: impl Future<Output = Result<T>>) -> Result<T> {
+    let _permit = match kind {
+        TaskKind::Cpu => &CPU_PERMITS,
+        TaskKind::Io => &IO_PERMITS,
+    }
+    .clone()
+    .acquire_owned()
+    .await
+    .context("semaphore closed")?;
+    task.await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_follow_configured_values() {
+        let scheduling = Scheduling::new(Some(4), None, None);
+        assert_eq!(
+            scheduling,
+            Scheduling {
+                cpu_tasks: 4,
+                io_tasks: 4,
+                max_open_files: 160
+            }
+        );
+        assert_eq!(Scheduling::new(Some(2), Some(16), Some(64)).total_tasks(), 18);
+        assert_eq!(Scheduling::new(Some(0), Some(0), Some(0)).max_open_files, 1);
+    }
+}
@@ -32,6 +32,7 @@ impl std::io::Result<u64> {
 // #[cfg(feature = "dds_recompression")]
 mod dds_recompression;
 mod dds_recompression_directx_tex;
+mod welch_resampling;
 
 #[cfg(feature = "intel_tex")]
 mod dds_recompression_intel_tex;
@@ -1,7 +1,8 @@
 use {
     crate::{modlist_json::image_format::DXGIFormat, progress_bars_v2::IndicatifWrapIoExt},
     anyhow::{Context, Result},
-    directxtex::{self, TexMetadata, DDS_FLAGS, DXGI_FORMAT, TEX_COMPRESS_FLAGS, TEX_FILTER_FLAGS, TEX_THRESHOLD_DEFAULT},
+    directxtex::{self, ScratchImage, TexMetadata, CP_FLAGS, DDS_FLAGS, DXGI_FORMAT, TEX_COMPRESS_FLAGS, TEX_FILTER_FLAGS, TEX_THRESHOLD_DEFAULT},
+    itertools::Itertools,
     num::ToPrimitive,
     std::io::{Read, Write},
     tap::prelude::*,
@@ -15,6 +16,45 @@ macro_rules! spanned {
     };
 }
 
+/// size of a single `DXGI_FORMAT_R32G32B32A32_FLOAT` pixel
+const RGBA_F32_PIXEL: usize = 16;
+
+/// `image` has to be a single `DXGI_FORMAT_R32G32B32A32_FLOAT` image, only its first mip level is used
+fn welch_resize(image: &ScratchImage, target_width: usize, target_height: usize) -> Result<ScratchImage> {
+    let metadata = image.metadata();
+    let (width, height) = (metadata.width, metadata.height);
+    let pixels = image
+        .pixels()
+        .get(..width * height * RGBA_F32_PIXEL)
+        .context("image is smaller than its dimensions")?
+        .chunks_exact(RGBA_F32_PIXEL)
+        .map(|pixel| std::array::from_fn(|channel| f32::from_ne_bytes(std::array::from_fn(|byte| pixel[channel * 4 + byte]))))
+        .collect_vec();
+    let resized = super::welch_resampling::resize_rgba(&pixels, width, height, target_width, target_height);
+    let mut output = ScratchImage::default();
+    output
+        .initialize_2d(
+            DXGI_FORMAT::DXGI_FORMAT_R32G32B32A32_FLOAT,
+            target_width,
+            target_height,
+            1,
+            1,
+            CP_FLAGS::CP_FLAGS_NONE,
+        )
+        .context("allocating resized image")?;
+    output
+        .pixels_mut()
+        .iter_mut()
+        .zip(
+            resized
+                .iter()
+                .flatten()
+                .flat_map(|channel| channel.to_ne_bytes()),
+        )
+        .for_each(|(output, byte)| *output = byte);
+    Ok(output)
+}
+
 #[tracing::instrument(skip(input, output))]
 pub fn resize_dds<R, W>(input: &mut R, target_width: u32, target_height: u32, target_format: DXGIFormat, target_mipmaps: u32, output: &mut W) -> Result<u64>
 where
@@ -53,7 +93,11 @@ where
                                         target_width.to_usize().context("bad target_width")?,
                                         target_height.to_usize().context("bad target_height")?,
                                     );
-                                    spanned!(image.resize(width, height, tex_filter_flags,)).context("resizing")
+                                    match tex_metadata.array_size == 1 && tex_metadata.depth == 1 {
+                                        true => spanned!(welch_resize(&image, width, height)).context("resizing with welch filter"),
+                                        // cubemaps and texture arrays are rare enough to leave to directxtex
+                                        false => spanned!(image.resize(width, height, tex_filter_flags,)).context("resizing"),
+                                    }
                                 })
                                 .and_then(|resized| {
                                     let target_mipmaps = target_mipmaps.to_usize().context("bad target_mipmaps")?;
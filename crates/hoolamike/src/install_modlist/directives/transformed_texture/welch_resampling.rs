@@ -0,0 +1,120 @@
+//! wabbajack resizes textures with imagesharp's welch resampler, using the same kernel gets the pixels
+//! as close as possible to what the modlist author had (the block compression still has to match for the hashes to)
+use {itertools::Itertools, tap::prelude::*};
+
+const RADIUS: f32 = 3.;
+const EPSILON: f32 = 0.001;
+
+/// imagesharp flushes tiny values to zero
+fn sinc(x: f32) -> f32 {
+    match x.abs() > EPSILON {
+        true => {
+            let x = x * std::f32::consts::PI;
+            (x.sin() / x).pipe(|sinc| match sinc.abs() < EPSILON {
+                true => 0.,
+                false => sinc,
+            })
+        }
+        false => 1.,
+    }
+}
+
+fn welch(x: f32) -> f32 {
+    let x = x.abs();
+    match x < RADIUS {
+        true => sinc(x) * (1. - (x * x / (RADIUS * RADIUS))),
+        false => 0.,
+    }
+}
+
+/// the first source index and the normalized weights for every destination index, mirrors imagesharp's `ResizeKernelMap`
+fn kernel_map(source_size: usize, destination_size: usize) -> Vec<(usize, Vec<f32>)> {
+    let ratio = source_size as f64 / destination_size as f64;
+    let scale = ratio.max(1.);
+    let radius = (scale * RADIUS as f64).ceil();
+    (0..destination_size)
+        .map(|index| {
+            let center = ((index as f64 + 0.5) * ratio) - 0.5;
+            let left = (center - radius).ceil().max(0.) as usize;
+            let right = ((center + radius).floor() as usize).min(source_size - 1);
+            let weights = (left..=right)
+                .map(|source| welch(((source as f64 - center) / scale) as f32))
+                .collect_vec();
+            let sum = weights.iter().sum::<f32>();
+            let weights = match sum > 0. {
+                true => weights.into_iter().map(|weight| weight / sum).collect(),
+                false => weights,
+            };
+            (left, weights)
+        })
+        .collect()
+}
+
+fn premultiply([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    [r * a, g * a, b * a, a]
+}
+
+fn unpremultiply([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    match a == 0. {
+        true => [r, g, b, a],
+        false => [r / a, g / a, b / a, a],
+    }
+}
+
+fn apply(pixels: impl Iterator<Item = [f32; 4]>, weights: &[f32]) -> [f32; 4] {
+    pixels.zip(weights).fold([0.; 4], |acc, (pixel, weight)| {
+        std::array::from_fn(|channel| acc[channel] + pixel[channel] * weight)
+    })
+}
+
+/// `pixels` are rows of rgba, alpha is premultiplied while resampling just like imagesharp does
+pub fn resize_rgba(pixels: &[[f32; 4]], width: usize, height: usize, target_width: usize, target_height: usize) -> Vec<[f32; 4]> {
+    assert_eq!(pixels.len(), width * height, "bad image dimensions");
+    let premultiplied = pixels.iter().copied().map(premultiply).collect_vec();
+    let horizontal = kernel_map(width, target_width);
+    let vertical = kernel_map(height, target_height);
+    let rows = premultiplied
+        .chunks_exact(width)
+        .flat_map(|row| {
+            horizontal
+                .iter()
+                .map(|(left, weights)| apply(row[*left..].iter().copied(), weights))
+        })
+        .collect_vec();
+    (0..target_height)
+        .flat_map(|y| (0..target_width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (top, weights) = &vertical[y];
+            apply((*top..).map(|source_y| rows[source_y * target_width + x]), weights)
+        })
+        .map(unpremultiply)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weights_are_normalized() {
+        kernel_map(2048, 512)
+            .into_iter()
+            .chain(kernel_map(4, 8))
+            .for_each(|(_, weights)| assert!((weights.iter().sum::<f32>() - 1.).abs() < 1e-4, "{weights:?}"));
+        assert_eq!(welch(0.), 1.);
+        assert_eq!(welch(3.), 0.);
+    }
+
+    #[test]
+    fn test_flat_color_stays_flat() {
+        let pixels = vec![[0.25, 0.5, 0.75, 1.]; 16 * 8];
+        resize_rgba(&pixels, 16, 8, 4, 2)
+            .into_iter()
+            .for_each(|pixel| {
+                pixel
+                    .iter()
+                    .zip([0.25, 0.5, 0.75, 1.])
+                    .for_each(|(channel, expected)| assert!((channel - expected).abs() < 1e-4, "{pixel:?}"))
+            });
+    }
+}
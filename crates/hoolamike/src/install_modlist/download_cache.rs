@@ -12,9 +12,12 @@ use {
     tracing_indicatif::span_ext::IndicatifSpanExt,
 };
 
+pub mod hash_cache;
+
 #[derive(Debug, Clone)]
 pub struct DownloadCache {
     pub root_directory: PathBuf,
+    pub deep_verify_archives: bool,
 }
 impl DownloadCache {
     pub fn new(root_directory: PathBuf) -> Result<Self> {
@@ -22,9 +25,14 @@ impl DownloadCache {
             .context("creating download directory")
             .map(|_| Self {
                 root_directory: root_directory.clone(),
+                deep_verify_archives: false,
             })
             .with_context(|| format!("creating download cache handler at [{}]", root_directory.display()))
     }
+
+    pub fn with_deep_verify_archives(self, deep_verify_archives: bool) -> Self {
+        Self { deep_verify_archives, ..self }
+    }
 }
 
 async fn read_file_size(path: &PathBuf) -> Result<u64> {
@@ -35,7 +43,7 @@ async fn read_file_size(path: &PathBuf) -> Result<u64> {
 }
 
 #[tracing::instrument]
-async fn calculate_hash(path: PathBuf) -> Result<u64> {
+pub async fn calculate_hash(path: PathBuf) -> Result<u64> {
     let size = tokio::fs::metadata(&path)
         .await
         .context("no such file")?
@@ -98,17 +106,41 @@ pub fn to_u64_from_base_64(input: String) -> Result<u64> {
         .context("decoding string as hashed bytes")
 }
 
+fn compare_base_64_hash(found_hash: &str, expected_hash: &str) -> Result<()> {
+    found_hash
+        .eq(expected_hash)
+        .then_some(())
+        .with_context(|| format!("hash mismatch, expected [{expected_hash}], found [{found_hash}]"))
+}
+
+pub fn compare_hash(found_hash: u64, expected_hash: &str) -> Result<()> {
+    compare_base_64_hash(&to_base_64_from_u64(found_hash), expected_hash)
+}
+
+/// archives in the downloads directory are looked up in the hash cache before reading them
 pub async fn validate_hash(path: PathBuf, expected_hash: String) -> Result<PathBuf> {
-    calculate_hash(path.clone())
-        .map_ok(to_base_64_from_u64)
-        .and_then(|hash| {
-            hash.eq(&expected_hash)
-                .then_some(path.clone())
-                .with_context(|| format!("hash mismatch, expected [{expected_hash}], found [{hash}]"))
-                .pipe(ready)
-        })
-        .await
-        .with_context(|| format!("validating hash for [{}]", path.display()))
+    match hash_cache::cached_hash(&path) {
+        Some(hash) => Ok(hash),
+        None => calculate_hash(path.clone())
+            .await
+            .map(to_base_64_from_u64)
+            .tap_ok(|hash| hash_cache::remember_hash(&path, hash)),
+    }
+    .and_then(|hash| compare_base_64_hash(&hash, &expected_hash))
+    .map(|_| path.clone())
+    .with_context(|| format!("validating hash for [{}]", path.display()))
+}
+
+/// an archive can match its hash and still fail to extract, `7z t` decompresses everything to make sure it does not
+pub async fn validate_archive_integrity(path: PathBuf) -> Result<PathBuf> {
+    tokio::task::spawn_blocking(move || {
+        crate::compression::wrapped_7zip::test_archive(&path)
+            .map(|report| tracing::debug!(?report, "archive passed the integrity check"))
+            .map(|_| path)
+    })
+    .map_context("task crashed")
+    .and_then(ready)
+    .await
 }
 
 pub async fn validate_file_size(path: PathBuf, expected_size: u64) -> Result<PathBuf> {
@@ -126,6 +158,7 @@ impl DownloadCache {
     }
     pub async fn verify(self: Arc<Self>, descriptor: ArchiveDescriptor) -> Result<WithArchiveDescriptor<PathBuf>> {
         let ArchiveDescriptor { hash, meta: _, name, size } = descriptor.clone();
+        let deep_verify_archives = self.deep_verify_archives;
         self.download_output_path(name)
             .pipe(Ok)
             .pipe(ready)
@@ -138,6 +171,12 @@ impl DownloadCache {
             .and_then(|exists| match exists {
                 Some(existing_path) => validate_file_size(existing_path.clone(), size)
                     .and_then(|found_path| validate_hash(found_path, hash))
+                    .and_then(
+                        move |found_path| match deep_verify_archives && crate::compression::wrapped_7zip::is_testable(&found_path) {
+                            true => validate_archive_integrity(found_path).boxed(),
+                            false => found_path.pipe(Ok).pipe(ready).boxed(),
+                        },
+                    )
                     .map_ok(Some)
                     .boxed(),
                 None => None.pipe(Ok).pipe(ready).boxed(),
@@ -0,0 +1,176 @@
+//! remembers the hashes of archives in the downloads directory, so that hundreds of gigabytes are not read again on every run
+use {
+    crate::json_store::JsonStore,
+    anyhow::{Context, Result},
+    once_cell::sync::Lazy,
+    parking_lot::RwLock,
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::UNIX_EPOCH,
+    },
+    tap::prelude::*,
+};
+
+pub const HASH_CACHE_FILE_NAME: &str = ".hoolamike-hashes.json";
+
+/// a hash is only trusted as long as the file it was calculated for has the same size and modification time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedHash {
+    pub size: u64,
+    /// nanoseconds since unix epoch
+    pub modified: u128,
+    /// base64, the way wabbajack stores it
+    pub hash: String,
+}
+
+/// keyed by the path relative to the downloads directory
+#[derive(Debug)]
+pub struct HashCache {
+    root_directory: PathBuf,
+    rehash: bool,
+    entries: JsonStore<BTreeMap<String, CachedHash>>,
+}
+
+pub(crate) fn fingerprint(path: &Path) -> Result<(u64, u128)> {
+    std::fs::metadata(path)
+        .and_then(|metadata| {
+            metadata
+                .modified()
+                .map(|modified| (metadata.len(), modified))
+        })
+        .with_context(|| format!("reading metadata of [{}]", path.display()))
+        .and_then(|(size, modified)| {
+            modified
+                .duration_since(UNIX_EPOCH)
+                .context("modification time is before unix epoch")
+                .map(|modified| (size, modified.as_nanos()))
+        })
+}
+
+impl HashCache {
+    /// with `rehash` every lookup misses, but the freshly calculated hashes are still written down
+    pub fn load(downloads_directory: &Path, rehash: bool) -> Result<Self> {
+        Ok(Self {
+            root_directory: downloads_directory.to_owned(),
+            rehash,
+            entries: JsonStore::load(downloads_directory.join(HASH_CACHE_FILE_NAME), "hash cache"),
+        })
+    }
+
+    /// only files in the downloads directory are cached, installed files are hashed every time
+    fn key(&self, path: &Path) -> Option<String> {
+        path.strip_prefix(&self.root_directory)
+            .ok()
+            .map(|relative| relative.to_string_lossy().to_string())
+    }
+
+    pub fn get(&self, path: &Path) -> Option<String> {
+        if self.rehash {
+            return None;
+        }
+        let key = self.key(path)?;
+        let (size, modified) = fingerprint(path).ok()?;
+        self.entries.read(|entries| {
+            entries
+                .get(&key)
+                .filter(|cached| cached.size == size && cached.modified == modified)
+                .map(|cached| cached.hash.clone())
+        })
+    }
+
+    pub fn insert(&self, path: &Path, hash: &str) -> Result<()> {
+        let Some(key) = self.key(path) else {
+            return Ok(());
+        };
+        let (size, modified) = fingerprint(path)?;
+        self.entries.update(|entries| {
+            entries.insert(
+                key,
+                CachedHash {
+                    size,
+                    modified,
+                    hash: hash.to_string(),
+                },
+            );
+        })
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.entries.flush()
+    }
+}
+
+/// hashes are validated deep inside the downloaders and directives, far away from the config
+static HASH_CACHE: Lazy<RwLock<Option<Arc<HashCache>>>> = Lazy::new(Default::default);
+
+pub fn configure_hash_cache(downloads_directory: &Path, rehash: bool) -> Result<()> {
+    *HASH_CACHE.write() = HashCache::load(downloads_directory, rehash)
+        .tap_ok(|_| {
+            if rehash {
+                tracing::info!("ignoring the hash cache, every archive will be hashed again")
+            }
+        })
+        .map(Arc::new)
+        .map(Some)?;
+    Ok(())
+}
+
+pub fn cached_hash(path: &Path) -> Option<String> {
+    HASH_CACHE.read().as_ref().and_then(|cache| cache.get(path))
+}
+
+/// the hash cache is an optimization, failing to update it is not worth failing over
+pub fn remember_hash(path: &Path, hash: &str) {
+    if let Some(cache) = HASH_CACHE.read().clone() {
+        cache
+            .insert(path, hash)
+            .unwrap_or_else(|reason| tracing::warn!(path=%path.display(), ?reason, "could not update the hash cache"))
+    }
+}
+
+/// hashes are written down every few seconds, this writes the ones calculated since
+pub fn flush_hash_cache() {
+    if let Some(cache) = HASH_CACHE.read().clone() {
+        cache
+            .flush()
+            .unwrap_or_else(|reason| tracing::warn!(?reason, "could not write the hash cache"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_is_forgotten_when_file_changes() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let archive = directory.path().join("some-mod.7z");
+        std::fs::write(&archive, b"original")?;
+        let cache = HashCache::load(directory.path(), false)?;
+        cache.insert(&archive, "aGFzaA==")?;
+        cache.flush()?;
+        assert_eq!(HashCache::load(directory.path(), false)?.get(&archive), Some("aGFzaA==".to_string()));
+        assert_eq!(HashCache::load(directory.path(), true)?.get(&archive), None);
+
+        std::fs::write(&archive, b"modified, and longer")?;
+        assert_eq!(cache.get(&archive), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_files_outside_downloads_directory_are_not_cached() -> Result<()> {
+        let downloads = tempfile::tempdir()?;
+        let installation = tempfile::tempdir()?;
+        let installed = installation.path().join("plugin.esp");
+        std::fs::write(&installed, b"plugin")?;
+        let cache = HashCache::load(downloads.path(), false)?;
+        cache.insert(&installed, "aGFzaA==")?;
+        assert_eq!(cache.get(&installed), None);
+        cache.flush()?;
+        assert!(!downloads.path().join(HASH_CACHE_FILE_NAME).exists());
+        Ok(())
+    }
+}
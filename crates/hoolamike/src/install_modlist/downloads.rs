@@ -1,39 +1,78 @@
 use {
-    super::*,
+    super::{
+        install_report::{shareable_url, DownloadRecord, DownloadSource},
+        *,
+    },
     crate::{
         config_file::{DownloadersConfig, GamesConfig},
         downloaders::{
+            challenge,
             gamefile_source_downloader::{get_game_file_source_synchronizers, GameFileSourceSynchronizers},
             helpers::FutureAnyhowExt,
+            http_client::http_client,
             mediafire::MediaFireDownloader,
+            mirrors::{Mirrors, ResolvedUrl},
+            moddb::ModDBDownloader,
             nexus::{self, NexusDownloader},
-            wabbajack_cdn::WabbajackCDNDownloader,
+            vector_plexus::VectorPlexusDownloader,
+            wabbajack_cdn::{PartDownload, WabbajackCDNDownloader},
             CopyFileTask,
             DownloadTask,
+            ManualDownloadTask,
             MergeDownloadTask,
             SyncTask,
             WithArchiveDescriptor,
         },
         error::{MultiErrorCollectExt, TotalResult},
-        modlist_json::{Archive, GoogleDriveState, HttpState, HumanUrl, ManualState, MediaFireState, MegaState, State},
-        progress_bars_v2::IndicatifWrapIoExt,
+        modlist_json::{Archive, ArchiveDescriptor, GoogleDriveState, HttpState, HumanUrl, ManualState, MediaFireState, MegaState, ModDBState, State},
+        progress_bars_v2::{events::ProgressTracker, IndicatifWrapIoExt},
+        shutdown::until_stopped,
     },
     anyhow::Result,
+    backend::DownloadRequest,
+    disk_space::PendingDownload,
     futures::{FutureExt, StreamExt, TryStreamExt},
-    std::{collections::HashMap, path::PathBuf, sync::Arc},
-    tracing::{debug, instrument, Instrument},
+    journal::{DownloadJournal, JournalEntry},
+    std::{
+        collections::{BTreeMap, HashMap},
+        hash::Hasher,
+        path::PathBuf,
+        sync::Arc,
+        time::Duration,
+    },
+    throttle::Throttle,
+    tracing::{debug, info, instrument, Instrument},
 };
 
+pub mod backend;
+pub mod disk_space;
+pub mod journal;
+pub mod throttle;
+
 #[derive(Clone)]
 pub struct DownloadersInner {
     pub nexus: Option<Arc<NexusDownloader>>,
 }
 
 impl DownloadersInner {
-    pub fn new(DownloadersConfig { nexus, downloads_directory: _ }: DownloadersConfig) -> Result<Self> {
+    pub fn new(
+        DownloadersConfig {
+            nexus,
+            downloads_directory: _,
+            deep_verify_archives: _,
+            max_bandwidth: _,
+            max_bandwidth_per_host: _,
+            proxy: _,
+            cookies: _,
+            user_agent: _,
+            mirrors: _,
+            backend: _,
+        }: DownloadersConfig,
+    ) -> Result<Self> {
         Ok(Self {
             nexus: nexus
                 .api_key
+                .or_else(crate::login::credentials::stored_nexus_api_key)
                 .map(NexusDownloader::new)
                 .transpose()?
                 .map(Arc::new),
@@ -46,6 +85,13 @@ pub struct Synchronizers {
     pub config: Arc<DownloadersConfig>,
     inner: DownloadersInner,
     pub(crate) cache: Arc<download_cache::DownloadCache>,
+    throttle: Arc<Throttle>,
+    journal: Arc<DownloadJournal>,
+    mirrors: Arc<Mirrors>,
+    /// archive name -> the mirror it was downloaded from, for the ones whose original url did not work
+    mirrors_used: Arc<parking_lot::Mutex<BTreeMap<String, HumanUrl>>>,
+    /// where every archive came from and how long it took, for the install report
+    download_records: Arc<parking_lot::Mutex<Vec<DownloadRecord>>>,
     game_synchronizers: Arc<GameFileSourceSynchronizers>,
 }
 
@@ -133,6 +179,7 @@ async fn copy_local_file(from: PathBuf, to: PathBuf, expected_size: u64) -> Resu
         .open(&to)
         .map_with_context(|| format!("opening [{}]", to.display()))
         .await?;
+    disk_space::preallocate(&target_file, expected_size)?;
 
     let copied = tokio::io::copy(&mut source_file, &mut tracing::Span::current().wrap_async_write(expected_size, target_file))
         .await
@@ -143,61 +190,239 @@ async fn copy_local_file(from: PathBuf, to: PathBuf, expected_size: u64) -> Resu
     }
     Ok(to)
 }
-#[instrument]
-pub async fn stream_merge_file(from: Vec<HumanUrl>, to: PathBuf, expected_size: u64) -> Result<PathBuf> {
+/// how many parts of a wabbajack cdn file are downloaded at once, they are still written (and hashed) in order
+const PARALLEL_PARTS: usize = 8;
+
+#[instrument(skip(parts, throttle, journal), fields(parts=%parts.len()))]
+pub async fn stream_merge_file(
+    parts: Vec<PartDownload>,
+    to: PathBuf,
+    expected_size: u64,
+    expected_hash: String,
+    throttle: Arc<Throttle>,
+    journal: Arc<DownloadJournal>,
+) -> Result<PathBuf> {
+    let name = journal_key(&to)?;
+    resume_or_stream_parts(&name, parts, to, expected_size, expected_hash, throttle, journal.clone())
+        .await
+        .tap(|result| journal.record(&name, result))
+}
+
+async fn resume_or_stream_parts(
+    name: &str,
+    parts: Vec<PartDownload>,
+    to: PathBuf,
+    expected_size: u64,
+    expected_hash: String,
+    throttle: Arc<Throttle>,
+    journal: Arc<DownloadJournal>,
+) -> Result<PathBuf> {
+    let client = http_client()?;
+    // only complete parts are kept, whatever comes after the last one is downloaded again
+    let present = journal.resume_offset(name, &to, expected_size);
+    let resume_from = parts
+        .iter()
+        .map(|part| part.offset + part.size)
+        .take_while(|end| *end <= present)
+        .last()
+        .unwrap_or(0);
     let target_file = tokio::fs::OpenOptions::new()
-        .write(true)
+        .append(true)
         .create(true)
-        .truncate(true)
         .open(&to)
         .map_with_context(|| format!("opening [{}]", to.display()))
         .await?;
+    target_file
+        .set_len(resume_from)
+        .await
+        .context("truncating partial file")?;
+    disk_space::preallocate(&target_file, expected_size)?;
+    if resume_from > 0 {
+        tracing::info!(%name, offset=%resume_from, "resuming interrupted download");
+    }
 
-    let mut writer = &mut tracing::Span::current().wrap_async_write(expected_size, target_file);
-    let mut downloaded = 0;
-    for from_chunk in from.clone().into_iter() {
-        let mut byte_stream = reqwest::get(from_chunk.to_string())
-            .await
-            .with_context(|| format!("making request to {from_chunk}"))?
-            .bytes_stream();
-        while let Some(chunk) = byte_stream.next().await {
-            match chunk {
-                Ok(chunk) => {
-                    downloaded += chunk.len() as u64;
-                    tokio::io::copy(&mut chunk.as_ref(), &mut writer)
-                        .await
-                        .with_context(|| format!("writing to fd {}", to.display()))?;
-                }
-                Err(message) => Err(message)?,
+    let mut writer = &mut tracing::Span::current()
+        .tap(|span| span.pb_inc(resume_from))
+        .wrap_async_write(expected_size, target_file);
+    let mut downloaded = resume_from;
+    let mut journaled = resume_from;
+    // the part downloaded before the interruption is not hashed here, resumed files are re-read once they are complete
+    let mut hasher = (resume_from == 0).then(|| xxhash_rust::xxh64::Xxh64::new(0));
+    journal.set(name, JournalEntry::InProgress { downloaded, expected_size })?;
+    let mut parts = parts
+        .into_iter()
+        .filter(|part| part.offset >= resume_from)
+        .map(|part| {
+            let client = client.clone();
+            let limit = throttle.for_url(&part.url);
+            async move {
+                let bytes = WabbajackCDNDownloader::download_part(&client, &part).await?;
+                limit.acquire(bytes.len() as u64).await;
+                Ok::<_, anyhow::Error>(bytes)
             }
+        })
+        .pipe(futures::stream::iter)
+        .buffered(PARALLEL_PARTS);
+    while let Some(part) = parts.next().await {
+        let part = part?;
+        downloaded += part.len() as u64;
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&part);
+        }
+        tokio::io::copy(&mut part.as_slice(), &mut writer)
+            .await
+            .with_context(|| format!("writing to fd {}", to.display()))?;
+        if downloaded - journaled >= journal::PROGRESS_INTERVAL {
+            journaled = downloaded;
+            journal.set(name, JournalEntry::InProgress { downloaded, expected_size })?;
         }
     }
 
     if downloaded != expected_size {
-        anyhow::bail!("[{from:?}] download finished, but received unexpected size (expected [{expected_size}] bytes, downloaded [{downloaded} bytes])")
+        anyhow::bail!("[{name}] download finished, but received unexpected size (expected [{expected_size}] bytes, downloaded [{downloaded} bytes])")
     }
-    Ok(to)
+    match hasher {
+        Some(hasher) => download_cache::compare_hash(hasher.finish(), &expected_hash)
+            .map(|_| download_cache::hash_cache::remember_hash(&to, &expected_hash))
+            .map(|_| to),
+        None => download_cache::validate_hash(to, expected_hash).await,
+    }
+    .with_context(|| format!("[{name}] download finished, but it is corrupted"))
 }
 
-#[instrument]
-pub async fn stream_file(from: HumanUrl, to: PathBuf, expected_size: u64) -> Result<PathBuf> {
+const MANUAL_DOWNLOAD_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// nexus does not hand out download links without premium, the archive is polled for until somebody else puts it into the downloads directory
+async fn wait_for_manual_download(website: String, to: PathBuf, descriptor: ArchiveDescriptor, cache: Arc<download_cache::DownloadCache>) -> Result<PathBuf> {
+    info!(
+        "waiting for [{}], download it from [{website}] (`hoolamike handle-nxm` running next to the installation does that)",
+        descriptor.name
+    );
+    loop {
+        crate::shutdown::check_stopped()?;
+        // files still being downloaded are not hashed over and over
+        if tokio::fs::metadata(&to)
+            .await
+            .is_ok_and(|metadata| metadata.len() == descriptor.size)
+        {
+            match cache.clone().verify(descriptor.clone()).await {
+                Ok(verified) => return Ok(verified.inner),
+                Err(reason) => debug!(?reason, name=%descriptor.name, "manual download does not match yet"),
+            }
+        }
+        tokio::time::sleep(MANUAL_DOWNLOAD_POLL_INTERVAL).await;
+    }
+}
+
+fn pending_download(task: &SyncTask) -> PendingDownload {
+    let (descriptor, to) = match task {
+        SyncTask::MergeDownload(WithArchiveDescriptor { inner: (_, to), descriptor }) => (descriptor, to),
+        SyncTask::Download(WithArchiveDescriptor { inner: (_, to), descriptor }) => (descriptor, to),
+        SyncTask::Copy(WithArchiveDescriptor { inner: (_, to), descriptor }) => (descriptor, to),
+        SyncTask::Manual(WithArchiveDescriptor { inner: (_, to), descriptor }) => (descriptor, to),
+    };
+    PendingDownload {
+        name: descriptor.name.clone(),
+        remaining: std::fs::metadata(to)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0)
+            .pipe(|present| descriptor.size.saturating_sub(present)),
+    }
+}
+
+/// the journal is keyed by the file name, which is the archive name
+fn journal_key(path: &std::path::Path) -> Result<String> {
+    path.file_name()
+        .with_context(|| format!("[{}] has no file name", path.display()))
+        .map(|name| name.to_string_lossy().to_string())
+}
+
+#[instrument(skip(throttle, journal))]
+pub async fn stream_file(
+    from: HumanUrl,
+    to: PathBuf,
+    expected_size: u64,
+    expected_hash: String,
+    throttle: Arc<Throttle>,
+    journal: Arc<DownloadJournal>,
+) -> Result<PathBuf> {
+    let name = journal_key(&to)?;
+    backend::download_backend()
+        .download(DownloadRequest {
+            name: name.clone(),
+            from,
+            to,
+            expected_size,
+            expected_hash,
+            throttle,
+            journal: journal.clone(),
+        })
+        .await
+        .tap(|result| journal.record(&name, result))
+}
+
+async fn resume_or_stream_file(
+    name: &str,
+    from: HumanUrl,
+    to: PathBuf,
+    expected_size: u64,
+    expected_hash: String,
+    throttle: Arc<Throttle>,
+    journal: Arc<DownloadJournal>,
+) -> Result<PathBuf> {
+    let limit = throttle.for_url(&from);
+    let resume_from = journal.resume_offset(name, &to, expected_size);
+    let response = http_client()?
+        .get(from.to_string())
+        .pipe(|request| match resume_from {
+            0 => request,
+            offset => request.header(reqwest::header::RANGE, format!("bytes={offset}-")),
+        })
+        .send()
+        .await
+        .with_context(|| format!("making request to {from}"))?
+        .pipe(|response| challenge::check_response(response, name.ends_with(".html") || name.ends_with(".htm")))
+        .await?;
+    let resume_from = match (resume_from, response.status()) {
+        (0, _) => 0,
+        (offset, reqwest::StatusCode::PARTIAL_CONTENT) => offset.tap(|offset| tracing::info!(%name, %offset, "resuming interrupted download")),
+        (_, status) => {
+            tracing::debug!(%name, %status, "server does not support resuming, starting over");
+            0
+        }
+    };
     let target_file = tokio::fs::OpenOptions::new()
         .write(true)
         .create(true)
-        .truncate(true)
+        .pipe(|options| match resume_from {
+            0 => options.truncate(true),
+            _ => options.append(true),
+        })
         .open(&to)
         .map_with_context(|| format!("opening [{}]", to.display()))
         .await?;
-    let mut writer = &mut tracing::Span::current().wrap_async_write(expected_size, tokio::io::BufWriter::new(target_file));
-    let mut byte_stream = reqwest::get(from.to_string())
-        .await
-        .with_context(|| format!("making request to {from}"))?
-        .bytes_stream();
-    let mut downloaded = 0;
+    disk_space::preallocate(&target_file, expected_size)?;
+    let mut writer = &mut tracing::Span::current()
+        .tap(|span| span.pb_inc(resume_from))
+        .wrap_async_write(expected_size, tokio::io::BufWriter::new(target_file));
+    let mut byte_stream = response.bytes_stream();
+    let mut downloaded = resume_from;
+    let mut journaled = resume_from;
+    // the part downloaded before the interruption is not hashed here, resumed files are re-read once they are complete
+    let mut hasher = (resume_from == 0).then(|| xxhash_rust::xxh64::Xxh64::new(0));
+    journal.set(name, JournalEntry::InProgress { downloaded, expected_size })?;
     while let Some(chunk) = byte_stream.next().await {
         match chunk {
             Ok(chunk) => {
                 downloaded += chunk.len() as u64;
+                if let Some(hasher) = hasher.as_mut() {
+                    hasher.update(&chunk);
+                }
+                limit.acquire(chunk.len() as u64).await;
+                if downloaded - journaled >= journal::PROGRESS_INTERVAL {
+                    journaled = downloaded;
+                    journal.set(name, JournalEntry::InProgress { downloaded, expected_size })?;
+                }
 
                 tokio::io::copy(&mut chunk.as_ref(), &mut writer)
                     .await
@@ -209,13 +434,31 @@ pub async fn stream_file(from: HumanUrl, to: PathBuf, expected_size: u64) -> Res
     if downloaded != expected_size {
         anyhow::bail!("[{from}] download finished, but received unexpected size (expected [{expected_size}] bytes, downloaded [{downloaded} bytes])")
     }
-    Ok(to)
+    match hasher {
+        Some(hasher) => download_cache::compare_hash(hasher.finish(), &expected_hash)
+            .map(|_| download_cache::hash_cache::remember_hash(&to, &expected_hash))
+            .map(|_| to),
+        None => download_cache::validate_hash(to, expected_hash).await,
+    }
+    .with_context(|| format!("[{from}] download finished, but it is corrupted"))
 }
 impl Synchronizers {
     pub fn new(config: DownloadersConfig, games_config: GamesConfig) -> Result<Self> {
         Ok(Self {
             config: Arc::new(config.clone()),
-            cache: Arc::new(download_cache::DownloadCache::new(config.downloads_directory.clone()).context("building download cache")?),
+            cache: download_cache::DownloadCache::new(config.downloads_directory.clone())
+                .context("building download cache")?
+                .with_deep_verify_archives(config.deep_verify_archives)
+                .pipe(Arc::new),
+            throttle: Throttle::new(&config).pipe(Arc::new),
+            journal: DownloadJournal::load(&config.downloads_directory)
+                .context("loading download journal")?
+                .pipe(Arc::new),
+            mirrors: Mirrors::new(&config.mirrors)
+                .context("parsing mirrors")?
+                .pipe(Arc::new),
+            mirrors_used: Default::default(),
+            download_records: Default::default(),
             inner: DownloadersInner::new(config).context("building downloaders")?,
             game_synchronizers: Arc::new(get_game_file_source_synchronizers(games_config).context("building game file source synchronizers")?),
         })
@@ -223,19 +466,38 @@ impl Synchronizers {
 
     pub async fn prepare_sync_task(self, Archive { descriptor, state }: Archive) -> Result<SyncTask> {
         match state.clone() {
-            State::Nexus(nexus_state) => self
-                .inner
-                .nexus
-                .clone()
-                .context("nexus not configured")
-                .pipe(ready)
-                .and_then(|nexus| nexus.download(nexus::DownloadFileRequest::from_nexus_state(nexus_state)))
-                .await
-                .map(|url| DownloadTask {
-                    inner: (url, self.cache.download_output_path(descriptor.name.clone())),
-                    descriptor,
-                })
-                .map(SyncTask::from),
+            State::Nexus(nexus_state) => {
+                let request = nexus::DownloadFileRequest::from_nexus_state(nexus_state);
+                let website_url = request.nexus_website_url();
+                let configured = self.inner.nexus.is_some();
+                let manual = ManualDownloadTask {
+                    inner: (website_url.clone(), self.cache.download_output_path(descriptor.name.clone())),
+                    descriptor: descriptor.clone(),
+                };
+                self.inner
+                    .nexus
+                    .clone()
+                    .context("nexus not configured")
+                    .pipe(ready)
+                    .and_then(|nexus| nexus.download(request))
+                    .await
+                    .map(|url| DownloadTask {
+                        inner: (url, self.cache.download_output_path(descriptor.name.clone())),
+                        descriptor,
+                    })
+                    .map(SyncTask::from)
+                    .or_else(
+                        |error| match (!configured || nexus::is_premium_required(&error), self.config.nexus.wait_for_manual_downloads) {
+                            (true, true) => Ok(SyncTask::Manual(manual)),
+                            (true, false) => Err(error.context(format!(
+                                "Manual action is required:\n\nURL: {website_url}\nNexus only hands out download links to premium users (and needs \
+                                 `downloaders.nexus.api_key` either way), run `hoolamike handle-nxm` to be guided through downloading the remaining files \
+                                 from the website, set `downloaders.nexus.wait_for_manual_downloads` for the installation to wait for them"
+                            ))),
+                            (false, _) => Err(error),
+                        },
+                    )
+            }
             State::GoogleDrive(GoogleDriveState { id }) => crate::downloaders::google_drive::GoogleDriveDownloader::download(id, descriptor.size)
                 .await
                 .map(|url| DownloadTask {
@@ -256,13 +518,22 @@ impl Synchronizers {
                 })
                 .map(SyncTask::from),
 
-            State::Http(HttpState { url, headers: _ }) => url
-                .pipe(|url| DownloadTask {
-                    inner: (url, self.cache.download_output_path(descriptor.name.clone())),
-                    descriptor,
+            State::Http(HttpState { url, headers: _ }) => self
+                .mirrors
+                .resolve(url)
+                .await
+                .map(|ResolvedUrl { url, mirror }| {
+                    if let Some(mirror) = mirror {
+                        self.mirrors_used
+                            .lock()
+                            .insert(descriptor.name.clone(), mirror);
+                    }
+                    DownloadTask {
+                        inner: (url, self.cache.download_output_path(descriptor.name.clone())),
+                        descriptor,
+                    }
                 })
-                .pipe(SyncTask::from)
-                .pipe(Ok),
+                .map(SyncTask::from),
             State::WabbajackCDN(state) => WabbajackCDNDownloader::prepare_download(state)
                 .await
                 .context("fetching from wabbajack cdn")
@@ -275,22 +546,53 @@ impl Synchronizers {
             State::Mega(MegaState { url }) => Err(anyhow::anyhow!(
                 "Manual action is required:\n\nURL: {url}\nMega is not supported (yet?), please download the file manually"
             )),
-            State::MediaFire(MediaFireState { url }) => {
-                // it cannot be done
-                MediaFireDownloader::download(url.clone())
-                    .await
-                    .context("mediafire")
-                    .map(|url| DownloadTask {
-                        inner: (url, self.cache.download_output_path(descriptor.name.clone())),
-                        descriptor,
-                    })
-                    .map(SyncTask::from)
-                    .with_context(|| format!("Manual action is required:\n\nURL: {url}\nGo to the website and download the file(s) manually"))
-            }
+            State::MediaFire(MediaFireState { url }) => MediaFireDownloader::download(url.clone())
+                .await
+                .context("mediafire")
+                .map(|url| DownloadTask {
+                    inner: (url, self.cache.download_output_path(descriptor.name.clone())),
+                    descriptor,
+                })
+                .map(SyncTask::from)
+                .with_context(|| format!("Manual action is required:\n\nURL: {url}\nGo to the website and download the file(s) manually")),
+            State::ModDB(ModDBState { url, .. }) => ModDBDownloader::download(url.clone())
+                .await
+                .context("moddb")
+                .map(|url| DownloadTask {
+                    inner: (url, self.cache.download_output_path(descriptor.name.clone())),
+                    descriptor,
+                })
+                .map(SyncTask::from)
+                .with_context(|| format!("Manual action is required:\n\nURL: {url}\nGo to the website and download the file(s) manually")),
+            State::VectorPlexus(state) => VectorPlexusDownloader::file_page_url(&state).and_then(|url| {
+                Err(anyhow::anyhow!(
+                    "Manual action is required:\n\nURL: {url}\nVectorPlexus requires logging in, please download [{}] manually",
+                    state.ips4_file
+                ))
+            }),
+            State::Unknown(unknown) => Err(anyhow::anyhow!(
+                "Manual action is required:\n\nDownload source {unknown}\nplease download [{}] manually and put it in the downloads directory",
+                descriptor.name
+            )),
         }
         .with_context(|| format!("when preparing download for\n{state:#?}"))
     }
 
+    /// archives synchronized so far, sorted by name
+    pub fn download_records(&self) -> Vec<DownloadRecord> {
+        let mirrors_used = self.mirrors_used.lock();
+        self.download_records
+            .lock()
+            .iter()
+            .cloned()
+            .map(|record| DownloadRecord {
+                mirror: mirrors_used.get(&record.name).map(shareable_url),
+                ..record
+            })
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .collect()
+    }
+
     #[instrument(skip_all, fields(archives=%archives.len()))]
     pub async fn sync_downloads(self, archives: Vec<Archive>) -> TotalResult<WithArchiveDescriptor<PathBuf>> {
         let base_concurrency = num_cpus::get() * 2;
@@ -298,8 +600,10 @@ impl Synchronizers {
             pb.pb_set_length(archives.iter().map(|a| a.descriptor.size).sum());
             pb.pb_set_style(&io_progress_style());
         });
+        let tracker = ProgressTracker::new("sync_downloads", archives.iter().map(|a| a.descriptor.size).sum()).pipe(Arc::new);
 
         futures::stream::iter(archives)
+            .pipe(until_stopped)
             .map(|Archive { descriptor, state }| async {
                 match self
                     .cache
@@ -313,6 +617,7 @@ impl Synchronizers {
                 {
                     Ok(verified) => Ok(Either::Left(verified.tap(|verified| {
                         sync_downloads.pb_inc(verified.descriptor.size);
+                        tracker.advance(verified.descriptor.size);
                         tracing::debug!(?verified, "succesfully verified a file");
                     }))),
                     Err(message) => self
@@ -328,7 +633,20 @@ impl Synchronizers {
             .buffer_unordered(num_cpus::get())
             .collect::<Vec<_>>()
             .await
+            .pipe(|tasks| {
+                tasks
+                    .iter()
+                    .filter_map(|task| match task {
+                        Ok(Either::Right(task)) => Some(pending_download(task)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .pipe(|pending| disk_space::check_free_space(&self.config.downloads_directory, pending))
+                    .map(|_| tasks)
+                    .map_err(|error| vec![error])
+            })?
             .pipe(futures::stream::iter)
+            .pipe(until_stopped)
             .map_ok(|file| {
                 let name = match &file {
                     Either::Left(left) => left.descriptor.name.clone(),
@@ -336,27 +654,71 @@ impl Synchronizers {
                         SyncTask::MergeDownload(d) => d.descriptor.name.clone(),
                         SyncTask::Download(d) => d.descriptor.name.clone(),
                         SyncTask::Copy(d) => d.descriptor.name.clone(),
+                        SyncTask::Manual(d) => d.descriptor.name.clone(),
                     },
                 };
+                match &file {
+                    Either::Left(_) => match self.journal.get(&name) {
+                        Some(JournalEntry::Completed) | None => Ok(()),
+                        Some(_) => self.journal.set(&name, JournalEntry::Completed),
+                    },
+                    Either::Right(_) => self.journal.queue(&name),
+                }
+                .unwrap_or_else(|reason| tracing::warn!(%name, ?reason, "could not update the download journal"));
+                let source = match &file {
+                    Either::Left(_) => DownloadSource::AlreadyDownloaded,
+                    Either::Right(sync_task) => DownloadSource::from(sync_task),
+                };
+                let started = std::time::Instant::now();
 
                 match file {
                     Either::Left(exists) => exists.pipe(Ok).pipe(ready).boxed(),
                     Either::Right(sync_task) => match sync_task {
-                        SyncTask::MergeDownload(WithArchiveDescriptor { inner: (from, to), descriptor }) => {
-                            stream_merge_file(from.clone(), to.clone(), descriptor.size)
-                                .map_ok(|inner| WithArchiveDescriptor { inner, descriptor })
-                                .map(move |res| res.with_context(|| format!("when downloading [{from:?} -> {to:?}]")))
-                                .instrument(sync_downloads.clone())
-                                .boxed()
-                        }
-                        SyncTask::Download(WithArchiveDescriptor { inner: (from, to), descriptor }) => stream_file(from.clone(), to.clone(), descriptor.size)
+                        SyncTask::MergeDownload(WithArchiveDescriptor { inner: (from, to), descriptor }) => stream_merge_file(
+                            from.clone(),
+                            to.clone(),
+                            descriptor.size,
+                            descriptor.hash.clone(),
+                            self.throttle.clone(),
+                            self.journal.clone(),
+                        )
+                        .map_ok(|inner| WithArchiveDescriptor { inner, descriptor })
+                        .map(move |res| res.with_context(|| format!("when downloading [{} parts -> {to:?}]", from.len())))
+                        .instrument(sync_downloads.clone())
+                        .boxed(),
+                        SyncTask::Download(WithArchiveDescriptor { inner: (from, to), descriptor }) => stream_file(
+                            from.clone(),
+                            to.clone(),
+                            descriptor.size,
+                            descriptor.hash.clone(),
+                            self.throttle.clone(),
+                            self.journal.clone(),
+                        )
+                        .map_ok(|inner| WithArchiveDescriptor { inner, descriptor })
+                        .map(move |res| res.with_context(|| format!("when downloading [{from} -> {to:?}]")))
+                        .instrument(sync_downloads.clone())
+                        .boxed(),
+                        SyncTask::Copy(WithArchiveDescriptor { inner: (from, to), descriptor }) => copy_local_file(from.clone(), to.clone(), descriptor.size)
+                            .inspect({
+                                cloned![name];
+                                let journal = self.journal.clone();
+                                move |res| journal.record(&name, res)
+                            })
                             .map_ok(|inner| WithArchiveDescriptor { inner, descriptor })
-                            .map(move |res| res.with_context(|| format!("when downloading [{from} -> {to:?}]")))
+                            .map(move |res| res.with_context(|| format!("when when copying [{from:?} -> {to:?}]")))
                             .instrument(sync_downloads.clone())
                             .boxed(),
-                        SyncTask::Copy(WithArchiveDescriptor { inner: (from, to), descriptor }) => copy_local_file(from.clone(), to.clone(), descriptor.size)
+                        SyncTask::Manual(WithArchiveDescriptor {
+                            inner: (website, to),
+                            descriptor,
+                        }) => wait_for_manual_download(website.clone(), to, descriptor.clone(), self.cache.clone())
+                            .inspect({
+                                cloned![name];
+                                let journal = self.journal.clone();
+                                move |res| journal.record(&name, res)
+                            })
                             .map_ok(|inner| WithArchiveDescriptor { inner, descriptor })
-                            .map(move |res| res.with_context(|| format!("when when copying [{from:?} -> {to:?}]")))
+                            .map(move |res| res.with_context(|| format!("when waiting for a manual download from [{website}]")))
                             .instrument(sync_downloads.clone())
                             .boxed(),
                     },
@@ -366,9 +728,18 @@ impl Synchronizers {
                     move |message| tracing::debug!(?name, ?message)
                 })
                 .inspect_ok({
-                    cloned![sync_downloads];
+                    cloned![sync_downloads, tracker];
+                    let download_records = self.download_records.clone();
                     move |res| {
                         sync_downloads.pb_inc(res.descriptor.size);
+                        tracker.advance(res.descriptor.size);
+                        download_records.lock().push(DownloadRecord {
+                            name: res.descriptor.name.clone(),
+                            size: res.descriptor.size,
+                            source,
+                            mirror: None,
+                            seconds: started.elapsed().as_secs_f64(),
+                        });
                         tracing::debug!(name, "[OK]");
                     }
                 })
@@ -380,5 +751,11 @@ impl Synchronizers {
             .try_buffer_unordered(base_concurrency * 2)
             .multi_error_collect()
             .await
+            .tap(|_| {
+                self.mirrors_used
+                    .lock()
+                    .iter()
+                    .for_each(|(archive, mirror)| tracing::info!(%archive, %mirror, "downloaded from a mirror"))
+            })
     }
 }
@@ -0,0 +1,176 @@
+use {
+    super::{journal::DownloadJournal, resume_or_stream_file, throttle::Throttle},
+    crate::{
+        config_file::DownloadBackendConfig,
+        install_modlist::download_cache::{validate_file_size, validate_hash},
+        modlist_json::HumanUrl,
+        progress_bars_v2::io_progress_style,
+    },
+    anyhow::{Context, Result},
+    futures::{future::BoxFuture, FutureExt, TryFutureExt},
+    once_cell::sync::Lazy,
+    parking_lot::RwLock,
+    std::{path::PathBuf, sync::Arc},
+    tap::prelude::*,
+    tracing_indicatif::span_ext::IndicatifSpanExt,
+};
+
+#[derive(Debug)]
+pub struct DownloadRequest {
+    /// name of the archive, also the journal key
+    pub name: String,
+    pub from: HumanUrl,
+    pub to: PathBuf,
+    pub expected_size: u64,
+    pub expected_hash: String,
+    pub throttle: Arc<Throttle>,
+    pub journal: Arc<DownloadJournal>,
+}
+
+/// transfers the bytes of a single download, resolving urls and recording the outcome in the journal happens around it.
+/// a backend has to verify the size and hash of what it downloaded before reporting success
+pub trait DownloadBackend: Send + Sync + std::fmt::Debug {
+    fn download(&self, request: DownloadRequest) -> BoxFuture<'_, Result<PathBuf>>;
+}
+
+/// resumes, throttles and hashes while downloading
+#[derive(Debug)]
+pub struct Builtin;
+
+impl DownloadBackend for Builtin {
+    fn download(
+        &self,
+        DownloadRequest {
+            name,
+            from,
+            to,
+            expected_size,
+            expected_hash,
+            throttle,
+            journal,
+        }: DownloadRequest,
+    ) -> BoxFuture<'_, Result<PathBuf>> {
+        async move { resume_or_stream_file(&name, from, to, expected_size, expected_hash, throttle, journal).await }.boxed()
+    }
+}
+
+#[derive(Debug)]
+pub struct Aria2c {
+    pub path: PathBuf,
+    pub connections: Option<u8>,
+    pub extra_args: Vec<String>,
+}
+
+impl Aria2c {
+    /// `--continue` picks up the `.aria2` control file left behind by an interrupted run
+    fn command(&self, DownloadRequest { from, to, .. }: &DownloadRequest) -> Result<tokio::process::Command> {
+        let directory = to
+            .parent()
+            .with_context(|| format!("[{}] has no parent directory", to.display()))?;
+        let file_name = to
+            .file_name()
+            .with_context(|| format!("[{}] has no file name", to.display()))?;
+        tokio::process::Command::new(&self.path)
+            .tap_mut(|command| {
+                command
+                    .arg("--continue=true")
+                    .arg("--allow-overwrite=true")
+                    .arg("--auto-file-renaming=false")
+                    .arg("--file-allocation=none")
+                    .arg("--console-log-level=warn")
+                    .arg("--summary-interval=0")
+                    .arg(format!("--dir={}", directory.display()))
+                    .arg(format!("--out={}", file_name.to_string_lossy()));
+                if let Some(connections) = self.connections {
+                    command
+                        .arg(format!("--split={connections}"))
+                        .arg(format!("--max-connection-per-server={connections}"));
+                }
+                command
+                    .args(&self.extra_args)
+                    .arg(from.to_string())
+                    .kill_on_drop(true);
+            })
+            .pipe(Ok)
+    }
+}
+
+impl DownloadBackend for Aria2c {
+    fn download(&self, request: DownloadRequest) -> BoxFuture<'_, Result<PathBuf>> {
+        async move {
+            let span = tracing::Span::current().tap(|span| {
+                span.pb_set_style(&io_progress_style());
+                span.pb_set_length(request.expected_size);
+            });
+            let output = self
+                .command(&request)?
+                .output()
+                .await
+                .with_context(|| format!("running [{}]", self.path.display()))?;
+            if !output.status.success() {
+                anyhow::bail!("aria2c exited with [{}]:\n{}", output.status, String::from_utf8_lossy(&output.stdout));
+            }
+            span.pb_inc(request.expected_size);
+            validate_file_size(request.to.clone(), request.expected_size)
+                .and_then(|to| validate_hash(to, request.expected_hash.clone()))
+                .await
+                .with_context(|| format!("[{}] download finished, but it is corrupted", request.from))
+        }
+        .boxed()
+    }
+}
+
+/// downloads are started deep inside the synchronizers and the nxm handler, far away from the config
+static DOWNLOAD_BACKEND: Lazy<RwLock<Arc<dyn DownloadBackend>>> = Lazy::new(|| RwLock::new(Arc::new(Builtin)));
+
+pub fn configure_download_backend(config: DownloadBackendConfig) {
+    *DOWNLOAD_BACKEND.write() = match config {
+        DownloadBackendConfig::Builtin => Arc::new(Builtin) as Arc<dyn DownloadBackend>,
+        DownloadBackendConfig::Aria2c { path, connections, extra_args } => {
+            Arc::new(Aria2c { path, connections, extra_args }).tap(|aria2c| tracing::info!(?aria2c, "downloads will go through aria2c"))
+        }
+    };
+}
+
+pub fn download_backend() -> Arc<dyn DownloadBackend> {
+    DOWNLOAD_BACKEND.read().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::str::FromStr};
+
+    #[test]
+    fn test_aria2c_arguments() -> Result<()> {
+        let aria2c = Aria2c {
+            path: "aria2c".into(),
+            connections: Some(8),
+            extra_args: vec!["--max-overall-download-limit=2M".into()],
+        };
+        let command = aria2c.command(&DownloadRequest {
+            name: "some-mod.7z".into(),
+            from: HumanUrl::from_str("https://files.example.com/some-mod.7z")?,
+            to: "/downloads/some-mod.7z".into(),
+            expected_size: 10,
+            expected_hash: "aGFzaA==".into(),
+            throttle: Default::default(),
+            journal: DownloadJournal::load(tempfile::tempdir()?.path())?.pipe(Arc::new),
+        })?;
+        let arguments = command
+            .as_std()
+            .get_args()
+            .map(|argument| argument.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert!(arguments.contains(&"--dir=/downloads".to_string()));
+        assert!(arguments.contains(&"--out=some-mod.7z".to_string()));
+        assert!(arguments.contains(&"--split=8".to_string()));
+        assert_eq!(
+            arguments[arguments.len() - 2..],
+            [
+                "--max-overall-download-limit=2M".to_string(),
+                "https://files.example.com/some-mod.7z".to_string()
+            ]
+        );
+        Ok(())
+    }
+}
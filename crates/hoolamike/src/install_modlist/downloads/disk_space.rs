@@ -0,0 +1,107 @@
+//! downloads fail before they start when they would not fit, instead of running out of space halfway through a modlist
+use {
+    anyhow::{Context, Result},
+    indicatif::HumanBytes,
+    itertools::Itertools,
+    std::{cmp::Reverse, path::Path},
+};
+
+/// how many of the biggest downloads are listed when they don't fit
+const BREAKDOWN_SIZE: usize = 10;
+
+#[derive(Debug)]
+pub struct PendingDownload {
+    pub name: String,
+    /// bytes still to be written, a partially downloaded file already takes up the rest
+    pub remaining: u64,
+}
+
+pub fn check_free_space(downloads_directory: &Path, pending: Vec<PendingDownload>) -> Result<()> {
+    fs2::available_space(downloads_directory)
+        .with_context(|| format!("checking free space in [{}]", downloads_directory.display()))
+        .and_then(|available| fits(downloads_directory, pending, available))
+}
+
+fn fits(downloads_directory: &Path, pending: Vec<PendingDownload>, available: u64) -> Result<()> {
+    let needed = pending.iter().map(|pending| pending.remaining).sum::<u64>();
+    if needed <= available {
+        tracing::info!(needed=%HumanBytes(needed), available=%HumanBytes(available), "enough free space for the downloads");
+        return Ok(());
+    }
+    let breakdown = pending
+        .iter()
+        .sorted_by_key(|pending| Reverse(pending.remaining))
+        .take(BREAKDOWN_SIZE)
+        .map(|pending| format!("  {:>12}  {}", HumanBytes(pending.remaining).to_string(), pending.name))
+        .join("\n");
+    anyhow::bail!(
+        "not enough free space for the downloads in [{}]\n  needed:    {}\n  available: {}\n  missing:   {}\n\n[{}] archives left to download, the biggest \
+         ones:\n{breakdown}",
+        downloads_directory.display(),
+        HumanBytes(needed),
+        HumanBytes(available),
+        HumanBytes(needed - available),
+        pending.len(),
+    )
+}
+
+/// reserves the whole file up front (less fragmentation, no ENOSPC halfway through),
+/// the size of the file stays the same so interrupted downloads can still be resumed
+#[cfg(target_os = "linux")]
+pub fn preallocate(file: &tokio::fs::File, size: u64) -> Result<()> {
+    use std::os::fd::AsRawFd;
+    if size == 0 {
+        return Ok(());
+    }
+    // SAFETY: the descriptor belongs to a file which stays open for the whole call
+    match unsafe { libc::fallocate(file.as_raw_fd(), libc::FALLOC_FL_KEEP_SIZE, 0, size as libc::off_t) } {
+        0 => Ok(()),
+        _ => match std::io::Error::last_os_error() {
+            error if error.raw_os_error() == Some(libc::ENOSPC) => Err(error).with_context(|| format!("not enough free space for [{}]", HumanBytes(size))),
+            error => {
+                tracing::debug!(?error, "filesystem does not support preallocation");
+                Ok(())
+            }
+        },
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn preallocate(_file: &tokio::fs::File, _size: u64) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(name: &str, remaining: u64) -> PendingDownload {
+        PendingDownload {
+            name: name.to_string(),
+            remaining,
+        }
+    }
+
+    #[test]
+    fn test_breakdown_lists_biggest_first() {
+        let error = fits(Path::new("/downloads"), vec![pending("small.7z", 10), pending("big.7z", 1000)], 100)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("[2] archives left"), "{error}");
+        assert!(error.find("big.7z").unwrap() < error.find("small.7z").unwrap(), "{error}");
+    }
+
+    #[test]
+    fn test_enough_space() {
+        assert!(fits(Path::new("/downloads"), vec![pending("a.7z", 50), pending("b.7z", 50)], 100).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_preallocate_keeps_size() -> Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let handle = tokio::fs::File::create(file.path()).await?;
+        preallocate(&handle, 1024 * 1024)?;
+        assert_eq!(handle.metadata().await?.len(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+//! remembers what happened to every download, so that an install interrupted by a crash picks up where it stopped
+use {
+    crate::json_store::JsonStore,
+    anyhow::Result,
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeMap, path::Path},
+    tap::prelude::*,
+};
+
+pub const JOURNAL_FILE_NAME: &str = ".hoolamike-downloads.json";
+
+/// how often the byte offset of a running download is written down
+pub const PROGRESS_INTERVAL: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum JournalEntry {
+    Queued,
+    InProgress { downloaded: u64, expected_size: u64 },
+    Completed,
+    Failed { reason: String },
+}
+
+/// keyed by the archive name, which is also the name of the file in the downloads directory
+#[derive(Debug)]
+pub struct DownloadJournal {
+    entries: JsonStore<BTreeMap<String, JournalEntry>>,
+}
+
+impl DownloadJournal {
+    pub fn load(downloads_directory: &Path) -> Result<Self> {
+        Ok(Self {
+            entries: JsonStore::load(downloads_directory.join(JOURNAL_FILE_NAME), "download journal"),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<JournalEntry> {
+        self.entries.read(|entries| entries.get(name).cloned())
+    }
+
+    pub fn set(&self, name: &str, entry: JournalEntry) -> Result<()> {
+        self.entries.update(|entries| {
+            entries.insert(name.to_string(), entry);
+        })
+    }
+
+    /// journal problems are not worth failing a download for
+    pub fn record<T>(&self, name: &str, result: &Result<T>) {
+        match result {
+            Ok(_) => JournalEntry::Completed,
+            Err(reason) => JournalEntry::Failed { reason: format!("{reason:#}") },
+        }
+        .pipe(|entry| self.set(name, entry))
+        .unwrap_or_else(|reason| tracing::warn!(%name, ?reason, "could not update the download journal"))
+    }
+
+    /// an interrupted download keeps its journal entry, so it is not reset back to queued
+    pub fn queue(&self, name: &str) -> Result<()> {
+        match self.get(name) {
+            Some(JournalEntry::InProgress { .. } | JournalEntry::Failed { .. }) => Ok(()),
+            _ => self.set(name, JournalEntry::Queued),
+        }
+    }
+
+    /// how many bytes of `path` can be kept, only files whose download was started by hoolamike are resumed,
+    /// anything else in the downloads directory might be a different version of the archive
+    pub fn resume_offset(&self, name: &str, path: &Path, expected_size: u64) -> u64 {
+        match self.get(name) {
+            Some(JournalEntry::InProgress { .. } | JournalEntry::Failed { .. }) => std::fs::metadata(path)
+                .map(|metadata| metadata.len())
+                .ok()
+                .filter(|&downloaded| downloaded < expected_size)
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_survives_reload() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let journal = DownloadJournal::load(directory.path())?;
+        journal.set(
+            "some-mod.7z",
+            JournalEntry::InProgress {
+                downloaded: 10,
+                expected_size: 100,
+            },
+        )?;
+        journal.set("other-mod.7z", JournalEntry::Completed)?;
+        journal.queue("some-mod.7z")?;
+        journal.queue("third-mod.7z")?;
+        drop(journal);
+
+        let reloaded = DownloadJournal::load(directory.path())?;
+        assert_eq!(
+            reloaded.get("some-mod.7z"),
+            Some(JournalEntry::InProgress {
+                downloaded: 10,
+                expected_size: 100
+            })
+        );
+        assert_eq!(reloaded.get("other-mod.7z"), Some(JournalEntry::Completed));
+        assert_eq!(reloaded.get("third-mod.7z"), Some(JournalEntry::Queued));
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_started_downloads_are_resumed() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let journal = DownloadJournal::load(directory.path())?;
+        for name in ["started.7z", "unknown.7z", "finished.7z"] {
+            std::fs::write(directory.path().join(name), [0u8; 10])?;
+        }
+        journal.set(
+            "started.7z",
+            JournalEntry::Failed {
+                reason: "connection reset".into(),
+            },
+        )?;
+        journal.set("finished.7z", JournalEntry::Completed)?;
+
+        assert_eq!(journal.resume_offset("started.7z", &directory.path().join("started.7z"), 100), 10);
+        assert_eq!(journal.resume_offset("started.7z", &directory.path().join("started.7z"), 10), 0);
+        assert_eq!(journal.resume_offset("unknown.7z", &directory.path().join("unknown.7z"), 100), 0);
+        assert_eq!(journal.resume_offset("finished.7z", &directory.path().join("finished.7z"), 100), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use {
+    crate::{config_file::DownloadersConfig, modlist_json::HumanUrl},
+    std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    tap::prelude::*,
+};
+
+/// refills at `rate` bytes per second and holds at most one second worth of bytes,
+/// a chunk bigger than what is available puts the bucket in debt which the next callers wait out
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(bytes_per_second: u64) -> Self {
+        let rate = bytes_per_second.max(1) as f64;
+        Self {
+            rate,
+            state: Mutex::new(BucketState {
+                tokens: rate,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// takes `bytes` out of the bucket, returning how long the caller has to wait before using them
+    fn take(&self, bytes: u64, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now
+            .saturating_duration_since(state.refilled_at)
+            .as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.rate) - bytes as f64;
+        state.refilled_at = now;
+        match state.tokens < 0. {
+            true => Duration::from_secs_f64(-state.tokens / self.rate),
+            false => Duration::ZERO,
+        }
+    }
+
+    pub async fn acquire(&self, bytes: u64) {
+        match self.take(bytes, Instant::now()) {
+            Duration::ZERO => {}
+            wait => tokio::time::sleep(wait).await,
+        }
+    }
+}
+
+/// buckets which apply to a single download
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthLimit(Vec<Arc<TokenBucket>>);
+
+impl BandwidthLimit {
+    pub async fn acquire(&self, bytes: u64) {
+        for bucket in self.0.iter() {
+            bucket.acquire(bytes).await
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Throttle {
+    global: Option<Arc<TokenBucket>>,
+    per_host: BTreeMap<String, Arc<TokenBucket>>,
+}
+
+fn kilobytes(kilobytes_per_second: u64) -> Arc<TokenBucket> {
+    TokenBucket::new(kilobytes_per_second.saturating_mul(1024)).pipe(Arc::new)
+}
+
+impl Throttle {
+    pub fn new(
+        DownloadersConfig {
+            max_bandwidth,
+            max_bandwidth_per_host,
+            ..
+        }: &DownloadersConfig,
+    ) -> Self {
+        Self {
+            global: max_bandwidth.map(kilobytes),
+            per_host: max_bandwidth_per_host
+                .iter()
+                .map(|(host, limit)| (host.trim_start_matches('.').to_lowercase(), kilobytes(*limit)))
+                .collect(),
+        }
+    }
+
+    /// the most specific host override wins, `cdn.example.com` is matched by both `cdn.example.com` and `example.com`
+    fn host_bucket(&self, host: &str) -> Option<Arc<TokenBucket>> {
+        let host = host.to_lowercase();
+        self.per_host
+            .iter()
+            .filter(|(pattern, _)| host == **pattern || host.ends_with(&format!(".{pattern}")))
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, bucket)| bucket.clone())
+    }
+
+    pub fn for_url(&self, url: &HumanUrl) -> BandwidthLimit {
+        let url: &url::Url = url.as_ref();
+        self.global
+            .iter()
+            .cloned()
+            .chain(url.host_str().and_then(|host| self.host_bucket(host)))
+            .collect::<Vec<_>>()
+            .pipe(BandwidthLimit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::str::FromStr};
+
+    #[test]
+    fn test_bucket_goes_into_debt() {
+        let bucket = TokenBucket::new(1000);
+        let start = Instant::now();
+        assert_eq!(bucket.take(1000, start), Duration::ZERO);
+        assert_eq!(bucket.take(500, start), Duration::from_millis(500));
+        // half a second later the debt is paid off, the next chunk waits only for itself
+        assert_eq!(bucket.take(250, start + Duration::from_millis(500)), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_bucket_does_not_accumulate_past_one_second() {
+        let bucket = TokenBucket::new(1000);
+        let start = Instant::now();
+        assert_eq!(bucket.take(2000, start + Duration::from_secs(60)), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_most_specific_host_wins() -> anyhow::Result<()> {
+        let throttle = Throttle::new(&DownloadersConfig {
+            max_bandwidth: Some(100),
+            max_bandwidth_per_host: [("nexus-cdn.com".to_string(), 10), ("cf-files.nexus-cdn.com".to_string(), 20)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        });
+        let limit = throttle.for_url(&HumanUrl::from_str("https://cf-files.nexus-cdn.com/file.7z")?);
+        assert_eq!(limit.0.len(), 2);
+        assert_eq!(limit.0[1].rate, 20. * 1024.);
+        assert_eq!(
+            throttle
+                .for_url(&HumanUrl::from_str("https://other.nexus-cdn.com/file.7z")?)
+                .0[1]
+                .rate,
+            10. * 1024.
+        );
+        assert_eq!(
+            throttle
+                .for_url(&HumanUrl::from_str("https://notnexus-cdn.com/file.7z")?)
+                .0
+                .len(),
+            1
+        );
+        Ok(())
+    }
+}
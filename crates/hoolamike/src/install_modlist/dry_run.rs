@@ -0,0 +1,168 @@
+//! `hoolamike install --dry-run` reports what an installation would do, nothing is downloaded and the installation directory is left alone
+use {
+    super::download_cache::DownloadCache,
+    crate::{
+        helpers::human_readable_size,
+        modlist_json::{Archive, Directive, DirectiveKind, DownloadKind},
+        progress_bars_v2::{events::print_report, io_progress_style},
+    },
+    futures::{StreamExt, TryFutureExt},
+    itertools::Itertools,
+    std::{collections::BTreeMap, sync::Arc},
+    tabled::{settings::Style, Tabled},
+    tap::prelude::*,
+    tracing::instrument,
+    tracing_indicatif::span_ext::IndicatifSpanExt,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct DirectiveKindSummary {
+    pub count: usize,
+    pub size: u64,
+}
+
+pub fn summarize_directives(directives: impl Iterator<Item = (DirectiveKind, u64)>) -> BTreeMap<DirectiveKind, DirectiveKindSummary> {
+    directives.fold(BTreeMap::new(), |acc, (kind, size)| {
+        acc.tap_mut(|acc| {
+            let summary = acc
+                .entry(kind)
+                .or_insert_with(DirectiveKindSummary::default);
+            summary.count += 1;
+            summary.size += size;
+        })
+    })
+}
+
+#[derive(Debug)]
+pub struct MissingArchive {
+    pub name: String,
+    pub source: DownloadKind,
+    pub size: u64,
+    pub reason: String,
+}
+
+/// same checks as before downloading, including the hash cache, but the failures are only collected
+#[instrument(skip_all, fields(archives=%archives.len()))]
+async fn find_missing_archives(cache: Arc<DownloadCache>, archives: Vec<Archive>) -> Vec<MissingArchive> {
+    let verify = tracing::Span::current().tap(|pb| {
+        pb.pb_set_style(&io_progress_style());
+        pb.pb_set_length(archives.iter().map(|archive| archive.descriptor.size).sum());
+    });
+    futures::stream::iter(archives)
+        .map(|Archive { descriptor, state }| {
+            let verify = verify.clone();
+            cache
+                .clone()
+                .verify(descriptor.clone())
+                .map_ok(move |_| verify.pb_inc(descriptor.size))
+                .map_err(move |reason| MissingArchive {
+                    name: descriptor.name,
+                    source: state.kind(),
+                    size: descriptor.size,
+                    reason: format!("{reason:#}"),
+                })
+        })
+        .buffer_unordered(num_cpus::get())
+        .filter_map(|verified| verified.err().pipe(std::future::ready))
+        .collect::<Vec<_>>()
+        .await
+        .tap_mut(|missing| missing.sort_by(|a, b| a.name.cmp(&b.name)))
+}
+
+#[derive(Tabled)]
+struct DirectiveKindRow {
+    kind: String,
+    directives: usize,
+    size: String,
+}
+
+#[derive(Tabled)]
+struct MissingArchiveRow {
+    name: String,
+    source: String,
+    size: String,
+    reason: String,
+}
+
+pub async fn dry_run(cache: Arc<DownloadCache>, archives: Vec<Archive>, directives: Vec<Directive>) -> anyhow::Result<()> {
+    let archive_count = archives.len();
+    let missing = find_missing_archives(cache, archives).await;
+    let directives = directives
+        .iter()
+        .map(|directive| (directive.directive_kind(), directive.size()))
+        .pipe(summarize_directives);
+    let total = directives
+        .values()
+        .fold(DirectiveKindSummary::default(), |acc, summary| DirectiveKindSummary {
+            count: acc.count + summary.count,
+            size: acc.size + summary.size,
+        });
+
+    directives
+        .iter()
+        .map(|(kind, summary)| DirectiveKindRow {
+            kind: kind.to_string(),
+            directives: summary.count,
+            size: human_readable_size(summary.size),
+        })
+        .chain(std::iter::once(DirectiveKindRow {
+            kind: "total".to_string(),
+            directives: total.count,
+            size: human_readable_size(total.size),
+        }))
+        .pipe(tabled::Table::new)
+        .with(Style::modern())
+        .pipe(print_report);
+    print_report(format_args!("total bytes to write: {} ({} bytes)", human_readable_size(total.size), total.size));
+
+    match missing.is_empty() {
+        true => print_report(format_args!("all [{archive_count}] archives are downloaded and verified")),
+        false => {
+            missing
+                .iter()
+                .map(|missing| MissingArchiveRow {
+                    name: missing.name.clone(),
+                    source: missing.source.to_string(),
+                    size: human_readable_size(missing.size),
+                    reason: missing.reason.clone(),
+                })
+                .pipe(tabled::Table::new)
+                .with(Style::modern())
+                .pipe(print_report);
+            print_report(format_args!(
+                "[{}/{archive_count}] archives are missing, [{}] left to download",
+                missing.len(),
+                missing
+                    .iter()
+                    .map(|missing| missing.size)
+                    .sum::<u64>()
+                    .pipe(human_readable_size)
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_directives() {
+        let summary = summarize_directives(
+            [
+                (DirectiveKind::FromArchive, 10),
+                (DirectiveKind::InlineFile, 1),
+                (DirectiveKind::FromArchive, 20),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(
+            summary.into_iter().collect_vec(),
+            vec![
+                (DirectiveKind::FromArchive, DirectiveKindSummary { count: 2, size: 30 }),
+                (DirectiveKind::InlineFile, DirectiveKindSummary { count: 1, size: 1 }),
+            ]
+        );
+    }
+}
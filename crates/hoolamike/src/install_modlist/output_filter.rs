@@ -0,0 +1,139 @@
+//! `--only "mods/**" --exclude "*.dds"` installs a part of the modlist, for staging an installation on a small disk or bisecting a broken file
+use {
+    super::directives::{expected_output, remapped_inline_file::wabbajack_consts::BSA_CREATION_DIR},
+    crate::{
+        modlist_json::{
+            directive::create_bsa_directive::{CreateBSADirective, CreateBSADirectiveKind},
+            Directive,
+        },
+        utils::MaybeWindowsPath,
+    },
+    anyhow::{Context, Result},
+    globset::{GlobBuilder, GlobSet, GlobSetBuilder},
+    std::{collections::BTreeSet, path::Path},
+};
+
+/// matched case-insensitively against `/` separated paths relative to the installation directory
+#[derive(Debug)]
+pub struct OutputFilter {
+    /// everything is included when empty
+    only: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    patterns
+        .iter()
+        .try_fold(GlobSetBuilder::new(), |mut builder, pattern| {
+            GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .literal_separator(false)
+                .build()
+                .with_context(|| format!("invalid glob [{pattern}]"))
+                .map(|glob| {
+                    builder.add(glob);
+                    builder
+                })
+        })
+        .and_then(|builder| builder.build().context("building glob set"))
+}
+
+fn bsa_temp_id(directive: &Directive) -> Option<&str> {
+    match directive {
+        Directive::CreateBSA(CreateBSADirective::Bsa(CreateBSADirectiveKind { temp_id, .. })) => Some(temp_id),
+        Directive::CreateBSA(CreateBSADirective::Ba2(CreateBSADirectiveKind { temp_id, .. })) => Some(temp_id),
+        _ => None,
+    }
+}
+
+/// files packed into a bsa are first written to `TEMP_BSA_FILES/<temp id>/...`
+pub(crate) fn staged_for_bsa(to: &Path) -> Option<String> {
+    BSA_CREATION_DIR.with(|bsa_creation_dir| {
+        to.strip_prefix(bsa_creation_dir)
+            .ok()
+            .and_then(|staged| staged.iter().next())
+            .map(|temp_id| temp_id.to_string_lossy().to_string())
+    })
+}
+
+impl OutputFilter {
+    pub fn new(only: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            only: match only.is_empty() {
+                true => None,
+                false => Some(glob_set(only)?),
+            },
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    pub fn matches(&self, path: &MaybeWindowsPath) -> bool {
+        let path = path.0.replace('\\', "/");
+        self.only
+            .as_ref()
+            .map(|only| only.is_match(&path))
+            .unwrap_or(true)
+            && !self.exclude.is_match(&path)
+    }
+
+    /// the files staged for a bsa go wherever the bsa goes, a bsa cannot be created with some of its files missing
+    pub fn filter_directives(&self, directives: Vec<Directive>) -> Vec<Directive> {
+        if self.only.is_none() && self.exclude.is_empty() {
+            return directives;
+        }
+        let included_bsas = directives
+            .iter()
+            .filter_map(|directive| bsa_temp_id(directive).filter(|_| self.matches(&expected_output(directive).2)))
+            .map(ToOwned::to_owned)
+            .collect::<BTreeSet<_>>();
+        directives
+            .into_iter()
+            .filter(|directive| {
+                let (_, _, to) = expected_output(directive);
+                match staged_for_bsa(&to.clone().into_path()) {
+                    Some(temp_id) => included_bsas.contains(&temp_id),
+                    None => self.matches(&to),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::modlist_json::directive::InlineFileDirective};
+
+    fn inline_file(to: &str) -> Directive {
+        Directive::InlineFile(InlineFileDirective {
+            hash: "AAAAAAAAAAA=".into(),
+            size: 1,
+            source_data_id: uuid::Uuid::nil(),
+            to: MaybeWindowsPath::new(to),
+        })
+    }
+
+    #[test]
+    fn test_only_and_exclude() -> Result<()> {
+        let filter = OutputFilter::new(&["mods/**".into()], &["*.dds".into()])?;
+        assert!(filter.matches(&MaybeWindowsPath::new(r"Mods\Armor\meshes\helmet.nif")));
+        assert!(!filter.matches(&MaybeWindowsPath::new(r"mods\Armor\textures\helmet.dds")));
+        assert!(!filter.matches(&MaybeWindowsPath::new(r"profiles\Default\modlist.txt")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_staged_bsa_files_follow_their_bsa() -> Result<()> {
+        let kept = OutputFilter::new(&[], &["*.dds".into()])?
+            .filter_directives(vec![
+                inline_file(r"mods\Armor\textures\helmet.dds"),
+                inline_file(r"TEMP_BSA_FILES\abc\textures\helmet.dds"),
+                inline_file(r"mods\Armor\helmet.esp"),
+            ])
+            .iter()
+            .map(|directive| expected_output(directive).2 .0)
+            .collect::<Vec<_>>();
+        // no bsa directive for `abc` is part of the installation
+        assert_eq!(kept, vec![r"mods\Armor\helmet.esp".to_string()]);
+        Ok(())
+    }
+}
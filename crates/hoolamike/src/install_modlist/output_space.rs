@@ -0,0 +1,191 @@
+//! the installation fails before it starts when its output would not fit, instead of running out of space at 97%
+use {
+    super::directives::{archive_hash_path, expected_output, scheduling::scheduling},
+    crate::modlist_json::{Archive, Directive},
+    anyhow::{Context, Result},
+    indicatif::HumanBytes,
+    itertools::Itertools,
+    std::{
+        cmp::Reverse,
+        collections::{BTreeMap, BTreeSet},
+        path::{Path, PathBuf},
+    },
+    tap::prelude::*,
+};
+
+/// bytes still to be written, files which are already there only need the difference
+fn output_needed(installation_path: &Path, directives: &[Directive]) -> u64 {
+    directives
+        .iter()
+        .map(|directive| {
+            let (_, size, to) = expected_output(directive);
+            std::fs::metadata(installation_path.join(to.into_path()))
+                .map(|metadata| size.saturating_sub(metadata.len()))
+                .unwrap_or(size)
+        })
+        .sum()
+}
+
+/// nested archives are extracted to temporary files whole, a nested archive is at most as big as the archive it's in,
+/// and as many of them are extracted at once as there are directives running
+fn temp_needed(directives: &[Directive], archives: &[Archive]) -> u64 {
+    let with_nested_archives = directives
+        .iter()
+        .filter_map(archive_hash_path)
+        .filter(|archive_hash_path| archive_hash_path.path.len() > 1)
+        .map(|archive_hash_path| archive_hash_path.source_hash.as_str())
+        .collect::<BTreeSet<_>>();
+    archives
+        .iter()
+        .filter(|archive| with_nested_archives.contains(archive.descriptor.hash.as_str()))
+        .map(|archive| archive.descriptor.size)
+        .sorted_by_key(|size| Reverse(*size))
+        .take(scheduling().total_tasks())
+        .sum()
+}
+
+/// the installation directory does not have to exist yet
+fn existing_ancestor(path: &Path) -> Result<&Path> {
+    path.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .with_context(|| format!("no part of [{}] exists", path.display()))
+}
+
+#[cfg(unix)]
+type VolumeId = u64;
+
+#[cfg(not(unix))]
+type VolumeId = PathBuf;
+
+/// the device the path is on
+#[cfg(unix)]
+fn volume_id(path: &Path) -> Result<VolumeId> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path)
+        .map(|metadata| metadata.dev())
+        .with_context(|| format!("reading metadata of [{}]", path.display()))
+}
+
+/// there is no stable volume id outside of unix, the drive (or share) the path is on stands in for it
+#[cfg(not(unix))]
+fn volume_id(path: &Path) -> Result<VolumeId> {
+    std::path::absolute(path)
+        .with_context(|| format!("resolving [{}]", path.display()))
+        .and_then(|path| {
+            path.components()
+                .next()
+                .map(|root| PathBuf::from(root.as_os_str()))
+                .with_context(|| format!("no root in [{}]", path.display()))
+        })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Volume {
+    /// every directory which ends up on this volume
+    directories: Vec<PathBuf>,
+    needed: u64,
+    available: u64,
+}
+
+fn fits(volumes: &[Volume]) -> Result<()> {
+    let too_small = volumes
+        .iter()
+        .filter(|volume| volume.needed > volume.available)
+        .map(
+            |Volume {
+                 directories,
+                 needed,
+                 available,
+             }| {
+                format!(
+                    "  [{}]\n    needed:    {}\n    available: {}\n    missing:   {}",
+                    directories
+                        .iter()
+                        .map(|directory| directory.display())
+                        .join("], ["),
+                    HumanBytes(*needed),
+                    HumanBytes(*available),
+                    HumanBytes(needed - available),
+                )
+            },
+        )
+        .collect_vec();
+    match too_small.is_empty() {
+        true => Ok(()),
+        false => anyhow::bail!(
+            "not enough free space for the installation:\n{}\n\nfree up some space, or pass --force to install anyway (eg. when most of the files are already \
+             installed)",
+            too_small.join("\n")
+        ),
+    }
+}
+
+pub fn check_output_space(installation_path: &Path, temp_directory: &Path, directives: &[Directive], archives: &[Archive]) -> Result<()> {
+    [
+        (installation_path, output_needed(installation_path, directives)),
+        (temp_directory, temp_needed(directives, archives)),
+    ]
+    .into_iter()
+    .map(|(directory, needed)| {
+        existing_ancestor(directory).and_then(|existing| {
+            volume_id(existing).and_then(|device| {
+                fs2::available_space(existing)
+                    .with_context(|| format!("checking free space in [{}]", existing.display()))
+                    .map(|available| (device, (directory.to_owned(), needed, available)))
+            })
+        })
+    })
+    .collect::<Result<Vec<_>>>()?
+    .into_iter()
+    // the output and temporary files often end up on the same drive
+    .fold(BTreeMap::<VolumeId, Volume>::new(), |mut volumes, (device, (directory, needed, available))| {
+        let volume = volumes.entry(device).or_insert(Volume {
+            directories: vec![],
+            needed: 0,
+            available,
+        });
+        volume.directories.push(directory);
+        volume.needed += needed;
+        volumes
+    })
+    .into_values()
+    .collect_vec()
+    .pipe(|volumes| fits(&volumes))
+    .map(|_| tracing::info!("enough free space for the installation"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fits() {
+        let volume = |needed, available| Volume {
+            directories: vec!["/games/modlist".into(), ".hoolamike/TEMP_FILES".into()],
+            needed,
+            available,
+        };
+        assert!(fits(&[volume(100, 100)]).is_ok());
+        let error = fits(&[volume(100, 100), volume(300, 100)])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("[/games/modlist], [.hoolamike/TEMP_FILES]"), "{error}");
+        assert!(error.contains("--force"), "{error}");
+    }
+
+    #[test]
+    fn test_existing_files_only_need_the_difference() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        std::fs::write(directory.path().join("plugin.esp"), b"plugin")?;
+        let directive = |to: &str, size| {
+            Directive::InlineFile(crate::modlist_json::directive::InlineFileDirective {
+                hash: "AAAAAAAAAAA=".into(),
+                size,
+                source_data_id: uuid::Uuid::nil(),
+                to: crate::utils::MaybeWindowsPath::new(to),
+            })
+        };
+        assert_eq!(output_needed(directory.path(), &[directive("plugin.esp", 6), directive("missing.esp", 10)]), 10);
+        Ok(())
+    }
+}
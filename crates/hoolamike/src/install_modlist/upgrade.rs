@@ -0,0 +1,323 @@
+//! `hoolamike upgrade` installs a new version of a modlist over the previous one,
+//! only directives whose output changed are executed and only the archives they need are downloaded
+use {
+    super::{
+        configure,
+        directives::{archive_hash_path, expected_output, scheduling::Scheduling, DirectivesHandler, DirectivesHandlerConfig},
+        downloads::Synchronizers,
+        dry_run,
+        install_report::InstallReport,
+        output_filter::staged_for_bsa,
+        output_space,
+    },
+    crate::{
+        config_file::{HoolamikeConfig, InstallationConfig},
+        error::TotalResult,
+        modlist_json::Directive,
+        utils::spawn_rayon,
+        verify_cli::check_output,
+        wabbajack_file::WabbajackFile,
+    },
+    anyhow::Context,
+    futures::{StreamExt, TryStreamExt},
+    itertools::{Either, Itertools},
+    std::{
+        collections::BTreeSet,
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
+    tap::prelude::*,
+    tracing::{info, instrument, warn},
+};
+
+#[derive(Debug, Default)]
+pub struct UpgradePlan {
+    /// new directives, and the ones whose output is not the same as in the previous version
+    pub changed: Vec<Directive>,
+    pub unchanged: Vec<Directive>,
+    /// outputs of the previous version which are not part of the new one, relative to the installation directory
+    pub obsolete: Vec<PathBuf>,
+}
+
+/// the output path and the hash of its contents
+fn output_of(directive: &Directive) -> (PathBuf, String) {
+    expected_output(directive).pipe(|(hash, _, to)| (to.into_path(), hash))
+}
+
+pub fn plan_upgrade(previous: &[Directive], directives: Vec<Directive>) -> UpgradePlan {
+    let previous_outputs = previous.iter().map(output_of).collect::<BTreeSet<_>>();
+    let paths = directives
+        .iter()
+        .map(|directive| output_of(directive).0)
+        .collect::<BTreeSet<_>>();
+    let obsolete = previous_outputs
+        .iter()
+        .map(|(path, _)| path)
+        .filter(|path| !paths.contains(*path))
+        .unique()
+        .cloned()
+        .collect();
+    let (unchanged, changed) = directives
+        .into_iter()
+        .partition(|directive| previous_outputs.contains(&output_of(directive)));
+    UpgradePlan { changed, unchanged, obsolete }
+}
+
+/// files of the previous version which were removed or edited by hand have to be installed again,
+/// files packed into a bsa are only checked when they are kept on disk
+async fn missing_outputs(installation_path: &Path, unchanged: Vec<Directive>, stream_bsa_files_gigabytes: Option<u64>) -> (Vec<Directive>, usize) {
+    let (missing, unchanged): (Vec<_>, Vec<_>) = unchanged
+        .pipe(futures::stream::iter)
+        .map(|directive| async move {
+            let (hash, size, to) = expected_output(&directive);
+            let to = to.into_path();
+            let streamed = stream_bsa_files_gigabytes.is_some() && staged_for_bsa(&to).is_some();
+            let missing = !streamed
+                && check_output(&installation_path.join(to), &hash, size, false)
+                    .await
+                    .is_some();
+            (missing, directive)
+        })
+        .buffered(num_cpus::get())
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .partition_map(|(missing, directive)| match missing {
+            true => Either::Left(directive),
+            false => Either::Right(directive),
+        });
+    (missing, unchanged.len())
+}
+
+/// only once the new version is installed, a failed upgrade leaves the previous one usable
+fn remove_obsolete(installation_path: &Path, obsolete: &[PathBuf]) {
+    obsolete.iter().for_each(|path| {
+        let path = installation_path.join(path);
+        match std::fs::remove_file(&path) {
+            Ok(()) => info!("removed obsolete [{}]", path.display()),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => warn!(?error, "could not remove obsolete [{}]", path.display()),
+        }
+    });
+}
+
+#[instrument(skip_all, fields(previous=%previous_wabbajack_file.display()))]
+pub async fn upgrade_modlist(
+    HoolamikeConfig {
+        downloaders,
+        installation:
+            InstallationConfig {
+                wabbajack_file_path,
+                installation_path,
+                cpu_tasks,
+                io_tasks,
+                max_open_files,
+                copy_strategy,
+                deduplicate_outputs,
+                bsa_compression,
+                stream_bsa_files_gigabytes,
+                remapping,
+                nested_archive_temp_gigabytes,
+            },
+        games,
+        fixup: _,
+        archives,
+        extras: _,
+    }: HoolamikeConfig,
+    previous_wabbajack_file: PathBuf,
+    force: bool,
+    report_html: bool,
+) -> TotalResult<()> {
+    configure(&downloaders, archives, Scheduling::new(cpu_tasks, io_tasks, max_open_files), false).map_err(|e| vec![e])?;
+    let synchronizers = Synchronizers::new(downloaders.clone(), games.clone())
+        .context("setting up downloaders")
+        .map_err(|e| vec![e])?;
+    let (_, WabbajackFile { modlist: previous, .. }) = spawn_rayon(move || WabbajackFile::load_wabbajack_file(previous_wabbajack_file))
+        .await
+        .context("loading previous modlist file")
+        .map_err(|e| vec![e])?;
+    let (wabbajack_file_handle, WabbajackFile { modlist, .. }) = spawn_rayon(move || WabbajackFile::load_wabbajack_file(wabbajack_file_path))
+        .await
+        .context("loading modlist file")
+        .map_err(|e| vec![e])?;
+
+    let expected_outputs = crate::verify_cli::expected_outputs(&modlist.directives)
+        .pipe(|outputs| crate::verify_cli::skip_streamed_bsa_files(outputs, stream_bsa_files_gigabytes));
+    let UpgradePlan { changed, unchanged, obsolete } = plan_upgrade(&previous.directives, modlist.directives);
+    let (missing, unchanged) = missing_outputs(&installation_path, unchanged, stream_bsa_files_gigabytes).await;
+    info!(
+        changed = changed.len(),
+        missing = missing.len(),
+        unchanged,
+        obsolete = obsolete.len(),
+        "upgrading [{}] from [{}] to [{}]",
+        modlist.name,
+        previous.version,
+        modlist.version
+    );
+
+    let directives = changed.into_iter().chain(missing).collect_vec();
+    let needed = directives
+        .iter()
+        .filter_map(archive_hash_path)
+        .map(|archive_hash_path| archive_hash_path.source_hash.as_str())
+        .collect::<BTreeSet<_>>();
+    let archives = modlist
+        .archives
+        .into_iter()
+        .filter(|archive| needed.contains(archive.descriptor.hash.as_str()))
+        .collect_vec();
+    if !force {
+        output_space::check_output_space(&installation_path, *crate::consts::TEMP_FILE_DIR, &directives, &archives).map_err(|e| vec![e])?;
+    }
+    let game_directory = games
+        .get(&modlist.game_type)
+        .with_context(|| format!("[{}] not found in {:?}", modlist.game_type, games.keys().collect::<Vec<_>>()))
+        .map(|game_config| game_config.root_directory.clone())
+        .map_err(|e| vec![e])?;
+
+    let started = std::time::Instant::now();
+    let report = InstallReport {
+        modlist: modlist.name.clone(),
+        version: modlist.version.clone(),
+        hoolamike_version: env!("CARGO_PKG_VERSION"),
+        started_at: chrono::Local::now(),
+        elapsed_seconds: 0.0,
+        downloads: vec![],
+        directive_kinds: directives
+            .iter()
+            .map(|directive| (directive.directive_kind(), directive.size()))
+            .pipe(dry_run::summarize_directives),
+        failures: vec![],
+        audit: None,
+    };
+    let result = async {
+        let summary = synchronizers.clone().sync_downloads(archives).await?;
+        // interrupted downloads leave nothing for the directives to work with
+        crate::shutdown::check_stopped().map_err(|e| vec![e])?;
+        DirectivesHandler::new(
+            DirectivesHandlerConfig {
+                wabbajack_file: wabbajack_file_handle,
+                output_directory: installation_path.clone(),
+                game_directory,
+                downloads_directory: downloaders.downloads_directory,
+                copy_strategy,
+                bsa_compression,
+                stream_bsa_files_gigabytes,
+                remapping,
+                nested_archive_temp_gigabytes,
+            },
+            summary,
+        )
+        .pipe(Arc::new)
+        .handle_directives(directives)
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(|e| vec![e])?;
+        crate::shutdown::check_stopped().map_err(|e| vec![e])
+    }
+    .await;
+
+    if result.is_ok() {
+        remove_obsolete(&installation_path, &obsolete);
+        if deduplicate_outputs {
+            super::try_deduplicate_outputs(installation_path.clone(), expected_outputs.clone()).await;
+        }
+    }
+    // sizes only, the directives themselves already checked the hashes of what they wrote
+    let audit = match &result {
+        Ok(_) => Some(crate::verify_cli::verify_outputs(&installation_path, expected_outputs, true).await),
+        Err(_) => None,
+    };
+    if let Some(audit) = audit.as_ref().filter(|audit| !audit.problems.is_empty()) {
+        warn!("[{}/{}] files do not match the modlist after the upgrade", audit.problems.len(), audit.checked);
+    }
+    InstallReport {
+        elapsed_seconds: started.elapsed().as_secs_f64(),
+        downloads: synchronizers.download_records(),
+        failures: result
+            .as_ref()
+            .err()
+            .map(|errors| errors.iter().map(|error| format!("{error:?}")).collect())
+            .unwrap_or_default(),
+        audit,
+        ..report
+    }
+    .write(&installation_path, report_html)
+    .map(|path| info!("install report written to [{}]", path.display()))
+    .unwrap_or_else(|error| warn!(?error, "could not write the install report"));
+    result.map(|_| vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{modlist_json::directive::InlineFileDirective, utils::MaybeWindowsPath},
+    };
+
+    fn inline_file(to: &str, hash: &str) -> Directive {
+        Directive::InlineFile(InlineFileDirective {
+            hash: hash.to_string(),
+            size: 1,
+            source_data_id: uuid::Uuid::nil(),
+            to: MaybeWindowsPath::new(to),
+        })
+    }
+
+    #[test]
+    fn test_plan_upgrade() {
+        let previous = [
+            inline_file("mods\\a\\unchanged.esp", "AAAAAAAAAAA="),
+            inline_file("mods\\a\\changed.esp", "AAAAAAAAAAA="),
+            inline_file("mods\\a\\removed.esp", "AAAAAAAAAAA="),
+        ];
+        let UpgradePlan { changed, unchanged, obsolete } = plan_upgrade(
+            &previous,
+            vec![
+                inline_file("mods\\a\\unchanged.esp", "AAAAAAAAAAA="),
+                inline_file("mods\\a\\changed.esp", "BBBBBBBBBBB="),
+                inline_file("mods\\a\\added.esp", "AAAAAAAAAAA="),
+            ],
+        );
+        let paths = |directives: &[Directive]| {
+            directives
+                .iter()
+                .map(|directive| output_of(directive).0)
+                .collect_vec()
+        };
+        assert_eq!(paths(&changed), vec![PathBuf::from("mods/a/changed.esp"), PathBuf::from("mods/a/added.esp")]);
+        assert_eq!(paths(&unchanged), vec![PathBuf::from("mods/a/unchanged.esp")]);
+        assert_eq!(obsolete, vec![PathBuf::from("mods/a/removed.esp")]);
+    }
+
+    #[tokio::test]
+    async fn test_edited_outputs_are_installed_again() -> anyhow::Result<()> {
+        let directory = tempfile::tempdir()?;
+        std::fs::create_dir_all(directory.path().join("mods/a"))?;
+        std::fs::write(directory.path().join("mods/a/kept.esp"), b"a")?;
+        std::fs::write(directory.path().join("mods/a/edited.esp"), b"b")?;
+        let hash = crate::install_modlist::download_cache::calculate_hash(directory.path().join("mods/a/kept.esp"))
+            .await
+            .map(crate::install_modlist::download_cache::to_base_64_from_u64)?;
+        let (missing, unchanged) = missing_outputs(
+            directory.path(),
+            vec![
+                inline_file("mods\\a\\kept.esp", &hash),
+                inline_file("mods\\a\\edited.esp", &hash),
+                inline_file("mods\\a\\removed.esp", &hash),
+            ],
+            None,
+        )
+        .await;
+        assert_eq!(
+            missing
+                .iter()
+                .map(|directive| output_of(directive).0)
+                .collect_vec(),
+            vec![PathBuf::from("mods/a/edited.esp"), PathBuf::from("mods/a/removed.esp")]
+        );
+        assert_eq!(unchanged, 1);
+        Ok(())
+    }
+}
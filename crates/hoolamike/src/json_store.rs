@@ -0,0 +1,176 @@
+//! the journals, caches and checkpoints which let a restarted run skip finished work are each kept in a single json file,
+//! changes are written down at most once per flush interval (and when the store is dropped), not once per change
+use {
+    anyhow::{Context, Result},
+    parking_lot::Mutex,
+    serde::{de::DeserializeOwned, Serialize},
+    std::{
+        io::Write,
+        path::PathBuf,
+        time::{Duration, Instant},
+    },
+    tap::prelude::*,
+};
+
+/// how much work a crash can lose, writing after every change would take longer than the work itself
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+struct StoreState<T> {
+    value: T,
+    last_flush: Instant,
+    dirty: bool,
+}
+
+#[derive(Debug)]
+pub struct JsonStore<T: Serialize> {
+    path: PathBuf,
+    /// what is stored, for log messages
+    description: &'static str,
+    state: Mutex<StoreState<T>>,
+    /// held while writing, so that an older snapshot never replaces a newer one
+    writing: Mutex<()>,
+}
+
+impl<T: Serialize + DeserializeOwned + Default> JsonStore<T> {
+    /// a missing or broken file starts out empty, these files only ever save work
+    pub fn load(path: PathBuf, description: &'static str) -> Self {
+        match path.exists() {
+            true => std::fs::read_to_string(&path)
+                .context("reading")
+                .and_then(|contents| serde_json::from_str(&contents).context("parsing"))
+                .with_context(|| format!("loading {description} from [{}]", path.display()))
+                .unwrap_or_else(|reason| {
+                    tracing::warn!(?reason, "{description} is broken, starting over");
+                    Default::default()
+                }),
+            false => Default::default(),
+        }
+        .pipe(|value| Self::new(path, description, value))
+    }
+}
+
+impl<T: Serialize> JsonStore<T> {
+    /// nothing is written until the first change
+    pub fn new(path: PathBuf, description: &'static str, value: T) -> Self {
+        Self {
+            path,
+            description,
+            state: Mutex::new(StoreState {
+                value,
+                last_flush: Instant::now(),
+                dirty: false,
+            }),
+            writing: Mutex::new(()),
+        }
+    }
+
+    pub fn read<R>(&self, read: impl FnOnce(&T) -> R) -> R {
+        read(&self.state.lock().value)
+    }
+
+    /// written down once the flush interval has passed since the last write
+    pub fn update<R>(&self, update: impl FnOnce(&mut T) -> R) -> Result<R> {
+        let (output, due) = {
+            let mut state = self.state.lock();
+            let output = update(&mut state.value);
+            state.dirty = true;
+            (output, state.last_flush.elapsed() >= FLUSH_INTERVAL)
+        };
+        match due {
+            true => self.write(false).map(|_| output),
+            false => Ok(output),
+        }
+    }
+
+    /// writes pending changes right away
+    pub fn flush(&self) -> Result<()> {
+        self.write(true)
+    }
+
+    /// only the snapshot is taken under the lock, the file is written without blocking readers and writers
+    fn write(&self, wait: bool) -> Result<()> {
+        // somebody else is writing already, whatever they missed stays dirty for the next write
+        let Some(_writing) = (match wait {
+            true => Some(self.writing.lock()),
+            false => self.writing.try_lock(),
+        }) else {
+            return Ok(());
+        };
+        let contents = {
+            let mut state = self.state.lock();
+            if !state.dirty {
+                return Ok(());
+            }
+            state.dirty = false;
+            state.last_flush = Instant::now();
+            serde_json::to_vec(&state.value).context("serializing")
+        };
+        // a crash mid-write must not lose the previous file, so it's replaced only once the new one is complete
+        contents
+            .and_then(|contents| {
+                self.path
+                    .parent()
+                    .context("no parent directory")
+                    .and_then(|parent| {
+                        std::fs::create_dir_all(parent)
+                            .context("creating parent directory")
+                            .and_then(|_| tempfile::NamedTempFile::new_in(parent).context("creating temporary file"))
+                    })
+                    .and_then(|mut file| {
+                        file.write_all(&contents).context("writing").and_then(|_| {
+                            file.persist(&self.path)
+                                .context("replacing the previous file")
+                        })
+                    })
+            })
+            .map(|_| ())
+            .with_context(|| format!("writing {} to [{}]", self.description, self.path.display()))
+            .tap_err(|_| self.state.lock().dirty = true)
+    }
+
+    /// the work is done, the next run starts from scratch
+    pub fn remove(&self) -> Result<()> {
+        let _writing = self.writing.lock();
+        self.state.lock().dirty = false;
+        match self.path.exists() {
+            true => std::fs::remove_file(&self.path).with_context(|| format!("removing {} at [{}]", self.description, self.path.display())),
+            false => Ok(()),
+        }
+    }
+}
+
+impl<T: Serialize> Drop for JsonStore<T> {
+    fn drop(&mut self) {
+        self.flush()
+            .unwrap_or_else(|reason| tracing::warn!(?reason, "could not write {}", self.description))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::collections::BTreeMap};
+
+    type Store = JsonStore<BTreeMap<String, u64>>;
+
+    #[test]
+    fn test_changes_are_written_on_flush_and_on_drop() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("store.json");
+        let store = Store::load(path.clone(), "test store");
+        store.update(|value| value.insert("a".into(), 1))?;
+        assert!(!path.exists());
+        store.flush()?;
+        store.update(|value| value.insert("b".into(), 2))?;
+        assert_eq!(Store::load(path.clone(), "test store").read(|value| value.len()), 1);
+        drop(store);
+        assert_eq!(Store::load(path.clone(), "test store").read(|value| value.get("b").copied()), Some(2));
+
+        std::fs::write(&path, b"{ broken")?;
+        let store = Store::load(path.clone(), "test store");
+        assert!(store.read(|value| value.is_empty()));
+        store.remove()?;
+        assert!(!path.exists());
+        Ok(())
+    }
+}
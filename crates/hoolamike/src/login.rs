@@ -0,0 +1,238 @@
+//! `hoolamike login nexus` goes through the nexus single sign-on flow, so that the api key does not have to be copied from the website into the config
+use {
+    crate::downloaders::nexus::NexusDownloader,
+    anyhow::{Context, Result},
+    futures::{SinkExt, StreamExt},
+    serde::{Deserialize, Serialize},
+    std::{path::PathBuf, sync::Arc},
+    tap::prelude::*,
+    tokio_tungstenite::tungstenite::Message,
+    tracing::{info, warn},
+};
+
+#[derive(clap::Args)]
+pub struct LoginCli {
+    #[command(subcommand)]
+    pub service: LoginService,
+}
+
+#[derive(clap::Subcommand)]
+pub enum LoginService {
+    /// opens the nexus website to authorize hoolamike, the api key is then stored in the user's config directory
+    /// and used whenever `downloaders.nexus.api_key` is not set
+    ///
+    /// the key ends up in a plain file only the current user can read (not in the os keyring),
+    /// anyone with access to the account can read it just like the config
+    Nexus {
+        /// it will be invoked as <use-browser> <url>
+        #[arg(long, default_value = "firefox")]
+        use_browser: String,
+        /// application slug registered with nexus, hoolamike does not have one of its own yet,
+        /// nexus refuses any slug it has not approved
+        #[arg(long)]
+        application: String,
+        /// forgets the stored api key instead
+        #[arg(long)]
+        logout: bool,
+    },
+}
+
+impl LoginCli {
+    pub async fn run(self) -> Result<()> {
+        match self.service {
+            LoginService::Nexus { logout: true, .. } => credentials::forget_nexus_api_key(),
+            LoginService::Nexus {
+                use_browser,
+                application,
+                logout: false,
+            } => {
+                let api_key = nexus_sso::login(&use_browser, &application)
+                    .await
+                    .context("logging in to nexus")?;
+                // a key nexus does not accept would only fail the next install
+                let user = NexusDownloader::new(api_key.clone())
+                    .map(Arc::new)
+                    .context("bad nexus client")?
+                    .validate()
+                    .await
+                    .context("validating the new api key")?;
+                credentials::store_nexus_api_key(&api_key).map(|path| info!("api key stored at [{}]", path.display()))?;
+                match user.is_premium {
+                    true => info!("logged in to nexus as [{}] (premium)", user.name),
+                    false => info!(
+                        "logged in to nexus as [{}], without premium downloads have to go through `hoolamike handle-nxm`",
+                        user.name
+                    ),
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+pub mod credentials {
+    use {super::*, std::io::Write};
+
+    fn nexus_api_key_path() -> Result<PathBuf> {
+        directories::ProjectDirs::from("", "", "hoolamike")
+            .context("could not determine the config directory")
+            .map(|dirs| dirs.config_dir().join("nexus_api_key"))
+    }
+
+    /// plain text, only readable by the current user
+    pub fn store_nexus_api_key(api_key: &str) -> Result<PathBuf> {
+        let path = nexus_api_key_path()?;
+        path.parent()
+            .context("no parent")
+            .and_then(|parent| std::fs::create_dir_all(parent).with_context(|| format!("creating [{}]", parent.display())))?;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&path)
+            .and_then(|mut file| file.write_all(api_key.as_bytes()))
+            .with_context(|| format!("writing [{}]", path.display()))
+            .map(|_| path)
+    }
+
+    pub fn stored_nexus_api_key() -> Option<String> {
+        nexus_api_key_path()
+            .and_then(|path| std::fs::read_to_string(&path).with_context(|| format!("reading [{}]", path.display())))
+            .map(|api_key| api_key.trim().to_string())
+            .ok()
+            .filter(|api_key| !api_key.is_empty())
+    }
+
+    pub fn forget_nexus_api_key() -> Result<()> {
+        nexus_api_key_path().and_then(|path| match path.exists() {
+            true => std::fs::remove_file(&path)
+                .with_context(|| format!("removing [{}]", path.display()))
+                .tap_ok(|_| info!("removed [{}]", path.display())),
+            false => Ok(()).tap_ok(|_| warn!("not logged in to nexus")),
+        })
+    }
+}
+
+/// https://github.com/Nexus-Mods/sso-integration-demo
+pub mod nexus_sso {
+    use super::*;
+
+    const SSO_URL: &str = "wss://sso.nexusmods.com";
+
+    #[derive(Debug, Serialize)]
+    struct SsoRequest {
+        id: uuid::Uuid,
+        token: Option<String>,
+        protocol: u8,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct SsoResponse {
+        success: bool,
+        #[serde(default)]
+        data: Option<SsoData>,
+        #[serde(default)]
+        error: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(untagged)]
+    enum SsoData {
+        ApiKey {
+            api_key: String,
+        },
+        /// only needed for resuming a dropped connection
+        #[allow(dead_code)]
+        ConnectionToken {
+            connection_token: String,
+        },
+    }
+
+    fn authorization_url(id: &uuid::Uuid, application: &str) -> String {
+        format!("https://www.nexusmods.com/sso?id={id}&application={application}")
+    }
+
+    pub async fn login(use_browser: &str, application: &str) -> Result<String> {
+        let id = uuid::Uuid::new_v4();
+        let (mut socket, _) = tokio_tungstenite::connect_async(SSO_URL)
+            .await
+            .with_context(|| format!("connecting to [{SSO_URL}]"))?;
+        let request = SsoRequest { id, token: None, protocol: 2 }
+            .pipe_ref(serde_json::to_string)
+            .context("serializing request")?;
+        socket
+            .send(Message::text(request))
+            .await
+            .context("sending sso request")?;
+
+        let mut browser_opened = false;
+        while let Some(message) = socket.next().await {
+            let message = message.context("reading from sso socket")?;
+            let text = match message {
+                Message::Text(_) => message.into_text().context("bad text message")?,
+                Message::Close(reason) => anyhow::bail!("nexus closed the connection: {reason:?}"),
+                _ => continue,
+            };
+            let response = serde_json::from_str::<SsoResponse>(&text).with_context(|| format!("bad sso response: [{text}]"))?;
+            match response {
+                SsoResponse { success: false, error, .. } => anyhow::bail!("nexus refused the login: {}", error.unwrap_or_default()),
+                SsoResponse {
+                    data: Some(SsoData::ApiKey { api_key }),
+                    ..
+                } => return Ok(api_key),
+                SsoResponse {
+                    data: Some(SsoData::ConnectionToken { .. }),
+                    ..
+                }
+                | SsoResponse { data: None, .. } => {
+                    if !browser_opened {
+                        let url = authorization_url(&id, application);
+                        info!("authorize hoolamike at [{url}] (opening it with {use_browser})");
+                        tokio::process::Command::new(use_browser)
+                            .arg(&url)
+                            .spawn()
+                            .with_context(|| format!("opening [{url}] with ({use_browser}) failed, open it manually"))
+                            .tap_err(|message| warn!("{message:?}"))
+                            .ok();
+                        browser_opened = true;
+                    }
+                }
+            }
+        }
+        anyhow::bail!("sso connection ended before nexus sent the api key")
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_sso_responses() -> Result<()> {
+            assert!(matches!(
+                serde_json::from_str::<SsoResponse>(r#"{"success":true,"data":{"connection_token":"abc"},"error":null}"#)?,
+                SsoResponse {
+                    success: true,
+                    data: Some(SsoData::ConnectionToken { .. }),
+                    ..
+                }
+            ));
+            assert!(matches!(
+                serde_json::from_str::<SsoResponse>(r#"{"success":true,"data":{"api_key":"secret"},"error":null}"#)?,
+                SsoResponse {
+                    data: Some(SsoData::ApiKey { api_key }),
+                    ..
+                } if api_key == "secret"
+            ));
+            assert!(matches!(
+                serde_json::from_str::<SsoResponse>(r#"{"success":false,"data":null,"error":"invalid application"}"#)?,
+                SsoResponse {
+                    success: false,
+                    error: Some(_),
+                    ..
+                }
+            ));
+            Ok(())
+        }
+    }
+}
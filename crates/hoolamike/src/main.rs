@@ -21,11 +21,8 @@ struct Cli {
     /// the hoolamike config file is where you configure your installation - we're linux users, we can't afford windows
     /// which means we can't afford GUI-capable hardware anyway
     ///
-    /// in the config you'll have to specify a modlist file - you'll have to download it
-    /// can it be downloaded autside of wabbajack gui client?
-    /// yes and no
-    /// they can be found here: https://build.wabbajack.org/authored_files **BUT** the manual download should be avoided unless absolutely necessary.
-    /// probably best approach would be visiting official Wabbajack discord server and asking someone which file is safe to download
+    /// in the config you'll have to specify a modlist file - `hoolamike browse` lists the modlists of the wabbajack gallery
+    /// and downloads (and verifies) the one you pick
     #[arg(long, short = 'c', default_value = std::env::current_dir().unwrap().join("hoolamike.yaml").into_os_string())]
     hoolamike_config: PathBuf,
     #[command(subcommand)]
@@ -38,6 +35,12 @@ struct Cli {
     nxm_link_handler_port: u16,
     /// this is just for the nxm handler
     nxm_link: Option<HumanUrl>,
+    /// `json` prints newline-delimited json events (phases, progress, installed directives, errors) instead of progress bars
+    #[arg(long, value_enum, default_value_t = Default::default())]
+    progress: progress_bars_v2::events::ProgressOutput,
+    /// sends the json events to this unix socket instead of stdout
+    #[arg(long)]
+    progress_socket: Option<PathBuf>,
 }
 
 #[derive(clap::Args, Default)]
@@ -45,17 +48,54 @@ pub struct DebugHelpers {
     /// skip verification (used mostly for developing the tool)
     #[arg(long)]
     skip_verify_and_downloads: bool,
+    /// ignore the hash cache of the downloads directory and hash every archive again
+    #[arg(long)]
+    rehash: bool,
+    /// resolves the modlist and verifies the downloads, then reports what would be installed
+    /// without downloading anything or touching the installation directory
+    #[arg(long)]
+    dry_run: bool,
     #[arg(long)]
     start_from_directive: Option<String>,
     #[arg(long)]
     skip_kind: Vec<DirectiveKind>,
     #[arg(long)]
     contains: Vec<String>,
+    /// only installs files whose path (relative to the installation directory) matches one of these globs, e.g. `--only "mods/**"`
+    #[arg(long)]
+    only: Vec<String>,
+    /// skips files whose path matches one of these globs, e.g. `--exclude "*.dds"` installs everything but loose textures
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// installs even when there does not seem to be enough free space for the output and temporary files
+    #[arg(long)]
+    force: bool,
+    /// writes a human-readable html version of the install report next to the json one
+    #[arg(long)]
+    report_html: bool,
+    /// builds every bsa/ba2 archive without compression, same as `installation.bsa_compression.uncompressed`
+    #[arg(long)]
+    uncompressed_bsa: bool,
 }
 
 #[derive(Subcommand)]
 enum HoolamikeDebugCommand {
-    ReserializeDirectives { modlist_file: PathBuf },
+    /// parses the modlist and writes it back to stdout byte for byte the way wabbajack wrote it, reports where it differs
+    ReserializeModlist { modlist_file: PathBuf },
+    /// prints the json schema of the modlist model, other tools can validate modlists with it
+    ExportSchema {
+        #[arg(long, value_enum, default_value_t)]
+        of: modlist_json::schema::SchemaOf,
+    },
+    /// which archives feed which output directories, to find where the long poles of an installation come from
+    DirectiveGraph {
+        modlist_file: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: install_modlist::directive_graph::GraphFormat,
+        /// how many directories deep the outputs are grouped, `2` groups them by `mods/<mod name>`
+        #[arg(long, default_value_t = 2)]
+        depth: usize,
+    },
 }
 
 #[derive(Args)]
@@ -70,7 +110,14 @@ enum Commands {
     /// (or tries to queue up the download in case the link is provided)
     HandleNxm(nxm_handler::cli::HandleNxmCli),
     /// Emulates TTW installer (make sure to add installer variables to hoolamike.yaml)
-    TaleOfTwoWastelands(crate::extensions::tale_of_two_wastelands_installer::CliConfig),
+    TaleOfTwoWastelands(crate::extensions::mpi_installer::CliConfig),
+    /// runs any other MPI based installer defined in `extras.mpi_installers` of hoolamike.yaml
+    MpiInstaller {
+        /// name of the installer in hoolamike.yaml
+        name: String,
+        #[command(flatten)]
+        cli_config: crate::extensions::mpi_installer::CliConfig,
+    },
     /// applies 4GB patch to FalloutNV.exe (replaces FNVPatcher.exe/FNVPatcher.py etc )
     FalloutNewVegasPatcher {
         /// path to FalloutNV.exe
@@ -83,6 +130,12 @@ enum Commands {
         /// path to modlist (.wabbajack) file
         path: PathBuf,
     },
+    /// checks the modlist (.wabbajack or its extracted `modlist` json) for entries which make the installation fail
+    /// or install something else than the author expects (missing or empty archives, clashing outputs, unknown games)
+    Lint {
+        /// path to modlist (.wabbajack) file
+        path: PathBuf,
+    },
     /// prints information about the modlist
     ModlistInfo {
         /// path to modlist (.wabbajack) file
@@ -92,6 +145,19 @@ enum Commands {
         #[command(flatten)]
         debug: DebugHelpers,
     },
+    /// installs a new version of the modlist (`installation.wabbajack_file_path`) over an existing installation,
+    /// only the files which changed are installed and only the archives they need are downloaded
+    Upgrade {
+        /// the .wabbajack file of the version which is currently installed
+        #[arg(long)]
+        from: PathBuf,
+        /// upgrades even when there does not seem to be enough free space for the output and temporary files
+        #[arg(long)]
+        force: bool,
+        /// writes a human-readable html version of the install report next to the json one
+        #[arg(long)]
+        report_html: bool,
+    },
     /// prints prints default config. save it and modify to your liking
     PrintDefaultConfig,
     /// runs post-install fixup - wouldn't be possible without extensive research done by Omni
@@ -100,6 +166,21 @@ enum Commands {
     /// exposes the bare archive handling functionality used in hoolamike, useful for debugging
     Archive(self::archive_cli::ArchiveCliCommand),
     Audio(self::audio_cli::AudioCliCommand),
+    /// manages the downloads directory, e.g. imports archives downloaded by other mod managers
+    Downloads(self::downloads_cli::DownloadsCliCommand),
+    /// downloads a modlist (.wabbajack) file from the official modlist repositories, pass `browse` to list them
+    FetchModlist(self::fetch_modlist::FetchModlistCli),
+    /// lists the modlists of the wabbajack gallery (filtered by game, size, nsfw) and downloads the picked one
+    Browse(self::browse::BrowseCli),
+    /// checks every file of an existing installation against the modlist, without running any directives
+    Verify(self::verify_cli::VerifyCli),
+    /// logs in to download services instead of pasting api keys into the config
+    Login(self::login::LoginCli),
+    /// finds the supported games in the steam libraries (proton ones included) and heroic (gog, epic), fills in `games` of the config
+    DetectGames(self::detect_games::DetectGamesCli),
+    /// opens a minimal graphical front-end: config editor, modlist picker and installation progress
+    #[cfg(feature = "gui")]
+    Gui,
 }
 
 pub mod read_wrappers;
@@ -110,26 +191,53 @@ pub mod nxm_handler;
 
 pub mod archive_cli;
 pub mod audio_cli;
+pub mod browse;
 pub mod compression;
 pub mod config_file;
+pub mod detect_games;
 pub mod downloaders;
+pub mod downloads_cli;
 pub mod error;
+pub mod fetch_modlist;
+#[cfg(feature = "gui")]
+pub mod gui;
 pub mod helpers;
 pub mod install_modlist;
+pub mod json_store;
+pub mod login;
 pub mod modlist_data;
 pub mod modlist_json;
 pub mod octadiff_reader;
+pub mod patching;
 pub mod post_install_fixup;
 pub mod progress_bars_v2;
+pub mod shutdown;
+pub mod verify_cli;
 pub mod wabbajack_file;
 
 /// non-wabbajack extensions will go here
 pub mod extensions;
 
 pub mod consts {
-    use {once_cell::sync::Lazy, std::path::Path, tap::prelude::*};
-    pub static TEMP_FILE_DIR: Lazy<&'static Path> =
-        Lazy::new(|| Path::new(".hoolamike/TEMP_FILES").tap(|path| std::fs::create_dir_all(path).expect("could not create temporary dir storage")));
+    use {
+        once_cell::sync::Lazy,
+        std::path::{Path, PathBuf},
+        tap::prelude::*,
+    };
+
+    /// relative to the working directory unless `HOOLAMIKE_DATA_DIR` says otherwise - extracted files are linked into the installation,
+    /// which only works when both are on the same drive, so the platform cache directory is not a good default
+    pub static DATA_DIR: Lazy<PathBuf> = Lazy::new(|| {
+        std::env::var_os("HOOLAMIKE_DATA_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(".hoolamike"))
+    });
+    pub static TEMP_FILE_DIR: Lazy<&'static Path> = Lazy::new(|| {
+        DATA_DIR
+            .join("TEMP_FILES")
+            .tap(|path| std::fs::create_dir_all(path).expect("could not create temporary dir storage"))
+            .pipe(|path| &*Box::leak(path.into_boxed_path()))
+    });
 }
 
 #[derive(Debug, ValueEnum, Clone, Copy, Default, serde::Serialize)]
@@ -141,7 +249,7 @@ pub enum LoggingMode {
 }
 
 #[allow(unused_imports)]
-fn setup_logging(logging_mode: LoggingMode) -> Option<impl Drop> {
+fn setup_logging(logging_mode: LoggingMode, progress: progress_bars_v2::events::ProgressOutput) -> Option<impl Drop> {
     use {
         tracing_indicatif::IndicatifLayer,
         tracing_subscriber::{fmt, layer::SubscriberExt, prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt, EnvFilter},
@@ -159,6 +267,16 @@ fn setup_logging(logging_mode: LoggingMode) -> Option<impl Drop> {
             tracing::subscriber::set_global_default(subscriber).expect("Could not set global default");
             Some(guard)
         }
+        LoggingMode::Cli if progress == progress_bars_v2::events::ProgressOutput::Json => {
+            tracing_subscriber::registry()
+                .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::from_str("info").unwrap()))
+                .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+                .with(progress_bars_v2::events::ProgressEventsLayer)
+                .pipe(tracing::subscriber::set_global_default)
+                .context("Unable to set a global subscriber")
+                .expect("logging failed");
+            None
+        }
         LoggingMode::Cli => {
             let indicatif_layer = console::Term::stdout()
                 .size_checked()
@@ -216,8 +334,19 @@ async fn async_main() -> Result<()> {
         logging_mode,
         nxm_link_handler_port,
         nxm_link,
+        progress,
+        progress_socket,
     } = Cli::parse();
-    let _guard = setup_logging(logging_mode);
+    progress_bars_v2::events::validate(progress, progress_socket.as_ref())?;
+    if progress == progress_bars_v2::events::ProgressOutput::Json {
+        progress_bars_v2::events::configure_progress_events(progress_socket.as_deref())?;
+    }
+    let _guard = match &command {
+        // the gui renders the logs and progress itself
+        #[cfg(feature = "gui")]
+        Some(Commands::Gui) => None,
+        _ => setup_logging(logging_mode, progress),
+    };
     match (command, nxm_link) {
         (Some(command), _) => match command {
             Commands::FalloutNewVegasPatcher { at_path } => crate::extensions::fallout_new_vegas_4gb_patch::patch_fallout_new_vegas(&at_path)
@@ -233,6 +362,7 @@ async fn async_main() -> Result<()> {
                 .context("reading test file")
                 .and_then(|input| modlist_json::parsing_helpers::validate_modlist_file(&input))
                 .with_context(|| format!("testing file {}", path.display())),
+            Commands::Lint { path } => modlist_json::lint::lint_file(&path),
             Commands::ModlistInfo { path } => wabbajack_file::WabbajackFile::load_wabbajack_file(path)
                 .context("reading modlist")
                 .map(|(_, modlist)| ModlistSummary::new(&modlist.modlist))
@@ -244,8 +374,11 @@ async fn async_main() -> Result<()> {
             Commands::Install { debug } => {
                 let (config_path, config) = config_file::HoolamikeConfig::find(&hoolamike_config).context("reading hoolamike config file")?;
                 tracing::info!("found config at [{}]", config_path.display());
+                // only installs can stop gracefully, everything else keeps the default ctrl-c behavior
+                tokio::spawn(shutdown::handle_signals());
 
-                install_modlist::install_modlist(config, debug)
+                let dry_run = debug.dry_run;
+                install_modlist::install_modlist(config.clone(), debug)
                     .await
                     .map_err(|errors| {
                         errors
@@ -255,19 +388,60 @@ async fn async_main() -> Result<()> {
 
                         anyhow::anyhow!("could not finish installation due to [{}] errors", errors.len())
                     })
-                    .map(|count| println!("successfully installed [{}] mods", count.len()))
+                    .and_then(|count| match dry_run {
+                        true => Ok(()),
+                        false => {
+                            progress_bars_v2::events::print_report(format_args!("successfully installed [{}] mods", count.len()));
+                            post_install_fixup::load_order::LoadOrderContext::from_config(&config)
+                                .fix_load_order()
+                                .context("verifying load order")
+                        }
+                    })
+            }
+            Commands::Upgrade { from, force, report_html } => {
+                let (_config_path, config) = config_file::HoolamikeConfig::find(&hoolamike_config).context("reading hoolamike config file")?;
+                tokio::spawn(shutdown::handle_signals());
+                install_modlist::upgrade::upgrade_modlist(config.clone(), from, force, report_html)
+                    .await
+                    .map_err(|errors| {
+                        errors
+                            .iter()
+                            .enumerate()
+                            .for_each(|(idx, reason)| tracing::error!("{idx}. {reason:?}", idx = idx + 1));
+
+                        anyhow::anyhow!("could not finish the upgrade due to [{}] errors", errors.len())
+                    })
+                    .and_then(|_| {
+                        post_install_fixup::load_order::LoadOrderContext::from_config(&config)
+                            .fix_load_order()
+                            .context("verifying load order")
+                    })
             }
             Commands::HoolamikeDebug(HoolamikeDebug { command }) => match command {
-                HoolamikeDebugCommand::ReserializeDirectives { modlist_file } => wabbajack_file::WabbajackFile::load_wabbajack_file(modlist_file)
+                HoolamikeDebugCommand::ReserializeModlist { modlist_file } => wabbajack_file::WabbajackFile::load_modlist_json(&modlist_file)
+                    .and_then(|json| {
+                        modlist_json::round_trip::RoundTrip::parse(&json)
+                            .and_then(|round_trip| round_trip.to_json())
+                            .map(|reserialized| (json, reserialized))
+                    })
+                    .and_then(|(json, reserialized)| {
+                        match modlist_json::round_trip::first_difference(&json, &reserialized) {
+                            None => info!("round trip is byte identical"),
+                            Some(line) => tracing::warn!(%line, "round trip differs from the original"),
+                        }
+                        std::io::Write::write_all(&mut std::io::stdout(), reserialized.as_bytes()).context("writing modlist to stdout")
+                    }),
+                HoolamikeDebugCommand::ExportSchema { of } => modlist_json::schema::export_schema(of)
+                    .pipe_ref(|schema| serde_json::to_string_pretty(schema).context("serializing schema"))
+                    .map(|schema| println!("{schema}")),
+                HoolamikeDebugCommand::DirectiveGraph { modlist_file, format, depth } => wabbajack_file::WabbajackFile::load_wabbajack_file(modlist_file)
                     .context("loading modlist file")
-                    .and_then(|modlist| {
-                        modlist
-                            .1
-                            .modlist
-                            .directives
-                            .pipe_ref(|directives| serde_json::to_string_pretty(directives).context("serializing directives"))
+                    .map(|(_, wabbajack)| install_modlist::directive_graph::directive_graph(&wabbajack.modlist.archives, &wabbajack.modlist.directives, depth))
+                    .and_then(|graph| match format {
+                        install_modlist::directive_graph::GraphFormat::Dot => Ok(graph.to_dot()),
+                        install_modlist::directive_graph::GraphFormat::Json => serde_json::to_string_pretty(&graph).context("serializing graph"),
                     })
-                    .map(|directives| println!("{directives}")),
+                    .map(|graph| println!("{graph}")),
             },
             Commands::Archive(archive_cli_command) => archive_cli_command.run(),
             Commands::Audio(audio_cli_command) => audio_cli_command
@@ -277,6 +451,34 @@ async fn async_main() -> Result<()> {
                 let (_config_path, config) = config_file::HoolamikeConfig::find(&hoolamike_config).context("reading hoolamike config file")?;
                 crate::extensions::tale_of_two_wastelands_installer::install(cli_config, config)
             }
+            Commands::MpiInstaller { name, cli_config } => {
+                let (_config_path, config) = config_file::HoolamikeConfig::find(&hoolamike_config).context("reading hoolamike config file")?;
+                crate::extensions::mpi_installer::install_configured(&name, cli_config, config)
+            }
+            Commands::FetchModlist(fetch_modlist_cli) => {
+                let config = config_file::HoolamikeConfig::find(&hoolamike_config)
+                    .map(|(_, config)| config)
+                    .ok();
+                fetch_modlist_cli.run(config).await
+            }
+            Commands::Browse(browse_cli) => {
+                let config = config_file::HoolamikeConfig::find(&hoolamike_config)
+                    .map(|(_, config)| config)
+                    .ok();
+                browse_cli.run(config).await
+            }
+            Commands::Login(login_cli) => login_cli.run().await,
+            Commands::DetectGames(detect_games_cli) => detect_games_cli.run(&hoolamike_config),
+            Commands::Verify(verify_cli) => {
+                let (_config_path, config) = config_file::HoolamikeConfig::find(&hoolamike_config).context("reading hoolamike config file")?;
+                verify_cli.run(config).await
+            }
+            Commands::Downloads(downloads_cli_command) => {
+                let (_config_path, config) = config_file::HoolamikeConfig::find(&hoolamike_config).context("reading hoolamike config file")?;
+                downloads_cli_command.run(config).await
+            }
+            #[cfg(feature = "gui")]
+            Commands::Gui => gui::run(hoolamike_config).await,
             Commands::HandleNxm(handle_nxm_cli) => {
                 let (_config_path, config) = config_file::HoolamikeConfig::find(&hoolamike_config).context("reading hoolamike config file")?;
                 nxm_handler::run(config, handle_nxm_cli).await
@@ -304,5 +506,12 @@ async fn main() -> Result<()> {
         .num_threads(num_cpus::get().saturating_sub(2).max(1))
         .build_global()
         .unwrap();
-    async_main().await
+    let result = async_main().await;
+    // the hash cache lives in a global, it's never dropped
+    install_modlist::download_cache::hash_cache::flush_hash_cache();
+    if shutdown::stop_requested() {
+        tracing::warn!("{}", shutdown::STOPPED_MESSAGE);
+        std::process::exit(130);
+    }
+    result
 }
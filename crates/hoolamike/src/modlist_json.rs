@@ -1,5 +1,6 @@
 use {
     crate::{install_modlist::download_cache::to_base_64_from_u64, utils::MaybeWindowsPath},
+    schemars::JsonSchema,
     serde::{Deserialize, Serialize},
     std::hash::Hasher,
     tap::prelude::*,
@@ -32,6 +33,7 @@ macro_rules! test_example {
     derive_more::Into,
     serde::Serialize,
     serde::Deserialize,
+    schemars::JsonSchema,
     derive_more::AsMut,
 )]
 pub struct HumanUrl(url::Url);
@@ -42,7 +44,7 @@ impl std::fmt::Debug for HumanUrl {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct Modlist {
     /// archives: Vec<Archive>
@@ -101,7 +103,7 @@ pub struct Modlist {
     pub website: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct ArchiveDescriptor {
@@ -123,7 +125,7 @@ pub struct ArchiveDescriptor {
     pub size: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct Archive {
     #[serde(flatten)]
@@ -134,10 +136,15 @@ pub struct Archive {
     pub state: State,
 }
 
+pub mod format_version;
+pub mod lint;
+pub mod round_trip;
+pub mod schema;
 pub mod type_guard;
+pub mod unknown;
 
 #[allow(clippy::large_enum_variant)]
-#[derive(Debug, Serialize, Deserialize, enum_kinds::EnumKind, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, enum_kinds::EnumKind, Clone)]
 #[serde(tag = "$type")]
 #[serde(deny_unknown_fields)]
 #[enum_kind(DownloadKind, derive(Serialize, Deserialize, PartialOrd, Ord, derive_more::Display,))]
@@ -158,6 +165,15 @@ pub enum State {
     Manual(ManualState),
     #[serde(rename = "WabbajackCDNDownloader+State, Wabbajack.Lib")]
     WabbajackCDN(WabbajackCDNDownloaderState),
+    #[serde(rename = "ModDBDownloader, Wabbajack.Lib", alias = "ModDBDownloader+State, Wabbajack.Lib")]
+    ModDB(ModDBState),
+    /// modlists made before vectorplexus moved to oauth use the old name
+    #[serde(rename = "VectorPlexusOAuthDownloader+State, Wabbajack.Lib", alias = "VectorPlexusDownloader, Wabbajack.Lib")]
+    VectorPlexus(VectorPlexusState),
+    /// download sources added to wabbajack after this version of hoolamike, kept as they are
+    #[serde(untagged, deserialize_with = "self::unknown::state_fallback")]
+    #[schemars(skip)]
+    Unknown(self::unknown::UnknownState),
 }
 
 impl State {
@@ -166,7 +182,7 @@ impl State {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "PascalCase")]
 #[serde(deny_unknown_fields)]
 pub struct HttpState {
@@ -175,7 +191,7 @@ pub struct HttpState {
     pub url: HumanUrl,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "PascalCase")]
 #[serde(deny_unknown_fields)]
 pub struct ManualState {
@@ -183,35 +199,81 @@ pub struct ManualState {
     pub url: HumanUrl,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "PascalCase")]
 #[serde(deny_unknown_fields)]
 pub struct WabbajackCDNDownloaderState {
     pub url: HumanUrl,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "PascalCase")]
 #[serde(deny_unknown_fields)]
 pub struct GoogleDriveState {
     pub id: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "PascalCase")]
 #[serde(deny_unknown_fields)]
 pub struct MediaFireState {
     pub url: HumanUrl,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "PascalCase")]
 #[serde(deny_unknown_fields)]
 pub struct MegaState {
     pub url: HumanUrl,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct ModDBState {
+    /// url: HumanUrl
+    /// Description: The moddb download page, usually `https://www.moddb.com/downloads/start/<id>`.
+    /// Usage: Mirrors are listed on this page, each one redirects to the actual file.
+    pub url: HumanUrl,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    #[serde(rename = "ImageURL")]
+    pub image_url: Option<String>,
+    #[serde(rename = "IsNSFW")]
+    pub is_nsfw: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+pub struct VectorPlexusState {
+    /// ips4_mod: u64
+    /// Description: Id of the file on the Invision Community (IPS4) based vectorplexus website.
+    #[serde(rename = "IPS4Mod")]
+    pub ips4_mod: u64,
+    /// ips4_file: String
+    /// Description: Name of the file within the mod page.
+    #[serde(rename = "IPS4File")]
+    pub ips4_file: String,
+    /// ips4_url: String
+    /// Description: The mod page, users are sent here when the file has to be downloaded manually.
+    #[serde(rename = "IPS4Url", default)]
+    pub ips4_url: String,
+    #[serde(default)]
+    pub is_attachment: bool,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    #[serde(rename = "ImageURL")]
+    pub image_url: Option<String>,
+    #[serde(rename = "IsNSFW")]
+    pub is_nsfw: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "PascalCase")]
 #[serde(deny_unknown_fields)]
 pub struct GameFileSourceState {
@@ -221,22 +283,22 @@ pub struct GameFileSourceState {
     pub game: GameName,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, derive_more::Display, PartialEq, Eq, PartialOrd, Ord, Hash, derive_more::Constructor)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, derive_more::Display, PartialEq, Eq, PartialOrd, Ord, Hash, derive_more::Constructor)]
 pub struct GameName(String);
 
-#[derive(Debug, Serialize, Deserialize, Clone, derive_more::Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, derive_more::Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum SpecialGameName {
     ModdingTools,
     FalloutNewVegas,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, derive_more::Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, derive_more::Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(untagged)]
 pub enum NexusGameName {
     Special(SpecialGameName),
     GameName(GameName),
 }
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "PascalCase")]
 #[serde(deny_unknown_fields)]
 pub struct NexusState {
@@ -352,7 +414,7 @@ pub struct UnknownState {
 
 pub mod directive;
 
-#[derive(Debug, Serialize, Deserialize, enum_kinds::EnumKind)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, enum_kinds::EnumKind)]
 #[serde(tag = "$type")]
 #[serde(deny_unknown_fields)]
 #[enum_kind(DirectiveKind, derive(Serialize, Deserialize, PartialOrd, Ord, derive_more::Display, Hash, clap::ValueEnum))]
@@ -363,6 +425,10 @@ pub enum Directive {
     PatchedFromArchive(directive::PatchedFromArchiveDirective),
     RemappedInlineFile(directive::RemappedInlineFileDirective),
     TransformedTexture(directive::TransformedTextureDirective),
+    /// directives added to wabbajack after this version of hoolamike, kept as they are
+    #[serde(untagged, deserialize_with = "self::unknown::directive_fallback")]
+    #[schemars(skip)]
+    Unknown(self::unknown::UnknownDirective),
 }
 
 impl Directive {
@@ -374,6 +440,7 @@ impl Directive {
             Directive::PatchedFromArchive(d) => d.size,
             Directive::RemappedInlineFile(d) => d.size,
             Directive::TransformedTexture(d) => d.size,
+            Directive::Unknown(d) => d.size(),
         }
     }
     pub fn directive_hash(&self) -> String {
@@ -393,7 +460,7 @@ impl Directive {
 
 pub mod image_format;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct ImageState {
@@ -547,7 +614,7 @@ pub struct UnknownDirectiveState {
 //     BA2DX10Entry(BA2DX10Entry),
 // }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct BA2DX10EntryChunk {
@@ -592,12 +659,18 @@ pub mod parsing_helpers {
     }
 
     pub fn validate_modlist_file(input: &str) -> Result<()> {
+        parse_modlist_file(input).map(|_| ())
+    }
+
+    /// like parsing the modlist directly, but errors point at the offending part of the json
+    pub fn parse_modlist_file(input: &str) -> Result<crate::modlist_json::Modlist> {
         input
             .tap(|input| {
                 info!("file is {} bytes long", input.len());
             })
             .pipe_as_ref(serde_json::from_str::<Value>)
             .context("bad json")
+            .map(super::format_version::normalize)
             .and_then(|node| serde_json::to_string_pretty(&node).context("serializing"))
             .and_then(move |pretty_input| {
                 serde_json::from_str::<crate::modlist_json::Modlist>(&pretty_input)
@@ -617,7 +690,6 @@ pub mod parsing_helpers {
                     })
                     .context("bad modlist")
             })
-            .map(|_| ())
     }
 
     #[allow(unexpected_cfgs)]
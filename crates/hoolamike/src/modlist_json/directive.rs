@@ -5,7 +5,7 @@ pub mod archive_hash_path;
 pub mod create_bsa_directive;
 
 pub use archive_hash_path::ArchiveHashPath;
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct FromArchiveDirective {
@@ -27,7 +27,7 @@ pub struct FromArchiveDirective {
     pub archive_hash_path: ArchiveHashPath,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct InlineFileDirective {
@@ -50,7 +50,7 @@ pub struct InlineFileDirective {
     pub to: MaybeWindowsPath,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct PatchedFromArchiveDirective {
@@ -81,7 +81,7 @@ pub struct PatchedFromArchiveDirective {
     pub patch_id: uuid::Uuid,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct RemappedInlineFileDirective {
@@ -104,7 +104,7 @@ pub struct RemappedInlineFileDirective {
     pub to: MaybeWindowsPath,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct TransformedTextureDirective {
@@ -30,6 +30,21 @@ impl Serialize for ArchiveHashPath {
     }
 }
 
+/// `[source hash, path in the archive, path in the nested archive, ...]`
+impl schemars::JsonSchema for ArchiveHashPath {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "ArchiveHashPath".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "array",
+            "items": {"type": "string"},
+            "minItems": 1,
+        })
+    }
+}
+
 impl<'de> Deserialize<'de> for ArchiveHashPath {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -1,6 +1,6 @@
 use {super::*, crate::serde_type_guard};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct CreateBSADirectiveKind<DirectiveState, FileState> {
@@ -34,7 +34,7 @@ pub struct CreateBSADirectiveKind<DirectiveState, FileState> {
 pub mod ba2;
 pub mod bsa;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 #[serde(untagged)]
 pub enum CreateBSADirective {
@@ -1,6 +1,6 @@
 use {super::*, crate::serde_type_guard, type_guard::WithTypeGuard};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct BA2DX10Entry {
@@ -63,7 +63,7 @@ pub struct BA2DX10Entry {
     pub path: MaybeWindowsPath,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct BA2FileEntry {
@@ -102,7 +102,7 @@ pub struct BA2FileEntry {
 }
 
 #[allow(clippy::large_enum_variant)]
-#[derive(Debug, Serialize, Deserialize, enum_kinds::EnumKind)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, enum_kinds::EnumKind)]
 #[serde(tag = "$type")]
 #[serde(deny_unknown_fields)]
 #[enum_kind(BA2FileStateKind, derive(Serialize, Deserialize, PartialOrd, Ord, derive_more::Display,))]
@@ -114,7 +114,7 @@ pub enum FileState {
 
 serde_type_guard!(BA2DirectiveStateGuard, "BA2State, Compression.BSA");
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "PascalCase")]
 pub struct DirectiveStateData {
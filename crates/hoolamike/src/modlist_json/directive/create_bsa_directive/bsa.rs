@@ -1,13 +1,13 @@
 use {super::*, crate::serde_type_guard, type_guard::WithTypeGuard};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy)]
 #[serde(untagged)]
 pub enum Either<L, R> {
     Left(L),
     Right(R),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "PascalCase")]
 #[serde(deny_unknown_fields)]
 pub struct FileStateData {
@@ -25,7 +25,7 @@ pub struct FileStateData {
 serde_type_guard!(BSAFileStateTypeGuard, "BSAFileState, Compression.BSA");
 pub type FileState = WithTypeGuard<FileStateData, BSAFileStateTypeGuard>;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 #[serde(deny_unknown_fields)]
 pub struct DirectiveStateData {
@@ -0,0 +1,135 @@
+//! the modlist model follows what wabbajack 3.x writes. modlists compiled by 2.x still write the assembly after directive type
+//! names (`FromArchive, Wabbajack.Lib`), the json is normalized before it is parsed, so the rest of hoolamike only ever sees one format
+use {
+    serde_json::{Map, Value},
+    tracing::debug,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, derive_more::Display)]
+pub enum FormatVersion {
+    V2,
+    V3,
+}
+
+/// 2.x wrote the assembly after directive type names
+const LEGACY_ASSEMBLY_SUFFIX: &str = ", Wabbajack.Lib";
+
+impl FormatVersion {
+    /// by the major version of `WabbajackVersion`, modlists without one are treated as the newest format
+    pub fn detect(modlist: &Value) -> Self {
+        modlist
+            .get("WabbajackVersion")
+            .and_then(Value::as_str)
+            .and_then(|version| version.split('.').next()?.trim().parse::<u32>().ok())
+            .map(|major| match major {
+                0..=2 => Self::V2,
+                _ => Self::V3,
+            })
+            .unwrap_or(Self::V3)
+    }
+
+    fn normalize_directive(self, directive: &mut Map<String, Value>) {
+        match self {
+            Self::V2 => {
+                if let Some(type_name) = directive
+                    .get("$type")
+                    .and_then(Value::as_str)
+                    .and_then(|type_name| type_name.strip_suffix(LEGACY_ASSEMBLY_SUFFIX))
+                    .map(str::to_string)
+                {
+                    directive.insert("$type".into(), Value::String(type_name));
+                }
+            }
+            Self::V3 => {}
+        }
+    }
+}
+
+fn objects_in<'a>(modlist: &'a mut Value, key: &str) -> impl Iterator<Item = &'a mut Map<String, Value>> {
+    modlist
+        .get_mut(key)
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// modlist json of any supported format version into the format the model parses
+pub fn normalize(mut modlist: Value) -> Value {
+    let version = FormatVersion::detect(&modlist);
+    debug!(%version, "normalizing modlist");
+    objects_in(&mut modlist, "Directives").for_each(|directive| version.normalize_directive(directive));
+    modlist
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::modlist_json::{Directive, Modlist, State},
+        anyhow::{Context, Result},
+        tap::prelude::*,
+    };
+
+    fn parse_fixture(fixture: &str) -> Result<Modlist> {
+        serde_json::from_str::<Value>(fixture)
+            .context("bad json")
+            .tap_ok(|modlist| assert_eq!(FormatVersion::detect(modlist), FormatVersion::V3))
+            .map(normalize)
+            .and_then(|modlist| serde_json::from_value::<Modlist>(modlist).context("normalized modlist could not be parsed"))
+            .tap_ok(|modlist| {
+                assert!(
+                    !modlist
+                        .archives
+                        .iter()
+                        .any(|archive| matches!(archive.state, State::Unknown(_))),
+                    "{:#?}",
+                    modlist.archives
+                );
+                assert!(
+                    !modlist
+                        .directives
+                        .iter()
+                        .any(|directive| matches!(directive, Directive::Unknown(_))),
+                    "{:#?}",
+                    modlist.directives
+                );
+            })
+    }
+
+    #[test]
+    fn test_skyrim_special_edition_modlist() -> Result<()> {
+        parse_fixture(include_str!("./format_version/skyrim_special_edition.json")).map(|modlist| {
+            assert_eq!(modlist.game_type.to_string(), "SkyrimSpecialEdition");
+            assert!(matches!(&modlist.archives[0].state, State::Nexus(nexus) if nexus.game_name.to_string() == "SkyrimSpecialEdition"));
+        })
+    }
+
+    #[test]
+    fn test_fallout_4_modlist() -> Result<()> {
+        parse_fixture(include_str!("./format_version/fallout_4.json")).map(|modlist| assert_eq!(modlist.game_type.to_string(), "Fallout4"))
+    }
+
+    #[test]
+    fn test_fallout_new_vegas_modlist() -> Result<()> {
+        parse_fixture(include_str!("./format_version/fallout_new_vegas.json")).map(|modlist| assert_eq!(modlist.game_type.to_string(), "FalloutNewVegas"))
+    }
+
+    #[test]
+    fn test_fallout_3_modlist() -> Result<()> {
+        parse_fixture(include_str!("./format_version/fallout_3.json")).map(|modlist| assert_eq!(modlist.game_type.to_string(), "Fallout3"))
+    }
+
+    #[test]
+    fn test_legacy_directive_type_names() -> Result<()> {
+        let modlist = serde_json::json!({
+            "WabbajackVersion": "2.5.3.28",
+            "Directives": [{"$type": "InlineFile, Wabbajack.Lib"}],
+        });
+        assert_eq!(FormatVersion::detect(&modlist), FormatVersion::V2);
+        normalize(modlist)
+            .pipe_ref(|modlist| modlist.pointer("/Directives/0/$type").cloned())
+            .context("no directive")
+            .map(|type_name| assert_eq!(type_name, "InlineFile"))
+    }
+}
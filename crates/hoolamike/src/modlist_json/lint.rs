@@ -0,0 +1,412 @@
+//! `hoolamike lint` - entries which parse fine but make an installation fail or end up different than the modlist author expects
+use {
+    super::{Archive, Directive, GameName, Modlist, NexusGameName, State},
+    crate::{
+        install_modlist::directives::{archive_hash_path, expected_output},
+        progress_bars_v2::events::print_report,
+        utils::MaybeWindowsPath,
+    },
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    std::{collections::BTreeSet, path::Path},
+    tabled::{settings::Style, Tabled},
+    tap::prelude::*,
+};
+
+/// `Game` names wabbajack knows about, modlists are compiled with these
+pub const WABBAJACK_GAMES: &[&str] = &[
+    "Morrowind",
+    "Oblivion",
+    "OblivionRemastered",
+    "Fallout3",
+    "FalloutNewVegas",
+    "Skyrim",
+    "Enderal",
+    "Fallout4",
+    "SkyrimSpecialEdition",
+    "EnderalSpecialEdition",
+    "SkyrimVR",
+    "Fallout4VR",
+    "DarkestDungeon",
+    "Dishonored",
+    "Witcher",
+    "Witcher3",
+    "StardewValley",
+    "KingdomComeDeliverance",
+    "MechWarrior5Mercenaries",
+    "NoMansSky",
+    "DragonAgeOrigins",
+    "DragonAge2",
+    "DragonAgeInquisition",
+    "KerbalSpaceProgram",
+    "Terraria",
+    "Cyberpunk2077",
+    "Sims4",
+    "Starfield",
+    "BaldursGate3",
+    "SevenDaysToDie",
+    "ModdingTools",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, derive_more::Display)]
+pub enum Severity {
+    /// the installation fails
+    #[display("error")]
+    Error,
+    /// the installation finishes, but probably not with what the author wanted
+    #[display("warning")]
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, derive_more::Display)]
+pub enum LintKind {
+    ZeroSizeArchive,
+    DuplicateOutput,
+    MissingArchive,
+    CaseCollision,
+    ReservedName,
+    UnknownGame,
+    UnsupportedEntry,
+}
+
+impl LintKind {
+    pub fn severity(self) -> Severity {
+        match self {
+            LintKind::MissingArchive | LintKind::UnsupportedEntry => Severity::Error,
+            LintKind::ZeroSizeArchive | LintKind::DuplicateOutput | LintKind::CaseCollision | LintKind::ReservedName | LintKind::UnknownGame => {
+                Severity::Warning
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Lint {
+    pub kind: LintKind,
+    pub message: String,
+}
+
+#[derive(Tabled)]
+pub struct LintRow {
+    pub severity: String,
+    pub kind: String,
+    pub message: String,
+}
+
+impl From<&Lint> for LintRow {
+    fn from(lint: &Lint) -> Self {
+        Self {
+            severity: lint.kind.severity().to_string(),
+            kind: lint.kind.to_string(),
+            message: lint.message.clone(),
+        }
+    }
+}
+
+fn lint(kind: LintKind, message: String) -> Lint {
+    Lint { kind, message }
+}
+
+fn zero_size_archives(archives: &[Archive]) -> impl Iterator<Item = Lint> + '_ {
+    archives
+        .iter()
+        .filter(|archive| archive.descriptor.size == 0)
+        .map(|archive| {
+            lint(
+                LintKind::ZeroSizeArchive,
+                format!(
+                    "archive [{}] has a size of 0, its download cannot be told apart from a failed one, make sure the file in the downloads folder is not \
+                     empty and compile the modlist again",
+                    archive.descriptor.name
+                ),
+            )
+        })
+}
+
+/// outputs relative to the installation directory, with `/` separators
+fn outputs(directives: &[Directive]) -> Vec<(String, &Directive)> {
+    directives
+        .iter()
+        .map(|directive| {
+            (
+                expected_output(directive)
+                    .2
+                    .into_path()
+                    .to_string_lossy()
+                    .to_string(),
+                directive,
+            )
+        })
+        .collect()
+}
+
+fn duplicate_outputs<'a>(outputs: &'a [(String, &Directive)]) -> impl Iterator<Item = Lint> + 'a {
+    outputs
+        .iter()
+        .map(|(to, directive)| (to.as_str(), directive))
+        .into_group_map()
+        .into_iter()
+        .filter(|(_, directives)| directives.len() > 1)
+        .sorted_by_key(|(to, _)| *to)
+        .map(|(to, directives)| {
+            lint(
+                LintKind::DuplicateOutput,
+                format!(
+                    "[{to}] is written by {} directives ({}), only one of them ends up installed, remove the file from all but one mod",
+                    directives.len(),
+                    directives
+                        .iter()
+                        .map(|directive| directive.directive_kind().to_string())
+                        .join(", ")
+                ),
+            )
+        })
+}
+
+fn missing_archives<'a>(archives: &'a [Archive], directives: &'a [Directive]) -> impl Iterator<Item = Lint> + 'a {
+    let known = archives
+        .iter()
+        .map(|archive| archive.descriptor.hash.as_str())
+        .collect::<BTreeSet<_>>();
+    directives
+        .iter()
+        .filter_map(|directive| archive_hash_path(directive).map(|archive_hash_path| (archive_hash_path, directive)))
+        .filter(move |(archive_hash_path, _)| !known.contains(archive_hash_path.source_hash.as_str()))
+        .into_group_map_by(|(archive_hash_path, _)| archive_hash_path.source_hash.clone())
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(hash, directives)| {
+            lint(
+                LintKind::MissingArchive,
+                format!(
+                    "{} directives (e.g. [{}]) take their files from archive [{hash}] which is not in the archive list, the installation will fail, compile \
+                     the modlist again with the archive in the downloads folder",
+                    directives.len(),
+                    directives
+                        .first()
+                        .map(|(_, directive)| expected_output(directive).2.to_string())
+                        .unwrap_or_default(),
+                ),
+            )
+        })
+}
+
+/// windows (and proton prefixes with casefolding) see these as the same file, linux installs them side by side
+fn case_collisions<'a>(outputs: &'a [(String, &Directive)]) -> impl Iterator<Item = Lint> + 'a {
+    outputs
+        .iter()
+        .map(|(to, _)| to.as_str())
+        .unique()
+        .into_group_map_by(|to| MaybeWindowsPath::new(*to).comparison_key())
+        .into_iter()
+        .filter(|(_, spellings)| spellings.len() > 1)
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, spellings)| {
+            lint(
+                LintKind::CaseCollision,
+                format!(
+                    "{} differ only in case, the game sees one file while a case sensitive filesystem keeps all of them, rename them consistently",
+                    spellings
+                        .iter()
+                        .map(|spelling| format!("[{spelling}]"))
+                        .join(", ")
+                ),
+            )
+        })
+}
+
+/// device names (`aux`, `con.esp`) and names with trailing dots or spaces, windows refuses or renames them
+fn reserved_names(directives: &[Directive]) -> impl Iterator<Item = Lint> + '_ {
+    directives
+        .iter()
+        .map(|directive| expected_output(directive).2)
+        .filter_map(|to| {
+            to.reserved_components()
+                .map(|component| format!("[{component}]"))
+                .join(", ")
+                .pipe(|components| (!components.is_empty()).then(|| (to.to_string(), components)))
+        })
+        .unique()
+        .map(|(to, components)| {
+            lint(
+                LintKind::ReservedName,
+                format!("[{to}] contains {components}, which the game cannot open under windows or proton, rename the file in its mod"),
+            )
+        })
+}
+
+fn unknown_games(modlist: &Modlist) -> impl Iterator<Item = Lint> + '_ {
+    let is_known = |game: &GameName| WABBAJACK_GAMES.contains(&game.to_string().as_str());
+    std::iter::once(("the modlist game", &modlist.game_type))
+        .chain(
+            modlist
+                .archives
+                .iter()
+                .filter_map(|archive| match &archive.state {
+                    State::GameFileSource(state) => Some(("a game file source", &state.game)),
+                    State::Nexus(state) => match &state.game_name {
+                        NexusGameName::GameName(game) => Some(("a nexus download", game)),
+                        NexusGameName::Special(_) => None,
+                    },
+                    _ => None,
+                }),
+        )
+        .filter(move |(_, game)| !is_known(game))
+        .into_group_map_by(|(_, game)| game.to_string())
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(game, uses)| {
+            lint(
+                LintKind::UnknownGame,
+                format!(
+                    "[{game}] (used by {}) is not a game wabbajack knows, check the spelling, it has to be one of: {}",
+                    uses.iter().map(|(used_by, _)| *used_by).unique().join(", "),
+                    WABBAJACK_GAMES.join(", ")
+                ),
+            )
+        })
+}
+
+fn unsupported_entries(modlist: &Modlist) -> impl Iterator<Item = Lint> + '_ {
+    modlist
+        .archives
+        .iter()
+        .filter_map(|archive| match &archive.state {
+            State::Unknown(unknown) => Some(format!("archive [{}]: download source {unknown}", archive.descriptor.name)),
+            _ => None,
+        })
+        .chain(
+            modlist
+                .directives
+                .iter()
+                .filter_map(|directive| match directive {
+                    Directive::Unknown(unknown) => Some(format!("directive {unknown}")),
+                    _ => None,
+                }),
+        )
+        .map(|message| lint(LintKind::UnsupportedEntry, message))
+}
+
+pub fn lint_modlist(modlist: &Modlist) -> Vec<Lint> {
+    let outputs = outputs(&modlist.directives);
+    std::iter::empty()
+        .chain(zero_size_archives(&modlist.archives))
+        .chain(duplicate_outputs(&outputs))
+        .chain(missing_archives(&modlist.archives, &modlist.directives))
+        .chain(case_collisions(&outputs))
+        .chain(reserved_names(&modlist.directives))
+        .chain(unknown_games(modlist))
+        .chain(unsupported_entries(modlist))
+        .collect()
+}
+
+/// `.wabbajack` files and extracted `modlist` json alike, fails when any of the lints would make the installation fail
+pub fn lint_file(path: &Path) -> Result<()> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("wabbajack") => crate::wabbajack_file::WabbajackFile::load_wabbajack_file(path.to_owned()).map(|(_, wabbajack)| wabbajack.modlist),
+        _ => std::fs::read_to_string(path)
+            .context("reading modlist json")
+            .and_then(|input| super::parsing_helpers::parse_modlist_file(&input)),
+    }
+    .with_context(|| format!("loading modlist from [{}]", path.display()))
+    .map(|modlist| lint_modlist(&modlist))
+    .and_then(|lints| {
+        if lints.is_empty() {
+            print_report("no problems found");
+            return Ok(());
+        }
+        lints
+            .iter()
+            .map(LintRow::from)
+            .pipe(tabled::Table::new)
+            .with(Style::modern())
+            .pipe(print_report);
+        let errors = lints
+            .iter()
+            .filter(|lint| lint.kind.severity() == Severity::Error)
+            .count();
+        match errors {
+            0 => Ok(()),
+            errors => anyhow::bail!("{errors} of {} problems would make the installation fail", lints.len()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            modlist_json::{
+                directive::{ArchiveHashPath, FromArchiveDirective},
+                ArchiveDescriptor,
+                GameFileSourceState,
+            },
+            utils::MaybeWindowsPath,
+        },
+    };
+
+    fn from_archive(source_hash: &str, to: &str) -> Directive {
+        Directive::FromArchive(FromArchiveDirective {
+            hash: "AAAAAAAAAAA=".into(),
+            size: 1,
+            to: MaybeWindowsPath::new(to),
+            archive_hash_path: ArchiveHashPath {
+                source_hash: source_hash.into(),
+                path: vec![MaybeWindowsPath::new("file")],
+            },
+        })
+    }
+
+    #[test]
+    fn test_suspicious_entries_are_flagged() {
+        let modlist = Modlist {
+            archives: vec![Archive {
+                descriptor: ArchiveDescriptor {
+                    hash: "archive=".into(),
+                    meta: String::new(),
+                    name: "Data_Skyrim.esm".into(),
+                    size: 0,
+                },
+                state: State::GameFileSource(GameFileSourceState {
+                    game_version: "1.6.640.0".into(),
+                    hash: "archive=".into(),
+                    game_file: MaybeWindowsPath::new("Data\\Skyrim.esm"),
+                    game: GameName::new("SkyrimSE".into()),
+                }),
+            }],
+            author: String::new(),
+            description: String::new(),
+            directives: vec![
+                from_archive("archive=", "mods\\A\\plugin.esp"),
+                from_archive("archive=", "mods\\A\\plugin.esp"),
+                from_archive("archive=", "mods\\A\\Textures\\a.dds"),
+                from_archive("archive=", "mods\\A\\textures\\a.dds"),
+                from_archive("missing=", "mods\\B\\b.esp"),
+                from_archive("archive=", "mods\\C\\aux\\c.esp"),
+            ],
+            game_type: GameName::new("SkyrimSpecialEdition".into()),
+            image: String::new(),
+            is_nsfw: false,
+            name: "lint".into(),
+            readme: String::new(),
+            version: "1.0.0".into(),
+            wabbajack_version: "3.7.5.3".into(),
+            website: String::new(),
+        };
+        assert_eq!(
+            lint_modlist(&modlist)
+                .iter()
+                .map(|lint| lint.kind)
+                .collect_vec(),
+            [
+                LintKind::ZeroSizeArchive,
+                LintKind::DuplicateOutput,
+                LintKind::MissingArchive,
+                LintKind::CaseCollision,
+                LintKind::ReservedName,
+                LintKind::UnknownGame,
+            ]
+        );
+    }
+}
@@ -0,0 +1,372 @@
+//! parse → serialize round trips which reproduce the modlist json byte for byte, so a modlist edited with hoolamike can be
+//! diffed against what wabbajack wrote. the original json is kept next to the parsed model: whatever the model did not
+//! change is written back exactly as it was read (key order, fields the model does not know, the 2.x type names undone by
+//! [`super::format_version::normalize`]), changed values are written the way the model serializes them. indentation,
+//! line endings and string escapes are copied from the original
+use {
+    super::{format_version::normalize, Modlist},
+    anyhow::{Context, Result},
+    serde::Serialize,
+    serde_json::{
+        ser::{CharEscape, CompactFormatter, Formatter, PrettyFormatter},
+        Map,
+        Value,
+    },
+    std::{collections::BTreeSet, io},
+    tap::prelude::*,
+};
+
+/// how the original json was laid out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonStyle {
+    /// `None` for compact json
+    indent: Option<Vec<u8>>,
+    crlf: bool,
+    trailing_newline: bool,
+    /// ascii characters the original wrote as `\uXXXX` (System.Text.Json does that with e.g. `+`, `'` and `"`)
+    escaped: BTreeSet<char>,
+    /// every non-ascii character is written as `\uXXXX`
+    escape_non_ascii: bool,
+    uppercase_hex: bool,
+}
+
+/// code units of all the `\uXXXX` escapes in the json, along with the hex digits they were written with
+fn unicode_escapes(json: &str) -> Vec<(u16, &str)> {
+    let mut escapes = vec![];
+    let mut rest = json;
+    while let Some(index) = rest.find('\\') {
+        rest = &rest[index + 1..];
+        match rest.strip_prefix('u') {
+            Some(escape) => {
+                if let Some(escape) = escape.get(..4).and_then(|hex| {
+                    u16::from_str_radix(hex, 16)
+                        .ok()
+                        .map(|code_unit| (code_unit, hex))
+                }) {
+                    escapes.push(escape);
+                }
+                rest = escape;
+            }
+            // `\\` would otherwise start an escape of its own
+            None => rest = rest.get(1..).unwrap_or_default(),
+        }
+    }
+    escapes
+}
+
+impl JsonStyle {
+    pub fn detect(json: &str) -> Self {
+        let escapes = unicode_escapes(json);
+        Self {
+            indent: json.trim_end().split_once('\n').map(|(_, rest)| {
+                rest.bytes()
+                    .take_while(|byte| matches!(byte, b' ' | b'\t'))
+                    .collect()
+            }),
+            crlf: json.contains("\r\n"),
+            trailing_newline: json.ends_with('\n'),
+            escaped: escapes
+                .iter()
+                .filter_map(|(code_unit, _)| {
+                    u8::try_from(*code_unit)
+                        .ok()
+                        .filter(u8::is_ascii)
+                        .map(char::from)
+                })
+                .collect(),
+            escape_non_ascii: json.is_ascii() && escapes.iter().any(|(code_unit, _)| *code_unit > 0x7f),
+            uppercase_hex: escapes
+                .iter()
+                .any(|(_, hex)| hex.bytes().any(|byte| byte.is_ascii_uppercase())),
+        }
+    }
+
+    fn escapes(&self, c: char) -> bool {
+        match c.is_ascii() {
+            true => self.escaped.contains(&c),
+            false => self.escape_non_ascii,
+        }
+    }
+
+    fn write_unicode_escape<W: ?Sized + io::Write>(&self, writer: &mut W, code_unit: u16) -> io::Result<()> {
+        match self.uppercase_hex {
+            true => write!(writer, "\\u{code_unit:04X}"),
+            false => write!(writer, "\\u{code_unit:04x}"),
+        }
+    }
+
+    pub fn write(&self, json: &Value) -> Result<String> {
+        let mut out = vec![];
+        let formatter = StyledFormatter {
+            style: self,
+            layout: match self.indent.as_deref() {
+                Some(indent) => Layout::Pretty(PrettyFormatter::with_indent(indent)),
+                None => Layout::Compact(CompactFormatter),
+            },
+        };
+        json.serialize(&mut serde_json::Serializer::with_formatter(&mut out, formatter))
+            .context("serializing json")?;
+        if self.trailing_newline {
+            out.push(b'\n');
+        }
+        String::from_utf8(out)
+            .context("serialized json is not utf-8")
+            // strings have their newlines escaped, so only the layout changes
+            .map(|json| match self.crlf {
+                true => json.replace('\n', "\r\n"),
+                false => json,
+            })
+    }
+}
+
+enum Layout<'a> {
+    Compact(CompactFormatter),
+    Pretty(PrettyFormatter<'a>),
+}
+
+struct StyledFormatter<'a> {
+    style: &'a JsonStyle,
+    layout: Layout<'a>,
+}
+
+macro_rules! delegate_to_layout {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<W>(&mut self, writer: &mut W $(, $arg: $ty)*) -> io::Result<()>
+            where
+                W: ?Sized + io::Write,
+            {
+                match &mut self.layout {
+                    Layout::Compact(formatter) => formatter.$method(writer $(, $arg)*),
+                    Layout::Pretty(formatter) => formatter.$method(writer $(, $arg)*),
+                }
+            }
+        )*
+    };
+}
+
+impl Formatter for StyledFormatter<'_> {
+    delegate_to_layout! {
+        begin_array();
+        end_array();
+        begin_array_value(first: bool);
+        end_array_value();
+        begin_object();
+        end_object();
+        begin_object_key(first: bool);
+        begin_object_value();
+        end_object_value();
+    }
+
+    fn write_string_fragment<W>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        let mut start = 0;
+        for (index, c) in fragment.char_indices() {
+            if self.style.escapes(c) {
+                writer.write_all(fragment[start..index].as_bytes())?;
+                for code_unit in c.encode_utf16(&mut [0; 2]) {
+                    self.style.write_unicode_escape(writer, *code_unit)?;
+                }
+                start = index + c.len_utf8();
+            }
+        }
+        writer.write_all(fragment[start..].as_bytes())
+    }
+
+    fn write_char_escape<W>(&mut self, writer: &mut W, char_escape: CharEscape) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        match char_escape {
+            CharEscape::Quote if self.style.escaped.contains(&'"') => self.style.write_unicode_escape(writer, u16::from(b'"')),
+            CharEscape::ReverseSolidus if self.style.escaped.contains(&'\\') => self.style.write_unicode_escape(writer, u16::from(b'\\')),
+            CharEscape::AsciiControl(byte) => self.style.write_unicode_escape(writer, u16::from(byte)),
+            char_escape => CompactFormatter.write_char_escape(writer, char_escape),
+        }
+    }
+}
+
+/// `original` as read (`None` where [`normalize`] changed its shape), `normalized` the same after [`normalize`],
+/// `parsed` what the model made of it and `current` what the model holds now. arrays are matched up by index
+fn reconcile(original: Option<&Value>, normalized: &Value, parsed: &Value, current: &Value) -> Value {
+    if parsed == current {
+        return original.unwrap_or(normalized).clone();
+    }
+    match (normalized, current) {
+        (Value::Object(normalized), Value::Object(current_fields)) => {
+            let parsed = parsed.as_object();
+            normalized
+                .iter()
+                .filter_map(|(key, normalized)| match current_fields.get(key) {
+                    Some(current) => Some((
+                        key.clone(),
+                        reconcile(
+                            original.and_then(|original| original.get(key)),
+                            normalized,
+                            parsed
+                                .and_then(|parsed| parsed.get(key))
+                                .unwrap_or(&Value::Null),
+                            current,
+                        ),
+                    )),
+                    // fields the model does not know about are kept as they are
+                    None if parsed.is_none_or(|parsed| !parsed.contains_key(key)) => Some((
+                        key.clone(),
+                        original
+                            .and_then(|original| original.get(key))
+                            .unwrap_or(normalized)
+                            .clone(),
+                    )),
+                    // and the ones it removed are gone
+                    None => None,
+                })
+                .chain(
+                    current_fields
+                        .iter()
+                        // defaults the model filled in are not written unless they were changed
+                        .filter(|(key, value)| !normalized.contains_key(*key) && parsed.and_then(|parsed| parsed.get(*key)) != Some(*value))
+                        .map(|(key, value)| (key.clone(), value.clone())),
+                )
+                .collect::<Map<_, _>>()
+                .pipe(Value::Object)
+        }
+        (Value::Array(normalized), Value::Array(current_items)) => current_items
+            .iter()
+            .enumerate()
+            .map(|(index, current)| match normalized.get(index) {
+                Some(normalized) => reconcile(
+                    original.and_then(|original| original.get(index)),
+                    normalized,
+                    parsed.get(index).unwrap_or(&Value::Null),
+                    current,
+                ),
+                None => current.clone(),
+            })
+            .collect::<Vec<_>>()
+            .pipe(Value::Array),
+        _ => current.clone(),
+    }
+}
+
+/// a parsed modlist which remembers the json it was parsed from
+pub struct RoundTrip {
+    original: Value,
+    normalized: Value,
+    parsed: Value,
+    style: JsonStyle,
+    /// edit this, [`RoundTrip::to_json`] writes the changes into the original json
+    pub modlist: Modlist,
+}
+
+impl RoundTrip {
+    pub fn parse(json: &str) -> Result<Self> {
+        let original = serde_json::from_str::<Value>(json).context("bad json")?;
+        let modlist = super::parsing_helpers::parse_modlist_file(json)?;
+        Ok(Self {
+            normalized: normalize(original.clone()),
+            parsed: serde_json::to_value(&modlist).context("serializing parsed modlist")?,
+            style: JsonStyle::detect(json),
+            original,
+            modlist,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_value(&self.modlist)
+            .context("serializing modlist")
+            .map(|current| reconcile(Some(&self.original), &self.normalized, &self.parsed, &current))
+            .and_then(|json| self.style.write(&json))
+    }
+}
+
+/// 1-based line of the first difference, `None` when both are byte identical
+pub fn first_difference(original: &str, reserialized: &str) -> Option<usize> {
+    (original != reserialized).then(|| {
+        original
+            .split('\n')
+            .zip(reserialized.split('\n'))
+            .take_while(|(original, reserialized)| original == reserialized)
+            .count()
+            + 1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{modlist_json::Directive, utils::MaybeWindowsPath},
+    };
+
+    fn assert_round_trip(json: &str) -> Result<()> {
+        RoundTrip::parse(json)
+            .and_then(|round_trip| round_trip.to_json())
+            .map(|reserialized| assert_eq!(first_difference(json, &reserialized), None, "{reserialized}"))
+    }
+
+    #[test]
+    fn test_modlists_round_trip_byte_identical() -> Result<()> {
+        [
+            include_str!("./format_version/skyrim_special_edition.json"),
+            include_str!("./format_version/fallout_4.json"),
+            include_str!("./format_version/fallout_new_vegas.json"),
+            include_str!("./format_version/fallout_3.json"),
+        ]
+        .into_iter()
+        .try_for_each(assert_round_trip)
+    }
+
+    #[test]
+    fn test_layout_and_escapes_are_kept() -> Result<()> {
+        let modlist = serde_json::json!({
+            "WabbajackVersion": "3.7.5.3",
+            "Name": "a 'quoted' \"name\" + ünïcode",
+            "Version": "1.0.0",
+            "GameType": "SkyrimSpecialEdition",
+            "IsNSFW": false,
+            "Archives": [],
+            "Directives": [],
+        });
+        let system_text_json = |json: String| {
+            json.replace('\'', "\\u0027")
+                .replace("\\\"", "\\u0022")
+                .replace('+', "\\u002B")
+                .replace('ü', "\\u00FC")
+                .replace('ï', "\\u00EF")
+        };
+        assert_round_trip(&modlist.to_string())?;
+        assert_round_trip(&system_text_json(modlist.to_string()))?;
+        serde_json::to_string_pretty(&modlist)
+            .context("serializing")
+            .map(|pretty| system_text_json(pretty).replace('\n', "\r\n") + "\r\n")
+            .and_then(|pretty| assert_round_trip(&pretty))
+    }
+
+    #[test]
+    fn test_edits_keep_the_original_layout() -> Result<()> {
+        let json = include_str!("./format_version/fallout_4.json");
+        let mut round_trip = RoundTrip::parse(json)?;
+        let Some(Directive::FromArchive(directive)) = round_trip
+            .modlist
+            .directives
+            .iter_mut()
+            .find(|directive| matches!(directive, Directive::FromArchive(_)))
+        else {
+            anyhow::bail!("no FromArchive directive in fixture");
+        };
+        directive.to = MaybeWindowsPath::new("mods\\Renamed\\renamed.esp");
+        let reserialized = round_trip.to_json()?;
+        assert_eq!(json.lines().count(), reserialized.lines().count());
+        assert_eq!(
+            json.lines()
+                .zip(reserialized.lines())
+                .filter(|(original, reserialized)| original != reserialized)
+                .count(),
+            1
+        );
+        assert!(reserialized.contains("mods\\\\Renamed\\\\renamed.esp"));
+        RoundTrip::parse(&reserialized).map(|reparsed| assert!(reparsed.to_json().is_ok_and(|again| again == reserialized)))
+    }
+}
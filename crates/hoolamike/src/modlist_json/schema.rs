@@ -0,0 +1,43 @@
+//! json schema of the modlist model, for validating modlists without running hoolamike.
+//! it describes the json after [`super::format_version::normalize`] and leaves out the unknown entries hoolamike
+//! tolerates, so everything it accepts is something hoolamike can install
+use {
+    super::{Directive, Modlist, State},
+    schemars::{schema_for, Schema},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SchemaOf {
+    /// the whole `modlist` file of a `.wabbajack` archive
+    #[default]
+    Modlist,
+    /// a single entry of `Directives`
+    Directive,
+    /// the `State` of a single entry of `Archives`
+    State,
+}
+
+pub fn export_schema(of: SchemaOf) -> Schema {
+    match of {
+        SchemaOf::Modlist => schema_for!(Modlist),
+        SchemaOf::Directive => schema_for!(Directive),
+        SchemaOf::State => schema_for!(State),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anyhow::Result};
+
+    #[test]
+    fn test_schemas_describe_the_model() -> Result<()> {
+        let modlist = serde_json::to_value(export_schema(SchemaOf::Modlist))?;
+        assert!(modlist.pointer("/properties/Archives").is_some());
+        assert!(modlist.pointer("/properties/Directives").is_some());
+        let directive = serde_json::to_string(&export_schema(SchemaOf::Directive))?;
+        assert!(directive.contains("CreateBSA") && directive.contains("TransformedTexture"));
+        let state = serde_json::to_string(&export_schema(SchemaOf::State))?;
+        assert!(state.contains("NexusDownloader, Wabbajack.Lib"));
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+//! wabbajack keeps adding download sources and directives, a modlist made with a newer version should still load.
+//! whatever hoolamike does not know is kept the way it was (reserializing the modlist does not lose it),
+//! only the archives and directives which are actually needed fail the installation
+use {
+    super::{directive, Directive, Modlist, State},
+    crate::utils::MaybeWindowsPath,
+    itertools::Itertools,
+    schemars::JsonSchema,
+    serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize},
+    serde_json::{Map, Value},
+};
+
+/// also used for known types with fields this version of hoolamike does not know about
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct UnknownEntry {
+    #[serde(rename = "$type")]
+    pub type_name: String,
+    #[serde(flatten)]
+    pub fields: Map<String, Value>,
+}
+
+pub type UnknownState = UnknownEntry;
+pub type UnknownDirective = UnknownEntry;
+
+impl UnknownEntry {
+    fn field(&self, name: &str) -> Option<&Value> {
+        self.fields.get(name)
+    }
+
+    /// every wabbajack directive has a `Hash`, `Size` and `To`, so an unknown one can still be checked for being installed already
+    pub fn hash(&self) -> Option<&str> {
+        self.field("Hash").and_then(Value::as_str)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.field("Size")
+            .and_then(Value::as_u64)
+            .unwrap_or_default()
+    }
+
+    pub fn to(&self) -> Option<MaybeWindowsPath> {
+        self.field("To")
+            .and_then(Value::as_str)
+            .map(|to| MaybeWindowsPath(to.to_string()))
+    }
+}
+
+fn parse_as<T: DeserializeOwned>(fields: &Map<String, Value>) -> Result<(), serde_json::Error> {
+    serde_json::from_value::<T>(Value::Object(fields.clone())).map(|_| ())
+}
+
+/// `None` when hoolamike does not know the `$type`, otherwise why it did not parse
+fn known_state_error(entry: &UnknownEntry) -> Option<Result<(), serde_json::Error>> {
+    let fields = &entry.fields;
+    match entry.type_name.as_str() {
+        "NexusDownloader, Wabbajack.Lib" => Some(parse_as::<super::NexusState>(fields)),
+        "GameFileSourceDownloader, Wabbajack.Lib" => Some(parse_as::<super::GameFileSourceState>(fields)),
+        "MegaDownloader, Wabbajack.Lib" => Some(parse_as::<super::MegaState>(fields)),
+        "GoogleDriveDownloader, Wabbajack.Lib" => Some(parse_as::<super::GoogleDriveState>(fields)),
+        "MediaFireDownloader+State, Wabbajack.Lib" => Some(parse_as::<super::MediaFireState>(fields)),
+        "HttpDownloader, Wabbajack.Lib" => Some(parse_as::<super::HttpState>(fields)),
+        "ManualDownloader, Wabbajack.Lib" => Some(parse_as::<super::ManualState>(fields)),
+        "WabbajackCDNDownloader+State, Wabbajack.Lib" => Some(parse_as::<super::WabbajackCDNDownloaderState>(fields)),
+        "ModDBDownloader, Wabbajack.Lib" | "ModDBDownloader+State, Wabbajack.Lib" => Some(parse_as::<super::ModDBState>(fields)),
+        "VectorPlexusOAuthDownloader+State, Wabbajack.Lib" | "VectorPlexusDownloader, Wabbajack.Lib" => Some(parse_as::<super::VectorPlexusState>(fields)),
+        _ => None,
+    }
+}
+
+/// `None` when hoolamike does not know the `$type`, otherwise why it did not parse
+fn known_directive_error(entry: &UnknownEntry) -> Option<Result<(), serde_json::Error>> {
+    let fields = &entry.fields;
+    match entry.type_name.as_str() {
+        "CreateBSA" => Some(parse_as::<directive::create_bsa_directive::CreateBSADirective>(fields)),
+        "FromArchive" => Some(parse_as::<directive::FromArchiveDirective>(fields)),
+        "InlineFile" => Some(parse_as::<directive::InlineFileDirective>(fields)),
+        "PatchedFromArchive" => Some(parse_as::<directive::PatchedFromArchiveDirective>(fields)),
+        "RemappedInlineFile" => Some(parse_as::<directive::RemappedInlineFileDirective>(fields)),
+        "TransformedTexture" => Some(parse_as::<directive::TransformedTextureDirective>(fields)),
+        _ => None,
+    }
+}
+
+/// serde drops the reason a known `$type` did not parse when it falls back to the unknown variant, it's parsed once more to tell it
+fn fallback<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    known_error: fn(&UnknownEntry) -> Option<Result<(), serde_json::Error>>,
+) -> Result<UnknownEntry, D::Error> {
+    UnknownEntry::deserialize(deserializer).inspect(|entry| {
+        if let Some(Err(reason)) = known_error(entry) {
+            tracing::warn!(%reason, "[{}] does not parse, keeping it as it is", entry.type_name)
+        }
+    })
+}
+
+pub fn state_fallback<'de, D: Deserializer<'de>>(deserializer: D) -> Result<UnknownState, D::Error> {
+    fallback(deserializer, known_state_error)
+}
+
+pub fn directive_fallback<'de, D: Deserializer<'de>>(deserializer: D) -> Result<UnknownDirective, D::Error> {
+    fallback(deserializer, known_directive_error)
+}
+
+impl std::fmt::Display for UnknownEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] is not supported by this version of hoolamike or has fields it does not know about (fields: {})",
+            self.type_name,
+            self.fields.keys().join(", ")
+        )
+    }
+}
+
+/// the installation only fails once an unknown entry is actually needed, this tells about them up front
+pub fn warn_about_unknown_entries(modlist: &Modlist) {
+    modlist
+        .archives
+        .iter()
+        .filter_map(|archive| match &archive.state {
+            State::Unknown(unknown) => Some(unknown.type_name.as_str()),
+            _ => None,
+        })
+        .chain(
+            modlist
+                .directives
+                .iter()
+                .filter_map(|directive| match directive {
+                    Directive::Unknown(unknown) => Some(unknown.type_name.as_str()),
+                    _ => None,
+                }),
+        )
+        .counts()
+        .into_iter()
+        .sorted()
+        .for_each(|(type_name, count)| {
+            tracing::warn!(
+                "{count} entries of [{type_name}] are not supported by this version of hoolamike, archives which are already downloaded and files which are \
+                 already installed are fine, the installation fails on the rest"
+            )
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        anyhow::{Context, Result},
+    };
+
+    #[test]
+    fn test_unknown_entries_survive_reserialization() -> Result<()> {
+        let state = serde_json::json!({
+            "$type": "SomeNewDownloader, Wabbajack.Lib",
+            "Url": "https://example.com/file.7z",
+            "Token": {"Nested": [1, 2, 3]},
+        });
+        let directive = serde_json::json!({
+            "$type": "SomeNewDirective",
+            "Hash": "AAAAAAAAAAA=",
+            "Size": 42,
+            "To": "mods\\some mod\\plugin.esp",
+        });
+        let parsed_state = serde_json::from_value::<State>(state.clone())?;
+        let parsed_directive = serde_json::from_value::<Directive>(directive.clone())?;
+        assert!(matches!(&parsed_state, State::Unknown(unknown) if unknown.type_name == "SomeNewDownloader, Wabbajack.Lib"));
+        let Directive::Unknown(unknown) = &parsed_directive else {
+            anyhow::bail!("expected an unknown directive, got {parsed_directive:?}");
+        };
+        assert_eq!((unknown.hash(), unknown.size()), (Some("AAAAAAAAAAA="), 42));
+        assert_eq!(unknown.to().map(|to| to.0), Some("mods\\some mod\\plugin.esp".to_string()));
+        assert_eq!(serde_json::to_value(&parsed_state)?, state);
+        assert_eq!(serde_json::to_value(&parsed_directive)?, directive);
+        Ok(())
+    }
+
+    #[test]
+    fn test_known_types_are_not_unknown() -> Result<()> {
+        serde_json::from_value::<State>(serde_json::json!({
+            "$type": "HttpDownloader, Wabbajack.Lib",
+            "Headers": [],
+            "Url": "https://example.com/file.7z",
+        }))
+        .map(|state| assert!(matches!(state, State::Http(_))))
+        .map_err(Into::into)
+    }
+
+    #[test]
+    fn test_known_types_which_do_not_parse_keep_the_reason() -> Result<()> {
+        let state = serde_json::from_value::<State>(serde_json::json!({
+            "$type": "HttpDownloader, Wabbajack.Lib",
+            "Headers": [],
+            "Url": "https://example.com/file.7z",
+            "AddedInANewerVersion": true,
+        }))?;
+        let State::Unknown(unknown) = &state else {
+            anyhow::bail!("expected an unknown state, got {state:?}");
+        };
+        let reason = known_state_error(unknown)
+            .context("http is a known type")?
+            .unwrap_err();
+        assert!(reason.to_string().contains("AddedInANewerVersion"), "{reason}");
+        let unknown = UnknownEntry {
+            type_name: "SomeNewDirective".into(),
+            fields: Map::new(),
+        };
+        assert!(known_directive_error(&unknown).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_old_download_state_names_parse() -> Result<()> {
+        let vector_plexus = serde_json::from_value::<State>(serde_json::json!({
+            "$type": "VectorPlexusDownloader, Wabbajack.Lib",
+            "IPS4Mod": 42,
+            "IPS4File": "file.7z",
+        }))?;
+        assert!(matches!(vector_plexus, State::VectorPlexus(_)), "{vector_plexus:?}");
+        let mod_db = serde_json::from_value::<State>(serde_json::json!({
+            "$type": "ModDBDownloader+State, Wabbajack.Lib",
+            "Url": "https://www.moddb.com/downloads/start/42",
+        }))?;
+        assert!(matches!(mod_db, State::ModDB(_)), "{mod_db:?}");
+        Ok(())
+    }
+}
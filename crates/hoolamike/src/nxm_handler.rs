@@ -6,7 +6,10 @@ use {
             DownloadTask,
             WithArchiveDescriptor,
         },
-        install_modlist::{download_cache::DownloadCache, downloads::stream_file},
+        install_modlist::{
+            download_cache::DownloadCache,
+            downloads::{journal::DownloadJournal, stream_file, throttle::Throttle},
+        },
         modlist_json::{Archive, HumanUrl, Modlist, State},
         progress_bars_v2::io_progress_style,
         utils::{spawn_rayon, Obfuscated},
@@ -25,7 +28,7 @@ use {
     tokio_stream::wrappers::UnboundedReceiverStream,
     tracing::{debug, info, warn},
     tracing_indicatif::span_ext::IndicatifSpanExt,
-    utils::AbortOnDropExt,
+    utils::{AbortOnDropExt, QueueProgress},
 };
 
 pub mod cli;
@@ -108,9 +111,11 @@ pub async fn run(
         installation: InstallationConfig {
             wabbajack_file_path,
             installation_path: _,
+            ..
         },
         games: _,
         fixup: _,
+        archives,
         extras: _,
     }: HoolamikeConfig,
     HandleNxmCli {
@@ -120,6 +125,12 @@ pub async fn run(
         use_browser,
     }: HandleNxmCli,
 ) -> Result<()> {
+    // downloads can be deep-verified with 7z
+    crate::compression::passwords::set_archive_passwords(archives.passwords);
+    crate::compression::configure_wrapped_7zip(archives.seven_zip);
+    crate::downloaders::http_client::configure_http_client(&downloaders)?;
+    crate::install_modlist::downloads::backend::configure_download_backend(downloaders.backend.clone());
+    crate::install_modlist::download_cache::hash_cache::configure_hash_cache(&downloaders.downloads_directory, false).context("loading hash cache")?;
     match nxm_link {
         Some(nxm_link) => handle_nxm_link(port, nxm_link).await,
         None => {
@@ -133,7 +144,8 @@ pub async fn run(
                 .nexus
                 .api_key
                 .clone()
-                .context("nexus api key is required even for non-premium users")
+                .or_else(crate::login::credentials::stored_nexus_api_key)
+                .context("nexus api key is required even for non-premium users (run `hoolamike login nexus`)")
                 .and_then(|api_key| {
                     NexusDownloader::new(api_key)
                         .map(Arc::new)
@@ -174,8 +186,13 @@ pub async fn run(
                 })
                 .context("extracting specified wabbajack file")?;
 
+            let throttle = Throttle::new(&downloaders).pipe(Arc::new);
+            let journal = DownloadJournal::load(&downloaders.downloads_directory)
+                .context("loading download journal")
+                .map(Arc::new)?;
             let download_cache = DownloadCache::new(downloaders.downloads_directory)
                 .context("initializing download cache")
+                .map(|cache| cache.with_deep_verify_archives(downloaders.deep_verify_archives))
                 .map(Arc::new)?;
 
             let mut archive_lookup = {
@@ -238,8 +255,15 @@ pub async fn run(
                                  inner: (url, output_path),
                                  descriptor,
                              }| {
-                                stream_file(url.clone(), output_path.clone(), descriptor.size)
-                                    .inspect_err(move |reason| tracing::error!(?url, ?output_path, "could not finish download:\n\n{reason:?}"))
+                                stream_file(
+                                    url.clone(),
+                                    output_path.clone(),
+                                    descriptor.size,
+                                    descriptor.hash.clone(),
+                                    throttle.clone(),
+                                    journal.clone(),
+                                )
+                                .inspect_err(move |reason| tracing::error!(?url, ?output_path, "could not finish download:\n\n{reason:?}"))
                             },
                         )
                         .buffer_unordered(num_cpus::get() * 2)
@@ -329,8 +353,9 @@ pub async fn run(
                 })
                 .collect::<HashMap<_, _>>();
 
+            let progress = QueueProgress::new(initial_count);
             while let Some(nexus_website_url) = archive_lookup.keys().next().cloned() {
-                info!("queued {}/{}", initial_count.saturating_sub(archive_lookup.len()), initial_count);
+                info!("{}", progress.report(archive_lookup.len()));
 
                 info!("opening {nexus_website_url} with {use_browser}");
                 tokio::process::Command::new(&use_browser)
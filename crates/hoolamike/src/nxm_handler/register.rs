@@ -4,6 +4,7 @@ use {
     tracing::{info, instrument},
 };
 
+#[cfg(target_os = "linux")]
 mod linux {
     use super::*;
 
@@ -101,7 +102,7 @@ mod macos {
 
     #[instrument]
     pub fn register_nxm_handler() -> Result<()> {
-        todo!("setting up nxm handler is not implemented on this platform (macos)")
+        anyhow::bail!("setting up nxm handler is not implemented on this platform (macos), open nxm links with `hoolamike <nxm link>` instead")
     }
 }
 
@@ -28,3 +28,47 @@ impl<T> AbortOnDropExt<T> for tokio::task::JoinHandle<T> {
         AbortOnDrop(self)
     }
 }
+
+/// how far along the user is with clicking through the nexus website
+pub struct QueueProgress {
+    started: std::time::Instant,
+    total: usize,
+}
+
+impl QueueProgress {
+    pub fn new(total: usize) -> Self {
+        Self {
+            started: std::time::Instant::now(),
+            total,
+        }
+    }
+
+    /// extrapolated from how long the already queued files took, unknown until the first one is queued
+    fn eta(&self, elapsed: std::time::Duration, remaining: usize) -> Option<std::time::Duration> {
+        match self.total.saturating_sub(remaining) {
+            0 => None,
+            done => Some(elapsed.div_f64(done as f64).mul_f64(remaining as f64)),
+        }
+    }
+
+    pub fn report(&self, remaining: usize) -> String {
+        let done = self.total.saturating_sub(remaining);
+        match self.eta(self.started.elapsed(), remaining) {
+            Some(eta) => format!("queued {done}/{}, {remaining} remaining (~{} left)", self.total, indicatif::HumanDuration(eta)),
+            None => format!("queued {done}/{}, {remaining} remaining", self.total),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::time::Duration};
+
+    #[test]
+    fn test_eta_is_extrapolated_from_queued_files() {
+        let progress = QueueProgress::new(10);
+        assert_eq!(progress.eta(Duration::from_secs(30), 10), None);
+        assert_eq!(progress.eta(Duration::from_secs(30), 7), Some(Duration::from_secs(70)));
+        assert_eq!(progress.eta(Duration::from_secs(30), 0), Some(Duration::ZERO));
+    }
+}
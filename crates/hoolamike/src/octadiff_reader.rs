@@ -362,5 +362,7 @@ where
     }
 }
 
+pub mod writer;
+
 #[cfg(test)]
 mod tests;
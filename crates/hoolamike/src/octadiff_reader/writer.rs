@@ -0,0 +1,426 @@
+//! the other half of octodiff: a signature describes the chunks of the original file, a delta
+//! tells how to build the new file out of those chunks and whatever bytes could not be found in them
+use {
+    super::*,
+    sha1::{Digest, Sha1},
+    std::{
+        collections::{BTreeMap, VecDeque},
+        io::{BufReader, Write},
+    },
+};
+
+/// same default as octodiff itself
+pub const DEFAULT_CHUNK_SIZE: u16 = 2048;
+const DEFAULT_ROLLING_CHECKSUM_ALGORITHM_NAME: &[u8] = b"Adler32";
+/// bytes which were not found in the original file are written out in commands of at most this size
+const MAX_WRITE_COMMAND_LENGTH: usize = 1024 * 1024;
+
+type Sha1Hash = [u8; DEFAULT_HASH_ALGORITHM_HASH_LEN];
+
+/// octodiff's adler32 does not take the modulo, both halves wrap around at 16 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RollingChecksum(u32);
+
+impl RollingChecksum {
+    fn calculate(block: &[u8]) -> Self {
+        let (a, b) = block.iter().fold((1u16, 0u16), |(a, b), byte| {
+            let a = a.wrapping_add(*byte as u16);
+            (a, b.wrapping_add(a))
+        });
+        Self(((b as u32) << 16) | a as u32)
+    }
+
+    fn rotate(self, remove: u8, add: u8, chunk_size: usize) -> Self {
+        let (a, b) = ((self.0 & 0xffff) as u16, (self.0 >> 16) as u16);
+        let a = a.wrapping_sub(remove as u16).wrapping_add(add as u16);
+        let b = b
+            .wrapping_sub((chunk_size as u16).wrapping_mul(remove as u16))
+            .wrapping_add(a)
+            .wrapping_sub(1);
+        Self(((b as u32) << 16) | a as u32)
+    }
+}
+
+fn sha1_hash(bytes: &[u8]) -> Sha1Hash {
+    Sha1::digest(bytes).into()
+}
+
+#[binrw::binrw]
+#[brw(little, magic = b"OCTOSIG")]
+#[derive(Debug)]
+pub struct SignatureMetadata {
+    #[br(assert(version == BINARY_VERSION, "binary version missmatch"))]
+    pub version: BinaryVersion,
+    #[br(assert(hash_algorithm_name.bytes == DEFAULT_HASH_ALGORITM_NAME, "hash algorithm mismatch"))]
+    pub hash_algorithm_name: LengthPrefixedString,
+    #[br(assert(rolling_checksum_algorithm_name.bytes == DEFAULT_ROLLING_CHECKSUM_ALGORITHM_NAME, "rolling checksum algorithm mismatch"))]
+    pub rolling_checksum_algorithm_name: LengthPrefixedString,
+}
+
+impl Default for SignatureMetadata {
+    fn default() -> Self {
+        Self {
+            version: BINARY_VERSION,
+            hash_algorithm_name: LengthPrefixedString {
+                bytes: DEFAULT_HASH_ALGORITM_NAME.to_vec(),
+            },
+            rolling_checksum_algorithm_name: LengthPrefixedString {
+                bytes: DEFAULT_ROLLING_CHECKSUM_ALGORITHM_NAME.to_vec(),
+            },
+        }
+    }
+}
+
+#[binrw::binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignatureChunk {
+    pub length: u16,
+    pub rolling_checksum: u32,
+    pub hash: Sha1Hash,
+}
+
+/// chunks follow each other, their offsets in the original file are not stored
+#[derive(Debug)]
+pub struct Signature {
+    pub metadata: SignatureMetadata,
+    pub chunks: Vec<SignatureChunk>,
+}
+
+impl Signature {
+    pub fn from_source(source: impl Read, chunk_size: u16) -> Result<Self> {
+        anyhow::ensure!(chunk_size > 0, "chunk size must be non-zero");
+        let mut source = BufReader::new(source);
+        let mut buf = vec![0; chunk_size as usize];
+        std::iter::from_fn(|| {
+            fill_buffer(&mut source, &mut buf)
+                .map(|read| {
+                    (read > 0).then(|| SignatureChunk {
+                        length: read as u16,
+                        rolling_checksum: RollingChecksum::calculate(&buf[..read]).0,
+                        hash: sha1_hash(&buf[..read]),
+                    })
+                })
+                .transpose()
+        })
+        .collect::<Result<Vec<_>>>()
+        .context("reading chunks of original file")
+        .map(|chunks| Self {
+            metadata: SignatureMetadata::default(),
+            chunks,
+        })
+    }
+
+    pub fn read<R: Read + Seek>(mut reader: R) -> Result<Self> {
+        let metadata = WithEof::<SignatureMetadata>::read_le(&mut reader)
+            .context("reading signature metadata")
+            .map(|WithEof { inner, eof: _ }| inner)?;
+        let mut chunks = vec![];
+        while reader.stream_position()? < reader.stream_len()? {
+            SignatureChunk::read(&mut reader)
+                .with_context(|| format!("reading chunk [{}]", chunks.len()))
+                .map(|chunk| chunks.push(chunk))?;
+        }
+        Ok(Self { metadata, chunks })
+    }
+
+    pub fn write<W: Write + Seek>(self, mut writer: W) -> Result<()> {
+        WithEof {
+            inner: self.metadata,
+            eof: BINARY_END_OF_METADATA,
+        }
+        .write_le(&mut writer)
+        .context("writing signature metadata")?;
+        self.chunks
+            .iter()
+            .try_for_each(|chunk| chunk.write(&mut writer))
+            .context("writing chunks")
+    }
+
+    /// chunks of the original file by length and rolling checksum, along with where they start
+    fn index(&self) -> BTreeMap<(u16, u32), Vec<(u64, Sha1Hash)>> {
+        self.chunks
+            .iter()
+            .scan(0u64, |start, chunk| {
+                let chunk_start = *start;
+                *start += chunk.length as u64;
+                Some((chunk_start, chunk))
+            })
+            .fold(BTreeMap::<_, Vec<_>>::new(), |mut index, (start, chunk)| {
+                index
+                    .entry((chunk.length, chunk.rolling_checksum))
+                    .or_default()
+                    .push((start, chunk.hash));
+                index
+            })
+    }
+}
+
+/// fills the buffer unless the reader ends first
+fn fill_buffer(mut reader: impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).context("reading")? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+/// neighbouring copies are merged and writes are buffered, so the delta stays as small as octodiff's own
+struct DeltaWriter<W> {
+    writer: W,
+    pending_copy: Option<(u64, u64)>,
+    pending_write: Vec<u8>,
+}
+
+impl<W: Write + Seek> DeltaWriter<W> {
+    fn copy(&mut self, start: u64, length: u64) -> Result<()> {
+        self.flush_write()?;
+        match self.pending_copy.as_mut() {
+            Some((pending_start, pending_length)) if *pending_start + *pending_length == start => {
+                *pending_length += length;
+                Ok(())
+            }
+            _ => self
+                .flush_copy()
+                .map(|_| self.pending_copy = Some((start, length))),
+        }
+    }
+
+    fn write(&mut self, bytes: impl IntoIterator<Item = u8>) -> Result<()> {
+        self.flush_copy()?;
+        for byte in bytes {
+            self.pending_write.push(byte);
+            if self.pending_write.len() >= MAX_WRITE_COMMAND_LENGTH {
+                self.flush_write()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_copy(&mut self) -> Result<()> {
+        match self.pending_copy.take() {
+            Some((start, length)) => self
+                .writer
+                .write_all(&[0x60])
+                .context("writing copy command")
+                .and_then(|_| {
+                    CopyDataCommand {
+                        start: start as _,
+                        length: length as _,
+                    }
+                    .write(&mut self.writer)
+                    .context("writing copy command")
+                }),
+            None => Ok(()),
+        }
+    }
+
+    fn flush_write(&mut self) -> Result<()> {
+        match self.pending_write.is_empty() {
+            true => Ok(()),
+            false => self
+                .writer
+                .write_all(&[0x80])
+                .context("writing write command")
+                .and_then(|_| {
+                    WriteDataCommand {
+                        length: self.pending_write.len() as _,
+                    }
+                    .write(&mut self.writer)
+                    .context("writing write command")
+                })
+                .and_then(|_| {
+                    self.writer
+                        .write_all(&self.pending_write)
+                        .context("writing data")
+                })
+                .map(|_| self.pending_write.clear()),
+        }
+    }
+
+    fn finish(mut self) -> Result<W> {
+        self.flush_copy()
+            .and_then(|_| self.flush_write())
+            .and_then(|_| self.writer.flush().context("flushing delta"))
+            .map(|_| self.writer)
+    }
+}
+
+/// writes a delta which turns the file described by `signature` into `new_file`, the new file is read twice:
+/// once for the hash which goes into the header and once to look for chunks of the original file
+#[tracing::instrument(skip_all)]
+pub fn write_delta<R, W>(signature: &Signature, mut new_file: R, mut writer: W) -> Result<W>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+{
+    let hash = {
+        let (mut hasher, mut buf) = (Sha1::new(), vec![0; 64 * 1024]);
+        loop {
+            match fill_buffer(&mut new_file, &mut buf).context("hashing new file")? {
+                0 => break,
+                read => hasher.update(&buf[..read]),
+            }
+        }
+        new_file.rewind().context("rewinding new file")?;
+        Sha1Hash::from(hasher.finalize())
+    };
+    WithEof {
+        inner: OctodiffMetadata {
+            version: BINARY_VERSION,
+            hash_algorithm_name: LengthPrefixedString {
+                bytes: DEFAULT_HASH_ALGORITM_NAME.to_vec(),
+            },
+            hash_length: DEFAULT_HASH_ALGORITHM_HASH_LEN as _,
+            hash: HashBytes(ConstantSizedString { bytes: hash }),
+        },
+        eof: BINARY_END_OF_METADATA,
+    }
+    .write_le(&mut writer)
+    .context("writing delta metadata")?;
+
+    let index = signature.index();
+    let chunk_size = signature
+        .chunks
+        .iter()
+        .map(|chunk| chunk.length)
+        .max()
+        .unwrap_or(DEFAULT_CHUNK_SIZE) as usize;
+    let find = |window: &[u8], checksum: RollingChecksum| {
+        index
+            .get(&(window.len() as u16, checksum.0))
+            .and_then(|candidates| {
+                let hash = sha1_hash(window);
+                candidates
+                    .iter()
+                    .find_map(|(start, candidate)| (*candidate == hash).then_some(*start))
+            })
+    };
+
+    let mut new_file = BufReader::new(new_file).bytes();
+    let mut delta = DeltaWriter {
+        writer,
+        pending_copy: None,
+        pending_write: vec![],
+    };
+    let mut window = VecDeque::with_capacity(chunk_size);
+    loop {
+        // (re)fill the window after a match
+        while window.len() < chunk_size {
+            match new_file.next().transpose().context("reading new file")? {
+                Some(byte) => window.push_back(byte),
+                None => break,
+            }
+        }
+        if window.len() < chunk_size {
+            // the last chunk of the original file can be shorter than the others
+            let window = window.make_contiguous();
+            match find(window, RollingChecksum::calculate(window)) {
+                Some(start) => delta.copy(start, window.len() as _)?,
+                None => delta.write(window.iter().copied())?,
+            }
+            break;
+        }
+        let mut checksum = RollingChecksum::calculate(window.make_contiguous());
+        loop {
+            if let Some(start) = find(window.make_contiguous(), checksum) {
+                delta.copy(start, window.len() as _)?;
+                window.clear();
+                break;
+            }
+            match new_file.next().transpose().context("reading new file")? {
+                Some(byte) => {
+                    let removed = window.pop_front().expect("window is full");
+                    window.push_back(byte);
+                    checksum = checksum.rotate(removed, byte, chunk_size);
+                    delta.write([removed])?;
+                }
+                None => break,
+            }
+        }
+        if window.len() == chunk_size {
+            // the new file ended without the window matching anything
+            let removed = window.pop_front().expect("window is full");
+            delta.write([removed])?;
+        }
+    }
+    delta.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::io::Cursor};
+
+    fn round_trip(original: &[u8], new: &[u8], chunk_size: u16) -> Result<Vec<String>> {
+        let mut signature = Cursor::new(vec![]);
+        Signature::from_source(original, chunk_size)?.write(&mut signature)?;
+        let signature = Signature::read(Cursor::new(signature.into_inner()))?;
+        let delta = write_delta(&signature, Cursor::new(new), Cursor::new(vec![]))?.into_inner();
+        let (_, commands) = OctodiffMetadata::explain(Cursor::new(delta.clone()))?;
+        let mut patched = vec![];
+        if let Some(mut reader) = ApplyDetla::new_from_readers(Cursor::new(original), Cursor::new(delta))? {
+            assert_eq!(reader.metadata.hash.0.bytes, sha1_hash(new));
+            reader.read_to_end(&mut patched)?;
+        }
+        assert_eq!(patched, new);
+        Ok(commands
+            .into_iter()
+            .map(|command| command.to_string())
+            .collect())
+    }
+
+    #[test]
+    fn test_rolling_checksum_rotates_like_it_is_calculated() {
+        let bytes = (0..=255u8).cycle().take(5000).collect::<Vec<_>>();
+        let chunk_size = 2048;
+        (0..bytes.len() - chunk_size).fold(RollingChecksum::calculate(&bytes[..chunk_size]), |checksum, start| {
+            assert_eq!(checksum, RollingChecksum::calculate(&bytes[start..][..chunk_size]));
+            checksum.rotate(bytes[start], bytes[start + chunk_size], chunk_size)
+        });
+    }
+
+    #[test]
+    fn test_identical_files_are_a_single_copy() -> Result<()> {
+        let original = (0..10_000u32)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect::<Vec<_>>();
+        let commands = round_trip(&original, &original, 1024)?;
+        assert_eq!(commands, vec!["copy start=0, length=10000"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trips_edited_files() -> Result<()> {
+        let original = (0..20_000u32)
+            .map(|i| (i * 31 % 253) as u8)
+            .collect::<Vec<_>>();
+        let edited = [b"prefix".as_slice(), &original[..5000], b"inserted in the middle", &original[7000..], b"suffix"].concat();
+        round_trip(&original, &edited, 512)?;
+        round_trip(&original, &original[..100], 512)?;
+        round_trip(&original, b"", 512)?;
+        round_trip(b"", b"nothing to copy from", 512)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_delta_is_applied_as_a_patch() -> Result<()> {
+        let original = (0..4096u32).map(|i| (i % 13) as u8).collect::<Vec<_>>();
+        let new = [&original[1000..], b"more".as_slice()].concat();
+        let directory = tempfile::tempdir()?;
+        let (original_path, delta_path) = (directory.path().join("original"), directory.path().join("delta"));
+        std::fs::write(&original_path, &original)?;
+        let signature = Signature::from_source(original.as_slice(), DEFAULT_CHUNK_SIZE)?;
+        write_delta(&signature, Cursor::new(&new), std::fs::File::create(&delta_path)?)?;
+        let mut patched = vec![];
+        crate::patching::apply_patch(
+            (&original_path, std::fs::File::open(&original_path)?),
+            (&delta_path, std::fs::File::open(&delta_path)?),
+        )?
+        .read_to_end(&mut patched)?;
+        assert_eq!(patched, new);
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! deltas of `PatchedFromArchive` directives, wabbajack compiles octodiff ones but community patch workflows
+//! also produce xdelta3 (vcdiff) and bsdiff, the format is told by the first bytes of the patch
+use {
+    crate::{
+        compression::forward_only_seek::ForwardOnlySeek,
+        utils::{scoped_temp_path, PathReadWrite, ReadableCatchUnwindExt},
+    },
+    anyhow::{Context, Result},
+    std::{
+        fs::File,
+        io::{BufReader, Read, Seek, SeekFrom},
+        path::Path,
+    },
+    tap::prelude::*,
+    tempfile::TempPath,
+};
+
+pub mod bsdiff;
+
+const OCTODIFF_MAGIC: &[u8] = b"OCTODELTA";
+/// `VCD` with the high bits set, followed by the version
+const VCDIFF_MAGIC: &[u8] = &[0xD6, 0xC3, 0xC4, 0x00];
+/// patches are applied side by side, each one only keeps this much of the source and of the patch in memory
+pub const PATCH_BUFFER_SIZE: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    Octodiff,
+    Xdelta3,
+    Bsdiff,
+}
+
+impl PatchFormat {
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        [
+            (OCTODIFF_MAGIC, Self::Octodiff),
+            (VCDIFF_MAGIC, Self::Xdelta3),
+            (bsdiff::MAGIC.as_slice(), Self::Bsdiff),
+        ]
+        .into_iter()
+        .find_map(|(magic, format)| header.starts_with(magic).then_some(format))
+    }
+
+    /// leaves the patch where it was
+    pub fn detect_in(patch: &mut File) -> Result<Self> {
+        let mut header = Vec::with_capacity(OCTODIFF_MAGIC.len());
+        let mut header_reader = patch.take(OCTODIFF_MAGIC.len() as _);
+        header_reader
+            .read_to_end(&mut header)
+            .and_then(|_| header_reader.into_inner().seek(SeekFrom::Start(0)))
+            .context("reading patch header")?;
+        Self::detect(&header).with_context(|| format!("unknown patch format (header: {header:02x?})"))
+    }
+}
+
+/// the xdelta decoder only works with paths, the patched file is written down and read back
+struct DecodedFile {
+    file: File,
+    _path: TempPath,
+}
+
+impl Read for DecodedFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+fn decode_xdelta3(source: &Path, patch: &Path) -> Result<DecodedFile> {
+    scoped_temp_path().and_then(|output| {
+        std::panic::catch_unwind(|| xdelta::decode_file(Some(&source.to_path_buf()), &patch.to_path_buf(), &output.to_path_buf()))
+            .for_anyhow()
+            .context("decoding xdelta3 patch")
+            .and_then(|_| output.open_file_read())
+            .map(|(_, file)| DecodedFile { file, _path: output })
+    })
+}
+
+/// the patched file, octodiff and bsdiff patches are applied as it's being read through bounded buffers
+pub fn apply_patch((source_path, source): (&Path, File), (patch_path, mut patch): (&Path, File)) -> Result<Box<dyn Read>> {
+    PatchFormat::detect_in(&mut patch)
+        .tap_ok(|format| tracing::debug!(?format, "applying patch"))
+        .and_then(|format| match format {
+            PatchFormat::Octodiff => crate::octadiff_reader::ApplyDetla::new_from_readers(
+                BufReader::with_capacity(PATCH_BUFFER_SIZE, source),
+                ForwardOnlySeek::new(BufReader::with_capacity(PATCH_BUFFER_SIZE, patch)),
+            )
+            .context("invalid delta")?
+            .context("delta is empty")
+            .map(|reader| Box::new(reader) as Box<dyn Read>),
+            PatchFormat::Xdelta3 => decode_xdelta3(source_path, patch_path).map(|reader| Box::new(reader) as Box<dyn Read>),
+            PatchFormat::Bsdiff => bsdiff::BsdiffReader::new(BufReader::with_capacity(PATCH_BUFFER_SIZE, source), || {
+                patch_path.open_file_read().map(|(_, patch)| patch)
+            })
+            .map(|reader| Box::new(reader) as Box<dyn Read>),
+        })
+        .with_context(|| format!("applying [{}] to [{}]", patch_path.display(), source_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_formats() {
+        assert_eq!(
+            PatchFormat::detect(include_bytes!("./octadiff_reader/example-1.octadiff-delta")),
+            Some(PatchFormat::Octodiff)
+        );
+        assert_eq!(PatchFormat::detect(&[0xD6, 0xC3, 0xC4, 0x00, 0x05]), Some(PatchFormat::Xdelta3));
+        assert_eq!(PatchFormat::detect(b"BSDIFF40\x00\x00"), Some(PatchFormat::Bsdiff));
+        assert_eq!(PatchFormat::detect(b"BSDIFF4"), None);
+    }
+}
@@ -0,0 +1,222 @@
+//! bsdiff 4 (`BSDIFF40`) patches, a header followed by three bzip2 streams: control triples,
+//! bytes added to the source bytes and bytes inserted as they are
+use {
+    anyhow::{Context, Result},
+    bzip2::read::BzDecoder,
+    std::io::{self, BufReader, Read, Seek, SeekFrom},
+};
+
+pub const MAGIC: &[u8; 8] = b"BSDIFF40";
+const HEADER_LENGTH: usize = 32;
+/// diff bytes are read through this buffer before they are added to the source bytes
+const DIFF_BUFFER_LENGTH: usize = 8 * 1024;
+/// compressed bytes read ahead for each of the blocks
+const BLOCK_BUFFER_LENGTH: usize = 64 * 1024;
+
+/// bsdiff stores numbers as sign and magnitude
+fn offtin(bytes: &[u8]) -> i64 {
+    let bytes: [u8; 8] = bytes.try_into().expect("numbers are 8 bytes long");
+    let magnitude = (u64::from_le_bytes(bytes) & !(1 << 63)) as i64;
+    match bytes[7] & 0x80 {
+        0 => magnitude,
+        _ => -magnitude,
+    }
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+type Block<D> = BzDecoder<BufReader<io::Take<D>>>;
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Control,
+    Add { add: u64, insert: u64, seek: i64 },
+    Insert { insert: u64, seek: i64 },
+}
+
+/// the patched file, produced as it's read
+pub struct BsdiffReader<S, D> {
+    source: S,
+    /// where the patch reads the source next, it can point before or past the source
+    old_position: i64,
+    /// where the source reader actually is
+    source_position: i64,
+    control: Block<D>,
+    diff: Block<D>,
+    extra: Block<D>,
+    /// bytes of the patched file which were not read yet
+    remaining: u64,
+    step: Step,
+}
+
+impl<S, D> BsdiffReader<S, D>
+where
+    S: Read + Seek,
+    D: Read + Seek,
+{
+    /// the blocks are read one after another but used side by side, each one is read through its own handle to the patch
+    pub fn new(source: S, mut open_patch: impl FnMut() -> Result<D>) -> Result<Self> {
+        let mut header = [0; HEADER_LENGTH];
+        open_patch()
+            .and_then(|mut patch| patch.read_exact(&mut header).context("patch is too short"))
+            .context("reading header")?;
+        anyhow::ensure!(header.starts_with(MAGIC), "not a bsdiff 4 patch");
+        let field = |index: usize| u64::try_from(offtin(&header[MAGIC.len() + index * 8..][..8])).context("negative length in header");
+        let (control_length, diff_length, new_size) = (field(0)?, field(1)?, field(2)?);
+        let mut block = |start: u64, length: u64| {
+            open_patch()
+                .and_then(|mut patch| {
+                    patch
+                        .seek(SeekFrom::Start(start))
+                        .context("seeking")
+                        .map(|_| patch)
+                })
+                .map(|patch| BzDecoder::new(BufReader::with_capacity(BLOCK_BUFFER_LENGTH, patch.take(length))))
+        };
+        let diff_start = HEADER_LENGTH as u64 + control_length;
+        let extra_start = diff_start + diff_length;
+        Ok(Self {
+            source,
+            old_position: 0,
+            source_position: 0,
+            control: block(HEADER_LENGTH as u64, control_length).context("control block")?,
+            diff: block(diff_start, diff_length).context("diff block")?,
+            extra: block(extra_start, u64::MAX).context("extra block")?,
+            remaining: new_size,
+            step: Step::Control,
+        })
+    }
+
+    /// bytes outside of the source count as zeros
+    fn read_old(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        buf.fill(0);
+        let skip = (-self.old_position).clamp(0, buf.len() as i64) as usize;
+        if skip < buf.len() {
+            let start = self.old_position + skip as i64;
+            if self.source_position != start {
+                self.source.seek(SeekFrom::Start(start as u64))?;
+                self.source_position = start;
+            }
+            let mut filled = skip;
+            while filled < buf.len() {
+                match self.source.read(&mut buf[filled..])? {
+                    0 => break,
+                    read => filled += read,
+                }
+            }
+            self.source_position += (filled - skip) as i64;
+        }
+        self.old_position += buf.len() as i64;
+        Ok(())
+    }
+}
+
+impl<S, D> Read for BsdiffReader<S, D>
+where
+    S: Read + Seek,
+    D: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.remaining == 0 || buf.is_empty() {
+                return Ok(0);
+            }
+            let available = |length: u64, limit: usize| length.min(self.remaining).min(limit as u64) as usize;
+            match self.step {
+                Step::Control => {
+                    let mut triple = [0; 24];
+                    self.control.read_exact(&mut triple)?;
+                    let (add, insert, seek) = (offtin(&triple[..8]), offtin(&triple[8..16]), offtin(&triple[16..]));
+                    self.step = Step::Add {
+                        add: u64::try_from(add).map_err(|_| invalid_data("negative add length"))?,
+                        insert: u64::try_from(insert).map_err(|_| invalid_data("negative insert length"))?,
+                        seek,
+                    };
+                }
+                Step::Add { add: 0, insert, seek } => self.step = Step::Insert { insert, seek },
+                Step::Add { add, insert, seek } => {
+                    let length = available(add, buf.len().min(DIFF_BUFFER_LENGTH));
+                    let mut diff = [0; DIFF_BUFFER_LENGTH];
+                    self.read_old(&mut buf[..length])?;
+                    self.diff.read_exact(&mut diff[..length])?;
+                    buf[..length]
+                        .iter_mut()
+                        .zip(&diff[..length])
+                        .for_each(|(byte, diff)| *byte = byte.wrapping_add(*diff));
+                    self.step = Step::Add {
+                        add: add - length as u64,
+                        insert,
+                        seek,
+                    };
+                    self.remaining -= length as u64;
+                    return Ok(length);
+                }
+                Step::Insert { insert: 0, seek } => {
+                    self.old_position += seek;
+                    self.step = Step::Control;
+                }
+                Step::Insert { insert, seek } => {
+                    let length = available(insert, buf.len());
+                    self.extra.read_exact(&mut buf[..length])?;
+                    self.step = Step::Insert {
+                        insert: insert - length as u64,
+                        seek,
+                    };
+                    self.remaining -= length as u64;
+                    return Ok(length);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::io::{Cursor, Write},
+    };
+
+    fn number(value: i64) -> [u8; 8] {
+        (value.unsigned_abs() | if value < 0 { 1 << 63 } else { 0 }).to_le_bytes()
+    }
+
+    fn compressed(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::best());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_offtin() {
+        assert_eq!(offtin(&number(1234)), 1234);
+        assert_eq!(offtin(&number(-1234)), -1234);
+    }
+
+    #[test]
+    fn test_applies_patch() -> Result<()> {
+        let old = b"hello world";
+        // keeps `hello `, inserts `there`, then goes back to the start and adds one to `h`
+        let control = [number(6), number(5), number(-6), number(1), number(0), number(0)].concat();
+        let diff = [vec![0; 6], vec![1]].concat();
+        let extra = b"there".to_vec();
+        let (control, diff, extra) = (compressed(&control), compressed(&diff), compressed(&extra));
+        let patch = [
+            MAGIC.to_vec(),
+            number(control.len() as i64).to_vec(),
+            number(diff.len() as i64).to_vec(),
+            number(12).to_vec(),
+            control,
+            diff,
+            extra,
+        ]
+        .concat();
+
+        let mut patched = Vec::new();
+        BsdiffReader::new(Cursor::new(old), || Ok(Cursor::new(patch.clone())))?.read_to_end(&mut patched)?;
+        assert_eq!(patched, b"hello therei");
+        Ok(())
+    }
+}
@@ -31,6 +31,7 @@ macro_rules! target_os_only {
 // }
 
 pub mod diffing;
+pub mod load_order;
 
 #[extension_traits::extension(pub trait LinesPreservePlatform)]
 impl str {
@@ -231,6 +232,7 @@ fn post_install_fixup_common(config: &HoolamikeConfig) -> Result<()> {
     Ok(())
         //
         .and_then(|_| set_resolution::update_resolution(&config.installation.installation_path, config.fixup.game_resolution))
+        .and_then(|_| load_order::LoadOrderContext::from_config(config).fix_load_order())
 }
 
 #[instrument]
@@ -0,0 +1,406 @@
+//! MO2 keeps the load order of every profile in `profiles/<name>/plugins.txt` and `profiles/<name>/loadorder.txt`.
+//! modlists authored on windows frequently ship them with a BOM, mixed line endings, a legacy codepage
+//! or plugin names whose casing does not match the files on disk - none of which matters on a case-insensitive filesystem
+use {
+    super::common::patch_file,
+    crate::{config_file::HoolamikeConfig, install_modlist::directives::remapped_inline_file::wabbajack_consts::MO2_PROFILES_FOLDER_NAME},
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, BTreeSet},
+        path::{Path, PathBuf},
+    },
+    tap::prelude::*,
+    tracing::{debug, info, info_span, instrument, warn},
+};
+
+pub const PLUGINS_TXT: &str = "plugins.txt";
+pub const LOADORDER_TXT: &str = "loadorder.txt";
+pub const MODLIST_TXT: &str = "modlist.txt";
+
+const PLUGIN_EXTENSIONS: &[&str] = &["esp", "esm", "esl"];
+const LINE_ENDING: &str = "\r\n";
+const BOM: char = '\u{feff}';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginEntry {
+    pub name: String,
+    pub enabled: bool,
+}
+
+/// newer games mark enabled plugins with an asterisk, older ones only list the enabled plugins
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PluginsTxt {
+    pub asterisk_format: bool,
+    pub entries: Vec<PluginEntry>,
+}
+
+fn meaningful_lines(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .trim_start_matches(BOM)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+impl PluginsTxt {
+    pub fn parse(contents: &str) -> Self {
+        let asterisk_format = meaningful_lines(contents).any(|line| line.starts_with('*'));
+        Self {
+            asterisk_format,
+            entries: meaningful_lines(contents)
+                .map(|line| match line.strip_prefix('*') {
+                    Some(name) => PluginEntry {
+                        name: name.to_string(),
+                        enabled: true,
+                    },
+                    None => PluginEntry {
+                        name: line.to_string(),
+                        enabled: !asterisk_format,
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.name.as_str())
+    }
+}
+
+impl std::fmt::Display for PluginsTxt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("# This file was automatically generated by Mod Organizer.")?;
+        f.write_str(LINE_ENDING)?;
+        self.entries
+            .iter()
+            .try_for_each(|PluginEntry { name, enabled }| {
+                match (self.asterisk_format, enabled) {
+                    (true, true) => write!(f, "*{name}"),
+                    _ => write!(f, "{name}"),
+                }
+                .and_then(|_| f.write_str(LINE_ENDING))
+            })
+    }
+}
+
+pub fn parse_loadorder_txt(contents: &str) -> Vec<String> {
+    meaningful_lines(contents).map(ToOwned::to_owned).collect()
+}
+
+pub fn write_loadorder_txt(entries: &[String]) -> String {
+    std::iter::once("# This file was automatically generated by Mod Organizer.")
+        .chain(entries.iter().map(String::as_str))
+        .map(|line| format!("{line}{LINE_ENDING}"))
+        .collect()
+}
+
+/// plugins present in plugins.txt keep the relative order from plugins.txt, anything else stays where it was
+pub fn reorder_loadorder(loadorder: &[String], plugins: &PluginsTxt) -> Vec<String> {
+    let key = |name: &str| name.to_lowercase();
+    let listed = loadorder
+        .iter()
+        .map(|name| key(name))
+        .collect::<BTreeSet<_>>();
+    let in_plugins = plugins.names().map(key).collect::<BTreeSet<_>>();
+    let mut ordered = plugins
+        .names()
+        .filter(|name| listed.contains(&key(name)))
+        .map(ToOwned::to_owned)
+        .collect_vec()
+        .into_iter();
+    loadorder
+        .iter()
+        .map(|name| match in_plugins.contains(&key(name)) {
+            true => ordered.next().unwrap_or_else(|| name.clone()),
+            false => name.clone(),
+        })
+        .chain(
+            plugins
+                .names()
+                .filter(|name| !listed.contains(&key(name)))
+                .map(ToOwned::to_owned),
+        )
+        .unique_by(|name| key(name))
+        .collect()
+}
+
+/// plugin file names keyed by their lowercase name
+type PluginFiles = BTreeMap<String, String>;
+
+fn plugins_in_directory(directory: &Path) -> Result<PluginFiles> {
+    match directory.exists() {
+        false => Ok(Default::default()),
+        true => std::fs::read_dir(directory)
+            .with_context(|| format!("reading [{directory:?}]"))?
+            .map(|entry| {
+                entry
+                    .context("reading directory entry")
+                    .map(|entry| entry.path())
+            })
+            .filter_ok(|path| path.is_file())
+            .filter_map_ok(|path| {
+                path.extension()
+                    .map(|extension| extension.to_string_lossy().to_lowercase())
+                    .filter(|extension| PLUGIN_EXTENSIONS.contains(&extension.as_str()))
+                    .and_then(|_| {
+                        path.file_name()
+                            .map(|name| name.to_string_lossy().to_string())
+                    })
+            })
+            .map_ok(|name| (name.to_lowercase(), name))
+            .collect(),
+    }
+}
+
+fn enabled_mods(profile: &Path) -> Result<Vec<String>> {
+    let modlist = profile.join(MODLIST_TXT);
+    match modlist.exists() {
+        false => Ok(vec![]),
+        true => read_text_lossy(&modlist).map(|contents| {
+            meaningful_lines(&contents)
+                .filter_map(|line| line.strip_prefix('+'))
+                .map(ToOwned::to_owned)
+                .collect()
+        }),
+    }
+}
+
+/// falls back to latin-1 for files saved in a legacy windows codepage
+fn decode_lossy(bytes: Vec<u8>) -> String {
+    match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(not_utf8) => not_utf8.into_bytes().into_iter().map(char::from).collect(),
+    }
+}
+
+fn read_text_lossy(path: &Path) -> Result<String> {
+    std::fs::read(path)
+        .with_context(|| format!("reading [{path:?}]"))
+        .map(decode_lossy)
+}
+
+/// rewrites the file as utf-8 without a BOM so that it can be patched like any other text file
+fn normalize_encoding(path: &Path) -> Result<String> {
+    std::fs::read(path)
+        .with_context(|| format!("reading [{path:?}]"))
+        .and_then(|bytes| {
+            let text = decode_lossy(bytes.clone())
+                .trim_start_matches(BOM)
+                .to_string();
+            match text.as_bytes() == bytes.as_slice() {
+                true => Ok(text),
+                false => {
+                    info!("re-encoding [{path:?}] as utf-8");
+                    std::fs::write(path, &text)
+                        .context("writing re-encoded file")
+                        .map(|_| text)
+                }
+            }
+        })
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LoadOrderReport {
+    /// listed in plugins.txt but not present in any enabled mod or game data directory
+    pub missing: Vec<String>,
+    /// present in an enabled mod but not listed in plugins.txt
+    pub unlisted: Vec<String>,
+    /// entries whose casing was fixed to match the file on disk
+    pub recased: Vec<(String, String)>,
+}
+
+pub struct LoadOrderContext {
+    pub installation_path: PathBuf,
+    /// `Data` directories of the game, plugins found there are implicitly available
+    pub game_data_directories: Vec<PathBuf>,
+}
+
+impl LoadOrderContext {
+    pub fn from_config(config: &HoolamikeConfig) -> Self {
+        let installation_path = config.installation.installation_path.clone();
+        Self {
+            game_data_directories: config
+                .games
+                .values()
+                .map(|game| game.root_directory.join("Data"))
+                // stock game folders shipped with the modlist
+                .chain(
+                    std::fs::read_dir(&installation_path)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(Result::ok)
+                        .map(|entry| entry.path().join("Data"))
+                        .filter(|data| data.is_dir()),
+                )
+                .collect(),
+            installation_path,
+        }
+    }
+
+    fn profiles(&self) -> Result<Vec<PathBuf>> {
+        let profiles = MO2_PROFILES_FOLDER_NAME.with(|profiles| self.installation_path.join(profiles));
+        match profiles.exists() {
+            false => Ok(vec![]),
+            true => std::fs::read_dir(&profiles)
+                .with_context(|| format!("reading [{profiles:?}]"))?
+                .map(|entry| {
+                    entry
+                        .context("reading profile entry")
+                        .map(|entry| entry.path())
+                })
+                .filter_ok(|path| path.is_dir())
+                .collect::<Result<Vec<_>>>()
+                .map(|profiles| profiles.tap_mut(|profiles| profiles.sort())),
+        }
+    }
+
+    #[instrument(skip(self, game_plugins))]
+    fn fix_profile(&self, profile: &Path, game_plugins: &PluginFiles) -> Result<LoadOrderReport> {
+        let plugins_txt = profile.join(PLUGINS_TXT);
+        if !plugins_txt.exists() {
+            debug!("profile has no {PLUGINS_TXT}, skipping");
+            return Ok(Default::default());
+        }
+        let mod_plugins = enabled_mods(profile)
+            .context("reading enabled mods")?
+            .into_iter()
+            .map(|name| self.installation_path.join("mods").join(name))
+            .map(|mod_directory| plugins_in_directory(&mod_directory))
+            .fold_ok(PluginFiles::new(), |acc, next| acc.tap_mut(|acc| acc.extend(next)))?;
+        let available = game_plugins
+            .clone()
+            .tap_mut(|available| available.extend(mod_plugins.clone()));
+        let mut report = LoadOrderReport::default();
+        let mut plugins = normalize_encoding(&plugins_txt).map(|contents| PluginsTxt::parse(&contents))?;
+        plugins
+            .entries
+            .iter_mut()
+            .for_each(|entry| match available.get(&entry.name.to_lowercase()) {
+                Some(on_disk) if on_disk != &entry.name => {
+                    report.recased.push((entry.name.clone(), on_disk.clone()));
+                    entry.name = on_disk.clone();
+                }
+                Some(_) => {}
+                None => report.missing.push(entry.name.clone()),
+            });
+        let listed = plugins
+            .names()
+            .map(str::to_lowercase)
+            .collect::<BTreeSet<_>>();
+        report.unlisted = mod_plugins
+            .iter()
+            .filter(|(key, _)| !listed.contains(*key))
+            .map(|(_, name)| name.clone())
+            .collect();
+
+        patch_file(&plugins_txt, |_| Ok(plugins.to_string())).context("writing plugins.txt")?;
+
+        let loadorder_txt = profile.join(LOADORDER_TXT);
+        match loadorder_txt.exists() {
+            true => normalize_encoding(&loadorder_txt).map(|contents| parse_loadorder_txt(&contents))?,
+            false => {
+                info!("generating missing {LOADORDER_TXT}");
+                vec![]
+            }
+        }
+        .pipe(|loadorder| reorder_loadorder(&loadorder, &plugins))
+        .pipe(|loadorder| write_loadorder_txt(&loadorder))
+        .pipe(|contents| match loadorder_txt.exists() {
+            true => patch_file(&loadorder_txt, |_| Ok(contents)),
+            false => std::fs::write(&loadorder_txt, contents).context("creating file"),
+        })
+        .context("writing loadorder.txt")?;
+        Ok(report)
+    }
+
+    /// normalizes encoding and line endings of every profile's load order files and reports plugins that are missing or not listed
+    #[instrument(skip(self), fields(installation_path=?self.installation_path))]
+    pub fn fix_load_order(&self) -> Result<()> {
+        let game_plugins = self
+            .game_data_directories
+            .iter()
+            .map(|data| plugins_in_directory(data))
+            .fold_ok(PluginFiles::new(), |acc, next| acc.tap_mut(|acc| acc.extend(next)))
+            .context("listing game plugins")?;
+        self.profiles()
+            .context("listing profiles")?
+            .into_iter()
+            .try_for_each(|profile| {
+                let name = profile
+                    .file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or(Cow::Borrowed("?"))
+                    .to_string();
+                info_span!("profile", %name).in_scope(|| {
+                    self.fix_profile(&profile, &game_plugins)
+                        .with_context(|| format!("fixing load order of profile [{name}]"))
+                        .map(|LoadOrderReport { missing, unlisted, recased }| {
+                            recased
+                                .iter()
+                                .for_each(|(from, to)| info!("fixed plugin name casing [{from}] -> [{to}]"));
+                            if !missing.is_empty() {
+                                warn!("[{}] plugins are listed but could not be found:\n{}", missing.len(), missing.join("\n"));
+                            }
+                            if !unlisted.is_empty() {
+                                warn!(
+                                    "[{}] plugins from enabled mods are not listed in {PLUGINS_TXT}:\n{}",
+                                    unlisted.len(),
+                                    unlisted.join("\n")
+                                );
+                            }
+                        })
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_asterisk_format() {
+        let plugins = PluginsTxt::parse("\u{feff}# comment\r\n*Skyrim.esm\r\nDisabled.esp\n*Enabled.esp\n\n");
+        assert!(plugins.asterisk_format);
+        assert_eq!(
+            plugins.entries,
+            vec![
+                PluginEntry {
+                    name: "Skyrim.esm".into(),
+                    enabled: true
+                },
+                PluginEntry {
+                    name: "Disabled.esp".into(),
+                    enabled: false
+                },
+                PluginEntry {
+                    name: "Enabled.esp".into(),
+                    enabled: true
+                },
+            ]
+        );
+        assert_eq!(
+            plugins.to_string(),
+            "# This file was automatically generated by Mod Organizer.\r\n*Skyrim.esm\r\nDisabled.esp\r\n*Enabled.esp\r\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_format() {
+        let plugins = PluginsTxt::parse("FalloutNV.esm\nYUP.esm\n");
+        assert!(!plugins.asterisk_format);
+        assert!(plugins.entries.iter().all(|entry| entry.enabled));
+    }
+
+    #[test]
+    fn test_reorder_loadorder() {
+        let plugins = PluginsTxt::parse("*A.esp\n*C.esp\n*B.esp\n*D.esp\n");
+        let loadorder = ["Base.esm", "a.esp", "B.esp", "Disabled.esp", "C.esp"].map(String::from);
+        assert_eq!(
+            reorder_loadorder(&loadorder, &plugins),
+            ["Base.esm", "A.esp", "C.esp", "Disabled.esp", "B.esp", "D.esp"].map(String::from)
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub mod events;
 pub mod hooks;
 pub use hooks::{read::ReadHookExt, write::WriteHookExt};
 use {hooks::IoHook, indicatif::ProgressStyle, tracing_indicatif::span_ext::IndicatifSpanExt};
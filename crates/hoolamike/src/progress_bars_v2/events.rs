@@ -0,0 +1,296 @@
+//! `--progress json` replaces the progress bars with newline-delimited json events on stdout (or a unix socket),
+//! for wrappers, front-ends and ci which would otherwise have to scrape indicatif output
+use {
+    anyhow::{Context, Result},
+    clap::ValueEnum,
+    once_cell::sync::Lazy,
+    parking_lot::Mutex,
+    serde::Serialize,
+    std::{
+        io::Write,
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicBool, AtomicU64, Ordering},
+        time::{Duration, Instant},
+    },
+    tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id},
+        Event,
+        Level,
+        Subscriber,
+    },
+    tracing_subscriber::{layer::Context as LayerContext, registry::LookupSpan, Layer},
+};
+
+#[derive(Debug, ValueEnum, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProgressOutput {
+    /// progress bars on the terminal
+    #[default]
+    Bars,
+    /// newline-delimited json events, logs go to stderr
+    Json,
+}
+
+/// spans which are reported as phases, the rest are implementation details
+pub const PHASES: &[&str] = &["install_modlist", "upgrade_modlist", "sync_downloads", "validating_hashes", "handle_directives"];
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent<'a> {
+    PhaseStarted {
+        phase: &'a str,
+    },
+    PhaseFinished {
+        phase: &'a str,
+        elapsed_seconds: f64,
+    },
+    Progress {
+        phase: &'a str,
+        done: u64,
+        total: u64,
+        eta_seconds: Option<f64>,
+    },
+    DirectiveInstalled {
+        directive: &'a str,
+        output: &'a Path,
+    },
+    Error {
+        message: String,
+    },
+}
+
+type Sink = Box<dyn Write + Send>;
+
+static SINK: Lazy<Mutex<Option<Sink>>> = Lazy::new(|| Mutex::new(None));
+
+static EVENTS_ON_STDOUT: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+fn connect(socket: &Path) -> Result<Sink> {
+    std::os::unix::net::UnixStream::connect(socket)
+        .with_context(|| format!("connecting to progress socket at [{}]", socket.display()))
+        .map(|stream| Box::new(stream) as Sink)
+}
+
+/// frontends on windows read the events from stdout instead
+#[cfg(not(unix))]
+fn connect(socket: &Path) -> Result<Sink> {
+    anyhow::bail!(
+        "progress sockets are not supported on this platform, leave out [{}] to get the events on stdout",
+        socket.display()
+    )
+}
+
+/// events are only emitted once this is called, without it the progress bars are all there is
+pub fn configure_progress_events(socket: Option<&Path>) -> Result<()> {
+    let sink: Sink = match socket {
+        Some(socket) => connect(socket)?,
+        None => {
+            EVENTS_ON_STDOUT.store(true, Ordering::Relaxed);
+            Box::new(std::io::stdout())
+        }
+    };
+    *SINK.lock() = Some(sink);
+    Ok(())
+}
+
+pub fn events_enabled() -> bool {
+    SINK.lock().is_some()
+}
+
+/// human readable output (summaries, dry run and verification tables) would break the event stream, it goes to stderr when events are on stdout
+pub fn print_report(report: impl std::fmt::Display) {
+    match EVENTS_ON_STDOUT.load(Ordering::Relaxed) {
+        true => eprintln!("{report}"),
+        false => println!("{report}"),
+    }
+}
+
+fn write_event(sink: &mut impl Write, event: &ProgressEvent) -> Result<()> {
+    serde_json::to_writer(&mut *sink, event).context("serializing event")?;
+    sink.write_all(b"\n")
+        .and_then(|_| sink.flush())
+        .context("writing event")
+}
+
+/// a reader which went away should not take the installation down with it, events stop instead
+pub fn emit(event: ProgressEvent) {
+    let mut sink = SINK.lock();
+    if let Some(error) = sink
+        .as_mut()
+        .and_then(|sink| write_event(sink, &event).err())
+    {
+        *sink = None;
+        tracing::warn!(?error, "progress events disabled");
+    }
+}
+
+/// an event per directive would flood the reader, and have every worker wait for the sink
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// byte progress of a single phase, the eta assumes the rest goes as fast as what is done already
+pub struct ProgressTracker {
+    phase: &'static str,
+    total: u64,
+    done: AtomicU64,
+    started: Instant,
+    /// milliseconds since `started` of the last progress event
+    last_emitted: AtomicU64,
+}
+
+fn eta_seconds(done: u64, total: u64, elapsed_seconds: f64) -> Option<f64> {
+    match done {
+        0 => None,
+        done => Some(elapsed_seconds / done as f64 * total.saturating_sub(done) as f64),
+    }
+}
+
+impl ProgressTracker {
+    pub fn new(phase: &'static str, total: u64) -> Self {
+        Self {
+            phase,
+            total,
+            done: AtomicU64::new(0),
+            started: Instant::now(),
+            last_emitted: AtomicU64::new(0),
+        }
+    }
+
+    /// at most one event per interval, the last one is always reported
+    fn due(&self, done: u64) -> bool {
+        let now = self.started.elapsed().as_millis() as u64;
+        let last = self.last_emitted.load(Ordering::Relaxed);
+        done >= self.total
+            || (now.saturating_sub(last) >= PROGRESS_INTERVAL.as_millis() as u64
+                && self
+                    .last_emitted
+                    .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok())
+    }
+
+    pub fn advance(&self, bytes: u64) {
+        let done = self.done.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if self.due(done) && events_enabled() {
+            emit(ProgressEvent::Progress {
+                phase: self.phase,
+                done,
+                total: self.total,
+                eta_seconds: eta_seconds(done, self.total, self.started.elapsed().as_secs_f64()),
+            })
+        }
+    }
+}
+
+pub fn directive_installed(directive: &str, output: &Path) {
+    if events_enabled() {
+        emit(ProgressEvent::DirectiveInstalled { directive, output })
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: Option<String>,
+    fields: Vec<String>,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.message = Some(format!("{value:?}")),
+            name => self.fields.push(format!("{name}={value:?}")),
+        }
+    }
+}
+
+struct PhaseStarted(Instant);
+
+/// turns phase spans and errors into events
+pub struct ProgressEventsLayer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for ProgressEventsLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        let phase = attrs.metadata().name();
+        if PHASES.contains(&phase) {
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(PhaseStarted(Instant::now()));
+            }
+            emit(ProgressEvent::PhaseStarted { phase })
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: LayerContext<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            if let Some(PhaseStarted(started)) = span.extensions().get::<PhaseStarted>() {
+                emit(ProgressEvent::PhaseFinished {
+                    phase: span.name(),
+                    elapsed_seconds: started.elapsed().as_secs_f64(),
+                })
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        if *event.metadata().level() == Level::ERROR {
+            let mut visitor = MessageVisitor::default();
+            event.record(&mut visitor);
+            emit(ProgressEvent::Error {
+                message: visitor
+                    .message
+                    .into_iter()
+                    .chain(visitor.fields)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            })
+        }
+    }
+}
+
+/// `--progress-socket` without `--progress json` would be silently ignored otherwise
+pub fn validate(progress: ProgressOutput, socket: Option<&PathBuf>) -> Result<()> {
+    match (progress, socket) {
+        (ProgressOutput::Bars, Some(socket)) => anyhow::bail!("--progress-socket [{}] requires --progress json", socket.display()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_are_single_lines() -> Result<()> {
+        let mut out = Vec::new();
+        write_event(
+            &mut out,
+            &ProgressEvent::Progress {
+                phase: "handle_directives",
+                done: 50,
+                total: 100,
+                eta_seconds: eta_seconds(50, 100, 10.0),
+            },
+        )?;
+        write_event(&mut out, &ProgressEvent::Error { message: "multi\nline".into() })?;
+        let lines = String::from_utf8(out)?
+            .lines()
+            .map(serde_json::from_str::<serde_json::Value>)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "progress");
+        assert_eq!(lines[0]["eta_seconds"], 10.0);
+        assert_eq!(lines[1]["message"], "multi\nline");
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress_events_are_rate_limited() {
+        let tracker = ProgressTracker::new("handle_directives", 100);
+        assert!(!tracker.due(10));
+        assert!(tracker.due(100));
+        let tracker = ProgressTracker {
+            started: Instant::now() - PROGRESS_INTERVAL,
+            ..ProgressTracker::new("handle_directives", 100)
+        };
+        assert!(tracker.due(10));
+        assert!(!tracker.due(20));
+    }
+}
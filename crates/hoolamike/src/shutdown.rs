@@ -0,0 +1,71 @@
+//! the first ctrl-c (or SIGTERM) only stops new directives and downloads from being started, the running ones finish
+//! and the journals are written down, so that `hoolamike install` picks up where it stopped. the second one kills 7z and exits right away
+use {
+    futures::{Stream, StreamExt},
+    std::{
+        future::ready,
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    },
+};
+
+pub const STOPPED_MESSAGE: &str = "installation stopped, resume with `hoolamike install`";
+
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub fn request_stop() -> bool {
+    STOP_REQUESTED.swap(true, Ordering::SeqCst)
+}
+
+pub fn stop_requested() -> bool {
+    STOP_REQUESTED.load(Ordering::SeqCst)
+}
+
+pub fn check_stopped() -> anyhow::Result<()> {
+    match stop_requested() {
+        true => Err(anyhow::anyhow!(STOPPED_MESSAGE)),
+        false => Ok(()),
+    }
+}
+
+/// ends the stream once a stop is requested, whatever was already taken out of it keeps running
+pub fn until_stopped<S: Stream>(stream: S) -> impl Stream<Item = S::Item> {
+    stream.take_while(|_| ready(!stop_requested()))
+}
+
+async fn next_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            interrupted = tokio::signal::ctrl_c() => interrupted,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await
+    }
+}
+
+/// 7z runs in separate processes, they would keep running (and leave their temp files behind) once hoolamike is gone
+///
+/// only `install` and `upgrade` check for a stop, other commands keep the default ctrl-c behavior and must not spawn this
+pub async fn handle_signals() {
+    loop {
+        if let Err(error) = next_signal().await {
+            tracing::warn!(?error, "could not listen for signals");
+            return;
+        }
+        match request_stop() {
+            false => tracing::warn!("stopping once the running directives and downloads are done, interrupt again to stop right away"),
+            true => {
+                tracing::warn!("interrupted, stopping running 7z processes");
+                tokio::task::spawn_blocking(|| crate::compression::cancel_running_extractions(Duration::from_secs(10)))
+                    .await
+                    .ok();
+                std::process::exit(130);
+            }
+        }
+    }
+}
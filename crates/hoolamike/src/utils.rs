@@ -64,29 +64,7 @@ impl<T, E> std::result::Result<T, E> {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Hash, derive_more::Display, Clone, Ord)]
-pub struct MaybeWindowsPath(pub String);
-
-impl std::fmt::Debug for MaybeWindowsPath {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
-    }
-}
-
-impl MaybeWindowsPath {
-    pub fn into_path(self) -> PathBuf {
-        let s = self.0;
-        let s = match s.contains("\\\\") {
-            true => s.split("\\\\").join("/"),
-            false => s,
-        };
-        let s = match s.contains("\\") {
-            true => s.split("\\").join("/"),
-            false => s,
-        };
-        PathBuf::from(s)
-    }
-}
+pub use hoola_paths::MaybeWindowsPath;
 
 pub fn boxed_iter<'a, T: 'a>(iter: impl Iterator<Item = T> + 'a) -> Box<dyn Iterator<Item = T> + 'a> {
     Box::new(iter)
@@ -117,6 +95,12 @@ impl<T: AsRef<std::path::Path>> T {
                 if let Some(parent) = self.as_ref().parent() {
                     std::fs::create_dir_all(parent).context("creating full path for output file")?;
                 }
+                // a hard linked file (deduplicated outputs, `copy_strategy: hardlink`) shares its contents,
+                // truncating it would overwrite every other link as well
+                #[cfg(unix)]
+                if std::fs::metadata(self).is_ok_and(|metadata| std::os::unix::fs::MetadataExt::nlink(&metadata) > 1) {
+                    std::fs::remove_file(self).context("unlinking shared file before writing")?;
+                }
                 std::fs::OpenOptions::new()
                     .write(true)
                     .create(true)
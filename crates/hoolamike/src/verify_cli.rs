@@ -0,0 +1,223 @@
+//! `hoolamike verify` checks an existing installation against the modlist without running any directives,
+//! useful after the installation was edited by hand
+use {
+    crate::{
+        config_file::{HoolamikeConfig, InstallationConfig},
+        install_modlist::{
+            directives::{expected_output, is_whitelisted_by_path},
+            download_cache::{calculate_hash, to_base_64_from_u64},
+        },
+        modlist_json::{Directive, DirectiveKind},
+        progress_bars_v2::{count_progress_style, events::print_report},
+        wabbajack_file::WabbajackFile,
+    },
+    anyhow::{Context, Result},
+    futures::StreamExt,
+    serde::Serialize,
+    std::path::{Path, PathBuf},
+    tabled::{settings::Style, Tabled},
+    tap::prelude::*,
+    tracing::{info, info_span, Instrument},
+    tracing_indicatif::span_ext::IndicatifSpanExt,
+};
+
+#[derive(clap::Args)]
+pub struct VerifyCli {
+    /// only compare file sizes, much faster but does not notice files which were edited in place
+    #[arg(long)]
+    pub quick: bool,
+    /// also write the report as json to this file
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, derive_more::Display)]
+#[serde(tag = "problem", rename_all = "snake_case")]
+pub enum FileProblem {
+    #[display("missing")]
+    Missing,
+    #[display("size is [{found}], expected [{expected}]")]
+    WrongSize { expected: u64, found: u64 },
+    #[display("hash is [{found}], expected [{expected}]")]
+    Modified { expected: String, found: String },
+    #[display("could not be read: {reason}")]
+    Unreadable { reason: String },
+}
+
+/// `None` when the file matches, dds files are recompressed differently than in wabbajack so only their size is compared
+pub async fn check_output(path: &Path, expected_hash: &str, expected_size: u64, quick: bool) -> Option<FileProblem> {
+    let found = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata.len(),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Some(FileProblem::Missing),
+        Err(error) => return Some(FileProblem::Unreadable { reason: error.to_string() }),
+    };
+    if found != expected_size {
+        return Some(FileProblem::WrongSize {
+            expected: expected_size,
+            found,
+        });
+    }
+    if quick || is_whitelisted_by_path(path) {
+        return None;
+    }
+    match calculate_hash(path.to_owned())
+        .await
+        .map(to_base_64_from_u64)
+    {
+        Ok(found) if found == expected_hash => None,
+        Ok(found) => Some(FileProblem::Modified {
+            expected: expected_hash.to_string(),
+            found,
+        }),
+        Err(reason) => Some(FileProblem::Unreadable { reason: format!("{reason:#}") }),
+    }
+}
+
+#[derive(Debug, Serialize, Tabled)]
+pub struct ProblemRow {
+    pub path: String,
+    pub kind: DirectiveKind,
+    #[serde(flatten)]
+    pub problem: FileProblem,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerificationReport {
+    pub checked: usize,
+    pub problems: Vec<ProblemRow>,
+}
+
+/// the expected output of every directive, as `(kind, hash, size, path relative to the installation directory)`
+pub fn expected_outputs(directives: &[Directive]) -> Vec<(DirectiveKind, String, u64, PathBuf)> {
+    directives
+        .iter()
+        .map(|directive| {
+            let (hash, size, to) = expected_output(directive);
+            (directive.directive_kind(), hash, size, to.into_path())
+        })
+        .collect()
+}
+
+pub async fn verify_outputs(installation_path: &Path, outputs: Vec<(DirectiveKind, String, u64, PathBuf)>, quick: bool) -> VerificationReport {
+    let checked = outputs.len();
+    let verifying = info_span!("verifying_installation").tap(|pb| {
+        pb.pb_set_style(&count_progress_style());
+        pb.pb_set_length(checked as _);
+    });
+    let problems = outputs
+        .pipe(futures::stream::iter)
+        .map(|(kind, hash, size, to)| {
+            let path = installation_path.join(&to);
+            async move {
+                check_output(&path, &hash, size, quick)
+                    .await
+                    .map(|problem| ProblemRow {
+                        path: to.display().to_string(),
+                        kind,
+                        problem,
+                    })
+            }
+        })
+        .buffer_unordered(num_cpus::get())
+        .inspect(|_| verifying.pb_inc(1))
+        .filter_map(std::future::ready)
+        .collect::<Vec<_>>()
+        .instrument(verifying.clone())
+        .await
+        .tap_mut(|problems| problems.sort_by(|a, b| a.path.cmp(&b.path)));
+    VerificationReport { checked, problems }
+}
+
+/// with `installation.stream_bsa_files_gigabytes` the files packed into a bsa may never exist on disk, their archive does
+pub fn skip_streamed_bsa_files(
+    outputs: Vec<(DirectiveKind, String, u64, PathBuf)>,
+    stream_bsa_files_gigabytes: Option<u64>,
+) -> Vec<(DirectiveKind, String, u64, PathBuf)> {
+    match stream_bsa_files_gigabytes {
+        Some(_) => outputs
+            .into_iter()
+            .filter(|(_, _, _, path)| crate::install_modlist::output_filter::staged_for_bsa(path).is_none())
+            .collect(),
+        None => outputs,
+    }
+}
+
+impl VerifyCli {
+    pub async fn run(
+        self,
+        HoolamikeConfig {
+            installation:
+                InstallationConfig {
+                    wabbajack_file_path,
+                    installation_path,
+                    stream_bsa_files_gigabytes,
+                    ..
+                },
+            ..
+        }: HoolamikeConfig,
+    ) -> Result<()> {
+        let Self { quick, report } = self;
+        let (_handle, WabbajackFile { modlist, .. }) = WabbajackFile::load_wabbajack_file(wabbajack_file_path).context("loading modlist file")?;
+        let report_data = verify_outputs(
+            &installation_path,
+            skip_streamed_bsa_files(expected_outputs(&modlist.directives), stream_bsa_files_gigabytes),
+            quick,
+        )
+        .await;
+        let checked = report_data.checked;
+
+        if let Some(report) = report {
+            serde_json::to_string_pretty(&report_data)
+                .context("serializing report")
+                .and_then(|contents| std::fs::write(&report, contents).with_context(|| format!("writing report to [{}]", report.display())))?;
+        }
+        match report_data.problems.is_empty() {
+            true => {
+                info!(%checked, "installation at [{}] matches the modlist", installation_path.display());
+                Ok(())
+            }
+            false => {
+                report_data
+                    .problems
+                    .iter()
+                    .pipe(tabled::Table::new)
+                    .with(Style::modern())
+                    .pipe(print_report);
+                anyhow::bail!(
+                    "[{}/{checked}] files are missing or modified, run `hoolamike install` to restore them",
+                    report_data.problems.len()
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_output() -> Result<()> {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("plugin.esp");
+        std::fs::write(&path, b"plugin")?;
+        let hash = calculate_hash(path.clone())
+            .await
+            .map(to_base_64_from_u64)?;
+
+        assert_eq!(check_output(&path, &hash, 6, false).await, None);
+        assert_eq!(
+            check_output(&directory.path().join("missing.esp"), &hash, 6, false).await,
+            Some(FileProblem::Missing)
+        );
+        assert_eq!(
+            check_output(&path, &hash, 7, false).await,
+            Some(FileProblem::WrongSize { expected: 7, found: 6 })
+        );
+
+        std::fs::write(&path, b"edited")?;
+        assert!(matches!(check_output(&path, &hash, 6, false).await, Some(FileProblem::Modified { .. })));
+        assert_eq!(check_output(&path, &hash, 6, true).await, None);
+        Ok(())
+    }
+}
@@ -17,7 +17,28 @@ pub struct WabbajackFile {
 
 const MODLIST_JSON_FILENAME: &str = "modlist";
 
+fn read_modlist_json(mut handle: impl Read) -> Result<String> {
+    String::new()
+        .pipe(|mut out| handle.read_to_string(&mut out).map(|_| out))
+        .context("reading modlist json to string")
+}
+
 impl WabbajackFile {
+    /// the `modlist` json exactly as it is stored in the .wabbajack file
+    pub fn load_modlist_json(at_path: &Path) -> Result<String> {
+        at_path
+            .open_file_read()
+            .and_then(|(_, file)| crate::compression::compress_tools::ArchiveHandle::new(file))
+            .context("reading archive")
+            .and_then(|mut archive| {
+                archive
+                    .get_handle(Path::new(MODLIST_JSON_FILENAME))
+                    .context("looking up file by name")
+            })
+            .and_then(read_modlist_json)
+            .with_context(|| format!("reading [{MODLIST_JSON_FILENAME}] from [{}]", at_path.display()))
+    }
+
     #[tracing::instrument]
     pub fn load_wabbajack_file(at_path: PathBuf) -> Result<(WabbajackFileHandle, Self)> {
         at_path
@@ -29,18 +50,16 @@ impl WabbajackFile {
                     archive
                         .get_handle(Path::new(MODLIST_JSON_FILENAME))
                         .context("looking up file by name")
-                        .and_then(|mut handle| {
-                            String::new()
-                                .pipe(|mut out| handle.read_to_string(&mut out).map(|_| out))
-                                .context("reading modlist json to string")
-                        })
+                        .and_then(read_modlist_json)
                         .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).context("reading archive json contents"))
+                        .map(crate::modlist_json::format_version::normalize)
                         .and_then(|json| {
                             serde_json::to_string_pretty(&json)
                                 .context("serializing json")
                                 .and_then(|output| serde_json::from_str(&output).context("output is a valid json but not a valid modlist file"))
                         })
                         .with_context(|| format!("reading [{MODLIST_JSON_FILENAME}]"))
+                        .tap_ok(crate::modlist_json::unknown::warn_about_unknown_entries)
                         .map(|modlist| Self {
                             wabbajack_file_path: at_path.clone(),
                             wabbajack_entries: entries,
@@ -0,0 +1,68 @@
+use super::*;
+
+pub struct QueueBuilder<K, V> {
+    expiration: Expiration,
+    budget: Option<weight::WeightBudget<V>>,
+    persistence: Option<Arc<dyn Persistence<K, V>>>,
+}
+
+impl<K, V> CachedFutureQueue<K, V>
+where
+    K: std::hash::Hash + Eq + std::fmt::Debug + Clone + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    pub fn builder() -> QueueBuilder<K, V> {
+        QueueBuilder {
+            expiration: Default::default(),
+            budget: None,
+            persistence: None,
+        }
+    }
+}
+
+impl<K, V> QueueBuilder<K, V>
+where
+    K: std::hash::Hash + Eq + std::fmt::Debug + Clone + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    pub fn expiration(self, expiration: Expiration) -> Self {
+        Self { expiration, ..self }
+    }
+
+    /// see [weight]
+    pub fn weight_budget(self, max_weight: u64, weigher: impl Fn(&V) -> u64 + Send + Sync + 'static) -> Self {
+        Self {
+            budget: Some(weight::WeightBudget::new(max_weight, weigher)),
+            ..self
+        }
+    }
+
+    /// see [persistence], values loaded with [CachedFutureQueue::rehydrate] are not stored again
+    pub fn persistence(self, persistence: impl Persistence<K, V> + 'static) -> Self {
+        Self {
+            persistence: Some(Arc::new(persistence)),
+            ..self
+        }
+    }
+
+    /// with an [Expiration] this spawns a sweeper task, so it has to be called from within a tokio runtime
+    pub fn build(self) -> Arc<CachedFutureQueue<K, V>> {
+        let Self {
+            expiration,
+            budget,
+            persistence,
+        } = self;
+        Arc::new(CachedFutureQueue {
+            tasks: Default::default(),
+            expiration,
+            budget,
+            persistence,
+            counters: Default::default(),
+        })
+        .tap(|queue| {
+            if !expiration.never_expires() {
+                CachedFutureQueue::spawn_sweeper(Arc::downgrade(queue), expiration.sweep_interval());
+            }
+        })
+    }
+}
@@ -0,0 +1,193 @@
+//! entries are kept forever by default, a long install computes far more of them than it ever needs at once -
+//! with an [Expiration] finished entries get dropped some time after they were created or last used
+
+use {
+    super::*,
+    std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Weak,
+        },
+        time::Duration,
+    },
+};
+
+/// sweeping more often than this is a waste of time
+const MIN_SWEEP_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Expiration {
+    /// counted from the moment the entry was created
+    pub ttl: Option<Duration>,
+    /// counted from the last [CachedFutureQueue::get] of the entry
+    pub max_idle: Option<Duration>,
+}
+
+impl Expiration {
+    pub fn never_expires(&self) -> bool {
+        self.ttl.is_none() && self.max_idle.is_none()
+    }
+
+    /// an entry outlives its deadline by at most this much
+    pub fn sweep_interval(&self) -> Duration {
+        self.ttl
+            .into_iter()
+            .chain(self.max_idle)
+            .min()
+            .map(|shortest| (shortest / 2).max(MIN_SWEEP_INTERVAL))
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// entries still being computed never expire, dropping them would only start the same work again
+    pub(crate) fn is_expired<V>(&self, entry: &Entry<V>, now: Instant) -> bool {
+        entry.ready().is_some()
+            && (self
+                .ttl
+                .is_some_and(|ttl| now.saturating_duration_since(entry.created) >= ttl)
+                || self
+                    .max_idle
+                    .is_some_and(|max_idle| now.saturating_duration_since(entry.last_access()) >= max_idle))
+    }
+}
+
+pub(crate) struct Entry<V> {
+    pub(crate) future: Shared<ClonableJoinHandle<Arc<V>>>,
+    created: Instant,
+    /// nanoseconds since `created`
+    last_access: AtomicU64,
+}
+
+impl<V> Entry<V> {
+    pub(crate) fn new(future: Shared<ClonableJoinHandle<Arc<V>>>) -> Self {
+        Self {
+            future,
+            created: Instant::now(),
+            last_access: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn last_access(&self) -> Instant {
+        self.created + Duration::from_nanos(self.last_access.load(Ordering::Relaxed))
+    }
+
+    /// the output of a finished computation, even if nobody awaited it yet (like a preheated one)
+    pub(crate) fn ready(&self) -> Option<std::result::Result<Arc<V>, ArcJoinError>> {
+        self.future
+            .peek()
+            .cloned()
+            .or_else(|| self.future.clone().now_or_never())
+    }
+
+    pub(crate) fn touch(&self) -> &Self {
+        self.last_access.store(
+            self.created
+                .elapsed()
+                .as_nanos()
+                .try_into()
+                .unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+        self
+    }
+}
+
+impl<K, V> CachedFutureQueue<K, V>
+where
+    K: std::hash::Hash + Eq + std::fmt::Debug + Clone + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    /// spawns a sweeper task which lives as long as the queue does, so it has to be called from within a tokio runtime
+    pub fn with_expiration(expiration: Expiration) -> Arc<Self> {
+        Self::builder().expiration(expiration).build()
+    }
+
+    pub(crate) fn spawn_sweeper(queue: Weak<Self>, interval: Duration) {
+        tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                match queue.upgrade() {
+                    Some(queue) => {
+                        queue.sweep();
+                    }
+                    None => break,
+                }
+            }
+        });
+    }
+
+    /// drops expired entries right away, returns how many were dropped
+    #[instrument(skip(self), level = "TRACE")]
+    pub fn sweep(&self) -> usize {
+        let now = Instant::now();
+        // counted inside of retain, entries inserted meanwhile would throw off a len() difference
+        let mut swept = 0;
+        self.tasks
+            .retain(|_, entry| match self.expiration.is_expired(entry, now) {
+                true => {
+                    swept += 1;
+                    false
+                }
+                false => true,
+            });
+        swept.tap(|swept| self.counters.evicted(*swept))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tokio::time::sleep};
+
+    #[test_log::test(tokio::test)]
+    async fn test_ttl_expires_finished_entries() -> anyhow::Result<()> {
+        let queue = CachedFutureQueue::with_expiration(Expiration {
+            ttl: Some(Duration::from_millis(100)),
+            max_idle: None,
+        });
+        let first = queue.clone().get(1, |num| async move { num * 2 }).await?;
+        let cached = queue.clone().get(1, |num| async move { num * 3 }).await?;
+        assert!(Arc::ptr_eq(&first, &cached));
+
+        sleep(Duration::from_millis(250)).await;
+        assert!(queue.is_empty());
+        assert_eq!(queue.stats().evictions, 1);
+        let recomputed = queue.clone().get(1, |num| async move { num * 3 }).await?;
+        assert_eq!(*recomputed, 3);
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_max_idle_is_extended_by_access() -> anyhow::Result<()> {
+        let queue = CachedFutureQueue::with_expiration(Expiration {
+            ttl: None,
+            max_idle: Some(Duration::from_millis(200)),
+        });
+        queue.clone().get(1, |num| async move { num }).await?;
+        for _ in 0..4 {
+            sleep(Duration::from_millis(80)).await;
+            queue.clone().get(1, |num| async move { num }).await?;
+        }
+        assert_eq!(queue.len(), 1);
+        sleep(Duration::from_millis(400)).await;
+        assert!(queue.is_empty());
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_in_flight_entries_do_not_expire() -> anyhow::Result<()> {
+        let queue = CachedFutureQueue::with_expiration(Expiration {
+            ttl: Some(Duration::from_millis(10)),
+            max_idle: None,
+        });
+        let slow = tokio::task::spawn(queue.clone().get(1, |num| async move {
+            sleep(Duration::from_millis(100)).await;
+            num
+        }));
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(queue.sweep(), 0);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(*slow.await??, 1);
+        Ok(())
+    }
+}
@@ -1,17 +1,32 @@
 use {
     dashmap::DashMap,
+    expiration::Entry,
     futures::{future::Shared, FutureExt},
+    stats::Counters,
     std::{
         future::{ready, Future},
         sync::Arc,
+        time::Instant,
     },
     tap::prelude::*,
     tokio::task::JoinHandle,
     tracing::{instrument, trace_span, Instrument},
 };
 
+pub mod builder;
+pub mod expiration;
+pub mod persistence;
+pub mod stats;
+pub mod weight;
+
+pub use {builder::QueueBuilder, expiration::Expiration, persistence::Persistence, stats::QueueStats};
+
 pub struct CachedFutureQueue<K, V> {
-    tasks: DashMap<K, Shared<ClonableJoinHandle<Arc<V>>>>,
+    tasks: DashMap<K, Entry<V>>,
+    expiration: Expiration,
+    budget: Option<weight::WeightBudget<V>>,
+    persistence: Option<Arc<dyn Persistence<K, V>>>,
+    counters: Arc<Counters>,
 }
 
 #[derive(Debug, Clone)]
@@ -39,7 +54,10 @@ impl std::error::Error for ArcJoinError {
     }
 }
 
-struct ClonableJoinHandle<T>(JoinHandle<T>);
+pub struct ClonableJoinHandle<T>(JoinHandle<T>);
+
+/// the computation of a single value, every clone resolves to the same [Arc]
+pub type SharedTask<V> = Shared<ClonableJoinHandle<Arc<V>>>;
 
 impl From<tokio::task::JoinError> for ArcJoinError {
     fn from(value: tokio::task::JoinError) -> Self {
@@ -61,15 +79,65 @@ where
     V: Send + Sync + 'static,
 {
     pub fn new() -> Arc<Self> {
-        Arc::new(Self { tasks: Default::default() })
+        Self::builder().build()
+    }
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+    /// the next [Self::get] computes the value again, callers already waiting for it still get the old one
+    pub fn invalidate(&self, key: &K) -> bool {
+        self.tasks.remove(key).is_some()
+    }
+    pub fn clear(&self) {
+        self.tasks.clear()
+    }
+    /// snapshot of the successfully computed values, entries still in flight are skipped
+    pub fn iter_ready(&self) -> impl Iterator<Item = (K, Arc<V>)> {
+        self.tasks
+            .iter()
+            .filter_map(|entry| match entry.value().ready() {
+                Some(Ok(value)) => Some((entry.key().clone(), value.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+    /// the value or its computation if it's already there, never starts one
+    pub fn try_get(&self, key: &K) -> Option<SharedTask<V>> {
+        self.tasks
+            .get(key)
+            .filter(|entry| !self.expiration.is_expired(entry, Instant::now()))
+            .map(|entry| entry.touch().future.clone())
+    }
+    /// waits for every computation started so far (ones started in the meantime are not waited for), eg. before shutting down
+    #[instrument(skip(self), level = "TRACE")]
+    pub async fn wait_all(&self) -> Vec<(K, std::result::Result<Arc<V>, ArcJoinError>)> {
+        self.tasks
+            .iter()
+            .map(|entry| {
+                let key = entry.key().clone();
+                entry
+                    .value()
+                    .future
+                    .clone()
+                    .map(move |result| (key, result))
+            })
+            .collect::<Vec<_>>()
+            .pipe(futures::future::join_all)
+            .await
     }
     pub fn preheat(&self, key: K, value: V) {
         self.tasks.insert(
             key,
             tokio::task::spawn(ready(value.pipe(Arc::new)))
                 .pipe(ClonableJoinHandle)
-                .shared(),
+                .shared()
+                .pipe(Entry::new),
         );
+        self.enforce_budget();
     }
     #[instrument(skip(self, with), level = "TRACE")]
     pub async fn get<F, Fut>(self: Arc<Self>, key: K, with: F) -> std::result::Result<Arc<V>, ArcJoinError>
@@ -77,21 +145,81 @@ where
         Fut: Future<Output = V> + Send + 'static,
         F: FnOnce(K) -> Fut + Send + 'static,
     {
+        // the sweeper might not have gotten to it yet
+        if self
+            .tasks
+            .remove_if(&key, |_, entry| self.expiration.is_expired(entry, Instant::now()))
+            .is_some()
+        {
+            self.counters.evicted(1);
+        }
+        let mut missed = false;
         let future = self
             .tasks
             .entry(key.clone())
-            .or_insert_with(move || {
+            .or_insert_with(|| {
+                missed = true;
+                let in_flight = self.counters.miss(&key);
+                let persistence = self.persistence.clone();
+                let persisted_key = key.clone();
                 tokio::task::spawn(
                     (with)(key.clone())
                         .instrument(trace_span!("doing_work"))
-                        .map(Arc::new),
+                        .map(move |value| {
+                            Self::persist(persistence.as_ref(), &persisted_key, &value);
+                            drop(in_flight);
+                            Arc::new(value)
+                        }),
                 )
                 .pipe(ClonableJoinHandle)
                 .shared()
+                .pipe(Entry::new)
             })
+            .touch()
+            .future
             .clone();
+        if !missed {
+            self.counters.hit(&key);
+        }
 
-        future.await
+        future.await.tap(|_| {
+            if missed {
+                self.enforce_budget();
+            }
+        })
+    }
+}
+
+impl<K, T, E> CachedFutureQueue<K, std::result::Result<T, E>>
+where
+    K: std::hash::Hash + Eq + std::fmt::Debug + Clone + Send + Sync + 'static,
+    T: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    /// like [Self::get], but only successes stay cached - callers waiting on a failed (or panicked) computation
+    /// all get its error, the next caller starts over
+    #[instrument(skip(self, with), level = "TRACE")]
+    pub async fn get_try<F, Fut>(self: Arc<Self>, key: K, with: F) -> std::result::Result<Arc<std::result::Result<T, E>>, ArcJoinError>
+    where
+        Fut: Future<Output = std::result::Result<T, E>> + Send + 'static,
+        F: FnOnce(K) -> Fut + Send + 'static,
+    {
+        let result = self.clone().get(key.clone(), with).await;
+        let failed = match &result {
+            Ok(value) => value.is_err(),
+            Err(_) => true,
+        };
+        if failed {
+            // only this very attempt, a retry might have replaced it already
+            self.tasks
+                .remove_if(&key, |_, entry| match (entry.future.peek(), &result) {
+                    (Some(Ok(cached)), Ok(value)) => Arc::ptr_eq(cached, value),
+                    (Some(Err(_)), Err(_)) => true,
+                    _ => false,
+                });
+            tracing::trace!(?key, "computation failed, not caching it");
+        }
+        result
     }
 }
 
@@ -138,4 +266,77 @@ mod tests {
         assert!(Arc::ptr_eq(a, b));
         Ok(())
     }
+
+    #[test_log::test(tokio::test)]
+    async fn test_invalidate_and_iter_ready() -> anyhow::Result<()> {
+        let queue = CachedFutureQueue::new();
+        queue.preheat(1, "one");
+        let two = queue.clone().get(2, |_| async move { "two" }).await?;
+        let _in_flight = tokio::task::spawn(queue.clone().get(3, |_| async move {
+            sleep(Duration::from_millis(500)).await;
+            "three"
+        }));
+        // preheated values are ready once their task had a chance to run
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            queue
+                .iter_ready()
+                .map(|(key, value)| (key, *value))
+                .collect::<std::collections::BTreeMap<_, _>>(),
+            [(1, "one"), (2, "two")].into_iter().collect()
+        );
+
+        assert!(queue.invalidate(&2));
+        assert!(!queue.invalidate(&2));
+        let recomputed = queue.clone().get(2, |_| async move { "two again" }).await?;
+        assert!(!Arc::ptr_eq(&two, &recomputed));
+
+        queue.clear();
+        assert!(queue.is_empty());
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_try_get_and_wait_all() -> anyhow::Result<()> {
+        let queue = CachedFutureQueue::new();
+        assert!(queue.try_get(&1).is_none());
+        let _in_flight = tokio::task::spawn(queue.clone().get(1, |num| async move {
+            sleep(Duration::from_millis(100)).await;
+            num * 2
+        }));
+        sleep(Duration::from_millis(20)).await;
+        let shared = queue.try_get(&1).expect("in flight");
+        assert!(queue.try_get(&2).is_none());
+        assert_eq!(queue.stats().misses, 1);
+
+        queue.clone().get(2, |num| async move { num * 2 }).await?;
+        let finished = queue.wait_all().await;
+        assert_eq!(finished.len(), 2);
+        assert!(finished.iter().all(|(_, result)| result.is_ok()));
+        assert_eq!(*shared.await?, 2);
+        Ok(())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_failures_are_not_cached() -> anyhow::Result<()> {
+        let queue = CachedFutureQueue::<u32, Result<u32, String>>::new();
+        let failed = queue
+            .clone()
+            .get_try(1, |_| async move { Err("flaky mirror".to_string()) })
+            .await?;
+        assert_eq!(*failed, Err("flaky mirror".to_string()));
+        assert!(queue.is_empty());
+
+        let retried = queue
+            .clone()
+            .get_try(1, |num| async move { Ok(num * 2) })
+            .await?;
+        assert_eq!(*retried, Ok(2));
+        let cached = queue
+            .clone()
+            .get_try(1, |_| async move { Err("not called".to_string()) })
+            .await?;
+        assert!(Arc::ptr_eq(&retried, &cached));
+        Ok(())
+    }
 }
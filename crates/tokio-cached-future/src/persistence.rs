@@ -0,0 +1,166 @@
+//! values can be written somewhere as soon as they are computed and read back on the next start with [CachedFutureQueue::rehydrate],
+//! so a crashed install does not have to redo the expensive work (like extracting huge archives) it already finished
+
+use super::*;
+
+/// failures are logged and otherwise ignored, the cache works the same without persistence - just slower on the next run
+pub trait Persistence<K, V>: Send + Sync {
+    /// called from the task which computed the value, once per computation
+    fn store(&self, key: &K, value: &V) -> std::io::Result<()>;
+    /// every stored value, when a key was stored more than once the last one wins
+    fn load(&self) -> std::io::Result<Vec<(K, V)>>;
+}
+
+impl<K, V> CachedFutureQueue<K, V>
+where
+    K: std::hash::Hash + Eq + std::fmt::Debug + Clone + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    pub(crate) fn persist(persistence: Option<&Arc<dyn Persistence<K, V>>>, key: &K, value: &V) {
+        if let Some(persistence) = persistence {
+            if let Err(error) = persistence.store(key, value) {
+                tracing::warn!(?error, ?key, "could not persist computed value");
+            }
+        }
+    }
+
+    /// preheats the queue with everything persisted before, returns the number of values loaded
+    #[instrument(skip(self), level = "DEBUG")]
+    pub fn rehydrate(&self) -> std::io::Result<usize> {
+        match self.persistence.as_ref() {
+            Some(persistence) => persistence.load().map(|values| {
+                values
+                    .into_iter()
+                    .map(|(key, value)| self.preheat(key, value))
+                    .count()
+            }),
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+pub use json::JsonLinesFile;
+
+#[cfg(feature = "json")]
+mod json {
+    use {
+        super::*,
+        serde::{de::DeserializeOwned, Deserialize, Serialize},
+        std::{
+            io::{BufRead, Write},
+            marker::PhantomData,
+            path::{Path, PathBuf},
+            sync::Mutex,
+        },
+    };
+
+    #[derive(Serialize, Deserialize)]
+    struct Line<K, V> {
+        key: K,
+        value: V,
+    }
+
+    /// one json object per line, appended on every store - a line cut short by a crash is skipped when loading
+    pub struct JsonLinesFile<K, V> {
+        path: PathBuf,
+        /// lines of concurrent stores must not interleave
+        writer: Mutex<()>,
+        _entry: PhantomData<fn() -> (K, V)>,
+    }
+
+    impl<K, V> JsonLinesFile<K, V> {
+        pub fn new(path: &Path) -> Self {
+            Self {
+                path: path.to_owned(),
+                writer: Mutex::new(()),
+                _entry: PhantomData,
+            }
+        }
+    }
+
+    impl<K, V> Persistence<K, V> for JsonLinesFile<K, V>
+    where
+        K: Serialize + DeserializeOwned + std::hash::Hash + Eq,
+        V: Serialize + DeserializeOwned,
+    {
+        fn store(&self, key: &K, value: &V) -> std::io::Result<()> {
+            let line = serde_json::to_string(&Line { key, value })?;
+            let _writer = self
+                .writer
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .and_then(|mut file| writeln!(file, "{line}"))
+        }
+
+        fn load(&self) -> std::io::Result<Vec<(K, V)>> {
+            match std::fs::File::open(&self.path) {
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+                Err(error) => Err(error),
+                Ok(file) => std::io::BufReader::new(file)
+                    .lines()
+                    .try_fold(std::collections::HashMap::new(), |mut values, line| {
+                        line.map(|line| match serde_json::from_str::<Line<K, V>>(&line) {
+                            Ok(Line { key, value }) => {
+                                values.insert(key, value);
+                                values
+                            }
+                            Err(error) => {
+                                tracing::warn!(?error, path=%self.path.display(), "skipping unreadable line");
+                                values
+                            }
+                        })
+                    })
+                    .map(|values| values.into_iter().collect()),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test_log::test(tokio::test)]
+        async fn test_values_survive_a_restart() -> anyhow::Result<()> {
+            let dir = tempfile::tempdir()?;
+            let path = dir.path().join("queue.jsonl");
+            let build = || {
+                CachedFutureQueue::<String, u64>::builder()
+                    .persistence(JsonLinesFile::new(&path))
+                    .build()
+            };
+
+            let queue = build();
+            assert_eq!(queue.rehydrate()?, 0);
+            queue
+                .clone()
+                .get("a.7z".into(), |_| async move { 1 })
+                .await?;
+            queue
+                .clone()
+                .get("b.7z".into(), |_| async move { 2 })
+                .await?;
+            // crashed in the middle of writing a line
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| write!(file, "{{\"key\":\"c.7z\",\"val"))?;
+
+            let restarted = build();
+            assert_eq!(restarted.rehydrate()?, 2);
+            assert_eq!(
+                *restarted
+                    .clone()
+                    .get("a.7z".into(), |_| async move { 100 })
+                    .await?,
+                1
+            );
+            assert_eq!(restarted.stats().misses, 0);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! counters kept by every queue, cheap enough to never turn off
+
+use {
+    super::*,
+    std::sync::atomic::{AtomicU64, Ordering},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// [CachedFutureQueue::get] calls which found the value (or its computation) already there
+    pub hits: u64,
+    /// [CachedFutureQueue::get] calls which had to start the computation
+    pub misses: u64,
+    /// entries dropped by the queue itself, explicit invalidation does not count
+    pub evictions: u64,
+    /// computations running right now
+    pub in_flight: u64,
+    pub entries: usize,
+}
+
+impl QueueStats {
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    in_flight: AtomicU64,
+}
+
+/// lives inside of the spawned computation, so it is dropped when the computation finishes, panics or gets aborted
+pub(crate) struct InFlight(Arc<Counters>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Counters {
+    pub(crate) fn hit<K: std::fmt::Debug>(&self, key: &K) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        tracing::trace!(?key, "cache hit");
+    }
+
+    pub(crate) fn miss<K: std::fmt::Debug>(self: &Arc<Self>, key: &K) -> InFlight {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        tracing::trace!(?key, "cache miss");
+        InFlight(self.clone())
+    }
+
+    pub(crate) fn evicted(&self, count: usize) {
+        if count > 0 {
+            self.evictions.fetch_add(count as u64, Ordering::Relaxed);
+            tracing::trace!(%count, "evicted entries");
+        }
+    }
+}
+
+impl<K, V> CachedFutureQueue<K, V>
+where
+    K: std::hash::Hash + Eq + std::fmt::Debug + Clone + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    pub fn stats(&self) -> QueueStats {
+        QueueStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+            in_flight: self.counters.in_flight.load(Ordering::Relaxed),
+            entries: self.tasks.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tokio::time::sleep};
+
+    #[test_log::test(tokio::test)]
+    async fn test_stats() -> anyhow::Result<()> {
+        let queue = CachedFutureQueue::new();
+        let slow = tokio::task::spawn(queue.clone().get(1, |num| async move {
+            sleep(std::time::Duration::from_millis(100)).await;
+            num
+        }));
+        sleep(std::time::Duration::from_millis(20)).await;
+        assert_eq!(queue.stats().in_flight, 1);
+        queue.clone().get(1, |num| async move { num }).await?;
+        slow.await??;
+        queue.clone().get(2, |num| async move { num }).await?;
+        assert_eq!(
+            queue.stats(),
+            QueueStats {
+                hits: 1,
+                misses: 2,
+                evictions: 0,
+                in_flight: 0,
+                entries: 2,
+            }
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+//! entries differ wildly in size (a 4 KB esp next to a 2 GB BSA), so the number of entries says little about the memory (or disk)
+//! they hold on to - with a budget every finished value is weighed and the least recently used ones get evicted once the total
+//! goes over the limit. computations still in flight are not weighed and never evicted
+
+use {super::*, std::time::Instant};
+
+pub(crate) struct WeightBudget<V> {
+    max_weight: u64,
+    weigher: Arc<dyn Fn(&V) -> u64 + Send + Sync>,
+}
+
+impl<V> WeightBudget<V> {
+    pub(crate) fn new(max_weight: u64, weigher: impl Fn(&V) -> u64 + Send + Sync + 'static) -> Self {
+        Self {
+            max_weight,
+            weigher: Arc::new(weigher),
+        }
+    }
+}
+
+impl<K, V> CachedFutureQueue<K, V>
+where
+    K: std::hash::Hash + Eq + std::fmt::Debug + Clone + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+{
+    /// (key, weight, last access) of every finished value
+    fn weighed(&self, budget: &WeightBudget<V>) -> Vec<(K, u64, Instant)> {
+        self.tasks
+            .iter()
+            .filter_map(|entry| match entry.value().ready() {
+                Some(Ok(value)) => Some((entry.key().clone(), (budget.weigher)(&value), entry.value().last_access())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// total weight of the finished values, always 0 without a budget
+    pub fn total_weight(&self) -> u64 {
+        self.budget
+            .as_ref()
+            .map(|budget| {
+                self.weighed(budget)
+                    .into_iter()
+                    .map(|(_, weight, _)| weight)
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// a single value heavier than the whole budget is handed to its callers, but not kept
+    #[instrument(skip(self), level = "TRACE")]
+    pub(crate) fn enforce_budget(&self) -> usize {
+        let Some(budget) = self.budget.as_ref() else {
+            return 0;
+        };
+        let weighed = self.weighed(budget);
+        let total = weighed.iter().map(|(_, weight, _)| weight).sum::<u64>();
+        weighed
+            // values over the whole budget go first, evicting everything else would not make room for them anyway
+            .tap_mut(|weighed| weighed.sort_by_key(|(_, weight, last_access)| (*weight <= budget.max_weight, *last_access)))
+            .into_iter()
+            .scan(total, |total, (key, weight, _)| match *total > budget.max_weight {
+                true => {
+                    *total -= weight;
+                    Some(key)
+                }
+                false => None,
+            })
+            .filter(|key| self.tasks.remove(key).is_some())
+            .count()
+            .tap(|evicted| self.counters.evicted(*evicted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn test_least_recently_used_values_are_evicted_by_weight() -> anyhow::Result<()> {
+        let queue = CachedFutureQueue::<&str, Vec<u8>>::builder()
+            .weight_budget(100, |value| value.len() as u64)
+            .build();
+        queue
+            .clone()
+            .get("esp", |_| async move { vec![0; 10] })
+            .await?;
+        queue
+            .clone()
+            .get("bsa", |_| async move { vec![0; 60] })
+            .await?;
+        // touching the esp makes the bsa the least recently used one
+        queue.clone().get("esp", |_| async move { vec![] }).await?;
+        assert_eq!(queue.total_weight(), 70);
+
+        queue
+            .clone()
+            .get("textures", |_| async move { vec![0; 50] })
+            .await?;
+        assert_eq!(queue.total_weight(), 60);
+        assert_eq!(
+            queue
+                .iter_ready()
+                .map(|(key, _)| key)
+                .collect::<std::collections::BTreeSet<_>>(),
+            ["esp", "textures"].into_iter().collect()
+        );
+        assert_eq!(queue.stats().evictions, 1);
+
+        let huge = queue
+            .clone()
+            .get("huge", |_| async move { vec![0; 1000] })
+            .await?;
+        assert_eq!(huge.len(), 1000);
+        assert_eq!(queue.total_weight(), 60);
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+//! 7z calls which do not block the calling thread, the child process is killed when the future is dropped
+
+use {super::*, tokio::process::Command as AsyncCommand};
+
+/// same operations as [ArchiveHandle], obtained with [ArchiveHandle::async_handle]
+#[derive(Debug, Clone)]
+pub struct AsyncArchiveHandle {
+    handle: ArchiveHandle,
+}
+
+impl ArchiveHandle {
+    pub fn async_handle(&self) -> AsyncArchiveHandle {
+        AsyncArchiveHandle { handle: self.clone() }
+    }
+}
+
+async fn read_stdout_ok(command: Command, cancellation: &CancellationToken) -> Result<String> {
+    let dbg = command.command_debug();
+    let _running = cancellation.track_async().await?;
+    let program = command.get_program().to_owned();
+    let mut command = AsyncCommand::from(command);
+    command
+        .kill_on_drop(true)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    tokio::select! {
+        output = command.output() => output.map_err(|error| spawn_failure(error, &program)).and_then(stdout_ok),
+        // dropping the output future kills the child, tokio reaps it in the background
+        _ = cancellation.cancelled() => Err(crate::cancellation::Cancelled.into()),
+    }
+    .with_context(|| format!("when executing [{dbg}]"))
+}
+
+impl AsyncArchiveHandle {
+    async fn read_stdout_ok(&self, command: Command) -> Result<String> {
+        read_stdout_ok(command, &self.handle.binary.cancellation)
+            .await
+            .map_err(|e| classify_error(e, &self.handle.archive, self.handle.password.is_some()))
+    }
+
+    #[instrument]
+    pub async fn list_files(&self) -> Result<Vec<ListOutputEntry>> {
+        match self.handle.cached_listing() {
+            Some(cached) => Ok(cached),
+            None => self
+                .read_stdout_ok(self.handle.list_command())
+                .await
+                .and_then(|output| parse_list_output(output, &self.handle.archive))
+                .tap_ok(|entries| self.handle.cache_listing(entries)),
+        }
+    }
+
+    #[instrument]
+    pub async fn get_many_handles(&self, paths: &[&Path]) -> Result<Vec<(ListOutputEntry, ArchiveFileHandle)>> {
+        let entries = self
+            .list_files()
+            .await
+            .and_then(|files| select_entries(files, paths, &self.handle.archive))?;
+        let reservation = self
+            .handle
+            .binary
+            .reserve_temp_space_async(&entries)
+            .await
+            .context("reserving temp space")?;
+        let extraction_dir = ExtractionDir::create(&self.handle.binary.temp_files_dir, &self.handle.archive)
+            .context("creating extraction directory")
+            .map(|extraction_dir| extraction_dir.with_reservation(reservation))
+            .map(Arc::new)?;
+        let (command, _listfile) = self
+            .handle
+            .extract_command(&entries, &extraction_dir.path)?;
+        self.read_stdout_ok(command)
+            .await
+            .tap_ok(|res| tracing::debug!(%res))
+            .and_then(|_| open_extracted(entries, extraction_dir))
+    }
+
+    #[instrument]
+    pub async fn get_file(&self, file: &Path) -> Result<(ListOutputEntry, ArchiveFileHandle)> {
+        self.get_many_handles(&[file])
+            .await
+            .and_then(|file| file.into_iter().next().context("empty output"))
+    }
+
+    #[instrument]
+    pub async fn extract_all(&self, target_dir: &Path) -> Result<Vec<PathBuf>> {
+        let entries = self.list_files().await?;
+        self.read_stdout_ok(self.handle.extract_all_command(target_dir))
+            .await
+            .tap_ok(|res| tracing::debug!(%res))
+            .and_then(|_| collect_extracted(entries, target_dir))
+            .with_context(|| format!("extracting [{}] to [{}]", self.handle.archive.display(), target_dir.display()))
+    }
+}
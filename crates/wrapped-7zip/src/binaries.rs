@@ -0,0 +1,187 @@
+//! distros ship 7-zip under different names and with different codecs (`7zr` only does 7z, `7za` has no RAR, p7zip needs a separate RAR plugin),
+//! every binary found on `PATH` (or where the installer puts it on windows) is asked for the formats it supports (`7z i`) and the first capable one is picked per archive type
+
+use {
+    super::*,
+    std::{collections::BTreeSet, sync::OnceLock},
+};
+
+/// in order of preference
+pub(crate) const CANDIDATES: &[&str] = &["7z", "7zz", "7za", "7zr", "7z.exe", "7zz.exe", "7za.exe", "7zr.exe"];
+
+/// the 7-zip installer does not add itself to `PATH` on windows
+fn install_locations() -> Vec<PathBuf> {
+    #[cfg(windows)]
+    {
+        ["ProgramFiles", "ProgramFiles(x86)"]
+            .into_iter()
+            .filter_map(std::env::var_os)
+            .map(|directory| PathBuf::from(directory).join("7-Zip").join("7z.exe"))
+            .filter(|path| path.exists())
+            .collect()
+    }
+    #[cfg(not(windows))]
+    {
+        Vec::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SevenZipBinary {
+    pub path: PathBuf,
+    /// lowercase format names and extensions listed in the `Formats:` section of `7z i`
+    pub formats: BTreeSet<String>,
+}
+
+/// tokens of the `Formats:` section, the layout differs between versions so no attempt is made to tell the columns apart
+fn parse_formats(info: &str) -> BTreeSet<String> {
+    info.lines()
+        .skip_while(|line| line.trim() != "Formats:")
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty() && !line.trim_end().ends_with(':'))
+        .flat_map(|line| line.split_whitespace())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+impl SevenZipBinary {
+    #[instrument(level = "DEBUG")]
+    pub fn detect(path: &Path) -> Result<Self> {
+        Command::new(path)
+            .arg("i")
+            .read_stdout_ok()
+            .map(|info| parse_formats(&info))
+            .and_then(|formats| {
+                (!formats.is_empty())
+                    .then_some(formats)
+                    .context("no formats found in the output of [7z i]")
+            })
+            .map(|formats| Self {
+                path: path.to_owned(),
+                formats,
+            })
+            .with_context(|| format!("detecting capabilities of [{}]", path.display()))
+    }
+
+    pub fn supports(&self, extension: &str) -> bool {
+        self.formats.contains(&extension.to_lowercase())
+    }
+
+    /// every candidate found on `PATH`, detected once per process
+    pub fn all() -> &'static [Self] {
+        static DETECTED: OnceLock<Vec<SevenZipBinary>> = OnceLock::new();
+        DETECTED.get_or_init(|| {
+            CANDIDATES
+                .iter()
+                .filter_map(|bin| which::which(bin).ok())
+                .chain(install_locations())
+                .filter_map(|path| {
+                    std::fs::canonicalize(&path)
+                        .ok()
+                        .map(|canonical| (canonical, path))
+                })
+                .fold(BTreeMap::new(), |acc, (canonical, path)| {
+                    acc.tap_mut(|acc| {
+                        acc.entry(canonical).or_insert(path);
+                    })
+                })
+                .into_values()
+                .filter_map(|path| {
+                    Self::detect(&path)
+                        .tap_err(|error| tracing::warn!(?error, "skipping 7z candidate"))
+                        .ok()
+                })
+                .collect::<Vec<_>>()
+                .tap_mut(|found| {
+                    // preserves the preference order, deduplication above sorts by path
+                    found.sort_by_key(|binary| {
+                        CANDIDATES
+                            .iter()
+                            .position(|candidate| {
+                                binary
+                                    .path
+                                    .file_name()
+                                    .is_some_and(|name| name == *candidate)
+                            })
+                            .unwrap_or(usize::MAX)
+                    })
+                })
+                .tap(|found| tracing::debug!(?found, "detected 7z binaries"))
+        })
+    }
+
+    /// first binary able to handle the extension, 7z recognizes archives by their signature so unknown extensions are not an error
+    pub fn pick<'a>(binaries: &'a [Self], extension: Option<&str>) -> Result<&'a Self> {
+        let first = || {
+            binaries
+                .first()
+                .with_context(|| format!("no 7z binary found, tried: {}", CANDIDATES.join(", ")))
+        };
+        match extension.map(|extension| (extension, binaries.iter().find(|binary| binary.supports(extension)))) {
+            None => first(),
+            Some((_, Some(binary))) => Ok(binary),
+            // RAR support comes in a separate plugin (or binary) and it's the usual suspect
+            Some((extension, None)) if extension.eq_ignore_ascii_case("rar") && !binaries.is_empty() => Err(anyhow!(
+                "none of the 7z binaries supports RAR archives (found: {}), install 7-Zip from 7-zip.org (7zz) or the RAR plugin for p7zip (p7zip-rar)",
+                binaries
+                    .iter()
+                    .map(|binary| binary.path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            Some((_, None)) => first(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, pretty_assertions::assert_eq};
+
+    const P7ZIP_INFO: &str = r#"
+7-Zip (a) [64] 16.02 : Copyright (c) 1999-2016 Igor Pavlov : 2016-05-21
+
+Formats:
+ ...  AR    ar a deb lib    !<arch>0A
+ ...  Cab   cab             4D 53 43 46 00 00 00 00
+ C...F..c.a.m+ 7z     7z       7z BC AF 27 1C
+ C...F........ zip    zip z01 zipx jar xpi odt ods docx xlsx epub  50 4B 03 04
+
+Codecs:
+ 0  ED  40202  BCJ
+"#;
+
+    fn binary(name: &str, formats: &str) -> SevenZipBinary {
+        SevenZipBinary {
+            path: PathBuf::from(name),
+            formats: parse_formats(formats),
+        }
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        let formats = parse_formats(P7ZIP_INFO);
+        ["7z", "zip", "cab", "deb", "zipx"]
+            .into_iter()
+            .for_each(|format| assert!(formats.contains(format), "{format}"));
+        assert!(!formats.contains("rar"));
+        assert!(!formats.contains("bcj"));
+    }
+
+    #[test]
+    fn test_pick_prefers_capable_binary() {
+        let binaries = [
+            binary("7za", P7ZIP_INFO),
+            binary("7zz", &P7ZIP_INFO.replace(" ...  AR ", " ...  Rar5  rar r00  52 61 72 21\n ...  AR ")),
+        ];
+        assert_eq!(SevenZipBinary::pick(&binaries, Some("zip")).unwrap().path, Path::new("7za"));
+        assert_eq!(SevenZipBinary::pick(&binaries, Some("RAR")).unwrap().path, Path::new("7zz"));
+        assert_eq!(SevenZipBinary::pick(&binaries, None).unwrap().path, Path::new("7za"));
+        assert_eq!(SevenZipBinary::pick(&binaries, Some("fomod")).unwrap().path, Path::new("7za"));
+        let error = SevenZipBinary::pick(&binaries[..1], Some("rar"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("none of the 7z binaries supports RAR archives"), "{error}");
+        assert!(SevenZipBinary::pick(&[], None).is_err());
+    }
+}
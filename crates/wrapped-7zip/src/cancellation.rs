@@ -0,0 +1,222 @@
+//! 7z runs as a separate process, it does not notice when the caller gives up - cancelling kills every child
+//! started with the token, the extraction directories are removed as the failed calls unwind
+
+use {
+    super::*,
+    std::{
+        process::ExitStatus,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        time::{Duration, Instant},
+    },
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    running: AtomicUsize,
+}
+
+/// shared by every [Wrapped7Zip] (and the handles opened with it) it was passed to
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "7z command was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// counts the command as running for as long as it's alive, and holds one of the [limits] process slots (streams excepted)
+#[derive(Debug)]
+pub(crate) struct RunningGuard {
+    token: CancellationToken,
+    _permit: Option<limits::ProcessPermit>,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.token.inner.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// number of commands which have not finished (or cleaned up after themselves) yet
+    pub fn running(&self) -> usize {
+        self.inner.running.load(Ordering::SeqCst)
+    }
+
+    /// gives the running commands a chance to kill their children and remove the temp files, returns false on timeout
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        let started = Instant::now();
+        while self.running() > 0 {
+            if started.elapsed() > timeout {
+                return false;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        true
+    }
+
+    pub(crate) fn check(&self) -> Result<()> {
+        match self.is_cancelled() {
+            true => Err(Cancelled.into()),
+            false => Ok(()),
+        }
+    }
+
+    /// blocks until one of the [limits] process slots is free
+    pub(crate) fn track(&self) -> Result<RunningGuard> {
+        limits::acquire(self).and_then(|permit| self.track_with(Some(permit)))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn track_async(&self) -> Result<RunningGuard> {
+        limits::acquire_async(self)
+            .await
+            .and_then(|permit| self.track_with(Some(permit)))
+    }
+
+    /// for streamed reads, their reader decides how long the process lives and it may well wait for another 7z
+    /// (eg. a nested archive) before it's done - holding a slot meanwhile would deadlock once all slots are taken
+    pub(crate) fn track_stream(&self) -> Result<RunningGuard> {
+        self.track_with(None)
+    }
+
+    fn track_with(&self, permit: Option<limits::ProcessPermit>) -> Result<RunningGuard> {
+        self.inner.running.fetch_add(1, Ordering::SeqCst);
+        // the guard is created first so that a cancellation happening in between is not missed by [Self::wait_idle]
+        RunningGuard {
+            token: self.clone(),
+            _permit: permit,
+        }
+        .pipe(|guard| self.check().map(|_| guard))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn cancelled(&self) {
+        while !self.is_cancelled() {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// waits for the child to exit, kills (and reaps) it if the token gets cancelled in the meantime
+    pub(crate) fn wait(&self, child: &mut Child) -> Result<ExitStatus> {
+        loop {
+            if let Some(status) = child.try_wait().context("waiting for command")? {
+                return Ok(status);
+            }
+            if self.is_cancelled() {
+                child.kill().ok();
+                child.wait().ok();
+                return Err(Cancelled.into());
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// like [std::process::Command::output], but cancellable
+    pub(crate) fn output(&self, mut command: Command) -> Result<Output> {
+        let _running = self.track()?;
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| spawn_failure(error, command.get_program()))
+            .and_then(|mut child| {
+                // both pipes are drained in parallel, otherwise a chatty 7z blocks on a full pipe and never exits
+                let stdout = read_in_background(child.stdout.take().context("no stdout")?);
+                let stderr = read_in_background(child.stderr.take().context("no stderr")?);
+                let status = self.wait(&mut child)?;
+                Ok(Output {
+                    status,
+                    stdout: join_reader(stdout).context("reading stdout")?,
+                    stderr: join_reader(stderr).context("reading stderr")?,
+                })
+            })
+    }
+}
+
+pub(crate) fn read_in_background(mut reader: impl Read + Send + 'static) -> std::thread::JoinHandle<std::io::Result<Vec<u8>>> {
+    std::thread::spawn(move || {
+        let mut output = vec![];
+        reader.read_to_end(&mut output).map(|_| output)
+    })
+}
+
+pub(crate) fn join_reader<T>(reader: std::thread::JoinHandle<std::io::Result<T>>) -> Result<T> {
+    reader
+        .join()
+        .map_err(|_| anyhow!("reader thread panicked"))
+        .and_then(|output| output.context("reading output"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancelled_token_refuses_new_commands() {
+        let token = CancellationToken::new();
+        assert!(token.track().is_ok());
+        assert_eq!(token.running(), 0);
+        token.cancel();
+        assert!(token
+            .track()
+            .unwrap_err()
+            .downcast_ref::<Cancelled>()
+            .is_some());
+        assert_eq!(token.running(), 0);
+        assert!(token.wait_idle(Duration::from_millis(10)));
+        assert!(token.track_stream().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cancel_kills_running_child() -> Result<()> {
+        let token = CancellationToken::new();
+        let running = std::thread::spawn({
+            let token = token.clone();
+            move || {
+                token.output(Command::new("sleep").tap_mut(|c| {
+                    c.arg("60");
+                }))
+            }
+        });
+        let started = Instant::now();
+        while token.running() == 0 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        token.cancel();
+        assert!(token.wait_idle(Duration::from_secs(5)));
+        assert!(running
+            .join()
+            .expect("no panic")
+            .unwrap_err()
+            .downcast_ref::<Cancelled>()
+            .is_some());
+        assert!(started.elapsed() < Duration::from_secs(30));
+        Ok(())
+    }
+}
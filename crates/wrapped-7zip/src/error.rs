@@ -0,0 +1,169 @@
+//! errors are still [anyhow::Error]s, the kind is attached as context so that callers can branch on it with [Wrapped7ZipError::of]
+
+use {super::*, cancellation::Cancelled};
+
+/// 7z reports both cases the same way, the only difference is whether we tried with a password at all
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordError {
+    Missing { archive: PathBuf },
+    Wrong { archive: PathBuf },
+}
+
+impl std::fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordError::Missing { archive } => write!(f, "archive [{}] is password protected, but no password was provided", archive.display()),
+            PasswordError::Wrong { archive } => write!(f, "wrong password for archive [{}]", archive.display()),
+        }
+    }
+}
+
+impl std::error::Error for PasswordError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Wrapped7ZipError {
+    /// the archive is fine, the requested paths are not in it
+    NotFound {
+        archive: PathBuf,
+        paths: Vec<PathBuf>,
+    },
+    BadPassword(PasswordError),
+    /// 7z gave up on the archive, it's damaged or not an archive at all
+    Corrupted {
+        archive: PathBuf,
+    },
+    /// the binary is missing or could not be started
+    SpawnFailure {
+        binary: PathBuf,
+    },
+    /// 7z output was not what we expected, most likely an unsupported 7z version
+    ParseFailure {
+        archive: PathBuf,
+    },
+}
+
+impl std::fmt::Display for Wrapped7ZipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Wrapped7ZipError::NotFound { archive, paths } => write!(f, "paths not found in archive [{}]: {paths:?}", archive.display()),
+            Wrapped7ZipError::BadPassword(password_error) => password_error.fmt(f),
+            Wrapped7ZipError::Corrupted { archive } => write!(f, "archive [{}] is corrupted or not supported by 7z", archive.display()),
+            Wrapped7ZipError::SpawnFailure { binary } => write!(f, "could not run 7z binary [{}]", binary.display()),
+            Wrapped7ZipError::ParseFailure { archive } => write!(f, "unexpected 7z output for archive [{}]", archive.display()),
+        }
+    }
+}
+
+impl std::error::Error for Wrapped7ZipError {}
+
+impl Wrapped7ZipError {
+    /// finds the kind anywhere in the context chain
+    pub fn of(error: &anyhow::Error) -> Option<&Self> {
+        error.downcast_ref()
+    }
+}
+
+/// the root cause of every unsuccessful 7z run, 7z has no finer grained exit codes than these
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CommandFailed {
+    pub code: i32,
+}
+
+impl CommandFailed {
+    const FATAL_ERROR: i32 = 2;
+}
+
+impl std::fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "command failed with status [{}]", self.code)
+    }
+}
+
+impl std::error::Error for CommandFailed {}
+
+const WRONG_PASSWORD_MARKERS: &[&str] = &["Wrong password", "Can not open encrypted archive"];
+/// 7z exits with [CommandFailed::FATAL_ERROR] for a full disk or missing permissions as well, only these are the archive's fault
+const CORRUPTED_MARKERS: &[&str] = &[
+    "CRC Failed",
+    "Data Error",
+    "Headers Error",
+    "Unexpected end of archive",
+    "Can not open the file as archive",
+    "Is not archive",
+];
+
+/// attaches the [Wrapped7ZipError] kind to a failed 7z run, 7z reports (almost) everything through stderr so this is a best guess
+pub(crate) fn classify_error(error: anyhow::Error, archive: &Path, has_password: bool) -> anyhow::Error {
+    if Wrapped7ZipError::of(&error).is_some() || error.downcast_ref::<Cancelled>().is_some() {
+        return error;
+    }
+    let message = format!("{error:?}");
+    let wrong_password = WRONG_PASSWORD_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker));
+    let corrupted = CORRUPTED_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker));
+    let archive = archive.to_owned();
+    let exit_code = error
+        .downcast_ref::<CommandFailed>()
+        .map(|failed| failed.code);
+    match (wrong_password, has_password, exit_code) {
+        (true, false, _) => error.context(Wrapped7ZipError::BadPassword(PasswordError::Missing { archive })),
+        (true, true, _) => error.context(Wrapped7ZipError::BadPassword(PasswordError::Wrong { archive })),
+        (false, _, Some(CommandFailed::FATAL_ERROR)) if corrupted => error.context(Wrapped7ZipError::Corrupted { archive }),
+        (false, _, _) => error,
+    }
+}
+
+pub(crate) fn spawn_failure(error: std::io::Error, program: &std::ffi::OsStr) -> anyhow::Error {
+    anyhow::Error::new(error)
+        .context("spawning command")
+        .context(Wrapped7ZipError::SpawnFailure {
+            binary: PathBuf::from(program),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, pretty_assertions::assert_eq};
+
+    #[test]
+    fn test_classify_error() {
+        let archive = Path::new("archive.7z");
+        let failed = |code: i32, stderr: &str| anyhow::Error::new(CommandFailed { code }).context(stderr.to_string());
+        assert_eq!(
+            Wrapped7ZipError::of(&classify_error(failed(2, "ERROR: Wrong password : a.txt"), archive, true)),
+            Some(&Wrapped7ZipError::BadPassword(PasswordError::Wrong { archive: archive.to_owned() }))
+        );
+        assert_eq!(
+            Wrapped7ZipError::of(&classify_error(failed(2, "Can not open the file as archive"), archive, false)),
+            Some(&Wrapped7ZipError::Corrupted { archive: archive.to_owned() })
+        );
+        assert_eq!(
+            Wrapped7ZipError::of(&classify_error(failed(2, "ERROR: CRC Failed : textures\\a.dds"), archive, false)),
+            Some(&Wrapped7ZipError::Corrupted { archive: archive.to_owned() })
+        );
+        assert_eq!(
+            Wrapped7ZipError::of(&classify_error(
+                failed(2, "ERROR: E_FAIL : There is not enough space on the disk"),
+                archive,
+                false
+            )),
+            None
+        );
+        assert_eq!(
+            Wrapped7ZipError::of(&classify_error(failed(2, "ERROR: a.txt : Permission denied"), archive, false)),
+            None
+        );
+        assert_eq!(Wrapped7ZipError::of(&classify_error(failed(7, "Command Line Error"), archive, false)), None);
+        let not_found = anyhow!("nope").context(Wrapped7ZipError::NotFound {
+            archive: archive.to_owned(),
+            paths: vec![],
+        });
+        assert!(matches!(
+            Wrapped7ZipError::of(&classify_error(not_found.context("listing"), archive, false)),
+            Some(Wrapped7ZipError::NotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use {
+    super::*,
+    std::{
+        collections::BTreeSet,
+        convert::identity,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Mutex,
+            OnceLock,
+        },
+    },
+};
+
+const EXTRACTION_DIR_PREFIX: &str = "wrapped-7zip";
+
+static NEXT_EXTRACTION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// one directory per extraction call, named `wrapped-7zip-{pid}-{sequence}`
+/// so that two archives containing the same inner path never share an output location,
+/// and leftovers of killed processes can be recognized by their pid
+#[derive(Debug)]
+pub struct ExtractionDir {
+    pub path: PathBuf,
+    pub archive: PathBuf,
+    _reservation: Option<quota::Reservation>,
+}
+
+fn parse_owner_pid(name: &str) -> Option<u32> {
+    name.strip_prefix(EXTRACTION_DIR_PREFIX)
+        .and_then(|rest| rest.strip_prefix('-'))
+        .and_then(|rest| rest.split_once('-'))
+        .and_then(|(pid, sequence)| sequence.parse::<u64>().ok().and(pid.parse().ok()))
+}
+
+fn process_is_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        Path::new("/proc").join(pid.to_string()).exists()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        // no cheap way of checking, better leave the directory behind than remove a live one
+        true
+    }
+}
+
+impl ExtractionDir {
+    #[instrument(level = "DEBUG")]
+    pub fn create(temp_files_dir: &Path, archive: &Path) -> Result<Self> {
+        std::fs::create_dir_all(temp_files_dir)
+            .with_context(|| format!("creating temp files dir at [{}]", temp_files_dir.display()))
+            .and_then(|_| {
+                std::iter::repeat_with(|| NEXT_EXTRACTION_ID.fetch_add(1, Ordering::SeqCst))
+                    .map(|sequence| temp_files_dir.join(format!("{EXTRACTION_DIR_PREFIX}-{}-{sequence}", std::process::id())))
+                    .take(1024)
+                    .find_map(|path| match std::fs::create_dir(&path) {
+                        Ok(()) => Some(Ok(path)),
+                        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => None,
+                        Err(error) => Some(Err(error).with_context(|| format!("creating [{}]", path.display()))),
+                    })
+                    .context("could not find a free extraction directory name")
+                    .and_then(identity)
+            })
+            .map(|path| Self {
+                path,
+                archive: archive.to_owned(),
+                _reservation: None,
+            })
+    }
+
+    /// removes extraction directories left behind by processes that are no longer running,
+    /// runs only once per temp dir for the lifetime of the process
+    pub fn collect_garbage_once(temp_files_dir: &Path) {
+        static COLLECTED: OnceLock<Mutex<BTreeSet<PathBuf>>> = OnceLock::new();
+        let first_time = COLLECTED
+            .get_or_init(Default::default)
+            .lock()
+            .map(|mut collected| collected.insert(temp_files_dir.to_owned()))
+            .unwrap_or(false);
+        if first_time {
+            Self::collect_garbage(temp_files_dir)
+                .tap_err(|error| tracing::warn!(?error, "could not clean up stale extraction directories"))
+                .tap_ok(|removed| tracing::debug!(%removed, "cleaned up stale extraction directories"))
+                .ok();
+        }
+    }
+
+    /// extraction directories left behind by processes that are no longer running
+    pub fn stale(temp_files_dir: &Path) -> Result<Vec<PathBuf>> {
+        match temp_files_dir
+            .try_exists()
+            .context("checking temp files dir")?
+        {
+            false => Ok(vec![]),
+            true => std::fs::read_dir(temp_files_dir)
+                .context("listing temp files dir")?
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    entry
+                        .file_name()
+                        .to_str()
+                        .and_then(parse_owner_pid)
+                        .map(|pid| (pid, entry.path()))
+                })
+                .filter(|(pid, _)| !process_is_alive(*pid))
+                .map(|(_, path)| path)
+                .collect::<Vec<_>>()
+                .pipe(Ok),
+        }
+    }
+
+    pub fn collect_garbage(temp_files_dir: &Path) -> Result<usize> {
+        Self::stale(temp_files_dir).and_then(|stale| {
+            stale.into_iter().try_fold(0, |removed, path| {
+                tracing::debug!(?path, "removing stale extraction directory");
+                std::fs::remove_dir_all(&path)
+                    .with_context(|| format!("removing [{}]", path.display()))
+                    .map(|_| removed + 1)
+            })
+        })
+    }
+
+    /// the reserved temp space is given back once the directory is removed
+    pub(crate) fn with_reservation(self, reservation: Option<quota::Reservation>) -> Self {
+        self.tap_mut(|dir| dir._reservation = reservation)
+    }
+}
+
+impl Drop for ExtractionDir {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_dir_all(&self.path) {
+            if error.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!(?error, path=?self.path, archive=?self.archive, "could not remove extraction directory");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_owner_pid() {
+        assert_eq!(parse_owner_pid("wrapped-7zip-1234-5"), Some(1234));
+        assert_eq!(parse_owner_pid("wrapped-7zip-1234"), None);
+        assert_eq!(parse_owner_pid(".tmpAbc123"), None);
+    }
+
+    #[test]
+    fn test_dirs_are_unique_and_removed_on_drop() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let (a, b) = (
+            ExtractionDir::create(temp.path(), Path::new("a.7z"))?,
+            ExtractionDir::create(temp.path(), Path::new("b.7z"))?,
+        );
+        assert_ne!(a.path, b.path);
+        std::fs::write(a.path.join("same-name.txt"), "a")?;
+        std::fs::write(b.path.join("same-name.txt"), "b")?;
+        let a_path = a.path.clone();
+        drop(a);
+        assert!(!a_path.exists());
+        assert_eq!(std::fs::read_to_string(b.path.join("same-name.txt"))?, "b");
+        Ok(())
+    }
+
+    #[test]
+    fn test_garbage_collection_skips_live_processes() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let live = ExtractionDir::create(temp.path(), Path::new("live.7z"))?;
+        // pid 0 is never a regular process
+        let stale = temp.path().join(format!("{EXTRACTION_DIR_PREFIX}-0-0"));
+        std::fs::create_dir(&stale)?;
+        ExtractionDir::collect_garbage(temp.path())?;
+        assert!(live.path.exists());
+        #[cfg(target_os = "linux")]
+        assert!(!stale.exists());
+        Ok(())
+    }
+}
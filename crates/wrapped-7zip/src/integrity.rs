@@ -0,0 +1,133 @@
+//! `7z t` decompresses everything without writing anything, checking the CRCs stored in the archive along the way
+
+use {super::*, hoola_paths::MaybeWindowsPath};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestFailure {
+    pub path: PathBuf,
+    /// as reported by 7z, eg. `CRC Failed` or `Data Error`
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestReport {
+    pub archive: PathBuf,
+    pub failures: Vec<TestFailure>,
+}
+
+impl TestReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// turns a report with failures into an error listing all of them
+    pub fn ok(self) -> Result<Self> {
+        match self.is_ok() {
+            true => Ok(self),
+            false => Err(anyhow!(
+                "{} file(s) failed the integrity check:\n{}",
+                self.failures.len(),
+                self.failures
+                    .iter()
+                    .map(|TestFailure { path, reason }| format!("  {reason}: {}", path.display()))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ))
+            .with_context(|| Wrapped7ZipError::Corrupted { archive: self.archive.clone() }),
+        }
+    }
+}
+
+/// `ERROR: CRC Failed : textures/a.dds`, the reason can contain colons of its own (`CRC Failed in encrypted file. Wrong password? : a.dds`)
+fn parse_failures(output: &str) -> Vec<TestFailure> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("ERROR:"))
+        .filter_map(|error| error.rsplit_once(" : "))
+        .map(|(reason, path)| TestFailure {
+            path: path
+                .trim()
+                .to_string()
+                .pipe(MaybeWindowsPath)
+                .pipe(MaybeWindowsPath::into_path),
+            reason: reason.trim().to_string(),
+        })
+        .collect()
+}
+
+impl ArchiveHandle {
+    /// failures of individual files end up in the report, errors are reserved for archives 7z could not test at all
+    #[instrument]
+    pub fn test(&self) -> Result<TestReport> {
+        self.binary
+            .command(|c| {
+                c.arg("t")
+                    .arg(password_arg(self.password.as_deref()))
+                    .arg(&self.archive)
+            })
+            .pipe(|command| {
+                let dbg = command.command_debug();
+                self.binary
+                    .cancellation
+                    .output(command)
+                    .with_context(|| format!("when executing [{dbg}]"))
+            })
+            .and_then(|output| {
+                let failures = [&output.stdout, &output.stderr]
+                    .into_iter()
+                    .flat_map(|output| parse_failures(&String::from_utf8_lossy(output)))
+                    .collect::<Vec<_>>();
+                match (output.status.success(), failures.is_empty()) {
+                    (true, _) | (false, false) => Ok(TestReport {
+                        archive: self.archive.clone(),
+                        failures,
+                    }),
+                    (false, true) => anyhow::Error::new(CommandFailed {
+                        code: output.status.code().unwrap_or(-1),
+                    })
+                    .context(String::from_utf8_lossy(&output.stderr).to_string())
+                    .pipe(|error| Err(classify_error(error, &self.archive, self.password.is_some()))),
+                }
+            })
+            .with_context(|| format!("testing [{}]", self.archive.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, pretty_assertions::assert_eq};
+
+    #[test]
+    fn test_parse_failures() {
+        let output = r#"
+Testing archive: broken.7z
+--
+Path = broken.7z
+Type = 7z
+
+ERROR: CRC Failed : textures\armor\a.dds
+ERROR: Data Error : meshes/b.nif
+ERROR: CRC Failed in encrypted file. Wrong password? : secret.txt
+
+Sub items Errors: 3
+"#;
+        assert_eq!(
+            parse_failures(output),
+            vec![
+                TestFailure {
+                    path: PathBuf::from("textures/armor/a.dds"),
+                    reason: "CRC Failed".into(),
+                },
+                TestFailure {
+                    path: PathBuf::from("meshes/b.nif"),
+                    reason: "Data Error".into(),
+                },
+                TestFailure {
+                    path: PathBuf::from("secret.txt"),
+                    reason: "CRC Failed in encrypted file. Wrong password?".into(),
+                },
+            ]
+        );
+        assert_eq!(parse_failures("Everything is Ok"), vec![]);
+    }
+}
@@ -1,14 +1,17 @@
 #![allow(clippy::option_map_unit_fn)]
 
-pub use which;
 use {
     anyhow::{anyhow, Context, Result},
+    cancellation::CancellationToken,
+    error::{classify_error, spawn_failure, CommandFailed},
+    extraction_dir::ExtractionDir,
     list_output::{ListOutput, ListOutputEntry},
     std::{
         collections::BTreeMap,
+        io::{Read, Write},
         iter::once,
         path::{Path, PathBuf},
-        process::{Command, Output, Stdio},
+        process::{Child, ChildStdout, Command, Output, Stdio},
         str::FromStr,
         sync::Arc,
     },
@@ -16,12 +19,24 @@ use {
     tempfile::TempPath,
     tracing::instrument,
 };
+pub use {
+    error::{PasswordError, Wrapped7ZipError},
+    integrity::{TestFailure, TestReport},
+    limits::{set_max_processes, CompressionOptions},
+    list_cache::ListCache,
+    quota::{QuotaStats, TempQuota},
+    which,
+};
 
 #[derive(Clone, Debug)]
 pub struct Wrapped7Zip {
     bin: Arc<Path>,
     temp_files_dir: Arc<Path>,
     thread_count: Option<usize>,
+    cancellation: CancellationToken,
+    compression: CompressionOptions,
+    list_cache: Option<ListCache>,
+    temp_quota: Option<TempQuota>,
 }
 
 fn check_exists(file: &Path) -> Result<&Path> {
@@ -40,19 +55,66 @@ impl Wrapped7Zip {
         check_exists(bin)
             .context("checking if binary exists")
             .map(Arc::from)
+            .tap_ok(|_| ExtractionDir::collect_garbage_once(temp_files_dir))
             .map(|bin| Self {
                 bin,
                 temp_files_dir: Arc::from(temp_files_dir),
                 thread_count,
+                cancellation: CancellationToken::default(),
+                compression: CompressionOptions::default(),
+                list_cache: None,
+                temp_quota: None,
             })
             .with_context(|| format!("instantiating wrapper at [{}]", bin.display()))
     }
+
+    /// commands started after the token is cancelled fail right away, running ones get their 7z process killed
+    pub fn with_cancellation(self, cancellation: CancellationToken) -> Self {
+        Self { cancellation, ..self }
+    }
+
+    /// `-md` and `-mx` for the archives created with this instance
+    pub fn with_compression(self, compression: CompressionOptions) -> Self {
+        Self { compression, ..self }
+    }
+
+    pub fn compression(&self) -> &CompressionOptions {
+        &self.compression
+    }
+
+    /// listings are reused until the archive changes, see [list_cache]
+    pub fn with_list_cache(self, list_cache: ListCache) -> Self {
+        Self {
+            list_cache: Some(list_cache),
+            ..self
+        }
+    }
+
+    /// extractions wait for room in the temp dir instead of filling up the disk, see [quota]
+    pub fn with_temp_quota(self, temp_quota: TempQuota) -> Self {
+        Self {
+            temp_quota: Some(temp_quota),
+            ..self
+        }
+    }
+
+    /// like [CommandExt::read_stdout_ok], but the command can be cancelled
+    fn read_stdout_ok(&self, command: Command) -> Result<String> {
+        let dbg = command.command_debug();
+        self.cancellation
+            .output(command)
+            .and_then(stdout_ok)
+            .with_context(|| format!("when executing [{dbg}]"))
+    }
 }
 
-#[derive(Debug)]
+#[derive(derivative::Derivative, Clone)]
+#[derivative(Debug)]
 pub struct ArchiveHandle {
     binary: Wrapped7Zip,
     archive: PathBuf,
+    #[derivative(Debug = "ignore")]
+    password: Option<String>,
 }
 
 #[extension_traits::extension(pub trait CommandExt)]
@@ -61,6 +123,11 @@ impl Command {
         let command = self.get_program().to_string_lossy().to_string();
         self.get_args()
             .map(|a| a.to_string_lossy().to_string())
+            // passwords end up in logs and error messages otherwise
+            .map(|a| match a.starts_with("-p") && a.len() > 2 {
+                true => "-p***".to_string(),
+                false => a,
+            })
             .pipe(|args| once(command).chain(args).collect::<Vec<_>>())
             .join(" ")
     }
@@ -69,24 +136,26 @@ impl Command {
         self.stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .output()
-            .context("spawning command")
-            .and_then(|Output { status, stdout, stderr }| {
-                status
-                    .success()
-                    .then_some(())
-                    .ok_or_else(|| status.code().unwrap_or(-1))
-                    .map_err(|code| anyhow!("command failed with status [{code}]"))
-                    .with_context(|| String::from_utf8_lossy(&stderr).to_string())
-                    .and_then(|_| {
-                        stdout
-                            .pipe(String::from_utf8)
-                            .context("output is not a string")
-                    })
-            })
+            .map_err(|error| spawn_failure(error, self.get_program()))
+            .and_then(stdout_ok)
             .with_context(|| format!("when executing [{dbg}]"))
     }
 }
 
+fn stdout_ok(Output { status, stdout, stderr }: Output) -> Result<String> {
+    status
+        .success()
+        .then_some(())
+        .ok_or_else(|| status.code().unwrap_or(-1))
+        .map_err(|code| anyhow::Error::new(CommandFailed { code }))
+        .with_context(|| String::from_utf8_lossy(&stderr).to_string())
+        .and_then(|_| {
+            stdout
+                .pipe(String::from_utf8)
+                .context("output is not a string")
+        })
+}
+
 impl Wrapped7Zip {
     fn command<F: FnMut(&mut Command) -> &mut Command>(&self, mut build_command: F) -> Command {
         let mut command = Command::new(self.bin.as_ref());
@@ -103,30 +172,70 @@ impl Wrapped7Zip {
 
     #[tracing::instrument(level = "TRACE")]
     pub fn query_file_info(&self, path: &Path) -> Result<String> {
+        self.query_file_info_with_password(path, None)
+    }
+
+    #[tracing::instrument(level = "TRACE", skip(password))]
+    fn query_file_info_with_password(&self, path: &Path, password: Option<&str>) -> Result<String> {
         path.try_exists()
             .context("checking for file existence")
             .and_then(|exists| exists.then_some(path).context("path does not exist"))
-            .map(|path| self.command(|c| c.arg("l").arg(path)))
-            .and_then(|command| command.read_stdout_ok())
+            .map(|path| self.command(|c| c.arg("l").arg(password_arg(password)).arg(path)))
+            .and_then(|command| self.read_stdout_ok(command))
+            .map_err(|e| classify_error(e, path, password.is_some()))
     }
 
     #[tracing::instrument(level = "TRACE")]
     pub fn open_file(&self, archive: &Path) -> Result<ArchiveHandle> {
-        self.query_file_info(archive)
-            .map(|_| archive)
-            .map(|archive| ArchiveHandle {
-                binary: self.clone(),
-                archive: archive.into(),
-            })
+        self.open_file_with_password(archive, None)
+    }
+
+    #[tracing::instrument(level = "TRACE", skip(password))]
+    pub fn open_file_with_password(&self, archive: &Path, password: Option<String>) -> Result<ArchiveHandle> {
+        // a cached listing means 7z could open this very file before, no need to pay for another listing
+        match self
+            .list_cache
+            .as_ref()
+            .is_some_and(|cache| cache.contains(archive))
+        {
+            true => Ok(()),
+            false => self
+                .query_file_info_with_password(archive, password.as_deref())
+                .map(|_| ()),
+        }
+        .map(|_| archive)
+        .map(|archive| ArchiveHandle {
+            binary: self.clone(),
+            archive: archive.into(),
+            password,
+        })
     }
 
     pub fn find_bin(temp_files_dir: &Path, thread_count: Option<usize>) -> Result<Self> {
-        ["7z", "7z.exe"]
-            .into_iter()
+        binaries::CANDIDATES
+            .iter()
             .find_map(|bin| which::which(bin).ok())
             .context("no 7z binary")
+            .with_context(|| Wrapped7ZipError::SpawnFailure {
+                binary: PathBuf::from(binaries::CANDIDATES[0]),
+            })
             .and_then(|bin| Self::with_thread_count(&bin, temp_files_dir, thread_count))
     }
+
+    /// like [Self::find_bin], but picks a binary which supports the archive type
+    pub fn find_bin_for(temp_files_dir: &Path, thread_count: Option<usize>, extension: Option<&str>) -> Result<Self> {
+        binaries::SevenZipBinary::pick(binaries::SevenZipBinary::all(), extension)
+            .and_then(|binary| Self::with_thread_count(&binary.path, temp_files_dir, thread_count))
+            .with_context(|| format!("finding 7z binary for [{}] archives", extension.unwrap_or("unknown")))
+    }
+}
+
+/// always passed, an empty `-p` makes 7z fail on encrypted archives instead of waiting for the password on stdin
+///
+/// the password ends up in the process arguments, readable by anyone through `ps` or `/proc/<pid>/cmdline`,
+/// stdin is not an alternative, p7zip builds read a prompted password from the terminal and not from stdin
+fn password_arg(password: Option<&str>) -> String {
+    format!("-p{}", password.unwrap_or_default())
 }
 
 // thread_local! {
@@ -136,105 +245,399 @@ impl Wrapped7Zip {
 pub struct ArchiveFileHandle {
     pub path: TempPath,
     pub file: std::fs::File,
+    /// keeps the per-call extraction directory alive for as long as any of its files are in use
+    pub extraction_dir: Arc<ExtractionDir>,
 }
 
+/// a single file read straight from the stdout of `7z x -so`, nothing is written to disk
+#[derive(Debug)]
+pub struct ArchiveFileStream {
+    child: Child,
+    stdout: ChildStdout,
+    archive: PathBuf,
+    has_password: bool,
+    finished: bool,
+    cancellation: CancellationToken,
+    _running: cancellation::RunningGuard,
+}
+
+impl ArchiveFileStream {
+    /// 7z reports errors (corrupt data, wrong password) only through the exit status, which is known once the output ends
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.finished = true;
+        let status = self.child.wait()?;
+        match status.success() {
+            true => Ok(()),
+            false => {
+                let mut stderr = String::new();
+                if let Some(mut output) = self.child.stderr.take() {
+                    output.read_to_string(&mut stderr).ok();
+                }
+                anyhow::Error::new(CommandFailed {
+                    code: status.code().unwrap_or(-1),
+                })
+                .context(stderr)
+                .pipe(|error| classify_error(error, &self.archive, self.has_password))
+                .pipe(std::io::Error::other)
+                .pipe(Err)
+            }
+        }
+    }
+}
+
+impl Read for ArchiveFileStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if !self.finished && self.cancellation.is_cancelled() {
+            self.finished = true;
+            self.child.kill().ok();
+            self.child.wait().ok();
+            return Err(std::io::Error::other(cancellation::Cancelled));
+        }
+        match self.stdout.read(buf)? {
+            0 if !buf.is_empty() && !self.finished => self.finish().map(|_| 0),
+            read => Ok(read),
+        }
+    }
+}
+
+impl Drop for ArchiveFileStream {
+    fn drop(&mut self) {
+        if !self.finished {
+            // the reader stopped early, the rest of the output is not needed
+            self.child.kill().ok();
+            self.child.wait().ok();
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub mod async_handle;
+pub mod binaries;
+pub mod cancellation;
+pub mod error;
+pub mod extraction_dir;
+pub mod integrity;
+pub mod limits;
+pub mod list_cache;
 pub mod list_output;
+pub mod metadata;
+pub mod progress;
+pub mod quota;
+pub mod update;
+
+fn parse_list_output(output: String, archive: &Path) -> Result<Vec<ListOutputEntry>> {
+    list_output::ListOutput::from_str(&output)
+        .with_context(|| format!("unexpected output from list command:\n{output}"))
+        .with_context(|| Wrapped7ZipError::ParseFailure { archive: archive.to_owned() })
+        .map(|ListOutput { entries }| entries)
+}
+
+/// extracted files of the entries, directories are skipped
+fn collect_extracted(entries: Vec<ListOutputEntry>, target_dir: &Path) -> Result<Vec<PathBuf>> {
+    entries
+        .into_iter()
+        .map(|entry| (target_dir.join(&entry.original_path), entry))
+        .filter_map(|(path, entry)| match path.try_exists() {
+            Ok(true) => path.is_file().then(|| {
+                entry.try_apply_metadata(&path);
+                Ok(path)
+            }),
+            Ok(false) => Some(Err(anyhow!("no file was created for entry [{path:?}]"))),
+            Err(error) => Some(Err(error).with_context(|| format!("checking [{path:?}]"))),
+        })
+        .collect::<Result<Vec<_>>>()
+        .context("some files were not created")
+}
+
+/// entries of the requested paths (case insensitive), fails if any of them is missing
+fn select_entries(files: Vec<ListOutputEntry>, paths: &[&Path], archive: &Path) -> Result<Vec<ListOutputEntry>> {
+    let mut lookup = paths
+        .iter()
+        .copied()
+        .map(|p| (p.display().to_string().to_lowercase(), p))
+        .collect::<BTreeMap<_, _>>();
+    files
+        .into_iter()
+        .filter_map(|entry| {
+            lookup
+                .remove(&entry.path.display().to_string().to_lowercase())
+                .map(|_| entry)
+        })
+        .collect::<Vec<_>>()
+        .pipe(|entries| {
+            lookup
+                .is_empty()
+                .then_some(entries)
+                .with_context(|| format!("some paths were not found: {lookup:#?}"))
+                .with_context(|| Wrapped7ZipError::NotFound {
+                    archive: archive.to_owned(),
+                    paths: lookup.values().map(|path| path.to_path_buf()).collect(),
+                })
+        })
+}
 
-#[derive(Debug, PartialEq, PartialOrd, Hash)]
-pub(crate) struct MaybeWindowsPath(pub String);
+/// windows caps the whole command line at 32767 characters, linux allows more but not unlimited
+const MAX_INLINE_PATHS_LENGTH: usize = 8 * 1024;
 
-impl MaybeWindowsPath {
-    pub fn into_path(self) -> PathBuf {
-        let s = self.0;
-        let s = match s.contains("\\\\") {
-            true => s.split("\\\\").collect::<Vec<_>>().join("/"),
-            false => s,
-        };
-        let s = match s.contains("\\") {
-            true => s.split("\\").collect::<Vec<_>>().join("/"),
-            false => s,
-        };
-        PathBuf::from(s)
+/// one path per line, 7z reads it as UTF-8 with `-scsUTF-8`
+fn write_listfile(temp_files_dir: &Path, paths: &[&str]) -> Result<TempPath> {
+    tempfile::Builder::new()
+        .prefix("wrapped-7zip-listfile-")
+        .suffix(".txt")
+        .tempfile_in(temp_files_dir)
+        .context("creating listfile")
+        .and_then(|mut listfile| {
+            paths
+                .iter()
+                .try_for_each(|path| writeln!(listfile, "{path}"))
+                .context("writing listfile")
+                .map(|_| listfile.into_temp_path())
+        })
+        .with_context(|| format!("writing a listfile of {} paths to [{}]", paths.len(), temp_files_dir.display()))
+}
+
+/// paths are passed through a `@listfile` once there are too many of them for the command line,
+/// the returned listfile has to outlive the command
+fn append_paths(temp_files_dir: &Path, command: Command, paths: &[&str]) -> Result<(Command, Option<TempPath>)> {
+    match paths.iter().map(|path| path.len() + 1).sum::<usize>() > MAX_INLINE_PATHS_LENGTH {
+        true => write_listfile(temp_files_dir, paths).and_then(|listfile| {
+            // the command may run in another directory
+            std::fs::canonicalize(&listfile)
+                .context("canonicalizing listfile path")
+                .map(|listfile_path| {
+                    command
+                        .tap_mut(|c| {
+                            c.arg("-scsUTF-8")
+                                .arg(format!("@{}", listfile_path.display()));
+                        })
+                        .pipe(|c| (c, Some(listfile)))
+                })
+        }),
+        false => command
+            .tap_mut(|c| {
+                c.args(paths);
+            })
+            .pipe(|c| Ok((c, None))),
     }
 }
 
+fn open_extracted(entries: Vec<ListOutputEntry>, extraction_dir: Arc<ExtractionDir>) -> Result<Vec<(ListOutputEntry, ArchiveFileHandle)>> {
+    let temp_dir = extraction_dir.path.clone();
+    entries
+        .into_iter()
+        .map(|e| {
+            let path = temp_dir.join(&e.original_path).pipe(TempPath::from_path);
+            e.try_apply_metadata(&path);
+            let file = std::fs::File::open(&path).with_context(|| {
+                format!(
+                    "no file was created for entry [{path:?}]\n(found paths: [{:#?}])",
+                    std::fs::read_dir(&temp_dir).unwrap().collect::<Vec<_>>()
+                )
+            });
+            file.map(|file| {
+                (
+                    e,
+                    ArchiveFileHandle {
+                        path,
+                        file,
+                        extraction_dir: extraction_dir.clone(),
+                    },
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()
+        .context("some files were not created")
+}
+
 impl ArchiveHandle {
+    pub fn with_password(self, password: Option<String>) -> Self {
+        Self { password, ..self }
+    }
+
+    fn read_stdout_ok(&self, command: Command) -> Result<String> {
+        self.binary
+            .read_stdout_ok(command)
+            .map_err(|e| classify_error(e, &self.archive, self.password.is_some()))
+    }
+
+    fn list_command(&self) -> Command {
+        self.binary.command(|c| {
+            c.arg("l")
+                // more parsing-friendly output
+                .arg("-slt")
+                // file names do not depend on the console locale
+                .arg("-sccUTF-8")
+                .arg(password_arg(self.password.as_deref()))
+                .arg(&self.archive)
+        })
+    }
+
+    fn cached_listing(&self) -> Option<Vec<ListOutputEntry>> {
+        self.binary
+            .list_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&self.archive))
+    }
+
+    fn cache_listing(&self, entries: &[ListOutputEntry]) {
+        if let Some(cache) = self.binary.list_cache.as_ref() {
+            if let Err(error) = cache.put(&self.archive, entries) {
+                tracing::warn!(?error, "could not cache archive listing");
+            }
+        }
+    }
+
     #[instrument]
     pub fn list_files(&self) -> Result<Vec<ListOutputEntry>> {
-        self.binary
-            .command(|c| {
-                c.arg("l")
-                    // more parsing-friendly output
-                    .arg("-slt")
-                    .arg(&self.archive)
-            })
-            .read_stdout_ok()
-            .and_then(|o| list_output::ListOutput::from_str(&o).with_context(|| format!("unexpected output from list command:\n{o}")))
-            .map(|ListOutput { entries }| entries)
+        match self.cached_listing() {
+            Some(cached) => Ok(cached),
+            None => self
+                .list_command()
+                .pipe(|command| self.read_stdout_ok(command))
+                .and_then(|output| parse_list_output(output, &self.archive))
+                .tap_ok(|entries| self.cache_listing(entries)),
+        }
     }
 
+    /// sequential access to a single file without extracting it to a temporary directory first
     #[instrument]
-    pub fn get_many_handles(&self, paths: &[&Path]) -> Result<Vec<(ListOutputEntry, ArchiveFileHandle)>> {
-        let mut lookup = paths
-            .iter()
-            .copied()
-            .map(|p| (p.display().to_string().to_lowercase(), p))
-            .collect::<BTreeMap<_, _>>();
-        tempfile::tempdir_in(&self.binary.temp_files_dir)
-            .context("creating temporary directory")
-            .map(|temp_dir| temp_dir.into_path())
-            .and_then(|temp_dir| {
-                self.list_files()
-                    .map(|files| {
-                        files
-                            .into_iter()
-                            .filter_map(|entry| {
-                                lookup
-                                    .remove(&entry.path.display().to_string().to_lowercase())
-                                    .map(|_| entry)
+    pub fn stream_file(&self, file: &Path) -> Result<(ListOutputEntry, ArchiveFileStream)> {
+        let lookup = file.display().to_string().to_lowercase();
+        self.list_files()
+            .and_then(|files| {
+                files
+                    .into_iter()
+                    .find(|entry| entry.path.display().to_string().to_lowercase() == lookup)
+                    .with_context(|| format!("[{}] not found in archive", file.display()))
+                    .with_context(|| Wrapped7ZipError::NotFound {
+                        archive: self.archive.clone(),
+                        paths: vec![file.to_owned()],
+                    })
+            })
+            .and_then(|entry| {
+                let mut command = self.binary.command(|c| {
+                    c.arg("x")
+                        .arg("-so")
+                        // keeps stderr quiet enough for it not to fill up while stdout is being read
+                        .arg("-bsp0")
+                        .arg("-bso0")
+                        .arg(password_arg(self.password.as_deref()))
+                        .arg(&self.archive)
+                        .arg(&entry.original_path)
+                });
+                let dbg = command.command_debug();
+                let running = self.binary.cancellation.track_stream()?;
+                command
+                    .stdin(Stdio::null())
+                    .spawn()
+                    .map_err(|error| spawn_failure(error, command.get_program()))
+                    .and_then(|mut child| {
+                        child
+                            .stdout
+                            .take()
+                            .context("no stdout")
+                            .map(|stdout| ArchiveFileStream {
+                                child,
+                                stdout,
+                                archive: self.archive.clone(),
+                                has_password: self.password.is_some(),
+                                finished: false,
+                                cancellation: self.binary.cancellation.clone(),
+                                _running: running,
                             })
-                            .collect::<Vec<_>>()
                     })
-                    .and_then(|entries| {
-                        lookup
-                            .is_empty()
-                            .then_some(entries)
-                            .with_context(|| format!("some paths were not found: {lookup:#?}"))
+                    .with_context(|| format!("when executing [{dbg}]"))
+                    .map(|stream| (entry, stream))
+            })
+    }
+
+    #[instrument]
+    pub fn get_many_handles(&self, paths: &[&Path]) -> Result<Vec<(ListOutputEntry, ArchiveFileHandle)>> {
+        self.get_many_handles_with_progress(paths, |_| {})
+    }
+
+    /// `on_progress` receives the extraction percentage (0-100) as reported by 7z
+    #[instrument(skip(on_progress))]
+    pub fn get_many_handles_with_progress(&self, paths: &[&Path], on_progress: impl FnMut(u8)) -> Result<Vec<(ListOutputEntry, ArchiveFileHandle)>> {
+        self.list_files()
+            .and_then(|files| select_entries(files, paths, &self.archive))
+            .and_then(|entries| {
+                self.binary
+                    .reserve_temp_space(&entries)
+                    .context("reserving temp space")
+                    .and_then(|reservation| {
+                        ExtractionDir::create(&self.binary.temp_files_dir, &self.archive)
+                            .context("creating extraction directory")
+                            .map(|extraction_dir| extraction_dir.with_reservation(reservation))
                     })
-                    .and_then(|entries| {
-                        self.binary
-                            .command(|c| c.arg("x").arg(&self.archive))
-                            .pipe(|c| {
-                                let mut c = entries.iter().fold(c, |c, entry| {
-                                    c.tap_mut(|c| {
-                                        c.arg(&entry.original_path);
+                    .map(Arc::new)
+                    .and_then(|extraction_dir| {
+                        self.extract_command(&entries, &extraction_dir.path)
+                            .and_then(|(command, _listfile)| {
+                                command
+                                    .tap_mut(|c| {
+                                        c.arg("-bsp1");
                                     })
-                                });
-                                c.arg(format!("-o{}", temp_dir.display()));
-                                c.arg(&temp_dir);
-                                c
+                                    .pipe(|command| progress::read_stdout_with_progress(command, &self.binary.cancellation, on_progress))
+                                    .map_err(|e| classify_error(e, &self.archive, self.password.is_some()))
                             })
-                            .read_stdout_ok()
                             .tap_ok(|res| tracing::debug!(%res))
-                            .and_then(|_| {
-                                entries
-                                    .into_iter()
-                                    .map(|e| {
-                                        let path = temp_dir.join(&e.original_path).pipe(TempPath::from_path);
-                                        let file = std::fs::File::open(&path).with_context(|| {
-                                            format!(
-                                                "no file was created for entry [{path:?}]\n(found paths: [{:#?}])",
-                                                std::fs::read_dir(&temp_dir).unwrap().collect::<Vec<_>>()
-                                            )
-                                        });
-                                        file.map(|file| (e, ArchiveFileHandle { path, file }))
-                                    })
-                                    .collect::<Result<Vec<_>>>()
-                                    .context("some files were not created")
-                            })
+                            .and_then(|_| open_extracted(entries, extraction_dir))
                     })
             })
     }
+
+    /// the returned listfile has to outlive the command, see [append_paths]
+    fn extract_command(&self, entries: &[ListOutputEntry], temp_dir: &Path) -> Result<(Command, Option<TempPath>)> {
+        self.binary
+            .command(|c| {
+                c.arg("x")
+                    .arg(password_arg(self.password.as_deref()))
+                    .arg(&self.archive)
+            })
+            .pipe(|c| {
+                append_paths(
+                    &self.binary.temp_files_dir,
+                    c,
+                    &entries
+                        .iter()
+                        .map(|entry| entry.original_path.as_str())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .map(|(c, listfile)| {
+                c.tap_mut(|c| {
+                    c.arg(format!("-o{}", temp_dir.display()));
+                    c.arg(temp_dir);
+                })
+                .pipe(|c| (c, listfile))
+            })
+    }
+
+    /// extracts everything with a single call, for solid archives this is much cheaper than extracting files one batch at a time
+    /// returns the extracted files, directories are skipped
+    #[instrument]
+    pub fn extract_all(&self, target_dir: &Path) -> Result<Vec<PathBuf>> {
+        self.list_files().and_then(|entries| {
+            self.extract_all_command(target_dir)
+                .pipe(|command| self.read_stdout_ok(command))
+                .tap_ok(|res| tracing::debug!(%res))
+                .and_then(|_| collect_extracted(entries, target_dir))
+                .with_context(|| format!("extracting [{}] to [{}]", self.archive.display(), target_dir.display()))
+        })
+    }
+
+    fn extract_all_command(&self, target_dir: &Path) -> Command {
+        self.binary.command(|c| {
+            c.arg("x")
+                // the target directory is expected to be fresh, but leftovers should not make 7z ask what to do
+                .arg("-y")
+                .arg(password_arg(self.password.as_deref()))
+                .arg(&self.archive)
+                .arg(format!("-o{}", target_dir.display()))
+        })
+    }
+
     #[instrument]
     pub fn get_file(&self, file: &Path) -> Result<(ListOutputEntry, ArchiveFileHandle)> {
         self.get_many_handles(&[file])
@@ -0,0 +1,146 @@
+//! every 7z process keeps the dictionary of the archive it works on in memory, a handful of them running at once
+//! is enough to run an 8 GB machine out of memory - the number of processes is capped for the whole program
+//!
+//! streamed reads ([crate::ArchiveFileStream]) are not capped, a reader opening another archive before it's done
+//! would otherwise wait for its own slot forever
+
+use {
+    super::*,
+    cancellation::Cancelled,
+    std::{
+        sync::{Condvar, Mutex},
+        time::Duration,
+    },
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// `-md` and `-mx`, these only matter when creating archives - extraction uses whatever the archive was created with
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompressionOptions {
+    /// `-md`, eg. `64m` or `1g`
+    pub dictionary_size: Option<String>,
+    /// `-mx`, 0 (store) to 9 (ultra)
+    pub level: Option<u8>,
+}
+
+impl CompressionOptions {
+    pub fn args(&self) -> Vec<String> {
+        self.dictionary_size
+            .iter()
+            .map(|size| format!("-md{size}"))
+            .chain(self.level.map(|level| format!("-mx{}", level.min(9))))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ProcessSlots {
+    /// no limit by default
+    max: Option<usize>,
+    running: usize,
+}
+
+impl ProcessSlots {
+    fn try_take(&mut self) -> bool {
+        match self.max {
+            Some(max) if self.running >= max => false,
+            _ => {
+                self.running += 1;
+                true
+            }
+        }
+    }
+}
+
+static PROCESS_SLOTS: Mutex<ProcessSlots> = Mutex::new(ProcessSlots { max: None, running: 0 });
+static SLOT_FREED: Condvar = Condvar::new();
+
+fn slots() -> std::sync::MutexGuard<'static, ProcessSlots> {
+    // the counter stays consistent even if a holder panicked
+    PROCESS_SLOTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// maximum number of 7z processes running at the same time, `None` removes the limit - processes already running are not affected
+pub fn set_max_processes(max: Option<usize>) {
+    slots().max = max.map(|max| max.max(1));
+    SLOT_FREED.notify_all();
+}
+
+pub fn max_processes() -> Option<usize> {
+    slots().max
+}
+
+/// frees the slot when the process is done
+#[derive(Debug)]
+pub(crate) struct ProcessPermit(());
+
+impl Drop for ProcessPermit {
+    fn drop(&mut self) {
+        slots().running -= 1;
+        SLOT_FREED.notify_one();
+    }
+}
+
+/// waits for a free slot, gives up when the token gets cancelled in the meantime
+pub(crate) fn acquire(cancellation: &CancellationToken) -> Result<ProcessPermit> {
+    let mut slots = slots();
+    loop {
+        if cancellation.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        if slots.try_take() {
+            return Ok(ProcessPermit(()));
+        }
+        slots = SLOT_FREED
+            .wait_timeout(slots, POLL_INTERVAL)
+            .map(|(slots, _timeout)| slots)
+            .unwrap_or_else(|poisoned| poisoned.into_inner().0);
+    }
+}
+
+/// like [acquire], without blocking the runtime
+#[cfg(feature = "tokio")]
+pub(crate) async fn acquire_async(cancellation: &CancellationToken) -> Result<ProcessPermit> {
+    loop {
+        if cancellation.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        if slots().try_take() {
+            return Ok(ProcessPermit(()));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, pretty_assertions::assert_eq};
+
+    #[test]
+    fn test_compression_args() {
+        assert_eq!(CompressionOptions::default().args(), Vec::<String>::new());
+        assert_eq!(
+            CompressionOptions {
+                dictionary_size: Some("64m".into()),
+                level: Some(12),
+            }
+            .args(),
+            vec!["-md64m".to_string(), "-mx9".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_slots_are_capped() {
+        let mut slots = ProcessSlots { max: Some(2), running: 0 };
+        assert!(slots.try_take());
+        assert!(slots.try_take());
+        assert!(!slots.try_take());
+        slots.running -= 1;
+        assert!(slots.try_take());
+        slots.max = None;
+        assert!(slots.try_take());
+    }
+}
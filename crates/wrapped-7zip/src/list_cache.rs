@@ -0,0 +1,159 @@
+//! listing a big solid archive takes seconds and the same archives get listed over and over (also across runs),
+//! listings are kept on disk as one json file per archive - an archive is considered unchanged as long as its path, size and mtime are
+
+use {
+    super::*,
+    serde::{Deserialize, Serialize},
+    std::{hash::Hasher, time::UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheKey {
+    path: PathBuf,
+    size: u64,
+    modified_nanos: u128,
+}
+
+impl CacheKey {
+    fn for_archive(archive: &Path) -> Result<Self> {
+        std::fs::canonicalize(archive)
+            .context("canonicalizing path")
+            .and_then(|path| {
+                std::fs::metadata(&path)
+                    .context("reading metadata")
+                    .and_then(|metadata| {
+                        metadata
+                            .modified()
+                            .context("reading modification time")
+                            .and_then(|modified| {
+                                modified
+                                    .duration_since(UNIX_EPOCH)
+                                    .context("modified before epoch")
+                            })
+                            .map(|modified| Self {
+                                size: metadata.len(),
+                                modified_nanos: modified.as_nanos(),
+                                path,
+                            })
+                    })
+            })
+            .with_context(|| format!("building list cache key for [{}]", archive.display()))
+    }
+
+    fn file_name(&self) -> String {
+        let mut hasher = xxhash_rust::xxh64::Xxh64::new(0);
+        hasher.write(self.path.as_os_str().as_encoded_bytes());
+        hasher.write_u64(self.size);
+        hasher.write_u128(self.modified_nanos);
+        format!("{:016x}.json", hasher.finish())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedListing {
+    /// stored in full, a hash collision must not return the listing of another archive
+    key: CacheKey,
+    entries: Vec<ListOutputEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListCache {
+    directory: Arc<Path>,
+}
+
+impl ListCache {
+    pub fn new(directory: &Path) -> Result<Self> {
+        std::fs::create_dir_all(directory)
+            .with_context(|| format!("creating list cache directory at [{}]", directory.display()))
+            .map(|_| Self {
+                directory: Arc::from(directory),
+            })
+    }
+
+    /// cheaper than [Self::get], the listing is not read
+    pub fn contains(&self, archive: &Path) -> bool {
+        CacheKey::for_archive(archive)
+            .map(|key| self.directory.join(key.file_name()).exists())
+            .unwrap_or(false)
+    }
+
+    /// any problem with the cache is a miss, the archive just gets listed again
+    #[instrument(level = "DEBUG")]
+    pub fn get(&self, archive: &Path) -> Option<Vec<ListOutputEntry>> {
+        CacheKey::for_archive(archive)
+            .and_then(|key| {
+                let path = self.directory.join(key.file_name());
+                match path.exists() {
+                    false => Ok(None),
+                    true => std::fs::read(&path)
+                        .context("reading cached listing")
+                        .and_then(|cached| serde_json::from_slice::<CachedListing>(&cached).context("parsing cached listing"))
+                        .map(|CachedListing { key: cached_key, entries }| (cached_key == key).then_some(entries))
+                        .with_context(|| format!("reading [{}]", path.display())),
+                }
+            })
+            .tap_err(|error| tracing::debug!(?error, "list cache miss"))
+            .ok()
+            .flatten()
+    }
+
+    #[instrument(level = "DEBUG", skip(entries))]
+    pub fn put(&self, archive: &Path, entries: &[ListOutputEntry]) -> Result<()> {
+        CacheKey::for_archive(archive).and_then(|key| {
+            let path = self.directory.join(key.file_name());
+            tempfile::NamedTempFile::new_in(&self.directory)
+                .context("creating temp file")
+                .and_then(|mut file| {
+                    serde_json::to_writer(
+                        &mut file,
+                        &CachedListing {
+                            key,
+                            entries: entries.to_vec(),
+                        },
+                    )
+                    .context("writing listing")
+                    // concurrent writers of the same archive write the same content, last one wins
+                    .and_then(|_| file.persist(&path).context("persisting listing"))
+                })
+                .map(|_| ())
+                .with_context(|| format!("caching listing of [{}] at [{}]", archive.display(), path.display()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, pretty_assertions::assert_eq};
+
+    fn entry(path: &str) -> ListOutputEntry {
+        ListOutputEntry {
+            modified: chrono::NaiveDateTime::default(),
+            original_path: path.to_string(),
+            created: None,
+            size: 3,
+            path: PathBuf::from(path),
+            crc: Some(0xdeadbeef),
+            method: Some("LZMA2:24".into()),
+            encrypted: false,
+            attributes: Some("A".into()),
+        }
+    }
+
+    #[test]
+    fn test_cache_roundtrip_and_invalidation() -> Result<()> {
+        let dir = tempfile::tempdir().context("creating temp dir")?;
+        let archive = dir.path().join("archive.7z");
+        std::fs::write(&archive, b"abc").context("writing archive")?;
+        let cache = ListCache::new(&dir.path().join("cache"))?;
+
+        assert_eq!(cache.get(&archive), None);
+        assert!(!cache.contains(&archive));
+        cache.put(&archive, &[entry("a.txt")])?;
+        assert_eq!(cache.get(&archive), Some(vec![entry("a.txt")]));
+        assert!(cache.contains(&archive));
+
+        std::fs::write(&archive, b"abcd").context("changing archive")?;
+        assert_eq!(cache.get(&archive), None);
+        Ok(())
+    }
+}
@@ -1,16 +1,24 @@
 use {
     super::*,
     chrono::NaiveDateTime,
+    hoola_paths::MaybeWindowsPath,
     std::{collections::BTreeMap, ops::Not, str::FromStr},
 };
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ListOutputEntry {
     pub modified: chrono::NaiveDateTime,
     pub original_path: String,
     pub created: Option<chrono::NaiveDateTime>,
     pub size: u64,
     pub path: PathBuf,
+    /// not every format stores one (and the columnar fallback never has it)
+    pub crc: Option<u32>,
+    /// eg. `LZMA2:24` or `7zAES LZMA2:24`
+    pub method: Option<String>,
+    pub encrypted: bool,
+    /// raw attributes as printed by 7z, eg. `A` (windows) or `A_ -rw-r--r--` (unix permissions included)
+    pub attributes: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -26,73 +34,148 @@ fn parse_date(input: &str) -> Result<NaiveDateTime> {
         .pipe(|input| NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M:%S").with_context(|| format!("not a valid date: [{input}]")))
 }
 
+/// `7z l -slt` prints one `Key = Value` block per entry, the format 7z itself promises to keep stable
+fn parse_technical(s: &str) -> Result<Vec<ListOutputEntry>> {
+    s.split_once("----------")
+        .context("no indicator")
+        .and_then(|(_header, files)| {
+            files
+                .split("\n\n")
+                .filter_map(|entry| {
+                    entry
+                        .trim()
+                        .pipe(|trimmed| trimmed.is_empty().not().then_some(trimmed))
+                })
+                .filter(|entry| entry.lines().count() > 2)
+                .map(|entry| {
+                    entry
+                        .trim()
+                        .lines()
+                        .map(|line| {
+                            line.split_once("=")
+                                .context("no attribute indicator (=)")
+                                .map(|(k, v)| (k.trim(), v.trim()))
+                                .context(line.to_string())
+                        })
+                        .collect::<Result<BTreeMap<_, _>>>()
+                        .map(|e| {
+                            e.into_iter()
+                                .filter(|(_, v)| v.is_empty().not())
+                                .filter(|(_, v)| v != &"-")
+                                .collect::<BTreeMap<_, _>>()
+                        })
+                        .and_then(|mut entry| -> Result<_> {
+                            let path = entry.remove("Path").context("no such field")?.to_string();
+                            Ok(ListOutputEntry {
+                                created: entry
+                                    .remove("Created")
+                                    .map(parse_date)
+                                    .transpose()
+                                    .context("Created")?,
+                                modified: entry
+                                    .remove("Modified")
+                                    .context("no such field")
+                                    .and_then(parse_date)
+                                    .context("Modified")?,
+                                size: entry
+                                    .remove("Size")
+                                    .context("no such field")
+                                    .and_then(|v| v.parse().context("bad value"))
+                                    .context("Size")?,
+                                crc: entry
+                                    .remove("CRC")
+                                    .map(|v| u32::from_str_radix(v, 16).context("bad value"))
+                                    .transpose()
+                                    .context("CRC")?,
+                                method: entry.remove("Method").map(ToString::to_string),
+                                // `-` values are filtered out above
+                                encrypted: entry.remove("Encrypted").is_some_and(|v| v == "+"),
+                                attributes: entry.remove("Attributes").map(ToString::to_string),
+                                original_path: path.clone(),
+                                path: path
+                                    .pipe(MaybeWindowsPath)
+                                    .pipe(MaybeWindowsPath::into_path),
+                            })
+                        })
+                        .context(entry.to_string())
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+}
+
+/// the human oriented table, for 7z builds which ignore `-slt` - column widths are taken from the header so names with spaces survive
+/// ```text
+///    Date      Time    Attr         Size   Compressed  Name
+/// ------------------- ----- ------------ ------------  ------------------------
+/// 2024-08-04 22:02:17 ....A           12           16  some dir/file.txt
+/// ------------------- ----- ------------ ------------  ------------------------
+/// ```
+fn parse_columns(s: &str) -> Result<Vec<ListOutputEntry>> {
+    let mut lines = s.lines();
+    let name_column = lines
+        .by_ref()
+        .find(|line| line.trim_start().starts_with("Date") && line.trim_end().ends_with("Name"))
+        .context("no table header")?
+        .rfind("Name")
+        .context("no name column")?;
+    lines
+        .by_ref()
+        .next()
+        .filter(|line| line.starts_with("-----"))
+        .context("no table separator")?;
+    lines
+        .take_while(|line| !line.starts_with("-----"))
+        .map(|line| {
+            line.get(..name_column)
+                .zip(line.get(name_column..))
+                .context("line shorter than the header")
+                .and_then(|(columns, name)| {
+                    let columns = columns.split_whitespace().collect::<Vec<_>>();
+                    match columns.as_slice() {
+                        [date, time, attributes, size, ..] => Ok(ListOutputEntry {
+                            modified: parse_date(&format!("{date} {time}")).context("Modified")?,
+                            original_path: name.to_string(),
+                            created: None,
+                            size: size.parse().context("bad value").context("Size")?,
+                            path: name
+                                .to_string()
+                                .pipe(MaybeWindowsPath)
+                                .pipe(MaybeWindowsPath::into_path),
+                            crc: None,
+                            method: None,
+                            encrypted: false,
+                            attributes: Some(attributes.to_string()),
+                        }),
+                        other => Err(anyhow!("unexpected columns: {other:?}")),
+                    }
+                })
+                .with_context(|| line.to_string())
+        })
+        .collect()
+}
+
 impl FromStr for ListOutput {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        s.trim().to_string().pipe_ref(|trimmed| {
-            trimmed
-                .split_once("----------")
-                .context("no indicator")
-                .and_then(|(_header, files)| {
-                    files
-                        .split("\n\n")
-                        .filter_map(|entry| {
-                            entry
-                                .trim()
-                                .pipe(|trimmed| trimmed.is_empty().not().then_some(trimmed))
-                        })
-                        .filter(|entry| entry.lines().count() > 2)
-                        .map(|entry| {
-                            entry
-                                .trim()
-                                .lines()
-                                .map(|line| {
-                                    line.split_once("=")
-                                        .context("no attribute indicator (=)")
-                                        .map(|(k, v)| (k.trim(), v.trim()))
-                                        .context(line.to_string())
-                                })
-                                .collect::<Result<BTreeMap<_, _>>>()
-                                .map(|e| {
-                                    e.into_iter()
-                                        .filter(|(_, v)| v.is_empty().not())
-                                        .filter(|(_, v)| v != &"-")
-                                        .collect::<BTreeMap<_, _>>()
-                                })
-                                .and_then(|mut entry| -> Result<_> {
-                                    let path = entry.remove("Path").context("no such field")?.to_string();
-                                    Ok(ListOutputEntry {
-                                        created: entry
-                                            .remove("Created")
-                                            .map(parse_date)
-                                            .transpose()
-                                            .context("Created")?,
-                                        modified: entry
-                                            .remove("Modified")
-                                            .context("no such field")
-                                            .and_then(parse_date)
-                                            .context("Modified")?,
-                                        size: entry
-                                            .remove("Size")
-                                            .context("no such field")
-                                            .and_then(|v| v.parse().context("bad value"))
-                                            .context("Size")?,
-                                        original_path: path.clone(),
-                                        path: path
-                                            .pipe(MaybeWindowsPath)
-                                            .pipe(MaybeWindowsPath::into_path),
-                                    })
-                                })
-                                .context(entry.to_string())
-                        })
-                        .collect::<Result<Vec<_>>>()
-                        .map(|entries| Self { entries })
+        // 7z on windows ends lines with \r\n, which hides the blank lines separating the entries
+        s.replace("\r\n", "\n")
+            .trim()
+            .to_string()
+            .pipe_ref(|trimmed| {
+                parse_technical(trimmed).or_else(|technical| {
+                    parse_columns(trimmed)
+                        .context("parsing as a table")
+                        .map_err(|columns| columns.context(format!("parsing as technical listing: {technical:?}")))
                 })
-        })
+            })
+            .map(|entries| Self { entries })
     }
 }
 
+#[cfg(test)]
+mod tests;
+
 #[cfg(test)]
 mod test_date_parsing {
     use super::*;
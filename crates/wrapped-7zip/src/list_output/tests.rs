@@ -1 +1,100 @@
+use {super::*, pretty_assertions::assert_eq};
 
+const TECHNICAL: &str = "
+7-Zip [64] 16.02 : Copyright (c) 1999-2016 Igor Pavlov : 2016-05-21
+
+Scanning the drive for archives:
+1 file, 277 bytes (1 KiB)
+
+Listing archive: example.7z
+
+--
+Path = example.7z
+Type = 7z
+Physical Size = 277
+Headers Size = 162
+Method = LZMA2:12
+Solid = -
+Blocks = 1
+
+----------
+Path = textures\\armor = plate.dds
+Size = 12
+Packed Size = 16
+Modified = 2024-08-04 22:02:17.2575336
+Attributes = A
+CRC = 1A2B3C4D
+Encrypted = -
+Method = LZMA2:12
+Block = 0
+
+Path = secret.txt
+Size = 3
+Packed Size =
+Modified = 2024-08-06 13:25:23.4918567
+Created = 2024-08-06 13:25:20
+Attributes = A
+CRC =
+Encrypted = +
+Method = 7zAES LZMA2:12
+Block = 1
+";
+
+const COLUMNS: &str = "
+7-Zip [64] 16.02 : Copyright (c) 1999-2016 Igor Pavlov : 2016-05-21
+
+Listing archive: example.7z
+
+--
+Path = example.7z
+Type = 7z
+
+   Date      Time    Attr         Size   Compressed  Name
+------------------- ----- ------------ ------------  ------------------------
+2024-08-04 22:02:17 ....A           12           16  textures\\armor = plate.dds
+2024-08-06 13:25:23 ....A            3               some dir/secret.txt
+------------------- ----- ------------ ------------  ------------------------
+2024-08-06 13:25:23                 15           16  2 files
+";
+
+#[test]
+fn test_parses_technical_listing() -> Result<()> {
+    let ListOutput { entries } = TECHNICAL.parse()?;
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.size, entry.crc, entry.method.as_deref(), entry.encrypted))
+            .collect::<Vec<_>>(),
+        vec![
+            (PathBuf::from("textures/armor = plate.dds"), 12, Some(0x1A2B3C4D), Some("LZMA2:12"), false),
+            (PathBuf::from("secret.txt"), 3, None, Some("7zAES LZMA2:12"), true),
+        ]
+    );
+    assert!(entries[1].created.is_some());
+    Ok(())
+}
+
+#[test]
+fn test_windows_line_endings() -> Result<()> {
+    assert_eq!(TECHNICAL.replace('\n', "\r\n").parse::<ListOutput>()?, TECHNICAL.parse::<ListOutput>()?);
+    Ok(())
+}
+
+#[test]
+fn test_falls_back_to_columns() -> Result<()> {
+    let ListOutput { entries } = COLUMNS.parse()?;
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| (entry.original_path.as_str(), entry.size, entry.crc))
+            .collect::<Vec<_>>(),
+        vec![("textures\\armor = plate.dds", 12, None), ("some dir/secret.txt", 3, None)]
+    );
+    assert_eq!(entries[0].path, PathBuf::from("textures/armor = plate.dds"));
+    Ok(())
+}
+
+#[test]
+fn test_garbage_is_an_error() {
+    assert!("Everything is Ok".parse::<ListOutput>().is_err());
+}
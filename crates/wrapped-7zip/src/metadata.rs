@@ -0,0 +1,109 @@
+//! extracted files should look like the ones in the archive - some games order archives by their modification time
+
+use {
+    super::*,
+    chrono::{Local, TimeZone},
+    std::time::SystemTime,
+};
+
+/// `-rw-r--r--` style permissions, p7zip and 7zz print them after the windows attributes (`A_ -rw-r--r--`)
+fn parse_unix_mode(attributes: &str) -> Option<u32> {
+    attributes
+        .split_whitespace()
+        .find(|token| token.len() == 10 && token.starts_with(['-', 'd', 'l']))
+        .and_then(|token| {
+            token
+                .chars()
+                .skip(1)
+                .zip((0..9).rev())
+                .try_fold(0, |mode, (char, bit)| match char {
+                    '-' => Some(mode),
+                    'r' | 'w' | 'x' | 's' | 't' => Some(mode | (1 << bit)),
+                    // setuid/setgid/sticky without the execute bit
+                    'S' | 'T' => Some(mode),
+                    _ => None,
+                })
+        })
+}
+
+impl ListOutputEntry {
+    /// 7z prints local times
+    pub fn modified_time(&self) -> SystemTime {
+        Local
+            .from_local_datetime(&self.modified)
+            .earliest()
+            .map(SystemTime::from)
+            .unwrap_or_else(|| self.modified.and_utc().into())
+    }
+
+    pub fn unix_mode(&self) -> Option<u32> {
+        self.attributes.as_deref().and_then(parse_unix_mode)
+    }
+
+    /// modification time, and the unix permissions where the archive has them - the owner can always read and write the file
+    /// so that it can be cleaned up
+    pub fn apply_metadata(&self, path: &Path) -> Result<()> {
+        filetime::set_file_mtime(path, filetime::FileTime::from_system_time(self.modified_time()))
+            .context("setting modification time")
+            .and_then(|_| {
+                #[cfg(unix)]
+                if let Some(mode) = self.unix_mode() {
+                    use std::os::unix::fs::PermissionsExt;
+                    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode | 0o600)).context("setting permissions")?;
+                }
+                Ok(())
+            })
+            .with_context(|| format!("applying metadata of [{}] to [{}]", self.original_path, path.display()))
+    }
+
+    /// metadata is nice to have, failing to apply it should not fail the extraction
+    pub(crate) fn try_apply_metadata(&self, path: &Path) {
+        if let Err(error) = self.apply_metadata(path) {
+            tracing::warn!(?error, "could not preserve file metadata");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, pretty_assertions::assert_eq};
+
+    #[test]
+    fn test_parse_unix_mode() {
+        assert_eq!(parse_unix_mode("A_ -rw-r--r--"), Some(0o644));
+        assert_eq!(parse_unix_mode("A -rwxr-x---"), Some(0o750));
+        assert_eq!(parse_unix_mode("D_ drwxr-xr-x"), Some(0o755));
+        assert_eq!(parse_unix_mode("A"), None);
+        assert_eq!(parse_unix_mode("RHA"), None);
+    }
+
+    #[test]
+    fn test_apply_metadata() -> Result<()> {
+        let dir = tempfile::tempdir().context("creating temp dir")?;
+        let path = dir.path().join("file.bsa");
+        std::fs::write(&path, b"bsa").context("writing file")?;
+        let modified = chrono::NaiveDate::from_ymd_opt(2008, 10, 28)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .context("bad date")?;
+        let entry = ListOutputEntry {
+            modified,
+            original_path: "file.bsa".into(),
+            created: None,
+            size: 3,
+            path: PathBuf::from("file.bsa"),
+            crc: None,
+            method: None,
+            encrypted: false,
+            attributes: Some("A_ -r--r--r--".into()),
+        };
+        entry.apply_metadata(&path)?;
+        let metadata = std::fs::metadata(&path).context("reading metadata")?;
+        assert_eq!(metadata.modified().context("reading mtime")?, entry.modified_time());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o644);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+//! `-bsp1` makes 7z print its progress as `  42% 3 - some/file.dds`, every update is erased with backspaces and redrawn
+
+use {
+    crate::{
+        cancellation::{join_reader, read_in_background, CancellationToken},
+        error::{spawn_failure, CommandFailed},
+        CommandExt,
+    },
+    anyhow::{Context, Result},
+    std::{
+        io::Read,
+        process::{Command, Stdio},
+        sync::mpsc,
+        time::Duration,
+    },
+};
+
+/// percentage at the start of a single progress update
+fn parse_percentage(update: &str) -> Option<u8> {
+    update
+        .split_whitespace()
+        .next()
+        .and_then(|token| token.strip_suffix('%'))
+        .and_then(|percentage| percentage.parse::<u8>().ok())
+        .filter(|percentage| *percentage <= 100)
+}
+
+/// incremental parser, updates can be split between reads
+#[derive(Debug, Default)]
+pub struct ProgressParser {
+    pending: String,
+    /// everything that is not a progress update, for logs and error messages
+    pub other_output: String,
+}
+
+impl ProgressParser {
+    pub fn feed(&mut self, chunk: &str) -> Vec<u8> {
+        self.pending.push_str(chunk);
+        let mut percentages = vec![];
+        while let Some(end) = self.pending.find(['\u{8}', '\r', '\n']) {
+            let update = self.pending.drain(..=end).collect::<String>();
+            match parse_percentage(&update) {
+                Some(percentage) => percentages.push(percentage),
+                None => {
+                    if !update.trim().is_empty() {
+                        self.other_output
+                            .push_str(update.trim_end_matches(['\u{8}', '\r']));
+                    }
+                }
+            }
+        }
+        percentages
+    }
+
+    pub fn finish(mut self) -> String {
+        self.other_output.push_str(&self.pending);
+        self.other_output
+    }
+}
+
+/// decodes stdout as it comes, a read can end in the middle of a multi-byte character
+fn parse_in_background(mut stdout: impl Read + Send + 'static, progress: mpsc::Sender<u8>) -> std::thread::JoinHandle<std::io::Result<String>> {
+    std::thread::spawn(move || {
+        let mut parser = ProgressParser::default();
+        let mut buffer = [0u8; 4096];
+        let mut undecoded = Vec::new();
+        loop {
+            let read = stdout.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            undecoded.extend_from_slice(&buffer[..read]);
+            let valid_up_to = match std::str::from_utf8(&undecoded) {
+                Ok(valid) => valid.len(),
+                Err(error) => error.valid_up_to(),
+            };
+            let decoded = String::from_utf8_lossy(&undecoded[..valid_up_to]).to_string();
+            undecoded.drain(..valid_up_to);
+            parser
+                .feed(&decoded)
+                .into_iter()
+                // the receiver is gone only when the command got cancelled
+                .try_for_each(|percentage| progress.send(percentage))
+                .ok();
+        }
+        parser
+            .other_output
+            .push_str(&String::from_utf8_lossy(&undecoded));
+        Ok(parser.finish())
+    })
+}
+
+/// like [CommandExt::read_stdout_ok], but reports progress on the way - the command is expected to run with `-bsp1`
+pub(crate) fn read_stdout_with_progress(mut command: Command, cancellation: &CancellationToken, mut on_progress: impl FnMut(u8)) -> Result<String> {
+    let dbg = command.command_debug();
+    let _running = cancellation.track()?;
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| spawn_failure(error, command.get_program()))
+        .and_then(|mut child| {
+            let (progress_tx, progress_rx) = mpsc::channel();
+            let stdout = parse_in_background(child.stdout.take().context("no stdout")?, progress_tx);
+            // stderr has to be drained in parallel, otherwise a chatty 7z blocks on a full pipe
+            let stderr = read_in_background(child.stderr.take().context("no stderr")?);
+            loop {
+                match progress_rx.recv_timeout(Duration::from_millis(50)) {
+                    Ok(percentage) => on_progress(percentage),
+                    Err(mpsc::RecvTimeoutError::Timeout) => cancellation.check().or_else(|cancelled| {
+                        child.kill().ok();
+                        child.wait().ok();
+                        Err(cancelled)
+                    })?,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            let status = cancellation.wait(&mut child)?;
+            let stdout = join_reader(stdout).context("reading stdout")?;
+            let stderr = join_reader(stderr)
+                .context("reading stderr")
+                .map(|stderr| String::from_utf8_lossy(&stderr).to_string())?;
+            status
+                .success()
+                .then(|| {
+                    on_progress(100);
+                    stdout
+                })
+                .ok_or_else(|| status.code().unwrap_or(-1))
+                .map_err(|code| anyhow::Error::new(CommandFailed { code }))
+                .with_context(|| stderr)
+        })
+        .with_context(|| format!("when executing [{dbg}]"))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, pretty_assertions::assert_eq};
+
+    #[test]
+    fn test_parses_percentages_split_between_reads() {
+        let mut parser = ProgressParser::default();
+        let mut percentages = vec![];
+        percentages.extend(parser.feed("\n7-Zip [64] 16.02\n\n  0%\u{8}\u{8}\u{8}\u{8}    \u{8}\u{8}\u{8}\u{8}  1"));
+        percentages.extend(parser.feed("2% 3 - textures/a.dds\u{8}\u{8}\u{8}\u{8}"));
+        percentages.extend(parser.feed(" 100%\rEverything is Ok\n"));
+        assert_eq!(percentages, vec![0, 12, 100]);
+        assert_eq!(parser.finish(), "7-Zip [64] 16.02\nEverything is Ok\n");
+    }
+
+    #[test]
+    fn test_ignores_non_progress_output() {
+        assert_eq!(parse_percentage("  42% 3 - a.dds"), Some(42));
+        assert_eq!(parse_percentage("Extracting archive: a.7z"), None);
+        assert_eq!(parse_percentage("420%"), None);
+        assert_eq!(parse_percentage("%"), None);
+    }
+}
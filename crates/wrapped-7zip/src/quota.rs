@@ -0,0 +1,316 @@
+//! extracted files live in the temp dir for as long as their handles do, a big install can pile up hundreds of GB of them -
+//! with a quota every extraction reserves the (uncompressed) size of its files first and waits while the temp dir is full,
+//! which backpressures the callers instead of filling up the disk
+
+use {
+    super::*,
+    cancellation::Cancelled,
+    std::{
+        sync::{Condvar, Mutex, MutexGuard},
+        time::{Duration, Instant, SystemTime},
+    },
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// extractions which are still alive can all be waiting for each other (eg. a nested archive waiting while its parent holds on to
+/// its reservation), past this a reservation fails instead of hanging the install
+const DEFAULT_MAX_WAIT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaStats {
+    pub max_bytes: u64,
+    /// reserved by extractions whose files are still alive
+    pub used_bytes: u64,
+    pub peak_bytes: u64,
+    /// number of extractions which had to wait for space
+    pub waits: u64,
+    /// freed by removing leftovers of dead processes
+    pub evicted_bytes: u64,
+}
+
+#[derive(Debug)]
+struct Inner {
+    temp_files_dir: PathBuf,
+    stats: Mutex<QuotaStats>,
+    freed: Condvar,
+}
+
+/// shared by every [Wrapped7Zip] it's passed to, see [Wrapped7Zip::with_temp_quota]
+#[derive(Debug, Clone)]
+pub struct TempQuota {
+    inner: Arc<Inner>,
+    max_wait: Duration,
+}
+
+/// gives the bytes back to the quota when dropped, extraction directories hold on to theirs
+#[derive(Debug)]
+pub(crate) struct Reservation {
+    quota: TempQuota,
+    bytes: u64,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.quota.stats().used_bytes -= self.bytes;
+        self.quota.inner.freed.notify_all();
+    }
+}
+
+fn dir_size(path: &Path) -> u64 {
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+                    _ => entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+                })
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+impl TempQuota {
+    pub fn new(temp_files_dir: &Path, max_bytes: u64) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                temp_files_dir: temp_files_dir.to_owned(),
+                stats: Mutex::new(QuotaStats {
+                    max_bytes,
+                    ..Default::default()
+                }),
+                freed: Condvar::new(),
+            }),
+            max_wait: DEFAULT_MAX_WAIT,
+        }
+    }
+
+    /// how long a reservation waits for space before it fails
+    pub fn with_max_wait(self, max_wait: Duration) -> Self {
+        Self { max_wait, ..self }
+    }
+
+    fn stats(&self) -> MutexGuard<'_, QuotaStats> {
+        self.inner
+            .stats
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn snapshot(&self) -> QuotaStats {
+        *self.stats()
+    }
+
+    /// a single extraction bigger than the whole quota is let through once nothing else is using it, otherwise it would wait forever
+    fn try_take(stats: &mut QuotaStats, bytes: u64) -> bool {
+        match stats.used_bytes == 0 || stats.used_bytes + bytes <= stats.max_bytes {
+            true => {
+                stats.used_bytes += bytes;
+                stats.peak_bytes = stats.peak_bytes.max(stats.used_bytes);
+                true
+            }
+            false => false,
+        }
+    }
+
+    /// removes extraction directories of dead processes, least recently modified first, until `needed` bytes are freed
+    fn evict_stale(&self, needed: u64) -> u64 {
+        ExtractionDir::stale(&self.inner.temp_files_dir)
+            .tap_err(|error| tracing::warn!(?error, "could not look for stale extraction directories"))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|path| {
+                let modified = std::fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (modified, path)
+            })
+            .collect::<Vec<_>>()
+            .tap_mut(|stale| stale.sort_by_key(|(modified, _)| *modified))
+            .into_iter()
+            .fold(0, |freed, (_, path)| match freed >= needed {
+                true => freed,
+                false => {
+                    let size = dir_size(&path);
+                    match std::fs::remove_dir_all(&path) {
+                        Ok(()) => freed + size,
+                        Err(error) => {
+                            tracing::warn!(?error, ?path, "could not evict stale extraction directory");
+                            freed
+                        }
+                    }
+                }
+            })
+            .tap(|freed| self.stats().evicted_bytes += freed)
+    }
+
+    fn too_small(&self, bytes: u64) -> anyhow::Error {
+        let QuotaStats { max_bytes, used_bytes, .. } = self.snapshot();
+        anyhow::anyhow!(
+            "temp quota too small: waited [{:?}] for [{bytes}] bytes while extractions which are still alive hold [{used_bytes}] of [{max_bytes}] bytes, \
+             raise the quota",
+            self.max_wait
+        )
+    }
+
+    fn on_wait(&self, bytes: u64) {
+        let stats = {
+            let mut stats = self.stats();
+            stats.waits += 1;
+            *stats
+        };
+        tracing::info!(?stats, %bytes, "temp files quota exceeded, waiting for extracted files to be released");
+        self.evict_stale(bytes);
+    }
+
+    pub(crate) fn reserve(&self, bytes: u64, cancellation: &CancellationToken) -> Result<Reservation> {
+        let mut waited = false;
+        let started = Instant::now();
+        let mut stats = self.stats();
+        loop {
+            cancellation.check()?;
+            if Self::try_take(&mut stats, bytes) {
+                return Ok(Reservation { quota: self.clone(), bytes });
+            }
+            if started.elapsed() >= self.max_wait {
+                drop(stats);
+                return Err(self.too_small(bytes));
+            }
+            if !waited {
+                waited = true;
+                drop(stats);
+                self.on_wait(bytes);
+                stats = self.stats();
+                continue;
+            }
+            stats = self
+                .inner
+                .freed
+                .wait_timeout(stats, POLL_INTERVAL)
+                .map(|(stats, _timeout)| stats)
+                .unwrap_or_else(|poisoned| poisoned.into_inner().0);
+        }
+    }
+
+    /// like [Self::reserve], without blocking the runtime
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn reserve_async(&self, bytes: u64, cancellation: &CancellationToken) -> Result<Reservation> {
+        let mut waited = false;
+        let started = Instant::now();
+        loop {
+            cancellation.check()?;
+            if Self::try_take(&mut self.stats(), bytes) {
+                return Ok(Reservation { quota: self.clone(), bytes });
+            }
+            if started.elapsed() >= self.max_wait {
+                return Err(self.too_small(bytes));
+            }
+            if !waited {
+                waited = true;
+                self.on_wait(bytes);
+                continue;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// total size of the files of the entries, which is what they take once extracted
+pub(crate) fn extracted_size(entries: &[ListOutputEntry]) -> u64 {
+    entries.iter().map(|entry| entry.size).sum()
+}
+
+impl Wrapped7Zip {
+    /// reserves room in the temp dir for the entries, does nothing without a quota
+    pub(crate) fn reserve_temp_space(&self, entries: &[ListOutputEntry]) -> Result<Option<Reservation>> {
+        self.temp_quota
+            .as_ref()
+            .map(|quota| quota.reserve(extracted_size(entries), &self.cancellation))
+            .transpose()
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn reserve_temp_space_async(&self, entries: &[ListOutputEntry]) -> Result<Option<Reservation>> {
+        match self.temp_quota.as_ref() {
+            Some(quota) => quota
+                .reserve_async(extracted_size(entries), &self.cancellation)
+                .await
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, pretty_assertions::assert_eq};
+
+    #[test]
+    fn test_reservations_are_released_on_drop() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let quota = TempQuota::new(temp.path(), 100);
+        let token = CancellationToken::new();
+        let first = quota.reserve(60, &token)?;
+        let second = quota.reserve(40, &token)?;
+        assert_eq!(quota.snapshot().used_bytes, 100);
+        let waiting = std::thread::spawn({
+            let quota = quota.clone();
+            let token = token.clone();
+            move || quota.reserve(50, &token).map(|_| ())
+        });
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!waiting.is_finished());
+        drop(first);
+        waiting.join().expect("no panic")?;
+        drop(second);
+        assert_eq!(
+            quota.snapshot(),
+            QuotaStats {
+                max_bytes: 100,
+                used_bytes: 0,
+                peak_bytes: 100,
+                waits: 1,
+                evicted_bytes: 0,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_oversized_reservation_passes_when_idle() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let quota = TempQuota::new(temp.path(), 10);
+        let reservation = quota.reserve(1000, &CancellationToken::new())?;
+        assert_eq!(quota.snapshot().used_bytes, 1000);
+        drop(reservation);
+        assert_eq!(quota.snapshot().used_bytes, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gives_up_when_space_is_never_released() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let quota = TempQuota::new(temp.path(), 10).with_max_wait(Duration::from_millis(200));
+        let token = CancellationToken::new();
+        let _held = quota.reserve(10, &token)?;
+        let error = quota.reserve(5, &token).unwrap_err().to_string();
+        assert!(error.contains("temp quota too small"), "{error}");
+        assert_eq!(quota.snapshot().used_bytes, 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancelled_while_waiting() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let quota = TempQuota::new(temp.path(), 10);
+        let token = CancellationToken::new();
+        let _full = quota.reserve(10, &token)?;
+        token.cancel();
+        assert!(quota
+            .reserve(5, &token)
+            .unwrap_err()
+            .downcast_ref::<Cancelled>()
+            .is_some());
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use super::*;
 
 #[test]
 fn test_stat_example_file() -> Result<()> {
-    let output = Wrapped7Zip::find_bin(Path::new("."))?.query_file_info(Path::new("./test-data/example-1.rar"))?;
+    let output = Wrapped7Zip::find_bin(Path::new("."), None)?.query_file_info(Path::new("./test-data/example-1.rar"))?;
     println!("{output}");
     assert!(output.contains("20:58:56"));
     Ok(())
@@ -10,7 +10,7 @@ fn test_stat_example_file() -> Result<()> {
 
 #[test_log::test(tokio::test)]
 async fn test_extract_example_files() -> Result<()> {
-    let handler = Wrapped7Zip::find_bin(Path::new("."))?;
+    let handler = Wrapped7Zip::find_bin(Path::new("."), None)?;
     [
         //
         ("test-data/example-small-file.7z", "small-file.json"),
@@ -39,7 +39,7 @@ async fn test_extract_example_files() -> Result<()> {
 }
 #[test_log::test(tokio::test)]
 async fn extract_example_file() -> Result<()> {
-    let archive = Wrapped7Zip::find_bin(Path::new("."))?.open_file(Path::new("./test-data/example-1.rar"))?;
+    let archive = Wrapped7Zip::find_bin(Path::new("."), None)?.open_file(Path::new("./test-data/example-1.rar"))?;
     let files = archive.list_files()?;
     let (_, mut file) = archive.get_file(&files[0].path)?;
     let mut out = Vec::new();
@@ -51,3 +51,193 @@ async fn extract_example_file() -> Result<()> {
 
     Ok(())
 }
+
+#[test_log::test]
+fn test_password_protected_archive() -> Result<()> {
+    let handler = Wrapped7Zip::find_bin(Path::new("."), None)?;
+    let dir = tempfile::tempdir().context("creating temp dir")?;
+    let archive = dir.path().join("protected.7z");
+    std::fs::write(dir.path().join("secret.txt"), "very secret").context("writing file")?;
+    handler
+        .command(|c| {
+            c.arg("a")
+                .arg("-psecret")
+                // encrypts the file names too, so that even listing requires the password
+                .arg("-mhe=on")
+                .arg(&archive)
+                .arg(dir.path().join("secret.txt"))
+        })
+        .read_stdout_ok()
+        .context("creating protected archive")?;
+    let password_error = |result: Result<ArchiveHandle>| {
+        result
+            .expect_err("opening should fail")
+            .pipe_ref(Wrapped7ZipError::of)
+            .cloned()
+    };
+    assert_eq!(
+        password_error(handler.open_file(&archive)),
+        Some(Wrapped7ZipError::BadPassword(PasswordError::Missing { archive: archive.clone() }))
+    );
+    assert_eq!(
+        password_error(handler.open_file_with_password(&archive, Some("wrong".into()))),
+        Some(Wrapped7ZipError::BadPassword(PasswordError::Wrong { archive: archive.clone() }))
+    );
+    handler
+        .open_file_with_password(&archive, Some("secret".into()))
+        .and_then(|archive| archive.get_file(Path::new("secret.txt")))
+        .and_then(|(_, mut file)| std::io::read_to_string(&mut file.file).context("reading file"))
+        .map(|content| assert_eq!(content, "very secret"))
+}
+
+#[test_log::test]
+fn test_stream_file_matches_extracted_file() -> Result<()> {
+    let archive = Wrapped7Zip::find_bin(Path::new("."), None)?.open_file(Path::new("test-data/example-small-file.7z"))?;
+    let path = Path::new("long path/with some whitespace/lets add some more/small-file.json");
+    let read_all = |mut reader: Box<dyn std::io::Read + '_>| -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).context("reading").map(|_| out)
+    };
+    let (entry, stream) = archive.stream_file(path)?;
+    let streamed = read_all(Box::new(stream))?;
+    let (_, extracted) = archive.get_file(path)?;
+    assert_eq!(streamed.len() as u64, entry.size);
+    assert_eq!(streamed, read_all(Box::new(extracted.file))?);
+    assert!(archive
+        .stream_file(Path::new("does-not-exist.json"))
+        .is_err());
+    Ok(())
+}
+
+#[test_log::test]
+fn test_extract_all() -> Result<()> {
+    let archive = Wrapped7Zip::find_bin(Path::new("."), None)?.open_file(Path::new("test-data/example-small-file.7z"))?;
+    let dir = tempfile::tempdir().context("creating temp dir")?;
+    let extracted = archive.extract_all(dir.path())?;
+    let files = archive.list_files()?;
+    assert!(extracted
+        .iter()
+        .any(|path| path.ends_with("small-file.json")));
+    assert!(extracted.iter().all(|path| path.is_file()));
+    files
+        .iter()
+        .filter(|entry| dir.path().join(&entry.original_path).is_file())
+        .try_for_each(|entry| {
+            std::fs::metadata(dir.path().join(&entry.original_path))
+                .context("reading metadata")
+                .map(|metadata| assert_eq!(metadata.len(), entry.size, "{entry:?}"))
+        })
+}
+
+#[cfg(feature = "tokio")]
+#[test_log::test(tokio::test)]
+async fn test_async_handle_matches_blocking_handle() -> Result<()> {
+    let archive = Wrapped7Zip::find_bin(Path::new("."), None)?.open_file(Path::new("test-data/example-small-file.7z"))?;
+    let async_archive = archive.async_handle();
+    assert_eq!(async_archive.list_files().await?, archive.list_files()?);
+    let path = Path::new("long path/with some whitespace/lets add some more/small-file.json");
+    let (entry, extracted) = async_archive.get_file(path).await?;
+    let (blocking_entry, blocking_extracted) = archive.get_file(path)?;
+    assert_eq!(entry, blocking_entry);
+    assert_eq!(
+        std::fs::read(&extracted.path).context("reading extracted file")?,
+        std::fs::read(&blocking_extracted.path).context("reading extracted file")?
+    );
+    Ok(())
+}
+
+#[test_log::test]
+fn test_missing_path_is_not_found() -> Result<()> {
+    let archive = Wrapped7Zip::find_bin(Path::new("."), None)?.open_file(Path::new("test-data/example-small-file.7z"))?;
+    let error = archive
+        .get_file(Path::new("does-not-exist.json"))
+        .expect_err("file is not in the archive");
+    assert!(
+        matches!(Wrapped7ZipError::of(&error), Some(Wrapped7ZipError::NotFound { paths, .. }) if paths == &[PathBuf::from("does-not-exist.json")]),
+        "{error:?}"
+    );
+    Ok(())
+}
+
+#[test_log::test]
+fn test_integrity_check() -> Result<()> {
+    let handler = Wrapped7Zip::find_bin(Path::new("."), None)?;
+    let report = handler
+        .open_file(Path::new("test-data/example-small-file.7z"))?
+        .test()?;
+    assert!(report.is_ok(), "{report:?}");
+
+    let dir = tempfile::tempdir().context("creating temp dir")?;
+    let broken = dir.path().join("broken.7z");
+    let mut bytes = std::fs::read("test-data/example-small-file.7z").context("reading archive")?;
+    // the packed streams come right after the 32 byte signature header
+    bytes[40] ^= 0xff;
+    std::fs::write(&broken, bytes).context("writing broken archive")?;
+    match handler
+        .open_file(&broken)
+        .and_then(|archive| archive.test())
+    {
+        Ok(report) => assert!(!report.is_ok(), "{report:?}"),
+        Err(error) => assert!(matches!(Wrapped7ZipError::of(&error), Some(Wrapped7ZipError::Corrupted { .. })), "{error:?}"),
+    }
+    Ok(())
+}
+
+#[test_log::test]
+fn test_many_paths_go_through_listfile() -> Result<()> {
+    let archive = Wrapped7Zip::find_bin(Path::new("."), None)?.open_file(Path::new("test-data/example-small-file.7z"))?;
+    let entries = archive.list_files()?;
+    let (command, listfile) = archive.extract_command(&entries, Path::new("out"))?;
+    assert!(listfile.is_none());
+    assert!(command
+        .get_args()
+        .any(|arg| arg == entries[0].original_path.as_str()));
+
+    let many = std::iter::repeat(entries[0].clone())
+        .take(MAX_INLINE_PATHS_LENGTH / entries[0].original_path.len() + 1)
+        .collect::<Vec<_>>();
+    let (command, listfile) = archive.extract_command(&many, Path::new("out"))?;
+    let listfile = listfile.context("expected a listfile")?;
+    assert!(command
+        .get_args()
+        .any(|arg| arg.to_string_lossy() == format!("@{}", listfile.display())));
+    assert!(!command
+        .get_args()
+        .any(|arg| arg == entries[0].original_path.as_str()));
+    assert_eq!(
+        std::fs::read_to_string(&listfile)
+            .context("reading listfile")?
+            .lines()
+            .count(),
+        many.len()
+    );
+    Ok(())
+}
+
+#[test_log::test]
+fn test_add_and_delete_files() -> Result<()> {
+    let dir = tempfile::tempdir().context("creating temp dir")?;
+    let copy = dir.path().join("archive.7z");
+    std::fs::copy("test-data/example-small-file.7z", &copy).context("copying archive")?;
+    let content = dir.path().join("content");
+    std::fs::create_dir_all(content.join("textures")).context("creating content dir")?;
+    std::fs::write(content.join("textures/new.dds"), b"dds").context("writing new file")?;
+
+    let archive = Wrapped7Zip::find_bin(dir.path(), None)?.open_file(&copy)?;
+    let original = archive.list_files()?;
+    let added = archive.add_files(&content, &[Path::new("textures/new.dds")])?;
+    assert_eq!(added.len(), original.len() + 1);
+    assert!(added
+        .iter()
+        .any(|entry| entry.path == Path::new("textures/new.dds") && entry.size == 3));
+
+    let deleted = archive.delete_files(&[Path::new("textures/new.dds")])?;
+    assert_eq!(
+        deleted.iter().map(|entry| &entry.path).collect::<Vec<_>>(),
+        original.iter().map(|entry| &entry.path).collect::<Vec<_>>()
+    );
+    assert!(archive
+        .delete_files(&[Path::new("textures/new.dds")])
+        .is_err());
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! `7z u` and `7z d` change the archive in place, both return the listing of the archive afterwards -
+//! the archive gets a new mtime, so a cached listing of the old one is never reused
+
+use super::*;
+
+impl ArchiveHandle {
+    /// unlike extraction, an empty `-p` would make 7z ask for a password to encrypt the new files with
+    fn update_password_arg(&self) -> Option<String> {
+        self.password
+            .as_deref()
+            .map(|password| password_arg(Some(password)))
+    }
+
+    /// adds or replaces `files`, which are relative to `base_dir` and keep that relative path inside of the archive
+    #[instrument]
+    pub fn add_files(&self, base_dir: &Path, files: &[&Path]) -> Result<Vec<ListOutputEntry>> {
+        files
+            .iter()
+            .map(|file| {
+                file.is_relative()
+                    .then_some(file)
+                    .and_then(|file| file.to_str())
+                    .with_context(|| format!("[{}] must be a relative, UTF-8 path", file.display()))
+            })
+            .collect::<Result<Vec<_>>>()
+            .and_then(|paths| {
+                // 7z runs in base_dir
+                std::fs::canonicalize(&self.archive)
+                    .context("canonicalizing archive path")
+                    .map(|archive| (paths, archive))
+            })
+            .and_then(|(paths, archive)| {
+                self.binary
+                    .command(|c| {
+                        c.arg("u")
+                            .args(self.update_password_arg())
+                            .args(self.binary.compression().args())
+                            .arg(&archive)
+                    })
+                    .tap_mut(|c| {
+                        c.current_dir(base_dir);
+                    })
+                    .pipe(|c| append_paths(&self.binary.temp_files_dir, c, &paths))
+            })
+            .and_then(|(command, _listfile)| self.read_stdout_ok(command))
+            .and_then(|_| self.list_files())
+            .with_context(|| format!("adding {} file(s) from [{}] to [{}]", files.len(), base_dir.display(), self.archive.display()))
+    }
+
+    /// every path has to be in the archive, see [Wrapped7ZipError::NotFound]
+    #[instrument]
+    pub fn delete_files(&self, paths: &[&Path]) -> Result<Vec<ListOutputEntry>> {
+        self.list_files()
+            .and_then(|files| select_entries(files, paths, &self.archive))
+            .and_then(|entries| {
+                self.binary
+                    .command(|c| {
+                        c.arg("d")
+                            .args(self.update_password_arg())
+                            .arg(&self.archive)
+                    })
+                    .pipe(|c| {
+                        append_paths(
+                            &self.binary.temp_files_dir,
+                            c,
+                            &entries
+                                .iter()
+                                .map(|entry| entry.original_path.as_str())
+                                .collect::<Vec<_>>(),
+                        )
+                    })
+            })
+            .and_then(|(command, _listfile)| self.read_stdout_ok(command))
+            .and_then(|_| self.list_files())
+            .with_context(|| format!("deleting {} file(s) from [{}]", paths.len(), self.archive.display()))
+    }
+}